@@ -0,0 +1,299 @@
+//! Typed async client for the SettleOne backend
+//!
+//! Wraps the `/api/v1` endpoints with methods taking and returning the
+//! request/response types of [`settleone_types`]. Error responses are
+//! decoded from the server's error envelope into [`ClientError::Api`].
+
+use reqwest::RequestBuilder;
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+use settleone_types::address::{Address, EnsName};
+use settleone_types::api::{
+    AddPaymentRequest, BulkFinalizeRequest, BulkFinalizeResponse, CancelSessionRequest,
+    CreateSessionRequest, CreateSessionResponse, ErrorCode, ErrorResponse, FinalizeRequest,
+    FinalizeResponse, HealthResponse, LookupRequest, LookupResponse, NamehashRequest,
+    NamehashResponse, Paginated, PriceRequest, PriceResponse, QuoteCompareRequest,
+    QuoteCompareResponse, QuoteRequest, QuoteResponse, ReorderPaymentsRequest, ResolveRequest,
+    ResolveResponse, SessionResponse, SettlementReceipt, SettlementStatusResponse,
+};
+use settleone_types::session::Payment;
+
+pub use settleone_types;
+
+/// Header carrying the client API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Client errors
+#[derive(Error, Debug)]
+pub enum ClientError {
+    /// The server answered with its error envelope
+    #[error("{status} {}: {}", error.code, error.message)]
+    Api {
+        /// HTTP status code
+        status: u16,
+        error: ErrorResponse,
+    },
+
+    #[error("request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// The body did not match the expected type or the error envelope
+    #[error("unexpected {status} response: {body}")]
+    UnexpectedResponse { status: u16, body: String },
+}
+
+impl ClientError {
+    /// Error code of an API error
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            ClientError::Api { error, .. } => Some(error.code),
+            _ => None,
+        }
+    }
+}
+
+/// Client for one SettleOne server
+#[derive(Debug, Clone)]
+pub struct SettleOneClient {
+    base_url: String,
+    http: reqwest::Client,
+    api_key: Option<String>,
+}
+
+impl SettleOneClient {
+    /// Client for the server at `base_url`, e.g. `https://api.settleone.xyz`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            api_key: None,
+        }
+    }
+
+    /// Send `key` as `X-Api-Key` (required for mutations once keys are configured)
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    /// Use a preconfigured HTTP client (timeouts, proxies, TLS roots)
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Liveness check
+    pub async fn health(&self) -> Result<HealthResponse, ClientError> {
+        self.send(self.http.get(format!("{}/health", self.base_url)))
+            .await
+    }
+
+    /// Create a session
+    pub async fn create_session(
+        &self,
+        request: &CreateSessionRequest,
+    ) -> Result<CreateSessionResponse, ClientError> {
+        self.send(self.http.post(self.url("/session")).json(request))
+            .await
+    }
+
+    /// Fetch a session
+    pub async fn get_session(&self, id: &str) -> Result<SessionResponse, ClientError> {
+        self.send(self.http.get(self.url(&format!("/session/{}", id))))
+            .await
+    }
+
+    /// Add a payment to a session
+    pub async fn add_payment(
+        &self,
+        id: &str,
+        request: &AddPaymentRequest,
+    ) -> Result<SessionResponse, ClientError> {
+        let url = self.url(&format!("/session/{}/payment", id));
+        self.send(self.http.post(url).json(request)).await
+    }
+
+    /// One page of a session's payments; pass the previous page's
+    /// `next_cursor` to continue
+    pub async fn list_payments(
+        &self,
+        id: &str,
+        limit: Option<usize>,
+        cursor: Option<&str>,
+    ) -> Result<Paginated<Payment>, ClientError> {
+        let mut query = Vec::new();
+        if let Some(limit) = limit {
+            query.push(("limit", limit.to_string()));
+        }
+        if let Some(cursor) = cursor {
+            query.push(("cursor", cursor.to_string()));
+        }
+        let url = self.url(&format!("/session/{}/payments", id));
+        self.send(self.http.get(url).query(&query)).await
+    }
+
+    /// Remove a payment from a session
+    pub async fn remove_payment(
+        &self,
+        id: &str,
+        payment_id: &str,
+    ) -> Result<SessionResponse, ClientError> {
+        let url = self.url(&format!("/session/{}/payment/{}", id, payment_id));
+        self.send(self.http.delete(url)).await
+    }
+
+    /// Cancel a pending payment, keeping its record in the session
+    pub async fn cancel_payment(
+        &self,
+        id: &str,
+        payment_id: &str,
+    ) -> Result<SessionResponse, ClientError> {
+        let url = self.url(&format!("/session/{}/payment/{}/cancel", id, payment_id));
+        self.send(self.http.post(url)).await
+    }
+
+    /// Reorder a session's payments; `payment_ids` lists every payment once
+    pub async fn reorder_payments(
+        &self,
+        id: &str,
+        payment_ids: &[String],
+    ) -> Result<SessionResponse, ClientError> {
+        let request = ReorderPaymentsRequest {
+            payment_ids: payment_ids.to_vec(),
+        };
+        let url = self.url(&format!("/session/{}/payments/reorder", id));
+        self.send(self.http.post(url).json(&request)).await
+    }
+
+    /// Cancel a session, optionally recording why
+    pub async fn cancel_session(
+        &self,
+        id: &str,
+        reason: Option<&str>,
+    ) -> Result<SessionResponse, ClientError> {
+        let request = CancelSessionRequest {
+            reason: reason.map(str::to_string),
+        };
+        let url = self.url(&format!("/session/{}/cancel", id));
+        self.send(self.http.post(url).json(&request)).await
+    }
+
+    /// Finalize a session; set `expected_total`/`expected_payment_count` to
+    /// refuse if the session changed since it was reviewed
+    pub async fn finalize(
+        &self,
+        id: &str,
+        request: &FinalizeRequest,
+    ) -> Result<FinalizeResponse, ClientError> {
+        let url = self.url(&format!("/session/{}/finalize", id));
+        self.send(self.http.post(url).json(request)).await
+    }
+
+    /// Finalize several sessions; each result reports its own success or error
+    pub async fn finalize_many(
+        &self,
+        request: &BulkFinalizeRequest,
+    ) -> Result<BulkFinalizeResponse, ClientError> {
+        self.send(self.http.post(self.url("/sessions/finalize")).json(request))
+            .await
+    }
+
+    /// Transfers that settle a session and the per-recipient split
+    pub async fn receipt(&self, id: &str) -> Result<SettlementReceipt, ClientError> {
+        self.send(self.http.get(self.url(&format!("/session/{}/receipt", id))))
+            .await
+    }
+
+    /// Check the settlement transaction of a finalized session
+    pub async fn settlement_status(
+        &self,
+        id: &str,
+    ) -> Result<SettlementStatusResponse, ClientError> {
+        let url = self.url(&format!("/session/{}/settlement-status", id));
+        self.send(self.http.get(url)).await
+    }
+
+    /// Resolve an ENS name to an address
+    pub async fn resolve_ens(&self, name: &EnsName) -> Result<ResolveResponse, ClientError> {
+        let request = ResolveRequest {
+            name: name.clone(),
+            allow_stale: false,
+        };
+        self.send(self.http.get(self.url("/ens/resolve")).query(&request))
+            .await
+    }
+
+    /// Reverse lookup: address to ENS name
+    pub async fn lookup_address(&self, address: &Address) -> Result<LookupResponse, ClientError> {
+        let request = LookupRequest {
+            address: address.clone(),
+        };
+        self.send(self.http.get(self.url("/ens/lookup")).query(&request))
+            .await
+    }
+
+    /// Normalize an ENS name and compute its namehash
+    pub async fn ens_namehash(&self, name: &str) -> Result<NamehashResponse, ClientError> {
+        let request = NamehashRequest {
+            name: name.to_string(),
+        };
+        self.send(self.http.get(self.url("/ens/namehash")).query(&request))
+            .await
+    }
+
+    /// Cross-chain transfer quote
+    pub async fn get_quote(&self, request: &QuoteRequest) -> Result<QuoteResponse, ClientError> {
+        self.send(self.http.get(self.url("/quote")).query(request))
+            .await
+    }
+
+    /// Quotes for the same transfer at several amounts, in request order
+    pub async fn compare_quotes(
+        &self,
+        request: &QuoteCompareRequest,
+    ) -> Result<QuoteCompareResponse, ClientError> {
+        self.send(self.http.post(self.url("/quote/compare")).json(request))
+            .await
+    }
+
+    /// Approximate USD price of a token
+    pub async fn price(&self, chain_id: u64, token: &str) -> Result<PriceResponse, ClientError> {
+        let request = PriceRequest {
+            chain_id,
+            token: token.to_string(),
+        };
+        self.send(self.http.get(self.url("/price")).query(&request))
+            .await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/v1{}", self.base_url, path)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, ClientError> {
+        let request = match &self.api_key {
+            Some(key) => request.header(API_KEY_HEADER, key),
+            None => request,
+        };
+        let response = request.send().await?;
+        let status = response.status();
+        let code = status.as_u16();
+        let body = response.bytes().await?;
+        let unexpected = || ClientError::UnexpectedResponse {
+            status: code,
+            body: String::from_utf8_lossy(&body).into_owned(),
+        };
+
+        if status.is_success() {
+            return serde_json::from_slice(&body).map_err(|_| unexpected());
+        }
+        match serde_json::from_slice::<ErrorResponse>(&body) {
+            Ok(error) => Err(ClientError::Api {
+                status: code,
+                error,
+            }),
+            Err(_) => Err(unexpected()),
+        }
+    }
+}
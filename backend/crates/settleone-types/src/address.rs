@@ -0,0 +1,323 @@
+//! Validated Ethereum addresses and ENS names
+//!
+//! Both types validate and normalize when parsed, including when
+//! deserialized, so a value that exists is well-formed. Letter case never
+//! matters on input: addresses compare by their lowercase form and
+//! serialize in EIP-55 mixed case; names compare and serialize lowercased.
+
+use std::cell::Cell;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha3::{Digest, Keccak256};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+/// Longest accepted ENS name, in bytes (the DNS limit)
+pub const MAX_ENS_NAME_LEN: usize = 255;
+
+thread_local! {
+    /// Set while a [`MaskedAddresses`] serializes on this thread
+    static MASKING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Why a string is not an Ethereum address
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    #[error("Address must start with 0x")]
+    MissingPrefix,
+
+    #[error("Address must be 42 characters (0x + 40 hex digits)")]
+    InvalidLength,
+
+    #[error("Address contains non-hex characters")]
+    NonHex,
+}
+
+/// An Ethereum address: `0x` and 40 hex digits, in any letter case
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, ToSchema)]
+#[schema(example = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045")]
+pub struct Address(String);
+
+impl Address {
+    /// Canonical lowercase form
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// EIP-55 mixed-case form
+    pub fn checksummed(&self) -> String {
+        let hex = &self.0[2..];
+        let hash = Keccak256::digest(hex.as_bytes());
+        let mut out = String::with_capacity(42);
+        out.push_str("0x");
+        for (i, c) in hex.chars().enumerate() {
+            // Uppercase a letter when the matching nibble of the hash is >= 8
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            out.push(if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            });
+        }
+        out
+    }
+
+    /// First and last four hex digits of the EIP-55 form, as in
+    /// `0x5aAe...eAed`
+    pub fn masked(&self) -> String {
+        let full = self.checksummed();
+        format!("{}...{}", &full[..6], &full[38..])
+    }
+}
+
+impl TryFrom<&str> for Address {
+    type Error = AddressError;
+
+    /// Parse `address`, ignoring letter case and surrounding whitespace
+    fn try_from(address: &str) -> Result<Self, AddressError> {
+        let address = address.trim();
+        let hex = address
+            .strip_prefix("0x")
+            .or_else(|| address.strip_prefix("0X"))
+            .ok_or(AddressError::MissingPrefix)?;
+        if hex.len() != 40 {
+            return Err(AddressError::InvalidLength);
+        }
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(AddressError::NonHex);
+        }
+        Ok(Address(format!("0x{}", hex.to_ascii_lowercase())))
+    }
+}
+
+impl FromStr for Address {
+    type Err = AddressError;
+
+    fn from_str(address: &str) -> Result<Self, AddressError> {
+        Self::try_from(address)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.checksummed())
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if MASKING.get() {
+            serializer.serialize_str(&self.masked())
+        } else {
+            serializer.collect_str(self)
+        }
+    }
+}
+
+/// Serializes the value it wraps with every [`Address`] in it, at any
+/// depth, [masked](Address::masked). Only this serialization is affected:
+/// the same value serialized on its own keeps full addresses.
+#[derive(Debug)]
+pub struct MaskedAddresses<'a, T: ?Sized>(pub &'a T);
+
+impl<T: Serialize + ?Sized> Serialize for MaskedAddresses<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        /// Restores the flag however serializing ends, panics included
+        struct Restore(bool);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                MASKING.set(self.0);
+            }
+        }
+
+        let _restore = Restore(MASKING.replace(true));
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        Self::try_from(raw.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Why a string is not an ENS name
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EnsNameError {
+    #[error("ENS name must have at least two labels, e.g. name.eth")]
+    MissingTld,
+
+    #[error("ENS name contains an empty label")]
+    EmptyLabel,
+
+    #[error("ENS name must be at most {MAX_ENS_NAME_LEN} bytes")]
+    TooLong,
+
+    #[error("ENS name contains whitespace or control characters")]
+    InvalidCharacter,
+}
+
+/// A structurally valid ENS name, lowercased and without a trailing root dot.
+///
+/// Only the shape is checked here; UTS-46 normalization and policy such as
+/// the `.eth` suffix are up to the resolver.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, ToSchema)]
+#[schema(example = "vitalik.eth")]
+pub struct EnsName(String);
+
+impl EnsName {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<&str> for EnsName {
+    type Error = EnsNameError;
+
+    fn try_from(name: &str) -> Result<Self, EnsNameError> {
+        let name = name.trim();
+        let name = name.strip_suffix('.').unwrap_or(name).to_lowercase();
+        if name.len() > MAX_ENS_NAME_LEN {
+            return Err(EnsNameError::TooLong);
+        }
+        if name.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(EnsNameError::InvalidCharacter);
+        }
+        if !name.contains('.') {
+            return Err(EnsNameError::MissingTld);
+        }
+        if name.split('.').any(str::is_empty) {
+            return Err(EnsNameError::EmptyLabel);
+        }
+        Ok(EnsName(name))
+    }
+}
+
+impl FromStr for EnsName {
+    type Err = EnsNameError;
+
+    fn from_str(name: &str) -> Result<Self, EnsNameError> {
+        Self::try_from(name)
+    }
+}
+
+impl fmt::Display for EnsName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for EnsName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for EnsName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        Self::try_from(raw.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Vectors from the EIP-55 specification
+    const EIP55_VECTORS: &[&str] = &[
+        // All caps
+        "0x52908400098527886E0F7030069857D2E4169EE7",
+        "0x8617E340B3D01FA5F11F306F4090FD50E238070D",
+        // All lower
+        "0xde709f2102306220921060314715629080e2fb77",
+        "0x27b1fdb04752bbc536007a920d24acb045561c26",
+        // Normal
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ];
+
+    #[test]
+    fn test_checksum_eip55_vectors() {
+        for expected in EIP55_VECTORS {
+            let lower = Address::try_from(expected.to_lowercase().as_str()).unwrap();
+            assert_eq!(lower.to_string(), *expected);
+            let upper = expected.to_uppercase().replacen("0X", "0x", 1);
+            assert_eq!(Address::try_from(upper.as_str()).unwrap(), lower);
+        }
+    }
+
+    #[test]
+    fn test_address_parse_errors() {
+        let address = Address::try_from(" 0X5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed ").unwrap();
+        assert_eq!(
+            address.as_str(),
+            "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"
+        );
+        assert_eq!(
+            "5aaeb6053f3e94c9b9a09f33669435e7ef1beaed".parse::<Address>(),
+            Err(AddressError::MissingPrefix)
+        );
+        assert_eq!("0x123".parse::<Address>(), Err(AddressError::InvalidLength));
+        assert_eq!(
+            "0xZaaeb6053f3e94c9b9a09f33669435e7ef1beaed".parse::<Address>(),
+            Err(AddressError::NonHex)
+        );
+    }
+
+    #[test]
+    fn test_address_serde_accepts_any_case_and_emits_checksum() {
+        let expected = "\"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed\"";
+        for stored in ["\"0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed\"", expected] {
+            let address: Address = serde_json::from_str(stored).unwrap();
+            assert_eq!(serde_json::to_string(&address).unwrap(), expected);
+        }
+        let err = serde_json::from_str::<Address>("\"0xRecipient\"").unwrap_err();
+        assert!(err.to_string().contains("42 characters"));
+    }
+
+    #[test]
+    fn test_masked_addresses_serialize_masked_only_when_wrapped() {
+        let address: Address = "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"
+            .parse()
+            .unwrap();
+        assert_eq!(address.masked(), "0x5aAe...eAed");
+
+        let nested = vec![Some(address.clone())];
+        assert_eq!(
+            serde_json::to_string(&MaskedAddresses(&nested)).unwrap(),
+            "[\"0x5aAe...eAed\"]"
+        );
+        assert_eq!(
+            serde_json::to_string(&nested).unwrap(),
+            "[\"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed\"]"
+        );
+    }
+
+    #[test]
+    fn test_ens_name_normalization() {
+        let name = EnsName::try_from(" Vitalik.ETH. ").unwrap();
+        assert_eq!(name.as_str(), "vitalik.eth");
+        assert_eq!(serde_json::to_string(&name).unwrap(), "\"vitalik.eth\"");
+        assert_eq!(
+            EnsName::try_from("sub.name.eth").unwrap().as_str(),
+            "sub.name.eth"
+        );
+
+        assert_eq!(EnsName::try_from("vitalik"), Err(EnsNameError::MissingTld));
+        assert_eq!(EnsName::try_from("a..eth"), Err(EnsNameError::EmptyLabel));
+        assert_eq!(EnsName::try_from(".eth"), Err(EnsNameError::EmptyLabel));
+        assert_eq!(
+            EnsName::try_from("vita lik.eth"),
+            Err(EnsNameError::InvalidCharacter)
+        );
+        let long = format!("{}.eth", "a".repeat(MAX_ENS_NAME_LEN));
+        assert_eq!(EnsName::try_from(long.as_str()), Err(EnsNameError::TooLong));
+        assert!(serde_json::from_str::<EnsName>("\"\"").is_err());
+    }
+}
@@ -0,0 +1,345 @@
+//! Token amounts in base units
+//!
+//! [`Amount`] is an unsigned 256-bit integer, the range of an ERC-20
+//! balance, so no realistic sum of payments overflows it. It parses once,
+//! on deserialization, and serializes as a plain decimal string.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+/// Largest power of ten that fits in a limb, used to print in chunks
+const CHUNK: u64 = 10_000_000_000_000_000_000;
+const CHUNK_DIGITS: usize = 19;
+
+/// Why a string is not an amount
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AmountError {
+    /// Empty, or anything but ASCII digits (no signs, dots or exponents)
+    #[error("'{0}' is not an amount in base units (digits only)")]
+    NotDecimal(String),
+
+    #[error("'{0}' does not fit in 256 bits")]
+    TooLarge(String),
+}
+
+/// A non-negative amount in token base units, up to 2^256 - 1
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, ToSchema)]
+#[schema(value_type = String, example = "1500000")]
+pub struct Amount([u64; 4]);
+
+impl Amount {
+    pub const ZERO: Amount = Amount([0; 4]);
+    pub const MAX: Amount = Amount([u64::MAX; 4]);
+
+    pub fn is_zero(&self) -> bool {
+        *self == Self::ZERO
+    }
+
+    /// `self + other`, or `None` on overflow
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        let mut out = [0u64; 4];
+        let mut carry = false;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let (sum, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = c1 || c2;
+        }
+        (!carry).then_some(Amount(out))
+    }
+
+    /// `self - other`, or `None` if `other` is larger
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        let mut out = [0u64; 4];
+        let mut borrow = false;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let (diff, b1) = self.0[i].overflowing_sub(other.0[i]);
+            let (diff, b2) = diff.overflowing_sub(borrow as u64);
+            *limb = diff;
+            borrow = b1 || b2;
+        }
+        (!borrow).then_some(Amount(out))
+    }
+
+    /// The amount as 32 big-endian bytes, the layout of an ABI `uint256`
+    pub fn to_be_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (chunk, limb) in bytes.chunks_exact_mut(8).zip(self.0.iter().rev()) {
+            chunk.copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// `self * factor + addend`, or `None` on overflow
+    fn checked_mul_add(self, factor: u64, addend: u64) -> Option<Amount> {
+        let mut out = [0u64; 4];
+        let mut carry = addend as u128;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let wide = self.0[i] as u128 * factor as u128 + carry;
+            *limb = wide as u64;
+            carry = wide >> 64;
+        }
+        (carry == 0).then_some(Amount(out))
+    }
+
+    /// `(self / divisor, self % divisor)`
+    fn div_rem(self, divisor: u64) -> (Amount, u64) {
+        let mut out = [0u64; 4];
+        let mut rem = 0u128;
+        for i in (0..4).rev() {
+            let wide = (rem << 64) | self.0[i] as u128;
+            out[i] = (wide / divisor as u128) as u64;
+            rem = wide % divisor as u128;
+        }
+        (Amount(out), rem as u64)
+    }
+}
+
+impl From<u64> for Amount {
+    fn from(value: u64) -> Self {
+        Amount([value, 0, 0, 0])
+    }
+}
+
+impl From<u128> for Amount {
+    fn from(value: u128) -> Self {
+        Amount([value as u64, (value >> 64) as u64, 0, 0])
+    }
+}
+
+impl TryFrom<Amount> for u128 {
+    type Error = Amount;
+
+    /// The amount as a `u128`, or the amount back if it does not fit
+    fn try_from(amount: Amount) -> Result<Self, Amount> {
+        match amount.0 {
+            [low, high, 0, 0] => Ok((high as u128) << 64 | low as u128),
+            _ => Err(amount),
+        }
+    }
+}
+
+impl Ord for Amount {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for Amount {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl TryFrom<&str> for Amount {
+    type Error = AmountError;
+
+    fn try_from(value: &str) -> Result<Self, AmountError> {
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(AmountError::NotDecimal(value.to_string()));
+        }
+        value.bytes().try_fold(Amount::ZERO, |amount, digit| {
+            amount
+                .checked_mul_add(10, (digit - b'0') as u64)
+                .ok_or_else(|| AmountError::TooLarge(value.to_string()))
+        })
+    }
+}
+
+impl FromStr for Amount {
+    type Err = AmountError;
+
+    fn from_str(value: &str) -> Result<Self, AmountError> {
+        Self::try_from(value)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Split into base-10^19 chunks, least significant first
+        let mut chunks = Vec::with_capacity(5);
+        let mut rest = *self;
+        loop {
+            let (quotient, chunk) = rest.div_rem(CHUNK);
+            chunks.push(chunk);
+            if quotient.is_zero() {
+                break;
+            }
+            rest = quotient;
+        }
+        let mut chunks = chunks.into_iter().rev();
+        let mut out = chunks.next().unwrap_or_default().to_string();
+        for chunk in chunks {
+            out.push_str(&format!("{:0width$}", chunk, width = CHUNK_DIGITS));
+        }
+        f.pad(&out)
+    }
+}
+
+impl fmt::Debug for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Amount({})", self)
+    }
+}
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// 2^256 - 1
+    const MAX: &str =
+        "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+
+    /// Sum of two decimal strings, digit by digit (the reference bigint)
+    fn decimal_add(a: &str, b: &str) -> String {
+        let (a, b) = (a.as_bytes(), b.as_bytes());
+        let mut digits = Vec::new();
+        let mut carry = 0;
+        for i in 0..a.len().max(b.len()) {
+            let digit = |s: &[u8]| s.len().checked_sub(i + 1).map_or(0, |j| s[j] - b'0');
+            let sum = digit(a) + digit(b) + carry;
+            digits.push(b'0' + sum % 10);
+            carry = sum / 10;
+        }
+        if carry > 0 {
+            digits.push(b'0' + carry);
+        }
+        digits.reverse();
+        String::from_utf8(digits).unwrap()
+    }
+
+    /// A random amount of up to `bits` bits
+    fn random_amount(rng: &mut StdRng, bits: u32) -> Amount {
+        let mut limbs: [u64; 4] = rng.gen();
+        let bits = rng.gen_range(0..=bits);
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let low = i as u32 * 64;
+            if bits <= low {
+                *limb = 0;
+            } else if bits - low < 64 {
+                *limb &= (1u64 << (bits - low)) - 1;
+            }
+        }
+        Amount(limbs)
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!("0".parse::<Amount>(), Ok(Amount::ZERO));
+        assert_eq!("007".parse::<Amount>(), Ok(Amount::from(7u64)));
+        assert_eq!(Amount::from(1_500_000u64).to_string(), "1500000");
+        assert_eq!(Amount::MAX.to_string(), MAX);
+        assert_eq!(MAX.parse::<Amount>(), Ok(Amount::MAX));
+        assert_eq!(
+            u128::MAX.to_string().parse::<Amount>(),
+            Ok(Amount::from(u128::MAX))
+        );
+        // Chunk boundaries keep their inner zeros
+        assert_eq!(
+            "10000000000000000000"
+                .parse::<Amount>()
+                .unwrap()
+                .to_string(),
+            "10000000000000000000"
+        );
+
+        let past_max = decimal_add(MAX, "1");
+        assert_eq!(
+            past_max.parse::<Amount>(),
+            Err(AmountError::TooLarge(past_max.clone()))
+        );
+        for value in ["", "-1", "+1", "1.5", "1e6", " 1", "0x10"] {
+            assert_eq!(
+                value.parse::<Amount>(),
+                Err(AmountError::NotDecimal(value.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_serde_is_a_decimal_string() {
+        let amount: Amount = serde_json::from_str("\"1500000\"").unwrap();
+        assert_eq!(amount, Amount::from(1_500_000u64));
+        assert_eq!(serde_json::to_string(&amount).unwrap(), "\"1500000\"");
+        assert!(serde_json::from_str::<Amount>("1500000").is_err());
+        assert!(serde_json::from_str::<Amount>("\"1.5\"").is_err());
+    }
+
+    #[test]
+    fn test_checked_arithmetic_and_ordering() {
+        let one = Amount::from(1u64);
+        assert_eq!(Amount::MAX.checked_add(one), None);
+        assert_eq!(Amount::ZERO.checked_sub(one), None);
+        assert_eq!(
+            Amount::from(u128::MAX)
+                .checked_add(one)
+                .unwrap()
+                .to_string(),
+            "340282366920938463463374607431768211456"
+        );
+        assert!(Amount::from(u128::MAX) < Amount::from(u128::MAX).checked_add(one).unwrap());
+        assert!(Amount::from(2u64) > one);
+        assert_eq!(u128::try_from(Amount::from(u128::MAX)), Ok(u128::MAX));
+        assert!(u128::try_from(Amount::MAX).is_err());
+    }
+
+    #[test]
+    fn test_to_be_bytes() {
+        let mut expected = [0u8; 32];
+        expected[16..].copy_from_slice(&u128::MAX.to_be_bytes());
+        assert_eq!(Amount::from(u128::MAX).to_be_bytes(), expected);
+        assert_eq!(Amount::from(0x0102u64).to_be_bytes()[30..], [1, 2]);
+        assert_eq!(Amount::MAX.to_be_bytes(), [0xff; 32]);
+    }
+
+    #[test]
+    fn test_random_values_round_trip() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..2_000 {
+            let amount = random_amount(&mut rng, 256);
+            let text = amount.to_string();
+            assert_eq!(text.parse::<Amount>(), Ok(amount), "{}", text);
+            let json = serde_json::to_string(&amount).unwrap();
+            assert_eq!(serde_json::from_str::<Amount>(&json).unwrap(), amount);
+        }
+    }
+
+    #[test]
+    fn test_random_sums_match_reference() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..2_000 {
+            // Below 2^255 so the sum always fits
+            let a = random_amount(&mut rng, 255);
+            let b = random_amount(&mut rng, 255);
+            let (a_text, b_text) = (a.to_string(), b.to_string());
+            let sum = a.checked_add(b).unwrap();
+            assert_eq!(sum.to_string(), decimal_add(&a_text, &b_text));
+            assert_eq!(sum.checked_sub(b), Some(a));
+            // Without leading zeros, longer decimals are larger
+            assert_eq!(
+                a.cmp(&b),
+                (a_text.len(), &a_text).cmp(&(b_text.len(), &b_text))
+            );
+        }
+    }
+}
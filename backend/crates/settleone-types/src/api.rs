@@ -0,0 +1,669 @@
+//! Request and response bodies of the HTTP API
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::address::{Address, EnsName};
+use crate::amount::Amount;
+use crate::session::{
+    Payment, PaymentStatus, PinnedRecipient, RecipientShare, Session, SessionEvent, SessionStatus,
+    SessionTemplate, SettlementMode, Transfer,
+};
+
+/// Machine-readable error code of an [`ErrorResponse`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    NotFound,
+    #[serde(rename = "validation_error")]
+    Validation,
+    Conflict,
+    /// The resource existed but was archived
+    Gone,
+    #[serde(rename = "upstream_error")]
+    Upstream,
+    /// An upstream could not be reached at all: it is down
+    UpstreamUnreachable,
+    /// An upstream did not answer in time: it is slow
+    UpstreamTimeout,
+    Unauthorized,
+    Forbidden,
+    MethodNotAllowed,
+    /// Well-formed input that contradicts itself
+    #[serde(rename = "unprocessable_entity")]
+    Unprocessable,
+    RateLimited,
+    NotImplemented,
+    #[serde(rename = "internal_error")]
+    Internal,
+    /// A handler panicked
+    InternalPanic,
+    /// Writes are paused for maintenance; reads still work
+    Maintenance,
+    /// A code this version does not know about
+    #[serde(other)]
+    Unknown,
+}
+
+impl ErrorCode {
+    /// The code as sent on the wire
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::Validation => "validation_error",
+            ErrorCode::Conflict => "conflict",
+            ErrorCode::Gone => "gone",
+            ErrorCode::Upstream => "upstream_error",
+            ErrorCode::UpstreamUnreachable => "upstream_unreachable",
+            ErrorCode::UpstreamTimeout => "upstream_timeout",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::Forbidden => "forbidden",
+            ErrorCode::MethodNotAllowed => "method_not_allowed",
+            ErrorCode::Unprocessable => "unprocessable_entity",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::NotImplemented => "not_implemented",
+            ErrorCode::Internal => "internal_error",
+            ErrorCode::InternalPanic => "internal_panic",
+            ErrorCode::Maintenance => "maintenance",
+            ErrorCode::Unknown => "unknown",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A single invalid input field
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// JSON body of every error response
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ErrorResponse {
+    /// Machine-readable error code, e.g. `not_found` or `validation_error`
+    pub code: ErrorCode,
+    /// Human-readable message
+    pub message: String,
+    /// Structured details; for validation errors `{ "fields": [FieldError] }`
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<serde_json::Value>,
+    /// Id of the request that failed (also sent as `x-request-id`)
+    pub request_id: Option<String>,
+}
+
+/// Health check response
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HealthResponse {
+    pub status: String,
+    pub version: String,
+}
+
+/// One page of a listing
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    /// Pass as `cursor` to fetch the next page; absent on the last page
+    pub next_cursor: Option<String>,
+    /// Number of items across all pages
+    pub total: usize,
+}
+
+/// `?limit=&offset=` of the legacy payment listing
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListPaymentsQuery {
+    /// Payments per page, clamped to 1 to 100 (default 50)
+    pub limit: Option<usize>,
+    /// Payments to skip
+    pub offset: Option<usize>,
+}
+
+/// Page of a session's payments on the legacy `/api` tree, which pages by
+/// offset; `/api/v1` returns a [`Paginated`] page instead
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ListPaymentsResponse {
+    pub payments: Vec<Payment>,
+    pub total: usize,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+/// Create session request
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CreateSessionRequest {
+    pub user_address: Address,
+    /// ENS name to resolve now and pin for the lifetime of the session
+    pub recipient_name: Option<EnsName>,
+    /// Decimals of the settlement token (0-18, default 6 for USDC)
+    pub token_decimals: Option<u8>,
+    /// `treasury` settles the whole total to `TREASURY_ADDRESS` (default `direct`)
+    pub settlement_mode: Option<SettlementMode>,
+    /// ISO 4217 code to also show the total in, such as `EUR`
+    pub display_currency: Option<String>,
+}
+
+/// Create session response
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreateSessionResponse {
+    pub session_id: String,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned_recipient: Option<PinnedRecipient>,
+}
+
+/// Add payment request
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AddPaymentRequest {
+    pub recipient: Address,
+    pub recipient_ens: Option<EnsName>,
+    /// Amount in token base units, as a decimal string; may be omitted when
+    /// `human_amount` is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Amount>,
+    /// Amount in whole tokens, e.g. `"12.5"`, converted with the session's
+    /// token decimals; must agree with `amount` if both are given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub human_amount: Option<String>,
+}
+
+/// Session response
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SessionResponse {
+    pub session: Session,
+    /// `total_amount` formatted with the session's token decimals
+    pub total_amount_display: String,
+    /// The total in the session's `display_currency`; left out when there
+    /// is none or no exchange rate is available
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_display: Option<TotalDisplay>,
+}
+
+/// A session total converted to fiat
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TotalDisplay {
+    /// ISO 4217 code
+    pub currency: String,
+    /// The total in `currency`, rounded to its minor unit
+    pub amount: String,
+    /// Units of `currency` per US dollar
+    pub rate: String,
+    /// When the rate was fetched
+    pub rate_timestamp: DateTime<Utc>,
+}
+
+impl SessionResponse {
+    pub fn new(session: Session) -> Self {
+        Self {
+            total_amount_display: session.display_total(),
+            total_display: None,
+            session,
+        }
+    }
+}
+
+/// Payment filter of session reads
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PaymentStatusQuery {
+    /// Only list payments in this status; totals still cover every payment
+    pub payment_status: Option<PaymentStatus>,
+}
+
+/// Event log read of a session
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SessionEventsQuery {
+    /// Only return events after this sequence (all events if unset)
+    pub since_sequence: Option<u64>,
+}
+
+/// Events of a session, oldest first
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SessionEventsResponse {
+    pub session_id: String,
+    pub events: Vec<SessionEvent>,
+}
+
+/// Cancel session request
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CancelSessionRequest {
+    /// Why the session is being cancelled (at most 200 characters)
+    pub reason: Option<String>,
+}
+
+/// Reorder payments request
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ReorderPaymentsRequest {
+    /// Every payment id of the session, each once, in the new order
+    pub payment_ids: Vec<String>,
+}
+
+/// Save a session template
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CreateTemplateRequest {
+    /// At most 100 characters
+    pub name: String,
+    /// User of the sessions created from the template
+    pub owner: Address,
+    pub entries: Vec<TemplateEntryRequest>,
+}
+
+/// One entry of a [`CreateTemplateRequest`]: a `recipient`, a
+/// `recipient_ens` name, or both
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateEntryRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipient: Option<Address>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipient_ens: Option<EnsName>,
+    /// Amount in token base units
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_amount: Option<Amount>,
+    /// At most 200 characters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// Template listing filter
+#[derive(Debug, Clone, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TemplateListQuery {
+    /// Only list this owner's templates
+    pub owner: Address,
+}
+
+/// Templates of one owner, oldest first
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TemplateListResponse {
+    pub templates: Vec<SessionTemplate>,
+}
+
+/// A payment and the session it was made in
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RecipientPayment {
+    pub session_id: String,
+    /// Decimals of the session's settlement token
+    pub token_decimals: u8,
+    pub payment: Payment,
+}
+
+/// What a user paid one recipient in tokens with the same decimals
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RecipientTotal {
+    pub token_decimals: u8,
+    /// In token base units
+    pub amount: Amount,
+    /// `amount` formatted with `token_decimals`
+    pub amount_display: String,
+    pub payment_count: usize,
+}
+
+/// A user's settled payments to one recipient, across sessions
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RecipientHistoryResponse {
+    pub user: Address,
+    /// The recipient as looked up: a checksummed address or a lowercased
+    /// ENS name
+    pub recipient: String,
+    /// Lifetime totals, one per token decimals (ascending)
+    pub totals: Vec<RecipientTotal>,
+    pub first_paid_at: Option<DateTime<Utc>>,
+    pub last_paid_at: Option<DateTime<Utc>>,
+    /// Settled payments, newest first
+    pub payments: Paginated<RecipientPayment>,
+}
+
+/// Create a session from a template
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CreateFromTemplateRequest {
+    /// Amounts in token base units by entry index, replacing the entries'
+    /// `default_amount`
+    #[serde(default)]
+    pub amounts: BTreeMap<usize, Amount>,
+}
+
+/// Finalize session request
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FinalizeRequest {
+    pub tx_hash: Option<String>,
+    /// Reject with 409 unless `total_amount` (base units) still equals this
+    pub expected_total: Option<Amount>,
+    /// Reject with 409 unless the session still has this many non-cancelled payments
+    pub expected_payment_count: Option<usize>,
+    /// Re-resolve every payment's `recipient_ens` and reject with 409 if any
+    /// no longer maps to the stored recipient
+    #[serde(default)]
+    pub verify_ens: bool,
+    /// Cancel payments whose transfers fall below `MIN_PAYMENT_AMOUNT`
+    /// instead of rejecting the finalize with 422
+    #[serde(default)]
+    pub skip_dust: bool,
+}
+
+/// Finalize session
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FinalizeResponse {
+    pub session_id: String,
+    pub status: String,
+    pub tx_hash: Option<String>,
+    /// Queued for the backend's next settlement batch, which sets `tx_hash`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub batched: bool,
+}
+
+/// Finalize several sessions at once
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BulkFinalizeRequest {
+    /// Sessions to finalize, at most 100
+    pub session_ids: Vec<String>,
+    /// Settlement transaction covering every session of the batch
+    pub tx_hash: Option<String>,
+}
+
+/// Outcome of finalizing one session of a batch
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BulkFinalizeResult {
+    pub session_id: String,
+    /// `pending` once finalized, `failed` otherwise
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<String>,
+    /// Why the session could not be finalized
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorResponse>,
+}
+
+/// Per-session outcomes of a bulk finalize, in request order
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BulkFinalizeResponse {
+    pub finalized: usize,
+    pub failed: usize,
+    pub results: Vec<BulkFinalizeResult>,
+}
+
+/// How a session settles and who it pays
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SettlementReceipt {
+    pub session_id: String,
+    pub status: SessionStatus,
+    pub settlement_mode: SettlementMode,
+    /// Sum of non-cancelled payments, in token base units
+    pub total_amount: Amount,
+    pub tx_hash: Option<String>,
+    /// On-chain transfers: one per recipient in direct mode, the total to
+    /// the treasury in treasury mode
+    pub transfers: Vec<Transfer>,
+    /// Intended split of the total between recipients
+    pub recipients: Vec<RecipientShare>,
+}
+
+impl SettlementReceipt {
+    pub fn new(session: &Session) -> Self {
+        Self {
+            session_id: session.id.clone(),
+            status: session.status.clone(),
+            settlement_mode: session.settlement_mode,
+            total_amount: session.total_amount,
+            tx_hash: session.tx_hash.clone(),
+            transfers: session.transfers(),
+            recipients: session.recipient_shares(),
+        }
+    }
+}
+
+/// Settlement transaction status
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SettlementStatusResponse {
+    pub session_id: String,
+    /// Session status after this check (`settled` once the tx has
+    /// `SETTLEMENT_MIN_CONFIRMATIONS` confirmations)
+    pub session_status: SessionStatus,
+    pub tx_hash: String,
+    /// `pending` (not mined), `confirmed` or `failed` (reverted)
+    pub tx_status: String,
+    /// Blocks confirming the transaction, including its own
+    pub confirmations: u64,
+    pub block_number: Option<u64>,
+}
+
+/// What settling a session would cost right now
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SettlementPreview {
+    pub session_id: String,
+    /// Transfers the settlement would make
+    pub transfers: Vec<Transfer>,
+    /// Gas of those transfers (an upper estimate per transfer)
+    pub estimated_gas: u64,
+    /// Current gas price of the settlement chain, in wei
+    pub gas_price_wei: Amount,
+    /// `estimated_gas * gas_price_wei`, in wei of the native token
+    pub estimated_fee_wei: Amount,
+    /// The fee in US dollars; null while the native token has no price
+    pub estimated_fee_usd: Option<f64>,
+}
+
+/// ENS resolution request
+#[derive(Debug, Clone, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ResolveRequest {
+    #[param(value_type = String)]
+    pub name: EnsName,
+    /// Answer from an expired cache entry while refreshing it in the background
+    #[serde(default)]
+    pub allow_stale: bool,
+}
+
+/// ENS resolution response
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ResolveResponse {
+    pub name: EnsName,
+    pub address: Option<Address>,
+    pub avatar: Option<String>,
+    /// Whether the answer came from the resolution cache
+    pub cached: bool,
+    /// Seconds since the answer was fetched upstream (0 for a fresh fetch)
+    pub age_secs: u64,
+    pub error: Option<String>,
+}
+
+/// Batch ENS resolution request
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ResolveBatchRequest {
+    /// 1 to 50 names
+    pub names: Vec<EnsName>,
+}
+
+/// Batch ENS resolution response
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ResolveBatchResponse {
+    /// One result per name, in request order; failures carry an `error`
+    pub results: Vec<ResolveResponse>,
+}
+
+/// Address lookup request
+#[derive(Debug, Clone, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LookupRequest {
+    #[param(value_type = String)]
+    pub address: Address,
+}
+
+/// Namehash request parameters
+#[derive(Debug, Clone, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NamehashRequest {
+    /// Any ENS name, including TLDs such as `eth`
+    pub name: String,
+}
+
+/// A name with its EIP-137 namehash
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NamehashResponse {
+    /// The name as given
+    pub name: String,
+    /// The name after normalization, which the hash is computed from
+    pub normalized: String,
+    /// `0x`-prefixed 32-byte namehash
+    #[schema(example = "0x93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae")]
+    pub namehash: String,
+}
+
+/// Content hash request parameters
+#[derive(Debug, Clone, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ContentHashRequest {
+    #[param(value_type = String)]
+    pub name: EnsName,
+}
+
+/// A name's EIP-1577 content hash
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ContentHashResponse {
+    pub name: EnsName,
+    /// Decoded record, e.g. `ipfs://Qm...`; null if the name has none
+    #[schema(example = "ipfs://QmRAQB6YaCyidP37UdDnjFY5vQuiBrcqdyoW1CuDgwxkD4")]
+    pub contenthash: Option<String>,
+}
+
+/// Address lookup response
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LookupResponse {
+    pub address: Address,
+    pub name: Option<EnsName>,
+    /// Avatar of the name, from the same reverse lookup
+    #[serde(default)]
+    pub avatar: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Quote request parameters
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct QuoteRequest {
+    pub from_chain: String,
+    pub to_chain: String,
+    pub from_token: String,
+    pub to_token: String,
+    pub from_amount: String,
+    pub from_address: Option<String>,
+    /// Maximum slippage as a fraction, e.g. `0.005` for 0.5% (the
+    /// configured default if omitted)
+    pub slippage: Option<f64>,
+}
+
+/// Quotes for several amounts of one transfer
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct QuoteCompareRequest {
+    pub from_chain: String,
+    pub to_chain: String,
+    pub from_token: String,
+    pub to_token: String,
+    pub from_address: Option<String>,
+    /// Amounts to quote, in base units (at most 10)
+    pub amounts: Vec<String>,
+}
+
+/// One quote per requested amount, in request order
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QuoteCompareResponse {
+    /// A failed amount has `to_amount` `"0"` and `error` set
+    pub quotes: Vec<QuoteResponse>,
+}
+
+/// Quote response
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QuoteResponse {
+    /// Source amount in base units
+    pub from_amount: String,
+    /// Destination amount in base units
+    pub to_amount: String,
+    /// `from_amount` in whole tokens, rounded for display (absent if the
+    /// token's decimals are unknown)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_amount_formatted: Option<String>,
+    /// `to_amount` in whole tokens, rounded for display
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_amount_formatted: Option<String>,
+    pub estimated_gas: String,
+    pub estimated_time: u64, // seconds
+    #[schema(value_type = Option<Object>)]
+    pub route: Option<serde_json::Value>,
+    pub error: Option<String>,
+    /// Maximum slippage the quote was requested with, as a fraction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slippage: Option<f64>,
+    /// Set when `from_amount` looks like a units mistake, e.g. whole
+    /// tokens sent as base units, or when `slippage` was raised to the floor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+/// Token price request parameters
+#[derive(Debug, Clone, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PriceRequest {
+    /// EVM chain id, e.g. `8453` for Base
+    pub chain_id: u64,
+    /// Token contract address (or a symbol LI.FI knows)
+    pub token: String,
+}
+
+/// A token's approximate USD price
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PriceResponse {
+    pub chain_id: u64,
+    /// The token as asked, lowercased
+    pub token: String,
+    /// USD per whole token
+    pub usd: f64,
+    /// When the price was fetched; may be minutes old if every source is down
+    pub timestamp: DateTime<Utc>,
+    /// Where the price came from, e.g. `lifi`
+    pub source: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes_match_wire_names() {
+        for code in [
+            ErrorCode::NotFound,
+            ErrorCode::Validation,
+            ErrorCode::Upstream,
+            ErrorCode::UpstreamUnreachable,
+            ErrorCode::UpstreamTimeout,
+            ErrorCode::MethodNotAllowed,
+            ErrorCode::Unprocessable,
+            ErrorCode::Internal,
+            ErrorCode::InternalPanic,
+        ] {
+            let wire = serde_json::to_value(code).unwrap();
+            assert_eq!(wire, code.as_str());
+            assert_eq!(serde_json::from_value::<ErrorCode>(wire).unwrap(), code);
+        }
+        let unknown: ErrorCode = serde_json::from_str("\"teapot\"").unwrap();
+        assert_eq!(unknown, ErrorCode::Unknown);
+    }
+}
@@ -0,0 +1,232 @@
+//! Test fixtures: known-good addresses and names, and [`SessionBuilder`]
+//!
+//! Built sessions go through the same [`Session`] methods as the API
+//! (adding, cancelling and settling payments), so their totals and payment
+//! states hold the invariants of sessions created for real.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{Duration, Utc};
+
+use crate::address::{Address, EnsName};
+use crate::session::{Payment, PaymentStatus, Session, SessionStatus};
+
+/// Session owner used when a test does not pick one
+pub const USER: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+/// Payment recipients, in EIP-55 checksummed form
+pub const ALICE: &str = "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359";
+pub const BOB: &str = "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB";
+pub const CAROL: &str = "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb";
+/// ENS names of the recipients above
+pub const ALICE_ENS: &str = "alice.eth";
+pub const BOB_ENS: &str = "bob.eth";
+
+/// Parse a fixture address, panicking if it is malformed
+pub fn address(raw: &str) -> Address {
+    raw.parse().expect("fixture address is valid")
+}
+
+/// A payment the builder adds once the session exists
+struct PlannedPayment {
+    recipient: Address,
+    recipient_ens: Option<EnsName>,
+    amount: String,
+    cancelled: bool,
+}
+
+/// Fluent construction of sessions for tests.
+///
+/// ```ignore
+/// let session = SessionBuilder::new()
+///     .payment(ALICE, "100")
+///     .status(SessionStatus::Settled)
+///     .created_days_ago(3)
+///     .build();
+/// ```
+pub struct SessionBuilder {
+    id: Option<String>,
+    user: Address,
+    payments: Vec<PlannedPayment>,
+    status: SessionStatus,
+    age: Duration,
+}
+
+impl SessionBuilder {
+    /// An active session of [`USER`] without payments, created now
+    pub fn new() -> Self {
+        Self {
+            id: None,
+            user: address(USER),
+            payments: Vec::new(),
+            status: SessionStatus::Active,
+            age: Duration::zero(),
+        }
+    }
+
+    /// Session id (unique per builder if unset)
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    pub fn user(mut self, user: &str) -> Self {
+        self.user = address(user);
+        self
+    }
+
+    /// Add a payment of `amount` base units to `recipient`
+    pub fn payment(self, recipient: &str, amount: &str) -> Self {
+        self.planned(recipient, None, amount, false)
+    }
+
+    /// Add a payment addressed by ENS name, resolved to `recipient`
+    pub fn ens_payment(self, recipient: &str, name: &str, amount: &str) -> Self {
+        let name = name.parse().expect("fixture ENS name is valid");
+        self.planned(recipient, Some(name), amount, false)
+    }
+
+    /// Add a payment and cancel it, so it is listed but not in the total
+    pub fn cancelled_payment(self, recipient: &str, amount: &str) -> Self {
+        self.planned(recipient, None, amount, true)
+    }
+
+    /// Final status, reached the way the API reaches it
+    pub fn status(mut self, status: SessionStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn created_days_ago(self, days: i64) -> Self {
+        self.created_ago(Duration::days(days))
+    }
+
+    pub fn created_ago(mut self, age: Duration) -> Self {
+        self.age = age;
+        self
+    }
+
+    /// Build the session, panicking if the payments overflow the total
+    pub fn build(self) -> Session {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        let id = self
+            .id
+            .unwrap_or_else(|| format!("session-{}", NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+        let mut session = Session::new(id, self.user);
+        session.created_at = Utc::now() - self.age;
+
+        for (i, planned) in self.payments.into_iter().enumerate() {
+            let payment_id = format!("p{}", i + 1);
+            session
+                .add_payment(Payment {
+                    id: payment_id.clone(),
+                    index: 0,
+                    recipient: planned.recipient,
+                    recipient_ens: planned.recipient_ens,
+                    amount: planned.amount.parse().expect("fixture amount is valid"),
+                    status: PaymentStatus::Pending,
+                    created_at: session.created_at,
+                    confirmed_at: None,
+                    settled_at: None,
+                    cancel_reason: None,
+                    memo: None,
+                })
+                .expect("fixture payments fit in the total");
+            if planned.cancelled {
+                session
+                    .cancel_payment(&payment_id)
+                    .expect("new payments are cancellable");
+            }
+        }
+
+        match self.status {
+            SessionStatus::Active => {}
+            SessionStatus::Pending => session.status = SessionStatus::Pending,
+            SessionStatus::Settled => {
+                session.status = SessionStatus::Pending;
+                session.mark_settled();
+            }
+            SessionStatus::Cancelled => {
+                session
+                    .cancel(None)
+                    .expect("active sessions are cancellable");
+            }
+        }
+        session
+    }
+
+    fn planned(
+        mut self,
+        recipient: &str,
+        recipient_ens: Option<EnsName>,
+        amount: &str,
+        cancelled: bool,
+    ) -> Self {
+        self.payments.push(PlannedPayment {
+            recipient: address(recipient),
+            recipient_ens,
+            amount: amount.to_string(),
+            cancelled,
+        });
+        self
+    }
+}
+
+impl Default for SessionBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture_addresses_are_checksummed() {
+        for raw in [USER, ALICE, BOB, CAROL] {
+            assert_eq!(address(raw).to_string(), raw);
+        }
+        for name in [ALICE_ENS, BOB_ENS] {
+            assert!(name.parse::<EnsName>().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_built_sessions_keep_their_invariants() {
+        let session = SessionBuilder::new()
+            .user(BOB)
+            .payment(ALICE, "100")
+            .cancelled_payment(CAROL, "40")
+            .payment(CAROL, "25")
+            .status(SessionStatus::Settled)
+            .created_days_ago(3)
+            .build();
+
+        assert_eq!(session.user, address(BOB));
+        assert_eq!(session.status, SessionStatus::Settled);
+        assert_eq!(session.total_amount.to_string(), "125");
+        let statuses: Vec<_> = session.payments.iter().map(|p| p.status.clone()).collect();
+        assert_eq!(
+            statuses,
+            [
+                PaymentStatus::Settled,
+                PaymentStatus::Cancelled,
+                PaymentStatus::Settled
+            ]
+        );
+        let indexes: Vec<u32> = session.payments.iter().map(|p| p.index).collect();
+        assert_eq!(indexes, [0, 1, 2]);
+        let age = Utc::now() - session.created_at;
+        assert!(age >= Duration::days(3) && age < Duration::days(3) + Duration::minutes(1));
+
+        // Unnamed sessions never collide
+        assert_ne!(
+            SessionBuilder::new().build().id,
+            SessionBuilder::new().build().id
+        );
+        let cancelled = SessionBuilder::new()
+            .status(SessionStatus::Cancelled)
+            .build();
+        assert_eq!(cancelled.status, SessionStatus::Cancelled);
+    }
+}
@@ -0,0 +1,15 @@
+//! Types shared by the SettleOne backend and its clients
+//!
+//! [`session`] holds the session domain model, [`api`] the request and
+//! response bodies of the HTTP API, [`address`] validated addresses and ENS
+//! names, [`amount`] 256-bit base-unit amounts and [`units`] token amount
+//! parsing and formatting. With the `test-util` feature, [`fixtures`] builds
+//! sessions for tests.
+
+pub mod address;
+pub mod amount;
+pub mod api;
+#[cfg(any(test, feature = "test-util"))]
+pub mod fixtures;
+pub mod session;
+pub mod units;
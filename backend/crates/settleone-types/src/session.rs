@@ -0,0 +1,943 @@
+//! Session and payment models
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::address::{Address, EnsName};
+use crate::amount::Amount;
+use crate::units::format_units;
+
+/// Session and payment state errors
+#[derive(Error, Debug, PartialEq)]
+pub enum SessionError {
+    #[error("Session {0} not found")]
+    SessionNotFound(String),
+
+    #[error("Payment {0} not found")]
+    PaymentNotFound(String),
+
+    #[error("Payment {id} is {status:?}; only pending payments can be cancelled")]
+    PaymentNotCancellable { id: String, status: PaymentStatus },
+
+    #[error("Session {id} is {status:?}; only active or pending sessions can be cancelled")]
+    SessionNotCancellable { id: String, status: SessionStatus },
+
+    #[error("Session {id} is {status:?}; only active or pending sessions can be finalized")]
+    SessionNotFinalizable { id: String, status: SessionStatus },
+
+    #[error("The store already holds the maximum of {0} payments")]
+    PaymentLimitReached(usize),
+
+    #[error("User {user} already has the maximum of {max} active sessions")]
+    ActiveSessionLimitReached { user: Address, max: usize },
+
+    #[error("{0}")]
+    InvalidPayment(String),
+
+    #[error("Session {id} changed since it was reviewed: {reason}")]
+    SessionModified { id: String, reason: String },
+
+    #[error("Session {id} was finalized moments ago; retry in {retry_after_secs}s")]
+    FinalizeCooldown { id: String, retry_after_secs: u64 },
+
+    #[error("Session {id} is {status:?}; only active sessions can be reordered")]
+    SessionNotReorderable { id: String, status: SessionStatus },
+
+    #[error("{0}")]
+    InvalidOrder(String),
+
+    #[error(
+        "Session {id} would settle transfers below the minimum payment of {minimum} ({}); \
+         remove those payments, or finalize with skip_dust to cancel them",
+        describe_transfers(.dust)
+    )]
+    DustPayments {
+        id: String,
+        minimum: Amount,
+        dust: Vec<Transfer>,
+    },
+}
+
+/// `"5 to 0x…, 7 to 0x…"`
+fn describe_transfers(transfers: &[Transfer]) -> String {
+    transfers
+        .iter()
+        .map(|t| format!("{} to {}", t.amount, t.to))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Maximum length of a cancellation reason
+pub const MAX_CANCEL_REASON_LEN: usize = 200;
+
+/// Maximum length of a payment memo
+pub const MAX_MEMO_LEN: usize = 200;
+
+/// Maximum length of a session template name
+pub const MAX_TEMPLATE_NAME_LEN: usize = 100;
+
+/// Reason recorded when the expiry sweep cancels an idle session
+pub const EXPIRED_CANCEL_REASON: &str = "expired";
+
+/// Decimals of the settlement token when a session does not say (USDC)
+pub const DEFAULT_TOKEN_DECIMALS: u8 = 6;
+
+/// Largest accepted `token_decimals`
+pub const MAX_TOKEN_DECIMALS: u8 = 18;
+
+/// ISO 4217 currencies a session may show its total in
+pub const DISPLAY_CURRENCIES: &[&str] = &[
+    "AUD", "BRL", "CAD", "CHF", "CNY", "EUR", "GBP", "INR", "JPY", "USD",
+];
+
+/// Decimal places `currency` is shown with (its ISO 4217 minor unit)
+pub fn currency_minor_units(currency: &str) -> u8 {
+    match currency {
+        "JPY" => 0,
+        _ => 2,
+    }
+}
+
+fn default_token_decimals() -> u8 {
+    DEFAULT_TOKEN_DECIMALS
+}
+
+/// Stored shape of [`Session`] written by this build; bumped whenever a
+/// change needs a migration of previously stored sessions
+pub const SESSION_SCHEMA_VERSION: u32 = 3;
+
+/// Sessions stored before `schema_version` existed are version 1
+fn legacy_schema_version() -> u32 {
+    1
+}
+
+/// Session status
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionStatus {
+    Active,
+    Pending,
+    Settled,
+    Cancelled,
+}
+
+/// Payment status
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentStatus {
+    Pending,
+    Confirmed,
+    Settled,
+    Cancelled,
+}
+
+/// How a session's funds move on chain
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SettlementMode {
+    /// One transfer to each recipient
+    #[default]
+    Direct,
+    /// The whole total to the treasury in one transfer; the per-recipient
+    /// split is accounted for off-chain
+    Treasury,
+}
+
+/// Payment model
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Payment {
+    pub id: String,
+    /// Position within the session, assigned when the payment is added;
+    /// payments are listed in ascending index order
+    pub index: u32,
+    pub recipient: Address,
+    pub recipient_ens: Option<EnsName>,
+    /// Amount in token base units
+    pub amount: Amount,
+    pub status: PaymentStatus,
+    pub created_at: DateTime<Utc>,
+    /// When the settlement transaction was first seen mined
+    #[serde(default)]
+    pub confirmed_at: Option<DateTime<Utc>>,
+    /// When the payment was settled
+    #[serde(default)]
+    pub settled_at: Option<DateTime<Utc>>,
+    /// Why the payment was cancelled, when the backend cancelled it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancel_reason: Option<String>,
+    /// Free-form note, e.g. carried over from a session template
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+impl Payment {
+    /// Move to `status`, stamping its lifecycle timestamp the first time it
+    /// is reached. Settling also stamps `confirmed_at` if it was skipped.
+    pub fn transition(&mut self, status: PaymentStatus, at: DateTime<Utc>) {
+        match status {
+            PaymentStatus::Confirmed => {
+                self.confirmed_at.get_or_insert(at);
+            }
+            PaymentStatus::Settled => {
+                self.confirmed_at.get_or_insert(at);
+                self.settled_at.get_or_insert(at);
+            }
+            PaymentStatus::Pending | PaymentStatus::Cancelled => {}
+        }
+        self.status = status;
+    }
+}
+
+/// Kind of a [`SessionEvent`], as sent in its `type` field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+pub enum SessionEventType {
+    #[serde(rename = "payment.added")]
+    PaymentAdded,
+    #[serde(rename = "payment.removed")]
+    PaymentRemoved,
+}
+
+/// A change to one payment of a session, for ledgers kept outside the
+/// backend
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SessionEvent {
+    #[serde(rename = "type")]
+    pub kind: SessionEventType,
+    pub session_id: String,
+    /// 1 for the session's first event, then one more per event, so a
+    /// receiver can tell it missed some
+    pub sequence: u64,
+    /// The payment added, or as it was before removal
+    pub payment: Payment,
+    /// The session total after the change
+    pub total_amount: Amount,
+}
+
+/// ENS name → address mapping locked in when the session was created
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct PinnedRecipient {
+    pub name: EnsName,
+    pub address: Address,
+    pub resolved_at: DateTime<Utc>,
+}
+
+/// A reusable set of recipients that new sessions are created from
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct SessionTemplate {
+    pub id: String,
+    pub name: String,
+    /// User of the sessions created from the template
+    pub owner: Address,
+    pub entries: Vec<TemplateEntry>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One payment of a [`SessionTemplate`]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct TemplateEntry {
+    /// Recipient address; for entries with `recipient_ens`, what the name
+    /// resolved to when the template was saved
+    pub recipient: Address,
+    /// Re-resolved every time a session is created from the template
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipient_ens: Option<EnsName>,
+    /// Amount in token base units, used unless the session request overrides it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_amount: Option<Amount>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// One on-chain transfer of a settlement
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct Transfer {
+    pub to: Address,
+    /// Amount in token base units
+    pub amount: Amount,
+}
+
+/// What one recipient is owed by a session
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct RecipientShare {
+    pub recipient: Address,
+    pub recipient_ens: Option<EnsName>,
+    /// Sum of the recipient's non-cancelled payments, in token base units
+    pub amount: Amount,
+}
+
+/// Session state a client confirmed before finalizing; `None` fields are
+/// not checked
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FinalizeGuard {
+    /// Expected `total_amount`, in token base units
+    pub total: Option<Amount>,
+    /// Expected number of non-cancelled payments
+    pub payment_count: Option<usize>,
+}
+
+/// What finalize does with transfers below the minimum payment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DustPolicy {
+    /// Smallest transfer worth settling, in token base units (zero: no check)
+    pub minimum: Amount,
+    /// Cancel the payments behind dust transfers instead of refusing
+    pub skip: bool,
+}
+
+/// Session model
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Session {
+    /// Shape of the stored session (see [`SESSION_SCHEMA_VERSION`])
+    #[serde(default = "legacy_schema_version")]
+    pub schema_version: u32,
+    pub id: String,
+    pub user: Address,
+    pub status: SessionStatus,
+    pub payments: Vec<Payment>,
+    /// Sum of non-cancelled payments, in token base units
+    pub total_amount: Amount,
+    /// Decimals of the settlement token, used to display amounts
+    #[serde(default = "default_token_decimals")]
+    pub token_decimals: u8,
+    pub tx_hash: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Recipient resolved at creation; later ENS changes do not affect it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned_recipient: Option<PinnedRecipient>,
+    /// Incremented on every change; basis of the session's ETag
+    #[serde(default)]
+    pub version: u64,
+    /// Why the session was cancelled (user-supplied, or `expired` by the sweep)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cancel_reason: Option<String>,
+    #[serde(default)]
+    pub settlement_mode: SettlementMode,
+    /// Treasury address locked in at creation (treasury mode only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub treasury_address: Option<Address>,
+    /// When the session was last finalized, the start of its finalize cooldown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_finalize_at: Option<DateTime<Utc>>,
+    /// Fiat currency (one of [`DISPLAY_CURRENCIES`]) the client shows totals in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_currency: Option<String>,
+}
+
+impl Session {
+    /// Create a new session
+    pub fn new(id: String, user: Address) -> Self {
+        Self {
+            schema_version: SESSION_SCHEMA_VERSION,
+            id,
+            user,
+            status: SessionStatus::Active,
+            payments: Vec::new(),
+            total_amount: Amount::ZERO,
+            token_decimals: DEFAULT_TOKEN_DECIMALS,
+            tx_hash: None,
+            created_at: Utc::now(),
+            pinned_recipient: None,
+            version: 1,
+            cancel_reason: None,
+            settlement_mode: SettlementMode::Direct,
+            treasury_address: None,
+            last_finalize_at: None,
+            display_currency: None,
+        }
+    }
+
+    /// Record that the session changed
+    pub fn touch(&mut self) {
+        self.version += 1;
+    }
+
+    /// Total formatted with the session's token decimals, e.g. `"1.5"`
+    pub fn display_total(&self) -> String {
+        format_units(self.total_amount, self.token_decimals)
+    }
+
+    /// Weak ETag identifying this version of the session
+    pub fn etag(&self) -> String {
+        format!("W/\"{}-{}\"", self.id, self.version)
+    }
+
+    /// Add a payment to the session, after every existing one.
+    ///
+    /// Payments to the pinned recipient name always use the pinned address.
+    pub fn add_payment(&mut self, mut payment: Payment) -> Result<(), String> {
+        if let (Some(pinned), Some(name)) = (&self.pinned_recipient, &payment.recipient_ens) {
+            if pinned.name == *name {
+                payment.recipient = pinned.address.clone();
+            }
+        }
+        payment.index = self.payments.last().map_or(0, |last| last.index + 1);
+        self.payments.push(payment);
+        if let Err(e) = self.recalculate_total() {
+            // Rollback payment addition if total calculation fails
+            self.payments.pop();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Remove a payment from the session
+    pub fn remove_payment(&mut self, payment_id: &str) -> Result<(), String> {
+        if let Some(index) = self.payments.iter().position(|p| p.id == payment_id) {
+            self.payments.remove(index);
+            self.recalculate_total()?;
+            Ok(())
+        } else {
+            Err(format!("Payment {} not found", payment_id))
+        }
+    }
+
+    /// Put the payments in the order of `payment_ids`, which must name every
+    /// payment exactly once; only active sessions can be reordered
+    pub fn reorder_payments(&mut self, payment_ids: &[String]) -> Result<(), SessionError> {
+        if self.status != SessionStatus::Active {
+            return Err(SessionError::SessionNotReorderable {
+                id: self.id.clone(),
+                status: self.status.clone(),
+            });
+        }
+        if payment_ids.len() != self.payments.len() {
+            return Err(SessionError::InvalidOrder(format!(
+                "Expected all {} payment ids, got {}",
+                self.payments.len(),
+                payment_ids.len()
+            )));
+        }
+        let mut positions = HashMap::with_capacity(payment_ids.len());
+        for (position, id) in payment_ids.iter().enumerate() {
+            if positions.insert(id.as_str(), position as u32).is_some() {
+                return Err(SessionError::InvalidOrder(format!(
+                    "Payment {} is listed more than once",
+                    id
+                )));
+            }
+        }
+        for payment in &self.payments {
+            if !positions.contains_key(payment.id.as_str()) {
+                return Err(SessionError::InvalidOrder(format!(
+                    "Payment {} is missing from the order",
+                    payment.id
+                )));
+            }
+        }
+        for payment in &mut self.payments {
+            payment.index = positions[payment.id.as_str()];
+        }
+        self.sort_payments();
+        Ok(())
+    }
+
+    /// Restore the listing order, ascending by index
+    pub fn sort_payments(&mut self) {
+        self.payments.sort_by_key(|payment| payment.index);
+    }
+
+    /// Mark a pending payment as cancelled, keeping it in the payment list
+    /// for auditability but excluding it from the total
+    pub fn cancel_payment(&mut self, payment_id: &str) -> Result<(), SessionError> {
+        let payment = self
+            .payments
+            .iter_mut()
+            .find(|p| p.id == payment_id)
+            .ok_or_else(|| SessionError::PaymentNotFound(payment_id.to_string()))?;
+
+        if payment.status != PaymentStatus::Pending {
+            return Err(SessionError::PaymentNotCancellable {
+                id: payment_id.to_string(),
+                status: payment.status.clone(),
+            });
+        }
+
+        payment.status = PaymentStatus::Cancelled;
+        // Removing an amount from a total that already fit cannot overflow
+        self.recalculate_total()
+            .expect("total of a subset of payments is valid");
+        Ok(())
+    }
+
+    /// Cancel an active or pending session, recording why
+    pub fn cancel(&mut self, reason: Option<String>) -> Result<(), SessionError> {
+        if !matches!(self.status, SessionStatus::Active | SessionStatus::Pending) {
+            return Err(SessionError::SessionNotCancellable {
+                id: self.id.clone(),
+                status: self.status.clone(),
+            });
+        }
+        self.status = SessionStatus::Cancelled;
+        self.cancel_reason = reason;
+        Ok(())
+    }
+
+    /// Check that the session still matches what the client confirmed
+    pub fn check_guard(&self, guard: &FinalizeGuard) -> Result<(), SessionError> {
+        let modified = |reason: String| SessionError::SessionModified {
+            id: self.id.clone(),
+            reason,
+        };
+        if let Some(expected) = guard.total {
+            if self.total_amount != expected {
+                return Err(modified(format!(
+                    "total is {}, expected {}",
+                    self.total_amount, expected
+                )));
+            }
+        }
+        if let Some(expected) = guard.payment_count {
+            let count = self
+                .payments
+                .iter()
+                .filter(|p| p.status != PaymentStatus::Cancelled)
+                .count();
+            if count != expected {
+                return Err(modified(format!(
+                    "{} payments, expected {}",
+                    count, expected
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Transfers that would settle less than `minimum`
+    pub fn dust_transfers(&self, minimum: Amount) -> Vec<Transfer> {
+        self.transfers()
+            .into_iter()
+            .filter(|transfer| transfer.amount < minimum)
+            .collect()
+    }
+
+    /// Refuse to settle dust transfers, or with [`DustPolicy::skip`] cancel
+    /// the payments behind them, recording why.
+    ///
+    /// Returns the ids of the payments cancelled.
+    pub fn apply_dust_policy(&mut self, policy: &DustPolicy) -> Result<Vec<String>, SessionError> {
+        let dust = self.dust_transfers(policy.minimum);
+        if dust.is_empty() {
+            return Ok(Vec::new());
+        }
+        if !policy.skip {
+            return Err(SessionError::DustPayments {
+                id: self.id.clone(),
+                minimum: policy.minimum,
+                dust,
+            });
+        }
+
+        // A treasury transfer carries every payment
+        let treasury =
+            self.settlement_mode == SettlementMode::Treasury && self.treasury_address.is_some();
+        let reason = format!(
+            "dust: settles less than the minimum payment of {}",
+            policy.minimum
+        );
+        let mut cancelled = Vec::new();
+        for payment in &mut self.payments {
+            if payment.status == PaymentStatus::Pending
+                && (treasury || dust.iter().any(|t| t.to == payment.recipient))
+            {
+                payment.status = PaymentStatus::Cancelled;
+                payment.cancel_reason = Some(reason.clone());
+                cancelled.push(payment.id.clone());
+            }
+        }
+        self.recalculate_total()
+            .expect("total of a subset of payments is valid");
+        Ok(cancelled)
+    }
+
+    /// Mark the pending payments of a pending session as confirmed, once its
+    /// settlement transaction is mined.
+    ///
+    /// Returns whether any payment transitioned.
+    pub fn mark_confirmed(&mut self) -> bool {
+        if self.status != SessionStatus::Pending {
+            return false;
+        }
+        let now = Utc::now();
+        let mut changed = false;
+        for payment in &mut self.payments {
+            if payment.status == PaymentStatus::Pending {
+                payment.transition(PaymentStatus::Confirmed, now);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Mark a pending session and its non-cancelled payments as settled.
+    ///
+    /// Returns whether the session transitioned.
+    pub fn mark_settled(&mut self) -> bool {
+        if self.status != SessionStatus::Pending {
+            return false;
+        }
+        self.status = SessionStatus::Settled;
+        let now = Utc::now();
+        for payment in &mut self.payments {
+            if payment.status != PaymentStatus::Cancelled {
+                payment.transition(PaymentStatus::Settled, now);
+            }
+        }
+        true
+    }
+
+    /// Amount owed to each recipient, in order of their first payment.
+    ///
+    /// Cancelled payments are excluded.
+    pub fn recipient_shares(&self) -> Vec<RecipientShare> {
+        let mut shares: Vec<RecipientShare> = Vec::new();
+        for payment in &self.payments {
+            if payment.status == PaymentStatus::Cancelled {
+                continue;
+            }
+            match shares
+                .iter_mut()
+                .find(|share| share.recipient == payment.recipient)
+            {
+                Some(share) => {
+                    // Each share is part of the total, which did not overflow
+                    share.amount = share
+                        .amount
+                        .checked_add(payment.amount)
+                        .expect("share of a valid total");
+                    share.recipient_ens = share
+                        .recipient_ens
+                        .take()
+                        .or_else(|| payment.recipient_ens.clone());
+                }
+                None => shares.push(RecipientShare {
+                    recipient: payment.recipient.clone(),
+                    recipient_ens: payment.recipient_ens.clone(),
+                    amount: payment.amount,
+                }),
+            }
+        }
+        shares
+    }
+
+    /// Transfers that settle the session: one per recipient in direct mode,
+    /// a single transfer of the total to the treasury in treasury mode
+    pub fn transfers(&self) -> Vec<Transfer> {
+        match (self.settlement_mode, &self.treasury_address) {
+            (SettlementMode::Treasury, Some(treasury)) => {
+                if self.total_amount.is_zero() {
+                    return Vec::new();
+                }
+                vec![Transfer {
+                    to: treasury.clone(),
+                    amount: self.total_amount,
+                }]
+            }
+            _ => self
+                .recipient_shares()
+                .into_iter()
+                .map(|share| Transfer {
+                    to: share.recipient,
+                    amount: share.amount,
+                })
+                .collect(),
+        }
+    }
+
+    /// Recalculate total amount (cancelled payments are excluded)
+    fn recalculate_total(&mut self) -> Result<(), String> {
+        let mut total = Amount::ZERO;
+        for payment in &self.payments {
+            if payment.status == PaymentStatus::Cancelled {
+                continue;
+            }
+            total = total
+                .checked_add(payment.amount)
+                .ok_or_else(|| "Total amount overflow".to_string())?;
+        }
+        self.total_amount = total;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(raw: &str) -> Address {
+        Address::try_from(raw).unwrap()
+    }
+
+    fn user() -> Address {
+        address("0x0000000000000000000000000000000000000001")
+    }
+
+    fn payment(id: &str, amount: &str, status: PaymentStatus) -> Payment {
+        Payment {
+            id: id.to_string(),
+            index: 0,
+            recipient: address("0x1111111111111111111111111111111111111111"),
+            recipient_ens: None,
+            amount: amount.parse().unwrap(),
+            status,
+            created_at: Utc::now(),
+            confirmed_at: None,
+            settled_at: None,
+            cancel_reason: None,
+            memo: None,
+        }
+    }
+
+    #[test]
+    fn test_payments_keep_their_index_order() {
+        let mut session = Session::new("s1".to_string(), user());
+        for id in ["p1", "p2", "p3"] {
+            session
+                .add_payment(payment(id, "100", PaymentStatus::Pending))
+                .unwrap();
+        }
+        let order = |session: &Session| -> Vec<(String, u32)> {
+            session
+                .payments
+                .iter()
+                .map(|p| (p.id.clone(), p.index))
+                .collect()
+        };
+
+        let new_order = ["p3", "p1", "p2"].map(String::from);
+        session.reorder_payments(&new_order).unwrap();
+        assert_eq!(
+            order(&session),
+            [("p3".into(), 0), ("p1".into(), 1), ("p2".into(), 2)]
+        );
+
+        // New payments go last, even after a removal
+        session.remove_payment("p2").unwrap();
+        session
+            .add_payment(payment("p4", "100", PaymentStatus::Pending))
+            .unwrap();
+        assert_eq!(session.payments.last().unwrap().index, 2);
+
+        assert!(matches!(
+            session.reorder_payments(&["p1".to_string()]),
+            Err(SessionError::InvalidOrder(_))
+        ));
+        session.status = SessionStatus::Pending;
+        let same = ["p3", "p1", "p4"].map(String::from);
+        assert!(matches!(
+            session.reorder_payments(&same),
+            Err(SessionError::SessionNotReorderable { .. })
+        ));
+    }
+
+    #[test]
+    fn test_cancel_payment_excluded_from_total() {
+        let mut session = Session::new("s1".to_string(), user());
+        session
+            .add_payment(payment("p1", "100", PaymentStatus::Pending))
+            .unwrap();
+        session
+            .add_payment(payment("p2", "250", PaymentStatus::Pending))
+            .unwrap();
+
+        session.cancel_payment("p1").unwrap();
+
+        assert_eq!(session.payments.len(), 2);
+        assert_eq!(session.payments[0].status, PaymentStatus::Cancelled);
+        assert_eq!(session.total_amount.to_string(), "250");
+    }
+
+    #[test]
+    fn test_lifecycle_timestamps_are_set_once() {
+        let mut session = Session::new("s1".to_string(), user());
+        for id in ["p1", "p2"] {
+            session
+                .add_payment(payment(id, "100", PaymentStatus::Pending))
+                .unwrap();
+        }
+        session.cancel_payment("p2").unwrap();
+        session.status = SessionStatus::Pending;
+
+        assert!(session.mark_confirmed());
+        let confirmed_at = session.payments[0].confirmed_at.expect("confirmed");
+        assert_eq!(session.payments[0].status, PaymentStatus::Confirmed);
+        assert_eq!(session.payments[0].settled_at, None);
+        // Repeating the transition changes nothing
+        assert!(!session.mark_confirmed());
+        assert_eq!(session.payments[0].confirmed_at, Some(confirmed_at));
+
+        assert!(session.mark_settled());
+        assert_eq!(session.payments[0].status, PaymentStatus::Settled);
+        assert_eq!(session.payments[0].confirmed_at, Some(confirmed_at));
+        let settled_at = session.payments[0].settled_at.expect("settled");
+        assert!(settled_at >= confirmed_at);
+        assert!(!session.mark_settled());
+        assert_eq!(session.payments[0].settled_at, Some(settled_at));
+
+        // Cancelled payments never reach either state
+        assert_eq!(session.payments[1].confirmed_at, None);
+        assert_eq!(session.payments[1].settled_at, None);
+
+        // Settling without a prior confirmation stamps both
+        let mut direct = payment("p3", "1", PaymentStatus::Pending);
+        let at = Utc::now();
+        direct.transition(PaymentStatus::Settled, at);
+        assert_eq!(
+            (direct.confirmed_at, direct.settled_at),
+            (Some(at), Some(at))
+        );
+    }
+
+    #[test]
+    fn test_display_total_uses_token_decimals() {
+        let mut session = Session::new("s1".to_string(), user());
+        session
+            .add_payment(payment("p1", "1500000", PaymentStatus::Pending))
+            .unwrap();
+        assert_eq!(session.display_total(), "1.5");
+
+        session.token_decimals = 18;
+        session
+            .add_payment(payment("p2", "1000000000000000000", PaymentStatus::Pending))
+            .unwrap();
+        assert_eq!(session.total_amount.to_string(), "1000000000001500000");
+        assert_eq!(session.display_total(), "1.0000000000015");
+    }
+
+    #[test]
+    fn test_cancel_settled_payment_rejected() {
+        let mut session = Session::new("s1".to_string(), user());
+        session
+            .add_payment(payment("p1", "100", PaymentStatus::Settled))
+            .unwrap();
+
+        let err = session.cancel_payment("p1").unwrap_err();
+        assert_eq!(
+            err,
+            SessionError::PaymentNotCancellable {
+                id: "p1".to_string(),
+                status: PaymentStatus::Settled,
+            }
+        );
+        assert_eq!(session.payments[0].status, PaymentStatus::Settled);
+        assert_eq!(session.total_amount.to_string(), "100");
+    }
+
+    #[test]
+    fn test_check_guard_compares_total_and_count() {
+        let mut session = Session::new("s1".to_string(), user());
+        session
+            .add_payment(payment("p1", "100", PaymentStatus::Pending))
+            .unwrap();
+        session
+            .add_payment(payment("p2", "250", PaymentStatus::Pending))
+            .unwrap();
+        session.cancel_payment("p1").unwrap();
+
+        let guard = FinalizeGuard {
+            total: Some(250u64.into()),
+            payment_count: Some(1),
+        };
+        assert_eq!(session.check_guard(&guard), Ok(()));
+        assert_eq!(session.check_guard(&FinalizeGuard::default()), Ok(()));
+
+        let stale = FinalizeGuard {
+            total: Some(350u64.into()),
+            ..FinalizeGuard::default()
+        };
+        assert!(matches!(
+            session.check_guard(&stale),
+            Err(SessionError::SessionModified { .. })
+        ));
+        let stale = FinalizeGuard {
+            payment_count: Some(2),
+            ..FinalizeGuard::default()
+        };
+        assert!(session.check_guard(&stale).is_err());
+    }
+
+    #[test]
+    fn test_cancel_unknown_payment() {
+        let mut session = Session::new("s1".to_string(), user());
+        assert_eq!(
+            session.cancel_payment("missing"),
+            Err(SessionError::PaymentNotFound("missing".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_transfers_by_settlement_mode() {
+        use crate::fixtures::{SessionBuilder, ALICE, ALICE_ENS, BOB, CAROL};
+
+        let lowercase_alice = ALICE.to_lowercase();
+        let mut session = SessionBuilder::new()
+            .payment(ALICE, "100")
+            .payment(BOB, "50")
+            // Same recipient in another letter case, addressed by name
+            .ens_payment(&lowercase_alice, ALICE_ENS, "25")
+            .cancelled_payment(CAROL, "999")
+            .build();
+
+        let shares = session.recipient_shares();
+        assert_eq!(shares.len(), 2);
+        assert_eq!(shares[0].recipient, address(ALICE));
+        assert_eq!(
+            shares[0].recipient_ens.as_ref().map(EnsName::as_str),
+            Some(ALICE_ENS)
+        );
+        assert_eq!(shares[0].amount.to_string(), "125");
+        assert_eq!(shares[1].amount.to_string(), "50");
+        assert_eq!(session.transfers().len(), 2);
+
+        session.settlement_mode = SettlementMode::Treasury;
+        let treasury = address("0x7ea5000000000000000000000000000000000000");
+        session.treasury_address = Some(treasury.clone());
+        assert_eq!(
+            session.transfers(),
+            vec![Transfer {
+                to: treasury,
+                amount: "175".parse().unwrap(),
+            }]
+        );
+        assert_eq!(session.recipient_shares(), shares);
+    }
+
+    #[test]
+    fn test_dust_policy_in_treasury_mode() {
+        use crate::fixtures::{SessionBuilder, ALICE, BOB};
+
+        let mut session = SessionBuilder::new()
+            .payment(ALICE, "40")
+            .payment(BOB, "59")
+            .build();
+        session.settlement_mode = SettlementMode::Treasury;
+        session.treasury_address = Some(address("0x7ea5000000000000000000000000000000000000"));
+
+        // Only the single treasury transfer is measured
+        let policy = |minimum: u64, skip| DustPolicy {
+            minimum: minimum.into(),
+            skip,
+        };
+        assert_eq!(session.apply_dust_policy(&policy(99, false)), Ok(vec![]));
+        assert!(matches!(
+            session.apply_dust_policy(&policy(100, false)),
+            Err(SessionError::DustPayments { .. })
+        ));
+        assert_eq!(session.total_amount.to_string(), "99");
+
+        // Skipping cancels every payment the transfer carried
+        assert_eq!(
+            session.apply_dust_policy(&policy(100, true)),
+            Ok(vec!["p1".to_string(), "p2".to_string()])
+        );
+        assert!(session.total_amount.is_zero());
+        assert!(session.transfers().is_empty());
+        assert!(session
+            .payments
+            .iter()
+            .all(|p| p.status == PaymentStatus::Cancelled && p.cancel_reason.is_some()));
+    }
+}
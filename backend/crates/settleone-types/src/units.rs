@@ -0,0 +1,209 @@
+//! Conversions between token base units and decimal strings
+
+use thiserror::Error;
+
+use crate::amount::Amount;
+
+/// Why a decimal string is not a token amount
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum UnitsError {
+    /// Signs, exponents, separators or anything else but `digits[.digits]`
+    #[error("'{0}' is not a decimal amount")]
+    NotDecimal(String),
+
+    #[error("'{value}' has more than {decimals} decimal places")]
+    TooManyDecimals { value: String, decimals: u8 },
+
+    #[error("'{0}' is too large")]
+    TooLarge(String),
+}
+
+/// Format a base-unit amount as a decimal string with `decimals` places,
+/// trimming trailing zeros (`format_units(1_500_000.into(), 6) == "1.5"`)
+pub fn format_units(amount: Amount, decimals: u8) -> String {
+    let decimals = decimals as usize;
+    let digits = format!("{:0>width$}", amount, width = decimals + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        return whole.to_string();
+    }
+    format!("{}.{}", whole, fraction)
+}
+
+/// Format like [`format_units`], rounded half up to at most `places`
+/// fractional digits (`format_units_rounded(1_234_567.into(), 6, 2) == "1.23"`)
+pub fn format_units_rounded(amount: Amount, decimals: u8, places: u8) -> String {
+    if places >= decimals {
+        return format_units(amount, decimals);
+    }
+    let dropped = (decimals - places) as usize;
+    let digits = format!("{:0>width$}", amount, width = dropped + 1);
+    let (kept, rest) = digits.split_at(digits.len() - dropped);
+    let kept: Amount = kept.parse().expect("decimal digits");
+    let rounded = if rest.as_bytes()[0] >= b'5' {
+        // Dropping a digit leaves room below the maximum for the carry
+        kept.checked_add(Amount::from(1u64)).unwrap_or(kept)
+    } else {
+        kept
+    };
+    format_units(rounded, places)
+}
+
+/// Parse a decimal string into base units with `decimals` places
+/// (`parse_units("1.5", 6) == Ok(1_500_000.into())`).
+///
+/// Never rounds: more fractional digits than `decimals` is an error, even
+/// when they are zeros.
+pub fn parse_units(value: &str, decimals: u8) -> Result<Amount, UnitsError> {
+    let value = value.trim();
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if whole.is_empty() && fraction.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+        return Err(UnitsError::NotDecimal(value.to_string()));
+    }
+    if fraction.len() > decimals as usize {
+        return Err(UnitsError::TooManyDecimals {
+            value: value.to_string(),
+            decimals,
+        });
+    }
+
+    let digits = format!("{}{:0<width$}", whole, fraction, width = decimals as usize);
+    digits
+        .parse::<Amount>()
+        .map_err(|_| UnitsError::TooLarge(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_units() {
+        assert_eq!(format_units(1_500_000u128.into(), 6), "1.5");
+        assert_eq!(format_units(1u128.into(), 6), "0.000001");
+        assert_eq!(format_units(0u128.into(), 6), "0");
+        assert_eq!(format_units(42u128.into(), 0), "42");
+        assert_eq!(
+            format_units(1_250_000_000_000_000_000u128.into(), 18),
+            "1.25"
+        );
+    }
+
+    #[test]
+    fn test_format_units_rounded() {
+        assert_eq!(format_units_rounded(1_234_567u128.into(), 6, 2), "1.23");
+        assert_eq!(format_units_rounded(1_235_000u128.into(), 6, 2), "1.24");
+        assert_eq!(format_units_rounded(998_500u128.into(), 6, 2), "1");
+        assert_eq!(format_units_rounded(4_999u128.into(), 6, 2), "0");
+        assert_eq!(format_units_rounded(1_500_000u128.into(), 6, 0), "2");
+        // Enough places is plain formatting
+        assert_eq!(format_units_rounded(1u128.into(), 6, 6), "0.000001");
+        assert_eq!(format_units_rounded(1u128.into(), 6, 18), "0.000001");
+        assert_eq!(
+            format_units_rounded(1_250_000_000_000_000_000u128.into(), 18, 1),
+            "1.3"
+        );
+        assert_eq!(
+            format_units_rounded(Amount::MAX, 18, 0),
+            "115792089237316195423570985008687907853269984665640564039458"
+        );
+    }
+
+    #[test]
+    fn test_parse_units() {
+        assert_eq!(parse_units("1.5", 6), Ok(Amount::from(1_500_000u128)));
+        assert_eq!(parse_units("0.000001", 6), Ok(Amount::from(1u128)));
+        assert_eq!(parse_units("3", 0), Ok(Amount::from(3u128)));
+        assert_eq!(
+            parse_units(".5", 18),
+            Ok(Amount::from(500_000_000_000_000_000u128))
+        );
+        assert!(parse_units("0.0000001", 6).is_err());
+        assert!(parse_units("1.2.3", 6).is_err());
+        assert!(parse_units("-1", 6).is_err());
+        assert!(parse_units("", 6).is_err());
+        assert!(parse_units(".", 6).is_err());
+        // Beyond u128 once scaled, but well within 256 bits
+        assert!(parse_units("999999999999999999999999", 18).is_ok());
+    }
+
+    #[test]
+    fn test_parse_units_rejects_non_decimal_notation() {
+        for value in [
+            "1e6", "1E6", "+1", "-0.5", "1,000", "0x10", "1 000", ".", "NaN",
+        ] {
+            assert_eq!(
+                parse_units(value, 6),
+                Err(UnitsError::NotDecimal(value.to_string()))
+            );
+        }
+        // A trailing dot is an integer with an empty fraction
+        assert_eq!(parse_units("1.", 6), Ok(Amount::from(1_000_000u128)));
+    }
+
+    #[test]
+    fn test_parse_units_never_rounds() {
+        assert_eq!(parse_units("0.000001", 6), Ok(Amount::from(1u128)));
+        assert_eq!(
+            parse_units("0.0000010", 6).unwrap_err(),
+            UnitsError::TooManyDecimals {
+                value: "0.0000010".to_string(),
+                decimals: 6,
+            }
+        );
+        assert_eq!(parse_units("12.5", 6), Ok(Amount::from(12_500_000u128)));
+        assert_eq!(
+            parse_units("12.500000", 6),
+            Ok(Amount::from(12_500_000u128))
+        );
+        assert_eq!(parse_units("0.999999", 6), Ok(Amount::from(999_999u128)));
+        assert!(parse_units("0.9999999", 6).is_err());
+        assert!(parse_units("1.5", 0).is_err());
+        assert_eq!(parse_units("007.25", 2), Ok(Amount::from(725u128)));
+    }
+
+    #[test]
+    fn test_u256_boundaries() {
+        let max = Amount::MAX.to_string();
+        assert_eq!(parse_units(&max, 0), Ok(Amount::MAX));
+        assert_eq!(format_units(Amount::MAX, 0), max);
+        // Well past u128
+        assert_eq!(
+            parse_units("340282366920938463463374607431768211456", 0),
+            Ok(Amount::from(u128::MAX).checked_add(1u64.into()).unwrap())
+        );
+        // One past the maximum
+        let past_max =
+            "115792089237316195423570985008687907853269984665640564039457584007913129639936";
+        assert_eq!(
+            parse_units(past_max, 0),
+            Err(UnitsError::TooLarge(past_max.to_string()))
+        );
+
+        // The largest amount expressible with 18 decimals round-trips
+        let max_18 = format_units(Amount::MAX, 18);
+        assert_eq!(
+            max_18,
+            "115792089237316195423570985008687907853269984665640564039457.584007913129639935"
+        );
+        assert_eq!(parse_units(&max_18, 18), Ok(Amount::MAX));
+        assert!(parse_units(
+            "115792089237316195423570985008687907853269984665640564039457.584007913129639936",
+            18
+        )
+        .is_err());
+        assert_eq!(
+            format_units(u128::MAX.into(), 6),
+            "340282366920938463463374607431768.211455"
+        );
+    }
+
+    #[test]
+    fn test_units_round_trip_with_18_decimals() {
+        for value in ["1.25", "0.000000000000000001", "123456789.987654321", "7"] {
+            assert_eq!(format_units(parse_units(value, 18).unwrap(), 18), value);
+        }
+    }
+}
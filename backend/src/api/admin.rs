@@ -0,0 +1,571 @@
+//! Operational endpoints under `/admin` (admin API key required)
+//!
+//! Served on the public listener, or on a dedicated one when `ADMIN_PORT`
+//! is set so the admin surface can be firewalled off.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::sync::atomic::Ordering;
+
+use axum::body::Body;
+use axum::http::{header, HeaderName, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use utoipa::openapi::Deprecated;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api::error::AppError;
+use crate::api::extract::{ValidJson, ValidQuery};
+use crate::api::openapi;
+use crate::api::session::missing_session;
+use crate::config::ConfigError;
+use crate::models::session::SessionEventType;
+use crate::services::health::HealthHistoryReport;
+use crate::services::jobs::JobStatus;
+use crate::services::snapshot::Snapshot;
+use crate::services::webhook::Webhook;
+use crate::AppState;
+
+/// Content type of newline-delimited JSON
+const NDJSON: &str = "application/x-ndjson";
+
+/// Header carrying the number of items across all pages
+static TOTAL_COUNT: HeaderName = HeaderName::from_static("x-total-count");
+
+/// In-memory store and cache sizes
+#[derive(Serialize, ToSchema)]
+pub struct AdminStats {
+    pub sessions: usize,
+    /// Cached LI.FI quotes (absent when the `lifi` feature is disabled)
+    #[cfg(feature = "lifi")]
+    pub quote_cache_entries: usize,
+    /// Cancelled sessions per cancellation reason
+    pub cancelled_by_reason: BTreeMap<String, usize>,
+}
+
+/// Report store and cache sizes
+#[utoipa::path(
+    get,
+    path = "/admin/stats",
+    tag = "admin",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Current sizes", body = AdminStats),
+        (status = 401, description = "Missing or invalid API key", body = crate::api::error::ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = crate::api::error::ErrorResponse)
+    )
+)]
+pub async fn stats(State(state): State<AppState>) -> Json<AdminStats> {
+    Json(AdminStats {
+        sessions: state.session_store.len().await,
+        #[cfg(feature = "lifi")]
+        quote_cache_entries: state.quote_cache.len().await,
+        cancelled_by_reason: state.session_store.cancel_reason_counts().await,
+    })
+}
+
+/// Entry counts of every in-memory store and cache, for spotting leaks
+#[derive(Serialize, ToSchema)]
+pub struct AdminInternals {
+    pub session_count: usize,
+    /// Cached ENS resolutions (absent when the `ens` feature is disabled)
+    #[cfg(feature = "ens")]
+    pub ens_cache_entries: usize,
+    /// Cached reverse ENS lookups (absent when the `ens` feature is disabled)
+    #[cfg(feature = "ens")]
+    pub reverse_cache_entries: usize,
+    /// Cached LI.FI quotes (absent when the `lifi` feature is disabled)
+    #[cfg(feature = "lifi")]
+    pub quote_cache_entries: usize,
+}
+
+/// Report in-memory entry counts
+///
+/// Counts include expired entries not yet purged, so steady growth here
+/// points at a leak rather than at load.
+#[utoipa::path(
+    get,
+    path = "/admin/internals",
+    tag = "admin",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Current entry counts", body = AdminInternals),
+        (status = 401, description = "Missing or invalid API key", body = crate::api::error::ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = crate::api::error::ErrorResponse)
+    )
+)]
+pub async fn internals(State(state): State<AppState>) -> Json<AdminInternals> {
+    #[cfg(feature = "ens")]
+    let (ens_cache_entries, reverse_cache_entries) = state.ens_service.cache_sizes().await;
+    Json(AdminInternals {
+        session_count: state.session_store.len().await,
+        #[cfg(feature = "ens")]
+        ens_cache_entries,
+        #[cfg(feature = "ens")]
+        reverse_cache_entries,
+        #[cfg(feature = "lifi")]
+        quote_cache_entries: state.quote_cache.len().await,
+    })
+}
+
+/// Admin listener health
+#[derive(Serialize, ToSchema)]
+pub struct AdminHealth {
+    #[schema(value_type = String)]
+    pub status: &'static str,
+    #[schema(value_type = String)]
+    pub version: &'static str,
+    /// `dedicated` when served on `ADMIN_PORT`, `shared` on the public listener
+    #[schema(value_type = String)]
+    pub listener: &'static str,
+}
+
+/// Health of the admin surface
+#[utoipa::path(
+    get,
+    path = "/admin/health",
+    tag = "admin",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Admin router is up", body = AdminHealth),
+        (status = 401, description = "Missing or invalid API key", body = crate::api::error::ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = crate::api::error::ErrorResponse)
+    )
+)]
+pub async fn health(State(state): State<AppState>) -> Json<AdminHealth> {
+    Json(AdminHealth {
+        status: "ok",
+        version: env!("CARGO_PKG_VERSION"),
+        listener: if state.config.admin_port.is_some() {
+            "dedicated"
+        } else {
+            "shared"
+        },
+    })
+}
+
+/// Recent readiness checks per dependency: uptime over the window and the
+/// last up/down transition
+///
+/// Filled by readiness probes (`/health/ready`); empty until the first one.
+#[utoipa::path(
+    get,
+    path = "/admin/health/history",
+    tag = "admin",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Check history by dependency", body = HealthHistoryReport),
+        (status = 401, description = "Missing or invalid API key", body = crate::api::error::ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = crate::api::error::ErrorResponse)
+    )
+)]
+pub async fn health_history(State(state): State<AppState>) -> Json<HealthHistoryReport> {
+    Json(state.readiness.history())
+}
+
+/// A served route
+#[derive(Serialize, ToSchema)]
+pub struct RouteInfo {
+    #[schema(value_type = String)]
+    pub method: &'static str,
+    pub path: String,
+    pub deprecated: bool,
+}
+
+/// List every route the service exposes
+#[utoipa::path(
+    get,
+    path = "/admin/routes",
+    tag = "admin",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Routes sorted by path", body = [RouteInfo]),
+        (status = 401, description = "Missing or invalid API key", body = crate::api::error::ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = crate::api::error::ErrorResponse)
+    )
+)]
+pub async fn routes() -> Json<Vec<RouteInfo>> {
+    let spec = openapi::spec();
+    Json(
+        openapi::operations(&spec)
+            .into_iter()
+            .map(|(method, path, operation)| RouteInfo {
+                method,
+                path: path.to_string(),
+                deprecated: matches!(operation.deprecated, Some(Deprecated::True)),
+            })
+            .collect(),
+    )
+}
+
+/// Status of every background job
+#[utoipa::path(
+    get,
+    path = "/admin/jobs",
+    tag = "admin",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Jobs sorted by name", body = [JobStatus]),
+        (status = 401, description = "Missing or invalid API key", body = crate::api::error::ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = crate::api::error::ErrorResponse)
+    )
+)]
+pub async fn jobs(State(state): State<AppState>) -> Json<Vec<JobStatus>> {
+    Json(state.jobs.statuses())
+}
+
+/// Outcome of a configuration reload
+#[derive(Serialize, ToSchema)]
+pub struct ConfigReloadResponse {
+    /// Variables whose value changed, e.g. `QUOTE_CACHE_TTL_SECS`
+    #[schema(value_type = Vec<String>)]
+    pub changed: Vec<&'static str>,
+}
+
+/// Reload the hot-reloadable settings (same as sending SIGHUP)
+///
+/// Rate limits, cache TTLs, CORS origins and the settlement confirmation
+/// depth apply at once; other settings still need a restart. An invalid
+/// configuration is rejected and the current settings are kept.
+#[utoipa::path(
+    post,
+    path = "/admin/config/reload",
+    tag = "admin",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Configuration reloaded", body = ConfigReloadResponse),
+        (status = 400, description = "New configuration is invalid", body = crate::api::error::ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = crate::api::error::ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = crate::api::error::ErrorResponse)
+    )
+)]
+pub async fn reload_config(
+    State(state): State<AppState>,
+) -> Result<Json<ConfigReloadResponse>, AppError> {
+    let changed = state.reload_config().map_err(|e| match e {
+        ConfigError::Invalid { key, .. } => AppError::validation(key, e.to_string()),
+    })?;
+    Ok(Json(ConfigReloadResponse { changed }))
+}
+
+/// A webhook receiver to register
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RegisterWebhookRequest {
+    /// http(s) URL events are POSTed to as JSON
+    pub url: String,
+    /// Event types to deliver (every type if unset)
+    pub types: Option<Vec<SessionEventType>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct WebhookListResponse {
+    pub webhooks: Vec<Webhook>,
+}
+
+/// Register a webhook receiver
+///
+/// The receiver gets every later session payment event of its types,
+/// POSTed as JSON one at a time in sequence order; a failed delivery is
+/// retried before any later event is sent.
+#[utoipa::path(
+    post,
+    path = "/admin/webhooks",
+    tag = "admin",
+    security(("api_key" = [])),
+    request_body = RegisterWebhookRequest,
+    responses(
+        (status = 201, description = "Receiver registered", body = Webhook),
+        (status = 401, description = "Missing or invalid API key", body = crate::api::error::ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = crate::api::error::ErrorResponse),
+        (status = 422, description = "Malformed body, URL or event types", body = crate::api::error::ErrorResponse)
+    )
+)]
+pub async fn register_webhook(
+    State(state): State<AppState>,
+    ValidJson(request): ValidJson<RegisterWebhookRequest>,
+) -> Result<(StatusCode, Json<Webhook>), AppError> {
+    let url = reqwest::Url::parse(request.url.trim())
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or_else(|| AppError::unprocessable("url", "url must be an http or https URL"))?;
+    let types = match request.types {
+        Some(types) if types.is_empty() => {
+            return Err(AppError::unprocessable(
+                "types",
+                "types must name at least one event type, or be left out",
+            ))
+        }
+        Some(types) => Some(types.into_iter().fold(Vec::new(), |mut unique, kind| {
+            if !unique.contains(&kind) {
+                unique.push(kind);
+            }
+            unique
+        })),
+        None => None,
+    };
+    let webhook = state.webhooks.register(url.to_string(), types);
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+/// List webhook receivers with their delivery state
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks",
+    tag = "admin",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Registered receivers", body = WebhookListResponse),
+        (status = 401, description = "Missing or invalid API key", body = crate::api::error::ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = crate::api::error::ErrorResponse)
+    )
+)]
+pub async fn list_webhooks(State(state): State<AppState>) -> Json<WebhookListResponse> {
+    Json(WebhookListResponse {
+        webhooks: state.webhooks.list(),
+    })
+}
+
+/// Unregister a webhook receiver, dropping its undelivered events
+#[utoipa::path(
+    delete,
+    path = "/admin/webhooks/{id}",
+    tag = "admin",
+    security(("api_key" = [])),
+    params(("id" = String, Path, description = "Webhook ID")),
+    responses(
+        (status = 204, description = "Receiver removed"),
+        (status = 401, description = "Missing or invalid API key", body = crate::api::error::ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = crate::api::error::ErrorResponse),
+        (status = 404, description = "Webhook not found", body = crate::api::error::ErrorResponse)
+    )
+)]
+pub async fn remove_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    if state.webhooks.remove(&id) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound(format!("Webhook {} not found", id)))
+    }
+}
+
+/// Events of one session to deliver again
+#[derive(Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RedeliverRequest {
+    pub session_id: String,
+    /// First sequence to redeliver
+    pub from_sequence: u64,
+    /// Last sequence to redeliver (the latest if unset)
+    pub to_sequence: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RedeliverResponse {
+    /// Events queued again for at least one webhook receiver
+    pub redelivered: usize,
+}
+
+/// Re-enqueue logged session events for webhook delivery
+///
+/// The events of `session_id` numbered `from_sequence..=to_sequence` are
+/// queued again for every webhook receiver of their type, with their
+/// original sequences, so receivers can deduplicate. Events no receiver
+/// wants are not counted.
+#[utoipa::path(
+    post,
+    path = "/admin/webhooks/redeliver",
+    tag = "admin",
+    security(("api_key" = [])),
+    request_body = RedeliverRequest,
+    responses(
+        (status = 200, description = "Events queued for the webhook receivers", body = RedeliverResponse),
+        (status = 401, description = "Missing or invalid API key", body = crate::api::error::ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = crate::api::error::ErrorResponse),
+        (status = 404, description = "Session not found", body = crate::api::error::ErrorResponse),
+        (status = 410, description = "Session was settled and archived", body = crate::api::error::ErrorResponse),
+        (status = 422, description = "Malformed body or empty range", body = crate::api::error::ErrorResponse)
+    )
+)]
+pub async fn redeliver_events(
+    State(state): State<AppState>,
+    ValidJson(request): ValidJson<RedeliverRequest>,
+) -> Result<Json<RedeliverResponse>, AppError> {
+    let to = request.to_sequence.unwrap_or(u64::MAX);
+    if to < request.from_sequence {
+        return Err(AppError::unprocessable(
+            "to_sequence",
+            "to_sequence must not be below from_sequence",
+        ));
+    }
+    let Some(events) = state
+        .session_store
+        .events_since(&request.session_id, request.from_sequence.saturating_sub(1))
+        .await
+    else {
+        return Err(missing_session(&state, &request.session_id).await);
+    };
+    let redelivered = events
+        .iter()
+        .take_while(|event| event.sequence <= to)
+        .filter(|event| state.webhooks.enqueue(event) > 0)
+        .count();
+    Ok(Json(RedeliverResponse { redelivered }))
+}
+
+/// Whether maintenance mode is on
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MaintenanceMode {
+    pub enabled: bool,
+}
+
+/// Turn maintenance mode on or off
+///
+/// While on, POST, PATCH and DELETE requests to the API and GraphQL/gRPC
+/// mutations get a 503 `maintenance` error with `Retry-After`; reads,
+/// health checks and `/admin` keep working.
+#[utoipa::path(
+    post,
+    path = "/admin/maintenance",
+    tag = "admin",
+    security(("api_key" = [])),
+    request_body = MaintenanceMode,
+    responses(
+        (status = 200, description = "Maintenance mode after the change", body = MaintenanceMode),
+        (status = 401, description = "Missing or invalid API key", body = crate::api::error::ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = crate::api::error::ErrorResponse),
+        (status = 422, description = "Malformed body", body = crate::api::error::ErrorResponse)
+    )
+)]
+pub async fn set_maintenance(
+    State(state): State<AppState>,
+    ValidJson(request): ValidJson<MaintenanceMode>,
+) -> Json<MaintenanceMode> {
+    let was = state.maintenance.swap(request.enabled, Ordering::Relaxed);
+    if was != request.enabled {
+        tracing::warn!(
+            "Maintenance mode {}",
+            if request.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        );
+    }
+    Json(MaintenanceMode {
+        enabled: request.enabled,
+    })
+}
+
+/// Body format of [`snapshot`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotFormat {
+    /// One snapshot document, importable with `snapshot import`
+    #[default]
+    Json,
+    /// One session per line, streamed
+    Ndjson,
+}
+
+/// Page of [`snapshot`]; sessions are ordered oldest first
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SnapshotQuery {
+    /// Sessions to return (all if unset)
+    pub limit: Option<usize>,
+    /// Sessions to skip
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default)]
+    pub format: SnapshotFormat,
+}
+
+/// Export stored sessions, one page at a time
+///
+/// `json` returns a snapshot document of the page's sessions, their event
+/// logs and every session template. `ndjson` streams the page's sessions
+/// one JSON object per line, reading each from the store as it is sent.
+/// `X-Total-Count` holds the number of stored sessions.
+#[utoipa::path(
+    get,
+    path = "/admin/snapshot",
+    tag = "admin",
+    security(("api_key" = [])),
+    params(SnapshotQuery),
+    responses(
+        (status = 200, description = "Snapshot page (application/json or application/x-ndjson)"),
+        (status = 400, description = "limit is zero", body = crate::api::error::ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = crate::api::error::ErrorResponse),
+        (status = 403, description = "API key is not an admin key", body = crate::api::error::ErrorResponse),
+        (status = 422, description = "Malformed limit, offset or format", body = crate::api::error::ErrorResponse)
+    )
+)]
+pub async fn snapshot(
+    State(state): State<AppState>,
+    ValidQuery(query): ValidQuery<SnapshotQuery>,
+) -> Result<Response, AppError> {
+    if query.limit == Some(0) {
+        return Err(AppError::validation("limit", "limit must be at least 1"));
+    }
+    let ids = state.session_store.ids().await;
+    let total = ids.len();
+    let page: Vec<String> = ids
+        .into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
+    let total_header = [(TOTAL_COUNT.clone(), HeaderValue::from(total))];
+
+    match query.format {
+        SnapshotFormat::Json => {
+            let mut sessions = Vec::with_capacity(page.len());
+            let mut events = Vec::new();
+            for id in &page {
+                if let Some(session) = state.session_store.get(id).await {
+                    sessions.push(session);
+                    events.extend(
+                        state
+                            .session_store
+                            .events_since(id, 0)
+                            .await
+                            .unwrap_or_default(),
+                    );
+                }
+            }
+            let snapshot = Snapshot::new(sessions)
+                .with_events(events)
+                .with_templates(state.template_store.snapshot().await);
+            Ok((total_header, Json(snapshot)).into_response())
+        }
+        SnapshotFormat::Ndjson => {
+            let store = state.session_store.clone();
+            // Sessions removed since the page was taken are skipped
+            let lines = stream::iter(page)
+                .filter_map(move |id| {
+                    let store = store.clone();
+                    async move { store.get(&id).await }
+                })
+                .map(|session| {
+                    let mut line =
+                        serde_json::to_vec(&session).expect("sessions serialize to JSON");
+                    line.push(b'\n');
+                    Ok::<_, Infallible>(line)
+                });
+            Ok((
+                total_header,
+                [(header::CONTENT_TYPE, HeaderValue::from_static(NDJSON))],
+                Body::from_stream(lines),
+            )
+                .into_response())
+        }
+    }
+}
@@ -1,75 +1,219 @@
 //! ENS resolution API handlers
 
-use axum::{extract::Query, extract::State, Json};
-use serde::{Deserialize, Serialize};
+use axum::{extract::State, Extension, Json};
 
+use crate::api::error::{AppError, ErrorResponse};
+use crate::api::extract::{ValidJson, ValidQuery};
+use crate::api::ApiVersion;
+use crate::services::ens::{namehash, normalize_name, EnsError, EnsNameError};
 use crate::AppState;
+pub use settleone_types::api::{
+    ContentHashRequest, ContentHashResponse, LookupRequest, LookupResponse, NamehashRequest,
+    NamehashResponse, ResolveBatchRequest, ResolveBatchResponse, ResolveRequest, ResolveResponse,
+};
 
-/// ENS resolution request
-#[derive(Deserialize)]
-pub struct ResolveRequest {
-    pub name: String,
-}
+/// Most names one batch resolution may list
+pub const MAX_BATCH_RESOLVE: usize = 50;
 
-/// ENS resolution response
-#[derive(Serialize)]
-pub struct ResolveResponse {
-    pub name: String,
-    pub address: Option<String>,
-    pub avatar: Option<String>,
-    pub error: Option<String>,
+/// Map an ENS service error onto the API error envelope
+pub fn ens_error(field: &str, e: EnsError) -> AppError {
+    match e {
+        EnsError::InvalidName(_) => AppError::validation(field, e.to_string()),
+        EnsError::NotFound(_) => AppError::NotFound(e.to_string()),
+        EnsError::ConnectError(_) => AppError::UpstreamUnreachable(e.to_string()),
+        EnsError::TimeoutError(_) => AppError::UpstreamTimeout(e.to_string()),
+        EnsError::ResolutionFailed(_) | EnsError::HttpStatusError(_) | EnsError::Unavailable(_) => {
+            AppError::Upstream(e.to_string())
+        }
+    }
 }
 
 /// Resolve an ENS name to an address
+#[utoipa::path(
+    get,
+    path = "/api/v1/ens/resolve",
+    tag = "ens",
+    params(ResolveRequest),
+    responses(
+        (status = 200, description = "Name resolved", body = ResolveResponse),
+        (status = 400, description = "Invalid ENS name", body = ErrorResponse),
+        (status = 404, description = "Name not found", body = ErrorResponse),
+        (status = 422, description = "Malformed ENS name", body = ErrorResponse),
+        (status = 502, description = "Resolver unreachable or failing", body = ErrorResponse),
+        (status = 504, description = "Resolver timed out", body = ErrorResponse)
+    )
+)]
 pub async fn resolve_ens(
     State(state): State<AppState>,
-    Query(params): Query<ResolveRequest>,
-) -> Json<ResolveResponse> {
-    match state.ens_service.resolve(&params.name).await {
-        Ok(result) => Json(ResolveResponse {
+    Extension(version): Extension<ApiVersion>,
+    ValidQuery(params): ValidQuery<ResolveRequest>,
+) -> Result<Json<ResolveResponse>, AppError> {
+    let resolved = if params.allow_stale {
+        state.ens_service.resolve_allow_stale(&params.name).await
+    } else {
+        state.ens_service.resolve(&params.name).await
+    };
+    match resolved {
+        Ok(result) => Ok(Json(ResolveResponse {
             name: params.name,
             address: Some(result.address),
             avatar: result.avatar,
+            cached: result.cache_age.is_some(),
+            age_secs: result.cache_age.map_or(0, |age| age.as_secs()),
             error: None,
-        }),
-        Err(e) => Json(ResolveResponse {
+        })),
+        Err(e) if version.strict_errors(&state.config) => Err(ens_error("name", e)),
+        Err(e) => Ok(Json(ResolveResponse {
             name: params.name,
             address: None,
             avatar: None,
+            cached: false,
+            age_secs: 0,
             error: Some(e.to_string()),
-        }),
+        })),
     }
 }
 
-/// Address lookup request
-#[derive(Deserialize)]
-pub struct LookupRequest {
-    pub address: String,
-}
+/// Resolve several ENS names at once.
+///
+/// Each name is resolved as by `/ens/resolve`; one that fails is reported
+/// in its result without failing the batch. When the ENS API fails, the
+/// affected names are looked up in a single subgraph query
+/// (`ENS_SUBGRAPH_URL`).
+#[utoipa::path(
+    post,
+    path = "/api/v1/ens/resolve/batch",
+    tag = "ens",
+    request_body = ResolveBatchRequest,
+    responses(
+        (status = 200, description = "One result per name, in request order", body = ResolveBatchResponse),
+        (status = 400, description = "No names, or more than 50", body = ErrorResponse),
+        (status = 422, description = "Malformed ENS name or unknown field", body = ErrorResponse)
+    )
+)]
+pub async fn resolve_ens_batch(
+    State(state): State<AppState>,
+    ValidJson(payload): ValidJson<ResolveBatchRequest>,
+) -> Result<Json<ResolveBatchResponse>, AppError> {
+    if payload.names.is_empty() || payload.names.len() > MAX_BATCH_RESOLVE {
+        return Err(AppError::validation(
+            "names",
+            format!("names must list 1 to {} names", MAX_BATCH_RESOLVE),
+        ));
+    }
 
-/// Address lookup response
-#[derive(Serialize)]
-pub struct LookupResponse {
-    pub address: String,
-    pub name: Option<String>,
-    pub error: Option<String>,
+    let resolved = state.ens_service.resolve_many(&payload.names).await;
+    let results = payload
+        .names
+        .into_iter()
+        .zip(resolved)
+        .map(|(name, result)| match result {
+            Ok(result) => ResolveResponse {
+                name,
+                address: Some(result.address),
+                avatar: result.avatar,
+                cached: result.cache_age.is_some(),
+                age_secs: result.cache_age.map_or(0, |age| age.as_secs()),
+                error: None,
+            },
+            Err(e) => ResolveResponse {
+                name,
+                address: None,
+                avatar: None,
+                cached: false,
+                age_secs: 0,
+                error: Some(e.to_string()),
+            },
+        })
+        .collect();
+    Ok(Json(ResolveBatchResponse { results }))
 }
 
 /// Reverse lookup: address to ENS name
+#[utoipa::path(
+    get,
+    path = "/api/v1/ens/lookup",
+    tag = "ens",
+    params(LookupRequest),
+    responses(
+        (status = 200, description = "Lookup completed (name may be null)", body = LookupResponse),
+        (status = 422, description = "Invalid address", body = ErrorResponse)
+    )
+)]
 pub async fn lookup_address(
     State(state): State<AppState>,
-    Query(params): Query<LookupRequest>,
+    ValidQuery(params): ValidQuery<LookupRequest>,
 ) -> Json<LookupResponse> {
-    match state.ens_service.reverse_lookup(&params.address).await {
-        Ok(name) => Json(LookupResponse {
-            address: params.address,
-            name,
-            error: None,
-        }),
-        Err(e) => Json(LookupResponse {
-            address: params.address,
-            name: None,
-            error: Some(e.to_string()),
-        }),
+    let result = state.ens_service.reverse_lookup(&params.address).await;
+    let (name, avatar) = match result {
+        Some(result) => (Some(result.name), result.avatar),
+        None => (None, None),
+    };
+    Json(LookupResponse {
+        address: params.address,
+        name,
+        avatar,
+        error: None,
+    })
+}
+
+/// The content hash record of a name, decoded into a URI.
+///
+/// Read on chain through `ETH_RPC_URL`; `contenthash` is null when the name
+/// has no resolver or no record.
+#[utoipa::path(
+    get,
+    path = "/api/v1/ens/contenthash",
+    tag = "ens",
+    params(ContentHashRequest),
+    responses(
+        (status = 200, description = "Content hash (may be null)", body = ContentHashResponse),
+        (status = 400, description = "Invalid ENS name", body = ErrorResponse),
+        (status = 422, description = "Malformed ENS name", body = ErrorResponse),
+        (status = 502, description = "RPC unreachable, failing or record undecodable", body = ErrorResponse),
+        (status = 504, description = "RPC timed out", body = ErrorResponse)
+    )
+)]
+pub async fn content_hash(
+    State(state): State<AppState>,
+    ValidQuery(params): ValidQuery<ContentHashRequest>,
+) -> Result<Json<ContentHashResponse>, AppError> {
+    let contenthash = state
+        .ens_service
+        .resolve_content_hash(&params.name)
+        .await
+        .map_err(|e| ens_error("name", e))?;
+    Ok(Json(ContentHashResponse {
+        name: params.name,
+        contenthash,
+    }))
+}
+
+/// Normalize a name and compute its EIP-137 namehash.
+///
+/// Any normalizable name is accepted, TLDs included; the `.eth` policy of
+/// resolution does not apply. Nothing is looked up.
+#[utoipa::path(
+    get,
+    path = "/api/v1/ens/namehash",
+    tag = "ens",
+    params(NamehashRequest),
+    responses(
+        (status = 200, description = "Namehash of the normalized name", body = NamehashResponse),
+        (status = 400, description = "Name cannot be normalized", body = ErrorResponse)
+    )
+)]
+pub async fn namehash_name(
+    ValidQuery(params): ValidQuery<NamehashRequest>,
+) -> Result<Json<NamehashResponse>, AppError> {
+    let invalid = |e: EnsNameError| AppError::validation("name", EnsError::from(e).to_string());
+    let normalized = normalize_name(&params.name).map_err(invalid)?;
+    if normalized.split('.').any(str::is_empty) {
+        return Err(invalid(EnsNameError::EmptyLabel));
     }
+    Ok(Json(NamehashResponse {
+        namehash: namehash(&normalized),
+        name: params.name,
+        normalized,
+    }))
 }
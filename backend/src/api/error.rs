@@ -1,31 +1,360 @@
+//! Unified JSON error envelope shared by all endpoints
+//!
+//! Every error response has the shape
+//! `{ "code": "...", "message": "...", "details": ..., "request_id": "..." }`.
+
+use std::time::Duration;
+
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde_json::json;
 
+use crate::api::middleware::current_request_id;
+use crate::reporting::{ErrorEvent, ErrorKind};
+pub use settleone_types::api::{ErrorCode, ErrorResponse, FieldError};
+
+/// `Retry-After` sent while maintenance mode refuses writes
+pub const MAINTENANCE_RETRY_AFTER: Duration = Duration::from_secs(60);
+
 #[derive(Debug)]
 #[allow(dead_code)]
 pub enum AppError {
     NotFound(String),
+    Validation {
+        message: String,
+        fields: Vec<FieldError>,
+    },
+    Conflict(String),
+    /// The resource existed but was archived
+    Gone(String),
+    /// An upstream answered with an error or an unusable payload
+    Upstream(String),
+    /// An upstream could not be connected to
+    UpstreamUnreachable(String),
+    /// An upstream did not answer in time; sent as a 504
+    UpstreamTimeout(String),
+    Unauthorized(String),
+    Forbidden(String),
+    /// The path exists but not for this method
+    MethodNotAllowed(String),
+    /// Input that is well-formed JSON but has invalid or contradictory fields
+    Unprocessable {
+        message: String,
+        fields: Vec<FieldError>,
+    },
+    RateLimited(String),
+    /// Rate limited with a known wait, sent as the `Retry-After` header
+    TooManyRequests {
+        message: String,
+        retry_after: Duration,
+    },
     NotImplemented(String),
-    InternalServerError(String),
-    // Add more variants as needed
+    /// Writes are paused by maintenance mode; sent with `Retry-After`
+    Maintenance(String),
+    Internal(String),
+    /// A handler panicked; the message never includes panic details
+    Panic,
+}
+
+impl AppError {
+    /// Validation error for a single field
+    pub fn validation(field: &str, message: impl Into<String>) -> Self {
+        let message = message.into();
+        AppError::Validation {
+            message: message.clone(),
+            fields: vec![FieldError {
+                field: field.to_string(),
+                message,
+            }],
+        }
+    }
+
+    /// Unprocessable-entity error for a single field
+    pub fn unprocessable(field: &str, message: impl Into<String>) -> Self {
+        let message = message.into();
+        AppError::Unprocessable {
+            message: message.clone(),
+            fields: vec![FieldError {
+                field: field.to_string(),
+                message,
+            }],
+        }
+    }
+
+    /// HTTP status for this error
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Validation { .. } => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Gone(_) => StatusCode::GONE,
+            AppError::Upstream(_) | AppError::UpstreamUnreachable(_) => StatusCode::BAD_GATEWAY,
+            AppError::UpstreamTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            AppError::Unprocessable { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::RateLimited(_) | AppError::TooManyRequests { .. } => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
+            AppError::NotImplemented(_) => StatusCode::NOT_IMPLEMENTED,
+            AppError::Maintenance(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Internal(_) | AppError::Panic => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Machine-readable error code
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::NotFound(_) => ErrorCode::NotFound,
+            AppError::Validation { .. } => ErrorCode::Validation,
+            AppError::Conflict(_) => ErrorCode::Conflict,
+            AppError::Gone(_) => ErrorCode::Gone,
+            AppError::Upstream(_) => ErrorCode::Upstream,
+            AppError::UpstreamUnreachable(_) => ErrorCode::UpstreamUnreachable,
+            AppError::UpstreamTimeout(_) => ErrorCode::UpstreamTimeout,
+            AppError::Unauthorized(_) => ErrorCode::Unauthorized,
+            AppError::Forbidden(_) => ErrorCode::Forbidden,
+            AppError::MethodNotAllowed(_) => ErrorCode::MethodNotAllowed,
+            AppError::Unprocessable { .. } => ErrorCode::Unprocessable,
+            AppError::RateLimited(_) | AppError::TooManyRequests { .. } => ErrorCode::RateLimited,
+            AppError::NotImplemented(_) => ErrorCode::NotImplemented,
+            AppError::Maintenance(_) => ErrorCode::Maintenance,
+            AppError::Internal(_) => ErrorCode::Internal,
+            AppError::Panic => ErrorCode::InternalPanic,
+        }
+    }
+
+    /// Human-readable message
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotFound(msg)
+            | AppError::Validation { message: msg, .. }
+            | AppError::Conflict(msg)
+            | AppError::Gone(msg)
+            | AppError::Upstream(msg)
+            | AppError::UpstreamUnreachable(msg)
+            | AppError::UpstreamTimeout(msg)
+            | AppError::Unauthorized(msg)
+            | AppError::Forbidden(msg)
+            | AppError::MethodNotAllowed(msg)
+            | AppError::Unprocessable { message: msg, .. }
+            | AppError::RateLimited(msg)
+            | AppError::TooManyRequests { message: msg, .. }
+            | AppError::NotImplemented(msg)
+            | AppError::Maintenance(msg)
+            | AppError::Internal(msg) => msg,
+            AppError::Panic => "Internal server error",
+        }
+    }
+
+    /// How long the client should wait before retrying, if known
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            AppError::TooManyRequests { retry_after, .. } => Some(*retry_after),
+            AppError::Maintenance(_) => Some(MAINTENANCE_RETRY_AFTER),
+            _ => None,
+        }
+    }
+
+    /// Optional structured details
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            AppError::Validation { fields, .. } | AppError::Unprocessable { fields, .. } => {
+                Some(json!({ "fields": fields }))
+            }
+            _ => None,
+        }
+    }
+
+    /// The error envelope sent for this error
+    pub fn body(&self) -> ErrorResponse {
+        ErrorResponse {
+            code: self.code(),
+            message: self.message().to_string(),
+            details: self.details(),
+            request_id: current_request_id(),
+        }
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
-            AppError::NotImplemented(msg) => (StatusCode::NOT_IMPLEMENTED, msg),
-            AppError::InternalServerError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-        };
+        let status = self.status();
+        let mut response = (status, Json(self.body())).into_response();
+        if let Some(retry_after) = self.retry_after() {
+            let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        // Picked up by the `report_errors` middleware
+        // Deliberate refusals are not server faults
+        let expected = matches!(self, AppError::NotImplemented(_) | AppError::Maintenance(_));
+        if status.is_server_error() && !expected {
+            let kind = match self {
+                AppError::Panic => ErrorKind::Panic,
+                _ => ErrorKind::ServerError,
+            };
+            let event =
+                ErrorEvent::new(kind, self.message()).with_context("code", self.code().as_str());
+            response.extensions_mut().insert(event);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::middleware::REQUEST_ID;
+
+    async fn render(error: AppError) -> (StatusCode, serde_json::Value) {
+        let response = REQUEST_ID
+            .scope("req-test".to_string(), async { error.into_response() })
+            .await;
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_not_found_shape() {
+        let (status, body) = render(AppError::NotFound("Session x not found".into())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            body,
+            json!({
+                "code": "not_found",
+                "message": "Session x not found",
+                "details": null,
+                "request_id": "req-test",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validation_shape() {
+        let (status, body) = render(AppError::validation("name", "must end with .eth")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            json!({
+                "code": "validation_error",
+                "message": "must end with .eth",
+                "details": {
+                    "fields": [{ "field": "name", "message": "must end with .eth" }]
+                },
+                "request_id": "req-test",
+            })
+        );
+    }
 
-        let body = Json(json!({
-            "error": error_message,
-        }));
+    #[tokio::test]
+    async fn test_unprocessable_shape() {
+        let (status, body) = render(AppError::unprocessable(
+            "user_address",
+            "Address must start with 0x",
+        ))
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "unprocessable_entity");
+        assert_eq!(
+            body["details"]["fields"],
+            json!([{ "field": "user_address", "message": "Address must start with 0x" }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_simple_variant_shapes() {
+        let cases = [
+            (
+                AppError::Conflict("c".into()),
+                StatusCode::CONFLICT,
+                "conflict",
+            ),
+            (AppError::Gone("g".into()), StatusCode::GONE, "gone"),
+            (
+                AppError::Upstream("u".into()),
+                StatusCode::BAD_GATEWAY,
+                "upstream_error",
+            ),
+            (
+                AppError::Unauthorized("a".into()),
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+            ),
+            (
+                AppError::Forbidden("f".into()),
+                StatusCode::FORBIDDEN,
+                "forbidden",
+            ),
+            (
+                AppError::MethodNotAllowed("m".into()),
+                StatusCode::METHOD_NOT_ALLOWED,
+                "method_not_allowed",
+            ),
+            (
+                AppError::RateLimited("r".into()),
+                StatusCode::TOO_MANY_REQUESTS,
+                "rate_limited",
+            ),
+            (
+                AppError::NotImplemented("n".into()),
+                StatusCode::NOT_IMPLEMENTED,
+                "not_implemented",
+            ),
+            (
+                AppError::Maintenance("m".into()),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "maintenance",
+            ),
+            (
+                AppError::Internal("i".into()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+            ),
+            (
+                AppError::Panic,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_panic",
+            ),
+        ];
+
+        for (error, expected_status, expected_code) in cases {
+            let message = error.message().to_string();
+            let (status, body) = render(error).await;
+            assert_eq!(status, expected_status);
+            assert_eq!(
+                body,
+                json!({
+                    "code": expected_code,
+                    "message": message,
+                    "details": null,
+                    "request_id": "req-test",
+                })
+            );
+        }
+    }
 
-        (status, body).into_response()
+    #[tokio::test]
+    async fn test_request_id_null_outside_request() {
+        let response = AppError::Internal("boom".into()).into_response();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(body["request_id"].is_null());
     }
 }
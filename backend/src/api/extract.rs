@@ -0,0 +1,228 @@
+//! Request extractors with typed validation errors
+//!
+//! [`ValidJson`] and [`ValidQuery`] deserialize like axum's `Json` and
+//! `Query`, but a field that fails to deserialize (a malformed address, an
+//! unknown enum value, a missing field) is a 422 error envelope naming the
+//! field instead of a plain-text rejection. Request bodies deny unknown
+//! fields; the error suggests the known field closest to a misspelt one,
+//! e.g. `recipient_ens` for `recipientEns`.
+
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::extract::{FromRequest, Request};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::request::Parts;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::de::DeserializeOwned;
+
+use crate::api::error::AppError;
+
+/// JSON request body, validated on extraction
+#[derive(Debug, Clone)]
+pub struct ValidJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Response> {
+        // Missing content type and malformed JSON are rejected as axum does
+        let Json(value) = Json::<serde_json::Value>::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        serde_path_to_error::deserialize(value)
+            .map(ValidJson)
+            .map_err(|e| field_error(e, "body").into_response())
+    }
+}
+
+/// Optional JSON request body: `None` for a request without one (no
+/// `Content-Type`), otherwise extracted as [`ValidJson`]
+#[derive(Debug, Clone)]
+pub struct OptionalJson<T>(pub Option<T>);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for OptionalJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Response> {
+        if !req.headers().contains_key(CONTENT_TYPE) {
+            return Ok(OptionalJson(None));
+        }
+        let ValidJson(value) = ValidJson::from_request(req, state).await?;
+        Ok(OptionalJson(Some(value)))
+    }
+}
+
+/// Query string parameters, validated on extraction
+#[derive(Debug, Clone)]
+pub struct ValidQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, AppError> {
+        let query = parts.uri.query().unwrap_or_default();
+        let params = serde_urlencoded::Deserializer::new(form_urlencoded::parse(query.as_bytes()));
+        serde_path_to_error::deserialize(params)
+            .map(ValidQuery)
+            .map_err(|e| field_error(e, "query"))
+    }
+}
+
+/// 422 naming the field at the error's path, or `whole` for errors that
+/// are not about one field (e.g. a missing field)
+fn field_error<E: std::fmt::Display>(e: serde_path_to_error::Error<E>, whole: &str) -> AppError {
+    let path = e.path().to_string();
+    let field = if path == "." { whole } else { &path };
+    let message = e.inner().to_string();
+    let message = suggest_field(&message).unwrap_or(message);
+    AppError::unprocessable(field, message)
+}
+
+/// For serde's "unknown field `x`, expected one of `a`, `b`" error, the
+/// message with the closest known field suggested, if one is close enough
+fn suggest_field(message: &str) -> Option<String> {
+    if !message.starts_with("unknown field") {
+        return None;
+    }
+    // Names are the odd pieces between backticks: the unknown one first
+    let mut names = message.split('`').skip(1).step_by(2);
+    let unknown = names.next()?;
+    let fold = |name: &str| -> Vec<char> {
+        name.chars()
+            .filter(|c| *c != '_' && *c != '-')
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let folded = fold(unknown);
+    let (distance, closest) = names
+        .map(|known| (edit_distance(&folded, &fold(known)), known))
+        .min_by_key(|(distance, _)| *distance)?;
+    if distance > (folded.len() / 3).max(1) {
+        return None;
+    }
+    Some(message.replacen(
+        ", expected",
+        &format!(", did you mean `{}`? Expected", closest),
+        1,
+    ))
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use serde::Deserialize;
+
+    use crate::models::address::Address;
+
+    #[derive(Debug, Deserialize)]
+    struct Payload {
+        #[allow(dead_code)]
+        payments: Vec<Entry>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Entry {
+        #[allow(dead_code)]
+        recipient: Address,
+    }
+
+    async fn reject(body: &str) -> AppError {
+        let request = Request::builder()
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = ValidJson::<Payload>::from_request(request, &())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY
+        );
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        AppError::unprocessable(
+            body["details"]["fields"][0]["field"].as_str().unwrap(),
+            body["details"]["fields"][0]["message"].as_str().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_unknown_field_suggestions() {
+        let message = "unknown field `recipientEns`, expected one of `recipient`, \
+                       `recipient_ens`, `amount`, `human_amount`";
+        assert_eq!(
+            suggest_field(message).unwrap(),
+            "unknown field `recipientEns`, did you mean `recipient_ens`? Expected one of \
+             `recipient`, `recipient_ens`, `amount`, `human_amount`"
+        );
+        // One edit away, and single-field messages
+        assert!(suggest_field("unknown field `amout`, expected `amount`")
+            .unwrap()
+            .contains("did you mean `amount`?"));
+        assert_eq!(
+            suggest_field("unknown field `colour`, expected `tx_hash` or `amount`"),
+            None
+        );
+        assert_eq!(suggest_field("missing field `amount`"), None);
+
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(edit_distance(&chars("kitten"), &chars("sitting")), 3);
+        assert_eq!(edit_distance(&chars(""), &chars("abc")), 3);
+    }
+
+    #[tokio::test]
+    async fn test_json_error_names_nested_field() {
+        let valid = "0x1111111111111111111111111111111111111111";
+        let body = format!(
+            r#"{{"payments": [{{"recipient": "{}"}}, {{"recipient": "0xBob"}}]}}"#,
+            valid
+        );
+        match reject(&body).await {
+            AppError::Unprocessable { fields, .. } => {
+                assert_eq!(fields[0].field, "payments[1].recipient");
+                assert!(fields[0].message.contains("42 characters"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match reject("{}").await {
+            AppError::Unprocessable { fields, .. } => {
+                assert_eq!(fields[0].field, "body");
+                assert!(fields[0].message.contains("missing field `payments`"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
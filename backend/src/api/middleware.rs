@@ -0,0 +1,548 @@
+//! HTTP middleware
+
+use std::any::Any;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::api::error::AppError;
+use crate::config::LiveConfig;
+use crate::reporting::{ErrorEvent, ErrorReporter};
+use crate::services::auth::{authenticate, ApiRole};
+use crate::services::jobs::panic_message;
+use crate::services::rate_limit::RouteClass;
+use crate::telemetry::{UpstreamCalls, UPSTREAM_CALLS};
+use crate::AppState;
+
+/// Header carrying the request id in both directions
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Header choosing how JSON responses write timestamps (`rfc3339` or `unix`)
+pub static TIMESTAMP_FORMAT_HEADER: HeaderName = HeaderName::from_static("x-timestamp-format");
+
+/// Largest JSON response rewritten for `X-Timestamp-Format: unix` or
+/// `MASK_ADDRESSES`
+const MAX_SHAPED_BODY_BYTES: u64 = 16 * 1024 * 1024;
+
+tokio::task_local! {
+    /// Request id of the request currently being handled
+    pub static REQUEST_ID: String;
+}
+
+/// Request id of the in-flight request, if called from within a handler
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Assign a request id (reusing a client-supplied `x-request-id` when present),
+/// expose it to handlers and echo it back on the response.
+pub async fn request_id(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= 128)
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let mut response = REQUEST_ID.scope(id.clone(), next.run(request)).await;
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response
+            .headers_mut()
+            .insert(REQUEST_ID_HEADER.clone(), value);
+    }
+    response
+}
+
+/// Turn a caught handler panic into the standard 500 envelope.
+///
+/// The panic message and backtrace are logged by the panic hook installed in
+/// [`crate::logging::init`]; nothing about the panic reaches the client.
+pub fn panic_response(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let mut response = AppError::Panic.into_response();
+    if let Some(event) = response.extensions_mut().get_mut::<ErrorEvent>() {
+        event.message = format!("panicked: {}", panic_message(&*panic));
+    }
+    response
+}
+
+/// Report panics and 5xx error envelopes, tagged with the request id, the
+/// route template and the method
+pub async fn report_errors(
+    State(reporter): State<Arc<dyn ErrorReporter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let mut response = next.run(request).await;
+    if let Some(mut event) = response.extensions_mut().remove::<ErrorEvent>() {
+        event.request_id = current_request_id();
+        event.route = route;
+        let event = event
+            .with_context("method", method.as_str())
+            .with_context("status", response.status().as_str());
+        reporter.report(event);
+    }
+    response
+}
+
+/// Replace the router's bare 405 with the error envelope, keeping the
+/// `Allow` header listing the methods the path accepts
+pub async fn method_not_allowed(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let response = next.run(request).await;
+    if response.status() != axum::http::StatusCode::METHOD_NOT_ALLOWED
+        || response.headers().contains_key(header::CONTENT_TYPE)
+    {
+        return response;
+    }
+
+    let allow = response.headers().get(header::ALLOW).cloned();
+    let mut response = AppError::MethodNotAllowed(format!("{} is not allowed on {}", method, path))
+        .into_response();
+    if let Some(allow) = allow {
+        response.headers_mut().insert(header::ALLOW, allow);
+    }
+    response
+}
+
+/// Date after which the unversioned `/api/...` routes may be removed
+const LEGACY_API_SUNSET: &str = "Thu, 01 Jul 2027 00:00:00 GMT";
+
+/// Mark responses from the unversioned API as deprecated (RFC 8594 / 9745)
+pub async fn deprecation_headers(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    headers.insert("sunset", HeaderValue::from_static(LEGACY_API_SUNSET));
+    headers.insert(
+        "link",
+        HeaderValue::from_static("</api/v1>; rel=\"successor-version\""),
+    );
+    response
+}
+
+/// Routes excluded from latency histograms (scrapes and liveness probes)
+const UNTIMED_ROUTES: &[&str] = &["/metrics", "/health"];
+
+/// Route template of the request (e.g. `/api/v1/session/:id`)
+fn matched_route(request: &Request) -> String {
+    request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string())
+}
+
+/// Record request count, in-flight gauge and latency histogram.
+///
+/// Must be installed with `route_layer` so that `MatchedPath` is available;
+/// labelling by route template rather than raw path bounds cardinality.
+pub async fn track_metrics(request: Request, next: Next) -> Response {
+    let route = matched_route(&request);
+    let method = request.method().to_string();
+
+    let start = Instant::now();
+    metrics::gauge!("http_requests_in_flight").increment(1.0);
+    let response = next.run(request).await;
+    metrics::gauge!("http_requests_in_flight").decrement(1.0);
+
+    let status = format!("{}xx", response.status().as_u16() / 100);
+    let labels = [
+        ("method", method),
+        ("route", route.clone()),
+        ("status", status),
+    ];
+
+    metrics::counter!("http_requests_total", &labels).increment(1);
+    if !UNTIMED_ROUTES.contains(&route.as_str()) {
+        metrics::histogram!("http_request_duration_seconds", &labels)
+            .record(start.elapsed().as_secs_f64());
+    }
+
+    response
+}
+
+/// Log one event per completed request with its route, status and latency.
+///
+/// Requests slower than `SLOW_REQUEST_THRESHOLD_MS` are also logged as a
+/// warning with the time spent in each upstream, and counted in
+/// `http_slow_requests_total`. Installed with `route_layer`, like
+/// [`track_metrics`].
+pub async fn log_request(
+    State(live_config): State<LiveConfig>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = matched_route(&request);
+    let method = request.method().clone();
+
+    let start = Instant::now();
+    let upstream_calls = UpstreamCalls::default();
+    let response = UPSTREAM_CALLS
+        .scope(upstream_calls.clone(), next.run(request))
+        .await;
+    let latency = start.elapsed();
+    let request_id = current_request_id().unwrap_or_default();
+
+    tracing::info!(
+        request_id = %request_id,
+        method = %method,
+        route = %route,
+        status = response.status().as_u16(),
+        latency_ms = latency.as_millis() as u64,
+        "request completed"
+    );
+
+    let threshold = live_config.get().slow_request_threshold;
+    if !threshold.is_zero() && latency > threshold {
+        metrics::counter!("http_slow_requests_total", "route" => route.clone()).increment(1);
+        tracing::warn!(
+            request_id = %request_id,
+            method = %method,
+            route = %route,
+            status = response.status().as_u16(),
+            latency_ms = latency.as_millis() as u64,
+            threshold_ms = threshold.as_millis() as u64,
+            upstream_calls = %upstream_calls.summary(),
+            "slow request"
+        );
+    }
+    response
+}
+
+/// Largest body buffered for logging; bigger or streamed bodies pass through
+const MAX_LOGGED_BODY_BYTES: u64 = 64 * 1024;
+
+/// Characters of a body written to the log
+const LOGGED_BODY_CHARS: usize = 1024;
+
+/// Log request and response bodies at debug level (`LOG_BODIES`).
+///
+/// Only bodies of known length up to [`MAX_LOGGED_BODY_BYTES`] are buffered,
+/// so streamed responses are never held back. JSON bodies are redacted with
+/// [`crate::logging::redact_json`]; other bodies are logged by size only.
+pub async fn log_bodies(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let redact_addresses = state.config.log_bodies_redact_addresses;
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    let (parts, body) = request.into_parts();
+    let body = buffer_and_log(&parts.headers, body, "request body", redact_addresses).await;
+    let response = match body {
+        Ok(body) => next.run(Request::from_parts(parts, body)).await,
+        Err(e) => {
+            return AppError::validation("body", format!("unreadable body: {}", e)).into_response()
+        }
+    };
+
+    let (parts, body) = response.into_parts();
+    let body = match buffer_and_log(&parts.headers, body, "response body", redact_addresses).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::warn!(method = %method, path = %path, "failed to read response body: {}", e);
+            return AppError::Internal("failed to read response body".to_string()).into_response();
+        }
+    };
+    Response::from_parts(parts, body)
+}
+
+/// Log `body` if it is small and complete, returning an equivalent body
+async fn buffer_and_log(
+    headers: &HeaderMap,
+    body: Body,
+    message: &'static str,
+    redact_addresses: bool,
+) -> Result<Body, axum::Error> {
+    let size = body.size_hint().exact();
+    if !size.is_some_and(|size| size > 0 && size <= MAX_LOGGED_BODY_BYTES) {
+        return Ok(body);
+    }
+    let bytes = axum::body::to_bytes(body, MAX_LOGGED_BODY_BYTES as usize).await?;
+    tracing::debug!(
+        body = %loggable_body(headers, &bytes, redact_addresses),
+        "{}",
+        message
+    );
+    Ok(Body::from(bytes))
+}
+
+/// Redacted, truncated text of a buffered body
+fn loggable_body(headers: &HeaderMap, bytes: &Bytes, redact_addresses: bool) -> String {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("unknown");
+    let json = content_type
+        .starts_with("application/json")
+        .then(|| serde_json::from_slice::<serde_json::Value>(bytes).ok())
+        .flatten();
+    let Some(mut json) = json else {
+        return format!("<{} bytes of {}>", bytes.len(), content_type);
+    };
+    crate::logging::redact_json(&mut json, redact_addresses);
+    let text = json.to_string();
+    match text.char_indices().nth(LOGGED_BODY_CHARS) {
+        Some((end, _)) => format!("{}... ({} bytes)", &text[..end], bytes.len()),
+        None => text,
+    }
+}
+
+/// How a client wants timestamps written in JSON responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampFormat {
+    /// RFC 3339 strings, as the types serialize them
+    #[default]
+    Rfc3339,
+    /// Integer seconds since the Unix epoch
+    Unix,
+}
+
+impl TimestampFormat {
+    /// The format requested by `X-Timestamp-Format`, RFC 3339 if absent
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, AppError> {
+        let Some(value) = headers.get(&TIMESTAMP_FORMAT_HEADER) else {
+            return Ok(Self::Rfc3339);
+        };
+        match value.to_str().map(str::trim) {
+            Ok(v) if v.eq_ignore_ascii_case("rfc3339") => Ok(Self::Rfc3339),
+            Ok(v) if v.eq_ignore_ascii_case("unix") => Ok(Self::Unix),
+            _ => Err(AppError::validation(
+                "X-Timestamp-Format",
+                "X-Timestamp-Format must be rfc3339 or unix",
+            )),
+        }
+    }
+}
+
+/// Write the `*_at` timestamps of JSON responses in the format asked for
+/// by `X-Timestamp-Format` (`rfc3339`, the default, or `unix`).
+///
+/// Handlers always serialize RFC 3339; for `unix` this layer rewrites every
+/// `*_at` field holding an RFC 3339 string, at any depth, as epoch seconds.
+/// Like [`log_bodies`], only bodies of known length are buffered.
+pub async fn timestamp_format(request: Request, next: Next) -> Response {
+    let format = match TimestampFormat::from_headers(request.headers()) {
+        Ok(format) => format,
+        Err(e) => return e.into_response(),
+    };
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("x-timestamp-format"));
+    if format == TimestampFormat::Rfc3339 {
+        return response;
+    }
+    rewrite_json(response, timestamps_to_unix).await
+}
+
+/// Apply `rewrite` to a JSON response body. Like [`log_bodies`], only
+/// bodies of known length are buffered; others, and non-JSON bodies, pass
+/// through unchanged.
+async fn rewrite_json(
+    response: Response,
+    rewrite: impl FnOnce(&mut serde_json::Value),
+) -> Response {
+    if !is_json(response.headers()) {
+        return response;
+    }
+    let size = response.body().size_hint().exact();
+    if !size.is_some_and(|size| size > 0 && size <= MAX_SHAPED_BODY_BYTES) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_SHAPED_BODY_BYTES as usize).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!("failed to read response body: {}", e);
+            return AppError::Internal("failed to read response body".to_string()).into_response();
+        }
+    };
+    let Ok(mut json) = serde_json::from_slice::<serde_json::Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    rewrite(&mut json);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(json.to_string()))
+}
+
+/// Whether `headers` declare a JSON body
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"))
+}
+
+/// Replace RFC 3339 strings under `*_at` keys with epoch seconds
+fn timestamps_to_unix(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                let parsed = match value {
+                    serde_json::Value::String(s) if key.ends_with("_at") => {
+                        chrono::DateTime::parse_from_rfc3339(s).ok()
+                    }
+                    _ => None,
+                };
+                match parsed {
+                    Some(at) => *value = at.timestamp().into(),
+                    None => timestamps_to_unix(value),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(timestamps_to_unix),
+        _ => {}
+    }
+}
+
+/// Resolve the client IP used as the rate-limit key.
+///
+/// `X-Forwarded-For` is only honored when a trusted proxy sits in front of
+/// the server; its last entry is the address the proxy itself observed.
+/// Without a connection address (e.g. in-process tests) all clients share
+/// the unspecified address.
+pub fn client_ip(request: &Request, trust_proxy: bool) -> IpAddr {
+    if trust_proxy {
+        let forwarded = request
+            .headers()
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok());
+        if let Some(ip) = forwarded {
+            return ip;
+        }
+    }
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+}
+
+/// Rate-limit API requests per client IP, with separate buckets for reads,
+/// session mutations and quotes.
+pub async fn rate_limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let path = request.uri().path();
+    let class = if path.ends_with("/quote") || path.ends_with("/quote/compare") {
+        RouteClass::Quote
+    } else if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        RouteClass::Read
+    } else {
+        RouteClass::Write
+    };
+    let client = client_ip(&request, state.config.trust_proxy);
+
+    match state.rate_limiter.check(client, class).await {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            metrics::counter!("rate_limited_requests_total", "class" => class.as_str())
+                .increment(1);
+            let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            AppError::TooManyRequests {
+                message: format!("Rate limit exceeded; retry in {}s", secs),
+                retry_after,
+            }
+            .into_response()
+        }
+    }
+}
+
+/// Header carrying the shared-secret API key
+pub static API_KEY_HEADER: HeaderName = HeaderName::from_static("x-api-key");
+
+/// Check the API key in `headers` grants at least `required`.
+///
+/// Error messages never include the provided key.
+pub fn authorize(state: &AppState, headers: &HeaderMap, required: ApiRole) -> Result<(), AppError> {
+    let provided = headers
+        .get(&API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| AppError::Unauthorized("Missing X-Api-Key header".to_string()))?;
+
+    match authenticate(&state.config.api_keys, provided) {
+        None => Err(AppError::Unauthorized("Invalid API key".to_string())),
+        Some(role) if role < required => Err(AppError::Forbidden(
+            "API key is not allowed to access this route".to_string(),
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Require a client (or admin) API key for mutating requests.
+///
+/// Safe methods stay open, and so does everything when no keys are configured.
+pub async fn require_client_key(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let safe = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    if safe || state.config.api_keys.is_empty() {
+        return next.run(request).await;
+    }
+    match authorize(&state, request.headers(), ApiRole::Client) {
+        Ok(()) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Refuse writes while maintenance mode is on
+pub fn ensure_writable(state: &AppState) -> Result<(), AppError> {
+    if state.maintenance.load(Ordering::Relaxed) {
+        return Err(AppError::Maintenance(
+            "Service is in maintenance mode; writes are paused, reads still work".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Answer mutating requests with 503 while maintenance mode is on
+pub async fn maintenance(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let safe = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    if safe {
+        return next.run(request).await;
+    }
+    match ensure_writable(&state) {
+        Ok(()) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Require an admin API key for every request
+pub async fn require_admin_key(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match authorize(&state, request.headers(), ApiRole::Admin) {
+        Ok(()) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
@@ -1,24 +1,158 @@
 //! API handlers module
 
-use axum::Json;
-use serde::Serialize;
+use axum::{extract::State, http::StatusCode, Json};
+use serde::{Serialize, Serializer};
 
+use crate::config::Config;
+use crate::models::address::{Address, MaskedAddresses};
+use crate::services::health::ReadinessReport;
+use crate::AppState;
+pub use settleone_types::api::HealthResponse;
+
+pub mod admin;
+#[cfg(feature = "ens")]
 pub mod ens;
 pub mod error;
+pub mod extract;
+pub mod metrics;
+pub mod middleware;
+pub mod openapi;
+pub mod pagination;
+#[cfg(feature = "lifi")]
+pub mod prices;
+#[cfg(feature = "lifi")]
 pub mod quote;
 pub mod session;
+#[cfg(feature = "settlement")]
+pub mod settlement;
+pub mod template;
+pub mod users;
+
+/// API version a request was routed through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiVersion {
+    /// Unversioned `/api/...` routes, kept for existing clients
+    Legacy,
+    /// `/api/v1/...` routes
+    V1,
+}
+
+impl ApiVersion {
+    /// Whether ENS/quote failures use the error envelope instead of a 200
+    #[cfg(any(feature = "ens", feature = "lifi"))]
+    pub fn strict_errors(self, config: &Config) -> bool {
+        self == ApiVersion::V1 || config.strict_errors
+    }
+}
+
+/// How session addresses leave the API. REST responses carrying session
+/// data are [`Shown`] with it; GraphQL and gRPC show each address with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressDisplay {
+    /// EIP-55 checksummed, in full
+    Full,
+    /// [`Address::masked`], as in `0x5aAe...eAed` (`MASK_ADDRESSES`)
+    Masked,
+}
+
+impl AddressDisplay {
+    /// The display `config` asks for
+    pub fn of(config: &Config) -> Self {
+        if config.mask_addresses {
+            AddressDisplay::Masked
+        } else {
+            AddressDisplay::Full
+        }
+    }
+
+    /// `address` as responses show it
+    pub fn show(self, address: &Address) -> String {
+        match self {
+            AddressDisplay::Full => address.to_string(),
+            AddressDisplay::Masked => address.masked(),
+        }
+    }
+
+    /// `value` as a response body showing its addresses this way
+    pub fn body<T>(self, value: T) -> Shown<T> {
+        Shown {
+            display: self,
+            value,
+        }
+    }
+}
 
-/// Health check response
-#[derive(Serialize)]
-pub struct HealthResponse {
-    pub status: &'static str,
-    pub version: &'static str,
+/// `value` as a JSON response body, addresses shown as `config` asks
+pub fn shown<T>(config: &Config, value: T) -> Json<Shown<T>> {
+    Json(AddressDisplay::of(config).body(value))
+}
+
+/// A response body that serializes every address in it, at any depth, as
+/// its [`AddressDisplay`] says. Only the response is shaped: the session
+/// data it was built from keeps full addresses.
+#[derive(Debug)]
+pub struct Shown<T> {
+    display: AddressDisplay,
+    value: T,
+}
+
+impl<T> Shown<T> {
+    /// The body as handlers built it, addresses in full
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T: Serialize> Serialize for Shown<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.display {
+            AddressDisplay::Full => self.value.serialize(serializer),
+            AddressDisplay::Masked => MaskedAddresses(&self.value).serialize(serializer),
+        }
+    }
+}
+
+/// Handler for the routes of an integration compiled out of this build
+#[cfg(not(all(feature = "ens", feature = "lifi", feature = "settlement")))]
+pub fn not_compiled_in(feature: &'static str) -> axum::routing::MethodRouter<AppState> {
+    axum::routing::any(move || async move {
+        error::AppError::NotImplemented(format!(
+            "The {} integration is not enabled in this build",
+            feature
+        ))
+    })
 }
 
 /// Health check endpoint
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    responses((status = 200, description = "Process is up", body = HealthResponse))
+)]
 pub async fn health_check() -> Json<HealthResponse> {
     Json(HealthResponse {
-        status: "ok",
-        version: env!("CARGO_PKG_VERSION"),
+        status: "ok".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
     })
 }
+
+/// Readiness probe: 200 when every dependency is reachable, 503 otherwise
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "All dependencies reachable", body = ReadinessReport),
+        (status = 503, description = "One or more dependencies failing", body = ReadinessReport)
+    )
+)]
+pub async fn readiness_check(State(state): State<AppState>) -> (StatusCode, Json<ReadinessReport>) {
+    let report = state.readiness.check().await;
+    let status = if report.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
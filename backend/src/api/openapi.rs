@@ -0,0 +1,240 @@
+//! OpenAPI specification generated from handler annotations
+//!
+//! Handlers are documented under `/api/v1`; the deprecated unversioned
+//! `/api/...` tree is mirrored into the spec by [`LegacyPaths`]. Each
+//! optional integration documents its routes in its own `OpenApi` struct,
+//! merged by [`spec`] when the feature is enabled.
+
+use axum::Json;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::openapi::{path::Operation, Deprecated, OpenApi as OpenApiSpec};
+use utoipa::{Modify, OpenApi};
+
+use crate::api::error::{ErrorResponse, FieldError};
+use crate::api::{admin, session, template, users, HealthResponse};
+use crate::models::session::{
+    Payment, PaymentStatus, PinnedRecipient, RecipientShare, Session, SessionStatus,
+    SessionTemplate, SettlementMode, TemplateEntry, Transfer,
+};
+use crate::services::health::{DependencyStatus, ReadinessReport};
+use crate::services::webhook::Webhook;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "SettleOne API", description = "Session-based USDC payments"),
+    paths(
+        crate::api::health_check,
+        crate::api::readiness_check,
+        crate::api::metrics::metrics,
+        openapi_json,
+        session::create_session,
+        session::get_session,
+        session::add_payment,
+        session::list_payments,
+        session::list_events,
+        session::get_receipt,
+        session::remove_payment,
+        session::cancel_payment,
+        session::reorder_payments,
+        session::cancel_session,
+        session::finalize_session,
+        session::finalize_sessions,
+        session::get_session_by_tx,
+        users::recipient_history,
+        template::create_template,
+        template::list_templates,
+        template::get_template,
+        template::delete_template,
+        template::create_from_template,
+        admin::stats,
+        admin::health,
+        admin::health_history,
+        admin::internals,
+        admin::jobs,
+        admin::routes,
+        admin::reload_config,
+        admin::set_maintenance,
+        admin::register_webhook,
+        admin::list_webhooks,
+        admin::remove_webhook,
+        admin::redeliver_events,
+        admin::snapshot,
+    ),
+    components(schemas(
+        ErrorResponse,
+        FieldError,
+        HealthResponse,
+        ReadinessReport,
+        DependencyStatus,
+        Session,
+        SessionStatus,
+        Payment,
+        PaymentStatus,
+        PinnedRecipient,
+        SettlementMode,
+        Transfer,
+        RecipientShare,
+        SessionTemplate,
+        TemplateEntry,
+        session::ListPaymentsResponse,
+        admin::AdminStats,
+        admin::AdminHealth,
+        admin::AdminInternals,
+        admin::RouteInfo,
+        admin::ConfigReloadResponse,
+        admin::MaintenanceMode,
+        admin::RegisterWebhookRequest,
+        admin::WebhookListResponse,
+        Webhook,
+        admin::RedeliverRequest,
+        admin::RedeliverResponse,
+        admin::SnapshotFormat,
+    )),
+    modifiers(&ApiKeyAuth),
+    tags(
+        (name = "health", description = "Liveness, readiness and metrics"),
+        (name = "session", description = "Payment sessions"),
+        (name = "template", description = "Session templates"),
+        (name = "admin", description = "Operational endpoints (admin API key)"),
+    )
+)]
+pub struct ApiDoc;
+
+#[cfg(feature = "ens")]
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        crate::api::ens::resolve_ens,
+        crate::api::ens::resolve_ens_batch,
+        crate::api::ens::lookup_address,
+        crate::api::ens::namehash_name,
+        crate::api::ens::content_hash
+    ),
+    tags((name = "ens", description = "ENS resolution"))
+)]
+struct EnsDoc;
+
+#[cfg(feature = "lifi")]
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        crate::api::quote::get_quote,
+        crate::api::quote::compare_quotes,
+        crate::api::prices::get_price
+    ),
+    tags((name = "quote", description = "LI.FI cross-chain quotes and token prices"))
+)]
+struct QuoteDoc;
+
+#[cfg(feature = "settlement")]
+#[derive(OpenApi)]
+#[openapi(paths(
+    crate::api::settlement::settlement_status,
+    crate::api::settlement::settlement_preview
+))]
+struct SettlementDoc;
+
+/// The full specification for the integrations compiled into this build
+pub fn spec() -> OpenApiSpec {
+    #[allow(unused_mut)]
+    let mut spec = ApiDoc::openapi();
+    #[cfg(feature = "ens")]
+    spec.merge(EnsDoc::openapi());
+    #[cfg(feature = "lifi")]
+    spec.merge(QuoteDoc::openapi());
+    #[cfg(feature = "settlement")]
+    spec.merge(SettlementDoc::openapi());
+    LegacyPaths.modify(&mut spec);
+    spec
+}
+
+/// Register the `X-Api-Key` security scheme
+struct ApiKeyAuth;
+
+impl Modify for ApiKeyAuth {
+    fn modify(&self, openapi: &mut OpenApiSpec) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Api-Key"))),
+        );
+    }
+}
+
+/// Mirror every `/api/v1/...` path under `/api/...`, marked deprecated
+struct LegacyPaths;
+
+impl Modify for LegacyPaths {
+    fn modify(&self, openapi: &mut OpenApiSpec) {
+        let legacy: Vec<(String, _)> = openapi
+            .paths
+            .paths
+            .iter()
+            .filter_map(|(path, item)| {
+                let rest = path.strip_prefix("/api/v1/")?;
+                let mut item = item.clone();
+                for operation in operations_mut(&mut item) {
+                    operation.deprecated = Some(Deprecated::True);
+                    operation.operation_id = operation
+                        .operation_id
+                        .as_ref()
+                        .map(|id| format!("{}_legacy", id));
+                }
+                Some((format!("/api/{}", rest), item))
+            })
+            .collect();
+
+        openapi.paths.paths.extend(legacy);
+    }
+}
+
+/// Every documented operation as `(method, path, operation)`, sorted by path
+pub fn operations(spec: &OpenApiSpec) -> Vec<(&'static str, &str, &Operation)> {
+    let mut operations: Vec<_> = spec
+        .paths
+        .paths
+        .iter()
+        .flat_map(|(path, item)| {
+            [
+                ("GET", &item.get),
+                ("PUT", &item.put),
+                ("POST", &item.post),
+                ("DELETE", &item.delete),
+                ("OPTIONS", &item.options),
+                ("HEAD", &item.head),
+                ("PATCH", &item.patch),
+                ("TRACE", &item.trace),
+            ]
+            .into_iter()
+            .filter_map(move |(method, op)| Some((method, path.as_str(), op.as_ref()?)))
+        })
+        .collect();
+    operations.sort_by(|a, b| (a.1, a.0).cmp(&(b.1, b.0)));
+    operations
+}
+
+fn operations_mut(item: &mut utoipa::openapi::PathItem) -> impl Iterator<Item = &mut Operation> {
+    [
+        item.get.as_mut(),
+        item.put.as_mut(),
+        item.post.as_mut(),
+        item.delete.as_mut(),
+        item.options.as_mut(),
+        item.head.as_mut(),
+        item.patch.as_mut(),
+        item.trace.as_mut(),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// Serve the OpenAPI specification
+#[utoipa::path(
+    get,
+    path = "/api/openapi.json",
+    tag = "health",
+    responses((status = 200, description = "OpenAPI 3.1 specification"))
+)]
+pub async fn openapi_json() -> Json<OpenApiSpec> {
+    Json(spec())
+}
@@ -0,0 +1,252 @@
+//! Cursor pagination shared by listing endpoints
+//!
+//! Listings are ordered by `(created_at, id)`, oldest first unless they
+//! use [`paginate_newest_first`], and returned in a [`Paginated`] envelope
+//! (see [`paginate`]). Listings with an order of their own, like a
+//! session's payments, key by `(index, id)` instead. `next_cursor` is an
+//! opaque token (base64 of the last item's key); clients pass it back as
+//! `?cursor=` for the next page.
+
+use axum::async_trait;
+use axum::extract::{FromRequestParts, Query};
+use axum::http::request::Parts;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::api::error::AppError;
+pub use settleone_types::api::Paginated;
+
+/// Page size when `limit` is not given
+pub const DEFAULT_PAGE_LIMIT: usize = 50;
+
+/// Largest accepted `limit`
+pub const MAX_PAGE_LIMIT: usize = 100;
+
+/// Cut the page described by `page` out of `items`, ordered by `key`
+pub fn paginate<T>(items: Vec<T>, page: &PageParams, key: impl Fn(&T) -> Cursor) -> Paginated<T> {
+    cut_page(items, page, key, false)
+}
+
+/// [`paginate`] in descending `key` order
+pub fn paginate_newest_first<T>(
+    items: Vec<T>,
+    page: &PageParams,
+    key: impl Fn(&T) -> Cursor,
+) -> Paginated<T> {
+    cut_page(items, page, key, true)
+}
+
+fn cut_page<T>(
+    mut items: Vec<T>,
+    page: &PageParams,
+    key: impl Fn(&T) -> Cursor,
+    descending: bool,
+) -> Paginated<T> {
+    let total = items.len();
+    items.sort_by_cached_key(|item| key(item));
+    if descending {
+        items.reverse();
+    }
+    let start = match &page.cursor {
+        Some(cursor) if descending => items.partition_point(|item| key(item) >= *cursor),
+        Some(cursor) => items.partition_point(|item| key(item) <= *cursor),
+        None => 0,
+    };
+    let mut items: Vec<T> = items.into_iter().skip(start).collect();
+    let next_cursor = (items.len() > page.limit).then(|| {
+        items.truncate(page.limit);
+        key(&items[page.limit - 1]).encode()
+    });
+    Paginated {
+        items,
+        next_cursor,
+        total,
+    }
+}
+
+/// Position in a listing: the key of the last item already returned
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cursor {
+    pub position: Position,
+    pub id: String,
+}
+
+/// What a listing is ordered by, ahead of the id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Position {
+    CreatedAt(DateTime<Utc>),
+    /// An explicit order, such as a payment's `index`
+    Index(u32),
+}
+
+impl Cursor {
+    pub fn new(created_at: DateTime<Utc>, id: impl Into<String>) -> Self {
+        Self {
+            position: Position::CreatedAt(created_at),
+            id: id.into(),
+        }
+    }
+
+    /// Key of an item at `index` of an explicitly ordered listing
+    pub fn at_index(index: u32, id: impl Into<String>) -> Self {
+        Self {
+            position: Position::Index(index),
+            id: id.into(),
+        }
+    }
+
+    /// Whether the cursor was made by [`Cursor::at_index`]
+    pub fn is_index(&self) -> bool {
+        matches!(self.position, Position::Index(_))
+    }
+
+    /// Opaque token handed to clients
+    pub fn encode(&self) -> String {
+        let position = match self.position {
+            Position::CreatedAt(created_at) => {
+                created_at.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+            }
+            Position::Index(index) => format!("#{}", index),
+        };
+        URL_SAFE_NO_PAD.encode(format!("{}|{}", position, self.id))
+    }
+
+    /// Parse a token made by [`Cursor::encode`]; `None` if it was altered
+    pub fn decode(token: &str) -> Option<Self> {
+        let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(token).ok()?).ok()?;
+        let (position, id) = raw.split_once('|')?;
+        if id.is_empty() {
+            return None;
+        }
+        if let Some(index) = position.strip_prefix('#') {
+            return Some(Self::at_index(index.parse().ok()?, id));
+        }
+        let created_at = DateTime::parse_from_rfc3339(position).ok()?;
+        Some(Self::new(created_at.with_timezone(&Utc), id))
+    }
+}
+
+/// `?limit=&cursor=` query parameters of listing endpoints
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    /// Items per page, 1 to 100 (default 50)
+    #[param(value_type = Option<usize>)]
+    pub limit: Option<String>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+/// Validated [`PageQuery`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageParams {
+    pub limit: usize,
+    pub cursor: Option<Cursor>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for PageParams {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, AppError> {
+        let Query(query) = Query::<PageQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| AppError::validation("query", e.body_text()))?;
+
+        let limit = match query.limit {
+            Some(raw) => raw
+                .trim()
+                .parse()
+                .ok()
+                .filter(|limit| (1..=MAX_PAGE_LIMIT).contains(limit))
+                .ok_or_else(|| {
+                    AppError::validation(
+                        "limit",
+                        format!("limit must be between 1 and {}", MAX_PAGE_LIMIT),
+                    )
+                })?,
+            None => DEFAULT_PAGE_LIMIT,
+        };
+        let cursor = query
+            .cursor
+            .map(|token| {
+                Cursor::decode(&token)
+                    .ok_or_else(|| AppError::validation("cursor", "cursor is invalid"))
+            })
+            .transpose()?;
+
+        Ok(Self { limit, cursor })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(secs, 123_456_789).unwrap()
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor::new(at(1_700_000_000), "3f0c|odd-id");
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+    }
+
+    #[test]
+    fn test_index_cursor_round_trip() {
+        let cursor = Cursor::at_index(42, "p|7");
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+        assert!(Cursor::decode(&URL_SAFE_NO_PAD.encode("#-1|p1")).is_none());
+        assert!(Cursor::decode(&URL_SAFE_NO_PAD.encode("#one|p1")).is_none());
+    }
+
+    #[test]
+    fn test_tampered_cursor_is_rejected() {
+        let token = Cursor::new(at(1_700_000_000), "p1").encode();
+        assert!(Cursor::decode(&token[1..]).is_none());
+        assert!(Cursor::decode("not base64!").is_none());
+        assert!(Cursor::decode(&URL_SAFE_NO_PAD.encode("yesterday|p1")).is_none());
+        assert!(Cursor::decode(&URL_SAFE_NO_PAD.encode("2026-01-01T00:00:00Z|")).is_none());
+    }
+
+    #[test]
+    fn test_pages_follow_cursor() {
+        let items: Vec<(i64, &str)> = vec![(3, "c"), (1, "a"), (2, "b"), (2, "a")];
+        let key = |item: &(i64, &str)| Cursor::new(at(item.0), item.1);
+        let mut page = PageParams {
+            limit: 3,
+            cursor: None,
+        };
+
+        let first = paginate(items.clone(), &page, key);
+        assert_eq!(first.items, [(1, "a"), (2, "a"), (2, "b")]);
+        assert_eq!(first.total, 4);
+
+        page.cursor = Cursor::decode(&first.next_cursor.unwrap());
+        let last = paginate(items, &page, key);
+        assert_eq!(last.items, [(3, "c")]);
+        assert!(last.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_newest_first_pages_follow_cursor() {
+        let items: Vec<(i64, &str)> = vec![(3, "c"), (1, "a"), (2, "b"), (2, "a")];
+        let key = |item: &(i64, &str)| Cursor::new(at(item.0), item.1);
+        let mut page = PageParams {
+            limit: 2,
+            cursor: None,
+        };
+
+        let first = paginate_newest_first(items.clone(), &page, key);
+        assert_eq!(first.items, [(3, "c"), (2, "b")]);
+        page.cursor = Cursor::decode(&first.next_cursor.unwrap());
+        let last = paginate_newest_first(items, &page, key);
+        assert_eq!(last.items, [(2, "a"), (1, "a")]);
+        assert!(last.next_cursor.is_none());
+        assert_eq!(last.total, 4);
+    }
+}
@@ -0,0 +1,43 @@
+//! Token price API handlers
+
+use axum::extract::{Query, State};
+use axum::Json;
+
+use crate::api::error::{AppError, ErrorResponse};
+use crate::services::prices::PriceError;
+use crate::AppState;
+pub use settleone_types::api::{PriceRequest, PriceResponse};
+
+/// Approximate USD price of a token, cached and shared with every other
+/// feature that values tokens
+#[utoipa::path(
+    get,
+    path = "/api/v1/price",
+    tag = "quote",
+    params(PriceRequest),
+    responses(
+        (status = 200, description = "Price", body = PriceResponse),
+        (status = 404, description = "No source can price the token", body = ErrorResponse),
+        (status = 502, description = "Every price source failed", body = ErrorResponse)
+    )
+)]
+pub async fn get_price(
+    State(state): State<AppState>,
+    Query(params): Query<PriceRequest>,
+) -> Result<Json<PriceResponse>, AppError> {
+    let quote = state
+        .price_service
+        .get(params.chain_id, &params.token)
+        .await
+        .map_err(|e| match e {
+            PriceError::NotFound(_) => AppError::NotFound(e.to_string()),
+            PriceError::Unavailable(_) => AppError::Upstream(e.to_string()),
+        })?;
+    Ok(Json(PriceResponse {
+        chain_id: params.chain_id,
+        token: params.token.trim().to_lowercase(),
+        usd: quote.usd,
+        timestamp: quote.timestamp,
+        source: quote.source.to_string(),
+    }))
+}
@@ -1,52 +1,314 @@
 //! LI.FI quote API handlers
 
-use axum::{extract::Query, Json};
-use serde::{Deserialize, Serialize};
+use axum::{
+    extract::{Query, State},
+    Extension, Json,
+};
+use futures::stream::{self, StreamExt};
 
-use crate::services::lifi::LifiService;
+use crate::api::error::{AppError, ErrorResponse};
+use crate::api::extract::ValidJson;
+use crate::api::ApiVersion;
+use crate::services::lifi::{LifiError, QuoteResult};
+use crate::services::quote_cache::{QuoteKey, TokenFacts};
+use crate::AppState;
+pub use settleone_types::api::{
+    QuoteCompareRequest, QuoteCompareResponse, QuoteRequest, QuoteResponse,
+};
+use settleone_types::units::format_units_rounded;
 
-/// Quote request parameters
-#[derive(Deserialize)]
-pub struct QuoteRequest {
-    pub from_chain: String,
-    pub to_chain: String,
-    pub from_token: String,
-    pub to_token: String,
-    pub from_amount: String,
-    pub from_address: Option<String>,
+/// Fractional digits of formatted quote amounts, the precision of USDC
+pub const DEFAULT_QUOTE_DISPLAY_DECIMALS: u8 = 6;
+
+/// USD value above which a quoted amount is taken for a units mistake
+pub const DEFAULT_QUOTE_SANITY_CEILING_USD: u64 = 10_000_000;
+
+/// Slippage of a quote that does not ask for one (0.5%)
+pub const DEFAULT_SLIPPAGE: f64 = 0.005;
+
+/// What happens to a quote whose amount is above the sanity ceiling
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SanityMode {
+    /// Quote it, with a `warning`
+    #[default]
+    Warn,
+    /// Refuse it with a 400 (an `error` in comparisons)
+    Reject,
 }
 
-/// Quote response
-#[derive(Serialize)]
-pub struct QuoteResponse {
-    pub from_amount: String,
-    pub to_amount: String,
-    pub estimated_gas: String,
-    pub estimated_time: u64, // seconds
-    pub route: Option<serde_json::Value>,
-    pub error: Option<String>,
+impl SanityMode {
+    /// Parse a `QUOTE_SANITY_MODE` value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "warn" => Some(SanityMode::Warn),
+            "reject" => Some(SanityMode::Reject),
+            _ => None,
+        }
+    }
+}
+
+/// Most amounts a comparison may ask for
+pub const MAX_COMPARE_AMOUNTS: usize = 10;
+
+/// Upstream quotes a comparison fetches at once
+const COMPARE_CONCURRENCY: usize = 4;
+
+/// Map a LI.FI service error onto the API error envelope
+fn lifi_error(e: LifiError) -> AppError {
+    match e {
+        LifiError::NoRoute => AppError::NotFound(e.to_string()),
+        LifiError::ConnectError(_) => AppError::UpstreamUnreachable(e.to_string()),
+        LifiError::TimeoutError(_) => AppError::UpstreamTimeout(e.to_string()),
+        LifiError::ApiError(_)
+        | LifiError::HttpStatusError(_)
+        | LifiError::Unavailable(_)
+        | LifiError::ParseError(_) => AppError::Upstream(e.to_string()),
+        LifiError::InvalidChain(_) => AppError::validation("from_chain", e.to_string()),
+        LifiError::RateLimited(_) => AppError::RateLimited(e.to_string()),
+    }
 }
 
 /// Get cross-chain quote from LI.FI
-pub async fn get_quote(Query(params): Query<QuoteRequest>) -> Json<QuoteResponse> {
-    let lifi_service = LifiService::new();
-
-    match lifi_service.get_quote(&params).await {
-        Ok(quote) => Json(QuoteResponse {
-            from_amount: params.from_amount,
-            to_amount: quote.to_amount,
-            estimated_gas: quote.estimated_gas,
-            estimated_time: quote.estimated_time,
-            route: quote.route,
-            error: None,
-        }),
-        Err(e) => Json(QuoteResponse {
-            from_amount: params.from_amount,
-            to_amount: "0".to_string(),
-            estimated_gas: "0".to_string(),
-            estimated_time: 0,
-            route: None,
-            error: Some(e.to_string()),
-        }),
+#[utoipa::path(
+    get,
+    path = "/api/v1/quote",
+    tag = "quote",
+    params(QuoteRequest),
+    responses(
+        (status = 200, description = "Quote", body = QuoteResponse),
+        (status = 400, description = "from_amount is above the sanity ceiling (QUOTE_SANITY_MODE=reject; when LI.FI fails, judged by the token's last quote, and not at all for a token never quoted), or slippage is not a fraction from 0 up to 1", body = ErrorResponse),
+        (status = 404, description = "No route available", body = ErrorResponse),
+        (status = 429, description = "Outbound LI.FI budget exhausted", body = ErrorResponse),
+        (status = 502, description = "LI.FI unreachable or failing", body = ErrorResponse),
+        (status = 504, description = "LI.FI timed out", body = ErrorResponse)
+    )
+)]
+pub async fn get_quote(
+    State(state): State<AppState>,
+    Extension(version): Extension<ApiVersion>,
+    Query(mut params): Query<QuoteRequest>,
+) -> Result<Json<QuoteResponse>, AppError> {
+    let (slippage, adjusted) = effective_slippage(&state, params.slippage)?;
+    params.slippage = Some(slippage);
+    let result = cached_quote(&state, &params).await;
+    let token = from_token(&state, &params, &result).await;
+    if state.config.quote_sanity_mode == SanityMode::Reject {
+        if let Some(problem) = implausible_amount(&state, &params.from_amount, token) {
+            return Err(AppError::validation("from_amount", problem));
+        }
+    }
+    let mut response = match result {
+        Err(e) if version.strict_errors(&state.config) => return Err(lifi_error(e)),
+        result => quote_response(&state, params.from_amount, result, token),
+    };
+    response.slippage = Some(slippage);
+    if let Some(adjusted) = adjusted {
+        response.warning = Some(match response.warning {
+            Some(warning) => format!("{}; {}", warning, adjusted),
+            None => adjusted,
+        });
+    }
+    Ok(Json(response))
+}
+
+/// Slippage to quote with: the requested one raised to `MIN_SLIPPAGE`
+/// (with a note of the adjustment), or `DEFAULT_SLIPPAGE` if none was asked for
+fn effective_slippage(
+    state: &AppState,
+    requested: Option<f64>,
+) -> Result<(f64, Option<String>), AppError> {
+    let floor = state.config.min_slippage;
+    match requested {
+        None => Ok((state.config.default_slippage, None)),
+        Some(slippage) if !(0.0..1.0).contains(&slippage) => Err(AppError::validation(
+            "slippage",
+            "slippage must be a fraction from 0 up to 1",
+        )),
+        Some(slippage) if slippage < floor => Ok((
+            floor,
+            Some(format!(
+                "slippage {} is below the minimum of {} and was raised to it",
+                slippage, floor
+            )),
+        )),
+        Some(slippage) => Ok((slippage, None)),
+    }
+}
+
+/// Quote the same transfer at several amounts, e.g. to plot how fees scale.
+///
+/// Amounts are quoted concurrently (a few at a time) through the quote
+/// cache; an amount that cannot be quoted carries its `error` instead of
+/// failing the whole comparison.
+#[utoipa::path(
+    post,
+    path = "/api/v1/quote/compare",
+    tag = "quote",
+    request_body = QuoteCompareRequest,
+    responses(
+        (status = 200, description = "One quote per amount, in request order", body = QuoteCompareResponse),
+        (status = 400, description = "No amounts, or more than 10", body = ErrorResponse),
+        (status = 422, description = "Malformed body or unknown field", body = ErrorResponse)
+    )
+)]
+pub async fn compare_quotes(
+    State(state): State<AppState>,
+    ValidJson(payload): ValidJson<QuoteCompareRequest>,
+) -> Result<Json<QuoteCompareResponse>, AppError> {
+    if payload.amounts.is_empty() || payload.amounts.len() > MAX_COMPARE_AMOUNTS {
+        return Err(AppError::validation(
+            "amounts",
+            format!("amounts must list 1 to {} amounts", MAX_COMPARE_AMOUNTS),
+        ));
+    }
+
+    let requests: Vec<_> = payload
+        .amounts
+        .iter()
+        .map(|amount| QuoteRequest {
+            from_chain: payload.from_chain.clone(),
+            to_chain: payload.to_chain.clone(),
+            from_token: payload.from_token.clone(),
+            to_token: payload.to_token.clone(),
+            from_amount: amount.clone(),
+            from_address: payload.from_address.clone(),
+            slippage: Some(state.config.default_slippage),
+        })
+        .collect();
+    let quotes = stream::iter(requests)
+        .map(|params| {
+            let state = state.clone();
+            async move {
+                let result = cached_quote(&state, &params).await;
+                let token = from_token(&state, &params, &result).await;
+                QuoteResponse {
+                    slippage: params.slippage,
+                    ..quote_response(&state, params.from_amount, result, token)
+                }
+            }
+        })
+        .buffered(COMPARE_CONCURRENCY)
+        .collect()
+        .await;
+    Ok(Json(QuoteCompareResponse { quotes }))
+}
+
+/// Quote from the cache, fetching and caching it on a miss
+async fn cached_quote(state: &AppState, params: &QuoteRequest) -> Result<QuoteResult, LifiError> {
+    let cache_key = QuoteKey::from(params);
+    if let Some(quote) = state.quote_cache.get(&cache_key).await {
+        return Ok(quote);
+    }
+    let result = state.lifi_service.get_quote(params).await;
+    if let Ok(ref quote) = result {
+        let ttl = state.live_config.get().quote_cache_ttl;
+        state
+            .quote_cache
+            .insert(cache_key, quote.clone(), ttl)
+            .await;
+    }
+    result
+}
+
+/// Source token of a quote: as the quote reports it, or as an earlier quote
+/// of the same token did when this one failed
+async fn from_token(
+    state: &AppState,
+    params: &QuoteRequest,
+    result: &Result<QuoteResult, LifiError>,
+) -> Option<TokenFacts> {
+    match result {
+        Ok(quote) => TokenFacts::of(quote),
+        Err(_) => state.quote_cache.token(&QuoteKey::from(params)).await,
+    }
+}
+
+/// Why `from_amount` is likely a units mistake: it is worth more than
+/// `QUOTE_SANITY_CEILING_USD`, valued at LI.FI's USD price of the token
+/// (or one dollar per token when unpriced). `None` when it looks plausible,
+/// the token is unknown or the check is off.
+fn implausible_amount(
+    state: &AppState,
+    from_amount: &str,
+    token: Option<TokenFacts>,
+) -> Option<String> {
+    let ceiling = state.config.quote_sanity_ceiling_usd;
+    let TokenFacts {
+        decimals,
+        price_usd,
+    } = token?;
+    let amount = from_amount.parse().ok()?;
+    let tokens = format_units_rounded(amount, decimals, 2);
+    let value = tokens.parse::<f64>().ok()? * price_usd.unwrap_or(1.0);
+    if ceiling == 0 || value <= ceiling as f64 {
+        return None;
     }
+    Some(format!(
+        "from_amount {} is {} tokens (about ${:.0}), above the ${} sanity ceiling; \
+         amounts are in base units ({} decimals)",
+        from_amount, tokens, value, ceiling, decimals
+    ))
+}
+
+/// Response body for a quote of `from_amount` in `token`; a failure becomes
+/// a zero quote carrying the error. An implausible amount gets a `warning`,
+/// or fails under [`SanityMode::Reject`].
+fn quote_response(
+    state: &AppState,
+    from_amount: String,
+    result: Result<QuoteResult, LifiError>,
+    token: Option<TokenFacts>,
+) -> QuoteResponse {
+    let warning = implausible_amount(state, &from_amount, token);
+    if let (Some(problem), SanityMode::Reject) = (&warning, state.config.quote_sanity_mode) {
+        return failed_quote(from_amount, problem.clone());
+    }
+    let quote = match result {
+        Ok(quote) => quote,
+        Err(e) => {
+            return QuoteResponse {
+                warning,
+                ..failed_quote(from_amount, e.to_string())
+            }
+        }
+    };
+    QuoteResponse {
+        from_amount_formatted: display_amount(state, &from_amount, quote.from_decimals),
+        to_amount_formatted: display_amount(state, &quote.to_amount, quote.to_decimals),
+        from_amount,
+        to_amount: quote.to_amount,
+        estimated_gas: quote.estimated_gas,
+        estimated_time: quote.estimated_time,
+        route: quote.route,
+        error: None,
+        slippage: None,
+        warning,
+    }
+}
+
+/// Zero quote of `from_amount` carrying `error`
+fn failed_quote(from_amount: String, error: String) -> QuoteResponse {
+    QuoteResponse {
+        from_amount,
+        to_amount: "0".to_string(),
+        from_amount_formatted: None,
+        to_amount_formatted: None,
+        estimated_gas: "0".to_string(),
+        estimated_time: 0,
+        route: None,
+        error: Some(error),
+        slippage: None,
+        warning: None,
+    }
+}
+
+/// A base-unit amount in whole tokens, rounded to the configured display
+/// precision; `None` if the decimals are unknown or the amount is malformed
+fn display_amount(state: &AppState, amount: &str, decimals: Option<u8>) -> Option<String> {
+    let amount = amount.parse().ok()?;
+    Some(format_units_rounded(
+        amount,
+        decimals?,
+        state.config.quote_display_decimals,
+    ))
 }
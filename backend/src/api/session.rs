@@ -1,55 +1,114 @@
 //! Session management API handlers
 
+use std::time::Duration;
+
 use axum::{
-    extract::{Path, State},
-    Json,
+    async_trait,
+    extract::{rejection::QueryRejection, FromRequestParts, Path, Query, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
-use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::api::error::AppError;
-use crate::models::session::{Payment, PaymentStatus, Session};
+#[cfg(feature = "ens")]
+use crate::api::ens::ens_error;
+use crate::api::error::{AppError, ErrorResponse};
+use crate::api::extract::{OptionalJson, ValidJson};
+use crate::api::pagination::{
+    paginate, Cursor, PageParams, PageQuery, Paginated, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT,
+};
+use crate::api::{shown, ApiVersion, Shown};
+use crate::models::address::{Address, EnsName};
+use crate::models::amount::Amount;
+use crate::models::session::{
+    DustPolicy, FinalizeGuard, Payment, PaymentStatus, PinnedRecipient, Session, SessionError,
+    SettlementMode, DISPLAY_CURRENCIES, MAX_CANCEL_REASON_LEN, MAX_TOKEN_DECIMALS,
+};
+use crate::services::fx;
+use crate::utils::amounts::{format_units, parse_units};
 use crate::AppState;
+pub use settleone_types::api::{
+    AddPaymentRequest, BulkFinalizeRequest, BulkFinalizeResponse, BulkFinalizeResult,
+    CancelSessionRequest, CreateSessionRequest, CreateSessionResponse, FinalizeRequest,
+    FinalizeResponse, ListPaymentsQuery, ListPaymentsResponse, PaymentStatusQuery,
+    ReorderPaymentsRequest, SessionEventsQuery, SessionEventsResponse, SessionResponse,
+    SettlementReceipt,
+};
 
-/// Create session request
-#[derive(Deserialize)]
-pub struct CreateSessionRequest {
-    pub user_address: String,
-}
-
-/// Create session response
-#[derive(Serialize)]
-pub struct CreateSessionResponse {
-    pub session_id: String,
-    pub status: String,
-}
-
-/// Add payment request
-#[derive(Deserialize)]
-pub struct AddPaymentRequest {
-    pub recipient: String,
-    pub recipient_ens: Option<String>,
-    pub amount: String, // String to handle large numbers
-}
-
-/// Session response
-#[derive(Serialize)]
-pub struct SessionResponse {
-    pub session: Session,
-}
-
-/// Create a new session
+/// Create a new session (201 Created on v1, 200 on the legacy API)
+#[utoipa::path(
+    post,
+    path = "/api/v1/session",
+    tag = "session",
+    request_body = CreateSessionRequest,
+    responses(
+        (status = 201, description = "Session created", body = CreateSessionResponse),
+        (status = 400, description = "Invalid recipient name, token_decimals, settlement_mode or display_currency", body = ErrorResponse),
+        (status = 403, description = "User or pinned recipient address is blocked", body = ErrorResponse),
+        (status = 404, description = "Recipient name not found", body = ErrorResponse),
+        (status = 422, description = "Malformed user_address or recipient_name, or an unknown field", body = ErrorResponse),
+        (status = 502, description = "ENS resolver unreachable or failing", body = ErrorResponse),
+        (status = 504, description = "ENS resolver timed out", body = ErrorResponse),
+        (status = 429, description = "Too many active sessions for user", body = ErrorResponse)
+    )
+)]
 pub async fn create_session(
     State(state): State<AppState>,
-    Json(payload): Json<CreateSessionRequest>,
-) -> Result<Json<CreateSessionResponse>, AppError> {
-    let session_id = Uuid::new_v4().to_string();
+    Extension(version): Extension<ApiVersion>,
+    ValidJson(payload): ValidJson<CreateSessionRequest>,
+) -> Result<(StatusCode, Json<Shown<CreateSessionResponse>>), AppError> {
+    ensure_not_blocked(&state, "User", &payload.user_address)?;
+    let mut session = Session::new(Uuid::new_v4().to_string(), payload.user_address.clone());
+    if let Some(decimals) = payload.token_decimals {
+        if decimals > MAX_TOKEN_DECIMALS {
+            return Err(AppError::validation(
+                "token_decimals",
+                format!(
+                    "token_decimals must be between 0 and {}",
+                    MAX_TOKEN_DECIMALS
+                ),
+            ));
+        }
+        session.token_decimals = decimals;
+    }
+    if payload.settlement_mode == Some(SettlementMode::Treasury) {
+        // Locked in now so a later TREASURY_ADDRESS change cannot redirect funds
+        let treasury = state.config.treasury_address.clone().ok_or_else(|| {
+            AppError::validation(
+                "settlement_mode",
+                "treasury settlement is not configured on this server",
+            )
+        })?;
+        session.settlement_mode = SettlementMode::Treasury;
+        session.treasury_address = Some(treasury);
+    }
+    if let Some(currency) = payload.display_currency {
+        let currency = currency.trim().to_uppercase();
+        if !DISPLAY_CURRENCIES.contains(&currency.as_str()) {
+            return Err(AppError::validation(
+                "display_currency",
+                format!(
+                    "display_currency must be one of {}",
+                    DISPLAY_CURRENCIES.join(", ")
+                ),
+            ));
+        }
+        session.display_currency = Some(currency);
+    }
 
-    // Create session in the store
+    // Resolve the recipient now so a later ENS change cannot redirect funds
+    if let Some(name) = payload.recipient_name {
+        session.pinned_recipient = Some(pin_recipient(&state, &name).await?);
+    }
+
+    // Create session in the store, enforcing the per-user active session cap
+    let max_active = state.config.max_active_sessions_per_user;
     let session = state
         .session_store
-        .create(session_id.clone(), payload.user_address.clone())
-        .await;
+        .try_create(session, max_active, None)
+        .await
+        .map_err(session_error)?;
 
     tracing::info!(
         "Created session {} for user {}",
@@ -57,35 +116,288 @@ pub async fn create_session(
         payload.user_address
     );
 
-    Ok(Json(CreateSessionResponse {
-        session_id: session.id,
-        status: "active".to_string(),
-    }))
+    let status = match version {
+        ApiVersion::V1 => StatusCode::CREATED,
+        ApiVersion::Legacy => StatusCode::OK,
+    };
+
+    Ok((
+        status,
+        shown(
+            &state.config,
+            CreateSessionResponse {
+                session_id: session.id,
+                status: "active".to_string(),
+                pinned_recipient: session.pinned_recipient,
+            },
+        ),
+    ))
+}
+
+/// Resolve `name` to the recipient pinned on a new session
+#[cfg(feature = "ens")]
+async fn pin_recipient(state: &AppState, name: &EnsName) -> Result<PinnedRecipient, AppError> {
+    let resolved = state
+        .ens_service
+        .resolve(name)
+        .await
+        .map_err(|e| ens_error("recipient_name", e))?;
+    ensure_not_blocked(state, "Recipient", &resolved.address)?;
+    Ok(PinnedRecipient {
+        name: name.clone(),
+        address: resolved.address,
+        resolved_at: chrono::Utc::now(),
+    })
+}
+
+/// Pinning a recipient needs ENS, which this build leaves out
+#[cfg(not(feature = "ens"))]
+async fn pin_recipient(_state: &AppState, _name: &EnsName) -> Result<PinnedRecipient, AppError> {
+    Err(AppError::NotImplemented(
+        "recipient_name requires the ens integration, which is not enabled in this build"
+            .to_string(),
+    ))
+}
+
+/// Names re-resolved at once when verifying a session's recipients
+#[cfg(feature = "ens")]
+const VERIFY_ENS_CONCURRENCY: usize = 4;
+
+/// Check every live payment's `recipient_ens` still resolves to its
+/// recipient, bypassing the cache; a 409 lists the names that changed
+#[cfg(feature = "ens")]
+async fn verify_recipient_ens(state: &AppState, session: &Session) -> Result<(), AppError> {
+    use futures::stream::{self, StreamExt, TryStreamExt};
+
+    let mut expected: Vec<(EnsName, Address)> = session
+        .payments
+        .iter()
+        .filter(|p| p.status != PaymentStatus::Cancelled)
+        .filter_map(|p| Some((p.recipient_ens.clone()?, p.recipient.clone())))
+        .collect();
+    expected.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()).then(a.1.cmp(&b.1)));
+    expected.dedup();
+
+    let mismatches: Vec<String> = stream::iter(expected)
+        .map(|(name, recipient)| async move {
+            let now = match state.ens_service.resolve_fresh(&name).await {
+                Ok(resolved) if resolved.address == recipient => return Ok(None),
+                Ok(resolved) => format!("now resolves to {}", resolved.address),
+                Err(crate::services::ens::EnsError::NotFound(_)) => {
+                    "no longer resolves".to_string()
+                }
+                Err(e) => return Err(ens_error("recipient_ens", e)),
+            };
+            Ok(Some(format!("{} (paid to {}) {}", name, recipient, now)))
+        })
+        .buffered(VERIFY_ENS_CONCURRENCY)
+        .try_filter_map(|mismatch| async move { Ok(mismatch) })
+        .try_collect()
+        .await?;
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Conflict(format!(
+            "ENS records changed since the payments were added: {}",
+            mismatches.join("; ")
+        )))
+    }
+}
+
+/// Verifying recipients needs ENS, which this build leaves out
+#[cfg(not(feature = "ens"))]
+async fn verify_recipient_ens(_state: &AppState, _session: &Session) -> Result<(), AppError> {
+    Err(AppError::NotImplemented(
+        "verify_ens requires the ens integration, which is not enabled in this build".to_string(),
+    ))
+}
+
+/// Reject addresses on the `BLOCKED_ADDRESSES` list
+pub fn ensure_not_blocked(state: &AppState, role: &str, address: &Address) -> Result<(), AppError> {
+    if state.config.is_blocked(address) {
+        tracing::warn!(
+            "Rejected blocked {} address {}",
+            role.to_lowercase(),
+            address
+        );
+        metrics::counter!("blocked_addresses_rejected_total").increment(1);
+        return Err(AppError::Forbidden(format!(
+            "{} address {} is blocked",
+            role, address
+        )));
+    }
+    Ok(())
+}
+
+/// Error for a session missing from the store: 410 if it was archived, 404 otherwise
+pub async fn missing_session(state: &AppState, id: &str) -> AppError {
+    if state.session_store.is_archived(id).await {
+        AppError::Gone(format!(
+            "Session {} was settled and has been archived; it is no longer served by this API",
+            id
+        ))
+    } else {
+        AppError::NotFound(format!("Session {} not found", id))
+    }
+}
+
+/// Session reads must be revalidated against the ETag on every poll
+const SESSION_CACHE_CONTROL: &str = "private, max-age=0, must-revalidate";
+
+/// Whether an `If-None-Match` header value matches `etag` (weak comparison)
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match
+        .split(',')
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == opaque(etag))
+}
+
+/// `?payment_status=` of session reads, rejecting unknown statuses with a 400
+pub struct PaymentStatusFilter(Option<PaymentStatus>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for PaymentStatusFilter {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, AppError> {
+        let Query(query) = Query::<PaymentStatusQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| {
+                AppError::validation(
+                    "payment_status",
+                    "payment_status must be pending, confirmed, settled or cancelled",
+                )
+            })?;
+        Ok(Self(query.payment_status))
+    }
+}
+
+impl PaymentStatusFilter {
+    /// Drop the payments not in the requested status
+    fn apply(&self, payments: &mut Vec<Payment>) {
+        if let Some(status) = &self.0 {
+            payments.retain(|p| p.status == *status);
+        }
+    }
 }
 
 /// Get session by ID
+#[utoipa::path(
+    get,
+    path = "/api/v1/session/{id}",
+    tag = "session",
+    params(
+        ("id" = String, Path, description = "Session ID"),
+        PaymentStatusQuery,
+        ("If-None-Match" = Option<String>, Header, description = "ETag from a previous read")
+    ),
+    responses(
+        (status = 200, description = "Session", body = SessionResponse,
+            headers(("ETag" = String, description = "Weak ETag of this session version and the exchange rate of its total_display"))),
+        (status = 304, description = "Session unchanged since the given ETag"),
+        (status = 400, description = "Invalid payment_status", body = ErrorResponse),
+        (status = 404, description = "Session not found", body = ErrorResponse),
+        (status = 410, description = "Session was settled and archived", body = ErrorResponse)
+    )
+)]
 pub async fn get_session(
     State(state): State<AppState>,
     Path(id): Path<String>,
-) -> Result<Json<SessionResponse>, AppError> {
+    filter: PaymentStatusFilter,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
     tracing::info!("Getting session {}", id);
 
-    match state.session_store.get(&id).await {
-        Some(session) => Ok(Json(SessionResponse { session })),
-        None => Err(AppError::NotFound(format!("Session {} not found", id))),
+    let Some(session) = state.session_store.get(&id).await else {
+        return Err(missing_session(&state, &id).await);
+    };
+    Ok(session_response(&state, session, filter, &headers).await)
+}
+
+/// A session read: its body with the display-currency total and only the
+/// payments `filter` selects, or 304 if `headers` name its ETag. The ETag
+/// covers the session version and the exchange rate of the total.
+async fn session_response(
+    state: &AppState,
+    session: Session,
+    filter: PaymentStatusFilter,
+    headers: &HeaderMap,
+) -> Response {
+    // Totals are computed before filtering, so they cover every payment
+    let total_display = fx::total_display(&state.fx_rates, &session).await;
+    let etag = match &total_display {
+        Some(total) => format!(
+            "W/\"{}-{}-{}\"",
+            session.id,
+            session.version,
+            total.rate_timestamp.timestamp_millis()
+        ),
+        None => session.etag(),
+    };
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| etag_matches(v, &etag));
+
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let mut body = SessionResponse::new(session);
+        body.total_display = total_display;
+        filter.apply(&mut body.session.payments);
+        shown(&state.config, body).into_response()
+    };
+    let response_headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response_headers.insert(header::ETAG, value);
     }
+    response_headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(SESSION_CACHE_CONTROL),
+    );
+    response
 }
 
 /// Add payment to session
+#[utoipa::path(
+    post,
+    path = "/api/v1/session/{id}/payment",
+    tag = "session",
+    params(("id" = String, Path, description = "Session ID")),
+    request_body = AddPaymentRequest,
+    responses(
+        (status = 200, description = "Updated session", body = SessionResponse),
+        (status = 400, description = "Missing amount, invalid human_amount, or amount below MIN_PAYMENT_AMOUNT", body = ErrorResponse),
+        (status = 403, description = "Recipient address is blocked", body = ErrorResponse),
+        (status = 404, description = "Session not found", body = ErrorResponse),
+        (status = 422, description = "Malformed recipient or amount, `amount` and `human_amount` disagree, or an unknown field", body = ErrorResponse),
+        (status = 429, description = "Store is at MAX_TOTAL_PAYMENTS", body = ErrorResponse)
+    )
+)]
 pub async fn add_payment(
     State(state): State<AppState>,
     Path(id): Path<String>,
-    Json(payload): Json<AddPaymentRequest>,
-) -> Result<Json<SessionResponse>, AppError> {
+    ValidJson(payload): ValidJson<AddPaymentRequest>,
+) -> Result<Json<Shown<SessionResponse>>, AppError> {
+    ensure_not_blocked(&state, "Recipient", &payload.recipient)?;
+    let amount = match &payload.human_amount {
+        Some(human) => {
+            let Some(session) = state.session_store.get(&id).await else {
+                return Err(missing_session(&state, &id).await);
+            };
+            base_amount(payload.amount, human, session.token_decimals)?
+        }
+        None => payload.amount.ok_or_else(|| {
+            AppError::validation("amount", "Either amount or human_amount is required")
+        })?,
+    };
+    ensure_minimum_amount(&state, amount)?;
+
     tracing::info!(
         "Adding payment to session {}: {} to {} (ENS: {:?})",
         id,
-        payload.amount,
+        amount,
         payload.recipient,
         payload.recipient_ens
     );
@@ -93,32 +405,216 @@ pub async fn add_payment(
     // Create the payment
     let payment = Payment {
         id: Uuid::new_v4().to_string(),
+        // Assigned by the session when the payment is added
+        index: 0,
         recipient: payload.recipient,
         recipient_ens: payload.recipient_ens,
-        amount: payload.amount,
+        amount,
         status: PaymentStatus::Pending,
         created_at: chrono::Utc::now(),
+        confirmed_at: None,
+        settled_at: None,
+        cancel_reason: None,
+        memo: None,
     };
 
-    // Add to session store
-    match state.session_store.add_payment(&id, payment).await {
-        Some(session) => Ok(Json(SessionResponse { session })),
-        None => Err(AppError::NotFound(format!(
-            "Session {} not found or payment failed",
-            id
-        ))),
+    // Add to session store, enforcing the global payment cap
+    let session = state
+        .session_store
+        .add_payment(&id, payment, state.config.max_total_payments)
+        .await
+        .map_err(session_error)?;
+    Ok(shown(&state.config, SessionResponse::new(session)))
+}
+
+/// Reject payments below `MIN_PAYMENT_AMOUNT`, which would cost more gas
+/// to settle than they are worth
+pub fn ensure_minimum_amount(state: &AppState, amount: Amount) -> Result<(), AppError> {
+    let minimum = state.config.min_payment_amount;
+    if amount < minimum {
+        return Err(AppError::validation(
+            "amount",
+            format!(
+                "amount {} is below the minimum payment of {} base units; \
+                 combine small payments to the same recipient into one",
+                amount, minimum
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Base-unit amount of a payment given as `human` whole tokens, checked
+/// against the base-unit `amount` when that was sent too
+fn base_amount(amount: Option<Amount>, human: &str, decimals: u8) -> Result<Amount, AppError> {
+    let base = parse_units(human, decimals)
+        .map_err(|e| AppError::validation("human_amount", e.to_string()))?;
+    match amount {
+        Some(sent) if sent != base => Err(AppError::unprocessable(
+            "human_amount",
+            format!(
+                "amount {} ({} at {} decimals) does not match human_amount {}",
+                sent,
+                format_units(sent, decimals),
+                decimals,
+                human
+            ),
+        )),
+        _ => Ok(base),
+    }
+}
+
+/// List a session's payments in their listing (`index`) order, as
+/// `GET /session/:id` returns them, one page at a time.
+///
+/// The legacy `/api` tree keeps its `?limit=&offset=` paging and returns a
+/// `ListPaymentsResponse`; the limit is clamped rather than rejected.
+#[utoipa::path(
+    get,
+    path = "/api/v1/session/{id}/payments",
+    tag = "session",
+    params(("id" = String, Path, description = "Session ID"), PageQuery, PaymentStatusQuery),
+    responses(
+        (status = 200, description = "Page of payments", body = Paginated<Payment>),
+        (status = 400, description = "Invalid limit, cursor or payment_status", body = ErrorResponse),
+        (status = 404, description = "Session not found", body = ErrorResponse),
+        (status = 410, description = "Session was settled and archived", body = ErrorResponse)
+    )
+)]
+pub async fn list_payments(
+    State(state): State<AppState>,
+    Extension(version): Extension<ApiVersion>,
+    Path(id): Path<String>,
+    page: Result<PageParams, AppError>,
+    legacy_page: Result<Query<ListPaymentsQuery>, QueryRejection>,
+    filter: PaymentStatusFilter,
+) -> Result<Response, AppError> {
+    // Bad paging parameters of the other tree are not an error
+    let page = match version {
+        ApiVersion::V1 => Ok(page?),
+        ApiVersion::Legacy => Err(legacy_page
+            .map_err(|e| AppError::validation("query", e.body_text()))?
+            .0),
+    };
+    let Some(mut session) = state.session_store.get(&id).await else {
+        return Err(missing_session(&state, &id).await);
+    };
+
+    filter.apply(&mut session.payments);
+    Ok(match page {
+        Ok(page) => {
+            if page.cursor.as_ref().is_some_and(|c| !c.is_index()) {
+                return Err(AppError::validation("cursor", "cursor is invalid"));
+            }
+            let page = paginate(session.payments, &page, |p| {
+                Cursor::at_index(p.index, p.id.as_str())
+            });
+            shown(&state.config, page).into_response()
+        }
+        Err(query) => {
+            shown(&state.config, legacy_payments_page(session.payments, query)).into_response()
+        }
+    })
+}
+
+/// Page of `payments` at `query.offset`, in listing order
+fn legacy_payments_page(payments: Vec<Payment>, query: ListPaymentsQuery) -> ListPaymentsResponse {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .clamp(1, MAX_PAGE_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+    ListPaymentsResponse {
+        total: payments.len(),
+        payments: payments.into_iter().skip(offset).take(limit).collect(),
+        limit,
+        offset,
+    }
+}
+
+/// Replay a session's payment events, e.g. to backfill a receiver that
+/// was down. The log is kept as long as the session is stored.
+#[utoipa::path(
+    get,
+    path = "/api/v1/session/{id}/events",
+    tag = "session",
+    params(("id" = String, Path, description = "Session ID"), SessionEventsQuery),
+    responses(
+        (status = 200, description = "Events after since_sequence, oldest first", body = SessionEventsResponse),
+        (status = 400, description = "Invalid since_sequence", body = ErrorResponse),
+        (status = 404, description = "Session not found", body = ErrorResponse),
+        (status = 410, description = "Session was settled and archived", body = ErrorResponse)
+    )
+)]
+pub async fn list_events(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    query: Result<Query<SessionEventsQuery>, QueryRejection>,
+) -> Result<Json<Shown<SessionEventsResponse>>, AppError> {
+    let Query(query) = query.map_err(|_| {
+        AppError::validation(
+            "since_sequence",
+            "since_sequence must be a non-negative integer",
+        )
+    })?;
+    let since = query.since_sequence.unwrap_or(0);
+    match state.session_store.events_since(&id, since).await {
+        Some(events) => Ok(shown(
+            &state.config,
+            SessionEventsResponse {
+                session_id: id,
+                events,
+            },
+        )),
+        None => Err(missing_session(&state, &id).await),
+    }
+}
+
+/// Settlement receipt: the transfers that settle the session and the
+/// per-recipient split they account for
+#[utoipa::path(
+    get,
+    path = "/api/v1/session/{id}/receipt",
+    tag = "session",
+    params(("id" = String, Path, description = "Session ID")),
+    responses(
+        (status = 200, description = "Settlement receipt", body = SettlementReceipt),
+        (status = 404, description = "Session not found", body = ErrorResponse),
+        (status = 410, description = "Session was settled and archived", body = ErrorResponse)
+    )
+)]
+pub async fn get_receipt(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Shown<SettlementReceipt>>, AppError> {
+    match state.session_store.get(&id).await {
+        Some(session) => Ok(shown(&state.config, SettlementReceipt::new(&session))),
+        None => Err(missing_session(&state, &id).await),
     }
 }
 
 /// Remove payment from session
+#[utoipa::path(
+    delete,
+    path = "/api/v1/session/{id}/payment/{payment_id}",
+    tag = "session",
+    params(
+        ("id" = String, Path, description = "Session ID"),
+        ("payment_id" = String, Path, description = "Payment ID")
+    ),
+    responses(
+        (status = 200, description = "Updated session", body = SessionResponse),
+        (status = 404, description = "Session or payment not found", body = ErrorResponse)
+    )
+)]
 pub async fn remove_payment(
     State(state): State<AppState>,
     Path((id, payment_id)): Path<(String, String)>,
-) -> Result<Json<SessionResponse>, AppError> {
+) -> Result<Json<Shown<SessionResponse>>, AppError> {
     tracing::info!("Removing payment {} from session {}", payment_id, id);
 
     match state.session_store.remove_payment(&id, &payment_id).await {
-        Some(session) => Ok(Json(SessionResponse { session })),
+        Some(session) => Ok(shown(&state.config, SessionResponse::new(session))),
         None => Err(AppError::NotFound(format!(
             "Session {} or Payment {} not found",
             id, payment_id
@@ -126,24 +622,266 @@ pub async fn remove_payment(
     }
 }
 
-/// Finalize session request
-#[derive(Deserialize)]
-pub struct FinalizeRequest {
-    pub tx_hash: Option<String>,
+/// Map a session state error onto the API error envelope
+pub fn session_error(e: SessionError) -> AppError {
+    match e {
+        SessionError::SessionNotFound(_) | SessionError::PaymentNotFound(_) => {
+            AppError::NotFound(e.to_string())
+        }
+        SessionError::PaymentNotCancellable { .. }
+        | SessionError::SessionNotCancellable { .. }
+        | SessionError::SessionNotFinalizable { .. }
+        | SessionError::SessionNotReorderable { .. } => AppError::Conflict(e.to_string()),
+        SessionError::PaymentLimitReached(_) | SessionError::ActiveSessionLimitReached { .. } => {
+            AppError::RateLimited(e.to_string())
+        }
+        SessionError::InvalidPayment(_) => AppError::validation("amount", e.to_string()),
+        SessionError::InvalidOrder(_) => AppError::unprocessable("payment_ids", e.to_string()),
+        SessionError::DustPayments { .. } => AppError::unprocessable("payments", e.to_string()),
+        SessionError::SessionModified { .. } => AppError::Conflict(e.to_string()),
+        SessionError::FinalizeCooldown {
+            retry_after_secs, ..
+        } => AppError::TooManyRequests {
+            message: e.to_string(),
+            retry_after: Duration::from_secs(retry_after_secs),
+        },
+    }
+}
+
+/// Cancel an active or pending session, optionally recording a reason
+#[utoipa::path(
+    post,
+    path = "/api/v1/session/{id}/cancel",
+    tag = "session",
+    params(("id" = String, Path, description = "Session ID")),
+    request_body(content = Option<CancelSessionRequest>),
+    responses(
+        (status = 200, description = "Cancelled session", body = SessionResponse),
+        (status = 400, description = "Reason too long", body = ErrorResponse),
+        (status = 422, description = "Unknown field in the body", body = ErrorResponse),
+        (status = 404, description = "Session not found", body = ErrorResponse),
+        (status = 409, description = "Session already settled or cancelled", body = ErrorResponse)
+    )
+)]
+pub async fn cancel_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    OptionalJson(payload): OptionalJson<CancelSessionRequest>,
+) -> Result<Json<Shown<SessionResponse>>, AppError> {
+    let reason = payload
+        .and_then(|p| p.reason)
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if let Some(ref reason) = reason {
+        if reason.chars().count() > MAX_CANCEL_REASON_LEN {
+            return Err(AppError::validation(
+                "reason",
+                format!("must be at most {} characters", MAX_CANCEL_REASON_LEN),
+            ));
+        }
+    }
+
+    tracing::info!("Cancelling session {} (reason: {:?})", id, reason);
+
+    let session = state
+        .session_store
+        .cancel(&id, reason)
+        .await
+        .map_err(session_error)?;
+
+    Ok(shown(&state.config, SessionResponse::new(session)))
+}
+
+/// Cancel a pending payment, keeping its record in the session
+#[utoipa::path(
+    post,
+    path = "/api/v1/session/{id}/payment/{payment_id}/cancel",
+    tag = "session",
+    params(
+        ("id" = String, Path, description = "Session ID"),
+        ("payment_id" = String, Path, description = "Payment ID")
+    ),
+    responses(
+        (status = 200, description = "Updated session", body = SessionResponse),
+        (status = 404, description = "Session or payment not found", body = ErrorResponse),
+        (status = 409, description = "Payment is not pending", body = ErrorResponse)
+    )
+)]
+pub async fn cancel_payment(
+    State(state): State<AppState>,
+    Path((id, payment_id)): Path<(String, String)>,
+) -> Result<Json<Shown<SessionResponse>>, AppError> {
+    tracing::info!("Cancelling payment {} in session {}", payment_id, id);
+
+    let session = state
+        .session_store
+        .cancel_payment(&id, &payment_id)
+        .await
+        .map_err(session_error)?;
+
+    Ok(shown(&state.config, SessionResponse::new(session)))
+}
+
+/// Reorder the payments of an active session
+///
+/// The body lists every payment id of the session exactly once, in the
+/// new order; payments are listed in that order from then on.
+#[utoipa::path(
+    post,
+    path = "/api/v1/session/{id}/payments/reorder",
+    tag = "session",
+    params(("id" = String, Path, description = "Session ID")),
+    request_body = ReorderPaymentsRequest,
+    responses(
+        (status = 200, description = "Session with its payments in the new order", body = SessionResponse),
+        (status = 404, description = "Session not found", body = ErrorResponse),
+        (status = 409, description = "Session is not active", body = ErrorResponse),
+        (status = 422, description = "Ids are not a permutation of the session's payments, or an unknown field", body = ErrorResponse)
+    )
+)]
+pub async fn reorder_payments(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    ValidJson(payload): ValidJson<ReorderPaymentsRequest>,
+) -> Result<Json<Shown<SessionResponse>>, AppError> {
+    tracing::info!("Reordering payments of session {}", id);
+
+    let session = state
+        .session_store
+        .reorder_payments(&id, &payload.payment_ids)
+        .await
+        .map_err(session_error)?;
+
+    Ok(shown(&state.config, SessionResponse::new(session)))
 }
 
-/// Finalize session
-#[derive(Serialize)]
-pub struct FinalizeResponse {
-    pub session_id: String,
-    pub status: String,
-    pub tx_hash: Option<String>,
+/// Find the session settled by an on-chain transaction, e.g. when support
+/// only has the hash. Hashes are matched without regard to case; the
+/// session is read as by `GET /session/{id}`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/sessions/by-tx/{tx_hash}",
+    tag = "session",
+    params(
+        ("tx_hash" = String, Path, description = "Settlement transaction hash"),
+        PaymentStatusQuery,
+        ("If-None-Match" = Option<String>, Header, description = "ETag from a previous read")
+    ),
+    responses(
+        (status = 200, description = "Session", body = SessionResponse,
+            headers(("ETag" = String, description = "Weak ETag of this session version and the exchange rate of its total_display"))),
+        (status = 304, description = "Session unchanged since the given ETag"),
+        (status = 400, description = "Invalid payment_status", body = ErrorResponse),
+        (status = 404, description = "No session has this transaction hash", body = ErrorResponse),
+        (status = 410, description = "The session was settled and archived", body = ErrorResponse)
+    )
+)]
+pub async fn get_session_by_tx(
+    State(state): State<AppState>,
+    Path(tx_hash): Path<String>,
+    filter: PaymentStatusFilter,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let tx_hash = tx_hash.trim().to_lowercase();
+    let Some(session) = state.session_store.find_by_tx_hash(&tx_hash).await else {
+        return Err(
+            match state.session_store.archived_by_tx_hash(&tx_hash).await {
+                Some(id) => missing_session(&state, &id).await,
+                None => AppError::NotFound(format!("No session has transaction {}", tx_hash)),
+            },
+        );
+    };
+    Ok(session_response(&state, session, filter, &headers).await)
 }
 
+/// Most sessions one bulk finalize may name
+pub const MAX_BULK_FINALIZE: usize = 100;
+
+/// Finalize a batch of sessions, e.g. when a merchant closes out the day.
+///
+/// Each session goes through the same checks as a single finalize; one that
+/// fails is reported in its result without stopping the rest of the batch.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sessions/finalize",
+    tag = "session",
+    request_body = BulkFinalizeRequest,
+    responses(
+        (status = 200, description = "One result per session, in request order", body = BulkFinalizeResponse),
+        (status = 400, description = "No sessions, or more than 100", body = ErrorResponse),
+        (status = 422, description = "Malformed body or unknown field", body = ErrorResponse)
+    )
+)]
+pub async fn finalize_sessions(
+    State(state): State<AppState>,
+    ValidJson(payload): ValidJson<BulkFinalizeRequest>,
+) -> Result<Json<BulkFinalizeResponse>, AppError> {
+    if payload.session_ids.is_empty() || payload.session_ids.len() > MAX_BULK_FINALIZE {
+        return Err(AppError::validation(
+            "session_ids",
+            format!("session_ids must list 1 to {} sessions", MAX_BULK_FINALIZE),
+        ));
+    }
+
+    let mut results = Vec::with_capacity(payload.session_ids.len());
+    for id in payload.session_ids {
+        let request = FinalizeRequest {
+            tx_hash: payload.tx_hash.clone(),
+            ..Default::default()
+        };
+        let result = match finalize_session(
+            State(state.clone()),
+            Path(id.clone()),
+            ValidJson(request),
+        )
+        .await
+        {
+            Ok(Json(finalized)) => BulkFinalizeResult {
+                session_id: id,
+                status: finalized.status,
+                tx_hash: finalized.tx_hash,
+                error: None,
+            },
+            Err(e) => BulkFinalizeResult {
+                session_id: id,
+                status: "failed".to_string(),
+                tx_hash: None,
+                error: Some(e.body()),
+            },
+        };
+        results.push(result);
+    }
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    Ok(Json(BulkFinalizeResponse {
+        finalized: results.len() - failed,
+        failed,
+        results,
+    }))
+}
+
+/// Finalize a session with an optional settlement transaction hash.
+///
+/// With `SETTLEMENT_BATCHING`, a session finalized without one is queued
+/// for the backend's next settlement batch (`batched` in the response).
+#[utoipa::path(
+    post,
+    path = "/api/v1/session/{id}/finalize",
+    tag = "session",
+    params(("id" = String, Path, description = "Session ID")),
+    request_body = FinalizeRequest,
+    responses(
+        (status = 200, description = "Session finalized", body = FinalizeResponse),
+        (status = 422, description = "Malformed expected_total or an unknown field, or transfers below MIN_PAYMENT_AMOUNT without skip_dust", body = ErrorResponse),
+        (status = 404, description = "Session not found", body = ErrorResponse),
+        (status = 409, description = "Session is settled or cancelled, changed since the client reviewed it, or (with verify_ens) a recipient's ENS record changed", body = ErrorResponse),
+        (status = 429, description = "Finalized too recently; see Retry-After", body = ErrorResponse)
+    )
+)]
 pub async fn finalize_session(
     State(state): State<AppState>,
     Path(id): Path<String>,
-    Json(payload): Json<FinalizeRequest>,
+    ValidJson(payload): ValidJson<FinalizeRequest>,
 ) -> Result<Json<FinalizeResponse>, AppError> {
     tracing::info!(
         "Finalizing session {} with tx_hash: {:?}",
@@ -153,17 +891,63 @@ pub async fn finalize_session(
 
     use crate::models::session::SessionStatus;
 
+    let guard = FinalizeGuard {
+        total: payload.expected_total,
+        payment_count: payload.expected_payment_count,
+    };
+
+    if payload.verify_ens {
+        let Some(session) = state.session_store.get(&id).await else {
+            return Err(missing_session(&state, &id).await);
+        };
+        verify_recipient_ens(&state, &session).await?;
+    }
+
     // Update session status and persist tx_hash
-    match state
+    let session = state
         .session_store
-        .finalize(&id, SessionStatus::Pending, payload.tx_hash.clone())
+        .finalize(
+            &id,
+            SessionStatus::Pending,
+            payload.tx_hash.clone(),
+            &guard,
+            &DustPolicy {
+                minimum: state.config.min_payment_amount,
+                skip: payload.skip_dust,
+            },
+            Duration::from_secs(state.config.finalize_cooldown_secs),
+        )
         .await
-    {
-        Some(session) => Ok(Json(FinalizeResponse {
-            session_id: id,
-            status: "pending".to_string(),
-            tx_hash: session.tx_hash,
-        })),
-        None => Err(AppError::NotFound(format!("Session {} not found", id))),
+        .map_err(session_error)?;
+
+    let batched = payload.tx_hash.is_none() && queue_settlement(&state, &session);
+    Ok(Json(FinalizeResponse {
+        session_id: id,
+        status: "pending".to_string(),
+        tx_hash: session.tx_hash,
+        batched,
+    }))
+}
+
+/// Queue a session finalized without a transaction for the settlement
+/// batcher, sending the batch right away once it is full; returns whether
+/// batching is on
+#[cfg(feature = "settlement")]
+fn queue_settlement(state: &AppState, session: &Session) -> bool {
+    let Some(batcher) = state.settlement_batcher.clone() else {
+        return false;
+    };
+    if batcher.enqueue(session) {
+        tokio::spawn(async move {
+            if let Err(e) = batcher.flush().await {
+                tracing::warn!(error = %e, "Settlement batch could not be sent");
+            }
+        });
     }
+    true
+}
+
+#[cfg(not(feature = "settlement"))]
+fn queue_settlement(_state: &AppState, _session: &Session) -> bool {
+    false
 }
@@ -0,0 +1,162 @@
+//! Settlement status and preview API handlers (`settlement` feature)
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+use crate::api::error::{AppError, ErrorResponse};
+use crate::api::session::missing_session;
+use crate::api::{shown, Shown};
+use crate::models::amount::Amount;
+use crate::services::prices::NATIVE_TOKEN;
+use crate::services::settlement::{
+    SettlementError, TxStatus, NATIVE_DECIMALS, NATIVE_PRICE_CHAIN_ID, TRANSFER_GAS,
+};
+use crate::AppState;
+pub use settleone_types::api::{SettlementPreview, SettlementStatusResponse};
+
+fn settlement_error(e: SettlementError) -> AppError {
+    match e {
+        SettlementError::Rpc(_) => AppError::Upstream(e.to_string()),
+    }
+}
+
+/// Estimate what settling the session would cost now: the gas of its
+/// transfers at the chain's gas price, and that fee in US dollars
+#[utoipa::path(
+    get,
+    path = "/api/v1/session/{id}/settlement-preview",
+    tag = "session",
+    params(("id" = String, Path, description = "Session ID")),
+    responses(
+        (status = 200, description = "Settlement cost estimate", body = SettlementPreview),
+        (status = 404, description = "Session not found", body = ErrorResponse),
+        (status = 410, description = "Session was settled and archived", body = ErrorResponse),
+        (status = 502, description = "Settlement chain RPC failed", body = ErrorResponse)
+    )
+)]
+pub async fn settlement_preview(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Shown<SettlementPreview>>, AppError> {
+    let Some(session) = state.session_store.get(&id).await else {
+        return Err(missing_session(&state, &id).await);
+    };
+    let transfers = session.transfers();
+    let estimated_gas = TRANSFER_GAS * transfers.len() as u64;
+    let gas_price = state
+        .settlement_service
+        .gas_price()
+        .await
+        .map_err(settlement_error)?;
+    let fee = gas_price.saturating_mul(u128::from(estimated_gas));
+
+    // The gas estimate stands without a price
+    let estimated_fee_usd = match state
+        .native_prices
+        .get(NATIVE_PRICE_CHAIN_ID, NATIVE_TOKEN)
+        .await
+    {
+        Ok(quote) => Some(fee_usd(fee, quote.usd)),
+        Err(e) => {
+            tracing::warn!(error = %e, "No native token price for the settlement fee");
+            None
+        }
+    };
+
+    let preview = SettlementPreview {
+        session_id: id,
+        transfers,
+        estimated_gas,
+        gas_price_wei: Amount::from(gas_price),
+        estimated_fee_wei: Amount::from(fee),
+        estimated_fee_usd,
+    };
+    Ok(shown(&state.config, preview))
+}
+
+/// `fee` wei of the native token in US dollars, to a millionth of a dollar
+fn fee_usd(fee: u128, usd_per_token: f64) -> f64 {
+    let tokens = fee as f64 / 10f64.powi(NATIVE_DECIMALS.into());
+    (tokens * usd_per_token * 1e6).round() / 1e6
+}
+
+/// Check the settlement transaction on chain, settling the session once it is deep enough
+///
+/// A session in a batch the settlement batcher submitted is only reported
+/// on: the batcher settles it once the batch's `BatchSettled` logs name it.
+#[utoipa::path(
+    get,
+    path = "/api/v1/session/{id}/settlement-status",
+    tag = "session",
+    params(("id" = String, Path, description = "Session ID")),
+    responses(
+        (status = 200, description = "Settlement status", body = SettlementStatusResponse),
+        (status = 404, description = "Session not found", body = ErrorResponse),
+        (status = 409, description = "Session has no settlement transaction", body = ErrorResponse),
+        (status = 410, description = "Session was settled and archived", body = ErrorResponse),
+        (status = 502, description = "Settlement chain RPC failed", body = ErrorResponse)
+    )
+)]
+pub async fn settlement_status(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<SettlementStatusResponse>, AppError> {
+    let Some(session) = state.session_store.get(&id).await else {
+        return Err(missing_session(&state, &id).await);
+    };
+    let tx_hash = session.tx_hash.clone().ok_or_else(|| {
+        AppError::Conflict(format!("Session {} has no settlement transaction", id))
+    })?;
+
+    let tx_status = state
+        .settlement_service
+        .tx_status(&tx_hash)
+        .await
+        .map_err(settlement_error)?;
+
+    let (label, confirmations, block_number) = match tx_status {
+        TxStatus::Pending => ("pending", 0, None),
+        TxStatus::Confirmed {
+            block_number,
+            confirmations,
+        } => ("confirmed", confirmations, Some(block_number)),
+        TxStatus::Failed {
+            block_number,
+            confirmations,
+        } => ("failed", confirmations, Some(block_number)),
+    };
+
+    // A mined batch transaction may not have settled this session
+    let batched = state
+        .settlement_batcher
+        .as_ref()
+        .is_some_and(|batcher| batcher.is_submitted(&id));
+    let min_confirmations = state.live_config.get().settlement_min_confirmations;
+    let session_status = if batched {
+        session.status
+    } else if label == "confirmed" && confirmations >= min_confirmations {
+        tracing::info!("Settlement {} confirmed for session {}", tx_hash, id);
+        state
+            .session_store
+            .settle(&id)
+            .await
+            .map(|s| s.status)
+            .unwrap_or(session.status)
+    } else {
+        if label == "confirmed" {
+            state.session_store.confirm(&id).await;
+        }
+        session.status
+    };
+
+    Ok(Json(SettlementStatusResponse {
+        session_id: id,
+        session_status,
+        tx_hash,
+        tx_status: label.to_string(),
+        confirmations,
+        block_number,
+    }))
+}
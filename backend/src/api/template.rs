@@ -0,0 +1,335 @@
+//! Session template API handlers
+//!
+//! A template saves a recurring set of recipients; each session created
+//! from it gets the template's entries as payments. Until requests are
+//! tied to a user, a template's owner is the address it was saved with.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use uuid::Uuid;
+
+#[cfg(feature = "ens")]
+use crate::api::ens::ens_error;
+use crate::api::error::{AppError, ErrorResponse};
+use crate::api::extract::{OptionalJson, ValidJson, ValidQuery};
+use crate::api::session::{ensure_minimum_amount, ensure_not_blocked, session_error};
+use crate::api::{shown, ApiVersion, Shown};
+use crate::models::address::{Address, EnsName};
+use crate::models::session::{
+    Payment, PaymentStatus, Session, SessionTemplate, TemplateEntry, MAX_MEMO_LEN,
+    MAX_TEMPLATE_NAME_LEN,
+};
+use crate::AppState;
+pub use settleone_types::api::{
+    CreateFromTemplateRequest, CreateTemplateRequest, SessionResponse, TemplateEntryRequest,
+    TemplateListQuery, TemplateListResponse,
+};
+
+/// Save a session template
+#[utoipa::path(
+    post,
+    path = "/api/v1/templates",
+    tag = "template",
+    request_body = CreateTemplateRequest,
+    responses(
+        (status = 201, description = "Template saved", body = SessionTemplate),
+        (status = 400, description = "Invalid name, entries, amount or memo", body = ErrorResponse),
+        (status = 403, description = "Owner or recipient address is blocked", body = ErrorResponse),
+        (status = 404, description = "Recipient name not found", body = ErrorResponse),
+        (status = 422, description = "Malformed address or name, a recipient that does not match its name, or an unknown field", body = ErrorResponse),
+        (status = 502, description = "ENS resolver unreachable or failing", body = ErrorResponse),
+        (status = 504, description = "ENS resolver timed out", body = ErrorResponse)
+    )
+)]
+pub async fn create_template(
+    State(state): State<AppState>,
+    ValidJson(payload): ValidJson<CreateTemplateRequest>,
+) -> Result<(StatusCode, Json<Shown<SessionTemplate>>), AppError> {
+    let name = payload.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_TEMPLATE_NAME_LEN {
+        return Err(AppError::validation(
+            "name",
+            format!("name must be 1 to {} characters", MAX_TEMPLATE_NAME_LEN),
+        ));
+    }
+    if payload.entries.is_empty() {
+        return Err(AppError::validation(
+            "entries",
+            "a template needs at least one entry",
+        ));
+    }
+    ensure_not_blocked(&state, "Owner", &payload.owner)?;
+
+    let mut entries = Vec::with_capacity(payload.entries.len());
+    for (index, entry) in payload.entries.into_iter().enumerate() {
+        entries.push(template_entry(&state, index, entry).await?);
+    }
+
+    let template = SessionTemplate {
+        id: Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        owner: payload.owner,
+        entries,
+        created_at: chrono::Utc::now(),
+    };
+    state.template_store.insert(template.clone()).await;
+    tracing::info!(
+        "Saved template {} for owner {} with {} entries",
+        template.id,
+        template.owner,
+        template.entries.len()
+    );
+    Ok((StatusCode::CREATED, shown(&state.config, template)))
+}
+
+/// Validate one entry of a new template, resolving its ENS name
+async fn template_entry(
+    state: &AppState,
+    index: usize,
+    entry: TemplateEntryRequest,
+) -> Result<TemplateEntry, AppError> {
+    let field = format!("entries[{}]", index);
+    if let Some(amount) = entry.default_amount {
+        ensure_minimum_amount(state, amount)?;
+    }
+    if entry
+        .memo
+        .as_ref()
+        .is_some_and(|memo| memo.chars().count() > MAX_MEMO_LEN)
+    {
+        return Err(AppError::validation(
+            &format!("{}.memo", field),
+            format!("memo must be at most {} characters", MAX_MEMO_LEN),
+        ));
+    }
+
+    let recipient = match (entry.recipient, &entry.recipient_ens) {
+        (recipient, Some(name)) => {
+            let resolved = current_address(state, name).await?;
+            if recipient.is_some_and(|recipient| recipient != resolved) {
+                return Err(AppError::unprocessable(
+                    &format!("{}.recipient", field),
+                    format!("{} resolves to {}, not the given recipient", name, resolved),
+                ));
+            }
+            resolved
+        }
+        (Some(recipient), None) => recipient,
+        (None, None) => {
+            return Err(AppError::validation(
+                &format!("{}.recipient", field),
+                "recipient or recipient_ens is required",
+            ))
+        }
+    };
+    ensure_not_blocked(state, "Recipient", &recipient)?;
+
+    Ok(TemplateEntry {
+        recipient,
+        recipient_ens: entry.recipient_ens,
+        default_amount: entry.default_amount,
+        memo: entry.memo,
+    })
+}
+
+/// List an owner's templates, oldest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/templates",
+    tag = "template",
+    params(TemplateListQuery),
+    responses(
+        (status = 200, description = "The owner's templates", body = TemplateListResponse),
+        (status = 422, description = "Missing or malformed owner", body = ErrorResponse)
+    )
+)]
+pub async fn list_templates(
+    State(state): State<AppState>,
+    ValidQuery(query): ValidQuery<TemplateListQuery>,
+) -> Json<Shown<TemplateListResponse>> {
+    let templates = state.template_store.list(&query.owner).await;
+    shown(&state.config, TemplateListResponse { templates })
+}
+
+/// Get a template by id
+#[utoipa::path(
+    get,
+    path = "/api/v1/templates/{id}",
+    tag = "template",
+    params(("id" = String, Path, description = "Template ID")),
+    responses(
+        (status = 200, description = "Template found", body = SessionTemplate),
+        (status = 404, description = "Template not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_template(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Shown<SessionTemplate>>, AppError> {
+    state
+        .template_store
+        .get(&id)
+        .await
+        .map(|template| shown(&state.config, template))
+        .ok_or_else(|| missing_template(&id))
+}
+
+/// Delete a template; sessions created from it are unaffected
+#[utoipa::path(
+    delete,
+    path = "/api/v1/templates/{id}",
+    tag = "template",
+    params(("id" = String, Path, description = "Template ID")),
+    responses(
+        (status = 204, description = "Template deleted"),
+        (status = 404, description = "Template not found", body = ErrorResponse)
+    )
+)]
+pub async fn delete_template(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    state
+        .template_store
+        .remove(&id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .ok_or_else(|| missing_template(&id))
+}
+
+/// Create a session for the template's owner with a payment per entry
+/// (201 Created on v1, 200 on the legacy API). ENS names are re-resolved,
+/// bypassing the cache, so payments go to their current address.
+#[utoipa::path(
+    post,
+    path = "/api/v1/session/from-template/{template_id}",
+    tag = "template",
+    params(("template_id" = String, Path, description = "Template ID")),
+    request_body(content = Option<CreateFromTemplateRequest>),
+    responses(
+        (status = 201, description = "Session created", body = SessionResponse),
+        (status = 400, description = "An entry without an amount, an amount below the minimum, or an override for a missing entry", body = ErrorResponse),
+        (status = 403, description = "Owner or recipient address is blocked", body = ErrorResponse),
+        (status = 404, description = "Template not found, or a recipient name no longer resolves", body = ErrorResponse),
+        (status = 422, description = "Malformed amounts or an unknown field", body = ErrorResponse),
+        (status = 429, description = "Too many active sessions for the owner, or the payment limit is reached", body = ErrorResponse),
+        (status = 502, description = "ENS resolver unreachable or failing", body = ErrorResponse),
+        (status = 504, description = "ENS resolver timed out", body = ErrorResponse)
+    )
+)]
+pub async fn create_from_template(
+    State(state): State<AppState>,
+    Extension(version): Extension<ApiVersion>,
+    Path(template_id): Path<String>,
+    OptionalJson(payload): OptionalJson<CreateFromTemplateRequest>,
+) -> Result<(StatusCode, Json<Shown<SessionResponse>>), AppError> {
+    let template = state
+        .template_store
+        .get(&template_id)
+        .await
+        .ok_or_else(|| missing_template(&template_id))?;
+    let amounts = payload.unwrap_or_default().amounts;
+    if let Some(index) = amounts.keys().find(|&&i| i >= template.entries.len()) {
+        return Err(AppError::validation(
+            "amounts",
+            format!(
+                "template has {} entries; there is no entry {}",
+                template.entries.len(),
+                index
+            ),
+        ));
+    }
+    ensure_not_blocked(&state, "User", &template.owner)?;
+
+    // Everything is checked before the session exists, so a failure
+    // leaves nothing half-created
+    let mut payments = Vec::with_capacity(template.entries.len());
+    for (index, entry) in template.entries.iter().enumerate() {
+        let amount = amounts
+            .get(&index)
+            .copied()
+            .or(entry.default_amount)
+            .ok_or_else(|| {
+                AppError::validation(
+                    &format!("amounts.{}", index),
+                    format!("entry {} has no default_amount; give one in amounts", index),
+                )
+            })?;
+        ensure_minimum_amount(&state, amount)?;
+        let recipient = match &entry.recipient_ens {
+            Some(name) => current_address(&state, name).await?,
+            None => entry.recipient.clone(),
+        };
+        ensure_not_blocked(&state, "Recipient", &recipient)?;
+        payments.push(Payment {
+            id: Uuid::new_v4().to_string(),
+            // Assigned by the session when the payment is added
+            index: 0,
+            recipient,
+            recipient_ens: entry.recipient_ens.clone(),
+            amount,
+            status: PaymentStatus::Pending,
+            created_at: chrono::Utc::now(),
+            confirmed_at: None,
+            settled_at: None,
+            cancel_reason: None,
+            memo: entry.memo.clone(),
+        });
+    }
+    let mut session = Session::new(Uuid::new_v4().to_string(), template.owner.clone());
+    for payment in payments {
+        session
+            .add_payment(payment)
+            .map_err(|e| AppError::validation("amounts", e))?;
+    }
+
+    // Stored whole, with both caps checked as it is, or not at all
+    let session = state
+        .session_store
+        .try_create(
+            session,
+            state.config.max_active_sessions_per_user,
+            state.config.max_total_payments,
+        )
+        .await
+        .map_err(session_error)?;
+
+    tracing::info!(
+        "Created session {} from template {} for user {}",
+        session.id,
+        template.id,
+        template.owner
+    );
+    let status = match version {
+        ApiVersion::V1 => StatusCode::CREATED,
+        ApiVersion::Legacy => StatusCode::OK,
+    };
+    Ok((status, shown(&state.config, SessionResponse::new(session))))
+}
+
+fn missing_template(id: &str) -> AppError {
+    AppError::NotFound(format!("Template {} not found", id))
+}
+
+/// What `name` resolves to now, bypassing the ENS cache
+#[cfg(feature = "ens")]
+async fn current_address(state: &AppState, name: &EnsName) -> Result<Address, AppError> {
+    state
+        .ens_service
+        .resolve_fresh(name)
+        .await
+        .map(|resolved| resolved.address)
+        .map_err(|e| ens_error("recipient_ens", e))
+}
+
+/// Entries named by ENS need ENS, which this build leaves out
+#[cfg(not(feature = "ens"))]
+async fn current_address(_state: &AppState, _name: &EnsName) -> Result<Address, AppError> {
+    Err(AppError::NotImplemented(
+        "recipient_ens entries require the ens integration, which is not enabled in this build"
+            .to_string(),
+    ))
+}
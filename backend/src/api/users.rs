@@ -0,0 +1,98 @@
+//! User-scoped views across sessions
+
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+use crate::api::error::{AppError, ErrorResponse};
+use crate::api::pagination::{paginate_newest_first, Cursor, PageParams, PageQuery};
+use crate::api::{shown, AddressDisplay, Shown};
+use crate::models::address::{Address, AddressError};
+use crate::models::amount::Amount;
+use crate::models::session::PaymentStatus;
+use crate::services::session::RecipientKey;
+use crate::utils::amounts::format_units;
+use crate::AppState;
+pub use settleone_types::api::{RecipientHistoryResponse, RecipientPayment, RecipientTotal};
+
+/// Settled payments of a user to one recipient across all of their
+/// sessions, archived ones included, newest first, with lifetime totals.
+/// The recipient is an address, or an ENS name matched against the names
+/// payments were made to.
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{address}/recipients/{recipient}",
+    tag = "session",
+    params(
+        ("address" = String, Path, description = "Session user address"),
+        ("recipient" = String, Path, description = "Recipient address or ENS name"),
+        PageQuery
+    ),
+    responses(
+        (status = 200, description = "Payment history", body = RecipientHistoryResponse),
+        (status = 400, description = "Invalid limit or cursor", body = ErrorResponse),
+        (status = 422, description = "Malformed address or recipient", body = ErrorResponse)
+    )
+)]
+pub async fn recipient_history(
+    State(state): State<AppState>,
+    Path((address, recipient)): Path<(String, String)>,
+    page: PageParams,
+) -> Result<Json<Shown<RecipientHistoryResponse>>, AppError> {
+    let user: Address = address
+        .parse()
+        .map_err(|e: AddressError| AppError::unprocessable("address", e.to_string()))?;
+    let key: RecipientKey = recipient
+        .parse()
+        .map_err(|e: String| AppError::unprocessable("recipient", e))?;
+
+    let payments = state
+        .session_store
+        .payments_to_recipient(&user, &key, Some(&PaymentStatus::Settled))
+        .await;
+    let mut totals: BTreeMap<u8, RecipientTotal> = BTreeMap::new();
+    for paid in &payments {
+        let total = totals
+            .entry(paid.token_decimals)
+            .or_insert_with(|| RecipientTotal {
+                token_decimals: paid.token_decimals,
+                amount: Amount::ZERO,
+                amount_display: String::new(),
+                payment_count: 0,
+            });
+        total.amount = total
+            .amount
+            .checked_add(paid.payment.amount)
+            .ok_or_else(|| AppError::Internal("recipient total overflows".to_string()))?;
+        total.payment_count += 1;
+    }
+    let totals = totals
+        .into_values()
+        .map(|total| RecipientTotal {
+            amount_display: format_units(total.amount, total.token_decimals),
+            ..total
+        })
+        .collect();
+
+    let paid_at = |p: &RecipientPayment| p.payment.settled_at.unwrap_or(p.payment.created_at);
+    let first_paid_at = payments.iter().map(paid_at).min();
+    let last_paid_at = payments.iter().map(paid_at).max();
+
+    let history = RecipientHistoryResponse {
+        user,
+        recipient: match key {
+            RecipientKey::Address(address) => AddressDisplay::of(&state.config).show(&address),
+            RecipientKey::Ens(name) => name.to_string(),
+        },
+        totals,
+        first_paid_at,
+        last_paid_at,
+        payments: paginate_newest_first(payments, &page, |p| {
+            Cursor::new(paid_at(p), p.payment.id.as_str())
+        }),
+    };
+    Ok(shown(&state.config, history))
+}
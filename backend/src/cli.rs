@@ -0,0 +1,136 @@
+//! Command line interface
+//!
+//! `serve` (the default) runs the HTTP server; the other subcommands run a
+//! one-off operation against the same configuration and services and exit.
+
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand};
+
+use crate::config::Config;
+use crate::models::address::EnsName;
+use crate::services::snapshot::Snapshot;
+use crate::AppState;
+
+/// SettleOne backend API for session-based USDC payments
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (default)
+    Serve,
+    /// Validate the configuration and print effective values (secrets masked)
+    CheckConfig,
+    /// Resolve an ENS name once and print the result as JSON
+    Resolve {
+        /// ENS name, e.g. vitalik.eth
+        name: EnsName,
+    },
+    /// Move the session snapshot at SESSION_SNAPSHOT_PATH in or out
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotCommand,
+    },
+    /// Run create → add payments → finalize load against a server and report latencies
+    Loadgen(crate::loadgen::LoadgenArgs),
+}
+
+#[derive(Subcommand)]
+pub enum SnapshotCommand {
+    /// Write the stored sessions to FILE
+    Export { file: PathBuf },
+    /// Replace the stored sessions with those in FILE (run while the server is stopped)
+    Import { file: PathBuf },
+}
+
+/// Load the configuration, exiting with status 1 if it is invalid
+pub fn load_config() -> Config {
+    Config::from_env().unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    })
+}
+
+/// Print every effective setting, then warnings on stderr
+pub fn check_config(config: &Config) {
+    for (key, value) in config.effective_values() {
+        println!("{}={}", key, value);
+    }
+    for warning in config.warnings() {
+        eprintln!("warning: {}", warning);
+    }
+}
+
+/// Resolve `name` with the configured ENS service
+#[cfg(feature = "ens")]
+pub async fn resolve(config: Config, name: &EnsName) -> anyhow::Result<()> {
+    let state = AppState::new(config);
+    let result = state.ens_service.resolve(name).await?;
+    println!(
+        "{}",
+        serde_json::json!({
+            "name": name,
+            "address": result.address,
+            "avatar": result.avatar,
+        })
+    );
+    Ok(())
+}
+
+/// ENS resolution is not available without the `ens` feature
+#[cfg(not(feature = "ens"))]
+pub async fn resolve(_config: Config, _name: &EnsName) -> anyhow::Result<()> {
+    anyhow::bail!("the ens integration is not enabled in this build")
+}
+
+/// Run a snapshot subcommand against the store at `SESSION_SNAPSHOT_PATH`
+pub async fn snapshot(config: Config, action: SnapshotCommand) -> anyhow::Result<()> {
+    let store_path = config
+        .session_snapshot_path
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("SESSION_SNAPSHOT_PATH is not set"))?;
+    let state = AppState::new(config);
+
+    match action {
+        SnapshotCommand::Export { file } => {
+            restore_sessions(&state, &store_path).await?;
+            let sessions = state.session_store.snapshot().await;
+            let count = sessions.len();
+            Snapshot::new(sessions)
+                .with_events(state.session_store.events_snapshot().await)
+                .with_templates(state.template_store.snapshot().await)
+                .write(&file)?;
+            eprintln!("Exported {} sessions to {}", count, file.display());
+        }
+        SnapshotCommand::Import { file } => {
+            let snapshot = Snapshot::read(&file)?;
+            let count = state.session_store.restore(snapshot.sessions).await;
+            state.session_store.restore_events(snapshot.events).await;
+            state.template_store.restore(snapshot.templates).await;
+            Snapshot::new(state.session_store.snapshot().await)
+                .with_events(state.session_store.events_snapshot().await)
+                .with_templates(state.template_store.snapshot().await)
+                .write(&store_path)?;
+            eprintln!("Imported {} sessions into {}", count, store_path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Load the snapshot at `path` into the session and template stores,
+/// returning the number of sessions; a missing file is an empty store
+pub async fn restore_sessions(state: &AppState, path: &Path) -> anyhow::Result<usize> {
+    if !path.exists() {
+        return Ok(0);
+    }
+    let snapshot = Snapshot::read(path)?;
+    let count = state.session_store.restore(snapshot.sessions).await;
+    state.session_store.restore_events(snapshot.events).await;
+    state.template_store.restore(snapshot.templates).await;
+    Ok(count)
+}
@@ -0,0 +1,269 @@
+//! Settings that can change without a restart
+//!
+//! [`DynamicConfig`] holds the tunables (rate limits, cache TTLs, CORS
+//! origins, settlement confirmation depth, slow-request threshold). The running process keeps the
+//! current value in a [`LiveConfig`], which `SIGHUP` and
+//! `POST /admin/config/reload` swap atomically; services read it on every
+//! use instead of copying values at construction.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+
+use super::{parse_number, ConfigError};
+#[cfg(feature = "ens")]
+use crate::services::ens::DEFAULT_CACHE_TTL;
+#[cfg(feature = "lifi")]
+use crate::services::quote_cache::DEFAULT_QUOTE_CACHE_TTL;
+use crate::services::rate_limit::{
+    DEFAULT_QUOTE_PER_MINUTE, DEFAULT_READ_PER_MINUTE, DEFAULT_WRITE_PER_MINUTE,
+};
+
+/// Slow-request threshold unless `SLOW_REQUEST_THRESHOLD_MS` overrides it
+pub const DEFAULT_SLOW_REQUEST_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// Hot-reloadable settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicConfig {
+    /// Per-IP requests per minute for read endpoints (0 disables)
+    pub rate_limit_read_per_minute: u32,
+
+    /// Per-IP requests per minute for session-mutating endpoints (0 disables)
+    pub rate_limit_write_per_minute: u32,
+
+    /// Per-IP requests per minute for the quote endpoint (0 disables)
+    pub rate_limit_quote_per_minute: u32,
+
+    /// Lifetime of cached ENS resolutions
+    #[cfg(feature = "ens")]
+    pub ens_cache_ttl: Duration,
+
+    /// Lifetime of cached LI.FI quotes
+    #[cfg(feature = "lifi")]
+    pub quote_cache_ttl: Duration,
+
+    /// Origins allowed by CORS (any origin if empty)
+    pub cors_allowed_origins: Vec<String>,
+
+    /// Confirmations a settlement transaction needs before its session is settled
+    #[cfg(feature = "settlement")]
+    pub settlement_min_confirmations: u64,
+
+    /// Requests slower than this are logged as warnings (zero disables)
+    pub slow_request_threshold: Duration,
+}
+
+impl DynamicConfig {
+    /// Parse the dynamic settings with the same rules as [`super::Config::from_lookup`]
+    pub fn from_lookup<F>(var: F) -> Result<Self, ConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let rate_limit = |key: &'static str, default: u32| {
+            parse_number(key, var(key)).map(|limit| limit.unwrap_or(default))
+        };
+        #[cfg(any(feature = "ens", feature = "lifi"))]
+        let ttl = |key: &'static str, default: Duration| {
+            parse_number(key, var(key)).map(|secs| secs.map_or(default, Duration::from_secs))
+        };
+
+        let cors_allowed_origins = var("CORS_ALLOWED_ORIGINS")
+            .map(|raw| {
+                raw.split(',')
+                    .map(|origin| origin.trim().trim_end_matches('/').to_string())
+                    .filter(|origin| !origin.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        #[cfg(feature = "settlement")]
+        let settlement_min_confirmations = parse_number(
+            "SETTLEMENT_MIN_CONFIRMATIONS",
+            var("SETTLEMENT_MIN_CONFIRMATIONS"),
+        )?
+        .unwrap_or(1);
+        #[cfg(feature = "settlement")]
+        if settlement_min_confirmations == 0 {
+            return Err(ConfigError::Invalid {
+                key: "SETTLEMENT_MIN_CONFIRMATIONS",
+                reason: "must be at least 1".to_string(),
+            });
+        }
+
+        let slow_request_threshold = parse_number(
+            "SLOW_REQUEST_THRESHOLD_MS",
+            var("SLOW_REQUEST_THRESHOLD_MS"),
+        )?
+        .map_or(DEFAULT_SLOW_REQUEST_THRESHOLD, Duration::from_millis);
+
+        Ok(Self {
+            rate_limit_read_per_minute: rate_limit(
+                "RATE_LIMIT_READ_PER_MINUTE",
+                DEFAULT_READ_PER_MINUTE,
+            )?,
+            rate_limit_write_per_minute: rate_limit(
+                "RATE_LIMIT_WRITE_PER_MINUTE",
+                DEFAULT_WRITE_PER_MINUTE,
+            )?,
+            rate_limit_quote_per_minute: rate_limit(
+                "RATE_LIMIT_QUOTE_PER_MINUTE",
+                DEFAULT_QUOTE_PER_MINUTE,
+            )?,
+            #[cfg(feature = "ens")]
+            ens_cache_ttl: ttl("ENS_CACHE_TTL_SECS", DEFAULT_CACHE_TTL)?,
+            #[cfg(feature = "lifi")]
+            quote_cache_ttl: ttl("QUOTE_CACHE_TTL_SECS", DEFAULT_QUOTE_CACHE_TTL)?,
+            cors_allowed_origins,
+            #[cfg(feature = "settlement")]
+            settlement_min_confirmations,
+            slow_request_threshold,
+        })
+    }
+
+    /// Whether CORS allows requests from `origin`
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.cors_allowed_origins.is_empty()
+            || self
+                .cors_allowed_origins
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(origin))
+    }
+
+    /// Effective value of every dynamic setting by variable name
+    pub fn effective_values(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "RATE_LIMIT_READ_PER_MINUTE",
+                self.rate_limit_read_per_minute.to_string(),
+            ),
+            (
+                "RATE_LIMIT_WRITE_PER_MINUTE",
+                self.rate_limit_write_per_minute.to_string(),
+            ),
+            (
+                "RATE_LIMIT_QUOTE_PER_MINUTE",
+                self.rate_limit_quote_per_minute.to_string(),
+            ),
+            #[cfg(feature = "ens")]
+            (
+                "ENS_CACHE_TTL_SECS",
+                self.ens_cache_ttl.as_secs().to_string(),
+            ),
+            #[cfg(feature = "lifi")]
+            (
+                "QUOTE_CACHE_TTL_SECS",
+                self.quote_cache_ttl.as_secs().to_string(),
+            ),
+            ("CORS_ALLOWED_ORIGINS", self.cors_allowed_origins.join(",")),
+            #[cfg(feature = "settlement")]
+            (
+                "SETTLEMENT_MIN_CONFIRMATIONS",
+                self.settlement_min_confirmations.to_string(),
+            ),
+            (
+                "SLOW_REQUEST_THRESHOLD_MS",
+                self.slow_request_threshold.as_millis().to_string(),
+            ),
+        ]
+    }
+}
+
+impl Default for DynamicConfig {
+    fn default() -> Self {
+        Self::from_lookup(|_| None).expect("default dynamic configuration is valid")
+    }
+}
+
+/// The current [`DynamicConfig`], shared by every service that reads it
+#[derive(Clone, Default)]
+pub struct LiveConfig(Arc<ArcSwap<DynamicConfig>>);
+
+impl LiveConfig {
+    /// Start from `config`
+    pub fn new(config: DynamicConfig) -> Self {
+        Self(Arc::new(ArcSwap::from_pointee(config)))
+    }
+
+    /// Current settings; hold the result for one operation, not longer
+    pub fn get(&self) -> Arc<DynamicConfig> {
+        self.0.load_full()
+    }
+
+    /// Swap in `config`, logging and returning the variables that changed
+    pub fn replace(&self, config: DynamicConfig) -> Vec<&'static str> {
+        let old = self.0.swap(Arc::new(config));
+        let new = self.get();
+        let changed: Vec<_> = old
+            .effective_values()
+            .into_iter()
+            .zip(new.effective_values())
+            .filter(|((_, before), (_, after))| before != after)
+            .map(|((key, before), (_, after))| {
+                tracing::info!("Config {} changed: '{}' -> '{}'", key, before, after);
+                key
+            })
+            .collect();
+        if changed.is_empty() {
+            tracing::info!("Config reloaded, nothing changed");
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_reports_changed_keys() {
+        let live = LiveConfig::default();
+        assert!(live.replace(DynamicConfig::default()).is_empty());
+
+        let changed = live.replace(DynamicConfig {
+            rate_limit_write_per_minute: 5,
+            cors_allowed_origins: vec!["https://app.example.com".to_string()],
+            ..DynamicConfig::default()
+        });
+        assert_eq!(
+            changed,
+            ["RATE_LIMIT_WRITE_PER_MINUTE", "CORS_ALLOWED_ORIGINS"]
+        );
+        assert_eq!(live.get().rate_limit_write_per_minute, 5);
+        assert!(live.get().allows_origin("https://APP.example.com"));
+        assert!(!live.get().allows_origin("https://evil.example.com"));
+        assert!(DynamicConfig::default().allows_origin("https://evil.example.com"));
+    }
+
+    #[test]
+    fn test_from_lookup() {
+        let config = DynamicConfig::from_lookup(|key| match key {
+            "CORS_ALLOWED_ORIGINS" => Some("https://a.example, https://b.example/".to_string()),
+            "RATE_LIMIT_READ_PER_MINUTE" => Some("7".to_string()),
+            "SLOW_REQUEST_THRESHOLD_MS" => Some("250".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(config.slow_request_threshold, Duration::from_millis(250));
+        assert_eq!(
+            DynamicConfig::default().slow_request_threshold,
+            DEFAULT_SLOW_REQUEST_THRESHOLD
+        );
+        assert_eq!(
+            config.cors_allowed_origins,
+            ["https://a.example", "https://b.example"]
+        );
+        assert_eq!(config.rate_limit_read_per_minute, 7);
+
+        #[cfg(feature = "lifi")]
+        assert!(DynamicConfig::from_lookup(|key| {
+            (key == "QUOTE_CACHE_TTL_SECS").then(|| "soon".to_string())
+        })
+        .is_err());
+        #[cfg(feature = "settlement")]
+        assert!(DynamicConfig::from_lookup(|key| {
+            (key == "SETTLEMENT_MIN_CONFIRMATIONS").then(|| "0".to_string())
+        })
+        .is_err());
+    }
+}
@@ -1,58 +1,1900 @@
 //! Application configuration
+//!
+//! [`Config`] is read once at startup; its [`DynamicConfig`] section can be
+//! reloaded later (see [`dynamic`]).
+
+mod dynamic;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use serde::Deserialize;
+use thiserror::Error;
+
+#[cfg(feature = "lifi")]
+use crate::api::quote::{
+    SanityMode, DEFAULT_QUOTE_DISPLAY_DECIMALS, DEFAULT_QUOTE_SANITY_CEILING_USD, DEFAULT_SLIPPAGE,
+};
+use crate::listen::{ListenAddr, DEFAULT_SOCKET_MODE};
+use crate::logging::LogFormat;
+use crate::models::address::Address;
+use crate::models::amount::Amount;
+use crate::services::auth::{ApiKey, ApiRole};
+use crate::services::circuit_breaker::{DEFAULT_COOLDOWN, DEFAULT_FAILURE_THRESHOLD};
+#[cfg(feature = "ens")]
+use crate::services::ens::{
+    DEFAULT_ARWEAVE_GATEWAY_URL, DEFAULT_ENS_API_URL, DEFAULT_ETH_RPC_URL, DEFAULT_IPFS_GATEWAY_URL,
+};
+#[cfg(feature = "ens")]
+use crate::services::ens_cache::{self, EnsCacheBackend};
+use crate::services::fx::{DEFAULT_FX_RATE_MAX_AGE, DEFAULT_FX_RATE_URL};
+use crate::services::health::DEFAULT_HEALTH_HISTORY_SIZE;
+#[cfg(feature = "lifi")]
+use crate::services::lifi::{DEFAULT_RATE_LIMIT_BURST, DEFAULT_RATE_LIMIT_MAX_WAIT};
+#[cfg(feature = "lifi")]
+use crate::services::prices::{DEFAULT_PRICE_FRESH_FOR, DEFAULT_PRICE_MAX_AGE};
+#[cfg(feature = "lifi")]
+use crate::services::quote_cache::DEFAULT_QUOTE_CACHE_CAPACITY;
+#[cfg(feature = "settlement")]
+use crate::services::settlement::DEFAULT_NATIVE_TOKEN_PRICE_URL;
+#[cfg(feature = "settlement")]
+use crate::services::settlement_batch::{
+    DEFAULT_BATCH_INTERVAL, DEFAULT_BATCH_MAX_SESSIONS, MAX_BATCH_SESSIONS,
+};
+use crate::telemetry::DEFAULT_USER_AGENT;
+#[cfg(feature = "settlement")]
+use crate::utils::is_valid_address;
+
+pub use dynamic::{DynamicConfig, LiveConfig};
+
+/// Source of a fresh configuration on reload
+pub type ConfigLoader = Arc<dyn Fn() -> Result<Config, ConfigError> + Send + Sync>;
+
+/// Configuration errors that must stop the process at startup
+#[derive(Error, Debug, PartialEq)]
+pub enum ConfigError {
+    #[error("{key} is invalid: {reason}")]
+    Invalid { key: &'static str, reason: String },
+}
 
 /// Application configuration
+///
+/// Settings of an integration only exist when its cargo feature is enabled.
 #[derive(Debug, Clone, Deserialize)]
 #[allow(dead_code)]
 pub struct Config {
     /// Server port
     pub port: u16,
 
+    /// Public listener (`LISTEN`); defaults to TCP on all interfaces at `port`
+    #[serde(skip, default = "default_listen")]
+    pub listen: ListenAddr,
+
+    /// Permissions of the Unix socket file when `listen` is a socket
+    pub listen_socket_mode: u32,
+
+    /// Serve `/admin` on this port instead of the public listener
+    pub admin_port: Option<u16>,
+
+    /// Readiness checks kept per dependency for `/admin/health/history`
+    pub health_history_size: usize,
+
+    /// Serve the gRPC API on this port (off when unset)
+    #[cfg(feature = "grpc")]
+    pub grpc_port: Option<u16>,
+
+    /// PEM certificate chain; with `tls_key_path`, listeners serve TLS
+    pub tls_cert_path: Option<std::path::PathBuf>,
+
+    /// PEM private key matching `tls_cert_path`
+    pub tls_key_path: Option<std::path::PathBuf>,
+
+    /// Log output format (`LOG_FORMAT=pretty|json`)
+    #[serde(skip, default = "default_log_format")]
+    pub log_format: LogFormat,
+
+    /// Log request and response bodies (truncated, redacted) at debug level
+    pub log_bodies: bool,
+
+    /// Also redact `0x` addresses from logged bodies
+    pub log_bodies_redact_addresses: bool,
+
+    /// Show the addresses of session data in REST, GraphQL and gRPC responses only masked
+    pub mask_addresses: bool,
+
     /// Ethereum RPC URL (for ENS resolution)
+    #[cfg(feature = "ens")]
     pub eth_rpc_url: String,
 
     /// Arc chain RPC URL
+    #[cfg(feature = "settlement")]
     pub arc_rpc_url: String,
 
+    /// CoinGecko-style price of the settlement chain's gas token, for fee
+    /// estimates
+    #[cfg(feature = "settlement")]
+    pub native_token_price_url: String,
+
     /// LI.FI API URL
+    #[cfg(feature = "lifi")]
     pub lifi_api_url: String,
 
+    /// ENS resolution API URL (ensdata.net-compatible)
+    #[cfg(feature = "ens")]
+    pub ens_api_url: String,
+
+    /// `User-Agent` header sent on outbound requests to upstreams
+    pub http_user_agent: String,
+
+    /// OTLP/HTTP collector base URL for trace export (disabled if unset)
+    pub otel_exporter_otlp_endpoint: Option<String>,
+
+    /// Sentry DSN for error reports (`sentry` feature; nothing is sent if unset)
+    pub sentry_dsn: Option<String>,
+
+    /// Gateway used to rewrite `ipfs://` avatar URIs
+    #[cfg(feature = "ens")]
+    pub ipfs_gateway_url: String,
+
+    /// Gateway used to rewrite `ar://` avatar URIs
+    #[cfg(feature = "ens")]
+    pub arweave_gateway_url: String,
+
+    /// Accept subdomain names such as `sub.name.eth`, which the parent
+    /// owner can revoke
+    #[cfg(feature = "ens")]
+    pub ens_allow_subdomains: bool,
+
+    /// Where cached ENS resolutions are kept (`ENS_CACHE_BACKEND=memory|redis`)
+    #[cfg(feature = "ens")]
+    #[serde(skip)]
+    pub ens_cache_backend: EnsCacheBackend,
+
+    /// Redis server of the `redis` ENS cache backend
+    #[cfg(feature = "ens")]
+    pub ens_cache_redis_url: Option<String>,
+
+    /// ENS subgraph queried for names the API fails on (off when unset)
+    #[cfg(feature = "ens")]
+    pub ens_subgraph_url: Option<String>,
+
     /// LI.FI API Key (optional)
+    #[cfg(feature = "lifi")]
     pub lifi_api_key: Option<String>,
 
+    /// Further LI.FI API keys, used in turn with `lifi_api_key`
+    #[cfg(feature = "lifi")]
+    pub lifi_api_keys: Vec<String>,
+
     /// Yellow Network API Key (optional)
+    #[cfg(feature = "yellow")]
     pub yellow_api_key: Option<String>,
+
+    /// Deployed SessionSettlement contract address (optional)
+    #[cfg(feature = "settlement")]
+    pub settlement_contract_address: Option<String>,
+
+    /// Account the `ARC_RPC_URL` node signs backend settlements for
+    #[cfg(feature = "settlement")]
+    pub settlement_signer_address: Option<Address>,
+
+    /// Settle sessions finalized without a transaction in shared batches
+    /// sent by the signer
+    #[cfg(feature = "settlement")]
+    pub settlement_batching: bool,
+
+    /// Seconds between settlement batches
+    #[cfg(feature = "settlement")]
+    pub settlement_batch_interval_secs: u64,
+
+    /// Queued sessions that are sent as a batch without waiting for the interval
+    #[cfg(feature = "settlement")]
+    pub settlement_batch_max_sessions: usize,
+
+    /// Destination of treasury-mode settlements (treasury mode is rejected if unset)
+    pub treasury_address: Option<Address>,
+
+    /// Return the JSON error envelope from ENS and quote endpoints instead
+    /// of a 200 response with an `error` field
+    pub strict_errors: bool,
+
+    /// Maximum number of `Active` sessions a single user may hold (unlimited if unset)
+    pub max_active_sessions_per_user: Option<usize>,
+
+    /// Maximum payments held across all sessions in the store (unlimited if unset)
+    pub max_total_payments: Option<usize>,
+
+    /// Smallest payment, and settled transfer, in token base units (no
+    /// minimum if 0)
+    pub min_payment_amount: Amount,
+
+    /// Cancel sessions left active this many seconds after creation (never if unset)
+    pub session_ttl_secs: Option<u64>,
+
+    /// Seconds a session must wait between finalize attempts (no wait if 0)
+    pub finalize_cooldown_secs: u64,
+
+    /// Start in maintenance mode, refusing writes (toggled at runtime via
+    /// `POST /admin/maintenance`)
+    pub maintenance_mode: bool,
+
+    /// Archive settled sessions this many seconds after creation (never if unset)
+    pub session_archive_after_secs: Option<u64>,
+
+    /// JSON Lines file archived sessions are appended to (dropped if unset)
+    pub session_archive_path: Option<std::path::PathBuf>,
+
+    /// Session snapshot restored at startup and written on shutdown (off if unset)
+    pub session_snapshot_path: Option<std::path::PathBuf>,
+
+    /// Serve Swagger UI at `/docs`
+    pub enable_docs: bool,
+
+    /// Serve the GraphQL playground at `/graphql/playground`
+    pub enable_graphql_playground: bool,
+
+    /// Maximum number of cached LI.FI quotes
+    #[cfg(feature = "lifi")]
+    pub quote_cache_capacity: usize,
+
+    /// Fractional digits of the formatted amounts in quote responses
+    #[cfg(feature = "lifi")]
+    pub quote_display_decimals: u8,
+
+    /// USD value above which a quoted `from_amount` is taken for a units
+    /// mistake (off if 0)
+    #[cfg(feature = "lifi")]
+    pub quote_sanity_ceiling_usd: u64,
+
+    /// Warn about or reject quotes above the sanity ceiling
+    #[cfg(feature = "lifi")]
+    #[serde(skip)]
+    pub quote_sanity_mode: SanityMode,
+
+    /// Slippage of quotes that do not ask for one, as a fraction
+    #[cfg(feature = "lifi")]
+    pub default_slippage: f64,
+
+    /// Least slippage a quote is requested with; lower values are raised to it
+    #[cfg(feature = "lifi")]
+    pub min_slippage: f64,
+
+    /// Seconds a token price is served from cache before it is refetched
+    #[cfg(feature = "lifi")]
+    pub price_cache_ttl_secs: u64,
+
+    /// Seconds a cached token price may still be served while every price
+    /// source fails (at least `price_cache_ttl_secs`)
+    #[cfg(feature = "lifi")]
+    pub price_max_age_secs: u64,
+
+    /// USD exchange rates for sessions' `display_currency` (Coinbase
+    /// `exchange-rates` format)
+    pub fx_rate_url: String,
+
+    /// Seconds an exchange rate is used before it is refetched
+    pub fx_rate_max_age_secs: u64,
+
+    /// Outbound LI.FI calls per minute across the process (unlimited if 0)
+    #[cfg(feature = "lifi")]
+    pub lifi_rate_limit_per_minute: u32,
+
+    /// Outbound LI.FI calls a full bucket allows at once
+    #[cfg(feature = "lifi")]
+    pub lifi_rate_limit_burst: u32,
+
+    /// Milliseconds a LI.FI call may queue for the budget before it is rejected
+    #[cfg(feature = "lifi")]
+    pub lifi_rate_limit_max_wait_ms: u64,
+
+    /// Trust `X-Forwarded-For` for the client IP (only behind a reverse proxy)
+    pub trust_proxy: bool,
+
+    /// Seconds browsers may cache a CORS preflight (`Access-Control-Max-Age`)
+    pub cors_max_age_secs: u64,
+
+    /// Consecutive upstream failures before a circuit opens
+    pub circuit_breaker_threshold: u32,
+
+    /// Seconds an open circuit fails fast before probing the upstream
+    pub circuit_breaker_cooldown_secs: u64,
+
+    /// API keys accepted in `X-Api-Key` (mutating routes are open if empty)
+    #[serde(skip)]
+    pub api_keys: Vec<ApiKey>,
+
+    /// Lowercased addresses that may not create sessions or receive payments
+    #[serde(skip)]
+    pub blocked_addresses: HashSet<Address>,
+
+    /// Settings that can be reloaded at runtime, as read at startup; the
+    /// running values live in `AppState::live_config`
+    #[serde(skip)]
+    pub dynamic: DynamicConfig,
 }
 
-#[allow(dead_code)]
 impl Config {
-    /// Load configuration from environment
-    pub fn from_env() -> Self {
-        let port = std::env::var("PORT")
-            .unwrap_or_else(|_| "3001".to_string())
-            .parse()
-            .unwrap_or(3001);
+    /// Load and validate configuration from environment
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|key| std::env::var(key).ok())
+    }
+
+    /// Load and validate configuration using the given variable lookup.
+    ///
+    /// Unset variables fall back to defaults; variables that are set but
+    /// malformed are rejected so the process fails fast at startup instead
+    /// of erroring on every request.
+    pub fn from_lookup<F>(lookup: F) -> Result<Self, ConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        // Treat empty values (e.g. `LIFI_API_KEY=` in .env) as unset
+        let var = |key: &str| lookup(key).filter(|v| !v.trim().is_empty());
+
+        let mut port = match var("PORT") {
+            Some(raw) => raw.trim().parse().map_err(|_| ConfigError::Invalid {
+                key: "PORT",
+                reason: format!("'{}' is not a valid port number", raw),
+            })?,
+            None => 3001,
+        };
+
+        // An explicit TCP address also decides the port
+        let listen = match var("LISTEN") {
+            Some(raw) => ListenAddr::parse(&raw).map_err(|reason| ConfigError::Invalid {
+                key: "LISTEN",
+                reason,
+            })?,
+            None => ListenAddr::Tcp(([0, 0, 0, 0], port).into()),
+        };
+        if let ListenAddr::Tcp(addr) = listen {
+            port = addr.port();
+        }
+        let listen_socket_mode = match var("LISTEN_SOCKET_MODE") {
+            Some(raw) => u32::from_str_radix(raw.trim().trim_start_matches("0o"), 8)
+                .ok()
+                .filter(|mode| *mode <= 0o777)
+                .ok_or_else(|| ConfigError::Invalid {
+                    key: "LISTEN_SOCKET_MODE",
+                    reason: format!("'{}' is not an octal file mode", raw),
+                })?,
+            None => DEFAULT_SOCKET_MODE,
+        };
+
+        let admin_port = parse_number("ADMIN_PORT", var("ADMIN_PORT"))?;
+        if admin_port == Some(port) {
+            return Err(ConfigError::Invalid {
+                key: "ADMIN_PORT",
+                reason: "must differ from PORT".to_string(),
+            });
+        }
+
+        let health_history_size = parse_number("HEALTH_HISTORY_SIZE", var("HEALTH_HISTORY_SIZE"))?
+            .unwrap_or(DEFAULT_HEALTH_HISTORY_SIZE);
+        if health_history_size == 0 {
+            return Err(ConfigError::Invalid {
+                key: "HEALTH_HISTORY_SIZE",
+                reason: "must be at least 1".to_string(),
+            });
+        }
+
+        #[cfg(feature = "grpc")]
+        let grpc_port = parse_number("GRPC_PORT", var("GRPC_PORT"))?;
+        #[cfg(feature = "grpc")]
+        if grpc_port.is_some() && (grpc_port == Some(port) || grpc_port == admin_port) {
+            return Err(ConfigError::Invalid {
+                key: "GRPC_PORT",
+                reason: "must differ from PORT and ADMIN_PORT".to_string(),
+            });
+        }
+
+        let tls_cert_path = var("TLS_CERT_PATH").map(std::path::PathBuf::from);
+        let tls_key_path = var("TLS_KEY_PATH").map(std::path::PathBuf::from);
+        if tls_cert_path.is_some() != tls_key_path.is_some() {
+            return Err(ConfigError::Invalid {
+                key: if tls_cert_path.is_some() {
+                    "TLS_KEY_PATH"
+                } else {
+                    "TLS_CERT_PATH"
+                },
+                reason: "TLS_CERT_PATH and TLS_KEY_PATH must be set together".to_string(),
+            });
+        }
+        if tls_cert_path.is_some() && matches!(listen, ListenAddr::Unix(_)) {
+            return Err(ConfigError::Invalid {
+                key: "LISTEN",
+                reason: "TLS is not supported on unix sockets".to_string(),
+            });
+        }
+
+        let log_format = match var("LOG_FORMAT") {
+            Some(raw) => LogFormat::parse(&raw).ok_or_else(|| ConfigError::Invalid {
+                key: "LOG_FORMAT",
+                reason: format!("'{}' is not one of pretty, json", raw),
+            })?,
+            None => LogFormat::Pretty,
+        };
+        let log_bodies = parse_bool("LOG_BODIES", var("LOG_BODIES"))?;
+        let log_bodies_redact_addresses = parse_bool(
+            "LOG_BODIES_REDACT_ADDRESSES",
+            var("LOG_BODIES_REDACT_ADDRESSES"),
+        )?;
+        let mask_addresses = parse_bool("MASK_ADDRESSES", var("MASK_ADDRESSES"))?;
+
+        #[cfg(feature = "ens")]
+        let eth_rpc_url = var("ETH_RPC_URL").unwrap_or_else(|| DEFAULT_ETH_RPC_URL.to_string());
+        #[cfg(feature = "ens")]
+        validate_url("ETH_RPC_URL", &eth_rpc_url)?;
+
+        #[cfg(feature = "settlement")]
+        let arc_rpc_url =
+            var("ARC_RPC_URL").unwrap_or_else(|| "https://rpc.arc.circle.com".to_string());
+        #[cfg(feature = "settlement")]
+        validate_url("ARC_RPC_URL", &arc_rpc_url)?;
+        #[cfg(feature = "settlement")]
+        let native_token_price_url = var("NATIVE_TOKEN_PRICE_URL")
+            .unwrap_or_else(|| DEFAULT_NATIVE_TOKEN_PRICE_URL.to_string());
+        #[cfg(feature = "settlement")]
+        validate_url("NATIVE_TOKEN_PRICE_URL", &native_token_price_url)?;
+
+        #[cfg(feature = "lifi")]
+        let lifi_api_url = var("LIFI_API_URL").unwrap_or_else(|| "https://li.quest/v1".to_string());
+        #[cfg(feature = "lifi")]
+        validate_url("LIFI_API_URL", &lifi_api_url)?;
+
+        #[cfg(feature = "ens")]
+        let ens_api_url = var("ENS_API_URL").unwrap_or_else(|| DEFAULT_ENS_API_URL.to_string());
+        #[cfg(feature = "ens")]
+        validate_url("ENS_API_URL", &ens_api_url)?;
+        #[cfg(feature = "ens")]
+        let ens_allow_subdomains = match var("ENS_ALLOW_SUBDOMAINS") {
+            None => true,
+            raw => parse_bool("ENS_ALLOW_SUBDOMAINS", raw)?,
+        };
+        #[cfg(feature = "ens")]
+        let ens_cache_backend = match var("ENS_CACHE_BACKEND") {
+            Some(raw) => EnsCacheBackend::parse(&raw).ok_or_else(|| ConfigError::Invalid {
+                key: "ENS_CACHE_BACKEND",
+                reason: format!("'{}' is not one of memory, redis", raw),
+            })?,
+            None => EnsCacheBackend::Memory,
+        };
+        #[cfg(feature = "ens")]
+        let ens_cache_redis_url = var("ENS_CACHE_REDIS_URL");
+        // Opening a cache does not connect, so this only checks the settings
+        #[cfg(feature = "ens")]
+        ens_cache::from_backend(ens_cache_backend, ens_cache_redis_url.as_deref()).map_err(
+            |reason| ConfigError::Invalid {
+                key: "ENS_CACHE_BACKEND",
+                reason,
+            },
+        )?;
+        #[cfg(feature = "ens")]
+        let ens_subgraph_url = var("ENS_SUBGRAPH_URL");
+        #[cfg(feature = "ens")]
+        if let Some(url) = &ens_subgraph_url {
+            validate_url("ENS_SUBGRAPH_URL", url)?;
+        }
+
+        let http_user_agent = var("HTTP_USER_AGENT")
+            .map(|ua| ua.trim().to_string())
+            .unwrap_or_else(|| DEFAULT_USER_AGENT.to_string());
+        if reqwest::header::HeaderValue::from_str(&http_user_agent).is_err() {
+            return Err(ConfigError::Invalid {
+                key: "HTTP_USER_AGENT",
+                reason: "must be a valid HTTP header value".to_string(),
+            });
+        }
+
+        let otel_exporter_otlp_endpoint = var("OTEL_EXPORTER_OTLP_ENDPOINT");
+        if let Some(ref endpoint) = otel_exporter_otlp_endpoint {
+            validate_url("OTEL_EXPORTER_OTLP_ENDPOINT", endpoint)?;
+        }
+
+        let sentry_dsn = var("SENTRY_DSN");
+        #[cfg(feature = "sentry")]
+        if let Some(ref dsn) = sentry_dsn {
+            if let Err(e) = dsn.parse::<sentry::types::Dsn>() {
+                return Err(ConfigError::Invalid {
+                    key: "SENTRY_DSN",
+                    reason: e.to_string(),
+                });
+            }
+        }
+
+        #[cfg(feature = "ens")]
+        let ipfs_gateway_url =
+            var("IPFS_GATEWAY_URL").unwrap_or_else(|| DEFAULT_IPFS_GATEWAY_URL.to_string());
+        #[cfg(feature = "ens")]
+        validate_url("IPFS_GATEWAY_URL", &ipfs_gateway_url)?;
 
-        let eth_rpc_url =
-            std::env::var("ETH_RPC_URL").unwrap_or_else(|_| "https://eth.llamarpc.com".to_string());
+        #[cfg(feature = "ens")]
+        let arweave_gateway_url =
+            var("ARWEAVE_GATEWAY_URL").unwrap_or_else(|| DEFAULT_ARWEAVE_GATEWAY_URL.to_string());
+        #[cfg(feature = "ens")]
+        validate_url("ARWEAVE_GATEWAY_URL", &arweave_gateway_url)?;
 
-        let arc_rpc_url = std::env::var("ARC_RPC_URL")
-            .unwrap_or_else(|_| "https://rpc.arc.circle.com".to_string());
+        #[cfg(feature = "settlement")]
+        let settlement_contract_address = var("SETTLEMENT_CONTRACT_ADDRESS");
+        #[cfg(feature = "settlement")]
+        if let Some(ref address) = settlement_contract_address {
+            if !is_valid_address(address) {
+                return Err(ConfigError::Invalid {
+                    key: "SETTLEMENT_CONTRACT_ADDRESS",
+                    reason: "must be 0x followed by 40 hex digits".to_string(),
+                });
+            }
+        }
+        #[cfg(feature = "settlement")]
+        let settlement_signer_address = var("SETTLEMENT_SIGNER_ADDRESS")
+            .map(|address| {
+                Address::try_from(address.as_str()).map_err(|_| ConfigError::Invalid {
+                    key: "SETTLEMENT_SIGNER_ADDRESS",
+                    reason: "must be 0x followed by 40 hex digits".to_string(),
+                })
+            })
+            .transpose()?;
+        #[cfg(feature = "settlement")]
+        let settlement_batching = parse_bool("SETTLEMENT_BATCHING", var("SETTLEMENT_BATCHING"))?;
+        #[cfg(feature = "settlement")]
+        if settlement_batching
+            && (settlement_contract_address.is_none() || settlement_signer_address.is_none())
+        {
+            return Err(ConfigError::Invalid {
+                key: "SETTLEMENT_BATCHING",
+                reason: "requires SETTLEMENT_CONTRACT_ADDRESS and SETTLEMENT_SIGNER_ADDRESS"
+                    .to_string(),
+            });
+        }
+        #[cfg(feature = "settlement")]
+        let settlement_batch_interval_secs = parse_number(
+            "SETTLEMENT_BATCH_INTERVAL_SECS",
+            var("SETTLEMENT_BATCH_INTERVAL_SECS"),
+        )?
+        .unwrap_or(DEFAULT_BATCH_INTERVAL.as_secs());
+        #[cfg(feature = "settlement")]
+        if settlement_batch_interval_secs == 0 {
+            return Err(ConfigError::Invalid {
+                key: "SETTLEMENT_BATCH_INTERVAL_SECS",
+                reason: "must be at least 1".to_string(),
+            });
+        }
+        #[cfg(feature = "settlement")]
+        let settlement_batch_max_sessions = parse_number(
+            "SETTLEMENT_BATCH_MAX_SESSIONS",
+            var("SETTLEMENT_BATCH_MAX_SESSIONS"),
+        )?
+        .unwrap_or(DEFAULT_BATCH_MAX_SESSIONS);
+        #[cfg(feature = "settlement")]
+        if !(1..=MAX_BATCH_SESSIONS).contains(&settlement_batch_max_sessions) {
+            return Err(ConfigError::Invalid {
+                key: "SETTLEMENT_BATCH_MAX_SESSIONS",
+                reason: format!("must be 1 to {}", MAX_BATCH_SESSIONS),
+            });
+        }
+
+        let treasury_address = var("TREASURY_ADDRESS")
+            .map(|address| {
+                Address::try_from(address.as_str()).map_err(|_| ConfigError::Invalid {
+                    key: "TREASURY_ADDRESS",
+                    reason: "must be 0x followed by 40 hex digits".to_string(),
+                })
+            })
+            .transpose()?;
+
+        let strict_errors = parse_bool("STRICT_ERRORS", var("STRICT_ERRORS"))?;
+        let maintenance_mode = parse_bool("MAINTENANCE_MODE", var("MAINTENANCE_MODE"))?;
+        let enable_docs = parse_bool("ENABLE_DOCS", var("ENABLE_DOCS"))?;
+        let enable_graphql_playground = parse_bool(
+            "ENABLE_GRAPHQL_PLAYGROUND",
+            var("ENABLE_GRAPHQL_PLAYGROUND"),
+        )?;
+
+        let max_active_sessions_per_user = parse_number(
+            "MAX_ACTIVE_SESSIONS_PER_USER",
+            var("MAX_ACTIVE_SESSIONS_PER_USER"),
+        )?;
+        let max_total_payments = parse_number("MAX_TOTAL_PAYMENTS", var("MAX_TOTAL_PAYMENTS"))?;
+        let min_payment_amount = parse_number("MIN_PAYMENT_AMOUNT", var("MIN_PAYMENT_AMOUNT"))?
+            .unwrap_or_else(|| Amount::from(DEFAULT_MIN_PAYMENT_AMOUNT));
+
+        let session_ttl_secs = parse_number("SESSION_TTL_SECS", var("SESSION_TTL_SECS"))?;
+        let finalize_cooldown_secs =
+            parse_number("FINALIZE_COOLDOWN_SECS", var("FINALIZE_COOLDOWN_SECS"))?.unwrap_or(0);
+        let session_archive_after_secs = parse_number(
+            "SESSION_ARCHIVE_AFTER_SECS",
+            var("SESSION_ARCHIVE_AFTER_SECS"),
+        )?;
 
-        let lifi_api_url =
-            std::env::var("LIFI_API_URL").unwrap_or_else(|_| "https://li.quest/v1".to_string());
+        #[cfg(feature = "lifi")]
+        let quote_cache_capacity =
+            parse_number("QUOTE_CACHE_CAPACITY", var("QUOTE_CACHE_CAPACITY"))?
+                .unwrap_or(DEFAULT_QUOTE_CACHE_CAPACITY);
+        #[cfg(feature = "lifi")]
+        let quote_display_decimals =
+            parse_number("QUOTE_DISPLAY_DECIMALS", var("QUOTE_DISPLAY_DECIMALS"))?
+                .unwrap_or(DEFAULT_QUOTE_DISPLAY_DECIMALS);
+        #[cfg(feature = "lifi")]
+        let quote_sanity_ceiling_usd =
+            parse_number("QUOTE_SANITY_CEILING_USD", var("QUOTE_SANITY_CEILING_USD"))?
+                .unwrap_or(DEFAULT_QUOTE_SANITY_CEILING_USD);
+        #[cfg(feature = "lifi")]
+        let quote_sanity_mode = match var("QUOTE_SANITY_MODE") {
+            Some(raw) => SanityMode::parse(&raw).ok_or_else(|| ConfigError::Invalid {
+                key: "QUOTE_SANITY_MODE",
+                reason: format!("'{}' is not one of warn, reject", raw),
+            })?,
+            None => SanityMode::Warn,
+        };
+        #[cfg(feature = "lifi")]
+        let default_slippage = parse_fraction("DEFAULT_SLIPPAGE", var("DEFAULT_SLIPPAGE"))?
+            .unwrap_or(DEFAULT_SLIPPAGE);
+        #[cfg(feature = "lifi")]
+        let min_slippage = parse_fraction("MIN_SLIPPAGE", var("MIN_SLIPPAGE"))?.unwrap_or(0.0);
+        #[cfg(feature = "lifi")]
+        if default_slippage < min_slippage {
+            return Err(ConfigError::Invalid {
+                key: "DEFAULT_SLIPPAGE",
+                reason: format!(
+                    "{} is below MIN_SLIPPAGE ({})",
+                    default_slippage, min_slippage
+                ),
+            });
+        }
+        #[cfg(feature = "lifi")]
+        let price_cache_ttl_secs =
+            parse_number("PRICE_CACHE_TTL_SECS", var("PRICE_CACHE_TTL_SECS"))?
+                .unwrap_or(DEFAULT_PRICE_FRESH_FOR.as_secs());
+        #[cfg(feature = "lifi")]
+        let price_max_age_secs = parse_number("PRICE_MAX_AGE_SECS", var("PRICE_MAX_AGE_SECS"))?
+            .unwrap_or(DEFAULT_PRICE_MAX_AGE.as_secs());
+        let fx_rate_url = var("FX_RATE_URL").unwrap_or_else(|| DEFAULT_FX_RATE_URL.to_string());
+        validate_url("FX_RATE_URL", &fx_rate_url)?;
+        let fx_rate_max_age_secs =
+            parse_number("FX_RATE_MAX_AGE_SECS", var("FX_RATE_MAX_AGE_SECS"))?
+                .unwrap_or(DEFAULT_FX_RATE_MAX_AGE.as_secs());
+        if fx_rate_max_age_secs == 0 {
+            return Err(ConfigError::Invalid {
+                key: "FX_RATE_MAX_AGE_SECS",
+                reason: "must be at least 1".to_string(),
+            });
+        }
+        #[cfg(feature = "lifi")]
+        let lifi_rate_limit_per_minute = parse_number(
+            "LIFI_RATE_LIMIT_PER_MINUTE",
+            var("LIFI_RATE_LIMIT_PER_MINUTE"),
+        )?
+        .unwrap_or(0);
+        #[cfg(feature = "lifi")]
+        let lifi_rate_limit_burst =
+            parse_number("LIFI_RATE_LIMIT_BURST", var("LIFI_RATE_LIMIT_BURST"))?
+                .unwrap_or(DEFAULT_RATE_LIMIT_BURST);
+        #[cfg(feature = "lifi")]
+        let lifi_rate_limit_max_wait_ms = parse_number(
+            "LIFI_RATE_LIMIT_MAX_WAIT_MS",
+            var("LIFI_RATE_LIMIT_MAX_WAIT_MS"),
+        )?
+        .unwrap_or(DEFAULT_RATE_LIMIT_MAX_WAIT.as_millis() as u64);
+
+        let trust_proxy = parse_bool("TRUST_PROXY", var("TRUST_PROXY"))?;
+        let cors_max_age_secs =
+            parse_number("CORS_MAX_AGE_SECS", var("CORS_MAX_AGE_SECS"))?.unwrap_or(3600);
+        let dynamic = DynamicConfig::from_lookup(var)?;
+
+        let circuit_breaker_threshold = parse_number(
+            "CIRCUIT_BREAKER_THRESHOLD",
+            var("CIRCUIT_BREAKER_THRESHOLD"),
+        )?
+        .unwrap_or(DEFAULT_FAILURE_THRESHOLD);
+        let circuit_breaker_cooldown_secs = parse_number(
+            "CIRCUIT_BREAKER_COOLDOWN_SECS",
+            var("CIRCUIT_BREAKER_COOLDOWN_SECS"),
+        )?
+        .unwrap_or(DEFAULT_COOLDOWN.as_secs());
 
-        let lifi_api_key = std::env::var("LIFI_API_KEY").ok();
-        let yellow_api_key = std::env::var("YELLOW_API_KEY").ok();
+        let api_keys = match var("API_KEYS") {
+            Some(raw) => parse_api_keys(&raw)?,
+            None => Vec::new(),
+        };
 
-        Self {
+        let blocked_addresses = match var("BLOCKED_ADDRESSES") {
+            Some(raw) => parse_blocked_addresses(&raw)?,
+            None => HashSet::new(),
+        };
+
+        Ok(Self {
             port,
+            listen,
+            listen_socket_mode,
+            admin_port,
+            health_history_size,
+            #[cfg(feature = "grpc")]
+            grpc_port,
+            tls_cert_path,
+            tls_key_path,
+            log_format,
+            log_bodies,
+            log_bodies_redact_addresses,
+            mask_addresses,
+            #[cfg(feature = "ens")]
             eth_rpc_url,
+            #[cfg(feature = "settlement")]
             arc_rpc_url,
+            #[cfg(feature = "settlement")]
+            native_token_price_url,
+            #[cfg(feature = "lifi")]
             lifi_api_url,
-            lifi_api_key,
-            yellow_api_key,
+            #[cfg(feature = "ens")]
+            ens_api_url,
+            http_user_agent,
+            otel_exporter_otlp_endpoint,
+            sentry_dsn,
+            #[cfg(feature = "ens")]
+            ipfs_gateway_url,
+            #[cfg(feature = "ens")]
+            arweave_gateway_url,
+            #[cfg(feature = "ens")]
+            ens_allow_subdomains,
+            #[cfg(feature = "ens")]
+            ens_cache_backend,
+            #[cfg(feature = "ens")]
+            ens_cache_redis_url,
+            #[cfg(feature = "ens")]
+            ens_subgraph_url,
+            #[cfg(feature = "lifi")]
+            lifi_api_key: var("LIFI_API_KEY"),
+            #[cfg(feature = "lifi")]
+            lifi_api_keys: var("LIFI_API_KEYS")
+                .map(|raw| {
+                    raw.split(',')
+                        .map(str::trim)
+                        .filter(|key| !key.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            #[cfg(feature = "yellow")]
+            yellow_api_key: var("YELLOW_API_KEY"),
+            #[cfg(feature = "settlement")]
+            settlement_contract_address,
+            #[cfg(feature = "settlement")]
+            settlement_signer_address,
+            #[cfg(feature = "settlement")]
+            settlement_batching,
+            #[cfg(feature = "settlement")]
+            settlement_batch_interval_secs,
+            #[cfg(feature = "settlement")]
+            settlement_batch_max_sessions,
+            treasury_address,
+            strict_errors,
+            max_active_sessions_per_user,
+            max_total_payments,
+            min_payment_amount,
+            session_ttl_secs,
+            finalize_cooldown_secs,
+            maintenance_mode,
+            session_archive_after_secs,
+            session_archive_path: var("SESSION_ARCHIVE_PATH").map(Into::into),
+            session_snapshot_path: var("SESSION_SNAPSHOT_PATH").map(Into::into),
+            enable_docs,
+            enable_graphql_playground,
+            #[cfg(feature = "lifi")]
+            quote_cache_capacity,
+            #[cfg(feature = "lifi")]
+            quote_display_decimals,
+            #[cfg(feature = "lifi")]
+            quote_sanity_ceiling_usd,
+            #[cfg(feature = "lifi")]
+            quote_sanity_mode,
+            #[cfg(feature = "lifi")]
+            default_slippage,
+            #[cfg(feature = "lifi")]
+            min_slippage,
+            #[cfg(feature = "lifi")]
+            price_cache_ttl_secs,
+            #[cfg(feature = "lifi")]
+            price_max_age_secs,
+            fx_rate_url,
+            fx_rate_max_age_secs,
+            #[cfg(feature = "lifi")]
+            lifi_rate_limit_per_minute,
+            #[cfg(feature = "lifi")]
+            lifi_rate_limit_burst,
+            #[cfg(feature = "lifi")]
+            lifi_rate_limit_max_wait_ms,
+            trust_proxy,
+            cors_max_age_secs,
+            circuit_breaker_threshold,
+            circuit_breaker_cooldown_secs,
+            api_keys,
+            blocked_addresses,
+            dynamic,
+        })
+    }
+
+    /// Load configuration for a reload: values in the `.env` file (re-read,
+    /// so edits apply) take precedence over the process environment
+    pub fn reload_from_env() -> Result<Self, ConfigError> {
+        let dotenv: HashMap<String, String> = dotenvy::dotenv_iter()
+            .map(|vars| vars.filter_map(Result::ok).collect())
+            .unwrap_or_default();
+        Self::from_lookup(|key| dotenv.get(key).cloned().or_else(|| std::env::var(key).ok()))
+    }
+
+    /// Whether `address` is on the blocked list
+    pub fn is_blocked(&self, address: &Address) -> bool {
+        self.blocked_addresses.contains(address)
+    }
+
+    /// Every LI.FI API key in rotation order: `LIFI_API_KEY`, then the
+    /// `LIFI_API_KEYS` not already listed
+    #[cfg(feature = "lifi")]
+    pub fn all_lifi_api_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.lifi_api_key.iter().cloned().collect();
+        for key in &self.lifi_api_keys {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+        keys
+    }
+
+    /// Non-fatal configuration warnings (missing optional settings)
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        #[cfg(feature = "lifi")]
+        if self.all_lifi_api_keys().is_empty() {
+            warnings
+                .push("LIFI_API_KEY not set; LI.FI quotes use the public rate limit".to_string());
+        }
+        #[cfg(feature = "yellow")]
+        if self.yellow_api_key.is_none() {
+            warnings.push("YELLOW_API_KEY not set; Yellow Network features disabled".to_string());
+        }
+        if self.api_keys.is_empty() {
+            warnings.push(
+                "API_KEYS not set; session mutations are unauthenticated and /admin is locked"
+                    .to_string(),
+            );
+        }
+        #[cfg(feature = "settlement")]
+        if self.settlement_contract_address.is_none() {
+            warnings.push("SETTLEMENT_CONTRACT_ADDRESS not set".to_string());
+        }
+        #[cfg(not(feature = "sentry"))]
+        if self.sentry_dsn.is_some() {
+            warnings.push(
+                "SENTRY_DSN is set but this build has no sentry feature; errors are not reported"
+                    .to_string(),
+            );
+        }
+        warnings
+    }
+
+    /// Effective value of every setting by variable name, with secrets masked
+    pub fn effective_values(&self) -> Vec<(&'static str, String)> {
+        fn optional<T: ToString>(value: &Option<T>) -> String {
+            value.as_ref().map(T::to_string).unwrap_or_default()
+        }
+        fn secret(value: &Option<String>) -> String {
+            value.as_ref().map(|_| MASK.to_string()).unwrap_or_default()
+        }
+
+        let api_keys = self
+            .api_keys
+            .iter()
+            .map(|k| format!("{}:{:?}", MASK, k.role).to_lowercase())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let mut values = vec![
+            ("PORT", self.port.to_string()),
+            ("LISTEN", self.listen.to_string()),
+            (
+                "LISTEN_SOCKET_MODE",
+                format!("{:o}", self.listen_socket_mode),
+            ),
+            ("ADMIN_PORT", optional(&self.admin_port)),
+            ("HEALTH_HISTORY_SIZE", self.health_history_size.to_string()),
+            #[cfg(feature = "grpc")]
+            ("GRPC_PORT", optional(&self.grpc_port)),
+            (
+                "TLS_CERT_PATH",
+                optional(&self.tls_cert_path.as_ref().map(|p| p.display().to_string())),
+            ),
+            (
+                "TLS_KEY_PATH",
+                optional(&self.tls_key_path.as_ref().map(|p| p.display().to_string())),
+            ),
+            (
+                "LOG_FORMAT",
+                format!("{:?}", self.log_format).to_lowercase(),
+            ),
+            ("LOG_BODIES", self.log_bodies.to_string()),
+            (
+                "LOG_BODIES_REDACT_ADDRESSES",
+                self.log_bodies_redact_addresses.to_string(),
+            ),
+            ("MASK_ADDRESSES", self.mask_addresses.to_string()),
+            #[cfg(feature = "ens")]
+            ("ETH_RPC_URL", self.eth_rpc_url.clone()),
+            #[cfg(feature = "settlement")]
+            ("ARC_RPC_URL", self.arc_rpc_url.clone()),
+            #[cfg(feature = "settlement")]
+            (
+                "NATIVE_TOKEN_PRICE_URL",
+                self.native_token_price_url.clone(),
+            ),
+            #[cfg(feature = "lifi")]
+            ("LIFI_API_URL", self.lifi_api_url.clone()),
+            #[cfg(feature = "ens")]
+            ("ENS_API_URL", self.ens_api_url.clone()),
+            ("HTTP_USER_AGENT", self.http_user_agent.clone()),
+            (
+                "OTEL_EXPORTER_OTLP_ENDPOINT",
+                optional(&self.otel_exporter_otlp_endpoint),
+            ),
+            ("SENTRY_DSN", secret(&self.sentry_dsn)),
+            #[cfg(feature = "ens")]
+            ("IPFS_GATEWAY_URL", self.ipfs_gateway_url.clone()),
+            #[cfg(feature = "ens")]
+            ("ARWEAVE_GATEWAY_URL", self.arweave_gateway_url.clone()),
+            #[cfg(feature = "ens")]
+            (
+                "ENS_ALLOW_SUBDOMAINS",
+                self.ens_allow_subdomains.to_string(),
+            ),
+            #[cfg(feature = "ens")]
+            (
+                "ENS_CACHE_BACKEND",
+                self.ens_cache_backend.as_str().to_string(),
+            ),
+            #[cfg(feature = "ens")]
+            ("ENS_CACHE_REDIS_URL", secret(&self.ens_cache_redis_url)),
+            // Graph gateway URLs embed the API key
+            #[cfg(feature = "ens")]
+            ("ENS_SUBGRAPH_URL", secret(&self.ens_subgraph_url)),
+            #[cfg(feature = "lifi")]
+            ("LIFI_API_KEY", secret(&self.lifi_api_key)),
+            #[cfg(feature = "lifi")]
+            (
+                "LIFI_API_KEYS",
+                vec![MASK; self.lifi_api_keys.len()].join(","),
+            ),
+            #[cfg(feature = "yellow")]
+            ("YELLOW_API_KEY", secret(&self.yellow_api_key)),
+            #[cfg(feature = "settlement")]
+            (
+                "SETTLEMENT_CONTRACT_ADDRESS",
+                optional(&self.settlement_contract_address),
+            ),
+            #[cfg(feature = "settlement")]
+            (
+                "SETTLEMENT_SIGNER_ADDRESS",
+                optional(&self.settlement_signer_address),
+            ),
+            #[cfg(feature = "settlement")]
+            ("SETTLEMENT_BATCHING", self.settlement_batching.to_string()),
+            #[cfg(feature = "settlement")]
+            (
+                "SETTLEMENT_BATCH_INTERVAL_SECS",
+                self.settlement_batch_interval_secs.to_string(),
+            ),
+            #[cfg(feature = "settlement")]
+            (
+                "SETTLEMENT_BATCH_MAX_SESSIONS",
+                self.settlement_batch_max_sessions.to_string(),
+            ),
+            ("TREASURY_ADDRESS", optional(&self.treasury_address)),
+            ("STRICT_ERRORS", self.strict_errors.to_string()),
+            (
+                "MAX_ACTIVE_SESSIONS_PER_USER",
+                optional(&self.max_active_sessions_per_user),
+            ),
+            ("MAX_TOTAL_PAYMENTS", optional(&self.max_total_payments)),
+            ("MIN_PAYMENT_AMOUNT", self.min_payment_amount.to_string()),
+            ("SESSION_TTL_SECS", optional(&self.session_ttl_secs)),
+            (
+                "FINALIZE_COOLDOWN_SECS",
+                self.finalize_cooldown_secs.to_string(),
+            ),
+            ("MAINTENANCE_MODE", self.maintenance_mode.to_string()),
+            (
+                "SESSION_ARCHIVE_AFTER_SECS",
+                optional(&self.session_archive_after_secs),
+            ),
+            (
+                "SESSION_ARCHIVE_PATH",
+                optional(
+                    &self
+                        .session_archive_path
+                        .as_ref()
+                        .map(|p| p.display().to_string()),
+                ),
+            ),
+            (
+                "SESSION_SNAPSHOT_PATH",
+                optional(
+                    &self
+                        .session_snapshot_path
+                        .as_ref()
+                        .map(|p| p.display().to_string()),
+                ),
+            ),
+            ("ENABLE_DOCS", self.enable_docs.to_string()),
+            (
+                "ENABLE_GRAPHQL_PLAYGROUND",
+                self.enable_graphql_playground.to_string(),
+            ),
+            #[cfg(feature = "lifi")]
+            (
+                "QUOTE_CACHE_CAPACITY",
+                self.quote_cache_capacity.to_string(),
+            ),
+            #[cfg(feature = "lifi")]
+            (
+                "QUOTE_DISPLAY_DECIMALS",
+                self.quote_display_decimals.to_string(),
+            ),
+            #[cfg(feature = "lifi")]
+            (
+                "QUOTE_SANITY_CEILING_USD",
+                self.quote_sanity_ceiling_usd.to_string(),
+            ),
+            #[cfg(feature = "lifi")]
+            (
+                "QUOTE_SANITY_MODE",
+                format!("{:?}", self.quote_sanity_mode).to_lowercase(),
+            ),
+            #[cfg(feature = "lifi")]
+            ("DEFAULT_SLIPPAGE", self.default_slippage.to_string()),
+            #[cfg(feature = "lifi")]
+            ("MIN_SLIPPAGE", self.min_slippage.to_string()),
+            #[cfg(feature = "lifi")]
+            (
+                "PRICE_CACHE_TTL_SECS",
+                self.price_cache_ttl_secs.to_string(),
+            ),
+            #[cfg(feature = "lifi")]
+            ("PRICE_MAX_AGE_SECS", self.price_max_age_secs.to_string()),
+            ("FX_RATE_URL", self.fx_rate_url.clone()),
+            (
+                "FX_RATE_MAX_AGE_SECS",
+                self.fx_rate_max_age_secs.to_string(),
+            ),
+            #[cfg(feature = "lifi")]
+            (
+                "LIFI_RATE_LIMIT_PER_MINUTE",
+                self.lifi_rate_limit_per_minute.to_string(),
+            ),
+            #[cfg(feature = "lifi")]
+            (
+                "LIFI_RATE_LIMIT_BURST",
+                self.lifi_rate_limit_burst.to_string(),
+            ),
+            #[cfg(feature = "lifi")]
+            (
+                "LIFI_RATE_LIMIT_MAX_WAIT_MS",
+                self.lifi_rate_limit_max_wait_ms.to_string(),
+            ),
+            ("TRUST_PROXY", self.trust_proxy.to_string()),
+            ("CORS_MAX_AGE_SECS", self.cors_max_age_secs.to_string()),
+            (
+                "CIRCUIT_BREAKER_THRESHOLD",
+                self.circuit_breaker_threshold.to_string(),
+            ),
+            (
+                "CIRCUIT_BREAKER_COOLDOWN_SECS",
+                self.circuit_breaker_cooldown_secs.to_string(),
+            ),
+            ("API_KEYS", api_keys),
+            (
+                "BLOCKED_ADDRESSES",
+                format!("{} addresses", self.blocked_addresses.len()),
+            ),
+        ];
+        values.extend(self.dynamic.effective_values());
+        values
+    }
+}
+
+/// Default `MIN_PAYMENT_AMOUNT`: 0.01 USDC
+const DEFAULT_MIN_PAYMENT_AMOUNT: u64 = 10_000;
+
+/// Replacement for secret values in [`Config::effective_values`]
+const MASK: &str = "****";
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::from_lookup(|_| None).expect("default configuration is valid")
+    }
+}
+
+/// Parse an optional non-negative integer
+fn parse_number<T: std::str::FromStr>(
+    key: &'static str,
+    value: Option<String>,
+) -> Result<Option<T>, ConfigError> {
+    value
+        .map(|raw| {
+            raw.trim().parse().map_err(|_| ConfigError::Invalid {
+                key,
+                reason: format!("'{}' is not a non-negative integer", raw),
+            })
+        })
+        .transpose()
+}
+
+/// Parse a fraction in `[0, 1)`, such as a slippage tolerance
+#[cfg(feature = "lifi")]
+fn parse_fraction(key: &'static str, value: Option<String>) -> Result<Option<f64>, ConfigError> {
+    value
+        .map(|raw| match raw.trim().parse::<f64>() {
+            Ok(fraction) if (0.0..1.0).contains(&fraction) => Ok(fraction),
+            _ => Err(ConfigError::Invalid {
+                key,
+                reason: format!("'{}' is not a fraction from 0 up to 1", raw),
+            }),
+        })
+        .transpose()
+}
+
+fn default_listen() -> ListenAddr {
+    ListenAddr::Tcp(([0, 0, 0, 0], 3001).into())
+}
+
+fn default_log_format() -> LogFormat {
+    LogFormat::Pretty
+}
+
+/// Parse `BLOCKED_ADDRESSES`: an inline list of addresses, or the path of a
+/// file with one address per line (`#` starts a comment)
+fn parse_blocked_addresses(raw: &str) -> Result<HashSet<Address>, ConfigError> {
+    let raw = raw.trim();
+    let list = if raw.starts_with("0x") {
+        raw.to_string()
+    } else {
+        std::fs::read_to_string(raw).map_err(|e| ConfigError::Invalid {
+            key: "BLOCKED_ADDRESSES",
+            reason: format!("cannot read '{}': {}", raw, e),
+        })?
+    };
+
+    list.lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| line.split([',', ' ', '\t']))
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            Address::try_from(entry).map_err(|_| ConfigError::Invalid {
+                key: "BLOCKED_ADDRESSES",
+                reason: format!("'{}' is not a valid address", entry),
+            })
+        })
+        .collect()
+}
+
+/// Parse `API_KEYS`: comma-separated `key[:role]` entries (role defaults to `client`)
+fn parse_api_keys(raw: &str) -> Result<Vec<ApiKey>, ConfigError> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (key, role) = match entry.rsplit_once(':') {
+                Some((key, role)) => {
+                    let role = ApiRole::parse(role).ok_or_else(|| ConfigError::Invalid {
+                        key: "API_KEYS",
+                        reason: format!("unknown role '{}' (expected client or admin)", role),
+                    })?;
+                    (key, role)
+                }
+                None => (entry, ApiRole::Client),
+            };
+            if key.is_empty() {
+                return Err(ConfigError::Invalid {
+                    key: "API_KEYS",
+                    reason: "keys must not be empty".to_string(),
+                });
+            }
+            Ok(ApiKey {
+                key: key.to_string(),
+                role,
+            })
+        })
+        .collect()
+}
+
+/// Parse an optional boolean flag (unset means false)
+fn parse_bool(key: &'static str, value: Option<String>) -> Result<bool, ConfigError> {
+    match value.as_deref().map(str::trim) {
+        None => Ok(false),
+        Some(v) if v.eq_ignore_ascii_case("true") || v == "1" => Ok(true),
+        Some(v) if v.eq_ignore_ascii_case("false") || v == "0" => Ok(false),
+        Some(v) => Err(ConfigError::Invalid {
+            key,
+            reason: format!("'{}' is not a boolean", v),
+        }),
+    }
+}
+
+/// Ensure a URL setting is an absolute http(s) URL with a host
+fn validate_url(key: &'static str, url: &str) -> Result<(), ConfigError> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .ok_or_else(|| ConfigError::Invalid {
+            key,
+            reason: format!("'{}' must start with http:// or https://", url),
+        })?;
+
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    if host.is_empty() || host.contains(char::is_whitespace) {
+        return Err(ConfigError::Invalid {
+            key,
+            reason: format!("'{}' has no valid host", url),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn load(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Config::from_lookup(|key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn test_api_keys() {
+        let config = load(&[("API_KEYS", "abc:admin, def ,ghi:client")]).unwrap();
+        let roles: Vec<_> = config
+            .api_keys
+            .iter()
+            .map(|k| (k.key.as_str(), k.role))
+            .collect();
+        assert_eq!(
+            roles,
+            [
+                ("abc", ApiRole::Admin),
+                ("def", ApiRole::Client),
+                ("ghi", ApiRole::Client)
+            ]
+        );
+
+        let err = load(&[("API_KEYS", "abc:root")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                key: "API_KEYS",
+                ..
+            }
+        ));
+        // The key itself never appears in the error
+        assert!(!err.to_string().contains("abc"));
+    }
+
+    #[test]
+    fn test_log_format() {
+        assert_eq!(load(&[]).unwrap().log_format, LogFormat::Pretty);
+        let config = load(&[("LOG_FORMAT", "json")]).unwrap();
+        assert_eq!(config.log_format, LogFormat::Json);
+        let err = load(&[("LOG_FORMAT", "xml")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                key: "LOG_FORMAT",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_log_bodies_flags() {
+        let config = load(&[]).unwrap();
+        assert!(!config.log_bodies);
+        assert!(!config.log_bodies_redact_addresses);
+        let config = load(&[("LOG_BODIES", "true"), ("LOG_BODIES_REDACT_ADDRESSES", "1")]).unwrap();
+        assert!(config.log_bodies);
+        assert!(config.log_bodies_redact_addresses);
+        assert!(load(&[("LOG_BODIES", "verbose")]).is_err());
+    }
+
+    #[test]
+    fn test_mask_addresses_flag() {
+        assert!(!load(&[]).unwrap().mask_addresses);
+        assert!(load(&[("MASK_ADDRESSES", "true")]).unwrap().mask_addresses);
+        assert!(load(&[("MASK_ADDRESSES", "partly")]).is_err());
+    }
+
+    #[test]
+    fn test_rate_limits() {
+        let config = load(&[
+            ("RATE_LIMIT_QUOTE_PER_MINUTE", "5"),
+            ("TRUST_PROXY", "true"),
+        ])
+        .unwrap();
+        assert_eq!(config.dynamic.rate_limit_quote_per_minute, 5);
+        assert_eq!(
+            config.dynamic.rate_limit_read_per_minute,
+            crate::services::rate_limit::DEFAULT_READ_PER_MINUTE
+        );
+        assert!(config.trust_proxy);
+
+        let err = load(&[("RATE_LIMIT_WRITE_PER_MINUTE", "-1")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                key: "RATE_LIMIT_WRITE_PER_MINUTE",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_http_user_agent() {
+        let config = load(&[]).unwrap();
+        assert_eq!(config.http_user_agent, DEFAULT_USER_AGENT);
+
+        let config = load(&[("HTTP_USER_AGENT", "settleone/2.0 (+ops@example.com)")]).unwrap();
+        assert_eq!(config.http_user_agent, "settleone/2.0 (+ops@example.com)");
+
+        let err = load(&[("HTTP_USER_AGENT", "bad\nagent")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                key: "HTTP_USER_AGENT",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_defaults_when_unset() {
+        let config = load(&[]).unwrap();
+        assert_eq!(config.port, 3001);
+        #[cfg(feature = "lifi")]
+        {
+            assert_eq!(config.lifi_api_url, "https://li.quest/v1");
+            assert!(config.lifi_api_key.is_none());
+        }
+        // Missing optional keys only warn (API_KEYS plus one per integration key)
+        let integration_keys = [
+            cfg!(feature = "lifi"),
+            cfg!(feature = "yellow"),
+            cfg!(feature = "settlement"),
+        ];
+        assert_eq!(
+            config.warnings().len(),
+            1 + integration_keys.iter().filter(|enabled| **enabled).count()
+        );
+    }
+
+    #[test]
+    fn test_invalid_port_is_fatal() {
+        let err = load(&[("PORT", "not-a-port")]).unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { key: "PORT", .. }));
+        assert!(load(&[("PORT", "70000")]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "settlement")]
+    fn test_invalid_rpc_url_is_fatal() {
+        let err = load(&[("ARC_RPC_URL", "rpc.arc.circle.com")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                key: "ARC_RPC_URL",
+                ..
+            }
+        ));
+        let err = load(&[("NATIVE_TOKEN_PRICE_URL", "coingecko")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                key: "NATIVE_TOKEN_PRICE_URL",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "ens")]
+    fn test_invalid_eth_rpc_url_is_fatal() {
+        let err = load(&[("ETH_RPC_URL", "https://")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                key: "ETH_RPC_URL",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "settlement")]
+    fn test_invalid_settlement_address_is_fatal() {
+        let err = load(&[("SETTLEMENT_CONTRACT_ADDRESS", "0x1234")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                key: "SETTLEMENT_CONTRACT_ADDRESS",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "settlement")]
+    fn test_settlement_batching() {
+        const CONTRACT: (&str, &str) = (
+            "SETTLEMENT_CONTRACT_ADDRESS",
+            "0xe66B3Fa5F2b84df7CbD288EB3BC91feE48a90cB2",
+        );
+        const SIGNER: (&str, &str) = (
+            "SETTLEMENT_SIGNER_ADDRESS",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        );
+        let config = load(&[]).unwrap();
+        assert!(!config.settlement_batching);
+        assert_eq!(config.settlement_batch_interval_secs, 30);
+        assert_eq!(config.settlement_batch_max_sessions, 20);
+
+        let config = load(&[
+            ("SETTLEMENT_BATCHING", "true"),
+            CONTRACT,
+            SIGNER,
+            ("SETTLEMENT_BATCH_INTERVAL_SECS", "5"),
+            ("SETTLEMENT_BATCH_MAX_SESSIONS", "100"),
+        ])
+        .unwrap();
+        assert!(config.settlement_batching);
+        assert_eq!(
+            config.settlement_signer_address.unwrap().as_str(),
+            "0xdbf03b407c01e7cd3cbea99509d93f8dddc8c6fb"
+        );
+        assert_eq!(config.settlement_batch_interval_secs, 5);
+        assert_eq!(config.settlement_batch_max_sessions, 100);
+
+        // Batching needs a contract and a signer
+        assert!(load(&[("SETTLEMENT_BATCHING", "true"), CONTRACT]).is_err());
+        assert!(load(&[("SETTLEMENT_BATCHING", "true"), SIGNER]).is_err());
+        assert!(load(&[("SETTLEMENT_SIGNER_ADDRESS", "0x1234")]).is_err());
+        assert!(load(&[("SETTLEMENT_BATCH_INTERVAL_SECS", "0")]).is_err());
+        assert!(load(&[("SETTLEMENT_BATCH_MAX_SESSIONS", "101")]).is_err());
+    }
+
+    #[test]
+    fn test_treasury_address() {
+        assert!(load(&[]).unwrap().treasury_address.is_none());
+        let treasury = "0x000000000000000000000000000000000000dEaD";
+        let config = load(&[("TREASURY_ADDRESS", treasury)]).unwrap();
+        assert_eq!(
+            config.treasury_address.map(|address| address.to_string()),
+            Some(treasury.to_string())
+        );
+        let err = load(&[("TREASURY_ADDRESS", "treasury")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                key: "TREASURY_ADDRESS",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "lifi", feature = "settlement"))]
+    fn test_empty_optional_keys_treated_as_unset() {
+        let config = load(&[
+            ("LIFI_API_KEY", ""),
+            (
+                "SETTLEMENT_CONTRACT_ADDRESS",
+                "0xe66B3Fa5F2b84df7CbD288EB3BC91feE48a90cB2",
+            ),
+        ])
+        .unwrap();
+        assert!(config.lifi_api_key.is_none());
+        assert!(config.settlement_contract_address.is_some());
+    }
+
+    #[test]
+    #[cfg(feature = "lifi")]
+    fn test_lifi_api_keys_rotation_order() {
+        let config = load(&[
+            ("LIFI_API_KEY", "key-a"),
+            ("LIFI_API_KEYS", " key-b,, key-a ,key-c "),
+        ])
+        .unwrap();
+        assert_eq!(config.all_lifi_api_keys(), ["key-a", "key-b", "key-c"]);
+        // Key values are never shown, only how many there are
+        assert!(config
+            .effective_values()
+            .contains(&("LIFI_API_KEYS", "****,****,****".to_string())));
+
+        let config = load(&[("LIFI_API_KEYS", "key-b")]).unwrap();
+        assert_eq!(config.all_lifi_api_keys(), ["key-b"]);
+        assert!(config.warnings().iter().all(|w| !w.contains("LIFI")));
+    }
+
+    #[test]
+    fn test_sentry_dsn_is_masked() {
+        let dsn = "https://public@o0.ingest.sentry.io/1";
+        let config = load(&[("SENTRY_DSN", dsn)]).unwrap();
+        assert_eq!(config.sentry_dsn.as_deref(), Some(dsn));
+        assert!(config
+            .effective_values()
+            .contains(&("SENTRY_DSN", MASK.to_string())));
+    }
+
+    #[test]
+    #[cfg(feature = "sentry")]
+    fn test_invalid_sentry_dsn_is_fatal() {
+        let err = load(&[("SENTRY_DSN", "not a dsn")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                key: "SENTRY_DSN",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "ens")]
+    fn test_ens_allow_subdomains_defaults_to_true() {
+        assert!(load(&[]).unwrap().ens_allow_subdomains);
+        let config = load(&[("ENS_ALLOW_SUBDOMAINS", "false")]).unwrap();
+        assert!(!config.ens_allow_subdomains);
+        assert!(load(&[("ENS_ALLOW_SUBDOMAINS", "sometimes")]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "ens")]
+    fn test_ens_subgraph_url() {
+        assert_eq!(load(&[]).unwrap().ens_subgraph_url, None);
+        let url = "https://gateway.thegraph.com/api/key123/subgraphs/id/5XqPmWe";
+        let config = load(&[("ENS_SUBGRAPH_URL", url)]).unwrap();
+        assert_eq!(config.ens_subgraph_url.as_deref(), Some(url));
+        assert!(!format!("{:?}", config.effective_values()).contains("key123"));
+        assert!(load(&[("ENS_SUBGRAPH_URL", "gateway.thegraph.com")]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "ens")]
+    fn test_ens_cache_backend() {
+        assert_eq!(
+            load(&[]).unwrap().ens_cache_backend,
+            EnsCacheBackend::Memory
+        );
+        assert!(load(&[("ENS_CACHE_BACKEND", "memcached")]).is_err());
+        // Redis needs a server to talk to
+        assert!(load(&[("ENS_CACHE_BACKEND", "redis")]).is_err());
+        let redis = load(&[
+            ("ENS_CACHE_BACKEND", "Redis"),
+            ("ENS_CACHE_REDIS_URL", "redis://:hunter2@cache:6379/0"),
+        ]);
+        if cfg!(feature = "redis") {
+            let config = redis.unwrap();
+            assert_eq!(config.ens_cache_backend, EnsCacheBackend::Redis);
+            assert!(config
+                .effective_values()
+                .contains(&("ENS_CACHE_REDIS_URL", MASK.to_string())));
+        } else {
+            assert!(redis.is_err());
         }
+        assert!(load(&[
+            ("ENS_CACHE_BACKEND", "redis"),
+            ("ENS_CACHE_REDIS_URL", "http://cache:6379"),
+        ])
+        .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "lifi")]
+    fn test_quote_display_decimals() {
+        assert_eq!(load(&[]).unwrap().quote_display_decimals, 6);
+        let config = load(&[("QUOTE_DISPLAY_DECIMALS", "2")]).unwrap();
+        assert_eq!(config.quote_display_decimals, 2);
+        assert!(load(&[("QUOTE_DISPLAY_DECIMALS", "256")]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "lifi")]
+    fn test_quote_sanity_ceiling() {
+        let config = load(&[]).unwrap();
+        assert_eq!(config.quote_sanity_ceiling_usd, 10_000_000);
+        assert_eq!(config.quote_sanity_mode, SanityMode::Warn);
+        let config = load(&[
+            ("QUOTE_SANITY_CEILING_USD", "0"),
+            ("QUOTE_SANITY_MODE", "Reject"),
+        ])
+        .unwrap();
+        assert_eq!(config.quote_sanity_ceiling_usd, 0);
+        assert_eq!(config.quote_sanity_mode, SanityMode::Reject);
+        assert!(load(&[("QUOTE_SANITY_MODE", "block")]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "lifi")]
+    fn test_slippage_settings() {
+        let config = load(&[]).unwrap();
+        assert_eq!(config.default_slippage, 0.005);
+        assert_eq!(config.min_slippage, 0.0);
+        let config = load(&[("DEFAULT_SLIPPAGE", "0.01"), ("MIN_SLIPPAGE", "0.003")]).unwrap();
+        assert_eq!(config.default_slippage, 0.01);
+        assert_eq!(config.min_slippage, 0.003);
+        assert!(load(&[("DEFAULT_SLIPPAGE", "1")]).is_err());
+        assert!(load(&[("MIN_SLIPPAGE", "-0.1")]).is_err());
+        assert!(load(&[("MIN_SLIPPAGE", "half")]).is_err());
+        // The default may not sit below the floor
+        assert!(load(&[("MIN_SLIPPAGE", "0.01")]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "lifi")]
+    fn test_price_staleness_thresholds() {
+        let config = load(&[]).unwrap();
+        assert_eq!(
+            (config.price_cache_ttl_secs, config.price_max_age_secs),
+            (60, 900)
+        );
+        let config = load(&[("PRICE_CACHE_TTL_SECS", "5"), ("PRICE_MAX_AGE_SECS", "30")]).unwrap();
+        assert_eq!(
+            (config.price_cache_ttl_secs, config.price_max_age_secs),
+            (5, 30)
+        );
+        assert!(load(&[("PRICE_MAX_AGE_SECS", "-1")]).is_err());
+    }
+
+    #[test]
+    fn test_fx_rate_settings() {
+        let config = load(&[]).unwrap();
+        assert!(config.fx_rate_url.starts_with("https://api.coinbase.com/"));
+        assert_eq!(config.fx_rate_max_age_secs, 3600);
+        let config = load(&[
+            ("FX_RATE_URL", "http://rates.internal/usd"),
+            ("FX_RATE_MAX_AGE_SECS", "60"),
+        ])
+        .unwrap();
+        assert_eq!(
+            (config.fx_rate_url.as_str(), config.fx_rate_max_age_secs),
+            ("http://rates.internal/usd", 60)
+        );
+        assert!(load(&[("FX_RATE_URL", "rates.internal")]).is_err());
+        assert!(load(&[("FX_RATE_MAX_AGE_SECS", "0")]).is_err());
+    }
+
+    #[test]
+    fn test_cors_max_age() {
+        assert_eq!(load(&[]).unwrap().cors_max_age_secs, 3600);
+        let config = load(&[("CORS_MAX_AGE_SECS", "600")]).unwrap();
+        assert_eq!(config.cors_max_age_secs, 600);
+        assert!(load(&[("CORS_MAX_AGE_SECS", "1h")]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "lifi")]
+    fn test_lifi_rate_limit_is_off_by_default() {
+        let config = load(&[]).unwrap();
+        assert_eq!(config.lifi_rate_limit_per_minute, 0);
+        assert_eq!(config.lifi_rate_limit_burst, 10);
+        assert_eq!(config.lifi_rate_limit_max_wait_ms, 2000);
+
+        let config = load(&[
+            ("LIFI_RATE_LIMIT_PER_MINUTE", "120"),
+            ("LIFI_RATE_LIMIT_BURST", "5"),
+            ("LIFI_RATE_LIMIT_MAX_WAIT_MS", "0"),
+        ])
+        .unwrap();
+        assert_eq!(config.lifi_rate_limit_per_minute, 120);
+        assert_eq!(config.lifi_rate_limit_burst, 5);
+        assert_eq!(config.lifi_rate_limit_max_wait_ms, 0);
+        assert!(load(&[("LIFI_RATE_LIMIT_PER_MINUTE", "-1")]).is_err());
+    }
+
+    #[test]
+    fn test_strict_errors_flag() {
+        assert!(!load(&[]).unwrap().strict_errors);
+        assert!(load(&[("STRICT_ERRORS", "true")]).unwrap().strict_errors);
+        assert!(load(&[("STRICT_ERRORS", "maybe")]).is_err());
+    }
+
+    #[test]
+    fn test_max_active_sessions_per_user() {
+        assert_eq!(load(&[]).unwrap().max_active_sessions_per_user, None);
+        let config = load(&[("MAX_ACTIVE_SESSIONS_PER_USER", "5")]).unwrap();
+        assert_eq!(config.max_active_sessions_per_user, Some(5));
+        assert!(load(&[("MAX_ACTIVE_SESSIONS_PER_USER", "-1")]).is_err());
+        let config = load(&[("MAX_TOTAL_PAYMENTS", "10000")]).unwrap();
+        assert_eq!(config.max_total_payments, Some(10000));
+    }
+
+    #[test]
+    fn test_min_payment_amount() {
+        let minimum = |config: Config| config.min_payment_amount.to_string();
+        assert_eq!(minimum(load(&[]).unwrap()), "10000");
+        assert_eq!(minimum(load(&[("MIN_PAYMENT_AMOUNT", "0")]).unwrap()), "0");
+        assert!(load(&[("MIN_PAYMENT_AMOUNT", "0.01")]).is_err());
+    }
+
+    #[test]
+    fn test_effective_values_mask_secrets() {
+        let config = load(&[
+            ("LIFI_API_KEY", "lifi-secret"),
+            ("API_KEYS", "client-secret,admin-secret:admin"),
+            ("PORT", "4000"),
+        ])
+        .unwrap();
+        let values = config.effective_values();
+        let rendered = format!("{:?}", values);
+        assert!(!rendered.contains("secret"));
+
+        let value = |key: &str| values.iter().find(|(k, _)| *k == key).unwrap().1.clone();
+        assert_eq!(value("PORT"), "4000");
+        #[cfg(feature = "lifi")]
+        assert_eq!(value("LIFI_API_KEY"), "****");
+        #[cfg(feature = "yellow")]
+        assert_eq!(value("YELLOW_API_KEY"), "");
+        assert_eq!(value("API_KEYS"), "****:client,****:admin");
+    }
+
+    #[test]
+    fn test_blocked_addresses() {
+        let blocked = "0x1111111111111111111111111111111111111111";
+        let config = load(&[(
+            "BLOCKED_ADDRESSES",
+            "0xAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA, 0x1111111111111111111111111111111111111111",
+        )])
+        .unwrap();
+        let address = |raw: &str| Address::try_from(raw).unwrap();
+        assert_eq!(config.blocked_addresses.len(), 2);
+        assert!(config.is_blocked(&address(blocked)));
+        assert!(config.is_blocked(&address("0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")));
+        assert!(!config.is_blocked(&address("0x2222222222222222222222222222222222222222")));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocked.txt");
+        std::fs::write(
+            &path,
+            format!("# sanctions list\n{}  # known bad\n\n", blocked),
+        )
+        .unwrap();
+        let config = load(&[("BLOCKED_ADDRESSES", path.to_str().unwrap())]).unwrap();
+        assert!(config.is_blocked(&address(blocked)));
+        assert_eq!(config.blocked_addresses.len(), 1);
+
+        assert!(load(&[("BLOCKED_ADDRESSES", "0x123")]).is_err());
+        assert!(load(&[("BLOCKED_ADDRESSES", "/nonexistent/blocked.txt")]).is_err());
+        assert!(load(&[]).unwrap().blocked_addresses.is_empty());
+    }
+
+    #[test]
+    fn test_admin_port() {
+        assert_eq!(load(&[]).unwrap().admin_port, None);
+        let config = load(&[("ADMIN_PORT", "9091")]).unwrap();
+        assert_eq!(config.admin_port, Some(9091));
+        assert!(load(&[("ADMIN_PORT", "70000")]).is_err());
+        assert!(load(&[("PORT", "9091"), ("ADMIN_PORT", "9091")]).is_err());
+    }
+
+    #[test]
+    fn test_health_history_size() {
+        assert_eq!(load(&[]).unwrap().health_history_size, 100);
+        let config = load(&[("HEALTH_HISTORY_SIZE", "10")]).unwrap();
+        assert_eq!(config.health_history_size, 10);
+        assert!(load(&[("HEALTH_HISTORY_SIZE", "0")]).is_err());
+        assert!(load(&[("HEALTH_HISTORY_SIZE", "many")]).is_err());
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn test_grpc_port() {
+        assert_eq!(load(&[]).unwrap().grpc_port, None);
+        let config = load(&[("GRPC_PORT", "50051")]).unwrap();
+        assert_eq!(config.grpc_port, Some(50051));
+        assert!(load(&[("PORT", "50051"), ("GRPC_PORT", "50051")]).is_err());
+        assert!(load(&[("ADMIN_PORT", "50051"), ("GRPC_PORT", "50051")]).is_err());
+    }
+
+    #[test]
+    fn test_listen() {
+        let config = load(&[("PORT", "4000")]).unwrap();
+        assert_eq!(config.listen.to_string(), "tcp://0.0.0.0:4000");
+        assert_eq!(config.listen_socket_mode, 0o660);
+
+        let config = load(&[("LISTEN", "tcp://127.0.0.1:5000")]).unwrap();
+        assert_eq!(config.port, 5000);
+
+        let config = load(&[
+            ("LISTEN", "unix:///run/settleone.sock"),
+            ("LISTEN_SOCKET_MODE", "0600"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.listen,
+            ListenAddr::Unix("/run/settleone.sock".into())
+        );
+        assert_eq!(config.listen_socket_mode, 0o600);
+
+        assert!(load(&[("LISTEN", "localhost:3001")]).is_err());
+        assert!(load(&[("LISTEN_SOCKET_MODE", "999")]).is_err());
+        let err = load(&[
+            ("LISTEN", "unix:///run/settleone.sock"),
+            ("TLS_CERT_PATH", "cert.pem"),
+            ("TLS_KEY_PATH", "key.pem"),
+        ])
+        .unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { key: "LISTEN", .. }));
+    }
+
+    #[test]
+    fn test_session_ttl() {
+        assert_eq!(load(&[]).unwrap().session_ttl_secs, None);
+        let config = load(&[("SESSION_TTL_SECS", "3600")]).unwrap();
+        assert_eq!(config.session_ttl_secs, Some(3600));
+        assert!(load(&[("SESSION_TTL_SECS", "1h")]).is_err());
+    }
+
+    #[test]
+    fn test_finalize_cooldown() {
+        assert_eq!(load(&[]).unwrap().finalize_cooldown_secs, 0);
+        let config = load(&[("FINALIZE_COOLDOWN_SECS", "30")]).unwrap();
+        assert_eq!(config.finalize_cooldown_secs, 30);
+        assert!(load(&[("FINALIZE_COOLDOWN_SECS", "-1")]).is_err());
+    }
+
+    #[test]
+    fn test_maintenance_mode() {
+        assert!(!load(&[]).unwrap().maintenance_mode);
+        assert!(
+            load(&[("MAINTENANCE_MODE", "true")])
+                .unwrap()
+                .maintenance_mode
+        );
+        assert!(load(&[("MAINTENANCE_MODE", "maybe")]).is_err());
+    }
+
+    #[test]
+    fn test_tls_paths_set_together() {
+        let config = load(&[]).unwrap();
+        assert!(config.tls_cert_path.is_none() && config.tls_key_path.is_none());
+        let config = load(&[
+            ("TLS_CERT_PATH", "/etc/tls/cert.pem"),
+            ("TLS_KEY_PATH", "/etc/tls/key.pem"),
+        ])
+        .unwrap();
+        assert!(config.tls_cert_path.is_some() && config.tls_key_path.is_some());
+        let err = load(&[("TLS_CERT_PATH", "/etc/tls/cert.pem")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Invalid {
+                key: "TLS_KEY_PATH",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_session_archive() {
+        let config = load(&[]).unwrap();
+        assert_eq!(config.session_archive_after_secs, None);
+        assert_eq!(config.session_archive_path, None);
+        let config = load(&[
+            ("SESSION_ARCHIVE_AFTER_SECS", "86400"),
+            ("SESSION_ARCHIVE_PATH", "/var/lib/settleone/archive.jsonl"),
+        ])
+        .unwrap();
+        assert_eq!(config.session_archive_after_secs, Some(86400));
+        assert_eq!(
+            config.session_archive_path.unwrap().to_str(),
+            Some("/var/lib/settleone/archive.jsonl")
+        );
+        assert!(load(&[("SESSION_ARCHIVE_AFTER_SECS", "1d")]).is_err());
     }
 }
@@ -0,0 +1,525 @@
+//! GraphQL API at `/graphql`
+//!
+//! Queries and mutations are served over `GET`/`POST /graphql`,
+//! subscriptions over WebSocket at `/graphql/ws` (`graphql-transport-ws` or
+//! the legacy `graphql-ws` protocol). Mutations run the `/api/v1` handlers,
+//! so validation, blocked addresses and API keys behave as over REST; errors
+//! carry the REST error code in `extensions.code`.
+
+use std::sync::OnceLock;
+
+use async_graphql::http::{
+    playground_source, GraphQLPlaygroundConfig, WebSocket, WebSocketProtocols, WsMessage,
+    ALL_WEBSOCKET_PROTOCOLS,
+};
+use async_graphql::{
+    Context, Enum, ErrorExtensions, InputObject, Object, Schema, SimpleObject, Subscription,
+};
+use axum::extract::ws::{CloseFrame, Message, WebSocketUpgrade};
+use axum::extract::{FromRequest, Path, Request, State};
+use axum::http::{HeaderMap, Method};
+use axum::response::{Html, IntoResponse, Response};
+use axum::Json;
+use futures::{SinkExt, Stream, StreamExt};
+
+use crate::api::error::AppError;
+use crate::api::extract::ValidJson;
+use crate::api::middleware::{authorize, ensure_writable};
+use crate::api::pagination::{paginate, Cursor, PageParams, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT};
+use crate::api::session::missing_session;
+use crate::api::AddressDisplay;
+use crate::models::address::{Address, EnsName};
+use crate::models::amount::Amount;
+use crate::models::session as model;
+use crate::services::auth::ApiRole;
+use crate::AppState;
+use settleone_types::api;
+
+/// The GraphQL schema
+pub type SettleOneSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+/// The schema, built once; requests supply the [`AppState`] as data
+pub fn schema() -> &'static SettleOneSchema {
+    static SCHEMA: OnceLock<SettleOneSchema> = OnceLock::new();
+    SCHEMA.get_or_init(|| Schema::new(QueryRoot, MutationRoot, SubscriptionRoot))
+}
+
+/// Execute a query or mutation (`GET` with `?query=`, or a JSON `POST`)
+pub async fn execute(
+    State(state): State<AppState>,
+    request: Request,
+) -> Result<Json<async_graphql::Response>, AppError> {
+    let headers = request.headers().clone();
+    let request = if request.method() == Method::GET {
+        async_graphql::http::parse_query_string(request.uri().query().unwrap_or_default())
+            .map_err(|e| AppError::validation("query", e.to_string()))?
+    } else {
+        let Json(request) = Json::<async_graphql::Request>::from_request(request, &())
+            .await
+            .map_err(|e| AppError::validation("body", e.body_text()))?;
+        request
+    };
+    let request = request.data(state).data(headers);
+    Ok(Json(schema().execute(request).await))
+}
+
+/// Upgrade to a GraphQL-over-WebSocket connection for subscriptions
+pub async fn subscribe(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let protocol = headers
+        .get("sec-websocket-protocol")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').find_map(|p| p.trim().parse().ok()))
+        .unwrap_or(WebSocketProtocols::SubscriptionsTransportWS);
+
+    upgrade
+        .protocols(ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |socket| async move {
+            let (mut sink, incoming) = socket.split();
+            let incoming = incoming
+                .take_while(|message| futures::future::ready(message.is_ok()))
+                .filter_map(|message| {
+                    futures::future::ready(match message {
+                        Ok(Message::Text(text)) => Some(text.into_bytes()),
+                        Ok(Message::Binary(bytes)) => Some(bytes),
+                        _ => None,
+                    })
+                });
+            let mut data = async_graphql::Data::default();
+            data.insert(state);
+            data.insert(headers);
+            let mut outgoing =
+                WebSocket::new(schema().clone(), incoming, protocol).connection_data(data);
+            while let Some(message) = outgoing.next().await {
+                let message = match message {
+                    WsMessage::Text(text) => Message::Text(text),
+                    WsMessage::Close(code, reason) => Message::Close(Some(CloseFrame {
+                        code,
+                        reason: reason.into(),
+                    })),
+                };
+                if sink.send(message).await.is_err() {
+                    break;
+                }
+            }
+        })
+}
+
+/// GraphQL playground (`ENABLE_GRAPHQL_PLAYGROUND`)
+pub async fn playground() -> impl IntoResponse {
+    Html(playground_source(
+        GraphQLPlaygroundConfig::new("/graphql").subscription_endpoint("/graphql/ws"),
+    ))
+}
+
+/// Parse a string argument into its validated type
+fn parse_input<'a, T>(field: &str, raw: &'a str) -> async_graphql::Result<T>
+where
+    T: TryFrom<&'a str>,
+    T::Error: std::fmt::Display,
+{
+    T::try_from(raw).map_err(|e| gql_error(AppError::unprocessable(field, e.to_string())))
+}
+
+/// GraphQL error with the REST error code and field errors as extensions
+fn gql_error(e: AppError) -> async_graphql::Error {
+    let code = e.code();
+    let fields = match &e {
+        AppError::Validation { fields, .. } | AppError::Unprocessable { fields, .. } => {
+            serde_json::to_value(fields)
+                .ok()
+                .and_then(|v| async_graphql::Value::from_json(v).ok())
+        }
+        _ => None,
+    };
+    async_graphql::Error::new(e.message()).extend_with(|_, extensions| {
+        extensions.set("code", code.as_str());
+        if let Some(fields) = fields {
+            extensions.set("fields", fields);
+        }
+    })
+}
+
+/// How the request's responses show session addresses
+fn addresses(ctx: &Context<'_>) -> AddressDisplay {
+    AddressDisplay::of(&ctx.data_unchecked::<AppState>().config)
+}
+
+/// Mutations are refused in maintenance mode and need a client key once
+/// keys are configured, as over REST
+fn authorize_mutation(ctx: &Context<'_>) -> async_graphql::Result<()> {
+    let state = ctx.data_unchecked::<AppState>();
+    ensure_writable(state).map_err(gql_error)?;
+    if state.config.api_keys.is_empty() {
+        return Ok(());
+    }
+    let headers = ctx.data_unchecked::<HeaderMap>();
+    authorize(state, headers, ApiRole::Client).map_err(gql_error)
+}
+
+/// Session lifecycle state
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "crate::models::session::SessionStatus")]
+pub enum SessionStatus {
+    Active,
+    Pending,
+    Settled,
+    Cancelled,
+}
+
+/// Payment state
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "crate::models::session::PaymentStatus")]
+pub enum PaymentStatus {
+    Pending,
+    Confirmed,
+    Settled,
+    Cancelled,
+}
+
+/// A payment session
+pub struct Session(model::Session);
+
+#[Object]
+impl Session {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    /// EIP-55 checksummed address, masked with `MASK_ADDRESSES`
+    async fn user(&self, ctx: &Context<'_>) -> String {
+        addresses(ctx).show(&self.0.user)
+    }
+
+    async fn status(&self) -> SessionStatus {
+        self.0.status.clone().into()
+    }
+
+    async fn payments(&self) -> Vec<Payment> {
+        self.0.payments.iter().cloned().map(Payment).collect()
+    }
+
+    /// Sum of non-cancelled payments, in base units
+    async fn total_amount(&self) -> String {
+        self.0.total_amount.to_string()
+    }
+
+    /// `totalAmount` formatted with the session's token decimals
+    async fn total_amount_display(&self) -> String {
+        self.0.display_total()
+    }
+
+    async fn token_decimals(&self) -> u8 {
+        self.0.token_decimals
+    }
+
+    async fn tx_hash(&self) -> Option<&str> {
+        self.0.tx_hash.as_deref()
+    }
+
+    /// RFC 3339 creation time
+    async fn created_at(&self) -> String {
+        self.0.created_at.to_rfc3339()
+    }
+
+    /// ENS recipient resolved when the session was created
+    async fn pinned_recipient(&self, ctx: &Context<'_>) -> Option<PinnedRecipient> {
+        let addresses = addresses(ctx);
+        self.0
+            .pinned_recipient
+            .clone()
+            .map(|pinned| PinnedRecipient::new(pinned, addresses))
+    }
+
+    /// Incremented on every change
+    async fn version(&self) -> u64 {
+        self.0.version
+    }
+
+    async fn cancel_reason(&self) -> Option<&str> {
+        self.0.cancel_reason.as_deref()
+    }
+}
+
+/// A payment within a session
+pub struct Payment(model::Payment);
+
+#[Object]
+impl Payment {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    /// EIP-55 checksummed address, masked with `MASK_ADDRESSES`
+    async fn recipient(&self, ctx: &Context<'_>) -> String {
+        addresses(ctx).show(&self.0.recipient)
+    }
+
+    async fn recipient_ens(&self) -> Option<&str> {
+        self.0.recipient_ens.as_ref().map(EnsName::as_str)
+    }
+
+    /// Amount in base units
+    async fn amount(&self) -> String {
+        self.0.amount.to_string()
+    }
+
+    async fn status(&self) -> PaymentStatus {
+        self.0.status.clone().into()
+    }
+
+    /// RFC 3339 creation time
+    async fn created_at(&self) -> String {
+        self.0.created_at.to_rfc3339()
+    }
+
+    /// RFC 3339 time the settlement transaction was first seen mined
+    async fn confirmed_at(&self) -> Option<String> {
+        self.0.confirmed_at.map(|at| at.to_rfc3339())
+    }
+
+    /// RFC 3339 settlement time
+    async fn settled_at(&self) -> Option<String> {
+        self.0.settled_at.map(|at| at.to_rfc3339())
+    }
+
+    /// Why the backend cancelled the payment, e.g. as dust at finalize
+    async fn cancel_reason(&self) -> Option<&str> {
+        self.0.cancel_reason.as_deref()
+    }
+
+    /// ENS profile of `recipientEns`, resolved through the ENS cache
+    #[cfg(feature = "ens")]
+    async fn recipient_profile(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<EnsProfile>> {
+        let Some(name) = &self.0.recipient_ens else {
+            return Ok(None);
+        };
+        let state = ctx.data_unchecked::<AppState>();
+        let resolved = state
+            .ens_service
+            .resolve(name)
+            .await
+            .map_err(|e| gql_error(crate::api::ens::ens_error("recipientEns", e)))?;
+        Ok(Some(EnsProfile {
+            name: name.to_string(),
+            address: resolved.address.to_string(),
+            avatar: resolved.avatar,
+        }))
+    }
+}
+
+/// A resolved ENS name
+#[cfg(feature = "ens")]
+#[derive(SimpleObject)]
+pub struct EnsProfile {
+    pub name: String,
+    pub address: String,
+    pub avatar: Option<String>,
+}
+
+/// ENS recipient pinned to a session
+#[derive(SimpleObject)]
+pub struct PinnedRecipient {
+    pub name: String,
+    /// Masked with `MASK_ADDRESSES`
+    pub address: String,
+    /// RFC 3339 resolution time
+    pub resolved_at: String,
+}
+
+impl PinnedRecipient {
+    fn new(pinned: model::PinnedRecipient, addresses: AddressDisplay) -> Self {
+        Self {
+            name: pinned.name.to_string(),
+            address: addresses.show(&pinned.address),
+            resolved_at: pinned.resolved_at.to_rfc3339(),
+        }
+    }
+}
+
+/// One page of sessions
+#[derive(SimpleObject)]
+pub struct SessionPage {
+    pub items: Vec<Session>,
+    /// Pass as `after` for the next page; null on the last page
+    pub next_cursor: Option<String>,
+    /// Matching sessions across all pages
+    pub total: usize,
+}
+
+/// Result of `finalizeSession`
+#[derive(SimpleObject)]
+pub struct FinalizePayload {
+    pub session_id: String,
+    pub status: String,
+    pub tx_hash: Option<String>,
+}
+
+/// Payment to add with `addPayment`
+#[derive(InputObject)]
+pub struct AddPaymentInput {
+    pub recipient: String,
+    pub recipient_ens: Option<String>,
+    /// Amount in base units
+    pub amount: String,
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// A session by id
+    async fn session(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<Session> {
+        let state = ctx.data_unchecked::<AppState>();
+        match state.session_store.get(&id).await {
+            Some(session) => Ok(Session(session)),
+            None => Err(gql_error(missing_session(state, &id).await)),
+        }
+    }
+
+    /// Sessions oldest first, optionally of one user and/or in one status
+    async fn sessions(
+        &self,
+        ctx: &Context<'_>,
+        user: Option<String>,
+        status: Option<SessionStatus>,
+        #[graphql(desc = "Page size, 1 to 100 (default 50)")] first: Option<i32>,
+        #[graphql(desc = "`nextCursor` of the previous page")] after: Option<String>,
+    ) -> async_graphql::Result<SessionPage> {
+        let limit = match first {
+            Some(first) => usize::try_from(first)
+                .ok()
+                .filter(|limit| (1..=MAX_PAGE_LIMIT).contains(limit))
+                .ok_or_else(|| {
+                    gql_error(AppError::validation(
+                        "first",
+                        format!("first must be between 1 and {}", MAX_PAGE_LIMIT),
+                    ))
+                })?,
+            None => DEFAULT_PAGE_LIMIT,
+        };
+        let cursor = after
+            .map(|token| {
+                Cursor::decode(&token)
+                    .ok_or_else(|| gql_error(AppError::validation("after", "after is invalid")))
+            })
+            .transpose()?;
+
+        let user = user
+            .as_deref()
+            .map(|user| parse_input::<Address>("user", user))
+            .transpose()?;
+
+        let state = ctx.data_unchecked::<AppState>();
+        let sessions = state
+            .session_store
+            .list(user.as_ref(), status.map(Into::into))
+            .await;
+        let page = paginate(sessions, &PageParams { limit, cursor }, |s| {
+            Cursor::new(s.created_at, s.id.as_str())
+        });
+        Ok(SessionPage {
+            items: page.items.into_iter().map(Session).collect(),
+            next_cursor: page.next_cursor,
+            total: page.total,
+        })
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    /// Add a payment to an active session
+    async fn add_payment(
+        &self,
+        ctx: &Context<'_>,
+        session_id: String,
+        input: AddPaymentInput,
+    ) -> async_graphql::Result<Session> {
+        authorize_mutation(ctx)?;
+        let state = ctx.data_unchecked::<AppState>().clone();
+        let payload = api::AddPaymentRequest {
+            recipient: parse_input("recipient", &input.recipient)?,
+            recipient_ens: input
+                .recipient_ens
+                .as_deref()
+                .map(|name| parse_input::<EnsName>("recipientEns", name))
+                .transpose()?,
+            amount: Some(parse_input("amount", &input.amount)?),
+            human_amount: None,
+        };
+        let Json(updated) =
+            crate::api::session::add_payment(State(state), Path(session_id), ValidJson(payload))
+                .await
+                .map_err(gql_error)?;
+        Ok(Session(updated.into_inner().session))
+    }
+
+    /// Finalize a session; `expectedTotal`/`expectedPaymentCount` refuse
+    /// if it changed since it was reviewed, `verifyEns` if a recipient's
+    /// ENS record changed; `skipDust` cancels payments below the minimum
+    /// instead of refusing
+    #[allow(clippy::too_many_arguments)]
+    async fn finalize_session(
+        &self,
+        ctx: &Context<'_>,
+        session_id: String,
+        tx_hash: Option<String>,
+        expected_total: Option<String>,
+        expected_payment_count: Option<usize>,
+        verify_ens: Option<bool>,
+        skip_dust: Option<bool>,
+    ) -> async_graphql::Result<FinalizePayload> {
+        authorize_mutation(ctx)?;
+        let state = ctx.data_unchecked::<AppState>().clone();
+        let payload = api::FinalizeRequest {
+            tx_hash,
+            expected_total: expected_total
+                .as_deref()
+                .map(|total| parse_input::<Amount>("expectedTotal", total))
+                .transpose()?,
+            expected_payment_count,
+            verify_ens: verify_ens.unwrap_or_default(),
+            skip_dust: skip_dust.unwrap_or_default(),
+        };
+        let Json(finalized) = crate::api::session::finalize_session(
+            State(state),
+            Path(session_id),
+            ValidJson(payload),
+        )
+        .await
+        .map_err(gql_error)?;
+        Ok(FinalizePayload {
+            session_id: finalized.session_id,
+            status: finalized.status,
+            tx_hash: finalized.tx_hash,
+        })
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// The session now and after every change, until it is settled or cancelled
+    async fn session_updated(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+    ) -> async_graphql::Result<impl Stream<Item = Session>> {
+        let state = ctx.data_unchecked::<AppState>();
+        let Some(updates) = state.session_store.watch(&id).await else {
+            return Err(gql_error(missing_session(state, &id).await));
+        };
+        Ok(updates
+            .take_until(state.shutdown.clone().cancelled_owned())
+            .map(Session))
+    }
+}
@@ -0,0 +1,338 @@
+//! gRPC API (`grpc` feature)
+//!
+//! Serves `SessionService` and `QuoteService` from `proto/settleone.proto`
+//! on `GRPC_PORT`, without TLS. Each call runs the matching `/api/v1`
+//! handler, so validation, blocked addresses and API keys (`x-api-key`
+//! metadata on mutating calls) behave exactly as over REST; [`AppError`]s
+//! become gRPC status codes. `Watch` follows the session store's update
+//! channel.
+
+pub mod proto;
+
+use std::future::Future;
+
+use axum::extract::{Path, State};
+use axum::{Extension, Json};
+use futures::stream::{BoxStream, StreamExt};
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{Code, Request, Response, Status};
+
+use crate::api::error::AppError;
+use crate::api::extract::ValidJson;
+use crate::api::middleware::{authorize, ensure_writable};
+use crate::api::session::missing_session;
+use crate::api::{AddressDisplay, ApiVersion};
+use crate::models::address::{Address, EnsName};
+use crate::models::amount::Amount;
+use crate::models::session::{self as model, PaymentStatus, SessionStatus};
+use crate::services::auth::ApiRole;
+use crate::AppState;
+use proto::session_service_server::{SessionService, SessionServiceServer};
+use settleone_types::api;
+
+/// Serve the gRPC services on `listener` until `stopped` resolves
+pub async fn serve(
+    listener: std::net::TcpListener,
+    state: AppState,
+    stopped: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    listener.set_nonblocking(true)?;
+    let incoming = TcpListenerStream::new(tokio::net::TcpListener::from_std(listener)?);
+    let router = tonic::transport::Server::builder()
+        .add_service(SessionServiceServer::new(SessionGrpc(state.clone())));
+    #[cfg(feature = "lifi")]
+    let router = router.add_service(proto::quote_service_server::QuoteServiceServer::new(
+        QuoteGrpc(state),
+    ));
+    #[cfg(not(feature = "lifi"))]
+    let _ = state;
+    router
+        .serve_with_incoming_shutdown(incoming, stopped)
+        .await
+        .map_err(std::io::Error::other)
+}
+
+/// Map an API error onto the closest gRPC status
+fn status(e: AppError) -> Status {
+    let code = match e {
+        AppError::NotFound(_) | AppError::Gone(_) => Code::NotFound,
+        AppError::Validation { .. } | AppError::Unprocessable { .. } => Code::InvalidArgument,
+        AppError::Conflict(_) => Code::FailedPrecondition,
+        AppError::Upstream(_) | AppError::UpstreamUnreachable(_) | AppError::Maintenance(_) => {
+            Code::Unavailable
+        }
+        AppError::UpstreamTimeout(_) => Code::DeadlineExceeded,
+        AppError::Unauthorized(_) => Code::Unauthenticated,
+        AppError::Forbidden(_) => Code::PermissionDenied,
+        AppError::MethodNotAllowed(_) | AppError::NotImplemented(_) => Code::Unimplemented,
+        AppError::RateLimited(_) | AppError::TooManyRequests { .. } => Code::ResourceExhausted,
+        AppError::Internal(_) | AppError::Panic => Code::Internal,
+    };
+    Status::new(code, e.message())
+}
+
+/// Parse a string field of a request into its validated type
+fn parse_field<'a, T>(field: &str, raw: &'a str) -> Result<T, AppError>
+where
+    T: TryFrom<&'a str>,
+    T::Error: std::fmt::Display,
+{
+    T::try_from(raw).map_err(|e| AppError::unprocessable(field, e.to_string()))
+}
+
+/// Mutating calls are refused in maintenance mode and need a client key
+/// once keys are configured, as over REST
+fn authorize_mutation<T>(state: &AppState, request: &Request<T>) -> Result<(), AppError> {
+    ensure_writable(state)?;
+    if state.config.api_keys.is_empty() {
+        return Ok(());
+    }
+    let headers = request.metadata().clone().into_headers();
+    authorize(state, &headers, ApiRole::Client)
+}
+
+/// `SessionService` backed by the session handlers
+struct SessionGrpc(AppState);
+
+impl SessionGrpc {
+    /// How responses show session addresses
+    fn addresses(&self) -> AddressDisplay {
+        AddressDisplay::of(&self.0.config)
+    }
+}
+
+#[tonic::async_trait]
+impl SessionService for SessionGrpc {
+    type WatchStream = BoxStream<'static, Result<proto::Session, Status>>;
+
+    async fn create(
+        &self,
+        request: Request<proto::CreateSessionRequest>,
+    ) -> Result<Response<proto::CreateSessionResponse>, Status> {
+        authorize_mutation(&self.0, &request).map_err(status)?;
+        let request = request.into_inner();
+        let payload = api::CreateSessionRequest {
+            user_address: parse_field("user_address", &request.user_address).map_err(status)?,
+            recipient_name: request
+                .recipient_name
+                .as_deref()
+                .map(|name| parse_field::<EnsName>("recipient_name", name))
+                .transpose()
+                .map_err(status)?,
+            // Out-of-range values are rejected by the handler
+            token_decimals: request
+                .token_decimals
+                .map(|d| u8::try_from(d).unwrap_or(u8::MAX)),
+            settlement_mode: None,
+            display_currency: None,
+        };
+        let (_, Json(created)) = crate::api::session::create_session(
+            State(self.0.clone()),
+            Extension(ApiVersion::V1),
+            ValidJson(payload),
+        )
+        .await
+        .map_err(status)?;
+        let created = created.into_inner();
+        Ok(Response::new(proto::CreateSessionResponse {
+            session_id: created.session_id,
+            status: created.status,
+            pinned_recipient: created
+                .pinned_recipient
+                .map(|pinned| pinned_recipient_message(pinned, self.addresses())),
+        }))
+    }
+
+    async fn get(
+        &self,
+        request: Request<proto::GetSessionRequest>,
+    ) -> Result<Response<proto::Session>, Status> {
+        let id = request.into_inner().session_id;
+        match self.0.session_store.get(&id).await {
+            Some(session) => Ok(Response::new(session_message(session, self.addresses()))),
+            None => Err(status(missing_session(&self.0, &id).await)),
+        }
+    }
+
+    async fn add_payment(
+        &self,
+        request: Request<proto::AddPaymentRequest>,
+    ) -> Result<Response<proto::Session>, Status> {
+        authorize_mutation(&self.0, &request).map_err(status)?;
+        let request = request.into_inner();
+        let payload = api::AddPaymentRequest {
+            recipient: parse_field::<Address>("recipient", &request.recipient).map_err(status)?,
+            recipient_ens: request
+                .recipient_ens
+                .as_deref()
+                .map(|name| parse_field::<EnsName>("recipient_ens", name))
+                .transpose()
+                .map_err(status)?,
+            // Unset proto3 strings arrive empty
+            amount: (!request.amount.is_empty())
+                .then(|| parse_field::<Amount>("amount", &request.amount))
+                .transpose()
+                .map_err(status)?,
+            human_amount: None,
+        };
+        let Json(updated) = crate::api::session::add_payment(
+            State(self.0.clone()),
+            Path(request.session_id),
+            ValidJson(payload),
+        )
+        .await
+        .map_err(status)?;
+        Ok(Response::new(session_message(
+            updated.into_inner().session,
+            self.addresses(),
+        )))
+    }
+
+    async fn finalize(
+        &self,
+        request: Request<proto::FinalizeRequest>,
+    ) -> Result<Response<proto::FinalizeResponse>, Status> {
+        authorize_mutation(&self.0, &request).map_err(status)?;
+        let request = request.into_inner();
+        let payload = api::FinalizeRequest {
+            tx_hash: request.tx_hash,
+            expected_total: request
+                .expected_total
+                .as_deref()
+                .map(|total| parse_field::<Amount>("expected_total", total))
+                .transpose()
+                .map_err(status)?,
+            expected_payment_count: request
+                .expected_payment_count
+                .map(|n| usize::try_from(n).unwrap_or(usize::MAX)),
+            verify_ens: request.verify_ens,
+            skip_dust: request.skip_dust,
+        };
+        let Json(finalized) = crate::api::session::finalize_session(
+            State(self.0.clone()),
+            Path(request.session_id),
+            ValidJson(payload),
+        )
+        .await
+        .map_err(status)?;
+        Ok(Response::new(proto::FinalizeResponse {
+            session_id: finalized.session_id,
+            status: finalized.status,
+            tx_hash: finalized.tx_hash,
+        }))
+    }
+
+    async fn watch(
+        &self,
+        request: Request<proto::WatchSessionRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let id = request.into_inner().session_id;
+        let Some(updates) = self.0.session_store.watch(&id).await else {
+            return Err(status(missing_session(&self.0, &id).await));
+        };
+        let addresses = self.addresses();
+        let stream = updates
+            .take_until(self.0.shutdown.clone().cancelled_owned())
+            .map(move |session| session_message(session, addresses))
+            .map(Ok);
+        Ok(Response::new(stream.boxed()))
+    }
+}
+
+/// `QuoteService` backed by the quote handler
+#[cfg(feature = "lifi")]
+struct QuoteGrpc(AppState);
+
+#[cfg(feature = "lifi")]
+#[tonic::async_trait]
+impl proto::quote_service_server::QuoteService for QuoteGrpc {
+    async fn get_quote(
+        &self,
+        request: Request<proto::QuoteRequest>,
+    ) -> Result<Response<proto::QuoteResponse>, Status> {
+        let request = request.into_inner();
+        let params = api::QuoteRequest {
+            from_chain: request.from_chain,
+            to_chain: request.to_chain,
+            from_token: request.from_token,
+            to_token: request.to_token,
+            from_amount: request.from_amount,
+            from_address: request.from_address,
+            slippage: None,
+        };
+        let Json(quote) = crate::api::quote::get_quote(
+            State(self.0.clone()),
+            Extension(ApiVersion::V1),
+            axum::extract::Query(params),
+        )
+        .await
+        .map_err(status)?;
+        Ok(Response::new(proto::QuoteResponse {
+            from_amount: quote.from_amount,
+            to_amount: quote.to_amount,
+            estimated_gas: quote.estimated_gas,
+            estimated_time: quote.estimated_time,
+            route_json: quote.route.map(|route| route.to_string()),
+        }))
+    }
+}
+
+/// `session` as a message, its addresses shown as `addresses` says
+fn session_message(session: model::Session, addresses: AddressDisplay) -> proto::Session {
+    let status = match session.status {
+        SessionStatus::Active => proto::SessionStatus::Active,
+        SessionStatus::Pending => proto::SessionStatus::Pending,
+        SessionStatus::Settled => proto::SessionStatus::Settled,
+        SessionStatus::Cancelled => proto::SessionStatus::Cancelled,
+    };
+    proto::Session {
+        id: session.id,
+        user: addresses.show(&session.user),
+        status: status as i32,
+        payments: session
+            .payments
+            .into_iter()
+            .map(|payment| payment_message(payment, addresses))
+            .collect(),
+        total_amount: session.total_amount.to_string(),
+        token_decimals: session.token_decimals.into(),
+        tx_hash: session.tx_hash,
+        created_at: session.created_at.to_rfc3339(),
+        pinned_recipient: session
+            .pinned_recipient
+            .map(|pinned| pinned_recipient_message(pinned, addresses)),
+        version: session.version,
+        cancel_reason: session.cancel_reason,
+    }
+}
+
+fn payment_message(payment: model::Payment, addresses: AddressDisplay) -> proto::Payment {
+    let status = match payment.status {
+        PaymentStatus::Pending => proto::PaymentStatus::Pending,
+        PaymentStatus::Confirmed => proto::PaymentStatus::Confirmed,
+        PaymentStatus::Settled => proto::PaymentStatus::Settled,
+        PaymentStatus::Cancelled => proto::PaymentStatus::Cancelled,
+    };
+    proto::Payment {
+        id: payment.id,
+        recipient: addresses.show(&payment.recipient),
+        recipient_ens: payment.recipient_ens.map(|name| name.to_string()),
+        amount: payment.amount.to_string(),
+        status: status as i32,
+        created_at: payment.created_at.to_rfc3339(),
+        confirmed_at: payment.confirmed_at.map(|at| at.to_rfc3339()),
+        settled_at: payment.settled_at.map(|at| at.to_rfc3339()),
+        cancel_reason: payment.cancel_reason,
+    }
+}
+
+fn pinned_recipient_message(
+    pinned: model::PinnedRecipient,
+    addresses: AddressDisplay,
+) -> proto::PinnedRecipient {
+    proto::PinnedRecipient {
+        name: pinned.name.to_string(),
+        address: addresses.show(&pinned.address),
+        resolved_at: pinned.resolved_at.to_rfc3339(),
+    }
+}
@@ -0,0 +1,750 @@
+//! Messages and service plumbing for `proto/settleone.proto`
+//!
+//! Written by hand so the build needs no `protoc`. Keep field tags and
+//! method paths in sync with the proto file; a test parses it and checks
+//! them against this file.
+
+use std::convert::Infallible;
+
+use tonic::body::BoxBody;
+use tonic::codec::ProstCodec;
+use tonic::codegen::{http, Body, BoxFuture, Context, Future, Poll, Service, StdError};
+use tonic::server::{Grpc, ServerStreamingService, UnaryService};
+use tonic::{Request, Response, Status};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum SessionStatus {
+    Unspecified = 0,
+    Active = 1,
+    Pending = 2,
+    Settled = 3,
+    Cancelled = 4,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum PaymentStatus {
+    Unspecified = 0,
+    Pending = 1,
+    Confirmed = 2,
+    Settled = 3,
+    Cancelled = 4,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Payment {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub recipient: String,
+    #[prost(string, optional, tag = "3")]
+    pub recipient_ens: Option<String>,
+    #[prost(string, tag = "4")]
+    pub amount: String,
+    #[prost(enumeration = "PaymentStatus", tag = "5")]
+    pub status: i32,
+    #[prost(string, tag = "6")]
+    pub created_at: String,
+    #[prost(string, optional, tag = "7")]
+    pub confirmed_at: Option<String>,
+    #[prost(string, optional, tag = "8")]
+    pub settled_at: Option<String>,
+    #[prost(string, optional, tag = "9")]
+    pub cancel_reason: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PinnedRecipient {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub address: String,
+    #[prost(string, tag = "3")]
+    pub resolved_at: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Session {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub user: String,
+    #[prost(enumeration = "SessionStatus", tag = "3")]
+    pub status: i32,
+    #[prost(message, repeated, tag = "4")]
+    pub payments: Vec<Payment>,
+    #[prost(string, tag = "5")]
+    pub total_amount: String,
+    #[prost(uint32, tag = "6")]
+    pub token_decimals: u32,
+    #[prost(string, optional, tag = "7")]
+    pub tx_hash: Option<String>,
+    #[prost(string, tag = "8")]
+    pub created_at: String,
+    #[prost(message, optional, tag = "9")]
+    pub pinned_recipient: Option<PinnedRecipient>,
+    #[prost(uint64, tag = "10")]
+    pub version: u64,
+    #[prost(string, optional, tag = "11")]
+    pub cancel_reason: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CreateSessionRequest {
+    #[prost(string, tag = "1")]
+    pub user_address: String,
+    #[prost(string, optional, tag = "2")]
+    pub recipient_name: Option<String>,
+    #[prost(uint32, optional, tag = "3")]
+    pub token_decimals: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CreateSessionResponse {
+    #[prost(string, tag = "1")]
+    pub session_id: String,
+    #[prost(string, tag = "2")]
+    pub status: String,
+    #[prost(message, optional, tag = "3")]
+    pub pinned_recipient: Option<PinnedRecipient>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetSessionRequest {
+    #[prost(string, tag = "1")]
+    pub session_id: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddPaymentRequest {
+    #[prost(string, tag = "1")]
+    pub session_id: String,
+    #[prost(string, tag = "2")]
+    pub recipient: String,
+    #[prost(string, optional, tag = "3")]
+    pub recipient_ens: Option<String>,
+    #[prost(string, tag = "4")]
+    pub amount: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FinalizeRequest {
+    #[prost(string, tag = "1")]
+    pub session_id: String,
+    #[prost(string, optional, tag = "2")]
+    pub tx_hash: Option<String>,
+    #[prost(string, optional, tag = "3")]
+    pub expected_total: Option<String>,
+    #[prost(uint64, optional, tag = "4")]
+    pub expected_payment_count: Option<u64>,
+    #[prost(bool, tag = "5")]
+    pub verify_ens: bool,
+    #[prost(bool, tag = "6")]
+    pub skip_dust: bool,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FinalizeResponse {
+    #[prost(string, tag = "1")]
+    pub session_id: String,
+    #[prost(string, tag = "2")]
+    pub status: String,
+    #[prost(string, optional, tag = "3")]
+    pub tx_hash: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WatchSessionRequest {
+    #[prost(string, tag = "1")]
+    pub session_id: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QuoteRequest {
+    #[prost(string, tag = "1")]
+    pub from_chain: String,
+    #[prost(string, tag = "2")]
+    pub to_chain: String,
+    #[prost(string, tag = "3")]
+    pub from_token: String,
+    #[prost(string, tag = "4")]
+    pub to_token: String,
+    #[prost(string, tag = "5")]
+    pub from_amount: String,
+    #[prost(string, optional, tag = "6")]
+    pub from_address: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QuoteResponse {
+    #[prost(string, tag = "1")]
+    pub from_amount: String,
+    #[prost(string, tag = "2")]
+    pub to_amount: String,
+    #[prost(string, tag = "3")]
+    pub estimated_gas: String,
+    #[prost(uint64, tag = "4")]
+    pub estimated_time: u64,
+    #[prost(string, optional, tag = "5")]
+    pub route_json: Option<String>,
+}
+
+/// Adapts an async closure to tonic's per-method service traits
+struct Method<F>(F);
+
+impl<F, Fut, Req, Resp> UnaryService<Req> for Method<F>
+where
+    F: FnMut(Request<Req>) -> Fut,
+    Fut: Future<Output = Result<Response<Resp>, Status>> + Send + 'static,
+{
+    type Response = Resp;
+    type Future = BoxFuture<Response<Resp>, Status>;
+
+    fn call(&mut self, request: Request<Req>) -> Self::Future {
+        Box::pin((self.0)(request))
+    }
+}
+
+impl<F, Fut, Req, Resp, S> ServerStreamingService<Req> for Method<F>
+where
+    F: FnMut(Request<Req>) -> Fut,
+    Fut: Future<Output = Result<Response<S>, Status>> + Send + 'static,
+    S: tonic::codegen::tokio_stream::Stream<Item = Result<Resp, Status>> + Send + 'static,
+{
+    type Response = Resp;
+    type ResponseStream = S;
+    type Future = BoxFuture<Response<S>, Status>;
+
+    fn call(&mut self, request: Request<Req>) -> Self::Future {
+        Box::pin((self.0)(request))
+    }
+}
+
+fn unary<Req, Resp, M, B>(method: M, request: http::Request<B>) -> RouteFuture
+where
+    Req: prost::Message + Default + Send + 'static,
+    Resp: prost::Message + Send + 'static,
+    M: UnaryService<Req, Response = Resp> + Send + 'static,
+    M::Future: Send,
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    Box::pin(async move {
+        let mut grpc = Grpc::new(ProstCodec::<Resp, Req>::default());
+        Ok(grpc.unary(method, request).await)
+    })
+}
+
+fn server_streaming<Req, Resp, M, B>(method: M, request: http::Request<B>) -> RouteFuture
+where
+    Req: prost::Message + Default + Send + 'static,
+    Resp: prost::Message + Send + 'static,
+    M: ServerStreamingService<Req, Response = Resp> + Send + 'static,
+    M::Future: Send,
+    M::ResponseStream: Send + 'static,
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    Box::pin(async move {
+        let mut grpc = Grpc::new(ProstCodec::<Resp, Req>::default());
+        Ok(grpc.server_streaming(method, request).await)
+    })
+}
+
+type RouteFuture = BoxFuture<http::Response<BoxBody>, Infallible>;
+
+/// Answer for a method this server does not have
+fn unimplemented() -> RouteFuture {
+    Box::pin(async {
+        let mut response = http::Response::new(tonic::codegen::empty_body());
+        let headers = response.headers_mut();
+        headers.insert(
+            Status::GRPC_STATUS,
+            (tonic::Code::Unimplemented as i32).into(),
+        );
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            tonic::metadata::GRPC_CONTENT_TYPE,
+        );
+        Ok(response)
+    })
+}
+
+pub mod session_service_server {
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// `settleone.v1.SessionService`
+    #[tonic::async_trait]
+    pub trait SessionService: Send + Sync + 'static {
+        /// Stream returned by `watch`
+        type WatchStream: tonic::codegen::tokio_stream::Stream<Item = Result<super::Session, Status>>
+            + Send
+            + 'static;
+
+        async fn create(
+            &self,
+            request: Request<super::CreateSessionRequest>,
+        ) -> Result<Response<super::CreateSessionResponse>, Status>;
+
+        async fn get(
+            &self,
+            request: Request<super::GetSessionRequest>,
+        ) -> Result<Response<super::Session>, Status>;
+
+        async fn add_payment(
+            &self,
+            request: Request<super::AddPaymentRequest>,
+        ) -> Result<Response<super::Session>, Status>;
+
+        async fn finalize(
+            &self,
+            request: Request<super::FinalizeRequest>,
+        ) -> Result<Response<super::FinalizeResponse>, Status>;
+
+        async fn watch(
+            &self,
+            request: Request<super::WatchSessionRequest>,
+        ) -> Result<Response<Self::WatchStream>, Status>;
+    }
+
+    /// Tower service routing `SessionService` calls to `T`
+    pub struct SessionServiceServer<T> {
+        inner: Arc<T>,
+    }
+
+    impl<T> SessionServiceServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self {
+                inner: Arc::new(inner),
+            }
+        }
+    }
+
+    impl<T> Clone for SessionServiceServer<T> {
+        fn clone(&self) -> Self {
+            Self {
+                inner: self.inner.clone(),
+            }
+        }
+    }
+
+    impl<T, B> Service<http::Request<B>> for SessionServiceServer<T>
+    where
+        T: SessionService,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = RouteFuture;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<B>) -> RouteFuture {
+            let inner = self.inner.clone();
+            match request.uri().path() {
+                "/settleone.v1.SessionService/Create" => unary(
+                    Method(move |r| {
+                        let inner = inner.clone();
+                        async move { inner.create(r).await }
+                    }),
+                    request,
+                ),
+                "/settleone.v1.SessionService/Get" => unary(
+                    Method(move |r| {
+                        let inner = inner.clone();
+                        async move { inner.get(r).await }
+                    }),
+                    request,
+                ),
+                "/settleone.v1.SessionService/AddPayment" => unary(
+                    Method(move |r| {
+                        let inner = inner.clone();
+                        async move { inner.add_payment(r).await }
+                    }),
+                    request,
+                ),
+                "/settleone.v1.SessionService/Finalize" => unary(
+                    Method(move |r| {
+                        let inner = inner.clone();
+                        async move { inner.finalize(r).await }
+                    }),
+                    request,
+                ),
+                "/settleone.v1.SessionService/Watch" => server_streaming(
+                    Method(move |r| {
+                        let inner = inner.clone();
+                        async move { inner.watch(r).await }
+                    }),
+                    request,
+                ),
+                _ => unimplemented(),
+            }
+        }
+    }
+
+    impl<T> tonic::server::NamedService for SessionServiceServer<T> {
+        const NAME: &'static str = "settleone.v1.SessionService";
+    }
+}
+
+/// Only served with the `lifi` feature
+#[cfg(feature = "lifi")]
+pub mod quote_service_server {
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// `settleone.v1.QuoteService`
+    #[tonic::async_trait]
+    pub trait QuoteService: Send + Sync + 'static {
+        async fn get_quote(
+            &self,
+            request: Request<super::QuoteRequest>,
+        ) -> Result<Response<super::QuoteResponse>, Status>;
+    }
+
+    /// Tower service routing `QuoteService` calls to `T`
+    pub struct QuoteServiceServer<T> {
+        inner: Arc<T>,
+    }
+
+    impl<T> QuoteServiceServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self {
+                inner: Arc::new(inner),
+            }
+        }
+    }
+
+    impl<T> Clone for QuoteServiceServer<T> {
+        fn clone(&self) -> Self {
+            Self {
+                inner: self.inner.clone(),
+            }
+        }
+    }
+
+    impl<T, B> Service<http::Request<B>> for QuoteServiceServer<T>
+    where
+        T: QuoteService,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = RouteFuture;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<B>) -> RouteFuture {
+            let inner = self.inner.clone();
+            match request.uri().path() {
+                "/settleone.v1.QuoteService/GetQuote" => unary(
+                    Method(move |r| {
+                        let inner = inner.clone();
+                        async move { inner.get_quote(r).await }
+                    }),
+                    request,
+                ),
+                _ => unimplemented(),
+            }
+        }
+    }
+
+    impl<T> tonic::server::NamedService for QuoteServiceServer<T> {
+        const NAME: &'static str = "settleone.v1.QuoteService";
+    }
+}
+
+/// Clients for the tests; partners generate their own from the proto file
+#[cfg(test)]
+pub mod client {
+    use tonic::client::{Grpc, GrpcService};
+    use tonic::codegen::http::uri::PathAndQuery;
+    use tonic::codegen::{http::Uri, Bytes};
+    use tonic::{IntoRequest, Streaming};
+
+    use super::*;
+
+    /// Client for both services over one connection
+    pub struct SettleOneGrpcClient<T> {
+        inner: Grpc<T>,
+    }
+
+    impl<T> SettleOneGrpcClient<T>
+    where
+        T: GrpcService<BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            Self {
+                inner: Grpc::with_origin(inner, origin),
+            }
+        }
+
+        async fn ready(&mut self) -> Result<(), Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| Status::unknown(format!("Service was not ready: {}", e.into())))
+        }
+
+        async fn unary<Req, Resp>(
+            &mut self,
+            request: impl IntoRequest<Req>,
+            path: &'static str,
+        ) -> Result<Response<Resp>, Status>
+        where
+            Req: prost::Message + Send + 'static,
+            Resp: prost::Message + Default + Send + 'static,
+        {
+            self.ready().await?;
+            let codec = ProstCodec::<Req, Resp>::default();
+            self.inner
+                .unary(
+                    request.into_request(),
+                    PathAndQuery::from_static(path),
+                    codec,
+                )
+                .await
+        }
+
+        pub async fn create(
+            &mut self,
+            request: impl IntoRequest<CreateSessionRequest>,
+        ) -> Result<Response<CreateSessionResponse>, Status> {
+            self.unary(request, "/settleone.v1.SessionService/Create")
+                .await
+        }
+
+        pub async fn get(
+            &mut self,
+            request: impl IntoRequest<GetSessionRequest>,
+        ) -> Result<Response<Session>, Status> {
+            self.unary(request, "/settleone.v1.SessionService/Get")
+                .await
+        }
+
+        pub async fn add_payment(
+            &mut self,
+            request: impl IntoRequest<AddPaymentRequest>,
+        ) -> Result<Response<Session>, Status> {
+            self.unary(request, "/settleone.v1.SessionService/AddPayment")
+                .await
+        }
+
+        pub async fn finalize(
+            &mut self,
+            request: impl IntoRequest<FinalizeRequest>,
+        ) -> Result<Response<FinalizeResponse>, Status> {
+            self.unary(request, "/settleone.v1.SessionService/Finalize")
+                .await
+        }
+
+        pub async fn watch(
+            &mut self,
+            request: impl IntoRequest<WatchSessionRequest>,
+        ) -> Result<Response<Streaming<Session>>, Status> {
+            self.ready().await?;
+            let codec = ProstCodec::<WatchSessionRequest, Session>::default();
+            self.inner
+                .server_streaming(
+                    request.into_request(),
+                    PathAndQuery::from_static("/settleone.v1.SessionService/Watch"),
+                    codec,
+                )
+                .await
+        }
+
+        #[cfg(feature = "lifi")]
+        pub async fn get_quote(
+            &mut self,
+            request: impl IntoRequest<QuoteRequest>,
+        ) -> Result<Response<QuoteResponse>, Status> {
+            self.unary(request, "/settleone.v1.QuoteService/GetQuote")
+                .await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    const PROTO: &str = include_str!("../../proto/settleone.proto");
+    const SOURCE: &str = include_str!("proto.rs");
+
+    /// Messages by name, each field by name with its number and the
+    /// `#[prost(..)]` attribute it takes, less the tag; enums by name with
+    /// their variants; and the full path of every method
+    #[derive(Debug, Default, PartialEq)]
+    struct Schema {
+        messages: BTreeMap<String, BTreeMap<String, (u32, String)>>,
+        enums: BTreeMap<String, BTreeMap<String, i32>>,
+        methods: BTreeSet<String>,
+    }
+
+    /// `SessionStatus` as `SESSION_STATUS`
+    fn screaming_snake(name: &str) -> String {
+        let mut out = String::new();
+        for (i, c) in name.chars().enumerate() {
+            if i > 0 && c.is_ascii_uppercase() {
+                out.push('_');
+            }
+            out.push(c.to_ascii_uppercase());
+        }
+        out
+    }
+
+    /// `ACTIVE` as `Active`
+    fn upper_camel(name: &str) -> String {
+        name.split('_')
+            .map(|word| word[..1].to_string() + &word[1..].to_ascii_lowercase())
+            .collect()
+    }
+
+    /// The schema as prost-build would generate it from the proto file
+    fn proto_schema() -> Schema {
+        let text: Vec<&str> = PROTO
+            .lines()
+            .map(|line| line.split("//").next().unwrap_or_default())
+            .collect();
+        let text = ["{", "}", ";", "=", "(", ")"]
+            .iter()
+            .fold(text.join("\n"), |text, p| {
+                text.replace(p, &format!(" {} ", p))
+            });
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+
+        let mut package = "";
+        let mut fields = BTreeMap::new();
+        let mut schema = Schema::default();
+        let mut i = 0;
+        while i < tokens.len() {
+            let kind = tokens[i];
+            if kind == "package" {
+                package = tokens[i + 1];
+            }
+            if !["message", "enum", "service"].contains(&kind) {
+                i += 1;
+                continue;
+            }
+            let name = tokens[i + 1];
+            let end = i + tokens[i..].iter().position(|t| *t == "}").unwrap();
+            for statement in tokens[i + 3..end].split(|t| *t == ";") {
+                match (kind, statement) {
+                    ("message", [label @ ("optional" | "repeated"), ty, field, "=", n]) => {
+                        fields.insert((name, *field), (Some(*label), *ty, n.parse().unwrap()));
+                    }
+                    ("message", [ty, field, "=", n]) => {
+                        fields.insert((name, *field), (None, *ty, n.parse().unwrap()));
+                    }
+                    ("enum", [value, "=", n]) => {
+                        let prefix = format!("{}_", screaming_snake(name));
+                        let variant = upper_camel(value.strip_prefix(&prefix).unwrap());
+                        schema
+                            .enums
+                            .entry(name.to_string())
+                            .or_default()
+                            .insert(variant, n.parse().unwrap());
+                    }
+                    ("service", ["rpc", method, ..]) => {
+                        schema
+                            .methods
+                            .insert(format!("/{}.{}/{}", package, name, method));
+                    }
+                    (_, []) => {}
+                    _ => panic!("unexpected statement in {} {}: {:?}", kind, name, statement),
+                }
+            }
+            i = end + 1;
+        }
+
+        for ((message, field), (label, ty, number)) in fields {
+            let attribute = if schema.enums.contains_key(ty) {
+                format!("enumeration = \"{}\"", ty)
+            } else if ty.starts_with(|c: char| c.is_ascii_uppercase()) {
+                // Message fields are always optional in prost
+                format!("message, {}", label.unwrap_or("optional"))
+            } else {
+                label.map_or(ty.to_string(), |label| format!("{}, {}", ty, label))
+            };
+            schema
+                .messages
+                .entry(message.to_string())
+                .or_default()
+                .insert(field.to_string(), (number, attribute));
+        }
+        schema
+    }
+
+    /// The schema as this file declares it
+    fn rust_schema() -> Schema {
+        let mut schema = Schema::default();
+        let mut item: Option<(&str, &str)> = None;
+        let mut attribute: Option<&str> = None;
+        for line in SOURCE.lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix("pub struct ") {
+                item = Some(("struct", name.trim_end_matches(" {")));
+            } else if let Some(name) = line.strip_prefix("pub enum ") {
+                item = Some(("enum", name.trim_end_matches(" {")));
+            } else if line == "}" {
+                item = None;
+            } else if let Some(attr) = line
+                .strip_prefix("#[prost(")
+                .and_then(|attr| attr.strip_suffix(")]"))
+            {
+                attribute = Some(attr);
+            } else if let (Some(("struct", name)), Some(attr)) = (item, attribute.take()) {
+                let field = line
+                    .strip_prefix("pub ")
+                    .unwrap()
+                    .split(':')
+                    .next()
+                    .unwrap();
+                let (attr, tag) = attr.rsplit_once(", tag = ").unwrap();
+                schema.messages.entry(name.to_string()).or_default().insert(
+                    field.to_string(),
+                    (tag.trim_matches('"').parse().unwrap(), attr.to_string()),
+                );
+            } else if let (Some(("enum", name)), Some((variant, value))) = (
+                item,
+                line.strip_suffix(',').and_then(|l| l.split_once(" = ")),
+            ) {
+                schema
+                    .enums
+                    .entry(name.to_string())
+                    .or_default()
+                    .insert(variant.to_string(), value.parse().unwrap());
+            }
+        }
+        // Method paths are the string literals starting with the package,
+        // spelled in two parts so this one is not taken for a path
+        let package = concat!("\"/settleone", ".v1.");
+        for (start, _) in SOURCE.match_indices(package) {
+            let path = &SOURCE[start + 1..];
+            schema
+                .methods
+                .insert(path[..path.find('"').unwrap()].to_string());
+        }
+        schema
+    }
+
+    #[test]
+    fn test_messages_and_methods_match_the_proto_file() {
+        let proto = proto_schema();
+        assert_eq!(proto.messages.len(), 12);
+        assert_eq!(proto.methods.len(), 6);
+        assert_eq!(rust_schema(), proto);
+    }
+}
@@ -0,0 +1,195 @@
+//! Public listener address
+//!
+//! `LISTEN` selects a TCP address (`tcp://0.0.0.0:3001`) or a Unix domain
+//! socket (`unix:///var/run/settleone.sock`) for sidecar deployments behind
+//! a local proxy. A socket file is created with `LISTEN_SOCKET_MODE`
+//! permissions, replacing a stale file, and removed again on shutdown.
+
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use axum::Router;
+
+/// Default permissions of the socket file: owner and group may connect
+pub const DEFAULT_SOCKET_MODE: u32 = 0o660;
+
+/// Where the public listener binds
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl ListenAddr {
+    /// Parse a `LISTEN` value (`tcp://host:port` or `unix:///path`)
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if let Some(addr) = value.strip_prefix("tcp://") {
+            return addr
+                .parse()
+                .map(ListenAddr::Tcp)
+                .map_err(|_| format!("'{}' is not a host:port address", addr));
+        }
+        if let Some(path) = value.strip_prefix("unix://") {
+            if !cfg!(unix) {
+                return Err("unix sockets are not supported on this platform".to_string());
+            }
+            if !path.starts_with('/') {
+                return Err(format!("'{}' is not an absolute socket path", path));
+            }
+            return Ok(ListenAddr::Unix(PathBuf::from(path)));
+        }
+        Err(format!("'{}' must start with tcp:// or unix://", value))
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "tcp://{}", addr),
+            ListenAddr::Unix(path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
+
+/// A bound listener
+pub enum Listener {
+    Tcp(std::net::TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener, PathBuf),
+}
+
+impl Listener {
+    /// Bind `addr`; a socket file gets permissions `mode`
+    pub fn bind(addr: &ListenAddr, mode: u32) -> std::io::Result<Self> {
+        match addr {
+            ListenAddr::Tcp(addr) => Ok(Listener::Tcp(std::net::TcpListener::bind(addr)?)),
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => {
+                use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+                // Left behind by a process that did not shut down cleanly
+                if let Ok(meta) = std::fs::symlink_metadata(path) {
+                    if !meta.file_type().is_socket() {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::AlreadyExists,
+                            format!("{} exists and is not a socket", path.display()),
+                        ));
+                    }
+                    std::fs::remove_file(path)?;
+                }
+                let listener = std::os::unix::net::UnixListener::bind(path)?;
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+                Ok(Listener::Unix(listener, path.clone()))
+            }
+            #[cfg(not(unix))]
+            ListenAddr::Unix(_) => {
+                let _ = mode;
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "unix sockets are not supported on this platform",
+                ))
+            }
+        }
+    }
+}
+
+/// Serve `app` on a Unix socket until `stopped` resolves, then let open
+/// connections finish and remove the socket file
+#[cfg(unix)]
+pub async fn serve_unix(
+    listener: std::os::unix::net::UnixListener,
+    path: PathBuf,
+    app: Router,
+    stopped: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use hyper_util::server::conn::auto::Builder;
+    use hyper_util::server::graceful::GracefulShutdown;
+    use hyper_util::service::TowerToHyperService;
+
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::UnixListener::from_std(listener)?;
+    let builder = Builder::new(TokioExecutor::new());
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(stopped);
+
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!("Failed to accept on {}: {}", path.display(), e);
+                    continue;
+                }
+            },
+            _ = &mut stopped => break,
+        };
+        let service = TowerToHyperService::new(app.clone());
+        let conn = builder
+            .serve_connection_with_upgrades(TokioIo::new(stream), service)
+            .into_owned();
+        let conn = graceful.watch(conn);
+        tokio::spawn(async move {
+            if let Err(e) = conn.await {
+                tracing::debug!("Unix socket connection error: {}", e);
+            }
+        });
+    }
+
+    drop(listener);
+    graceful.shutdown().await;
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            ListenAddr::parse("tcp://0.0.0.0:3001").unwrap(),
+            ListenAddr::Tcp("0.0.0.0:3001".parse().unwrap())
+        );
+        assert_eq!(
+            ListenAddr::parse("unix:///var/run/settleone.sock").unwrap(),
+            ListenAddr::Unix("/var/run/settleone.sock".into())
+        );
+        assert_eq!(
+            ListenAddr::parse("unix:///tmp/a.sock").unwrap().to_string(),
+            "unix:///tmp/a.sock"
+        );
+
+        assert!(ListenAddr::parse("0.0.0.0:3001").is_err());
+        assert!(ListenAddr::parse("tcp://localhost").is_err());
+        assert!(ListenAddr::parse("unix://relative.sock").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_bind_replaces_stale_socket_and_applies_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("api.sock");
+        let addr = ListenAddr::Unix(path.clone());
+
+        let first = Listener::bind(&addr, DEFAULT_SOCKET_MODE).unwrap();
+        drop(first);
+        assert!(path.exists());
+
+        Listener::bind(&addr, 0o600).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // Only sockets are replaced
+        let file = dir.path().join("data.txt");
+        std::fs::write(&file, "keep").unwrap();
+        assert!(Listener::bind(&ListenAddr::Unix(file.clone()), DEFAULT_SOCKET_MODE).is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep");
+    }
+}
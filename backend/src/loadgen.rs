@@ -0,0 +1,305 @@
+//! Load generator (`loadgen` subcommand)
+//!
+//! Runs virtual users that each repeat create session → add payments →
+//! finalize through [`SettleOneClient`], then reports latency percentiles
+//! per endpoint, error counts and achieved requests per second. Without
+//! `--url` the server is started in-process on a loopback port with the
+//! default configuration (rate limits off), so the run is self-contained
+//! and usable as a regression benchmark in CI.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use clap::Args;
+use serde::Serialize;
+use settleone_client::{ClientError, SettleOneClient};
+use settleone_types::address::Address;
+use settleone_types::api::{AddPaymentRequest, CreateSessionRequest, FinalizeRequest};
+
+use crate::config::Config;
+use crate::AppState;
+
+/// Address every virtual user pays to
+const RECIPIENT: &str = "0x000000000000000000000000000000000000dEaD";
+
+#[derive(Args, Debug, Clone)]
+pub struct LoadgenArgs {
+    /// Server to load, e.g. http://localhost:3001 (default: an in-process server)
+    #[arg(long)]
+    pub url: Option<String>,
+    /// API key sent as X-Api-Key
+    #[arg(long)]
+    pub api_key: Option<String>,
+    /// Concurrent virtual users
+    #[arg(long, default_value_t = 10)]
+    pub users: usize,
+    /// Sessions each user creates and finalizes
+    #[arg(long, default_value_t = 10)]
+    pub iterations: usize,
+    /// Payments added to each session
+    #[arg(long, default_value_t = 3)]
+    pub payments: usize,
+    /// Pause between a user's requests, in milliseconds
+    #[arg(long, default_value_t = 0)]
+    pub pace_ms: u64,
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Latency summary of one endpoint
+#[derive(Debug, Serialize, PartialEq)]
+pub struct EndpointReport {
+    pub requests: usize,
+    pub errors: usize,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    /// Message of the first failed request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_error: Option<String>,
+}
+
+/// Result of a load run
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub target: String,
+    pub users: usize,
+    pub iterations: usize,
+    pub payments: usize,
+    pub elapsed_secs: f64,
+    pub requests: usize,
+    pub errors: usize,
+    pub rps: f64,
+    pub endpoints: BTreeMap<&'static str, EndpointReport>,
+}
+
+/// Raw samples of one endpoint
+#[derive(Default)]
+struct Samples {
+    latencies: Vec<Duration>,
+    errors: usize,
+    first_error: Option<String>,
+}
+
+/// Samples recorded by one virtual user, keyed by endpoint
+type Recorder = BTreeMap<&'static str, Samples>;
+
+/// Run the load test and print its report; fails if any request failed
+pub async fn run(args: LoadgenArgs) -> anyhow::Result<()> {
+    let target = match &args.url {
+        Some(url) => url.clone(),
+        None => format!("http://{}", spawn_in_process().await?),
+    };
+    let report = generate(&args, &target).await;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report.human());
+    }
+    if report.errors > 0 {
+        anyhow::bail!("{} of {} requests failed", report.errors, report.requests);
+    }
+    Ok(())
+}
+
+/// Serve the app with the default configuration on a loopback port
+async fn spawn_in_process() -> std::io::Result<SocketAddr> {
+    let mut config = Config::default();
+    config.dynamic.rate_limit_read_per_minute = 0;
+    config.dynamic.rate_limit_write_per_minute = 0;
+    config.dynamic.rate_limit_quote_per_minute = 0;
+    let state = AppState::new(config);
+    state.initialized.set();
+    let app = crate::create_app(state);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    tokio::spawn(async move { axum::serve(listener, service).await });
+    Ok(addr)
+}
+
+/// Drive `args.users` virtual users against `target`
+async fn generate(args: &LoadgenArgs, target: &str) -> Report {
+    let mut client = SettleOneClient::new(target);
+    if let Some(key) = &args.api_key {
+        client = client.with_api_key(key);
+    }
+
+    let started = Instant::now();
+    let users: Vec<_> = (0..args.users)
+        .map(|user| tokio::spawn(virtual_user(client.clone(), user, args.clone())))
+        .collect();
+    let mut samples = Recorder::new();
+    for recorded in futures::future::join_all(users).await {
+        // A panicked user contributes no samples
+        for (endpoint, user_samples) in recorded.unwrap_or_default() {
+            let merged = samples.entry(endpoint).or_default();
+            merged.latencies.extend(user_samples.latencies);
+            merged.errors += user_samples.errors;
+            merged.first_error = merged.first_error.take().or(user_samples.first_error);
+        }
+    }
+    let elapsed = started.elapsed();
+
+    let endpoints: BTreeMap<_, _> = samples
+        .into_iter()
+        .map(|(endpoint, samples)| (endpoint, summarize(samples)))
+        .collect();
+    let requests = endpoints.values().map(|e| e.requests).sum();
+    Report {
+        target: target.to_string(),
+        users: args.users,
+        iterations: args.iterations,
+        payments: args.payments,
+        elapsed_secs: elapsed.as_secs_f64(),
+        requests,
+        errors: endpoints.values().map(|e| e.errors).sum(),
+        rps: requests as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        endpoints,
+    }
+}
+
+/// One user's create → add payments → finalize loop
+async fn virtual_user(client: SettleOneClient, user: usize, args: LoadgenArgs) -> Recorder {
+    let mut recorder = Recorder::new();
+    let pace = Duration::from_millis(args.pace_ms);
+    let user_address = Address::try_from(format!("0x{:040x}", user + 1).as_str())
+        .expect("formatted address is valid");
+    let recipient = Address::try_from(RECIPIENT).expect("RECIPIENT is valid");
+
+    for iteration in 0..args.iterations {
+        let request = CreateSessionRequest {
+            user_address: user_address.clone(),
+            recipient_name: None,
+            token_decimals: None,
+            settlement_mode: None,
+            display_currency: None,
+        };
+        let created = timed(
+            &mut recorder,
+            "create_session",
+            client.create_session(&request),
+        )
+        .await;
+        let Some(session) = created else {
+            continue;
+        };
+        tokio::time::sleep(pace).await;
+
+        for _ in 0..args.payments {
+            let request = AddPaymentRequest {
+                recipient: recipient.clone(),
+                recipient_ens: None,
+                amount: Some(1_000_000u64.into()),
+                human_amount: None,
+            };
+            let payment = client.add_payment(&session.session_id, &request);
+            timed(&mut recorder, "add_payment", payment).await;
+            tokio::time::sleep(pace).await;
+        }
+
+        let request = FinalizeRequest {
+            tx_hash: Some(format!("0x{:032x}{:032x}", user, iteration)),
+            ..Default::default()
+        };
+        let finalized = client.finalize(&session.session_id, &request);
+        timed(&mut recorder, "finalize", finalized).await;
+        tokio::time::sleep(pace).await;
+    }
+    recorder
+}
+
+/// Await `call`, recording its latency and outcome under `endpoint`
+async fn timed<T>(
+    recorder: &mut Recorder,
+    endpoint: &'static str,
+    call: impl Future<Output = Result<T, ClientError>>,
+) -> Option<T> {
+    let started = Instant::now();
+    let result = call.await;
+    let samples = recorder.entry(endpoint).or_default();
+    samples.latencies.push(started.elapsed());
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            samples.errors += 1;
+            samples.first_error.get_or_insert_with(|| e.to_string());
+            None
+        }
+    }
+}
+
+fn summarize(mut samples: Samples) -> EndpointReport {
+    samples.latencies.sort();
+    let ms = |q: f64| percentile(&samples.latencies, q).as_secs_f64() * 1000.0;
+    EndpointReport {
+        requests: samples.latencies.len(),
+        errors: samples.errors,
+        p50_ms: ms(0.50),
+        p95_ms: ms(0.95),
+        p99_ms: ms(0.99),
+        max_ms: ms(1.0),
+        first_error: samples.first_error,
+    }
+}
+
+/// Nearest-rank percentile of `sorted`; zero when there are no samples
+fn percentile(sorted: &[Duration], q: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+impl Report {
+    /// Plain-text table of the report
+    fn human(&self) -> String {
+        let mut out = format!(
+            "target {}: {} users x {} sessions x {} payments\n\n",
+            self.target, self.users, self.iterations, self.payments
+        );
+        out.push_str(&format!(
+            "{:<16} {:>8} {:>7} {:>9} {:>9} {:>9} {:>9}\n",
+            "endpoint", "requests", "errors", "p50 ms", "p95 ms", "p99 ms", "max ms"
+        ));
+        for (endpoint, e) in &self.endpoints {
+            out.push_str(&format!(
+                "{:<16} {:>8} {:>7} {:>9.2} {:>9.2} {:>9.2} {:>9.2}\n",
+                endpoint, e.requests, e.errors, e.p50_ms, e.p95_ms, e.p99_ms, e.max_ms
+            ));
+        }
+        for (endpoint, e) in &self.endpoints {
+            if let Some(error) = &e.first_error {
+                out.push_str(&format!("\n{} first error: {}", endpoint, error));
+            }
+        }
+        out.push_str(&format!(
+            "\n{} requests, {} errors in {:.2}s ({:.1} req/s)\n",
+            self.requests, self.errors, self.elapsed_secs, self.rps
+        ));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile_nearest_rank() {
+        let sorted: Vec<_> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&sorted, 0.50), Duration::from_millis(50));
+        assert_eq!(percentile(&sorted, 0.95), Duration::from_millis(95));
+        assert_eq!(percentile(&sorted, 0.99), Duration::from_millis(99));
+        assert_eq!(percentile(&sorted, 1.0), Duration::from_millis(100));
+        assert_eq!(percentile(&sorted[..1], 0.99), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 0.5), Duration::ZERO);
+    }
+}
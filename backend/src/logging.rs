@@ -0,0 +1,307 @@
+//! Tracing subscriber setup
+//!
+//! `LOG_FORMAT=pretty` (the default) keeps the human-readable formatter;
+//! `LOG_FORMAT=json` emits one JSON object per event for log pipelines.
+//! Both formats redact sensitive fields such as API keys and signatures,
+//! and so does [`redact_json`] for logged request/response bodies.
+
+use std::backtrace::Backtrace;
+use std::fmt;
+
+use opentelemetry_sdk::trace::TracerProvider;
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::field::MakeExt;
+use tracing_subscriber::fmt::format::{self, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, MakeWriter};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+use crate::api::middleware::current_request_id;
+use crate::telemetry;
+
+/// Replacement for redacted field values
+const REDACTED: &str = "[REDACTED]";
+
+/// Field names (or name fragments) whose values are never logged
+const SENSITIVE_FIELDS: &[&str] = &[
+    "api_key",
+    "authorization",
+    "signature",
+    "private_key",
+    "secret",
+    "password",
+];
+
+/// Log output format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Pretty,
+    Json,
+}
+
+impl LogFormat {
+    /// Parse a `LOG_FORMAT` value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "pretty" => Some(LogFormat::Pretty),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a field's value must be redacted (`api_key`, `apiKey` and
+/// `x-api-key` all match)
+fn is_sensitive(name: &str) -> bool {
+    let name: String = name
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    SENSITIVE_FIELDS
+        .iter()
+        .any(|s| name.contains(&s.replace('_', "")))
+}
+
+/// Whether `value` is a `0x`-prefixed 20-byte hex address
+fn is_address(value: &str) -> bool {
+    value.len() == 42
+        && value.starts_with("0x")
+        && value[2..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Redact sensitive fields of a JSON body in place, at any depth, and with
+/// `addresses` also every string holding an `0x` address
+pub fn redact_json(value: &mut Value, addresses: bool) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if is_sensitive(key) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(value, addresses);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| redact_json(v, addresses)),
+        Value::String(s) if addresses && is_address(s) => *s = REDACTED.to_string(),
+        _ => {}
+    }
+}
+
+/// Install the global subscriber (filter from `RUST_LOG`), exporting spans
+/// through `tracer_provider` when one is configured
+pub fn init(format: LogFormat, tracer_provider: Option<&TracerProvider>) {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "settleone_backend=debug,tower_http=debug".into());
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracer_provider.map(telemetry::layer));
+
+    match format {
+        LogFormat::Pretty => {
+            let fields = format::debug_fn(|writer, field, value| {
+                if is_sensitive(field.name()) {
+                    write!(writer, "{}={}", field, REDACTED)
+                } else if field.name() == "message" {
+                    write!(writer, "{:?}", value)
+                } else {
+                    write!(writer, "{}={:?}", field, value)
+                }
+            })
+            .delimited(" ");
+            registry
+                .with(tracing_subscriber::fmt::layer().fmt_fields(fields))
+                .init();
+        }
+        LogFormat::Json => registry.with(json_layer(std::io::stdout)).init(),
+    }
+
+    install_panic_hook();
+}
+
+/// Report panics through tracing (with backtrace and request id) instead of
+/// printing them to stderr, so they reach the log pipeline
+fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        tracing::error!(
+            request_id = current_request_id().unwrap_or_default(),
+            backtrace = %Backtrace::force_capture(),
+            "panic: {}",
+            info
+        );
+    }));
+}
+
+/// Formatting layer writing one JSON object per event to `make_writer`
+pub fn json_layer<S, W>(
+    make_writer: W,
+) -> tracing_subscriber::fmt::Layer<S, format::DefaultFields, JsonFormat, W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + 'static,
+{
+    tracing_subscriber::fmt::layer()
+        .event_format(JsonFormat)
+        .with_writer(make_writer)
+}
+
+/// Event formatter producing `{timestamp, level, target, message, ...fields}`.
+///
+/// Events emitted while handling a request carry its `request_id` even when
+/// the call site does not record one.
+pub struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut object = Map::new();
+        object.insert(
+            "timestamp".to_string(),
+            Value::String(chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)),
+        );
+        object.insert(
+            "level".to_string(),
+            Value::String(metadata.level().to_string()),
+        );
+        object.insert(
+            "target".to_string(),
+            Value::String(metadata.target().to_string()),
+        );
+
+        let mut visitor = JsonVisitor(&mut object);
+        event.record(&mut visitor);
+
+        if !object.contains_key("request_id") {
+            if let Some(id) = current_request_id() {
+                object.insert("request_id".to_string(), Value::String(id));
+            }
+        }
+
+        writeln!(writer, "{}", Value::Object(object))
+    }
+}
+
+/// Collects event fields into a JSON object, redacting sensitive ones
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl JsonVisitor<'_> {
+    fn insert(&mut self, field: &Field, value: Value) {
+        let value = if is_sensitive(field.name()) {
+            Value::String(REDACTED.to_string())
+        } else {
+            value
+        };
+        self.0.insert(field.name().to_string(), value);
+    }
+}
+
+impl Visit for JsonVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, Value::String(value.to_string()));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, Value::String(format!("{:?}", value)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Capture;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_line_has_expected_keys() {
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(json_layer(capture.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(
+                request_id = "req-1",
+                method = "POST",
+                route = "/api/v1/session",
+                status = 201u16,
+                latency_ms = 3u64,
+                api_key = "super-secret",
+                "request completed"
+            );
+        });
+
+        let output = capture.output();
+        assert!(!output.contains("super-secret"));
+
+        let line: Value = serde_json::from_str(output.lines().next().unwrap()).unwrap();
+        for key in ["timestamp", "level", "target", "message"] {
+            assert!(line[key].is_string(), "missing {}", key);
+        }
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["message"], "request completed");
+        assert_eq!(line["request_id"], "req-1");
+        assert_eq!(line["route"], "/api/v1/session");
+        assert_eq!(line["status"], 201);
+        assert_eq!(line["latency_ms"], 3);
+        assert_eq!(line["api_key"], REDACTED);
+    }
+
+    #[test]
+    fn test_sensitive_field_names() {
+        assert!(is_sensitive("api_key"));
+        assert!(is_sensitive("x_api_key"));
+        assert!(is_sensitive("privateKey"));
+        assert!(is_sensitive("Authorization"));
+        assert!(is_sensitive("tx_signature"));
+        assert!(!is_sensitive("route"));
+        assert_eq!(LogFormat::parse("JSON"), Some(LogFormat::Json));
+        assert_eq!(LogFormat::parse("xml"), None);
+    }
+
+    #[test]
+    fn test_redact_json() {
+        let address = "0x1111111111111111111111111111111111111111";
+        let body = json!({
+            "user_address": address,
+            "signature": "0xdeadbeef",
+            "payments": [{ "recipient": address, "privateKey": "abc", "amount": "1" }],
+        });
+
+        let mut redacted = body.clone();
+        redact_json(&mut redacted, false);
+        assert_eq!(redacted["signature"], REDACTED);
+        assert_eq!(redacted["user_address"], address);
+        assert_eq!(redacted["payments"][0]["amount"], "1");
+
+        let mut redacted = body;
+        redact_json(&mut redacted, true);
+        assert_eq!(redacted["user_address"], REDACTED);
+        assert_eq!(redacted["payments"][0]["recipient"], REDACTED);
+    }
+}
@@ -7,115 +7,935 @@
 //! - Arc chain settlement
 
 mod api;
+mod cli;
 mod config;
+mod graphql;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod listen;
+mod loadgen;
+mod logging;
 mod models;
+mod reporting;
 mod services;
+mod telemetry;
+#[cfg(test)]
+mod testing;
+mod tls;
 mod utils;
 
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use axum::{
-    routing::{delete, get, post},
-    Router,
+    middleware,
+    routing::{delete, get, post, MethodRouter},
+    Extension, Router,
 };
-use tower_http::cors::{Any, CorsLayer};
+use tokio_util::sync::CancellationToken;
+use tower_http::catch_panic::CatchPanicLayer;
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 use tower_http::trace::TraceLayer;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use utoipa_swagger_ui::SwaggerUi;
 
+use crate::api::ApiVersion;
+use crate::config::{Config, ConfigError, ConfigLoader, LiveConfig};
+use crate::listen::Listener;
+use crate::logging::LogFormat;
+use crate::reporting::ErrorReporter;
+#[cfg(feature = "settlement")]
+use crate::services::chain::NonceManager;
+#[cfg(feature = "ens")]
 use crate::services::ens::EnsService;
+#[cfg(feature = "ens")]
+use crate::services::ens_cache;
+use crate::services::fx::FiatRateSource;
+use crate::services::health::{Initialized, ReadinessService};
+use crate::services::jobs::JobRunner;
+#[cfg(feature = "lifi")]
+use crate::services::lifi::LifiService;
+#[cfg(feature = "settlement")]
+use crate::services::prices::NativePriceSource;
+use crate::services::prices::{PriceService, PriceSource};
+#[cfg(feature = "lifi")]
+use crate::services::quote_cache::QuoteCache;
+use crate::services::rate_limit::RateLimiter;
 use crate::services::session::SessionStore;
+#[cfg(feature = "settlement")]
+use crate::services::settlement::{SettlementService, NATIVE_PRICE_TTL};
+#[cfg(feature = "settlement")]
+use crate::services::settlement_batch::SettlementBatcher;
+use crate::services::snapshot::Snapshot;
+use crate::services::template::TemplateStore;
+use crate::services::webhook::WebhookService;
+use crate::tls::RustlsConfig;
 
 /// Shared application state
+///
+/// Integration services only exist when their cargo feature is enabled.
 #[derive(Clone)]
 pub struct AppState {
     pub session_store: Arc<SessionStore>,
+    pub template_store: Arc<TemplateStore>,
+    /// Webhook receivers of session payment events
+    pub webhooks: Arc<WebhookService>,
+    #[cfg(feature = "ens")]
     pub ens_service: Arc<EnsService>,
+    /// Configuration as loaded at startup
+    pub config: Arc<Config>,
+    /// Current hot-reloadable settings; read these instead of `config.dynamic`
+    pub live_config: LiveConfig,
+    /// Where `reload_config` reads the new configuration from
+    pub config_loader: ConfigLoader,
+    pub readiness: Arc<ReadinessService>,
+    /// Set by [`initialize`]; `/health/ready` answers 503 until then
+    pub initialized: Arc<Initialized>,
+    #[cfg(feature = "lifi")]
+    pub quote_cache: Arc<QuoteCache>,
+    pub rate_limiter: Arc<RateLimiter>,
+    /// Cancelled on Ctrl-C / SIGTERM; stops the listeners and background jobs
+    pub shutdown: CancellationToken,
+    pub jobs: Arc<JobRunner>,
+    /// Receives panics, 5xx responses and failed job runs
+    pub reporter: Arc<dyn ErrorReporter>,
+    #[cfg(feature = "lifi")]
+    pub lifi_service: Arc<LifiService>,
+    /// Token prices, shared by every feature that values tokens
+    #[cfg(feature = "lifi")]
+    pub price_service: Arc<PriceService>,
+    /// USD exchange rates for sessions' display currencies
+    pub fx_rates: Arc<PriceService>,
+    #[cfg(feature = "settlement")]
+    pub settlement_service: Arc<SettlementService>,
+    /// Nonces of `SETTLEMENT_SIGNER_ADDRESS`, when one is set
+    #[cfg(feature = "settlement")]
+    pub nonce_manager: Option<Arc<NonceManager>>,
+    /// Set with `SETTLEMENT_BATCHING`; queues sessions for shared settlements
+    #[cfg(feature = "settlement")]
+    pub settlement_batcher: Option<Arc<SettlementBatcher>>,
+    /// Price of the settlement chain's gas token
+    #[cfg(feature = "settlement")]
+    pub native_prices: Arc<PriceService>,
+    /// Writes answer 503 while set; see `api::middleware::maintenance`
+    pub maintenance: Arc<AtomicBool>,
+}
+
+impl AppState {
+    /// Build the shared services for a validated configuration
+    fn new(config: Config) -> Self {
+        let session_store = Arc::new(SessionStore::new());
+        let shutdown = CancellationToken::new();
+        let live_config = LiveConfig::new(config.dynamic.clone());
+        let initialized = Arc::new(Initialized::default());
+        let reporter = reporting::from_config(&config);
+        #[cfg(any(feature = "ens", feature = "lifi"))]
+        let cooldown = Duration::from_secs(config.circuit_breaker_cooldown_secs);
+        #[cfg(feature = "lifi")]
+        let lifi_service = Arc::new(
+            LifiService::with_api(&config.lifi_api_url, None)
+                .with_api_keys(config.all_lifi_api_keys())
+                .with_circuit_breaker(config.circuit_breaker_threshold, cooldown)
+                .with_user_agent(&config.http_user_agent)
+                .with_rate_limit(
+                    config.lifi_rate_limit_per_minute,
+                    config.lifi_rate_limit_burst,
+                    Duration::from_millis(config.lifi_rate_limit_max_wait_ms),
+                ),
+        );
+        #[cfg(feature = "settlement")]
+        let settlement_service = Arc::new(
+            SettlementService::new(&config.arc_rpc_url).with_user_agent(&config.http_user_agent),
+        );
+        #[cfg(feature = "settlement")]
+        let nonce_manager = config.settlement_signer_address.as_ref().map(|signer| {
+            Arc::new(NonceManager::new(
+                settlement_service.clone(),
+                signer.clone(),
+            ))
+        });
+        #[cfg(feature = "settlement")]
+        let settlement_batcher = match (
+            config.settlement_batching,
+            &config.settlement_contract_address,
+            &nonce_manager,
+        ) {
+            (true, Some(contract), Some(nonces)) => Some(Arc::new(SettlementBatcher::new(
+                session_store.clone(),
+                settlement_service.clone(),
+                models::address::Address::try_from(contract.as_str())
+                    .expect("contract address was validated with the configuration"),
+                nonces.clone(),
+                config.settlement_batch_max_sessions,
+            ))),
+            _ => None,
+        };
+        Self {
+            session_store: session_store.clone(),
+            template_store: Arc::new(TemplateStore::new()),
+            webhooks: Arc::new(
+                WebhookService::new(session_store.clone()).with_user_agent(&config.http_user_agent),
+            ),
+            #[cfg(feature = "ens")]
+            ens_service: Arc::new(
+                EnsService::with_api_url(&config.ens_api_url)
+                    .with_gateways(&config.ipfs_gateway_url, &config.arweave_gateway_url)
+                    .with_rpc_url(&config.eth_rpc_url)
+                    .with_subgraph_url(config.ens_subgraph_url.as_deref())
+                    .with_circuit_breaker(config.circuit_breaker_threshold, cooldown)
+                    .with_user_agent(&config.http_user_agent)
+                    .with_subdomains(config.ens_allow_subdomains)
+                    .with_cache(
+                        ens_cache::from_backend(
+                            config.ens_cache_backend,
+                            config.ens_cache_redis_url.as_deref(),
+                        )
+                        .expect("ENS cache backend was validated with the configuration"),
+                    )
+                    .with_live_config(live_config.clone()),
+            ),
+            #[cfg(feature = "lifi")]
+            price_service: Arc::new(
+                PriceService::new(vec![lifi_service.clone() as Arc<dyn PriceSource>])
+                    .with_staleness(
+                        Duration::from_secs(config.price_cache_ttl_secs),
+                        Duration::from_secs(config.price_max_age_secs),
+                    ),
+            ),
+            fx_rates: Arc::new(
+                PriceService::new(vec![Arc::new(FiatRateSource::new(
+                    &config.fx_rate_url,
+                    &config.http_user_agent,
+                )) as Arc<dyn PriceSource>])
+                .with_staleness(
+                    Duration::from_secs(config.fx_rate_max_age_secs),
+                    Duration::from_secs(config.fx_rate_max_age_secs),
+                ),
+            ),
+            #[cfg(feature = "lifi")]
+            lifi_service,
+            #[cfg(feature = "settlement")]
+            settlement_service,
+            #[cfg(feature = "settlement")]
+            nonce_manager,
+            #[cfg(feature = "settlement")]
+            settlement_batcher,
+            #[cfg(feature = "settlement")]
+            native_prices: Arc::new(
+                PriceService::new(vec![Arc::new(NativePriceSource::new(
+                    &config.native_token_price_url,
+                    &config.http_user_agent,
+                )) as Arc<dyn PriceSource>])
+                .with_staleness(NATIVE_PRICE_TTL, NATIVE_PRICE_TTL),
+            ),
+            readiness: Arc::new(ReadinessService::new(
+                &config,
+                session_store,
+                initialized.clone(),
+            )),
+            initialized,
+            #[cfg(feature = "lifi")]
+            quote_cache: Arc::new(QuoteCache::new(config.quote_cache_capacity)),
+            rate_limiter: Arc::new(RateLimiter::new(live_config.clone())),
+            maintenance: Arc::new(AtomicBool::new(config.maintenance_mode)),
+            live_config,
+            config_loader: Arc::new(Config::reload_from_env),
+            jobs: Arc::new(JobRunner::new(shutdown.clone()).with_reporter(reporter.clone())),
+            reporter,
+            shutdown,
+            config: Arc::new(config),
+        }
+    }
+
+    /// Load a fresh configuration and apply its dynamic settings, returning
+    /// the variables that changed. Settings read only at startup keep their
+    /// old values until a restart.
+    fn reload_config(&self) -> Result<Vec<&'static str>, ConfigError> {
+        let config = (self.config_loader)()?;
+        Ok(self.live_config.replace(config.dynamic))
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize logging
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "settleone_backend=debug,tower_http=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    let cli = <cli::Cli as clap::Parser>::parse();
 
     // Load environment variables
     dotenvy::dotenv().ok();
 
+    // One-off commands report to stdout/stderr instead of the log pipeline
+    let result = match cli.command.unwrap_or(cli::Command::Serve) {
+        cli::Command::Serve => return serve().await,
+        cli::Command::CheckConfig => {
+            cli::check_config(&cli::load_config());
+            Ok(())
+        }
+        cli::Command::Resolve { name } => cli::resolve(cli::load_config(), &name).await,
+        cli::Command::Snapshot { action } => cli::snapshot(cli::load_config(), action).await,
+        cli::Command::Loadgen(args) => loadgen::run(args).await,
+    };
+    if let Err(e) = result {
+        eprintln!("error: {:#}", e);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Run the HTTP server until Ctrl-C / SIGTERM
+async fn serve() -> anyhow::Result<()> {
+    // Validate configuration before accepting any traffic; logging is set up
+    // first (pretty if the config is unusable) so the failure is reported
+    let config = Config::from_env();
+    let log_format = config
+        .as_ref()
+        .map(|c| c.log_format)
+        .unwrap_or(LogFormat::Pretty);
+    let otlp_endpoint = config
+        .as_ref()
+        .ok()
+        .and_then(|c| c.otel_exporter_otlp_endpoint.as_deref());
+    let tracer_provider = match otlp_endpoint.map(telemetry::otlp_provider) {
+        Some(Ok(provider)) => Some(provider),
+        Some(Err(e)) => {
+            eprintln!("Failed to set up OTLP trace export: {}", e);
+            None
+        }
+        None => None,
+    };
+    logging::init(log_format, tracer_provider.as_ref());
+
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("Fatal configuration error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    for warning in config.warnings() {
+        tracing::warn!("{}", warning);
+    }
+
     // Initialize shared state
-    let state = AppState {
-        session_store: Arc::new(SessionStore::new()),
-        ens_service: Arc::new(EnsService::new()),
+    let state = AppState::new(config);
+
+    // An unusable certificate is fatal, like an invalid config
+    let tls = match (&state.config.tls_cert_path, &state.config.tls_key_path) {
+        (Some(cert), Some(key)) => match tls::load(cert, key).await {
+            Ok(tls) => {
+                state.jobs.spawn(tls::CertReloadJob::new(
+                    tls.clone(),
+                    cert.clone(),
+                    key.clone(),
+                ));
+                Some(tls)
+            }
+            Err(e) => {
+                tracing::error!("Fatal TLS error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
     };
 
     // Build application
     let app = create_app(state.clone());
 
-    // Get port from environment or default
-    let port = std::env::var("PORT").unwrap_or_else(|_| "3001".to_string());
-    let addr = format!("0.0.0.0:{}", port);
+    tracing::info!(
+        "Starting SettleOne backend on {} ({})",
+        state.config.listen,
+        if tls.is_some() { "https" } else { "http" }
+    );
+
+    // Both listeners and the background jobs stop on the same shutdown signal
+    let shutdown = state.shutdown.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        tracing::info!("Shutting down");
+        shutdown.cancel();
+    });
+    let stopped = || state.shutdown.clone().cancelled_owned();
+
+    #[cfg(unix)]
+    tokio::spawn(reload_on_sighup(state.clone()));
+
+    // Bind first so liveness answers during initialization; readiness
+    // stays 503 until the snapshot is restored
+    let init = async {
+        initialize(state.clone())
+            .await
+            .map_err(std::io::Error::other)
+    };
+
+    // Start server, plus the dedicated admin and gRPC listeners when configured
+    let listener = Listener::bind(&state.config.listen, state.config.listen_socket_mode)?;
+    let public = serve_listener(listener, app, tls.clone(), stopped());
+    let admin = async {
+        let Some(admin_port) = state.config.admin_port else {
+            return Ok(());
+        };
+        let admin_addr = format!("0.0.0.0:{}", admin_port);
+        tracing::info!("Serving /admin on {}", admin_addr);
+        let admin_listener = Listener::Tcp(std::net::TcpListener::bind(&admin_addr)?);
+        serve_listener(
+            admin_listener,
+            create_admin_app(state.clone()),
+            tls,
+            stopped(),
+        )
+        .await
+    };
+    let grpc = async {
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_port) = state.config.grpc_port {
+            let grpc_addr = format!("0.0.0.0:{}", grpc_port);
+            tracing::info!("Serving gRPC on {}", grpc_addr);
+            let grpc_listener = std::net::TcpListener::bind(&grpc_addr)?;
+            grpc::serve(grpc_listener, state.clone(), stopped()).await?;
+        }
+        Ok::<_, std::io::Error>(())
+    };
+    tokio::try_join!(init, public, admin, grpc)?;
+
+    // A store stopped mid-restore is incomplete; keep the snapshot on disk
+    if let (Some(path), true) = (
+        &state.config.session_snapshot_path,
+        state.initialized.is_set(),
+    ) {
+        let sessions = state.session_store.snapshot().await;
+        let count = sessions.len();
+        Snapshot::new(sessions)
+            .with_events(state.session_store.events_snapshot().await)
+            .with_templates(state.template_store.snapshot().await)
+            .write(path)?;
+        tracing::info!("Saved {} sessions to {}", count, path.display());
+    }
+
+    if let Some(provider) = tracer_provider {
+        provider.shutdown()?;
+    }
+
+    Ok(())
+}
+
+/// Run the startup steps the server must finish before reporting ready,
+/// logging how long each took, then start the background jobs
+async fn initialize(state: AppState) -> anyhow::Result<()> {
+    let started = Instant::now();
+
+    if let Some(path) = &state.config.session_snapshot_path {
+        let step = Instant::now();
+        let restored = cli::restore_sessions(&state, path).await?;
+        tracing::info!(
+            "Restored {} sessions from {} in {:?}",
+            restored,
+            path.display(),
+            step.elapsed()
+        );
+    }
+
+    // A restart loses the nonce counter; pick it up from the chain. If the
+    // node is unreachable, the first transaction reads it instead.
+    #[cfg(feature = "settlement")]
+    if let Some(nonces) = &state.nonce_manager {
+        let step = Instant::now();
+        match nonces.reconcile().await {
+            Ok(reconciled) => tracing::info!(
+                "Settlement signer {} continues at nonce {} ({:?})",
+                nonces.signer(),
+                reconciled.next,
+                step.elapsed()
+            ),
+            Err(e) => tracing::warn!(
+                "Could not read the nonce of settlement signer {}: {}",
+                nonces.signer(),
+                e
+            ),
+        }
+    }
+
+    #[cfg(feature = "settlement")]
+    if let Some(batcher) = &state.settlement_batcher {
+        state
+            .jobs
+            .spawn(services::settlement_batch::SettlementBatchJob {
+                batcher: batcher.clone(),
+                live_config: state.live_config.clone(),
+                interval: Duration::from_secs(state.config.settlement_batch_interval_secs),
+            });
+    }
+
+    if let Some(ttl) = state.config.session_ttl_secs {
+        state.jobs.spawn(services::session::SessionExpiryJob {
+            store: state.session_store.clone(),
+            ttl: Duration::from_secs(ttl),
+        });
+    }
 
-    tracing::info!("Starting SettleOne backend on {}", addr);
+    if let Some(secs) = state.config.session_archive_after_secs {
+        state.jobs.spawn(services::session::SessionArchiveJob {
+            store: state.session_store.clone(),
+            older_than: Duration::from_secs(secs),
+            path: state.config.session_archive_path.clone(),
+        });
+    }
 
-    // Start server
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    state.jobs.spawn(services::webhook::WebhookDeliveryJob {
+        webhooks: state.webhooks.clone(),
+    });
 
+    state.initialized.set();
+    tracing::info!("Startup initialization finished in {:?}", started.elapsed());
     Ok(())
 }
 
+/// Serve `app` on `listener` until `stopped` resolves, over TLS (HTTP/2 or
+/// HTTP/1.1, negotiated via ALPN) when `tls` is set
+async fn serve_listener(
+    listener: Listener,
+    app: Router,
+    tls: Option<RustlsConfig>,
+    stopped: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    let listener = match listener {
+        Listener::Tcp(listener) => listener,
+        #[cfg(unix)]
+        Listener::Unix(listener, path) => {
+            return listen::serve_unix(listener, path, app, stopped).await;
+        }
+    };
+    listener.set_nonblocking(true)?;
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    match tls {
+        Some(tls) => {
+            let handle = axum_server::Handle::new();
+            let graceful = handle.clone();
+            tokio::spawn(async move {
+                stopped.await;
+                graceful.graceful_shutdown(None);
+            });
+            axum_server::from_tcp_rustls(listener, tls)
+                .handle(handle)
+                .serve(service)
+                .await
+        }
+        None => {
+            axum::serve(tokio::net::TcpListener::from_std(listener)?, service)
+                .with_graceful_shutdown(stopped)
+                .await
+        }
+    }
+}
+
+/// Resolve on Ctrl-C or SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl-C handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Reload the dynamic configuration on every SIGHUP until shutdown
+#[cfg(unix)]
+async fn reload_on_sighup(state: AppState) {
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .expect("Failed to install SIGHUP handler");
+    loop {
+        tokio::select! {
+            _ = state.shutdown.cancelled() => break,
+            _ = hangup.recv() => {}
+        }
+        tracing::info!("SIGHUP received, reloading configuration");
+        if let Err(e) = state.reload_config() {
+            tracing::error!(
+                "Configuration reload failed, keeping current settings: {}",
+                e
+            );
+        }
+    }
+}
+
 /// Create the application router with all API routes
 fn create_app(state: AppState) -> Router {
-    // CORS configuration - allow all origins for development
+    // CORS: any origin unless CORS_ALLOWED_ORIGINS (reloadable) lists some
+    // Clients read the request id and rate-limit waits from responses
+    let live_config = state.live_config.clone();
     let cors = CorsLayer::new()
-        .allow_origin(Any)
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            origin
+                .to_str()
+                .is_ok_and(|origin| live_config.get().allows_origin(origin))
+        }))
         .allow_methods(Any)
-        .allow_headers(Any);
+        .allow_headers(Any)
+        .expose_headers([
+            api::middleware::REQUEST_ID_HEADER.clone(),
+            axum::http::header::RETRY_AFTER,
+        ])
+        .max_age(Duration::from_secs(state.config.cors_max_age_secs));
+
+    // Metrics are recorded globally; make sure the recorder exists first
+    api::metrics::install_recorder();
+
+    // Versioned API; the unversioned tree keeps legacy semantics and
+    // advertises its deprecation. Both share the per-IP rate limits.
+    // Mutations require a client API key once keys are configured, and are
+    // refused outright in maintenance mode.
+    let rate_limit = middleware::from_fn_with_state(state.clone(), api::middleware::rate_limit);
+    let client_auth =
+        middleware::from_fn_with_state(state.clone(), api::middleware::require_client_key);
+    let maintenance = middleware::from_fn_with_state(state.clone(), api::middleware::maintenance);
+    let v1 = api_routes()
+        .layer(Extension(ApiVersion::V1))
+        .layer(client_auth.clone())
+        .layer(maintenance.clone())
+        .layer(rate_limit.clone());
+    let legacy = api_routes()
+        .layer(Extension(ApiVersion::Legacy))
+        .layer(client_auth)
+        .layer(maintenance)
+        .layer(rate_limit.clone())
+        .layer(middleware::from_fn(api::middleware::deprecation_headers));
+
+    // GraphQL checks API keys per mutation, so it only shares the rate limits
+    let graphql = table_router(graphql_route_table()).layer(rate_limit);
 
     // Build router with all routes
-    Router::new()
+    let mut router = table_router(root_route_table())
+        // API routes
+        .nest("/api/v1", v1)
+        .nest("/api", legacy);
+
+    // Operational routes, unless they get their own listener
+    if state.config.admin_port.is_none() {
+        router = router.nest("/admin", admin_routes(&state));
+    }
+
+    // REST responses honor X-Timestamp-Format; GraphQL has its own schema
+    let mut router = router
+        .layer(middleware::from_fn(api::middleware::timestamp_format))
+        .merge(graphql);
+
+    if state.config.enable_graphql_playground {
+        router = router.route("/graphql/playground", get(graphql::playground));
+    }
+
+    // Swagger UI (reads the spec served at /api/openapi.json)
+    if state.config.enable_docs {
+        router = router.merge(
+            SwaggerUi::new("/docs").config(utoipa_swagger_ui::Config::from("/api/openapi.json")),
+        );
+    }
+
+    // Hidden routes exercising the panic handler and slow-request warnings
+    #[cfg(test)]
+    let router = router
+        .route(
+            "/__test/panic",
+            get(|| async { panic!("test panic with secret detail") as &str }),
+        )
+        .route(
+            "/__test/slow",
+            get(|| async {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                "done"
+            }),
+        );
+
+    let mut router = router.with_state(state.clone());
+    if state.config.log_bodies {
+        router = router.layer(middleware::from_fn_with_state(
+            state.clone(),
+            api::middleware::log_bodies,
+        ));
+    }
+
+    with_observability(router, &state)
+        // gzip/br, negotiated via Accept-Encoding
+        .layer(CompressionLayer::new())
+        .layer(cors)
+}
+
+/// Create the router for the dedicated admin listener (`ADMIN_PORT`)
+fn create_admin_app(state: AppState) -> Router {
+    api::metrics::install_recorder();
+    let router = Router::new()
+        .nest("/admin", admin_routes(&state))
+        .layer(middleware::from_fn(api::middleware::timestamp_format));
+    with_observability(router.with_state(state.clone()), &state).layer(CompressionLayer::new())
+}
+
+/// Metrics, request logs, tracing, panic handling, error reports and request ids
+fn with_observability(router: Router, state: &AppState) -> Router {
+    router
+        .route_layer(middleware::from_fn(api::middleware::track_metrics))
+        .route_layer(middleware::from_fn_with_state(
+            state.live_config.clone(),
+            api::middleware::log_request,
+        ))
+        .layer(TraceLayer::new_for_http())
+        .layer(middleware::from_fn(api::middleware::method_not_allowed))
+        // Inside request_id so the 500 and 405 envelopes carry the request id
+        .layer(CatchPanicLayer::custom(api::middleware::panic_response))
+        .layer(middleware::from_fn_with_state(
+            state.reporter.clone(),
+            api::middleware::report_errors,
+        ))
+        .layer(middleware::from_fn(api::middleware::request_id))
+}
+
+/// Unversioned operational routes
+fn root_route_table() -> Vec<(&'static str, MethodRouter<AppState>)> {
+    vec![
         // Health check
-        .route("/health", get(api::health_check))
+        ("/health", get(api::health_check)),
+        ("/health/ready", get(api::readiness_check)),
+        // Prometheus metrics
+        ("/metrics", get(api::metrics::metrics)),
+        // OpenAPI specification
+        ("/api/openapi.json", get(api::openapi::openapi_json)),
+    ]
+}
+
+/// GraphQL queries and mutations, and subscriptions over WebSocket
+fn graphql_route_table() -> Vec<(&'static str, MethodRouter<AppState>)> {
+    vec![
+        ("/graphql", get(graphql::execute).post(graphql::execute)),
+        ("/graphql/ws", get(graphql::subscribe)),
+    ]
+}
+
+/// API routes shared by every version, relative to the version prefix.
+///
+/// Every route here must be documented in the spec from
+/// `api::openapi::spec`; a test checks both tables against it.
+fn api_route_table() -> Vec<(&'static str, MethodRouter<AppState>)> {
+    vec![
         // ENS routes
-        .route("/api/ens/resolve", get(api::ens::resolve_ens))
-        .route("/api/ens/lookup", get(api::ens::lookup_address))
+        #[cfg(feature = "ens")]
+        ("/ens/resolve", get(api::ens::resolve_ens)),
+        #[cfg(feature = "ens")]
+        ("/ens/resolve/batch", post(api::ens::resolve_ens_batch)),
+        #[cfg(feature = "ens")]
+        ("/ens/lookup", get(api::ens::lookup_address)),
+        #[cfg(feature = "ens")]
+        ("/ens/namehash", get(api::ens::namehash_name)),
+        #[cfg(feature = "ens")]
+        ("/ens/contenthash", get(api::ens::content_hash)),
         // Session routes
-        .route("/api/session", post(api::session::create_session))
-        .route("/api/session/:id", get(api::session::get_session))
-        .route("/api/session/:id/payment", post(api::session::add_payment))
-        .route(
-            "/api/session/:id/payment/:payment_id",
+        ("/session", post(api::session::create_session)),
+        ("/session/:id", get(api::session::get_session)),
+        ("/session/:id/payment", post(api::session::add_payment)),
+        ("/session/:id/payments", get(api::session::list_payments)),
+        ("/session/:id/events", get(api::session::list_events)),
+        (
+            "/session/:id/payments/reorder",
+            post(api::session::reorder_payments),
+        ),
+        ("/session/:id/receipt", get(api::session::get_receipt)),
+        (
+            "/session/:id/payment/:payment_id",
             delete(api::session::remove_payment),
-        )
-        .route(
-            "/api/session/:id/finalize",
+        ),
+        (
+            "/session/:id/payment/:payment_id/cancel",
+            post(api::session::cancel_payment),
+        ),
+        ("/session/:id/cancel", post(api::session::cancel_session)),
+        (
+            "/session/:id/finalize",
             post(api::session::finalize_session),
-        )
+        ),
+        ("/sessions/finalize", post(api::session::finalize_sessions)),
+        (
+            "/sessions/by-tx/:tx_hash",
+            get(api::session::get_session_by_tx),
+        ),
+        (
+            "/users/:address/recipients/:recipient",
+            get(api::users::recipient_history),
+        ),
+        // Template routes
+        (
+            "/templates",
+            post(api::template::create_template).get(api::template::list_templates),
+        ),
+        (
+            "/templates/:id",
+            get(api::template::get_template).delete(api::template::delete_template),
+        ),
+        (
+            "/session/from-template/:template_id",
+            post(api::template::create_from_template),
+        ),
+        #[cfg(feature = "settlement")]
+        (
+            "/session/:id/settlement-status",
+            get(api::settlement::settlement_status),
+        ),
+        #[cfg(feature = "settlement")]
+        (
+            "/session/:id/settlement-preview",
+            get(api::settlement::settlement_preview),
+        ),
         // Quote routes
-        .route("/api/quote", get(api::quote::get_quote))
-        // Shared state
-        .with_state(state)
-        // Middleware
-        .layer(TraceLayer::new_for_http())
-        .layer(cors)
+        #[cfg(feature = "lifi")]
+        ("/quote", get(api::quote::get_quote)),
+        #[cfg(feature = "lifi")]
+        ("/quote/compare", post(api::quote::compare_quotes)),
+        #[cfg(feature = "lifi")]
+        ("/price", get(api::prices::get_price)),
+    ]
+}
+
+/// Routes of integrations compiled out of this build; they answer 501
+fn disabled_route_table() -> Vec<(&'static str, MethodRouter<AppState>)> {
+    vec![
+        #[cfg(not(feature = "ens"))]
+        ("/ens/resolve", api::not_compiled_in("ens")),
+        #[cfg(not(feature = "ens"))]
+        ("/ens/resolve/batch", api::not_compiled_in("ens")),
+        #[cfg(not(feature = "ens"))]
+        ("/ens/lookup", api::not_compiled_in("ens")),
+        #[cfg(not(feature = "ens"))]
+        ("/ens/namehash", api::not_compiled_in("ens")),
+        #[cfg(not(feature = "ens"))]
+        ("/ens/contenthash", api::not_compiled_in("ens")),
+        #[cfg(not(feature = "settlement"))]
+        (
+            "/session/:id/settlement-status",
+            api::not_compiled_in("settlement"),
+        ),
+        #[cfg(not(feature = "settlement"))]
+        (
+            "/session/:id/settlement-preview",
+            api::not_compiled_in("settlement"),
+        ),
+        #[cfg(not(feature = "lifi"))]
+        ("/quote", api::not_compiled_in("lifi")),
+        #[cfg(not(feature = "lifi"))]
+        ("/quote/compare", api::not_compiled_in("lifi")),
+        #[cfg(not(feature = "lifi"))]
+        ("/price", api::not_compiled_in("lifi")),
+    ]
+}
+
+/// Operational routes, relative to `/admin`
+fn admin_route_table() -> Vec<(&'static str, MethodRouter<AppState>)> {
+    vec![
+        ("/config/reload", post(api::admin::reload_config)),
+        ("/health", get(api::admin::health)),
+        ("/health/history", get(api::admin::health_history)),
+        ("/internals", get(api::admin::internals)),
+        ("/jobs", get(api::admin::jobs)),
+        ("/maintenance", post(api::admin::set_maintenance)),
+        ("/routes", get(api::admin::routes)),
+        ("/snapshot", get(api::admin::snapshot)),
+        ("/stats", get(api::admin::stats)),
+        (
+            "/webhooks",
+            post(api::admin::register_webhook).get(api::admin::list_webhooks),
+        ),
+        ("/webhooks/:id", delete(api::admin::remove_webhook)),
+        ("/webhooks/redeliver", post(api::admin::redeliver_events)),
+    ]
+}
+
+/// Router for [`admin_route_table`], behind the admin API key
+fn admin_routes(state: &AppState) -> Router<AppState> {
+    table_router(admin_route_table()).layer(middleware::from_fn_with_state(
+        state.clone(),
+        api::middleware::require_admin_key,
+    ))
+}
+
+/// Router for [`api_route_table`].
+///
+/// Handlers that behave differently per version read the `ApiVersion`
+/// extension layered onto each tree.
+fn api_routes() -> Router<AppState> {
+    table_router(api_route_table()).merge(table_router(disabled_route_table()))
+}
+
+/// Build a router from a route table
+fn table_router(table: Vec<(&'static str, MethodRouter<AppState>)>) -> Router<AppState> {
+    table
+        .into_iter()
+        .fold(Router::new(), |router, (path, handler)| {
+            router.route(path, handler)
+        })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::DynamicConfig;
+    use crate::models::fixtures::{self, InsertInto, SessionBuilder};
+    use crate::services::auth::{ApiKey, ApiRole};
+    #[cfg(feature = "ens")]
+    use crate::services::ens::EnsError;
+    use crate::testing::assert_error;
+    use crate::testing::TestApp;
     use axum::http::StatusCode;
     use axum_test::TestServer;
     use serde_json::json;
+    use settleone_client::{ClientError, SettleOneClient};
+    #[cfg(feature = "lifi")]
+    use settleone_types::api::QuoteRequest;
+    use settleone_types::api::{
+        AddPaymentRequest, CreateSessionRequest, ErrorCode, FinalizeRequest,
+    };
+    use settleone_types::session::SessionStatus;
 
     fn create_test_state() -> AppState {
-        AppState {
-            session_store: Arc::new(SessionStore::new()),
-            ens_service: Arc::new(EnsService::new()),
-        }
+        create_test_state_with_config(Config::default())
+    }
+
+    fn create_test_state_with_config(config: Config) -> AppState {
+        let state = AppState::new(config);
+        state.initialized.set();
+        state
+    }
+
+    #[cfg(all(feature = "ens", feature = "lifi", feature = "settlement"))]
+    /// Start a local mock upstream serving `GET /` and a JSON-RPC `POST /rpc`
+    async fn spawn_mock_upstream() -> String {
+        let app = Router::new().route("/", get(|| async { "ok" })).route(
+            "/rpc",
+            post(|| async {
+                axum::Json(json!({ "jsonrpc": "2.0", "id": 1, "result": "0x4cef52" }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[cfg(feature = "lifi")]
+    /// URL of a local port with nothing listening on it
+    async fn unreachable_url() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        format!("http://{}", addr)
     }
 
     fn create_test_server() -> TestServer {
@@ -123,6 +943,16 @@ mod tests {
         TestServer::new(app).unwrap()
     }
 
+    #[cfg(feature = "ens")]
+    fn create_strict_test_server() -> TestServer {
+        let config = Config {
+            strict_errors: true,
+            ..Config::default()
+        };
+        let app = create_app(create_test_state_with_config(config));
+        TestServer::new(app).unwrap()
+    }
+
     // ── Health Check ──────────────────────────────────
 
     #[tokio::test]
@@ -136,6 +966,209 @@ mod tests {
         assert!(!body["version"].as_str().unwrap().is_empty());
     }
 
+    #[cfg(all(feature = "ens", feature = "lifi", feature = "settlement"))]
+    #[tokio::test]
+    async fn test_readiness_all_dependencies_up() {
+        let upstream = spawn_mock_upstream().await;
+        let config = Config {
+            ens_api_url: upstream.clone(),
+            lifi_api_url: upstream.clone(),
+            arc_rpc_url: format!("{}/rpc", upstream),
+            ..Config::default()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+
+        let response = server.get("/health/ready").await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["status"], "ready");
+        for name in ["ens", "lifi", "arc_rpc", "session_store"] {
+            assert_eq!(body["checks"][name]["status"], "up");
+            assert!(body["checks"][name]["latency_ms"].is_u64());
+        }
+    }
+
+    #[cfg(all(feature = "ens", feature = "lifi", feature = "settlement"))]
+    #[tokio::test]
+    async fn test_readiness_reports_failed_dependency() {
+        let upstream = spawn_mock_upstream().await;
+        let config = Config {
+            ens_api_url: upstream.clone(),
+            lifi_api_url: unreachable_url().await,
+            arc_rpc_url: format!("{}/rpc", upstream),
+            ..Config::default()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+
+        let response = server.get("/health/ready").await;
+        assert_eq!(response.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["status"], "not_ready");
+        assert_eq!(body["failures"], json!(["lifi"]));
+        assert_eq!(body["checks"]["lifi"]["status"], "down");
+        assert!(body["checks"]["lifi"]["error"].is_string());
+        assert_eq!(body["checks"]["ens"]["status"], "up");
+
+        // Liveness stays cheap and green
+        let live = server.get("/health").await;
+        assert_eq!(live.status_code(), StatusCode::OK);
+    }
+
+    #[cfg(all(feature = "ens", feature = "lifi", feature = "settlement"))]
+    #[tokio::test]
+    async fn test_readiness_history_tracks_flapping_dependency() {
+        use tracing_subscriber::layer::SubscriberExt;
+        use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+        let capture = crate::testing::Capture::default();
+        let subscriber =
+            tracing_subscriber::registry().with(crate::logging::json_layer(capture.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        // The ENS API answers down, up, up, then down for good
+        let ens = MockServer::start().await;
+        for (status, times) in [(503, 1), (200, 2)] {
+            Mock::given(method("GET"))
+                .respond_with(ResponseTemplate::new(status))
+                .up_to_n_times(times)
+                .mount(&ens)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&ens)
+            .await;
+
+        let upstream = spawn_mock_upstream().await;
+        let config = Config {
+            ens_api_url: ens.uri(),
+            lifi_api_url: upstream.clone(),
+            arc_rpc_url: format!("{}/rpc", upstream),
+            health_history_size: 3,
+            ..authenticated_config()
+        };
+        let mut state = create_test_state_with_config(config.clone());
+        state.readiness = Arc::new(
+            ReadinessService::new(
+                &config,
+                state.session_store.clone(),
+                state.initialized.clone(),
+            )
+            .with_report_ttl(Duration::ZERO),
+        );
+        let server = TestServer::new(create_app(state)).unwrap();
+
+        let history = || async {
+            server
+                .get("/admin/health/history")
+                .add_header("x-api-key", "admin-key")
+                .await
+                .json::<serde_json::Value>()
+        };
+        assert_eq!(history().await["dependencies"], json!({}));
+
+        let mut statuses = Vec::new();
+        for _ in 0..4 {
+            statuses.push(server.get("/health/ready").await.status_code());
+        }
+        assert_eq!(
+            statuses,
+            [
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::SERVICE_UNAVAILABLE
+            ]
+        );
+
+        let body = history().await;
+        assert_eq!(body["window"], 3);
+        // The first check fell out of the window: up, up, down
+        let ens = &body["dependencies"]["ens"];
+        assert_eq!(ens["status"], "down");
+        let checks: Vec<bool> = ens["checks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|check| check["up"].as_bool().unwrap())
+            .collect();
+        assert_eq!(checks, [true, true, false]);
+        let uptime = ens["uptime_percent"].as_f64().unwrap();
+        assert!((uptime - 200.0 / 3.0).abs() < 1e-9, "{}", uptime);
+        assert_eq!(ens["last_transition_at"], ens["checks"][2]["at"]);
+
+        let store = &body["dependencies"]["session_store"];
+        assert_eq!(store["status"], "up");
+        assert_eq!(store["uptime_percent"], 100.0);
+        assert_eq!(store["last_transition_at"], serde_json::Value::Null);
+
+        // One log line per transition, none for steady dependencies
+        let output = capture.output();
+        let transitions: Vec<String> = output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+            .filter(|line| line["dependency"].is_string())
+            .map(|line| line["message"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(transitions.len(), 2, "{}", output);
+        assert_eq!(transitions[0], "Dependency ens is back up");
+        assert!(transitions[1].starts_with("Dependency ens went down: "));
+    }
+
+    #[cfg(all(feature = "ens", feature = "lifi", feature = "settlement"))]
+    #[tokio::test]
+    async fn test_readiness_waits_for_startup_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+        let restored = models::session::Session::new(
+            "restored".into(),
+            "0x6666666666666666666666666666666666666666"
+                .parse()
+                .unwrap(),
+        );
+        Snapshot::new(vec![restored]).write(&path).unwrap();
+        let upstream = spawn_mock_upstream().await;
+        let config = Config {
+            ens_api_url: upstream.clone(),
+            lifi_api_url: upstream.clone(),
+            arc_rpc_url: format!("{}/rpc", upstream),
+            session_snapshot_path: Some(path),
+            ..Config::default()
+        };
+        // Served before initialization, as in `serve`
+        let state = AppState::new(config);
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+
+        // Hold the restore back until the server has been probed
+        let (release, slow_restore) = tokio::sync::oneshot::channel::<()>();
+        let init = tokio::spawn({
+            let state = state.clone();
+            async move {
+                slow_restore.await.unwrap();
+                initialize(state).await.unwrap();
+            }
+        });
+
+        let response = server.get("/health/ready").await;
+        assert_eq!(response.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["failures"], json!(["startup"]));
+        assert_eq!(body["checks"]["startup"]["status"], "down");
+        assert_eq!(server.get("/health").await.status_code(), StatusCode::OK);
+        assert!(state.session_store.get("restored").await.is_none());
+
+        release.send(()).unwrap();
+        init.await.unwrap();
+
+        let response = server.get("/health/ready").await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(server.get("/health").await.status_code(), StatusCode::OK);
+        server
+            .get("/api/v1/session/restored")
+            .await
+            .assert_status_ok();
+    }
+
     // ── Session CRUD ──────────────────────────────────
 
     #[tokio::test]
@@ -154,6 +1187,265 @@ mod tests {
         assert!(!body["session_id"].as_str().unwrap().is_empty());
     }
 
+    #[tokio::test]
+    async fn test_create_session_enforces_active_cap() {
+        let config = Config {
+            max_active_sessions_per_user: Some(2),
+            ..Config::default()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+        let user = json!({ "user_address": "0x0000000000000000000000000000000000000118" });
+
+        let first = server.post("/api/session").json(&user).await;
+        assert_eq!(first.status_code(), StatusCode::OK);
+        let second = server.post("/api/session").json(&user).await;
+        assert_eq!(second.status_code(), StatusCode::OK);
+
+        // At the cap: next creation is rejected
+        let rejected = server.post("/api/session").json(&user).await;
+        assert_eq!(rejected.status_code(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(rejected.json::<serde_json::Value>()["code"], "rate_limited");
+
+        // Other users are unaffected
+        let other = server
+            .post("/api/session")
+            .json(&json!({ "user_address": "0x0000000000000000000000000000000000000110" }))
+            .await;
+        assert_eq!(other.status_code(), StatusCode::OK);
+
+        // Finalizing one session frees a slot
+        let session_id = first.json::<serde_json::Value>()["session_id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        server
+            .post(&format!("/api/session/{}/finalize", session_id))
+            .json(&json!({ "tx_hash": "0xabc" }))
+            .await;
+
+        let after = server.post("/api/session").json(&user).await;
+        assert_eq!(after.status_code(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_create_session_with_token_decimals() {
+        let server = create_test_server();
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x5555555555555555555555555555555555555555", "token_decimals": 18 }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap();
+
+        let body: serde_json::Value = server
+            .post(&format!("/api/v1/session/{}/payment", session_id))
+            .json(&json!({ "recipient": "0x1111111111111111111111111111111111111111", "amount": "1250000000000000000" }))
+            .await
+            .json();
+        assert_eq!(body["session"]["token_decimals"], 18);
+        assert_eq!(body["session"]["total_amount"], "1250000000000000000");
+        assert_eq!(body["total_amount_display"], "1.25");
+
+        // USDC by default
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x5555555555555555555555555555555555555555" }))
+            .await
+            .json();
+        let body: serde_json::Value = server
+            .get(&format!(
+                "/api/v1/session/{}",
+                created["session_id"].as_str().unwrap()
+            ))
+            .await
+            .json();
+        assert_eq!(body["session"]["token_decimals"], 6);
+        assert_eq!(body["total_amount_display"], "0");
+
+        let response = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x5555555555555555555555555555555555555555", "token_decimals": 19 }))
+            .await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["details"]["fields"][0]["field"], "token_decimals");
+    }
+
+    #[tokio::test]
+    async fn test_treasury_mode_settles_total_in_one_transfer() {
+        let treasury = "0x000000000000000000000000000000000000bEEF";
+        let config = Config {
+            treasury_address: Some(treasury.parse().unwrap()),
+            ..Config::default()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x5555555555555555555555555555555555555555", "settlement_mode": "treasury" }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap();
+        for (recipient, amount) in [
+            ("0xA11Ce00000000000000000000000000000000000", "100000"),
+            ("0xB0b0000000000000000000000000000000000000", "250000"),
+            ("0xA11Ce00000000000000000000000000000000000", "50000"),
+        ] {
+            server
+                .post(&format!("/api/v1/session/{}/payment", session_id))
+                .json(&json!({ "recipient": recipient, "amount": amount }))
+                .await
+                .assert_status_ok();
+        }
+
+        let receipt: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}/receipt", session_id))
+            .await
+            .json();
+        assert_eq!(receipt["settlement_mode"], "treasury");
+        assert_eq!(receipt["total_amount"], "400000");
+        assert_eq!(
+            receipt["transfers"],
+            json!([{ "to": treasury, "amount": "400000" }])
+        );
+        assert_eq!(
+            receipt["recipients"],
+            json!([
+                { "recipient": "0xA11Ce00000000000000000000000000000000000", "recipient_ens": null, "amount": "150000" },
+                { "recipient": "0xB0b0000000000000000000000000000000000000", "recipient_ens": null, "amount": "250000" },
+            ])
+        );
+
+        // Direct sessions pay each recipient
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x5555555555555555555555555555555555555555" }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap();
+        for (recipient, amount) in [
+            ("0xA11Ce00000000000000000000000000000000000", "100000"),
+            ("0xB0b0000000000000000000000000000000000000", "250000"),
+        ] {
+            server
+                .post(&format!("/api/v1/session/{}/payment", session_id))
+                .json(&json!({ "recipient": recipient, "amount": amount }))
+                .await
+                .assert_status_ok();
+        }
+        let receipt: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}/receipt", session_id))
+            .await
+            .json();
+        assert_eq!(receipt["settlement_mode"], "direct");
+        assert_eq!(receipt["transfers"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            receipt["transfers"][1],
+            json!({ "to": "0xB0b0000000000000000000000000000000000000", "amount": "250000" })
+        );
+
+        // Treasury mode needs a configured treasury
+        let response = create_test_server()
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x5555555555555555555555555555555555555555", "settlement_mode": "treasury" }))
+            .await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["details"]["fields"][0]["field"], "settlement_mode");
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_create_session_pins_recipient() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        const ORIGINAL: &str = "0x1111111111111111111111111111111111111111";
+        const CHANGED: &str = "0x2222222222222222222222222222222222222222";
+
+        // Mock ENS API whose record for every name can be switched
+        let changed = Arc::new(AtomicBool::new(false));
+        let flag = changed.clone();
+        let ens = Router::new().route(
+            "/:name",
+            get(move || {
+                let flag = flag.clone();
+                async move {
+                    let address = if flag.load(Ordering::SeqCst) {
+                        CHANGED
+                    } else {
+                        ORIGINAL
+                    };
+                    axum::Json(json!({ "address": address }))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ens_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, ens).await.unwrap() });
+
+        let config = Config {
+            ens_api_url: ens_url,
+            ..Config::default()
+        };
+        let state = create_test_state_with_config(config);
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+
+        let created = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x5555555555555555555555555555555555555555", "recipient_name": "Alice.eth" }))
+            .await;
+        assert_eq!(created.status_code(), StatusCode::CREATED);
+        let body: serde_json::Value = created.json();
+        assert_eq!(body["pinned_recipient"]["name"], "alice.eth");
+        assert_eq!(body["pinned_recipient"]["address"], ORIGINAL);
+        let session_id = body["session_id"].as_str().unwrap().to_string();
+
+        // The ENS record changes; the service now resolves to a new address
+        changed.store(true, Ordering::SeqCst);
+        state.ens_service.clear_cache().await;
+        let now = state
+            .ens_service
+            .resolve(&"alice.eth".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(now.address.as_str(), CHANGED);
+
+        // The session keeps the pinned address, including for new payments
+        let payment = server
+            .post(&format!("/api/v1/session/{}/payment", session_id))
+            .json(&json!({
+                "recipient": CHANGED,
+                "recipient_ens": "alice.eth",
+                "amount": "100000"
+            }))
+            .await;
+        assert_eq!(payment.status_code(), StatusCode::OK);
+
+        let session: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}", session_id))
+            .await
+            .json();
+        assert_eq!(session["session"]["pinned_recipient"]["address"], ORIGINAL);
+        assert!(session["session"]["pinned_recipient"]["resolved_at"].is_string());
+        assert_eq!(session["session"]["payments"][0]["recipient"], ORIGINAL);
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_create_session_invalid_recipient_name() {
+        let server = create_test_server();
+        let response = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x5555555555555555555555555555555555555555", "recipient_name": "not-ens" }))
+            .await;
+        assert_error(
+            &response,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unprocessable_entity",
+        );
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["details"]["fields"][0]["field"], "recipient_name");
+    }
+
     #[tokio::test]
     async fn test_get_session() {
         let server = create_test_server();
@@ -197,7 +1489,7 @@ mod tests {
         let create_resp = server
             .post("/api/session")
             .json(&json!({
-                "user_address": "0xSender"
+                "user_address": "0x4444444444444444444444444444444444444444"
             }))
             .await;
 
@@ -210,7 +1502,7 @@ mod tests {
         let pay_resp = server
             .post(&format!("/api/session/{}/payment", session_id))
             .json(&json!({
-                "recipient": "0xRecipient1",
+                "recipient": "0x0000000000000000000000000000000000000001",
                 "recipient_ens": "alice.eth",
                 "amount": "1000000"
             }))
@@ -225,7 +1517,7 @@ mod tests {
         let pay_resp2 = server
             .post(&format!("/api/session/{}/payment", session_id))
             .json(&json!({
-                "recipient": "0xRecipient2",
+                "recipient": "0x0000000000000000000000000000000000000002",
                 "amount": "2000000"
             }))
             .await;
@@ -242,7 +1534,7 @@ mod tests {
         let response = server
             .post("/api/session/nonexistent/payment")
             .json(&json!({
-                "recipient": "0xRecipient",
+                "recipient": "0x1111111111111111111111111111111111111111",
                 "amount": "1000000"
             }))
             .await;
@@ -251,27 +1543,843 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_finalize_session() {
-        let server = create_test_server();
+    async fn test_unknown_body_fields_suggest_known_ones() {
+        let state = create_test_state();
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let session = SessionBuilder::new()
+            .insert_into(&state.session_store)
+            .await;
 
-        // Create session
-        let create_resp = server
-            .post("/api/session")
+        let response = server
+            .post(&format!("/api/v1/session/{}/payment", session.id))
             .json(&json!({
-                "user_address": "0xSender"
+                "recipient": fixtures::ALICE,
+                "recipientEns": "alice.eth",
+                "amount": "1000000"
             }))
             .await;
+        assert_error(
+            &response,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unprocessable_entity",
+        );
+        let body: serde_json::Value = response.json();
+        let field = &body["details"]["fields"][0];
+        assert_eq!(field["field"], "recipientEns");
+        let message = field["message"].as_str().unwrap();
+        assert!(
+            message.starts_with("unknown field `recipientEns`, did you mean `recipient_ens`?"),
+            "{}",
+            message
+        );
+        // Nothing was added
+        let stored = state.session_store.get(&session.id).await.unwrap();
+        assert!(stored.payments.is_empty());
 
-        let session_id = create_resp.json::<serde_json::Value>()["session_id"]
+        // Finalize, batch and optional bodies are as strict
+        for (path, body) in [
+            (
+                format!("/api/v1/session/{}/finalize", session.id),
+                json!({ "txHash": "0xabc" }),
+            ),
+            (
+                "/api/v1/sessions/finalize".to_string(),
+                json!({ "session_ids": [session.id], "tx_hash": "0xabc", "skipDust": true }),
+            ),
+            (
+                format!("/api/v1/session/{}/cancel", session.id),
+                json!({ "reasn": "typo" }),
+            ),
+        ] {
+            let response = server.post(&path).json(&body).await;
+            assert_error(
+                &response,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "unprocessable_entity",
+            );
+        }
+        let response = server
+            .post(&format!("/api/v1/session/{}/finalize", session.id))
+            .json(&json!({ "txHash": "0xabc" }))
+            .await;
+        let message = response.json::<serde_json::Value>()["details"]["fields"][0]["message"]
             .as_str()
             .unwrap()
             .to_string();
+        assert!(message.contains("did you mean `tx_hash`?"), "{}", message);
 
-        // Add payment
-        server
-            .post(&format!("/api/session/{}/payment", session_id))
+        // Names far from every known field get no suggestion
+        let response = server
+            .post(&format!("/api/v1/session/{}/finalize", session.id))
+            .json(&json!({ "flavour": "vanilla" }))
+            .await;
+        let message = response.json::<serde_json::Value>()["details"]["fields"][0]["message"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert!(!message.contains("did you mean"), "{}", message);
+
+        // A body-less cancel still works
+        let response = server
+            .post(&format!("/api/v1/session/{}/cancel", session.id))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_min_payment_amount_and_dust_at_finalize() {
+        let state = create_test_state();
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let session = SessionBuilder::new()
+            .insert_into(&state.session_store)
+            .await;
+        let add = |amount: &str| {
+            server
+                .post(&format!("/api/v1/session/{}/payment", session.id))
+                .json(&json!({ "recipient": fixtures::ALICE, "amount": amount }))
+        };
+
+        // Exactly the default minimum (0.01 USDC) is accepted, one unit less is not
+        add("10000").await.assert_status_ok();
+        let response = add("9999").await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+        let body: serde_json::Value = response.json();
+        assert!(body["message"]
+            .as_str()
+            .unwrap()
+            .contains("below the minimum payment of 10000"));
+
+        // Payments added under a lower minimum can still leave dust
+        let dusty = || {
+            SessionBuilder::new()
+                .payment(fixtures::ALICE, "10000")
+                .payment(fixtures::BOB, "9999")
+                .payment(fixtures::CAROL, "5000")
+                .payment(fixtures::CAROL, "5000")
+                .insert_into(&state.session_store)
+        };
+        let finalize = |id: String, body: serde_json::Value| {
+            server
+                .post(&format!("/api/v1/session/{}/finalize", id))
+                .json(&body)
+        };
+
+        // Payments to one recipient settle as one transfer, so Carol's two
+        // sub-minimum payments add up to exactly the minimum
+        let rejected = dusty().await;
+        let response = finalize(rejected.id.clone(), json!({})).await;
+        assert_error(
+            &response,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unprocessable_entity",
+        );
+        let message = response.json::<serde_json::Value>()["message"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert!(message.contains(&format!("9999 to {}", fixtures::BOB)));
+        assert!(!message.contains(fixtures::CAROL));
+        assert!(message.contains("skip_dust"));
+        let unchanged = state.session_store.get(&rejected.id).await.unwrap();
+        assert_eq!(unchanged.status, SessionStatus::Active);
+        assert_eq!(unchanged.total_amount.to_string(), "29999");
+
+        // With skip_dust the dust payment is cancelled and left out
+        let skipped = dusty().await;
+        finalize(skipped.id.clone(), json!({ "skip_dust": true }))
+            .await
+            .assert_status_ok();
+        let body: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}", skipped.id))
+            .await
+            .json();
+        let payments = body["session"]["payments"].as_array().unwrap();
+        let statuses: Vec<&str> = payments
+            .iter()
+            .map(|p| p["status"].as_str().unwrap())
+            .collect();
+        assert_eq!(statuses, ["pending", "cancelled", "pending", "pending"]);
+        assert_eq!(
+            payments[1]["cancel_reason"],
+            "dust: settles less than the minimum payment of 10000"
+        );
+        assert!(payments[0].get("cancel_reason").is_none());
+        assert_eq!(body["session"]["total_amount"], "20000");
+        assert_eq!(body["session"]["status"], "pending");
+    }
+
+    #[tokio::test]
+    async fn test_add_payment_enforces_global_cap() {
+        let config = Config {
+            max_total_payments: Some(3),
+            ..Config::default()
+        };
+        let state = create_test_state_with_config(config);
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let mut session_ids = Vec::new();
+        for user in [
+            "0x0000000000000000000000000000000000000102",
+            "0x0000000000000000000000000000000000000103",
+        ] {
+            let response = server
+                .post("/api/session")
+                .json(&json!({ "user_address": user }))
+                .await;
+            let body: serde_json::Value = response.json();
+            session_ids.push(body["session_id"].as_str().unwrap().to_string());
+        }
+        let add = |id: &str| {
+            server
+                .post(&format!("/api/session/{}/payment", id))
+                .json(&json!({ "recipient": "0x1111111111111111111111111111111111111111", "amount": "100000" }))
+        };
+
+        // The cap counts payments across sessions
+        for id in [&session_ids[0], &session_ids[0], &session_ids[1]] {
+            assert_eq!(add(id).await.status_code(), StatusCode::OK);
+        }
+        assert_eq!(state.session_store.payment_count(), 3);
+        assert_error(
+            &add(&session_ids[1]).await,
+            StatusCode::TOO_MANY_REQUESTS,
+            "rate_limited",
+        );
+
+        // Removing a payment frees a slot
+        let session = state.session_store.get(&session_ids[0]).await.unwrap();
+        state
+            .session_store
+            .remove_payment(&session_ids[0], &session.payments[0].id)
+            .await
+            .unwrap();
+        assert_eq!(state.session_store.payment_count(), 2);
+        assert_eq!(add(&session_ids[1]).await.status_code(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_payment_keeps_record() {
+        let server = create_test_server();
+
+        let create_resp = server
+            .post("/api/session")
+            .json(&json!({
+                "user_address": "0x4444444444444444444444444444444444444444"
+            }))
+            .await;
+
+        let session_id = create_resp.json::<serde_json::Value>()["session_id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let pay_resp = server
+            .post(&format!("/api/session/{}/payment", session_id))
+            .json(&json!({
+                "recipient": "0x0000000000000000000000000000000000000001",
+                "amount": "1000000"
+            }))
+            .await;
+        let payment_id = pay_resp.json::<serde_json::Value>()["session"]["payments"][0]["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        server
+            .post(&format!("/api/session/{}/payment", session_id))
+            .json(&json!({
+                "recipient": "0x0000000000000000000000000000000000000002",
+                "amount": "2000000"
+            }))
+            .await;
+
+        let cancel_resp = server
+            .post(&format!(
+                "/api/session/{}/payment/{}/cancel",
+                session_id, payment_id
+            ))
+            .await;
+
+        assert_eq!(cancel_resp.status_code(), StatusCode::OK);
+        let body: serde_json::Value = cancel_resp.json();
+        let payments = body["session"]["payments"].as_array().unwrap();
+        assert_eq!(payments.len(), 2);
+        assert_eq!(payments[0]["status"], "cancelled");
+        assert_eq!(body["session"]["total_amount"], "2000000");
+
+        // Cancelling twice is rejected
+        let again = server
+            .post(&format!(
+                "/api/session/{}/payment/{}/cancel",
+                session_id, payment_id
+            ))
+            .await;
+        assert_eq!(again.status_code(), StatusCode::CONFLICT);
+
+        // Unknown payment
+        let missing = server
+            .post(&format!("/api/session/{}/payment/nope/cancel", session_id))
+            .await;
+        assert_eq!(missing.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    /// Session of 25 payments, to `0x…01` through `0x…25`
+    async fn create_session_with_payments(server: &TestServer) -> String {
+        let create_resp = server
+            .post("/api/session")
+            .json(&json!({
+                "user_address": "0x4444444444444444444444444444444444444444"
+            }))
+            .await;
+
+        let session_id = create_resp.json::<serde_json::Value>()["session_id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        for i in 1..=25 {
+            server
+                .post(&format!("/api/session/{}/payment", session_id))
+                .json(&json!({
+                    "recipient": format!("0x{:040}", i),
+                    "amount": (i * 10_000).to_string()
+                }))
+                .await;
+        }
+        session_id
+    }
+
+    #[tokio::test]
+    async fn test_list_payments_paginated() {
+        let server = create_test_server();
+        let session_id = create_session_with_payments(&server).await;
+
+        // Walk the pages with the returned cursor
+        let page = |query: String| {
+            let request = server.get(&format!("/api/v1/session/{}/payments{}", session_id, query));
+            async move { request.await.json::<serde_json::Value>() }
+        };
+        let first = page("?limit=10".to_string()).await;
+        assert_eq!(first["total"], 25);
+        assert_eq!(first["items"].as_array().unwrap().len(), 10);
+        assert_eq!(
+            first["items"][0]["recipient"],
+            "0x0000000000000000000000000000000000000001"
+        );
+
+        let cursor = first["next_cursor"].as_str().unwrap();
+        let second = page(format!("?limit=10&cursor={}", cursor)).await;
+        assert_eq!(
+            second["items"][0]["recipient"],
+            "0x0000000000000000000000000000000000000011"
+        );
+        assert_eq!(
+            second["items"][9]["recipient"],
+            "0x0000000000000000000000000000000000000020"
+        );
+
+        // Last partial page has no cursor
+        let cursor = second["next_cursor"].as_str().unwrap();
+        let last = page(format!("?limit=10&cursor={}", cursor)).await;
+        assert_eq!(last["items"].as_array().unwrap().len(), 5);
+        assert!(last["next_cursor"].is_null());
+
+        // Default page size covers everything
+        let all = page(String::new()).await;
+        assert_eq!(all["items"].as_array().unwrap().len(), 25);
+        assert!(all["next_cursor"].is_null());
+
+        // Out-of-range limits and altered cursors are rejected
+        for query in ["?limit=0", "?limit=101", "?limit=ten", "?cursor=bm9wZQ"] {
+            let response = server
+                .get(&format!("/api/v1/session/{}/payments{}", session_id, query))
+                .await;
+            assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_legacy_list_payments_pages_by_offset() {
+        let server = create_test_server();
+        let session_id = create_session_with_payments(&server).await;
+        let page = |query: &str| {
+            let request = server.get(&format!("/api/session/{}/payments{}", session_id, query));
+            async move {
+                let response = request.await;
+                assert_eq!(response.status_code(), StatusCode::OK);
+                response.json::<serde_json::Value>()
+            }
+        };
+
+        // Middle page
+        let body = page("?limit=10&offset=10").await;
+        let payments = body["payments"].as_array().unwrap();
+        assert_eq!(body["total"], 25);
+        assert_eq!(body["limit"], 10);
+        assert_eq!(body["offset"], 10);
+        assert_eq!(payments.len(), 10);
+        assert_eq!(
+            payments[0]["recipient"],
+            "0x0000000000000000000000000000000000000011"
+        );
+        assert_eq!(
+            payments[9]["recipient"],
+            "0x0000000000000000000000000000000000000020"
+        );
+
+        // Last partial page
+        let body = page("?limit=10&offset=20").await;
+        assert_eq!(body["payments"].as_array().unwrap().len(), 5);
+
+        // Offset past the end
+        let body = page("?offset=100").await;
+        assert_eq!(body["payments"].as_array().unwrap().len(), 0);
+        assert_eq!(body["total"], 25);
+
+        // Limits are clamped, not rejected
+        let body = page("?limit=5000").await;
+        assert_eq!(body["limit"], 100);
+        assert_eq!(body["payments"].as_array().unwrap().len(), 25);
+        let body = page("?limit=0").await;
+        assert_eq!(body["limit"], 1);
+
+        // A v1 cursor means nothing here
+        let body = page("?cursor=bm9wZQ").await;
+        assert_eq!(body["payments"].as_array().unwrap().len(), 25);
+    }
+
+    #[tokio::test]
+    async fn test_list_payments_session_not_found() {
+        let server = create_test_server();
+        let response = server.get("/api/session/nonexistent/payments").await;
+
+        assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_session_read_filters_payments_by_status() {
+        let state = create_test_state();
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let mut session = SessionBuilder::new()
+            .payment(fixtures::ALICE, "100")
+            .payment(fixtures::BOB, "20")
+            .payment(fixtures::CAROL, "3")
+            .cancelled_payment(fixtures::ALICE, "4000")
+            .payment(fixtures::BOB, "500")
+            .build();
+        let now = chrono::Utc::now();
+        session.payments[1].transition(models::session::PaymentStatus::Confirmed, now);
+        session.payments[2].transition(models::session::PaymentStatus::Settled, now);
+        state.session_store.restore(vec![session.clone()]).await;
+
+        let ids = |payments: &serde_json::Value| -> Vec<String> {
+            payments
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|p| p["id"].as_str().unwrap().to_string())
+                .collect()
+        };
+        for (status, expected) in [
+            ("pending", vec!["p1", "p5"]),
+            ("confirmed", vec!["p2"]),
+            ("settled", vec!["p3"]),
+            ("cancelled", vec!["p4"]),
+        ] {
+            let body: serde_json::Value = server
+                .get(&format!("/api/session/{}", session.id))
+                .add_query_param("payment_status", status)
+                .await
+                .json();
+            assert_eq!(ids(&body["session"]["payments"]), expected, "{}", status);
+            // Totals still cover every live payment
+            assert_eq!(body["session"]["total_amount"], "623");
+
+            let page: serde_json::Value = server
+                .get(&format!("/api/v1/session/{}/payments", session.id))
+                .add_query_param("payment_status", status)
+                .await
+                .json();
+            assert_eq!(ids(&page["items"]), expected, "{}", status);
+            assert_eq!(page["total"], expected.len());
+            let legacy: serde_json::Value = server
+                .get(&format!("/api/session/{}/payments", session.id))
+                .add_query_param("payment_status", status)
+                .await
+                .json();
+            assert_eq!(ids(&legacy["payments"]), expected, "{}", status);
+        }
+
+        // Unfiltered reads list everything
+        let body: serde_json::Value = server
+            .get(&format!("/api/session/{}", session.id))
+            .await
+            .json();
+        assert_eq!(body["session"]["payments"].as_array().unwrap().len(), 5);
+
+        for path in ["", "/payments"] {
+            let response = server
+                .get(&format!("/api/session/{}{}", session.id, path))
+                .add_query_param("payment_status", "refunded")
+                .await;
+            assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+        }
+    }
+
+    /// Wait for the webhook forwarders to queue `queued` events in total
+    async fn await_webhook_queue(state: &AppState, queued: usize) {
+        for _ in 0..200 {
+            let total: usize = state.webhooks.list().iter().map(|w| w.queued).sum();
+            if total >= queued {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!(
+            "webhook events were not queued: {:?}",
+            state.webhooks.list()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webhooks_deliver_events_in_sequence_order() {
+        use std::future::IntoFuture;
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let receiver = MockServer::start().await;
+        // The first delivery fails, and is retried before anything later
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&receiver)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&receiver)
+            .await;
+
+        let state = create_test_state_with_config(authenticated_config());
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let register = |body: serde_json::Value| {
+            server
+                .post("/admin/webhooks")
+                .add_header("x-api-key", "admin-key")
+                .json(&body)
+        };
+        let added = register(json!({
+            "url": format!("{}/added", receiver.uri()),
+            "types": ["payment.added"],
+        }))
+        .await;
+        assert_eq!(added.status_code(), StatusCode::CREATED);
+        let added: serde_json::Value = added.json();
+        assert_eq!(added["types"], json!(["payment.added"]));
+        let all: serde_json::Value = register(json!({ "url": format!("{}/all", receiver.uri()) }))
+            .await
+            .json();
+        for body in [
+            json!({ "url": "ftp://example.com/hook" }),
+            json!({ "url": "not a url" }),
+            json!({ "url": receiver.uri(), "types": [] }),
+            json!({ "url": receiver.uri(), "types": ["payment.renamed"] }),
+        ] {
+            assert_error(
+                &register(body).await,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "unprocessable_entity",
+            );
+        }
+
+        // A burst of concurrent adds, then a removal
+        let session = SessionBuilder::new()
+            .insert_into(&state.session_store)
+            .await;
+        let adds = (1..=6).map(|i| {
+            server
+                .post(&format!("/api/v1/session/{}/payment", session.id))
+                .add_header("x-api-key", "client-key")
+                .json(&json!({ "recipient": fixtures::ALICE, "amount": (i * 100_000).to_string() }))
+                .into_future()
+        });
+        for response in futures::future::join_all(adds).await {
+            assert_eq!(response.status_code(), StatusCode::OK);
+        }
+        let stored = state.session_store.get(&session.id).await.unwrap();
+        server
+            .delete(&format!(
+                "/api/v1/session/{}/payment/{}",
+                session.id, stored.payments[0].id
+            ))
+            .add_header("x-api-key", "client-key")
+            .await
+            .assert_status_ok();
+        await_webhook_queue(&state, 6 + 7).await;
+
+        assert!(state.webhooks.deliver().await.is_err());
+        let listed: serde_json::Value = server
+            .get("/admin/webhooks")
+            .add_header("x-api-key", "admin-key")
+            .await
+            .json();
+        let failing = listed["webhooks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|w| w["last_error"].is_string())
+            .unwrap();
+        assert_eq!(failing["failures"], 1);
+        state.webhooks.deliver().await.unwrap();
+        assert!(state.webhooks.list().iter().all(|w| w.queued == 0));
+
+        let delivered = |route: &str| {
+            let route = route.to_string();
+            let receiver = &receiver;
+            async move {
+                receiver
+                    .received_requests()
+                    .await
+                    .unwrap()
+                    .iter()
+                    .filter(|r| r.url.path() == route)
+                    .map(|r| r.body_json::<serde_json::Value>().unwrap())
+                    .collect::<Vec<_>>()
+            }
+        };
+        let summary = |events: &[serde_json::Value]| -> Vec<(u64, String)> {
+            events
+                .iter()
+                .map(|e| {
+                    assert_eq!(e["session_id"], session.id.as_str());
+                    (
+                        e["sequence"].as_u64().unwrap(),
+                        e["type"].as_str().unwrap().to_string(),
+                    )
+                })
+                .collect()
+        };
+        let mut added_events = delivered("/added").await;
+        let mut all_events = delivered("/all").await;
+        // One of the receivers got the failed first event twice
+        for events in [&mut added_events, &mut all_events] {
+            if events.len() > 1 && events[0] == events[1] {
+                events.remove(0);
+            }
+        }
+        let adds: Vec<(u64, String)> = (1..=6).map(|s| (s, "payment.added".to_string())).collect();
+        assert_eq!(summary(&added_events), adds);
+        let mut everything = adds.clone();
+        everything.push((7, "payment.removed".to_string()));
+        assert_eq!(summary(&all_events), everything);
+        // Each event carries the session total after it
+        assert_eq!(added_events[5]["total_amount"], "2100000");
+        let removed: u64 = all_events[6]["payment"]["amount"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(
+            removed,
+            stored.payments[0]
+                .amount
+                .to_string()
+                .parse::<u64>()
+                .unwrap()
+        );
+        assert_eq!(
+            all_events[6]["total_amount"],
+            (2_100_000 - removed).to_string()
+        );
+
+        // Removed receivers get nothing more
+        let id = all["id"].as_str().unwrap();
+        let remove = || {
+            server
+                .delete(&format!("/admin/webhooks/{}", id))
+                .add_header("x-api-key", "admin-key")
+        };
+        assert_eq!(remove().await.status_code(), StatusCode::NO_CONTENT);
+        assert_error(&remove().await, StatusCode::NOT_FOUND, "not_found");
+        assert_eq!(state.webhooks.list().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_session_events_replay_after_a_sequence() {
+        let state = create_test_state_with_config(authenticated_config());
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let session = SessionBuilder::new()
+            .insert_into(&state.session_store)
+            .await;
+
+        let mut payment_ids = Vec::new();
+        for (recipient, amount) in [
+            (fixtures::ALICE, "1000000"),
+            (fixtures::BOB, "200000"),
+            (fixtures::CAROL, "30000"),
+        ] {
+            let body: serde_json::Value = server
+                .post(&format!("/api/v1/session/{}/payment", session.id))
+                .add_header("x-api-key", "client-key")
+                .json(&json!({ "recipient": recipient, "amount": amount }))
+                .await
+                .json();
+            let payments = body["session"]["payments"].as_array().unwrap();
+            payment_ids.push(payments.last().unwrap()["id"].as_str().unwrap().to_string());
+        }
+        server
+            .delete(&format!(
+                "/api/v1/session/{}/payment/{}",
+                session.id, payment_ids[0]
+            ))
+            .add_header("x-api-key", "client-key")
+            .await;
+        server
+            .post(&format!("/api/v1/session/{}/payment", session.id))
+            .add_header("x-api-key", "client-key")
+            .json(&json!({ "recipient": fixtures::ALICE, "amount": "40000000" }))
+            .await;
+
+        // Exactly the events after sequence 2, in order
+        let events = |server: &TestServer, since: &'static str| {
+            server
+                .get(&format!("/api/v1/session/{}/events", session.id))
+                .add_query_param("since_sequence", since)
+        };
+        let body: serde_json::Value = events(&server, "2").await.json();
+        assert_eq!(body["session_id"], session.id.as_str());
+        let summary: Vec<(u64, String, String, String)> = body["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| {
+                (
+                    e["sequence"].as_u64().unwrap(),
+                    e["type"].as_str().unwrap().to_string(),
+                    e["payment"]["amount"].as_str().unwrap().to_string(),
+                    e["total_amount"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect();
+        let expected = |sequence: u64, kind: &str, amount: &str, total: &str| {
+            (
+                sequence,
+                kind.to_string(),
+                amount.to_string(),
+                total.to_string(),
+            )
+        };
+        assert_eq!(
+            summary,
+            [
+                expected(3, "payment.added", "30000", "1230000"),
+                expected(4, "payment.removed", "1000000", "230000"),
+                expected(5, "payment.added", "40000000", "40230000"),
+            ]
+        );
+        let body: serde_json::Value = events(&server, "5").await.json();
+        assert_eq!(body["events"], json!([]));
+        assert_error(
+            &events(&server, "-1").await,
+            StatusCode::BAD_REQUEST,
+            "validation_error",
+        );
+        let response = server.get("/api/v1/session/nonexistent/events").await;
+        assert_error(&response, StatusCode::NOT_FOUND, "not_found");
+
+        // Admins re-enqueue a range for the webhook receivers; with none
+        // registered, nothing is queued
+        let redeliver = |body: serde_json::Value| {
+            server
+                .post("/admin/webhooks/redeliver")
+                .add_header("x-api-key", "admin-key")
+                .json(&body)
+        };
+        let range = json!({
+            "session_id": session.id,
+            "from_sequence": 2,
+            "to_sequence": 3,
+        });
+        assert_eq!(
+            redeliver(range.clone()).await.json::<serde_json::Value>(),
+            json!({ "redelivered": 0 })
+        );
+        let receiver = wiremock::MockServer::start().await;
+        wiremock::Mock::given(wiremock::matchers::method("POST"))
+            .respond_with(wiremock::ResponseTemplate::new(204))
+            .mount(&receiver)
+            .await;
+        state.webhooks.register(receiver.uri(), None);
+        assert_eq!(
+            redeliver(range).await.json::<serde_json::Value>(),
+            json!({ "redelivered": 2 })
+        );
+        state.webhooks.deliver().await.unwrap();
+        let delivered: Vec<u64> = receiver
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| {
+                r.body_json::<serde_json::Value>().unwrap()["sequence"]
+                    .as_u64()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(delivered, [2, 3]);
+        assert_error(
+            &redeliver(json!({ "session_id": session.id, "from_sequence": 3, "to_sequence": 2 }))
+                .await,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unprocessable_entity",
+        );
+        assert_error(
+            &redeliver(json!({ "session_id": "nonexistent", "from_sequence": 1 })).await,
+            StatusCode::NOT_FOUND,
+            "not_found",
+        );
+
+        // The log survives a snapshot round trip
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+        Snapshot::new(state.session_store.snapshot().await)
+            .with_events(state.session_store.events_snapshot().await)
+            .write(&path)
+            .unwrap();
+        let restored = create_test_state();
+        cli::restore_sessions(&restored, &path).await.unwrap();
+        let server = TestServer::new(create_app(restored)).unwrap();
+        let body: serde_json::Value = events(&server, "0").await.json();
+        let sequences: Vec<u64> = body["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["sequence"].as_u64().unwrap())
+            .collect();
+        assert_eq!(sequences, [1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_finalize_session() {
+        let server = create_test_server();
+
+        // Create session
+        let create_resp = server
+            .post("/api/session")
+            .json(&json!({
+                "user_address": "0x4444444444444444444444444444444444444444"
+            }))
+            .await;
+
+        let session_id = create_resp.json::<serde_json::Value>()["session_id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        // Add payment
+        server
+            .post(&format!("/api/session/{}/payment", session_id))
             .json(&json!({
-                "recipient": "0xRecipient",
+                "recipient": "0x1111111111111111111111111111111111111111",
                 "amount": "5000000"
             }))
             .await;
@@ -298,59 +2406,5179 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_finalize_session_not_found() {
-        let server = create_test_server();
-        let response = server
-            .post("/api/session/nonexistent/finalize")
+    async fn test_get_session_by_tx_hash() {
+        let state = create_test_state();
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let tx_hash = format!("0x{}", "ab12".repeat(16));
+        let session_id = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x0000000000000000000000000000000000000117" }))
+            .await
+            .json::<serde_json::Value>()["session_id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        server
+            .post(&format!("/api/v1/session/{}/payment", session_id))
             .json(&json!({
-                "tx_hash": "0xabc"
+                "recipient": "0x1111111111111111111111111111111111111111",
+                "amount": "5000000"
             }))
             .await;
+        let finalized = server
+            .post(&format!("/api/v1/session/{}/finalize", session_id))
+            .json(&json!({ "tx_hash": tx_hash }))
+            .await;
+        assert_eq!(finalized.status_code(), StatusCode::OK);
 
-        assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
-    }
-
-    // ── ENS Routes ────────────────────────────────────
+        // Lookups ignore case
+        for hash in [
+            tx_hash.clone(),
+            tx_hash.to_uppercase().replacen("0X", "0x", 1),
+        ] {
+            let response = server
+                .get(&format!("/api/v1/sessions/by-tx/{}", hash))
+                .await;
+            assert_eq!(response.status_code(), StatusCode::OK);
+            let body: serde_json::Value = response.json();
+            assert_eq!(body["session"]["id"], session_id);
+            assert_eq!(body["session"]["tx_hash"], tx_hash);
+        }
 
-    #[tokio::test]
-    async fn test_ens_resolve_invalid_name() {
-        let server = create_test_server();
-        let response = server.get("/api/ens/resolve?name=invalid").await;
+        let missing = server
+            .get(&format!("/api/v1/sessions/by-tx/0x{}", "cd34".repeat(16)))
+            .await;
+        assert_error(&missing, StatusCode::NOT_FOUND, "not_found");
 
-        assert_eq!(response.status_code(), StatusCode::OK);
-        let body: serde_json::Value = response.json();
-        assert!(body["error"].as_str().is_some());
-        assert!(body["address"].is_null());
+        // Once archived, the hash is answered as the session id would be
+        state.session_store.settle(&session_id).await.unwrap();
+        assert_eq!(
+            state
+                .session_store
+                .archive_settled(Duration::ZERO)
+                .await
+                .len(),
+            1
+        );
+        let archived = server
+            .get(&format!(
+                "/api/v1/sessions/by-tx/{}",
+                tx_hash.to_uppercase().replacen("0X", "0x", 1)
+            ))
+            .await;
+        assert_error(&archived, StatusCode::GONE, "gone");
     }
 
     #[tokio::test]
-    async fn test_ens_lookup_returns_response() {
+    async fn test_finalize_guard_rejects_changed_session() {
         let server = create_test_server();
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x4444444444444444444444444444444444444444" }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap();
+        let add_payment = |amount: &'static str| {
+            server
+                .post(&format!("/api/v1/session/{}/payment", session_id))
+                .json(&json!({ "recipient": "0x1111111111111111111111111111111111111111", "amount": amount }))
+        };
+        add_payment("5000000").await;
+
+        // The user reviewed 5 USDC in one payment, then another was added
+        add_payment("1000000").await;
         let response = server
-            .get("/api/ens/lookup?address=0x0000000000000000000000000000000000000000")
+            .post(&format!("/api/v1/session/{}/finalize", session_id))
+            .json(&json!({ "tx_hash": "0xabc", "expected_total": "5000000" }))
+            .await;
+        assert_error(&response, StatusCode::CONFLICT, "conflict");
+        let response = server
+            .post(&format!("/api/v1/session/{}/finalize", session_id))
+            .json(&json!({ "tx_hash": "0xabc", "expected_payment_count": 1 }))
+            .await;
+        assert_error(&response, StatusCode::CONFLICT, "conflict");
+        let session: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}", session_id))
+            .await
+            .json();
+        assert_eq!(session["session"]["status"], "active");
+        assert!(session["session"]["tx_hash"].is_null());
+
+        let response = server
+            .post(&format!("/api/v1/session/{}/finalize", session_id))
+            .json(&json!({ "expected_total": "six" }))
             .await;
+        assert_error(
+            &response,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unprocessable_entity",
+        );
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["details"]["fields"][0]["field"], "expected_total");
 
+        // Matching expectations proceed
+        let response = server
+            .post(&format!("/api/v1/session/{}/finalize", session_id))
+            .json(&json!({
+                "tx_hash": "0xabc",
+                "expected_total": "6000000",
+                "expected_payment_count": 2
+            }))
+            .await;
         assert_eq!(response.status_code(), StatusCode::OK);
         let body: serde_json::Value = response.json();
-        // Should return a valid response structure even if no name found
-        assert_eq!(
-            body["address"],
-            "0x0000000000000000000000000000000000000000"
-        );
+        assert_eq!(body["status"], "pending");
+        assert_eq!(body["tx_hash"], "0xabc");
     }
 
-    // ── Quote Route ───────────────────────────────────
-
     #[tokio::test]
-    async fn test_quote_returns_response() {
+    async fn test_finalize_cooldown() {
+        let config = Config {
+            finalize_cooldown_secs: 1,
+            ..Config::default()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x4444444444444444444444444444444444444444" }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap();
+        let finalize = |tx_hash: &'static str| {
+            server
+                .post(&format!("/api/v1/session/{}/finalize", session_id))
+                .json(&json!({ "tx_hash": tx_hash }))
+        };
+        assert_eq!(finalize("0xabc").await.status_code(), StatusCode::OK);
+
+        // An immediate retry is rejected and keeps the first tx_hash
+        let response = finalize("0xdef").await;
+        assert_error(&response, StatusCode::TOO_MANY_REQUESTS, "rate_limited");
+        assert_eq!(response.header("retry-after"), "1");
+        let session: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}", session_id))
+            .await
+            .json();
+        assert_eq!(session["session"]["tx_hash"], "0xabc");
+
+        // Once the cooldown has passed the session can be finalized again
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let response = finalize("0xdef").await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(response.json::<serde_json::Value>()["tx_hash"], "0xdef");
+    }
+
+    #[tokio::test]
+    async fn test_reorder_payments() {
+        let state = create_test_state();
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x4444444444444444444444444444444444444444" }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap();
+        let mut ids = Vec::new();
+        for amount in ["100000", "200000", "300000"] {
+            let body: serde_json::Value = server
+                .post(&format!("/api/v1/session/{}/payment", session_id))
+                .json(&json!({ "recipient": "0x1111111111111111111111111111111111111111", "amount": amount }))
+                .await
+                .json();
+            let payments = body["session"]["payments"].as_array().unwrap();
+            ids.push(payments.last().unwrap()["id"].as_str().unwrap().to_string());
+        }
+        let reorder = |order: serde_json::Value| {
+            server
+                .post(&format!("/api/v1/session/{}/payments/reorder", session_id))
+                .json(&json!({ "payment_ids": order }))
+        };
+        let amounts = |session: &serde_json::Value| -> Vec<String> {
+            session["payments"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|p| p["amount"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        let before: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}", session_id))
+            .await
+            .json();
+        assert_eq!(amounts(&before["session"]), ["100000", "200000", "300000"]);
+
+        let response = reorder(json!([ids[2], ids[0], ids[1]])).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(amounts(&body["session"]), ["300000", "100000", "200000"]);
+        assert_eq!(body["session"]["payments"][0]["index"], 0);
+        assert_eq!(
+            body["session"]["version"],
+            before["session"]["version"].as_u64().unwrap() + 1
+        );
+        let after: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}", session_id))
+            .await
+            .json();
+        assert_eq!(amounts(&after["session"]), ["300000", "100000", "200000"]);
+
+        // The payments listing pages through the same order
+        let page = |cursor: Option<&str>| {
+            let mut request = server
+                .get(&format!("/api/v1/session/{}/payments", session_id))
+                .add_query_param("limit", 2);
+            if let Some(cursor) = cursor {
+                request = request.add_query_param("cursor", cursor);
+            }
+            async move { request.await.json::<serde_json::Value>() }
+        };
+        let first = page(None).await;
+        assert_eq!(
+            amounts(&json!({ "payments": first["items"] })),
+            ["300000", "100000"]
+        );
+        let second = page(first["next_cursor"].as_str()).await;
+        assert_eq!(amounts(&json!({ "payments": second["items"] })), ["200000"]);
+        assert!(second["next_cursor"].is_null());
+        let legacy: serde_json::Value = server
+            .get(&format!("/api/session/{}/payments", session_id))
+            .await
+            .json();
+        assert_eq!(amounts(&legacy), ["300000", "100000", "200000"]);
+        // A cursor of a time-ordered listing does not apply here
+        let stale = api::pagination::Cursor::new(chrono::Utc::now(), ids[0].as_str()).encode();
+        let response = server
+            .get(&format!("/api/v1/session/{}/payments", session_id))
+            .add_query_param("cursor", stale)
+            .await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+
+        // Only a permutation of the session's payments is accepted
+        for order in [
+            json!([ids[0], ids[1]]),
+            json!([ids[0], ids[0], ids[1]]),
+            json!([ids[0], ids[1], "unknown"]),
+        ] {
+            let response = reorder(order).await;
+            assert_error(
+                &response,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "unprocessable_entity",
+            );
+            let body: serde_json::Value = response.json();
+            assert_eq!(body["details"]["fields"][0]["field"], "payment_ids");
+        }
+
+        // The order survives a snapshot round trip
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+        Snapshot::new(state.session_store.snapshot().await)
+            .write(&path)
+            .unwrap();
+        let restored = create_test_state();
+        restored
+            .session_store
+            .restore(Snapshot::read(&path).unwrap().sessions)
+            .await;
+        let server = TestServer::new(create_app(restored)).unwrap();
+        let body: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}", session_id))
+            .await
+            .json();
+        assert_eq!(amounts(&body["session"]), ["300000", "100000", "200000"]);
+
+        // Only active sessions can be reordered
+        server
+            .post(&format!("/api/v1/session/{}/finalize", session_id))
+            .json(&json!({}))
+            .await;
+        let response = server
+            .post(&format!("/api/v1/session/{}/payments/reorder", session_id))
+            .json(&json!({ "payment_ids": [ids[0], ids[1], ids[2]] }))
+            .await;
+        assert_error(&response, StatusCode::CONFLICT, "conflict");
+    }
+
+    #[tokio::test]
+    async fn test_finalize_session_not_found() {
         let server = create_test_server();
         let response = server
-            .get("/api/quote?from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount=1000000")
+            .post("/api/session/nonexistent/finalize")
+            .json(&json!({
+                "tx_hash": "0xabc"
+            }))
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_finalize_reports_partial_success() {
+        let server = create_test_server();
+        let mut ids = Vec::new();
+        for user in [
+            "0x0000000000000000000000000000000000000114",
+            "0x0000000000000000000000000000000000000115",
+            "0x0000000000000000000000000000000000000116",
+        ] {
+            let created: serde_json::Value = server
+                .post("/api/v1/session")
+                .json(&json!({ "user_address": user }))
+                .await
+                .json();
+            ids.push(created["session_id"].as_str().unwrap().to_string());
+        }
+        server
+            .post(&format!("/api/v1/session/{}/cancel", ids[1]))
+            .await
+            .assert_status_ok();
+
+        let response = server
+            .post("/api/v1/sessions/finalize")
+            .json(&json!({
+                "session_ids": [ids[0], ids[1], "missing", ids[2]],
+                "tx_hash": "0xbatch",
+            }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["finalized"], 2);
+        assert_eq!(body["failed"], 2);
+        let results = body["results"].as_array().unwrap();
+        let outcome = |i: usize| {
+            (
+                results[i]["session_id"].as_str().unwrap().to_string(),
+                results[i]["status"].as_str().unwrap().to_string(),
+                results[i]["error"]["code"].as_str().map(str::to_string),
+            )
+        };
+        assert_eq!(outcome(0), (ids[0].clone(), "pending".into(), None));
+        assert_eq!(
+            outcome(1),
+            (ids[1].clone(), "failed".into(), Some("conflict".into()))
+        );
+        assert_eq!(
+            outcome(2),
+            ("missing".into(), "failed".into(), Some("not_found".into()))
+        );
+        assert_eq!(outcome(3), (ids[2].clone(), "pending".into(), None));
+        assert_eq!(results[0]["tx_hash"], "0xbatch");
+        assert!(results[1].get("tx_hash").is_none());
+
+        // Finalized sessions carry the batch transaction; the cancelled one is untouched
+        let session: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}", ids[2]))
+            .await
+            .json();
+        assert_eq!(session["session"]["tx_hash"], "0xbatch");
+        let session: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}", ids[1]))
+            .await
+            .json();
+        assert_eq!(session["session"]["status"], "cancelled");
+
+        let too_many = vec!["id"; api::session::MAX_BULK_FINALIZE + 1];
+        let response = server
+            .post("/api/v1/sessions/finalize")
+            .json(&json!({ "session_ids": too_many }))
+            .await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+    }
+
+    #[tokio::test]
+    async fn test_mask_addresses_in_session_responses() {
+        const USER: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        const RECIPIENT: &str = "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359";
+
+        for mask in [true, false] {
+            let state = create_test_state_with_config(Config {
+                mask_addresses: mask,
+                ..Config::default()
+            });
+            let server = TestServer::new(create_app(state.clone())).unwrap();
+            let created: serde_json::Value = server
+                .post("/api/v1/session")
+                .json(&json!({ "user_address": USER }))
+                .await
+                .json();
+            let id = created["session_id"].as_str().unwrap();
+            let added: serde_json::Value = server
+                .post(&format!("/api/v1/session/{}/payment", id))
+                .json(&json!({ "recipient": RECIPIENT, "amount": "100000" }))
+                .await
+                .json();
+
+            let (user, recipient) = if mask {
+                ("0x5aAe...eAed", "0xfB69...d359")
+            } else {
+                (USER, RECIPIENT)
+            };
+            assert_eq!(added["session"]["payments"][0]["recipient"], recipient);
+            for path in [
+                format!("/api/v1/session/{}", id),
+                format!("/api/session/{}", id),
+            ] {
+                let body: serde_json::Value = server.get(&path).await.json();
+                assert_eq!(body["session"]["user"], user);
+                assert_eq!(body["session"]["payments"][0]["recipient"], recipient);
+                // Amounts and ids are never taken for addresses
+                assert_eq!(body["session"]["payments"][0]["amount"], "100000");
+                assert_eq!(body["session"]["id"], id);
+            }
+            let body: serde_json::Value = server
+                .get(&format!("/api/v1/session/{}/payments", id))
+                .await
+                .json();
+            assert_eq!(body["items"][0]["recipient"], recipient);
+            let body: serde_json::Value = server
+                .get(&format!("/api/v1/session/{}/events", id))
+                .await
+                .json();
+            assert_eq!(body["events"][0]["payment"]["recipient"], recipient);
+            // Every address of a receipt, transfers included
+            let receipt: serde_json::Value = server
+                .get(&format!("/api/v1/session/{}/receipt", id))
+                .await
+                .json();
+            assert_eq!(receipt["settlement_mode"], "direct");
+            assert_eq!(receipt["transfers"][0]["to"], recipient);
+            assert_eq!(receipt["recipients"][0]["recipient"], recipient);
+
+            // GraphQL and gRPC show them the same way
+            let body = graphql(
+                &server,
+                "query($id: String!) { session(id: $id) { user payments { recipient } } }",
+                json!({ "id": id }),
+            )
+            .await;
+            assert_eq!(body["data"]["session"]["user"], user);
+            assert_eq!(
+                body["data"]["session"]["payments"][0]["recipient"],
+                recipient
+            );
+            #[cfg(feature = "grpc")]
+            {
+                let mut client = spawn_grpc(state.clone());
+                let get = grpc::proto::GetSessionRequest {
+                    session_id: id.to_string(),
+                };
+                let session = client.get(get).await.unwrap().into_inner();
+                assert_eq!(session.user, user);
+                assert_eq!(session.payments[0].recipient, recipient);
+                state.shutdown.cancel();
+            }
+
+            // The stored session, which settlement pays out from, is unmasked
+            let stored = state.session_store.get(id).await.unwrap();
+            assert_eq!(stored.user.to_string(), USER);
+            assert_eq!(stored.payments[0].recipient.to_string(), RECIPIENT);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_format_header() {
+        let server = create_test_server();
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x4444444444444444444444444444444444444444" }))
+            .await
+            .json();
+        let id = created["session_id"].as_str().unwrap();
+        let path = format!("/api/v1/session/{}", id);
+        server
+            .post(&format!("{}/payment", path))
+            .json(&json!({ "recipient": "0x1111111111111111111111111111111111111111", "amount": "100000" }))
+            .await
+            .assert_status_ok();
+
+        let unix_of = |value: &serde_json::Value| {
+            chrono::DateTime::parse_from_rfc3339(value.as_str().unwrap())
+                .unwrap()
+                .timestamp()
+        };
+        let rfc3339 = server.get(&path).await.json::<serde_json::Value>()["session"].take();
+        let explicit: serde_json::Value = server
+            .get(&path)
+            .add_header("x-timestamp-format", "rfc3339")
+            .await
+            .json::<serde_json::Value>()["session"]
+            .take();
+        assert_eq!(explicit, rfc3339);
+        assert!(rfc3339["created_at"].is_string());
+
+        let response = server
+            .get(&path)
+            .add_header("x-timestamp-format", "unix")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert!(response
+            .header(axum::http::header::VARY)
+            .to_str()
+            .unwrap()
+            .contains("x-timestamp-format"));
+        let unix = response.json::<serde_json::Value>()["session"].take();
+        assert_eq!(unix["created_at"], unix_of(&rfc3339["created_at"]));
+        assert_eq!(
+            unix["payments"][0]["created_at"],
+            unix_of(&rfc3339["payments"][0]["created_at"])
+        );
+        // Unset timestamps stay null and other fields are untouched
+        assert!(unix["payments"][0]["settled_at"].is_null());
+        assert_eq!(unix["payments"][0]["amount"], "100000");
+        assert_eq!(unix["id"], id);
+
+        // Every REST endpoint, old and new, honors the header
+        for (list, items) in [
+            (format!("/api/v1/session/{}/payments", id), "items"),
+            (format!("/api/session/{}/payments", id), "payments"),
+        ] {
+            let body: serde_json::Value = server
+                .get(&list)
+                .add_header("x-timestamp-format", "unix")
+                .await
+                .json();
+            assert_eq!(
+                body[items][0]["created_at"], unix["payments"][0]["created_at"],
+                "{}",
+                list
+            );
+        }
+
+        let response = server
+            .get(&path)
+            .add_header("x-timestamp-format", "iso")
+            .await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+    }
+
+    #[tokio::test]
+    async fn test_get_session_etag_revalidation() {
+        use axum::http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH};
+        use axum::http::HeaderValue;
+
+        let server = create_test_server();
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x4444444444444444444444444444444444444444" }))
+            .await
+            .json();
+        let path = format!(
+            "/api/v1/session/{}",
+            created["session_id"].as_str().unwrap()
+        );
+
+        let response = server.get(&path).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(
+            response.header(CACHE_CONTROL),
+            "private, max-age=0, must-revalidate"
+        );
+        let etag = response.header(ETAG);
+        assert!(etag.to_str().unwrap().starts_with("W/\""));
+
+        // Unchanged: 304 with no body
+        let response = server
+            .get(&path)
+            .add_header(IF_NONE_MATCH, etag.clone())
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.header(ETAG), etag);
+        assert!(response.as_bytes().is_empty());
+
+        // A mutation changes the ETag
+        server
+            .post(&format!("{}/payment", path))
+            .json(&json!({ "recipient": "0x1111111111111111111111111111111111111111", "amount": "100000" }))
+            .await
+            .assert_status_ok();
+        let response = server
+            .get(&path)
+            .add_header(IF_NONE_MATCH, etag.clone())
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_ne!(response.header(ETAG), etag);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["session"]["payments"].as_array().unwrap().len(), 1);
+
+        // Strong form of the same tag and wildcard also match
+        let current = response.header(ETAG);
+        let strong = current
+            .to_str()
+            .unwrap()
+            .trim_start_matches("W/")
+            .to_string();
+        for tag in [strong.as_str(), "*"] {
+            let response = server
+                .get(&path)
+                .add_header(IF_NONE_MATCH, HeaderValue::from_str(tag).unwrap())
+                .await;
+            assert_eq!(response.status_code(), StatusCode::NOT_MODIFIED);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_responses_are_compressed_when_accepted() {
+        use axum::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
+        use axum::http::HeaderValue;
+
+        let server = create_test_server();
+        let response = server
+            .get("/api/openapi.json")
+            .add_header(ACCEPT_ENCODING, HeaderValue::from_static("br, gzip"))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(response.header(CONTENT_ENCODING), "br");
+
+        let response = server
+            .get("/api/openapi.json")
+            .add_header(ACCEPT_ENCODING, HeaderValue::from_static("gzip"))
+            .await;
+        assert_eq!(response.header(CONTENT_ENCODING), "gzip");
+
+        let response = server.get("/api/openapi.json").await;
+        assert!(response.maybe_header(CONTENT_ENCODING).is_none());
+    }
+
+    #[cfg(feature = "settlement")]
+    #[tokio::test]
+    async fn test_settlement_status_confirms_and_settles() {
+        let app = TestApp::spawn().await;
+        app.stub_tx_receipt("0xabc123def456", 0x10, 0x12, true)
+            .await;
+        let server = &app.server;
+
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x4444444444444444444444444444444444444444" }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap().to_string();
+        for amount in ["5000000", "1000000"] {
+            server
+                .post(&format!("/api/v1/session/{}/payment", session_id))
+                .json(&json!({ "recipient": "0x1111111111111111111111111111111111111111", "amount": amount }))
+                .await;
+        }
+        server
+            .post(&format!("/api/v1/session/{}/finalize", session_id))
+            .json(&json!({ "tx_hash": "0xabc123def456" }))
+            .await;
+
+        let response = server
+            .get(&format!("/api/v1/session/{}/settlement-status", session_id))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["tx_status"], "confirmed");
+        assert_eq!(body["session_status"], "settled");
+        assert_eq!(body["confirmations"], 3);
+        assert_eq!(body["block_number"], 16);
+
+        let session: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}", session_id))
+            .await
+            .json();
+        assert_eq!(session["session"]["status"], "settled");
+        for payment in session["session"]["payments"].as_array().unwrap() {
+            assert_eq!(payment["status"], "settled");
+        }
+    }
+
+    /// Contract and transaction of the settlement batching tests
+    #[cfg(feature = "settlement")]
+    const BATCH_CONTRACT: &str = "0xe66b3fa5f2b84df7cbd288eb3bc91fee48a90cb2";
+    #[cfg(feature = "settlement")]
+    const BATCH_TX: &str = "0x00000000000000000000000000000000000000000000000000000000000ba7c4";
+
+    /// App settling sessions in batches of `max_sessions`, signed for CAROL
+    #[cfg(feature = "settlement")]
+    async fn spawn_batching_app(max_sessions: usize) -> TestApp {
+        let app = TestApp::spawn_with(Config {
+            settlement_batching: true,
+            settlement_contract_address: Some(BATCH_CONTRACT.to_string()),
+            settlement_signer_address: Some(fixtures::address(fixtures::CAROL)),
+            settlement_batch_max_sessions: max_sessions,
+            ..Config::default()
+        })
+        .await;
+        app.stub_transaction_count(3).await;
+        app.stub_send_transaction(BATCH_TX).await;
+        for (id, recipient) in [("batch-a", fixtures::ALICE), ("batch-b", fixtures::BOB)] {
+            SessionBuilder::new()
+                .id(id)
+                .payment(recipient, "1000000")
+                .insert_into(&app.state.session_store)
+                .await;
+            let body: serde_json::Value = app
+                .server
+                .post(&format!("/api/v1/session/{}/finalize", id))
+                .json(&json!({}))
+                .await
+                .json();
+            assert_eq!(body["batched"], true);
+            assert!(body["tx_hash"].is_null());
+        }
+        app
+    }
+
+    /// `BatchSettled` log of the batching contract for `session_id`
+    #[cfg(feature = "settlement")]
+    fn batch_settled_log(session_id: &str) -> serde_json::Value {
+        use sha3::{Digest, Keccak256};
+        let hex = |bytes: &[u8]| -> String {
+            let digits: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("0x{}", digits)
+        };
+        json!({
+            "address": BATCH_CONTRACT,
+            "topics": [
+                hex(&Keccak256::digest(b"BatchSettled(bytes32,uint256,uint256)")),
+                hex(&services::settlement_batch::commitment(session_id)),
+            ],
+            "data": "0x",
+        })
+    }
+
+    #[cfg(feature = "settlement")]
+    #[tokio::test]
+    async fn test_settlement_batch_settles_sessions_in_one_transaction() {
+        let app = spawn_batching_app(2).await;
+        app.stub_tx_receipt_with_logs(
+            BATCH_TX,
+            0x10,
+            0x12,
+            true,
+            json!([batch_settled_log("batch-a"), batch_settled_log("batch-b")]),
+        )
+        .await;
+        let batcher = app.state.settlement_batcher.clone().unwrap();
+
+        // The second finalize filled the batch, which is sent in the
+        // background; a flush waits for that send (or makes it first)
+        batcher.flush().await.unwrap();
+        assert_eq!(batcher.queued(), 0);
+        let sent: Vec<serde_json::Value> = app
+            .rpc
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.body_json().unwrap())
+            .filter(|body: &serde_json::Value| body["method"] == "eth_sendTransaction")
+            .collect();
+        assert_eq!(sent.len(), 1);
+        let tx = &sent[0]["params"][0];
+        assert_eq!(tx["to"], BATCH_CONTRACT);
+        assert_eq!(tx["from"], fixtures::CAROL.to_lowercase());
+        assert_eq!(tx["nonce"], "0x3");
+        let data = tx["data"].as_str().unwrap();
+        for id in ["batch-a", "batch-b"] {
+            let commitment: String = services::settlement_batch::commitment(id)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            assert!(data.contains(&commitment));
+        }
+
+        // Both sessions share the transaction, and settle with it
+        for id in ["batch-a", "batch-b"] {
+            let session = app.state.session_store.get(id).await.unwrap();
+            assert_eq!(session.tx_hash.as_deref(), Some(BATCH_TX));
+        }
+        batcher.reconcile(1).await.unwrap();
+        for id in ["batch-a", "batch-b"] {
+            let body: serde_json::Value = app
+                .server
+                .get(&format!("/api/v1/session/{}", id))
+                .await
+                .json();
+            assert_eq!(body["session"]["status"], "settled");
+            assert_eq!(body["session"]["tx_hash"], BATCH_TX);
+            assert_eq!(body["session"]["payments"][0]["status"], "settled");
+        }
+    }
+
+    #[cfg(feature = "settlement")]
+    #[tokio::test]
+    async fn test_settlement_batch_partial_failure_settles_nothing() {
+        let app = spawn_batching_app(10).await;
+        // Mined, but only one of the two sessions was settled
+        app.stub_tx_receipt_with_logs(
+            BATCH_TX,
+            0x10,
+            0x12,
+            true,
+            json!([batch_settled_log("batch-a")]),
+        )
+        .await;
+        let batcher = app.state.settlement_batcher.clone().unwrap();
+        assert_eq!(batcher.queued(), 2);
+
+        assert_eq!(batcher.flush().await.unwrap().as_deref(), Some(BATCH_TX));
+        assert_eq!(batcher.queued(), 0);
+
+        // The transaction is mined deep enough, but settlement-status leaves
+        // the batch to the batcher's check of its logs
+        let body: serde_json::Value = app
+            .server
+            .get("/api/v1/session/batch-a/settlement-status")
+            .await
+            .json();
+        assert_eq!(body["tx_status"], "confirmed");
+        assert_eq!(body["session_status"], "pending");
+
+        batcher.reconcile(1).await.unwrap();
+        for id in ["batch-a", "batch-b"] {
+            let session = app.state.session_store.get(id).await.unwrap();
+            assert_eq!(session.status, models::session::SessionStatus::Pending);
+            assert_eq!(session.tx_hash, None);
+            assert!(session
+                .payments
+                .iter()
+                .all(|p| p.status == models::session::PaymentStatus::Pending));
+        }
+
+        // The sessions go out in the next batches, until they have failed
+        // MAX_BATCH_ATTEMPTS of them
+        use services::settlement_batch::MAX_BATCH_ATTEMPTS;
+        for failed in 1..=MAX_BATCH_ATTEMPTS {
+            let queued = if failed < MAX_BATCH_ATTEMPTS { 2 } else { 0 };
+            assert_eq!(batcher.queued(), queued);
+            if queued > 0 {
+                assert_eq!(batcher.flush().await.unwrap().as_deref(), Some(BATCH_TX));
+                batcher.reconcile(1).await.unwrap();
+            }
+        }
+        assert_eq!(batcher.flush().await.unwrap(), None);
+        let sent = app
+            .rpc
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|r| {
+                r.body_json::<serde_json::Value>().unwrap()["method"] == "eth_sendTransaction"
+            })
+            .count();
+        assert_eq!(sent, MAX_BATCH_ATTEMPTS as usize);
+
+        // Finalizing again queues a session afresh
+        app.server
+            .post("/api/v1/session/batch-a/finalize")
+            .json(&json!({}))
+            .await
+            .assert_status_ok();
+        assert_eq!(batcher.queued(), 1);
+    }
+
+    #[cfg(feature = "settlement")]
+    #[tokio::test]
+    async fn test_settlement_batch_sends_a_session_once() {
+        let app = spawn_batching_app(10).await;
+        let batcher = app.state.settlement_batcher.clone().unwrap();
+        // A queued session the client then sent a transaction for is left out
+        app.server
+            .post("/api/v1/session/batch-b/finalize")
+            .json(&json!({ "tx_hash": "0xabc123def456" }))
+            .await
+            .assert_status_ok();
+        assert_eq!(batcher.queued(), 2);
+        assert_eq!(batcher.flush().await.unwrap().as_deref(), Some(BATCH_TX));
+        let sent = app.rpc.received_requests().await.unwrap();
+        let data = sent
+            .iter()
+            .map(|r| r.body_json::<serde_json::Value>().unwrap())
+            .find(|body| body["method"] == "eth_sendTransaction")
+            .unwrap()["params"][0]["data"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let commitment = |id: &str| -> String {
+            services::settlement_batch::commitment(id)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect()
+        };
+        assert!(data.contains(&commitment("batch-a")));
+        assert!(!data.contains(&commitment("batch-b")));
+
+        // A retried finalize neither queues the session again nor sends it
+        let body: serde_json::Value = app
+            .server
+            .post("/api/v1/session/batch-a/finalize")
+            .json(&json!({}))
+            .await
+            .json();
+        assert_eq!(body["tx_hash"], BATCH_TX);
+        assert_eq!(batcher.queued(), 0);
+        assert_eq!(batcher.flush().await.unwrap(), None);
+        assert_eq!(sent_nonces(&app).await, [3]);
+    }
+
+    /// Nonces of the `eth_sendTransaction` calls the RPC mock received
+    #[cfg(feature = "settlement")]
+    async fn sent_nonces(app: &TestApp) -> Vec<u64> {
+        app.rpc
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.body_json::<serde_json::Value>().unwrap())
+            .filter(|body| body["method"] == "eth_sendTransaction")
+            .map(|body| {
+                let nonce = body["params"][0]["nonce"].as_str().unwrap();
+                u64::from_str_radix(nonce.trim_start_matches("0x"), 16).unwrap()
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "settlement")]
+    #[tokio::test]
+    async fn test_parallel_settlements_take_sequential_nonces() {
+        let app = TestApp::spawn_with(Config {
+            settlement_signer_address: Some(fixtures::address(fixtures::CAROL)),
+            ..Config::default()
+        })
+        .await;
+        app.stub_transaction_count(7).await;
+        app.stub_send_transaction(BATCH_TX).await;
+        let nonces = app.state.nonce_manager.clone().unwrap();
+        let contract = fixtures::address(BATCH_CONTRACT);
+
+        let sends: Vec<_> = (0..10u8)
+            .map(|i| {
+                let (nonces, contract) = (nonces.clone(), contract.clone());
+                tokio::spawn(async move { nonces.send(&contract, &[i]).await })
+            })
+            .collect();
+        for send in sends {
+            assert_eq!(send.await.unwrap().unwrap(), BATCH_TX);
+        }
+
+        // Each send got its own nonce, with none skipped
+        let mut sent = sent_nonces(&app).await;
+        sent.sort_unstable();
+        assert_eq!(sent, (7..17).collect::<Vec<u64>>());
+        assert_eq!(nonces.in_flight().await, sent);
+        // The chain counted them all
+        app.rpc.reset().await;
+        app.stub_transaction_count(17).await;
+        assert_eq!(nonces.reconcile().await.unwrap().next, 17);
+        assert!(nonces.in_flight().await.is_empty());
+    }
+
+    #[cfg(feature = "settlement")]
+    #[tokio::test]
+    async fn test_nonce_too_low_reconciles_and_resends() {
+        use wiremock::matchers::{body_partial_json, method};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let app = TestApp::spawn_with(Config {
+            settlement_signer_address: Some(fixtures::address(fixtures::CAROL)),
+            ..Config::default()
+        })
+        .await;
+        // The counter starts at 7, but two transactions were sent elsewhere
+        // before the first one of ours
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                json!({ "method": "eth_getTransactionCount" }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": "0x7",
+            })))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&app.rpc)
+            .await;
+        app.stub_transaction_count(9).await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({
+                "method": "eth_sendTransaction",
+                "params": [{ "nonce": "0x7" }],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "error": { "code": -32000, "message": "nonce too low" },
+            })))
+            .with_priority(1)
+            .mount(&app.rpc)
+            .await;
+        app.stub_send_transaction(BATCH_TX).await;
+        let nonces = app.state.nonce_manager.clone().unwrap();
+
+        let sent = nonces
+            .send(&fixtures::address(BATCH_CONTRACT), &[1])
+            .await
+            .unwrap();
+        assert_eq!(sent, BATCH_TX);
+        assert_eq!(sent_nonces(&app).await, [7, 9]);
+        assert_eq!(nonces.in_flight().await, [9]);
+        assert_eq!(nonces.allocate().await.unwrap(), 10);
+    }
+
+    #[cfg(feature = "settlement")]
+    #[tokio::test]
+    async fn test_reconcile_turns_dropped_transactions_into_gaps() {
+        use services::chain::Reconciled;
+        use wiremock::matchers::{body_partial_json, method};
+        use wiremock::{Mock, ResponseTemplate};
+
+        const DROPPED: &str = "0x00000000000000000000000000000000000000000000000000000000000d7007";
+        let app = TestApp::spawn_with(Config {
+            settlement_signer_address: Some(fixtures::address(fixtures::CAROL)),
+            ..Config::default()
+        })
+        .await;
+        app.stub_transaction_count(7).await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({
+                "method": "eth_sendTransaction",
+                "params": [{ "nonce": "0x7" }],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": DROPPED,
+            })))
+            .with_priority(1)
+            .mount(&app.rpc)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({
+                "method": "eth_getTransactionByHash",
+                "params": [DROPPED],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": { "hash": DROPPED },
+            })))
+            .mount(&app.rpc)
             .await;
+        app.stub_send_transaction(BATCH_TX).await;
+        let nonces = app.state.nonce_manager.clone().unwrap();
+        let contract = fixtures::address(BATCH_CONTRACT);
+        assert_eq!(nonces.send(&contract, &[1]).await.unwrap(), DROPPED);
+        assert_eq!(nonces.send(&contract, &[2]).await.unwrap(), BATCH_TX);
+
+        // While both are pending, reconciling changes nothing
+        let reconciled = nonces.reconcile().await.unwrap();
+        assert_eq!(
+            reconciled,
+            Reconciled {
+                next: 9,
+                dropped: vec![]
+            }
+        );
+        assert_eq!(nonces.in_flight().await, [7, 8]);
+
+        // The node loses the first from its mempool; 8 cannot be mined
+        // until another transaction takes 7
+        app.stub_dropped_transaction(DROPPED).await;
+        let reconciled = nonces.reconcile().await.unwrap();
+        assert_eq!(
+            reconciled,
+            Reconciled {
+                next: 9,
+                dropped: vec![DROPPED.to_string()]
+            }
+        );
+        assert_eq!(nonces.in_flight().await, [8]);
+        assert_eq!(nonces.send(&contract, &[3]).await.unwrap(), DROPPED);
+        assert_eq!(sent_nonces(&app).await, [7, 8, 7]);
+        assert_eq!(nonces.in_flight().await, [7, 8]);
+    }
+
+    #[cfg(feature = "settlement")]
+    #[tokio::test]
+    async fn test_settlement_batch_dropped_by_the_node_is_sent_again() {
+        let app = spawn_batching_app(10).await;
+        let batcher = app.state.settlement_batcher.clone().unwrap();
+        assert_eq!(batcher.flush().await.unwrap().as_deref(), Some(BATCH_TX));
+
+        app.stub_dropped_transaction(BATCH_TX).await;
+        batcher.reconcile(1).await.unwrap();
+        for id in ["batch-a", "batch-b"] {
+            let session = app.state.session_store.get(id).await.unwrap();
+            assert_eq!(session.status, models::session::SessionStatus::Pending);
+            assert_eq!(session.tx_hash, None);
+        }
+        assert_eq!(batcher.queued(), 2);
+
+        // The next batch takes the dropped transaction's nonce
+        assert_eq!(batcher.flush().await.unwrap().as_deref(), Some(BATCH_TX));
+        assert_eq!(sent_nonces(&app).await, [3, 3]);
+    }
+
+    #[cfg(feature = "settlement")]
+    #[tokio::test]
+    async fn test_settlement_preview_prices_gas_in_usd() {
+        let app = TestApp::spawn().await;
+        app.stub_gas_price(1_000_000_000).await;
+        let server = &app.server;
+
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x4444444444444444444444444444444444444444" }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap().to_string();
+        for recipient in [
+            "0x1111111111111111111111111111111111111111",
+            "0x2222222222222222222222222222222222222222",
+        ] {
+            server
+                .post(&format!("/api/v1/session/{}/payment", session_id))
+                .json(&json!({ "recipient": recipient, "amount": "1000000" }))
+                .await;
+        }
+        let path = format!("/api/v1/session/{}/settlement-preview", session_id);
 
+        // Without a gas token price the estimate still comes back
+        let response = server.get(&path).await;
         assert_eq!(response.status_code(), StatusCode::OK);
         let body: serde_json::Value = response.json();
-        // Should return a valid response structure (may have error if LI.FI is unreachable)
-        assert!(body["from_amount"].as_str().is_some());
+        assert_eq!(body["transfers"].as_array().unwrap().len(), 2);
+        assert_eq!(body["estimated_gas"], 130_000);
+        assert_eq!(body["gas_price_wei"], "1000000000");
+        assert_eq!(body["estimated_fee_wei"], "130000000000000");
+        assert!(body["estimated_fee_usd"].is_null());
+
+        // 0.00013 of a $2000 token
+        app.stub_native_price(2000.0).await;
+        let body: serde_json::Value = server.get(&path).await.json();
+        assert_eq!(body["estimated_fee_usd"], 0.26);
+
+        // The price is cached: only the failed and the first priced read
+        // asked the source
+        server.get(&path).await;
+        let price_requests = app
+            .rpc
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|r| r.url.path() == "/price")
+            .count();
+        assert_eq!(price_requests, 2);
+
+        let response = server
+            .get("/api/v1/session/missing/settlement-preview")
+            .await;
+        assert_error(&response, StatusCode::NOT_FOUND, "not_found");
+    }
+
+    #[cfg(feature = "settlement")]
+    #[tokio::test]
+    async fn test_payment_lifecycle_timestamps() {
+        let mut config = Config::default();
+        config.dynamic.settlement_min_confirmations = 3;
+        let app = TestApp::spawn_with(config).await;
+        let server = &app.server;
+
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x4444444444444444444444444444444444444444" }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap().to_string();
+        server
+            .post(&format!("/api/v1/session/{}/payment", session_id))
+            .json(&json!({ "recipient": "0x1111111111111111111111111111111111111111", "amount": "1000000" }))
+            .await;
+        let payment = || async {
+            let session: serde_json::Value = server
+                .get(&format!("/api/v1/session/{}", session_id))
+                .await
+                .json();
+            session["session"]["payments"][0].clone()
+        };
+        let check_status = || async {
+            server
+                .get(&format!("/api/v1/session/{}/settlement-status", session_id))
+                .await
+                .json::<serde_json::Value>()
+        };
+
+        let added = payment().await;
+        assert!(added["confirmed_at"].is_null());
+        assert!(added["settled_at"].is_null());
+
+        server
+            .post(&format!("/api/v1/session/{}/finalize", session_id))
+            .json(&json!({ "tx_hash": "0xabc123def456" }))
+            .await;
+
+        // Mined but not deep enough: confirmed, not settled
+        app.stub_tx_receipt("0xabc123def456", 0x10, 0x10, true)
+            .await;
+        assert_eq!(check_status().await["session_status"], "pending");
+        let confirmed = payment().await;
+        assert_eq!(confirmed["status"], "confirmed");
+        assert!(confirmed["confirmed_at"].is_string());
+        assert!(confirmed["settled_at"].is_null());
+
+        // Checking again keeps the first confirmation time
+        check_status().await;
+        assert_eq!(payment().await["confirmed_at"], confirmed["confirmed_at"]);
+
+        app.rpc.reset().await;
+        app.stub_tx_receipt("0xabc123def456", 0x10, 0x12, true)
+            .await;
+        assert_eq!(check_status().await["session_status"], "settled");
+        let settled = payment().await;
+        assert_eq!(settled["status"], "settled");
+        assert_eq!(settled["confirmed_at"], confirmed["confirmed_at"]);
+        assert!(settled["settled_at"].is_string());
+
+        check_status().await;
+        assert_eq!(payment().await["settled_at"], settled["settled_at"]);
+    }
+
+    #[cfg(feature = "settlement")]
+    #[tokio::test]
+    async fn test_settlement_status_reverted_tx_stays_pending() {
+        let app = TestApp::spawn().await;
+        app.stub_tx_receipt("0xabc123def456", 0x10, 0x12, false)
+            .await;
+        let server = &app.server;
+
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x4444444444444444444444444444444444444444" }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap().to_string();
+
+        // No transaction submitted yet
+        let response = server
+            .get(&format!("/api/v1/session/{}/settlement-status", session_id))
+            .await;
+        assert_error(&response, StatusCode::CONFLICT, "conflict");
+
+        server
+            .post(&format!("/api/v1/session/{}/finalize", session_id))
+            .json(&json!({ "tx_hash": "0xabc123def456" }))
+            .await;
+        let body: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}/settlement-status", session_id))
+            .await
+            .json();
+        assert_eq!(body["tx_status"], "failed");
+        assert_eq!(body["session_status"], "pending");
+    }
+
+    fn create_blocklist_server() -> TestServer {
+        let config = Config {
+            blocked_addresses: ["0xbad0000000000000000000000000000000000bad"
+                .parse()
+                .unwrap()]
+            .into_iter()
+            .collect(),
+            ..Config::default()
+        };
+        TestServer::new(create_app(create_test_state_with_config(config))).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_blocked_recipient_rejected() {
+        let server = create_blocklist_server();
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x1111111111111111111111111111111111111111" }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap();
+
+        // Matched case-insensitively
+        let response = server
+            .post(&format!("/api/v1/session/{}/payment", session_id))
+            .json(&json!({
+                "recipient": "0xBAD0000000000000000000000000000000000BAD",
+                "amount": "100"
+            }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["code"], "forbidden");
+        assert_eq!(
+            body["message"],
+            "Recipient address 0xBAd0000000000000000000000000000000000Bad is blocked"
+        );
+
+        let session: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}", session_id))
+            .await
+            .json();
+        assert!(session["session"]["payments"]
+            .as_array()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_recipient_address_rejected() {
+        let server = create_test_server();
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x4444444444444444444444444444444444444444" }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap();
+
+        for recipient in ["0xRecipient", "1111111111111111111111111111111111111111"] {
+            let response = server
+                .post(&format!("/api/v1/session/{}/payment", session_id))
+                .json(&json!({ "recipient": recipient, "amount": "100" }))
+                .await;
+            assert_error(
+                &response,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "unprocessable_entity",
+            );
+            let body: serde_json::Value = response.json();
+            assert_eq!(body["details"]["fields"][0]["field"], "recipient");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_user_address_is_validated_and_checksummed() {
+        let config = Config {
+            max_active_sessions_per_user: Some(1),
+            ..Config::default()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+
+        let response = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0xUser" }))
+            .await;
+        assert_error(
+            &response,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unprocessable_entity",
+        );
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["details"]["fields"][0]["field"], "user_address");
+
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed" }))
+            .await
+            .json();
+        let session: serde_json::Value = server
+            .get(&format!(
+                "/api/v1/session/{}",
+                created["session_id"].as_str().unwrap()
+            ))
+            .await
+            .json();
+        assert_eq!(
+            session["session"]["user"],
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+        );
+
+        // The checksummed form is the same user, so it counts against the cap
+        let response = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed" }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_amounts_are_256_bit_decimal_strings() {
+        let server = create_test_server();
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x4444444444444444444444444444444444444444" }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap();
+        let recipient = "0x1111111111111111111111111111111111111111";
+
+        // Two u128::MAX payments: the total is past u128 but still a string
+        let mut body = serde_json::Value::Null;
+        for _ in 0..2 {
+            body = server
+                .post(&format!("/api/v1/session/{}/payment", session_id))
+                .json(&json!({ "recipient": recipient, "amount": u128::MAX.to_string() }))
+                .await
+                .json();
+        }
+        assert_eq!(
+            body["session"]["total_amount"],
+            "680564733841876926926749214863536422910"
+        );
+
+        for amount in [json!("1.5"), json!("-1"), json!(""), json!(100)] {
+            let response = server
+                .post(&format!("/api/v1/session/{}/payment", session_id))
+                .json(&json!({ "recipient": recipient, "amount": amount }))
+                .await;
+            assert_error(
+                &response,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "unprocessable_entity",
+            );
+            let body: serde_json::Value = response.json();
+            assert_eq!(body["details"]["fields"][0]["field"], "amount");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_human_amount_uses_session_token_decimals() {
+        let server = create_test_server();
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x4444444444444444444444444444444444444444", "token_decimals": 6 }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap();
+        let path = format!("/api/v1/session/{}/payment", session_id);
+        let recipient = "0x1111111111111111111111111111111111111111";
+
+        let body: serde_json::Value = server
+            .post(&path)
+            .json(&json!({ "recipient": recipient, "human_amount": "12.5" }))
+            .await
+            .json();
+        assert_eq!(body["session"]["payments"][0]["amount"], "12500000");
+
+        // Both given and consistent
+        let body: serde_json::Value = server
+            .post(&path)
+            .json(&json!({ "recipient": recipient, "amount": "10000", "human_amount": "0.01" }))
+            .await
+            .json();
+        assert_eq!(body["session"]["payments"][1]["amount"], "10000");
+        assert_eq!(body["session"]["total_amount"], "12510000");
+
+        // Both given and inconsistent
+        let response = server
+            .post(&path)
+            .json(&json!({ "recipient": recipient, "amount": "12500000", "human_amount": "12.6" }))
+            .await;
+        assert_error(
+            &response,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unprocessable_entity",
+        );
+
+        // Too precise for the token, or not decimal notation
+        for human in ["0.0000001", "1e6", "-1"] {
+            let response = server
+                .post(&path)
+                .json(&json!({ "recipient": recipient, "human_amount": human }))
+                .await;
+            assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+            let body: serde_json::Value = response.json();
+            assert_eq!(body["details"]["fields"][0]["field"], "human_amount");
+        }
+
+        // Neither given
+        let response = server
+            .post(&path)
+            .json(&json!({ "recipient": recipient }))
+            .await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["details"]["fields"][0]["field"], "amount");
+
+        let response = server
+            .post("/api/v1/session/missing/payment")
+            .json(&json!({ "recipient": recipient, "human_amount": "1" }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_blocked_user_cannot_create_session() {
+        let server = create_blocklist_server();
+        let response = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0xbad0000000000000000000000000000000000bad" }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+        assert!(response.json::<serde_json::Value>()["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("User address"));
+    }
+
+    #[tokio::test]
+    async fn test_allowed_address_passes_blocklist() {
+        let server = create_blocklist_server();
+        let created = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x1111111111111111111111111111111111111111" }))
+            .await;
+        assert_eq!(created.status_code(), StatusCode::CREATED);
+        let session_id = created.json::<serde_json::Value>()["session_id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        server
+            .post(&format!("/api/v1/session/{}/payment", session_id))
+            .json(&json!({
+                "recipient": "0x2222222222222222222222222222222222222222",
+                "amount": "100000"
+            }))
+            .await
+            .assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn test_cancel_session_records_reason() {
+        let server = create_authenticated_server();
+        let mut session_ids = Vec::new();
+        for _ in 0..3 {
+            let created: serde_json::Value = server
+                .post("/api/v1/session")
+                .add_header("x-api-key", "client-key")
+                .json(&json!({ "user_address": "0x4444444444444444444444444444444444444444" }))
+                .await
+                .json();
+            session_ids.push(created["session_id"].as_str().unwrap().to_string());
+        }
+
+        let response = server
+            .post(&format!("/api/v1/session/{}/cancel", session_ids[0]))
+            .add_header("x-api-key", "client-key")
+            .json(&json!({ "reason": "  changed my mind " }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["session"]["status"], "cancelled");
+        assert_eq!(body["session"]["cancel_reason"], "changed my mind");
+
+        // The reason is optional, and capped in length
+        let response = server
+            .post(&format!("/api/v1/session/{}/cancel", session_ids[1]))
+            .add_header("x-api-key", "client-key")
+            .json(&json!({ "reason": "x".repeat(201) }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+        let response = server
+            .post(&format!("/api/v1/session/{}/cancel", session_ids[1]))
+            .add_header("x-api-key", "client-key")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert!(response.json::<serde_json::Value>()["session"]
+            .get("cancel_reason")
+            .is_none());
+
+        // Already cancelled
+        let response = server
+            .post(&format!("/api/v1/session/{}/cancel", session_ids[1]))
+            .add_header("x-api-key", "client-key")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::CONFLICT);
+
+        let stats: serde_json::Value = server
+            .get("/admin/stats")
+            .add_header("x-api-key", "admin-key")
+            .await
+            .json();
+        assert_eq!(
+            stats["cancelled_by_reason"],
+            json!({ "changed my mind": 1, "unspecified": 1 })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expiry_sweep_cancels_with_reason() {
+        let state = create_test_state();
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let session = SessionBuilder::new()
+            .payment(fixtures::ALICE, "100")
+            .insert_into(&state.session_store)
+            .await;
+
+        // Not old enough yet
+        assert_eq!(
+            state
+                .session_store
+                .sweep_expired(Duration::from_secs(3600))
+                .await,
+            0
+        );
+        assert_eq!(state.session_store.sweep_expired(Duration::ZERO).await, 1);
+
+        let body: serde_json::Value = server
+            .get(&format!("/api/v1/session/{}", session.id))
+            .await
+            .json();
+        assert_eq!(body["session"]["status"], "cancelled");
+        assert_eq!(body["session"]["cancel_reason"], "expired");
+        assert_eq!(
+            state.session_store.cancel_reason_counts().await["expired"],
+            1
+        );
+    }
+
+    /// A settled session created `age` ago
+    fn settled_session(id: &str, age: chrono::Duration) -> models::session::Session {
+        SessionBuilder::new()
+            .id(id)
+            .status(SessionStatus::Settled)
+            .created_ago(age)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_archive_settled_removes_old_sessions() {
+        let state = create_test_state();
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let store = &state.session_store;
+        SessionBuilder::new()
+            .id("old-active")
+            .created_days_ago(30)
+            .insert_into(store)
+            .await;
+        SessionBuilder::new()
+            .id("old-settled")
+            .payment(fixtures::ALICE, "100")
+            .status(SessionStatus::Settled)
+            .created_days_ago(30)
+            .insert_into(store)
+            .await;
+        store
+            .restore(vec![settled_session(
+                "new-settled",
+                chrono::Duration::minutes(5),
+            )])
+            .await;
+        assert_eq!(store.payment_count(), 1);
+
+        let archived = store.archive_settled(Duration::from_secs(86400)).await;
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].id, "old-settled");
+        assert_eq!(store.len().await, 2);
+        assert!(store.get("old-settled").await.is_none());
+        assert!(store.get("new-settled").await.is_some());
+        assert_eq!(store.payment_count(), 0);
+
+        let response = server.get("/api/v1/session/old-settled").await;
+        assert_error(&response, StatusCode::GONE, "gone");
+        let response = server.get("/api/v1/session/old-settled/payments").await;
+        assert_error(&response, StatusCode::GONE, "gone");
+        let response = server.get("/api/v1/session/never-existed").await;
+        assert_error(&response, StatusCode::NOT_FOUND, "not_found");
+
+        // Restoring an archived session brings it back
+        store.restore(archived).await;
+        let response = server.get("/api/v1/session/old-settled").await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_archive_job_appends_sessions_to_file() {
+        use crate::services::jobs::Job;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archive.jsonl");
+        let store = Arc::new(services::session::SessionStore::new());
+        store
+            .restore(vec![
+                settled_session("a", chrono::Duration::days(2)),
+                settled_session("b", chrono::Duration::days(3)),
+            ])
+            .await;
+        let job = services::session::SessionArchiveJob {
+            store: store.clone(),
+            older_than: Duration::from_secs(86400),
+            path: Some(path.clone()),
+        };
+        let ctx = services::jobs::JobContext {
+            shutdown: CancellationToken::new(),
+        };
+
+        job.run(&ctx).await.unwrap();
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["id"], "b");
+        assert_eq!(lines[1]["id"], "a");
+        assert_eq!(store.len().await, 0);
+
+        // An unwritable archive keeps the sessions live
+        store
+            .restore(vec![settled_session("c", chrono::Duration::days(2))])
+            .await;
+        let job = services::session::SessionArchiveJob {
+            path: Some(dir.path().join("missing/archive.jsonl")),
+            ..job
+        };
+        assert!(job.run(&ctx).await.is_err());
+        assert!(store.get("c").await.is_some());
+        assert!(!store.is_archived("c").await);
+    }
+
+    // ── ENS Routes ────────────────────────────────────
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_ens_resolve_invalid_name() {
+        let server = create_test_server();
+        let response = server.get("/api/ens/resolve?name=invalid.com").await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert!(body["error"].as_str().is_some());
+        assert!(body["address"].is_null());
+
+        // Names that are not even shaped like one are rejected on extraction
+        let response = server.get("/api/ens/resolve?name=invalid").await;
+        assert_error(
+            &response,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unprocessable_entity",
+        );
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["details"]["fields"][0]["field"], "name");
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_ens_lookup_returns_response() {
+        let app = TestApp::spawn().await;
+        let response = app
+            .server
+            .get("/api/ens/lookup?address=0x0000000000000000000000000000000000000000")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        // Should return a valid response structure even if no name found
+        assert_eq!(
+            body["address"],
+            "0x0000000000000000000000000000000000000000"
+        );
+        assert!(body["name"].is_null());
+
+        app.stub_ens_reverse("0x1111111111111111111111111111111111111111", "stub.eth")
+            .await;
+        let body: serde_json::Value = app
+            .server
+            .get("/api/ens/lookup?address=0x1111111111111111111111111111111111111111")
+            .await
+            .json();
+        assert_eq!(body["name"], "stub.eth");
+        assert!(body["avatar"].is_null());
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_ens_lookup_returns_avatar_from_reverse_call() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let app = TestApp::spawn().await;
+        let address = "0x1111111111111111111111111111111111111111";
+        Mock::given(method("GET"))
+            .and(path(format!("/{}", address)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "ens": "pic.eth",
+                "avatar": "ipfs://QmYwAPJzv5CZsnA/avatar.png",
+            })))
+            .expect(1)
+            .mount(&app.ens)
+            .await;
+
+        // The second lookup answers from the reverse cache, avatar included
+        for _ in 0..2 {
+            let body: serde_json::Value = app
+                .server
+                .get(&format!("/api/v1/ens/lookup?address={}", address))
+                .await
+                .json();
+            assert_eq!(body["name"], "pic.eth");
+            assert_eq!(
+                body["avatar"],
+                "https://ipfs.io/ipfs/QmYwAPJzv5CZsnA/avatar.png"
+            );
+        }
+        assert_eq!(app.ens.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_ens_resolve_returns_stubbed_address() {
+        let app = TestApp::spawn().await;
+        app.stub_ens_resolution("stub.eth", "0x2222222222222222222222222222222222222222")
+            .await;
+
+        let response = app.server.get("/api/v1/ens/resolve?name=stub.eth").await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["address"],
+            "0x2222222222222222222222222222222222222222"
+        );
+        assert_eq!(body["cached"], false);
+        assert_eq!(body["age_secs"], 0);
+
+        let body: serde_json::Value = app
+            .server
+            .get("/api/v1/ens/resolve?name=stub.eth")
+            .await
+            .json();
+        assert_eq!(body["cached"], true);
+
+        // Later lookups are served from the cache
+        let cached = app
+            .state
+            .ens_service
+            .resolve(&"stub.eth".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            cached.address.to_string(),
+            "0x2222222222222222222222222222222222222222"
+        );
+        assert_eq!(app.ens.received_requests().await.unwrap().len(), 1);
+
+        let response = app.server.get("/api/v1/ens/resolve?name=missing.eth").await;
+        assert_error(&response, StatusCode::NOT_FOUND, "not_found");
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_ens_batch_falls_back_to_one_subgraph_query() {
+        use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+        let subgraph = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": { "domains": [
+                    { "name": "down.eth", "resolvedAddress": { "id": "0x4444444444444444444444444444444444444444" } },
+                ] }
+            })))
+            .mount(&subgraph)
+            .await;
+        let app = TestApp::spawn_with(Config {
+            ens_subgraph_url: Some(subgraph.uri()),
+            ..Config::default()
+        })
+        .await;
+        app.stub_ens_resolution("live.eth", "0x2222222222222222222222222222222222222222")
+            .await;
+        // The API fails for every other name
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&app.ens)
+            .await;
+
+        let response = app
+            .server
+            .post("/api/v1/ens/resolve/batch")
+            .json(&json!({ "names": ["live.eth", "down.eth", "gone.eth", "ab.eth"] }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let results = response.json::<serde_json::Value>()["results"].clone();
+        assert_eq!(
+            results[0]["address"],
+            "0x2222222222222222222222222222222222222222"
+        );
+        assert_eq!(
+            results[1]["address"],
+            "0x4444444444444444444444444444444444444444"
+        );
+        // Missing from the subgraph too, and invalid without any lookup
+        assert_eq!(results[2]["address"], serde_json::Value::Null);
+        assert!(results[2]["error"].as_str().unwrap().contains("not found"));
+        assert!(results[3]["error"].as_str().unwrap().contains("Invalid"));
+
+        // Both failed names went out in a single query
+        let queries = subgraph.received_requests().await.unwrap();
+        assert_eq!(queries.len(), 1);
+        let query: serde_json::Value = queries[0].body_json().unwrap();
+        assert_eq!(query["variables"]["names"], json!(["down.eth", "gone.eth"]));
+
+        // The subgraph answer is cached like any other
+        let body: serde_json::Value = app
+            .server
+            .get("/api/v1/ens/resolve?name=down.eth")
+            .await
+            .json();
+        assert_eq!(body["cached"], true);
+
+        for names in [json!([]), json!(vec!["name.eth"; 51])] {
+            let response = app
+                .server
+                .post("/api/v1/ens/resolve/batch")
+                .json(&json!({ "names": names }))
+                .await;
+            assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+        }
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_ens_namehash() {
+        let server = create_test_server();
+
+        let body: serde_json::Value = server.get("/api/v1/ens/namehash?name=eth").await.json();
+        assert_eq!(
+            body["namehash"],
+            "0x93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae"
+        );
+
+        let response = server.get("/api/ens/namehash?name=Vitalik.ETH.").await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["name"], "Vitalik.ETH.");
+        assert_eq!(body["normalized"], "vitalik.eth");
+        assert_eq!(
+            body["namehash"],
+            "0xee6c4522aab0003e8d14cd40a6af439055fd2577951148c14b6cea9a53475835"
+        );
+
+        for invalid in ["a..eth", "", "xn--a.eth"] {
+            let response = server
+                .get("/api/v1/ens/namehash")
+                .add_query_param("name", invalid)
+                .await;
+            assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+        }
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_ens_content_hash() {
+        let app = TestApp::spawn().await;
+        app.stub_ens_content_hash(
+            "0x4976fb03C32e5B8cfe2b6cCB31c09Ba78EBaBa41",
+            "e3010170122029f2d17be6139079dc48696d1f582a8530eb9805b561eda517e22a892c7e3f1f",
+        )
+        .await;
+
+        let response = app.server.get("/api/ens/contenthash?name=alice.eth").await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["name"], "alice.eth");
+        assert_eq!(
+            body["contenthash"],
+            "ipfs://QmRAQB6YaCyidP37UdDnjFY5vQuiBrcqdyoW1CuDgwxkD4"
+        );
+        // Both calls are keyed by the name's namehash
+        let requests = app.rpc.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        let node = services::ens::namehash("alice.eth");
+        for request in &requests {
+            let body: serde_json::Value = request.body_json().unwrap();
+            let data = body["params"][0]["data"].as_str().unwrap();
+            assert!(data.ends_with(node.trim_start_matches("0x")), "{}", data);
+        }
+
+        let response = app.server.get("/api/v1/ens/contenthash?name=ab.eth").await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_ens_content_hash_unset() {
+        // A resolver without a record
+        let app = TestApp::spawn().await;
+        app.stub_ens_content_hash("0x4976fb03C32e5B8cfe2b6cCB31c09Ba78EBaBa41", "")
+            .await;
+        let body: serde_json::Value = app
+            .server
+            .get("/api/v1/ens/contenthash?name=alice.eth")
+            .await
+            .json();
+        assert_eq!(body["contenthash"], serde_json::Value::Null);
+
+        // No resolver at all: the second call is never made
+        let app = TestApp::spawn().await;
+        app.stub_ens_content_hash("0x0000000000000000000000000000000000000000", "e301")
+            .await;
+        let body: serde_json::Value = app
+            .server
+            .get("/api/v1/ens/contenthash?name=alice.eth")
+            .await
+            .json();
+        assert_eq!(body["contenthash"], serde_json::Value::Null);
+        assert_eq!(app.rpc.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_ens_resolve_accepts_address_field_variants() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let app = TestApp::spawn().await;
+        let address = "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed";
+        for (name, body) in [
+            ("plain.eth", json!({ "address": address })),
+            ("flat.eth", json!({ "eth_address": address })),
+            ("nested.eth", json!({ "addresses": { "eth": address } })),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(format!("/{}", name)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&app.ens)
+                .await;
+
+            let response = app
+                .server
+                .get(&format!("/api/v1/ens/resolve?name={}", name))
+                .await;
+            assert_eq!(response.status_code(), StatusCode::OK, "{}", name);
+            let body: serde_json::Value = response.json();
+            assert_eq!(
+                body["address"], "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_finalize_verify_ens_rejects_changed_records() {
+        let app = TestApp::spawn().await;
+        app.stub_ens_resolution(fixtures::ALICE_ENS, fixtures::ALICE)
+            .await;
+        app.stub_ens_resolution(fixtures::BOB_ENS, fixtures::BOB)
+            .await;
+        let session = || {
+            SessionBuilder::new()
+                .ens_payment(fixtures::ALICE, fixtures::ALICE_ENS, "1000000")
+                .ens_payment(fixtures::BOB, fixtures::BOB_ENS, "200000")
+                .payment(fixtures::CAROL, "30000")
+                .insert_into(&app.state.session_store)
+        };
+        let finalize = |id: String| {
+            app.server
+                .post(&format!("/api/v1/session/{}/finalize", id))
+                .json(&json!({ "verify_ens": true }))
+        };
+
+        // Records unchanged: finalize proceeds
+        let unchanged = session().await;
+        let response = finalize(unchanged.id.clone()).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(response.json::<serde_json::Value>()["status"], "pending");
+
+        // alice.eth moved to Carol and bob.eth was released; the cached
+        // answers from the first finalize must not hide it
+        app.ens.reset().await;
+        app.stub_ens_resolution(fixtures::ALICE_ENS, fixtures::CAROL)
+            .await;
+        let changed = session().await;
+        let response = finalize(changed.id.clone()).await;
+        assert_error(&response, StatusCode::CONFLICT, "conflict");
+        let message = response.json::<serde_json::Value>()["message"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert!(
+            message.contains(&format!(
+                "alice.eth (paid to {}) now resolves to {}",
+                fixtures::ALICE,
+                fixtures::CAROL
+            )),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains(&format!(
+                "bob.eth (paid to {}) no longer resolves",
+                fixtures::BOB
+            )),
+            "{}",
+            message
+        );
+        let stored = app.state.session_store.get(&changed.id).await.unwrap();
+        assert_eq!(stored.status, SessionStatus::Active);
+
+        // Without the flag the records are not checked
+        let response = app
+            .server
+            .post(&format!("/api/v1/session/{}/finalize", changed.id))
+            .json(&json!({}))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_ens_equivalent_names_share_cache_entry() {
+        let app = TestApp::spawn().await;
+        app.stub_ens_resolution("alice.eth", "0x3333333333333333333333333333333333333333")
+            .await;
+
+        let first = app
+            .state
+            .ens_service
+            .resolve(&"Alice.eth".parse().unwrap())
+            .await
+            .unwrap();
+        assert!(first.cache_age.is_none());
+        let second = app
+            .state
+            .ens_service
+            .resolve(&"alice.eth.".parse().unwrap())
+            .await
+            .unwrap();
+        assert!(second.cache_age.is_some());
+        assert_eq!(second.address, first.address);
+
+        assert_eq!(app.ens.received_requests().await.unwrap().len(), 1);
+        assert_eq!(app.state.ens_service.cache_sizes().await.0, 1);
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_ens_resolve_invalid_name_strict() {
+        let server = create_strict_test_server();
+        let response = server.get("/api/ens/resolve?name=invalid.com").await;
+
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["code"], "validation_error");
+        assert_eq!(body["details"]["fields"][0]["field"], "name");
+        assert!(!body["request_id"].as_str().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_ens_lookup_invalid_address_strict() {
+        let server = create_strict_test_server();
+        let response = server.get("/api/ens/lookup?address=not-an-address").await;
+
+        assert_error(
+            &response,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unprocessable_entity",
+        );
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["details"]["fields"][0]["field"], "address");
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_ens_resolve_allow_stale_refreshes_in_background() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // Mock ENS API: the name moves to a new address after the first lookup
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/:name",
+            get(move || {
+                let counter = counter.clone();
+                async move {
+                    let address = match counter.fetch_add(1, Ordering::SeqCst) {
+                        0 => "0x1111111111111111111111111111111111111111",
+                        _ => "0x2222222222222222222222222222222222222222",
+                    };
+                    axum::Json(json!({ "address": address }))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let mut state = create_test_state();
+        state.ens_service =
+            Arc::new(EnsService::with_api_url(&url).with_cache_ttl(Duration::from_millis(200)));
+        let server = TestServer::new(create_app(state)).unwrap();
+        let resolve = |query: &'static str| {
+            let request = server.get(&format!("/api/v1/ens/resolve?name=stale.eth{}", query));
+            async move { request.await.json::<serde_json::Value>()["address"].clone() }
+        };
+
+        assert_eq!(
+            resolve("").await,
+            "0x1111111111111111111111111111111111111111"
+        );
+        tokio::time::sleep(Duration::from_millis(250)).await;
+
+        // Expired entry: served stale, refreshed asynchronously
+        assert_eq!(
+            resolve("&allow_stale=true").await,
+            "0x1111111111111111111111111111111111111111"
+        );
+        for _ in 0..50 {
+            if calls.load(Ordering::SeqCst) >= 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        // The refreshed entry is fresh again and served from cache
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(
+            resolve("&allow_stale=true").await,
+            "0x2222222222222222222222222222222222222222"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Without the flag an expired entry is re-resolved before answering
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(
+            resolve("").await,
+            "0x2222222222222222222222222222222222222222"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    // ── Error Envelope ────────────────────────────────
+
+    #[tokio::test]
+    async fn test_error_envelope_echoes_request_id() {
+        let server = create_test_server();
+        let response = server
+            .get("/api/session/nonexistent")
+            .add_header(
+                axum::http::HeaderName::from_static("x-request-id"),
+                axum::http::HeaderValue::from_static("client-req-1"),
+            )
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(response.header("x-request-id"), "client-req-1");
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["request_id"], "client-req-1");
+        assert!(body["message"].as_str().unwrap().contains("nonexistent"));
+    }
+
+    #[tokio::test]
+    async fn test_handler_panic_returns_json_500() {
+        let server = create_test_server();
+        let response = server
+            .get("/__test/panic")
+            .add_header(
+                axum::http::HeaderName::from_static("x-request-id"),
+                axum::http::HeaderValue::from_static("panic-req-1"),
+            )
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.header("x-request-id"), "panic-req-1");
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body,
+            json!({
+                "code": "internal_panic",
+                "message": "Internal server error",
+                "details": null,
+                "request_id": "panic-req-1",
+            })
+        );
+        assert!(!response.text().contains("secret detail"));
+
+        // The server keeps serving after a panic
+        server.get("/health").await.assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn test_panics_and_server_errors_are_reported() {
+        let reporter = testing::CapturingReporter::default();
+        let mut state = create_test_state();
+        state.reporter = Arc::new(reporter.clone());
+        let server = TestServer::new(create_app(state)).unwrap();
+
+        server
+            .get("/__test/panic")
+            .add_header(
+                axum::http::HeaderName::from_static("x-request-id"),
+                axum::http::HeaderValue::from_static("panic-req-2"),
+            )
+            .await;
+        // Client errors are not reported
+        server.get("/api/v1/session/nonexistent").await;
+
+        let events = reporter.events();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.kind, reporting::ErrorKind::Panic);
+        assert_eq!(event.message, "panicked: test panic with secret detail");
+        assert_eq!(event.request_id.as_deref(), Some("panic-req-2"));
+        assert_eq!(event.route.as_deref(), Some("/__test/panic"));
+        assert_eq!(event.context["method"], "GET");
+        assert_eq!(event.context["status"], "500");
+        assert_eq!(event.context["code"], "internal_panic");
+    }
+
+    #[tokio::test]
+    async fn test_wrong_method_returns_405_envelope() {
+        let server = create_test_server();
+        let response = server.delete("/api/session").await;
+
+        assert_error(
+            &response,
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method_not_allowed",
+        );
+        assert_eq!(response.header("allow"), "POST");
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["message"], "DELETE is not allowed on /api/session");
+        assert!(!body["request_id"].as_str().unwrap().is_empty());
+
+        let response = server.post("/api/v1/session/abc").await;
+        assert_eq!(response.status_code(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.header("allow"), "GET,HEAD");
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflight_for_allowed_origin() {
+        let config = Config {
+            dynamic: DynamicConfig {
+                cors_allowed_origins: vec!["https://app.example.com".to_string()],
+                ..DynamicConfig::default()
+            },
+            ..Config::default()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+        let preflight = |origin: &'static str| {
+            server
+                .method(axum::http::Method::OPTIONS, "/api/v1/session")
+                .add_header(
+                    axum::http::header::ORIGIN,
+                    axum::http::HeaderValue::from_static(origin),
+                )
+                .add_header(
+                    axum::http::header::ACCESS_CONTROL_REQUEST_METHOD,
+                    axum::http::HeaderValue::from_static("POST"),
+                )
+                .add_header(
+                    axum::http::header::ACCESS_CONTROL_REQUEST_HEADERS,
+                    axum::http::HeaderValue::from_static("content-type,x-api-key"),
+                )
+        };
+
+        let response = preflight("https://app.example.com").await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(
+            response.header("access-control-allow-origin"),
+            "https://app.example.com"
+        );
+        assert_eq!(response.header("access-control-allow-methods"), "*");
+        assert_eq!(response.header("access-control-allow-headers"), "*");
+        assert_eq!(response.header("access-control-max-age"), "3600");
+
+        // Exposed headers ride on the actual response, not the preflight
+        let response = server
+            .get("/health")
+            .add_header(
+                axum::http::header::ORIGIN,
+                axum::http::HeaderValue::from_static("https://app.example.com"),
+            )
+            .await;
+        assert_eq!(
+            response.header("access-control-expose-headers"),
+            "x-request-id,retry-after"
+        );
+
+        // Other origins get no CORS grant
+        let response = preflight("https://evil.example.com").await;
+        assert!(!response
+            .headers()
+            .contains_key("access-control-allow-origin"));
+    }
+
+    // ── Body Logging ──────────────────────────────────
+
+    #[tokio::test]
+    async fn test_log_bodies_redacts_secrets() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let capture = crate::testing::Capture::default();
+        let subscriber =
+            tracing_subscriber::registry().with(crate::logging::json_layer(capture.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let config = Config {
+            log_bodies: true,
+            ..Config::default()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+        let response = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x8888888888888888888888888888888888888888", "signature": "0xdeadbeef" }))
+            .await;
+        // Rejected as an unknown field, but logged all the same
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+
+        let output = capture.output();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        let body = |message: &str| {
+            lines
+                .iter()
+                .find(|line| line["message"] == message)
+                .and_then(|line| line["body"].as_str())
+                .unwrap_or_else(|| panic!("no {} line in {}", message, output))
+                .to_string()
+        };
+        let request = body("request body");
+        assert!(request.contains("0x8888888888888888888888888888888888888888"));
+        assert!(request.contains("[REDACTED]"));
+        assert!(body("response body").contains("unknown field"));
+        assert!(!output.contains("0xdeadbeef"));
+
+        // Off by default
+        let logged = output.len();
+        let server = create_test_server();
+        server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x0000000000000000000000000000000000000104" }))
+            .await
+            .assert_status(StatusCode::CREATED);
+        assert!(!capture.output()[logged..].contains("request body"));
+    }
+
+    // ── Slow Requests ─────────────────────────────────
+
+    #[tokio::test]
+    async fn test_slow_requests_are_logged_with_upstream_breakdown() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let capture = crate::testing::Capture::default();
+        let subscriber =
+            tracing_subscriber::registry().with(crate::logging::json_layer(capture.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut config = Config::default();
+        config.dynamic.slow_request_threshold = std::time::Duration::from_millis(20);
+        let server =
+            TestServer::new(create_app(create_test_state_with_config(config.clone()))).unwrap();
+        let warnings = || -> Vec<serde_json::Value> {
+            capture
+                .output()
+                .lines()
+                .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+                .filter(|line| line["message"] == "slow request")
+                .collect()
+        };
+
+        server.get("/health").await;
+        assert!(warnings().is_empty());
+
+        let response = server.get("/__test/slow").await;
+        response.assert_status_ok();
+        let warning = warnings().pop().expect("slow request warning");
+        assert_eq!(warning["route"], "/__test/slow");
+        assert_eq!(
+            warning["request_id"].as_str().unwrap(),
+            response.header("x-request-id").to_str().unwrap()
+        );
+        assert!(warning["latency_ms"].as_u64().unwrap() >= 50);
+        assert_eq!(warning["threshold_ms"], 20);
+        assert_eq!(warning["upstream_calls"], "");
+
+        // Time spent upstream is broken down by provider
+        #[cfg(feature = "lifi")]
+        {
+            use wiremock::matchers::{method, path};
+            use wiremock::{Mock, ResponseTemplate};
+
+            let app = TestApp::spawn_with(config).await;
+            Mock::given(method("GET"))
+                .and(path("/quote"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(json!({ "estimate": { "toAmount": "1" } }))
+                        .set_delay(std::time::Duration::from_millis(50)),
+                )
+                .mount(&app.lifi)
+                .await;
+            app.server
+                .get("/api/v1/quote?from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount=1")
+                .await;
+            let warning = warnings().pop().expect("slow quote warning");
+            assert_eq!(warning["route"], "/api/v1/quote");
+            let upstream = warning["upstream_calls"].as_str().unwrap();
+            assert!(upstream.starts_with("lifi=1x/"), "{}", upstream);
+        }
+    }
+
+    // ── Metrics ───────────────────────────────────────
+
+    #[tokio::test]
+    async fn test_metrics_endpoint_exposes_series() {
+        let server = create_test_server();
+
+        server.get("/health").await;
+        server
+            .post("/api/session")
+            .json(&json!({
+                "user_address": "0x0000000000000000000000000000000000000113"
+            }))
+            .await;
+        server.get("/api/session/missing-for-metrics").await;
+
+        let response = server.get("/metrics").await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body = response.text();
+
+        assert!(body.contains("http_requests_total"));
+        assert!(body.contains("route=\"/api/session/:id\""));
+        assert!(body.contains("status=\"4xx\""));
+        assert!(body.contains("http_request_duration_seconds_bucket"));
+        assert!(body.contains("http_requests_in_flight"));
+        assert!(body.contains("sessions_created_total"));
+        // Raw paths must never leak into labels
+        assert!(!body.contains("missing-for-metrics"));
+        // Liveness probes are counted but not timed
+        assert!(
+            !body.contains("http_request_duration_seconds_bucket{method=\"GET\",route=\"/health\"")
+        );
+    }
+
+    // ── API Versions ──────────────────────────────────
+
+    #[tokio::test]
+    async fn test_v1_create_session_returns_created() {
+        let server = create_test_server();
+        let user = json!({ "user_address": "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045" });
+
+        let v1 = server.post("/api/v1/session").json(&user).await;
+        assert_eq!(v1.status_code(), StatusCode::CREATED);
+        assert!(v1.maybe_header("deprecation").is_none());
+
+        let legacy = server.post("/api/session").json(&user).await;
+        assert_eq!(legacy.status_code(), StatusCode::OK);
+        assert_eq!(legacy.header("deprecation"), "true");
+        assert!(legacy.maybe_header("sunset").is_some());
+
+        // Both trees share the same store
+        let session_id = v1.json::<serde_json::Value>()["session_id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let get_resp = server.get(&format!("/api/session/{}", session_id)).await;
+        assert_eq!(get_resp.status_code(), StatusCode::OK);
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_v1_ens_resolve_is_strict() {
+        let server = create_test_server();
+
+        let v1 = server.get("/api/v1/ens/resolve?name=invalid.com").await;
+        assert_eq!(v1.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(v1.json::<serde_json::Value>()["code"], "validation_error");
+
+        let legacy = server.get("/api/ens/resolve?name=invalid.com").await;
+        assert_eq!(legacy.status_code(), StatusCode::OK);
+        assert!(legacy.json::<serde_json::Value>()["error"].is_string());
+    }
+
+    #[cfg(feature = "lifi")]
+    #[tokio::test]
+    async fn test_v1_quote_upstream_failure_is_strict() {
+        let config = Config {
+            lifi_api_url: unreachable_url().await,
+            ..Config::default()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+        let query =
+            "from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount=1000000";
+
+        // Unreachable, as opposed to answering with an error
+        let v1 = server.get(&format!("/api/v1/quote?{}", query)).await;
+        assert_error(&v1, StatusCode::BAD_GATEWAY, "upstream_unreachable");
+
+        let legacy = server.get(&format!("/api/quote?{}", query)).await;
+        assert_eq!(legacy.status_code(), StatusCode::OK);
+        let body: serde_json::Value = legacy.json();
+        assert_eq!(body["to_amount"], "0");
+        assert!(body["error"].is_string());
+    }
+
+    // ── Circuit Breaker ───────────────────────────────
+
+    #[cfg(feature = "lifi")]
+    /// Mock upstream answering every request with 503
+    async fn spawn_failing_upstream() -> String {
+        let app = Router::new().fallback(|| async { StatusCode::SERVICE_UNAVAILABLE });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[cfg(feature = "lifi")]
+    #[tokio::test]
+    async fn test_quote_circuit_opens_and_fails_fast() {
+        let config = Config {
+            lifi_api_url: spawn_failing_upstream().await,
+            circuit_breaker_threshold: 2,
+            ..Config::default()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+        let query =
+            "from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount=1000000";
+
+        for _ in 0..2 {
+            let response = server.get(&format!("/api/v1/quote?{}", query)).await;
+            let body: serde_json::Value = response.json();
+            assert!(body["message"].as_str().unwrap().contains("503"));
+        }
+
+        let response = server.get(&format!("/api/v1/quote?{}", query)).await;
+        assert_eq!(response.status_code(), StatusCode::BAD_GATEWAY);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["message"], "lifi is unavailable (circuit open)");
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_ens_circuit_recovers_after_cooldown() {
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+        // Mock ENS API that is down until `healthy` is set
+        let healthy = Arc::new(AtomicBool::new(false));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (flag, counter) = (healthy.clone(), calls.clone());
+        let app = Router::new().route(
+            "/:name",
+            get(move || {
+                let (flag, counter) = (flag.clone(), counter.clone());
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    if flag.load(Ordering::SeqCst) {
+                        Ok(axum::Json(json!({
+                            "address": "0x1111111111111111111111111111111111111111"
+                        })))
+                    } else {
+                        Err(StatusCode::SERVICE_UNAVAILABLE)
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let ens =
+            EnsService::with_api_url(&url).with_circuit_breaker(2, Duration::from_millis(100));
+
+        for _ in 0..2 {
+            assert!(matches!(
+                ens.resolve(&"down.eth".parse().unwrap()).await,
+                Err(EnsError::HttpStatusError(503))
+            ));
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Open: fails fast without calling the upstream
+        assert!(matches!(
+            ens.resolve(&"down.eth".parse().unwrap()).await,
+            Err(EnsError::Unavailable(_))
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // After the cooldown a probe reaches the recovered upstream and closes the circuit
+        healthy.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(ens.resolve(&"recovered.eth".parse().unwrap()).await.is_ok());
+        assert!(ens.resolve(&"again.eth".parse().unwrap()).await.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    // ── OpenAPI ───────────────────────────────────────
+
+    /// Convert an axum route template (`/session/:id`) to OpenAPI (`/session/{id}`)
+    fn openapi_path(route: &str) -> String {
+        route
+            .split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(param) => format!("{{{}}}", param),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    #[tokio::test]
+    async fn test_openapi_documents_every_route() {
+        let server = create_test_server();
+        let response = server.get("/api/openapi.json").await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+
+        let spec: utoipa::openapi::OpenApi = response.json();
+        let documented: Vec<&String> = spec.paths.paths.keys().collect();
+
+        let mut expected: Vec<String> = root_route_table()
+            .into_iter()
+            .map(|(path, _)| path.to_string())
+            .collect();
+        for (path, _) in api_route_table() {
+            expected.push(format!("/api/v1{}", path));
+            expected.push(format!("/api{}", path));
+        }
+        for (path, _) in admin_route_table() {
+            expected.push(format!("/admin{}", path));
+        }
+
+        for route in expected {
+            let path = openapi_path(&route);
+            assert!(
+                documented.contains(&&path),
+                "route {} is missing from the OpenAPI spec",
+                path
+            );
+        }
+
+        // Legacy mirror is marked deprecated
+        let legacy_get = &spec.paths.paths["/api/session/{id}"].get;
+        assert!(matches!(
+            legacy_get.as_ref().unwrap().deprecated,
+            Some(utoipa::openapi::Deprecated::True)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_openapi_includes_error_and_enum_schemas() {
+        let server = create_test_server();
+        let spec: serde_json::Value = server.get("/api/openapi.json").await.json();
+        let schemas = &spec["components"]["schemas"];
+
+        assert!(schemas["ErrorResponse"]["properties"]["code"].is_object());
+        assert_eq!(
+            schemas["SessionStatus"]["enum"],
+            json!(["active", "pending", "settled", "cancelled"])
+        );
+        assert_eq!(
+            schemas["PaymentStatus"]["enum"],
+            json!(["pending", "confirmed", "settled", "cancelled"])
+        );
+    }
+
+    /// Collect every `$ref` target in `value`
+    fn schema_refs<'a>(value: &'a serde_json::Value, refs: &mut Vec<&'a str>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                if let Some(target) = map.get("$ref").and_then(|r| r.as_str()) {
+                    refs.push(target);
+                }
+                map.values().for_each(|v| schema_refs(v, refs));
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|v| schema_refs(v, refs)),
+            _ => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn test_openapi_schemas_are_complete() {
+        let server = create_test_server();
+        let spec: serde_json::Value = server.get("/api/openapi.json").await.json();
+        let schemas = &spec["components"]["schemas"];
+
+        for name in [
+            "CreateSessionRequest",
+            "CreateSessionResponse",
+            "AddPaymentRequest",
+            "SessionResponse",
+            "Address",
+            "EnsName",
+        ] {
+            assert!(schemas[name].is_object(), "schema {} is missing", name);
+        }
+        #[cfg(feature = "lifi")]
+        assert!(schemas["QuoteResponse"].is_object());
+
+        // Clients generated from the spec fail on dangling references
+        let mut refs = Vec::new();
+        schema_refs(&spec, &mut refs);
+        assert!(!refs.is_empty());
+        for target in refs {
+            let name = target
+                .strip_prefix("#/components/schemas/")
+                .unwrap_or_else(|| panic!("unexpected $ref {}", target));
+            assert!(
+                schemas[name].is_object(),
+                "$ref {} does not resolve",
+                target
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_swagger_ui_behind_flag() {
+        let server = create_test_server();
+        let response = server.get("/docs/").await;
+        assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+
+        let config = Config {
+            enable_docs: true,
+            ..Config::default()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+        let response = server.get("/docs/").await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+    }
+
+    // ── Rate Limiting ─────────────────────────────────
+
+    fn create_rate_limited_server(trust_proxy: bool) -> TestServer {
+        let config = Config {
+            trust_proxy,
+            dynamic: DynamicConfig {
+                rate_limit_write_per_minute: 3,
+                ..DynamicConfig::default()
+            },
+            ..Config::default()
+        };
+        TestServer::new(create_app(create_test_state_with_config(config))).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_burst_boundary() {
+        let server = create_rate_limited_server(true);
+        let body = json!({ "user_address": "0x0000000000000000000000000000000000000119" });
+
+        for _ in 0..3 {
+            let response = server
+                .post("/api/v1/session")
+                .add_header("x-forwarded-for", "203.0.113.7")
+                .json(&body)
+                .await;
+            assert_eq!(response.status_code(), StatusCode::CREATED);
+        }
+
+        // Legacy and v1 trees share the same bucket
+        let limited = server
+            .post("/api/session")
+            .add_header("x-forwarded-for", "203.0.113.7")
+            .json(&body)
+            .await;
+        assert_eq!(limited.status_code(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(limited.header("retry-after"), "20");
+        let envelope: serde_json::Value = limited.json();
+        assert_eq!(envelope["code"], "rate_limited");
+        assert!(envelope["request_id"].is_string());
+
+        // Reads have their own bucket, other clients are unaffected
+        let read = server
+            .get("/api/v1/session/unknown")
+            .add_header("x-forwarded-for", "203.0.113.7")
+            .await;
+        assert_eq!(read.status_code(), StatusCode::NOT_FOUND);
+        let other = server
+            .post("/api/v1/session")
+            .add_header("x-forwarded-for", "198.51.100.1, 203.0.113.8")
+            .json(&body)
+            .await;
+        assert_eq!(other.status_code(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_ignores_forwarded_for_without_trusted_proxy() {
+        let server = create_rate_limited_server(false);
+        let body = json!({ "user_address": "0x0000000000000000000000000000000000000108" });
+
+        for i in 0..3 {
+            let response = server
+                .post("/api/v1/session")
+                .add_header("x-forwarded-for", format!("203.0.113.{}", i))
+                .json(&body)
+                .await;
+            assert_eq!(response.status_code(), StatusCode::CREATED);
+        }
+
+        // A fresh spoofed address does not grant a fresh bucket
+        let limited = server
+            .post("/api/v1/session")
+            .add_header("x-forwarded-for", "203.0.113.99")
+            .json(&body)
+            .await;
+        assert_eq!(limited.status_code(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    // ── API Key Auth ──────────────────────────────────
+
+    fn create_authenticated_server() -> TestServer {
+        TestServer::new(create_app(create_test_state_with_config(
+            authenticated_config(),
+        )))
+        .unwrap()
+    }
+
+    /// Config with a `client-key` client key and an `admin-key` admin key
+    fn authenticated_config() -> Config {
+        Config {
+            api_keys: vec![
+                ApiKey {
+                    key: "client-key".to_string(),
+                    role: ApiRole::Client,
+                },
+                ApiKey {
+                    key: "admin-key".to_string(),
+                    role: ApiRole::Admin,
+                },
+            ],
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_mode_blocks_writes_only() {
+        let config = Config {
+            maintenance_mode: true,
+            ..authenticated_config()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+        let create = || {
+            server
+                .post("/api/v1/session")
+                .add_header("x-api-key", "client-key")
+                .json(&json!({ "user_address": "0x9999999999999999999999999999999999999999" }))
+        };
+
+        // Writes get 503 on both API trees, over GraphQL too
+        let response = create().await;
+        assert_error(&response, StatusCode::SERVICE_UNAVAILABLE, "maintenance");
+        assert_eq!(response.header("retry-after"), "60");
+        let response = server
+            .delete("/api/session/s1/payment/p1")
+            .add_header("x-api-key", "client-key")
+            .await;
+        assert_error(&response, StatusCode::SERVICE_UNAVAILABLE, "maintenance");
+        let body = graphql(
+            &server,
+            "mutation { finalizeSession(sessionId: \"s1\") { status } }",
+            json!({}),
+        )
+        .await;
+        assert_eq!(graphql_error_code(&body), "maintenance");
+
+        // Reads and health checks keep working
+        assert_eq!(server.get("/health").await.status_code(), StatusCode::OK);
+        let response = server.get("/api/v1/session/nonexistent").await;
+        assert_error(&response, StatusCode::NOT_FOUND, "not_found");
+
+        // Admins turn it off at runtime, and back on
+        let toggle = |enabled: bool| {
+            server
+                .post("/admin/maintenance")
+                .add_header("x-api-key", "admin-key")
+                .json(&json!({ "enabled": enabled }))
+        };
+        let response = server
+            .post("/admin/maintenance")
+            .add_header("x-api-key", "client-key")
+            .json(&json!({ "enabled": false }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+        let response = toggle(false).await;
+        assert_eq!(
+            response.json::<serde_json::Value>(),
+            json!({ "enabled": false })
+        );
+        assert_eq!(create().await.status_code(), StatusCode::CREATED);
+        toggle(true).await;
+        assert_error(
+            &create().await,
+            StatusCode::SERVICE_UNAVAILABLE,
+            "maintenance",
+        );
+    }
+
+    #[tokio::test]
+    async fn test_api_key_session_mutations() {
+        let server = create_authenticated_server();
+        let body = json!({ "user_address": "0x9999999999999999999999999999999999999999" });
+
+        let missing = server.post("/api/v1/session").json(&body).await;
+        assert_eq!(missing.status_code(), StatusCode::UNAUTHORIZED);
+        assert_eq!(missing.json::<serde_json::Value>()["code"], "unauthorized");
+
+        let wrong = server
+            .post("/api/v1/session")
+            .add_header("x-api-key", "not-a-key")
+            .json(&body)
+            .await;
+        assert_eq!(wrong.status_code(), StatusCode::UNAUTHORIZED);
+        assert!(!wrong.text().contains("not-a-key"));
+
+        for key in ["client-key", "admin-key"] {
+            let created = server
+                .post("/api/session")
+                .add_header("x-api-key", key)
+                .json(&body)
+                .await;
+            assert_eq!(created.status_code(), StatusCode::OK);
+
+            let session_id = created.json::<serde_json::Value>()["session_id"]
+                .as_str()
+                .unwrap()
+                .to_string();
+            // Reads stay open
+            let read = server.get(&format!("/api/session/{}", session_id)).await;
+            assert_eq!(read.status_code(), StatusCode::OK);
+        }
+
+        // Health stays open
+        assert_eq!(server.get("/health").await.status_code(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_api_key_admin_routes() {
+        let server = create_authenticated_server();
+
+        let missing = server.get("/admin/stats").await;
+        assert_eq!(missing.status_code(), StatusCode::UNAUTHORIZED);
+
+        let client = server
+            .get("/admin/stats")
+            .add_header("x-api-key", "client-key")
+            .await;
+        assert_eq!(client.status_code(), StatusCode::FORBIDDEN);
+        let envelope: serde_json::Value = client.json();
+        assert_eq!(envelope["code"], "forbidden");
+        assert!(!client.text().contains("client-key"));
+
+        let admin = server
+            .get("/admin/stats")
+            .add_header("x-api-key", "admin-key")
+            .await;
+        assert_eq!(admin.status_code(), StatusCode::OK);
+        assert_eq!(admin.json::<serde_json::Value>()["sessions"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_admin_locked_without_configured_keys() {
+        let server = create_test_server();
+        let response = server
+            .get("/admin/stats")
+            .add_header("x-api-key", "anything")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+
+        // Session mutations stay open until keys are configured
+        let created = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x0000000000000000000000000000000000000112" }))
+            .await;
+        assert_eq!(created.status_code(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_admin_snapshot_pages_and_streams_ndjson() {
+        let state = create_test_state_with_config(authenticated_config());
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let mut ids = Vec::new();
+        for days in (1..=5).rev() {
+            let session = SessionBuilder::new()
+                .payment(fixtures::ALICE, "100")
+                .created_days_ago(days)
+                .insert_into(&state.session_store)
+                .await;
+            ids.push(session.id);
+        }
+        let get = |query: &str| {
+            server
+                .get(&format!("/admin/snapshot{}", query))
+                .add_header("x-api-key", "admin-key")
+        };
+
+        // The whole store as one importable document
+        let response = get("").await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(response.header("x-total-count"), "5");
+        let snapshot: serde_json::Value = response.json();
+        assert_eq!(snapshot["version"], 1);
+        assert_eq!(snapshot["sessions"].as_array().unwrap().len(), 5);
+
+        // Pages are slices of the oldest-first order
+        let snapshot: serde_json::Value = get("?limit=2&offset=1").await.json();
+        let page: Vec<&str> = snapshot["sessions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(page, [ids[1].as_str(), ids[2].as_str()]);
+
+        // ndjson: one session object per line
+        let response = get("?format=ndjson&offset=3").await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(response.header("content-type"), "application/x-ndjson");
+        assert_eq!(response.header("x-total-count"), "5");
+        let text = response.text();
+        assert!(text.ends_with('\n'));
+        let lines: Vec<serde_json::Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(serde_json::Value::is_object));
+        assert_eq!(lines[0]["id"], ids[3].as_str());
+        assert_eq!(lines[1]["id"], ids[4].as_str());
+        // Past the end: an empty page
+        assert_eq!(get("?format=ndjson&offset=9").await.text(), "");
+
+        assert_error(
+            &get("?limit=0").await,
+            StatusCode::BAD_REQUEST,
+            "validation_error",
+        );
+        assert_error(
+            &get("?format=xml").await,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unprocessable_entity",
+        );
+        let client = server
+            .get("/admin/snapshot")
+            .add_header("x-api-key", "client-key")
+            .await;
+        assert_eq!(client.status_code(), StatusCode::FORBIDDEN);
+    }
+
+    // ── Admin ─────────────────────────────────────────
+
+    #[tokio::test]
+    async fn test_admin_health_and_route_listing() {
+        let server = create_authenticated_server();
+
+        let health = server
+            .get("/admin/health")
+            .add_header("x-api-key", "admin-key")
+            .await;
+        assert_eq!(health.status_code(), StatusCode::OK);
+        assert_eq!(health.json::<serde_json::Value>()["listener"], "shared");
+
+        let routes: Vec<serde_json::Value> = server
+            .get("/admin/routes")
+            .add_header("x-api-key", "admin-key")
+            .await
+            .json();
+        let listed = |method: &str, path: &str| {
+            routes
+                .iter()
+                .find(|r| r["method"] == method && r["path"] == path)
+                .cloned()
+        };
+        assert_eq!(
+            listed("POST", "/api/v1/session").unwrap()["deprecated"],
+            false
+        );
+        assert_eq!(listed("POST", "/api/session").unwrap()["deprecated"], true);
+        assert!(listed("GET", "/admin/routes").is_some());
+
+        let client = server
+            .get("/admin/routes")
+            .add_header("x-api-key", "client-key")
+            .await;
+        assert_eq!(client.status_code(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_admin_jobs_lists_session_expiry() {
+        let state = create_test_state_with_config(authenticated_config());
+        state.jobs.spawn(services::session::SessionExpiryJob {
+            store: state.session_store.clone(),
+            ttl: Duration::from_secs(3600),
+        });
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+
+        let jobs: Vec<serde_json::Value> = server
+            .get("/admin/jobs")
+            .add_header("x-api-key", "admin-key")
+            .await
+            .json();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0]["name"], "session_expiry");
+        assert_eq!(jobs[0]["failures"], 0);
+        assert!(jobs[0]["last_error"].is_null());
+        state.shutdown.cancel();
+    }
+
+    #[cfg(all(feature = "ens", feature = "lifi"))]
+    #[tokio::test]
+    async fn test_admin_internals_reports_entry_counts() {
+        let app = TestApp::spawn_with(authenticated_config()).await;
+        let internals = || async {
+            app.server
+                .get("/admin/internals")
+                .add_header("x-api-key", "admin-key")
+                .await
+                .json::<serde_json::Value>()
+        };
+        let body = internals().await;
+        for key in [
+            "session_count",
+            "ens_cache_entries",
+            "reverse_cache_entries",
+            "quote_cache_entries",
+        ] {
+            assert_eq!(body[key], 0, "{}", key);
+        }
+
+        for user in [
+            "0x0000000000000000000000000000000000000105",
+            "0x0000000000000000000000000000000000000106",
+            "0x0000000000000000000000000000000000000107",
+        ] {
+            app.server
+                .post("/api/v1/session")
+                .add_header("x-api-key", "client-key")
+                .json(&json!({ "user_address": user }))
+                .await;
+        }
+        // A forward resolution also fills the reverse cache
+        app.stub_ens_resolution("stub.eth", "0x2222222222222222222222222222222222222222")
+            .await;
+        app.stub_ens_reverse("0x1111111111111111111111111111111111111111", "other.eth")
+            .await;
+        app.server.get("/api/v1/ens/resolve?name=stub.eth").await;
+        app.server
+            .get("/api/ens/lookup?address=0x1111111111111111111111111111111111111111")
+            .await;
+        app.stub_lifi_quote("999000").await;
+        for amount in ["1000000", "2000000"] {
+            app.server
+                .get(&format!(
+                    "/api/v1/quote?from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount={}",
+                    amount
+                ))
+                .await;
+        }
+
+        let body = internals().await;
+        assert_eq!(body["session_count"], 3);
+        assert_eq!(body["ens_cache_entries"], 1);
+        assert_eq!(body["reverse_cache_entries"], 2);
+        assert_eq!(body["quote_cache_entries"], 2);
+
+        let client = app
+            .server
+            .get("/admin/internals")
+            .add_header("x-api-key", "client-key")
+            .await;
+        assert_eq!(client.status_code(), StatusCode::FORBIDDEN);
+    }
+
+    #[cfg(feature = "lifi")]
+    #[tokio::test]
+    async fn test_config_reload_applies_new_quote_cache_ttl() {
+        let app = TestApp::spawn_with(authenticated_config()).await;
+        app.stub_lifi_quote("999000").await;
+        let quote_twice = |amount: &'static str| {
+            let app = &app;
+            async move {
+                for _ in 0..2 {
+                    app.server
+                        .get(&format!(
+                            "/api/v1/quote?from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount={}",
+                            amount
+                        ))
+                        .await;
+                }
+            }
+        };
+        let reload = || {
+            app.server
+                .post("/admin/config/reload")
+                .add_header("x-api-key", "admin-key")
+        };
+        let upstream_calls = || async { app.lifi.received_requests().await.unwrap().len() };
+
+        // Default TTL: the repeated quote is served from the cache
+        quote_twice("1000000").await;
+        assert_eq!(upstream_calls().await, 1);
+
+        app.set_var("QUOTE_CACHE_TTL_SECS", "0");
+        let response = reload().await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(
+            response.json::<serde_json::Value>()["changed"],
+            json!(["QUOTE_CACHE_TTL_SECS"])
+        );
+        quote_twice("2000000").await;
+        assert_eq!(upstream_calls().await, 3);
+
+        // An invalid value is rejected and the running settings are kept
+        app.set_var("QUOTE_CACHE_TTL_SECS", "soon");
+        let response = reload().await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+        assert_eq!(app.state.live_config.get().quote_cache_ttl, Duration::ZERO);
+
+        let client = app
+            .server
+            .post("/admin/config/reload")
+            .add_header("x-api-key", "client-key")
+            .await;
+        assert_eq!(client.status_code(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_admin_port_moves_admin_routes_off_public_listener() {
+        let state = create_test_state_with_config(Config {
+            admin_port: Some(9091),
+            ..authenticated_config()
+        });
+        let public = TestServer::new(create_app(state.clone())).unwrap();
+        let admin = TestServer::new(create_admin_app(state.clone())).unwrap();
+
+        for path in ["/admin/stats", "/admin/health", "/admin/routes"] {
+            let response = public.get(path).add_header("x-api-key", "admin-key").await;
+            assert_eq!(response.status_code(), StatusCode::NOT_FOUND, "{}", path);
+
+            let response = admin.get(path).add_header("x-api-key", "admin-key").await;
+            assert_eq!(response.status_code(), StatusCode::OK, "{}", path);
+        }
+
+        // Still behind the admin key, and sharing state with the public listener
+        let response = admin.get("/admin/stats").await;
+        assert_eq!(response.status_code(), StatusCode::UNAUTHORIZED);
+        public
+            .post("/api/v1/session")
+            .add_header("x-api-key", "client-key")
+            .json(&json!({ "user_address": "0x0000000000000000000000000000000000000109" }))
+            .await;
+        let stats: serde_json::Value = admin
+            .get("/admin/stats")
+            .add_header("x-api-key", "admin-key")
+            .await
+            .json();
+        assert_eq!(stats["sessions"], 1);
+        let health: serde_json::Value = admin
+            .get("/admin/health")
+            .add_header("x-api-key", "admin-key")
+            .await
+            .json();
+        assert_eq!(health["listener"], "dedicated");
+
+        // The public API is not served on the admin listener
+        assert_eq!(
+            admin.get("/health").await.status_code(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    // ── Quote Route ───────────────────────────────────
+
+    #[cfg(feature = "lifi")]
+    #[tokio::test]
+    async fn test_quote_returns_response() {
+        let app = TestApp::spawn().await;
+        app.stub_lifi_quote("999000").await;
+        let response = app
+            .server
+            .get("/api/quote?from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount=1000000")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["from_amount"], "1000000");
+        assert_eq!(body["to_amount"], "999000");
+        assert_eq!(body["from_amount_formatted"], "1");
+        assert_eq!(body["to_amount_formatted"], "0.999");
+    }
+
+    #[cfg(feature = "lifi")]
+    #[tokio::test]
+    async fn test_quote_formatted_amounts_round_to_display_decimals() {
+        let app = TestApp::spawn_with(Config {
+            quote_display_decimals: 2,
+            ..Config::default()
+        })
+        .await;
+        app.stub_lifi_quote("1234567").await;
+        let response = app
+            .server
+            .get("/api/v1/quote?from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount=1250000")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        // Raw base units stay exact; only the display fields round
+        assert_eq!(body["to_amount"], "1234567");
+        assert_eq!(body["to_amount_formatted"], "1.23");
+        assert_eq!(body["from_amount_formatted"], "1.25");
+    }
+
+    /// Slippage of the last quote request LI.FI received
+    #[cfg(feature = "lifi")]
+    async fn upstream_slippage(app: &TestApp) -> Option<String> {
+        let requests = app.lifi.received_requests().await.unwrap();
+        let last = requests.iter().rev().find(|r| r.url.path() == "/quote")?;
+        last.url
+            .query_pairs()
+            .find(|(key, _)| key == "slippage")
+            .map(|(_, value)| value.into_owned())
+    }
+
+    #[cfg(feature = "lifi")]
+    fn slippage_config() -> Config {
+        Config {
+            default_slippage: 0.01,
+            min_slippage: 0.003,
+            ..Config::default()
+        }
+    }
+
+    #[cfg(feature = "lifi")]
+    #[tokio::test]
+    async fn test_quote_applies_default_slippage() {
+        let app = TestApp::spawn_with(slippage_config()).await;
+        app.stub_lifi_quote("999000").await;
+        let response = app
+            .server
+            .get("/api/v1/quote?from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount=1000000")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["slippage"], 0.01);
+        assert!(body.get("warning").is_none());
+        assert_eq!(upstream_slippage(&app).await.as_deref(), Some("0.01"));
+    }
+
+    #[cfg(feature = "lifi")]
+    #[tokio::test]
+    async fn test_quote_keeps_slippage_above_floor() {
+        let app = TestApp::spawn_with(slippage_config()).await;
+        app.stub_lifi_quote("999000").await;
+        let response = app
+            .server
+            .get("/api/v1/quote?from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount=1000000&slippage=0.02")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["slippage"], 0.02);
+        assert!(body.get("warning").is_none());
+        assert_eq!(upstream_slippage(&app).await.as_deref(), Some("0.02"));
+    }
+
+    #[cfg(feature = "lifi")]
+    #[tokio::test]
+    async fn test_quote_clamps_slippage_below_floor() {
+        const QUOTE: &str =
+            "/api/v1/quote?from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount=1000000";
+        let app = TestApp::spawn_with(slippage_config()).await;
+        app.stub_lifi_quote("999000").await;
+        let response = app.server.get(&format!("{}&slippage=0.001", QUOTE)).await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["slippage"], 0.003);
+        assert_eq!(
+            body["warning"],
+            "slippage 0.001 is below the minimum of 0.003 and was raised to it"
+        );
+        assert_eq!(upstream_slippage(&app).await.as_deref(), Some("0.003"));
+
+        // Not a fraction at all
+        let response = app.server.get(&format!("{}&slippage=1.5", QUOTE)).await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+    }
+
+    #[cfg(feature = "lifi")]
+    #[tokio::test]
+    async fn test_quote_sanity_ceiling_flags_units_mistakes() {
+        const QUOTE: &str =
+            "/api/v1/quote?from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC";
+        // A quintillion USDC: whole tokens sent as base units, twice over
+        const ABSURD: &str = "1000000000000000000000000";
+
+        let app = TestApp::spawn().await;
+        app.stub_lifi_quote("999000").await;
+        let body: serde_json::Value = app
+            .server
+            .get(&format!("{}&from_amount=1000000", QUOTE))
+            .await
+            .json();
+        assert!(body.get("warning").is_none());
+        let response = app
+            .server
+            .get(&format!("{}&from_amount={}", QUOTE, ABSURD))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["to_amount"], "999000");
+        let warning = body["warning"].as_str().unwrap();
+        assert!(warning.contains("$10000000 sanity ceiling"), "{}", warning);
+
+        let app = TestApp::spawn_with(Config {
+            quote_sanity_mode: api::quote::SanityMode::Reject,
+            ..Config::default()
+        })
+        .await;
+        app.stub_lifi_quote("999000").await;
+        let response = app
+            .server
+            .get(&format!("{}&from_amount=9999999000000", QUOTE))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let response = app
+            .server
+            .get(&format!("{}&from_amount={}", QUOTE, ABSURD))
+            .await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["details"]["fields"][0]["field"], "from_amount");
+
+        // Comparisons keep the plausible amounts
+        let body: serde_json::Value = app
+            .server
+            .post("/api/v1/quote/compare")
+            .json(&json!({
+                "from_chain": "8453",
+                "to_chain": "8453",
+                "from_token": "USDC",
+                "to_token": "USDC",
+                "amounts": ["1000000", ABSURD],
+            }))
+            .await
+            .json();
+        assert!(body["quotes"][0]["error"].is_null());
+        assert!(body["quotes"][1]["error"]
+            .as_str()
+            .unwrap()
+            .contains("sanity ceiling"));
+    }
+
+    #[cfg(feature = "lifi")]
+    #[tokio::test]
+    async fn test_quote_sanity_ceiling_holds_when_lifi_fails() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, ResponseTemplate};
+
+        const QUOTE: &str = "/api/v1/quote?from_chain=8453&to_chain=8453&to_token=USDC&from_token=";
+        const ABSURD: &str = "1000000000000000000000000";
+
+        let app = TestApp::spawn_with(Config {
+            quote_sanity_mode: api::quote::SanityMode::Reject,
+            ..Config::default()
+        })
+        .await;
+        app.stub_lifi_quote("999000").await;
+        let response = app
+            .server
+            .get(&format!("{}USDC&from_amount=1000000", QUOTE))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        app.lifi.reset().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&app.lifi)
+            .await;
+
+        // Judged by the earlier quote of the token
+        let response = app
+            .server
+            .get(&format!("{}usdc&from_amount={}", QUOTE, ABSURD))
+            .await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["details"]["fields"][0]["field"], "from_amount");
+        let response = app
+            .server
+            .get(&format!("{}USDC&from_amount=2000000", QUOTE))
+            .await;
+        assert_error(&response, StatusCode::BAD_GATEWAY, "upstream_error");
+        let body: serde_json::Value = app
+            .server
+            .post("/api/v1/quote/compare")
+            .json(&json!({
+                "from_chain": "8453",
+                "to_chain": "8453",
+                "from_token": "USDC",
+                "to_token": "USDC",
+                "amounts": ["2000000", ABSURD],
+            }))
+            .await
+            .json();
+        assert!(!body["quotes"][0]["error"]
+            .as_str()
+            .unwrap()
+            .contains("sanity ceiling"));
+        assert!(body["quotes"][1]["error"]
+            .as_str()
+            .unwrap()
+            .contains("sanity ceiling"));
+
+        // A token never quoted has nothing to judge by
+        let response = app
+            .server
+            .get(&format!("{}DAI&from_amount={}", QUOTE, ABSURD))
+            .await;
+        assert_error(&response, StatusCode::BAD_GATEWAY, "upstream_error");
+    }
+
+    #[cfg(feature = "lifi")]
+    #[tokio::test]
+    async fn test_price_is_fetched_once_and_cached() {
+        const USDC: &str = "0x833589fcd6edb6e08f4c7c32d4f71b54bda02913";
+        let app = TestApp::spawn().await;
+        app.stub_lifi_token_price(USDC, "0.9998").await;
+
+        for _ in 0..2 {
+            let response = app
+                .server
+                .get(&format!(
+                    "/api/v1/price?chain_id=8453&token={}",
+                    USDC.to_uppercase()
+                ))
+                .await;
+            assert_eq!(response.status_code(), StatusCode::OK);
+            let body: serde_json::Value = response.json();
+            assert_eq!(body["chain_id"], 8453);
+            assert_eq!(body["token"], USDC);
+            assert_eq!(body["usd"], 0.9998);
+            assert_eq!(body["source"], "lifi");
+            assert!(body["timestamp"].is_string());
+        }
+        assert_eq!(app.lifi.received_requests().await.unwrap().len(), 1);
+
+        // Unstubbed tokens get LI.FI's 404
+        let response = app
+            .server
+            .get("/api/v1/price?chain_id=8453&token=0xdead")
+            .await;
+        assert_error(&response, StatusCode::NOT_FOUND, "not_found");
+    }
+
+    #[tokio::test]
+    async fn test_session_template_crud_and_instantiation() {
+        let state = create_test_state();
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let response = server
+            .post("/api/v1/templates")
+            .json(&json!({
+                "name": " Payroll ",
+                "owner": fixtures::USER,
+                "entries": [
+                    { "recipient": fixtures::ALICE, "default_amount": "1000000", "memo": "rent" },
+                    { "recipient": fixtures::BOB }
+                ]
+            }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::CREATED);
+        let template: serde_json::Value = response.json();
+        let id = template["id"].as_str().unwrap().to_string();
+        assert_eq!(template["name"], "Payroll");
+        assert_eq!(template["entries"][0]["memo"], "rent");
+
+        // Listed for its owner only, and readable by id
+        let listed: serde_json::Value = server
+            .get(&format!("/api/v1/templates?owner={}", fixtures::USER))
+            .await
+            .json();
+        assert_eq!(listed["templates"][0]["id"], id.as_str());
+        let listed: serde_json::Value = server
+            .get(&format!("/api/v1/templates?owner={}", fixtures::BOB))
+            .await
+            .json();
+        assert_eq!(listed["templates"], json!([]));
+        assert_error(
+            &server.get("/api/v1/templates").await,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unprocessable_entity",
+        );
+        let response = server.get(&format!("/api/v1/templates/{}", id)).await;
+        assert_eq!(response.json::<serde_json::Value>(), template);
+
+        // Invalid templates
+        for body in [
+            json!({ "name": "", "owner": fixtures::USER, "entries": [{ "recipient": fixtures::ALICE }] }),
+            json!({ "name": "Empty", "owner": fixtures::USER, "entries": [] }),
+            json!({ "name": "Nobody", "owner": fixtures::USER, "entries": [{ "default_amount": "1" }] }),
+        ] {
+            let response = server.post("/api/v1/templates").json(&body).await;
+            assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+        }
+
+        // Bob's entry has no default amount, so it must be given
+        let instantiate = |body: serde_json::Value| {
+            server
+                .post(&format!("/api/v1/session/from-template/{}", id))
+                .json(&body)
+        };
+        let response = server
+            .post(&format!("/api/v1/session/from-template/{}", id))
+            .await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+        let response = instantiate(json!({ "amounts": { "5": "1" } })).await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+        assert!(state.session_store.snapshot().await.is_empty());
+
+        let response = instantiate(json!({ "amounts": { "1": "500000" } })).await;
+        assert_eq!(response.status_code(), StatusCode::CREATED);
+        let session = &response.json::<serde_json::Value>()["session"];
+        assert_eq!(session["user"], fixtures::USER);
+        assert_eq!(session["total_amount"], "1500000");
+        assert_eq!(session["payments"][0]["recipient"], fixtures::ALICE);
+        assert_eq!(session["payments"][0]["memo"], "rent");
+        assert_eq!(session["payments"][1]["recipient"], fixtures::BOB);
+        assert_eq!(session["payments"][1]["amount"], "500000");
+        // Overrides replace default amounts too
+        let response = server
+            .post(&format!("/api/session/from-template/{}", id))
+            .json(&json!({ "amounts": { "0": "2000000", "1": "1000000" } }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(
+            response.json::<serde_json::Value>()["session"]["total_amount"],
+            "3000000"
+        );
+        assert_eq!(state.session_store.payment_count(), 4);
+
+        // Templates are saved with the sessions' snapshot
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+        Snapshot::new(state.session_store.snapshot().await)
+            .with_templates(state.template_store.snapshot().await)
+            .write(&path)
+            .unwrap();
+        let restored = create_test_state();
+        cli::restore_sessions(&restored, &path).await.unwrap();
+        assert_eq!(
+            restored.template_store.get(&id).await.unwrap().name,
+            "Payroll"
+        );
+
+        let response = server.delete(&format!("/api/v1/templates/{}", id)).await;
+        assert_eq!(response.status_code(), StatusCode::NO_CONTENT);
+        for response in [
+            server.get(&format!("/api/v1/templates/{}", id)).await,
+            server.delete(&format!("/api/v1/templates/{}", id)).await,
+            instantiate(json!({})).await,
+        ] {
+            assert_error(&response, StatusCode::NOT_FOUND, "not_found");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_from_template_at_payment_cap_stores_nothing() {
+        let state = create_test_state_with_config(Config {
+            max_total_payments: Some(3),
+            ..Config::default()
+        });
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let template: serde_json::Value = server
+            .post("/api/v1/templates")
+            .json(&json!({
+                "name": "Payroll",
+                "owner": fixtures::USER,
+                "entries": [
+                    { "recipient": fixtures::ALICE, "default_amount": "100000" },
+                    { "recipient": fixtures::BOB, "default_amount": "100000" }
+                ]
+            }))
+            .await
+            .json();
+        let path = format!(
+            "/api/v1/session/from-template/{}",
+            template["id"].as_str().unwrap()
+        );
+        let created = server.post(&path).await;
+        assert_eq!(created.status_code(), StatusCode::CREATED);
+
+        // Two more payments would pass the cap: no session, not even an
+        // empty one counting toward the owner's active sessions
+        assert_error(
+            &server.post(&path).await,
+            StatusCode::TOO_MANY_REQUESTS,
+            "rate_limited",
+        );
+        let stored = state.session_store.snapshot().await;
+        assert_eq!(stored.len(), 1);
+        assert_eq!(
+            stored[0].id,
+            created.json::<serde_json::Value>()["session"]["id"]
+        );
+        assert_eq!(state.session_store.payment_count(), 2);
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_session_template_re_resolves_ens_names() {
+        let app = TestApp::spawn().await;
+        app.stub_ens_resolution(fixtures::ALICE_ENS, fixtures::ALICE)
+            .await;
+        let response = app
+            .server
+            .post("/api/v1/templates")
+            .json(&json!({
+                "name": "Alice",
+                "owner": fixtures::USER,
+                "entries": [{ "recipient_ens": fixtures::ALICE_ENS, "default_amount": "1000000" }]
+            }))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::CREATED);
+        let template: serde_json::Value = response.json();
+        assert_eq!(template["entries"][0]["recipient"], fixtures::ALICE);
+        let id = template["id"].as_str().unwrap().to_string();
+
+        // A recipient that disagrees with its name is refused
+        let response = app
+            .server
+            .post("/api/v1/templates")
+            .json(&json!({
+                "name": "Mismatch",
+                "owner": fixtures::USER,
+                "entries": [{ "recipient": fixtures::BOB, "recipient_ens": fixtures::ALICE_ENS }]
+            }))
+            .await;
+        assert_error(
+            &response,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unprocessable_entity",
+        );
+
+        // alice.eth moved to Carol; the cached answer must not be used
+        app.ens.reset().await;
+        app.stub_ens_resolution(fixtures::ALICE_ENS, fixtures::CAROL)
+            .await;
+        let response = app
+            .server
+            .post(&format!("/api/v1/session/from-template/{}", id))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::CREATED);
+        let payment = &response.json::<serde_json::Value>()["session"]["payments"][0];
+        assert_eq!(payment["recipient"], fixtures::CAROL);
+        assert_eq!(payment["recipient_ens"], fixtures::ALICE_ENS);
+        let stored = app.state.template_store.get(&id).await.unwrap();
+        assert_eq!(
+            stored.entries[0].recipient,
+            fixtures::address(fixtures::ALICE)
+        );
+
+        // A name that no longer resolves creates no session
+        app.ens.reset().await;
+        let response = app
+            .server
+            .post(&format!("/api/v1/session/from-template/{}", id))
+            .await;
+        assert_error(&response, StatusCode::NOT_FOUND, "not_found");
+        assert_eq!(app.state.session_store.len().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_recipient_history_across_sessions() {
+        let state = create_test_state();
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let mut settled = Vec::new();
+        for (days, amount) in [(3, "1000000"), (2, "250000"), (1, "500")] {
+            let session = SessionBuilder::new()
+                .ens_payment(fixtures::ALICE, fixtures::ALICE_ENS, amount)
+                .payment(fixtures::BOB, "7")
+                .status(SessionStatus::Settled)
+                .created_days_ago(days)
+                .insert_into(&state.session_store)
+                .await;
+            settled.push(session.id);
+        }
+        // Paid by address only, in a session with other decimals
+        let mut eighteen = SessionBuilder::new()
+            .payment(fixtures::ALICE, "2000000000000000000")
+            .status(SessionStatus::Settled)
+            .build();
+        eighteen.token_decimals = 18;
+        state.session_store.restore(vec![eighteen.clone()]).await;
+        // Neither unsettled payments nor other users' payments count
+        SessionBuilder::new()
+            .ens_payment(fixtures::ALICE, fixtures::ALICE_ENS, "9")
+            .insert_into(&state.session_store)
+            .await;
+        SessionBuilder::new()
+            .user(fixtures::BOB)
+            .payment(fixtures::ALICE, "9")
+            .status(SessionStatus::Settled)
+            .insert_into(&state.session_store)
+            .await;
+
+        let history = |recipient: &str, query: &str| {
+            server.get(&format!(
+                "/api/v1/users/{}/recipients/{}{}",
+                fixtures::USER.to_lowercase(),
+                recipient,
+                query
+            ))
+        };
+        let response = history(&fixtures::ALICE.to_uppercase().replace("0X", "0x"), "").await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["user"], fixtures::USER);
+        assert_eq!(body["recipient"], fixtures::ALICE);
+        assert_eq!(
+            body["totals"],
+            json!([
+                { "token_decimals": 6, "amount": "1250500", "amount_display": "1.2505", "payment_count": 3 },
+                { "token_decimals": 18, "amount": "2000000000000000000", "amount_display": "2", "payment_count": 1 }
+            ])
+        );
+        assert_eq!(body["payments"]["total"], 4);
+        assert_eq!(
+            body["payments"]["items"][0]["session_id"],
+            eighteen.id.as_str()
+        );
+        assert_eq!(body["payments"]["items"][0]["token_decimals"], 18);
+
+        // By stored ENS name, newest first, one page at a time
+        let body: serde_json::Value = history("Alice.ETH", "?limit=2").await.json();
+        assert_eq!(body["recipient"], fixtures::ALICE_ENS);
+        assert_eq!(body["totals"][0]["payment_count"], 3);
+        assert_eq!(body["totals"].as_array().unwrap().len(), 1);
+        let ids = |page: &serde_json::Value| -> Vec<String> {
+            page["payments"]["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|p| p["session_id"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(ids(&body), [settled[2].clone(), settled[1].clone()]);
+        let cursor = body["payments"]["next_cursor"].as_str().unwrap();
+        let next: serde_json::Value = history("alice.eth", &format!("?limit=2&cursor={}", cursor))
+            .await
+            .json();
+        assert_eq!(ids(&next), [settled[0].clone()]);
+        assert!(next["payments"]["next_cursor"].is_null());
+        assert!(next["last_paid_at"].as_str() > next["first_paid_at"].as_str());
+
+        // Nobody paid: empty history
+        let body: serde_json::Value = history(fixtures::CAROL, "").await.json();
+        assert_eq!(body["totals"], json!([]));
+        assert!(body["last_paid_at"].is_null());
+
+        assert_error(
+            &history("nobody", "").await,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unprocessable_entity",
+        );
+        let response = server
+            .get(&format!("/api/users/0x12/recipients/{}", fixtures::ALICE))
+            .await;
+        assert_error(
+            &response,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unprocessable_entity",
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recipient_history_counts_archived_sessions() {
+        let state = create_test_state();
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        let old = SessionBuilder::new()
+            .ens_payment(fixtures::ALICE, fixtures::ALICE_ENS, "1000000")
+            .status(SessionStatus::Settled)
+            .created_days_ago(40)
+            .insert_into(&state.session_store)
+            .await;
+        SessionBuilder::new()
+            .payment(fixtures::ALICE, "250000")
+            .status(SessionStatus::Settled)
+            .insert_into(&state.session_store)
+            .await;
+        let archived = state
+            .session_store
+            .archive_settled(Duration::from_secs(30 * 86400))
+            .await;
+        assert_eq!(archived.len(), 1);
+        assert!(state.session_store.get(&old.id).await.is_none());
+
+        let history = |recipient: &str| {
+            server.get(&format!(
+                "/api/v1/users/{}/recipients/{}",
+                fixtures::USER,
+                recipient
+            ))
+        };
+        // The archived payment still counts, by address and by name
+        let body: serde_json::Value = history(fixtures::ALICE).await.json();
+        assert_eq!(body["totals"][0]["amount"], "1250000");
+        assert_eq!(body["totals"][0]["payment_count"], 2);
+        assert_eq!(body["payments"]["items"][1]["session_id"], old.id.as_str());
+        let body: serde_json::Value = history(fixtures::ALICE_ENS).await.json();
+        assert_eq!(body["totals"][0]["amount"], "1000000");
+        assert_eq!(body["payments"]["items"][0]["payment"]["amount"], "1000000");
+    }
+
+    #[tokio::test]
+    async fn test_session_etag_follows_the_display_rate() {
+        use axum::http::header::IF_NONE_MATCH;
+
+        let app = TestApp::spawn_with(Config {
+            fx_rate_max_age_secs: 1,
+            ..Config::default()
+        })
+        .await;
+        app.stub_fx_rates(&[("EUR", "0.92")], 1).await;
+        let created: serde_json::Value = app
+            .server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": fixtures::USER, "display_currency": "EUR" }))
+            .await
+            .json();
+        let path = format!(
+            "/api/v1/session/{}",
+            created["session_id"].as_str().unwrap()
+        );
+        let response = app.server.get(&path).await;
+        let etag = response.header("etag");
+        let read = || {
+            app.server
+                .get(&path)
+                .add_header(IF_NONE_MATCH, etag.clone())
+        };
+        read().await.assert_status(StatusCode::NOT_MODIFIED);
+
+        // The session is unchanged, but its total is shown at a new rate
+        app.stub_fx_rates(&[("EUR", "0.95")], 1).await;
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let response = read().await;
+        response.assert_status_ok();
+        assert_ne!(response.header("etag"), etag);
+        assert_eq!(
+            response.json::<serde_json::Value>()["total_display"]["rate"],
+            "0.95"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_total_in_display_currency() {
+        let app = TestApp::spawn().await;
+        app.stub_fx_rates(&[("EUR", "0.92"), ("GBP", "0.79")], 1)
+            .await;
+        let user = "0x5555555555555555555555555555555555555555";
+
+        let created: serde_json::Value = app
+            .server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": user, "display_currency": "eur" }))
+            .await
+            .json();
+        let session_id = created["session_id"].as_str().unwrap();
+        app.server
+            .post(&format!("/api/v1/session/{}/payment", session_id))
+            .json(&json!({ "recipient": "0x1111111111111111111111111111111111111111", "amount": "12500000" }))
+            .await;
+
+        for _ in 0..2 {
+            let body: serde_json::Value = app
+                .server
+                .get(&format!("/api/v1/session/{}", session_id))
+                .await
+                .json();
+            assert_eq!(body["session"]["display_currency"], "EUR");
+            let display = &body["total_display"];
+            assert_eq!(display["currency"], "EUR");
+            assert_eq!(display["amount"], "11.50");
+            assert_eq!(display["rate"], "0.92");
+            assert!(display["rate_timestamp"].is_string());
+        }
+        // The rate is cached between reads
+        assert_eq!(app.fx.received_requests().await.unwrap().len(), 1);
+
+        // A lookup by settlement transaction reads the session the same way
+        let tx_hash = format!("0x{}", "ef56".repeat(16));
+        app.server
+            .post(&format!("/api/v1/session/{}/finalize", session_id))
+            .json(&json!({ "tx_hash": tx_hash }))
+            .await
+            .assert_status_ok();
+        let body: serde_json::Value = app
+            .server
+            .get(&format!("/api/v1/sessions/by-tx/{}", tx_hash))
+            .await
+            .json();
+        assert_eq!(body["session"]["id"], session_id);
+        assert_eq!(body["total_display"]["amount"], "11.50");
+
+        // A currency the source cannot price right now leaves the block out
+        let created: serde_json::Value = app
+            .server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": user, "display_currency": "CHF" }))
+            .await
+            .json();
+        let response = app
+            .server
+            .get(&format!(
+                "/api/v1/session/{}",
+                created["session_id"].as_str().unwrap()
+            ))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["session"]["display_currency"], "CHF");
+        assert!(body.get("total_display").is_none());
+
+        // Only allow-listed ISO 4217 codes are accepted
+        let response = app
+            .server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": user, "display_currency": "DOGE" }))
+            .await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+    }
+
+    #[cfg(feature = "lifi")]
+    #[tokio::test]
+    async fn test_quote_without_route_is_an_error() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let app = TestApp::spawn().await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "estimate": {} })))
+            .mount(&app.lifi)
+            .await;
+        let query = "from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount=1";
+
+        let response = app.server.get(&format!("/api/v1/quote?{}", query)).await;
+        assert_error(&response, StatusCode::NOT_FOUND, "not_found");
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["message"], "No route available for this transfer");
+
+        // Legacy responses keep their shape but say why the amount is zero
+        let response = app.server.get(&format!("/api/quote?{}", query)).await;
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["to_amount"], "0");
+        assert_eq!(body["error"], "No route available for this transfer");
+
+        // LI.FI's 404 "no available quotes" answer means the same
+        app.lifi.reset().await;
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(
+                ResponseTemplate::new(404)
+                    .set_body_json(json!({ "code": 1002, "message": "No available quotes" })),
+            )
+            .mount(&app.lifi)
+            .await;
+        let response = app.server.get(&format!("/api/v1/quote?{}", query)).await;
+        assert_error(&response, StatusCode::NOT_FOUND, "not_found");
+    }
+
+    #[cfg(feature = "lifi")]
+    #[tokio::test]
+    async fn test_quote_compare_returns_one_quote_per_amount_in_order() {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let app = TestApp::spawn().await;
+        for (amount, to_amount) in [("1000", "990"), ("2000", "1985"), ("3000", "2980")] {
+            Mock::given(method("GET"))
+                .and(path("/quote"))
+                .and(query_param("fromAmount", amount))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "estimate": {
+                        "toAmount": to_amount,
+                        "gasCosts": [{ "amount": "21000" }],
+                        "executionDuration": 30,
+                    }
+                })))
+                .mount(&app.lifi)
+                .await;
+        }
+        let request = json!({
+            "from_chain": "8453",
+            "to_chain": "8453",
+            "from_token": "USDC",
+            "to_token": "USDC",
+            "amounts": ["3000", "1000", "2000"],
+        });
+
+        let response = app
+            .server
+            .post("/api/v1/quote/compare")
+            .json(&request)
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        let quotes = body["quotes"].as_array().unwrap();
+        let pairs: Vec<_> = quotes
+            .iter()
+            .map(|q| {
+                (
+                    q["from_amount"].as_str().unwrap(),
+                    q["to_amount"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(pairs, [("3000", "2980"), ("1000", "990"), ("2000", "1985")]);
+        assert!(quotes.iter().all(|q| q["error"].is_null()));
+
+        // Comparisons share the quote cache with single quotes
+        let query = "from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount=1000";
+        let response = app.server.get(&format!("/api/v1/quote?{}", query)).await;
+        assert_eq!(response.json::<serde_json::Value>()["to_amount"], "990");
+        assert_eq!(app.lifi.received_requests().await.unwrap().len(), 3);
+
+        let mut too_many = request.clone();
+        too_many["amounts"] = json!(vec!["1000"; api::quote::MAX_COMPARE_AMOUNTS + 1]);
+        let response = app
+            .server
+            .post("/api/v1/quote/compare")
+            .json(&too_many)
+            .await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "validation_error");
+    }
+
+    // ── Outbound Requests ─────────────────────────────
+
+    /// Mock upstream recording the `User-Agent` of every request it receives
+    #[cfg(all(feature = "ens", feature = "lifi"))]
+    async fn spawn_user_agent_recorder() -> (String, Arc<std::sync::Mutex<Vec<String>>>) {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = seen.clone();
+        let app = Router::new().fallback(move |headers: axum::http::HeaderMap| {
+            let recorder = recorder.clone();
+            async move {
+                let user_agent = headers
+                    .get(axum::http::header::USER_AGENT)
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string();
+                recorder.lock().unwrap().push(user_agent);
+                StatusCode::NOT_FOUND
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), seen)
+    }
+
+    #[cfg(all(feature = "ens", feature = "lifi"))]
+    #[tokio::test]
+    async fn test_outbound_requests_send_default_user_agent() {
+        let (url, seen) = spawn_user_agent_recorder().await;
+        let config = Config {
+            ens_api_url: url.clone(),
+            lifi_api_url: url,
+            ..Config::default()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+
+        server.get("/api/v1/ens/resolve?name=vitalik.eth").await;
+        server
+            .get("/api/v1/quote?from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount=1000000")
+            .await;
+
+        let seen = seen.lock().unwrap().clone();
+        assert_eq!(seen.len(), 2);
+        assert!(seen.iter().all(|ua| ua == telemetry::DEFAULT_USER_AGENT));
+        assert!(seen[0].starts_with("settleone-backend/"));
+    }
+
+    #[cfg(all(feature = "ens", feature = "lifi", feature = "settlement"))]
+    #[tokio::test]
+    async fn test_outbound_user_agent_is_configurable() {
+        let (url, seen) = spawn_user_agent_recorder().await;
+        let config = Config {
+            ens_api_url: url.clone(),
+            lifi_api_url: url.clone(),
+            arc_rpc_url: url,
+            http_user_agent: "settleone-staging/1.0 (+ops@example.com)".to_string(),
+            ..Config::default()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+
+        server.get("/api/v1/ens/resolve?name=vitalik.eth").await;
+        server.get("/health/ready").await;
+
+        let seen = seen.lock().unwrap().clone();
+        assert!(seen.len() >= 2);
+        assert!(seen
+            .iter()
+            .all(|ua| ua == "settleone-staging/1.0 (+ops@example.com)"));
+    }
+
+    // ── TLS ───────────────────────────────────────────
+
+    /// Handshake with the TLS server on `port` trusting only `cert`, offering
+    /// `alpn`; returns the stream, the negotiated protocol and the served certificate
+    async fn tls_connect(
+        port: u16,
+        cert: rustls::pki_types::CertificateDer<'static>,
+        alpn: &[&[u8]],
+    ) -> (
+        tokio_rustls::client::TlsStream<tokio::net::TcpStream>,
+        Option<Vec<u8>>,
+        Vec<u8>,
+    ) {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(cert).unwrap();
+        let mut config = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = alpn.iter().map(|p| p.to_vec()).collect();
+        let tcp = tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .unwrap();
+        let stream = tokio_rustls::TlsConnector::from(Arc::new(config))
+            .connect("localhost".try_into().unwrap(), tcp)
+            .await
+            .unwrap();
+        let (_, conn) = stream.get_ref();
+        let protocol = conn.alpn_protocol().map(<[u8]>::to_vec);
+        let served = conn.peer_certificates().unwrap()[0].to_vec();
+        (stream, protocol, served)
+    }
+
+    #[tokio::test]
+    async fn test_tls_negotiates_http_versions_and_reloads_certificate() {
+        use crate::services::jobs::{Job, JobContext};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let cert_path = dir.path().join("cert.pem");
+        let key_path = dir.path().join("key.pem");
+        let cert = crate::testing::write_self_signed_cert(dir.path());
+        let config = tls::load(&cert_path, &key_path).await.unwrap();
+        let reload = tls::CertReloadJob::new(config.clone(), cert_path, key_path);
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let stop = CancellationToken::new();
+        tokio::spawn(serve_listener(
+            Listener::Tcp(listener),
+            create_app(create_test_state()),
+            Some(config),
+            stop.clone().cancelled_owned(),
+        ));
+
+        // HTTP/2 is preferred when the client offers it
+        let (_, protocol, served) = tls_connect(port, cert.clone(), &[b"h2", b"http/1.1"]).await;
+        assert_eq!(protocol.as_deref(), Some(&b"h2"[..]));
+        assert_eq!(served, cert.to_vec());
+
+        // HTTP/1.1 clients are served too
+        let (mut stream, protocol, _) = tls_connect(port, cert.clone(), &[b"http/1.1"]).await;
+        assert_eq!(protocol.as_deref(), Some(&b"http/1.1"[..]));
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+        // A replaced certificate is served once the reload job notices it;
+        // wait out coarse file timestamps so the rewrite is detectable
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let renewed = crate::testing::write_self_signed_cert(dir.path());
+        let ctx = JobContext {
+            shutdown: stop.clone(),
+        };
+        reload.run(&ctx).await.unwrap();
+        let (_, _, served) = tls_connect(port, renewed.clone(), &[b"h2"]).await;
+        assert_eq!(served, renewed.to_vec());
+
+        stop.cancel();
+    }
+
+    // ── Unix Socket ───────────────────────────────────
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serves_health_over_unix_socket() {
+        use hyper_util::rt::TokioIo;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("api.sock");
+        let listener = Listener::bind(&listen::ListenAddr::Unix(path.clone()), 0o600).unwrap();
+        let stop = CancellationToken::new();
+        let server = tokio::spawn(serve_listener(
+            listener,
+            create_app(create_test_state()),
+            None,
+            stop.clone().cancelled_owned(),
+        ));
+
+        let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .unwrap();
+        tokio::spawn(conn);
+        let request = axum::http::Request::get("/health")
+            .header("host", "localhost")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = sender.send_request(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(axum::body::Body::new(response.into_body()), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["status"], "ok");
+
+        // Graceful shutdown removes the socket file
+        drop(sender);
+        stop.cancel();
+        server.await.unwrap().unwrap();
+        assert!(!path.exists());
+    }
+
+    // ── Client SDK ────────────────────────────────────
+
+    /// Serve `state` over a real port and point the client SDK at it
+    fn spawn_client(state: AppState) -> (TestServer, SettleOneClient) {
+        let server = TestServer::builder()
+            .http_transport()
+            .build(create_app(state))
+            .unwrap();
+        let client = SettleOneClient::new(server.server_address().unwrap().to_string());
+        (server, client)
+    }
+
+    #[tokio::test]
+    async fn test_client_session_flow() {
+        let (_server, anonymous) =
+            spawn_client(create_test_state_with_config(authenticated_config()));
+        let client = anonymous.clone().with_api_key("client-key");
+
+        assert_eq!(client.health().await.unwrap().status, "ok");
+
+        let request = CreateSessionRequest {
+            user_address: "0x5555555555555555555555555555555555555555"
+                .parse()
+                .unwrap(),
+            recipient_name: None,
+            token_decimals: None,
+            settlement_mode: None,
+            display_currency: None,
+        };
+        let err = anonymous.create_session(&request).await.unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::Unauthorized));
+
+        let created = client.create_session(&request).await.unwrap();
+        assert_eq!(created.status, "active");
+        let id = created.session_id;
+
+        for amount in ["1500000", "2500000"] {
+            let request = AddPaymentRequest {
+                recipient: "0x1111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap(),
+                recipient_ens: None,
+                amount: Some(amount.parse().unwrap()),
+                human_amount: None,
+            };
+            client.add_payment(&id, &request).await.unwrap();
+        }
+        let session = client.get_session(&id).await.unwrap();
+        assert_eq!(session.session.total_amount.to_string(), "4000000");
+        assert_eq!(session.total_amount_display, "4");
+
+        let first = client.list_payments(&id, Some(1), None).await.unwrap();
+        assert_eq!(first.total, 2);
+        assert_eq!(first.items[0].amount.to_string(), "1500000");
+        let last = client
+            .list_payments(&id, Some(1), first.next_cursor.as_deref())
+            .await
+            .unwrap();
+        assert_eq!(last.items[0].amount.to_string(), "2500000");
+        assert!(last.next_cursor.is_none());
+
+        let request = FinalizeRequest {
+            tx_hash: Some("0xabc123def456".to_string()),
+            expected_total: Some(session.session.total_amount),
+            expected_payment_count: Some(2),
+            ..Default::default()
+        };
+        let finalized = client.finalize(&id, &request).await.unwrap();
+        assert_eq!(finalized.status, "pending");
+        assert_eq!(finalized.tx_hash.as_deref(), Some("0xabc123def456"));
+
+        // Error envelopes come back typed
+        match client.get_session("missing").await.unwrap_err() {
+            ClientError::Api { status, error } => {
+                assert_eq!(status, 404);
+                assert_eq!(error.code, ErrorCode::NotFound);
+                assert!(error.request_id.is_some());
+            }
+            other => panic!("expected an API error, got {:?}", other),
+        }
+        let err = client
+            .cancel_session(&id, Some(&"x".repeat(201)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::Validation));
+        let cancelled = client.cancel_session(&id, None).await.unwrap();
+        assert_eq!(cancelled.session.status, SessionStatus::Cancelled);
+        let err = client.cancel_session(&id, None).await.unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::Conflict));
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_client_resolves_ens() {
+        let app = TestApp::spawn().await;
+        app.stub_ens_resolution("stub.eth", "0x2222222222222222222222222222222222222222")
+            .await;
+        app.stub_ens_reverse("0x2222222222222222222222222222222222222222", "stub.eth")
+            .await;
+        let (_server, client) = spawn_client(app.state.clone());
+
+        let resolved = client
+            .resolve_ens(&"stub.eth".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            resolved
+                .address
+                .map(|address| address.to_string())
+                .as_deref(),
+            Some("0x2222222222222222222222222222222222222222")
+        );
+        assert!(!resolved.cached);
+        let lookup = client
+            .lookup_address(
+                &"0x2222222222222222222222222222222222222222"
+                    .parse()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            lookup.name.map(|name| name.to_string()).as_deref(),
+            Some("stub.eth")
+        );
+
+        let err = client
+            .resolve_ens(&"missing.eth".parse().unwrap())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::NotFound));
+    }
+
+    #[cfg(feature = "lifi")]
+    #[tokio::test]
+    async fn test_client_gets_quote() {
+        let app = TestApp::spawn().await;
+        app.stub_lifi_quote("999000").await;
+        let (_server, client) = spawn_client(app.state.clone());
+
+        let request = QuoteRequest {
+            from_chain: "8453".to_string(),
+            to_chain: "8453".to_string(),
+            from_token: "USDC".to_string(),
+            to_token: "USDC".to_string(),
+            from_amount: "1000000".to_string(),
+            from_address: None,
+            slippage: None,
+        };
+        let quote = client.get_quote(&request).await.unwrap();
+        assert_eq!(quote.from_amount, "1000000");
+        assert_eq!(quote.to_amount, "999000");
+        assert_eq!(quote.estimated_time, 30);
+    }
+
+    // ── GraphQL ───────────────────────────────────────
+
+    /// POST a GraphQL document, returning the response body
+    async fn graphql(
+        server: &TestServer,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> serde_json::Value {
+        let response = server
+            .post("/graphql")
+            .json(&json!({ "query": query, "variables": variables }))
+            .await;
+        response.assert_status_ok();
+        response.json()
+    }
+
+    /// Code of the first GraphQL error
+    fn graphql_error_code(body: &serde_json::Value) -> &str {
+        body["errors"][0]["extensions"]["code"].as_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_graphql_selects_requested_fields() {
+        let server = create_test_server();
+        let created = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x0000000000000000000000000000000000000101" }))
+            .await;
+        let id = created.json::<serde_json::Value>()["session_id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let add = "mutation($id: String!, $amount: String!) {
+            addPayment(sessionId: $id, input: { recipient: \"0x1111111111111111111111111111111111111111\", amount: $amount }) {
+                totalAmount
+            }
+        }";
+        for amount in ["1500000", "2500000"] {
+            let body = graphql(&server, add, json!({ "id": id, "amount": amount })).await;
+            assert!(body["errors"].is_null(), "{}", body);
+        }
+
+        let body = graphql(
+            &server,
+            "query($id: String!) { session(id: $id) { id status totalAmountDisplay payments { amount } } }",
+            json!({ "id": id }),
+        )
+        .await;
+        assert_eq!(
+            body["data"]["session"],
+            json!({
+                "id": id,
+                "status": "ACTIVE",
+                "totalAmountDisplay": "4",
+                "payments": [{ "amount": "1500000" }, { "amount": "2500000" }],
+            })
+        );
+
+        // GET works for queries too
+        let response = server
+            .get("/graphql")
+            .add_query_param("query", format!("{{ session(id: \"{}\") {{ user }} }}", id))
+            .await;
+        assert_eq!(
+            response.json::<serde_json::Value>()["data"],
+            json!({ "session": { "user": "0x0000000000000000000000000000000000000101" } })
+        );
+
+        let body = graphql(&server, "{ session(id: \"missing\") { id } }", json!({})).await;
+        assert_eq!(graphql_error_code(&body), "not_found");
+    }
+
+    #[tokio::test]
+    async fn test_graphql_sessions_paginate() {
+        let state = create_test_state();
+        for (id, user) in [
+            ("s1", "0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"),
+            ("s2", "0xAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"),
+            ("s3", "0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"),
+            ("s4", "0x0000000000000000000000000000000000000111"),
+        ] {
+            state
+                .session_store
+                .create(id.to_string(), user.parse().unwrap())
+                .await;
+        }
+        state.session_store.cancel("s3", None).await.unwrap();
+        let server = TestServer::new(create_app(state)).unwrap();
+
+        let query = "query($after: String) {
+            sessions(user: \"0xAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA\", first: 2, after: $after) { items { id } nextCursor total }
+        }";
+        let first = graphql(&server, query, json!({})).await;
+        let page = &first["data"]["sessions"];
+        assert_eq!(page["total"], 3);
+        assert_eq!(page["items"], json!([{ "id": "s1" }, { "id": "s2" }]));
+        let last = graphql(&server, query, json!({ "after": page["nextCursor"] })).await;
+        assert_eq!(last["data"]["sessions"]["items"], json!([{ "id": "s3" }]));
+        assert!(last["data"]["sessions"]["nextCursor"].is_null());
+
+        let cancelled = graphql(
+            &server,
+            "{ sessions(status: CANCELLED) { items { id cancelReason } } }",
+            json!({}),
+        )
+        .await;
+        assert_eq!(
+            cancelled["data"]["sessions"]["items"],
+            json!([{ "id": "s3", "cancelReason": null }])
+        );
+
+        let invalid = graphql(&server, "{ sessions(first: 0) { total } }", json!({})).await;
+        assert_eq!(graphql_error_code(&invalid), "validation_error");
+        assert_eq!(
+            invalid["errors"][0]["extensions"]["fields"][0]["field"],
+            "first"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_graphql_mutations_require_key_and_validate() {
+        let state = create_test_state_with_config(authenticated_config());
+        state
+            .session_store
+            .create(
+                "keyed".to_string(),
+                "0x9999999999999999999999999999999999999999"
+                    .parse()
+                    .unwrap(),
+            )
+            .await;
+        let server = TestServer::new(create_app(state)).unwrap();
+        let add = json!({
+            "query": "mutation($amount: String!) {
+                addPayment(sessionId: \"keyed\", input: { recipient: \"0x1111111111111111111111111111111111111111\", amount: $amount }) { version }
+            }",
+            "variables": { "amount": "1000000" },
+        });
+
+        let body: serde_json::Value = server.post("/graphql").json(&add).await.json();
+        assert_eq!(graphql_error_code(&body), "unauthorized");
+        // Queries stay open
+        let body = graphql(&server, "{ session(id: \"keyed\") { version } }", json!({})).await;
+        assert_eq!(body["data"]["session"]["version"], 1);
+
+        let mut invalid = add.clone();
+        invalid["variables"]["amount"] = json!("lots");
+        let body: serde_json::Value = server
+            .post("/graphql")
+            .add_header("x-api-key", "client-key")
+            .json(&invalid)
+            .await
+            .json();
+        assert_eq!(graphql_error_code(&body), "unprocessable_entity");
+        assert_eq!(
+            body["errors"][0]["extensions"]["fields"][0]["field"],
+            "amount"
+        );
+
+        let body: serde_json::Value = server
+            .post("/graphql")
+            .add_header("x-api-key", "client-key")
+            .json(&add)
+            .await
+            .json();
+        assert_eq!(body["data"]["addPayment"]["version"], 2);
+
+        let finalize = json!({
+            "query": "mutation {
+                finalizeSession(sessionId: \"keyed\", txHash: \"0xabc123def456\", expectedPaymentCount: 2) { status }
+            }",
+        });
+        let body: serde_json::Value = server
+            .post("/graphql")
+            .add_header("x-api-key", "client-key")
+            .json(&finalize)
+            .await
+            .json();
+        assert_eq!(graphql_error_code(&body), "conflict");
+        let mut finalize = finalize;
+        finalize["query"] =
+            json!("mutation { finalizeSession(sessionId: \"keyed\") { sessionId status } }");
+        let body: serde_json::Value = server
+            .post("/graphql")
+            .add_header("x-api-key", "client-key")
+            .json(&finalize)
+            .await
+            .json();
+        assert_eq!(
+            body["data"]["finalizeSession"],
+            json!({ "sessionId": "keyed", "status": "pending" })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_graphql_subscription_streams_updates() {
+        let state = create_test_state();
+        state
+            .session_store
+            .create(
+                "watched".to_string(),
+                "0x7777777777777777777777777777777777777777"
+                    .parse()
+                    .unwrap(),
+            )
+            .await;
+        let server = TestServer::builder()
+            .http_transport()
+            .build(create_app(state.clone()))
+            .unwrap();
+        let mut ws = server
+            .get_websocket("/graphql/ws")
+            .add_header("sec-websocket-protocol", "graphql-transport-ws")
+            .await
+            .into_websocket()
+            .await;
+
+        ws.send_json(&json!({ "type": "connection_init" })).await;
+        assert_eq!(
+            ws.receive_json::<serde_json::Value>().await["type"],
+            "connection_ack"
+        );
+        ws.send_json(&json!({
+            "id": "1",
+            "type": "subscribe",
+            "payload": { "query": "subscription { sessionUpdated(id: \"watched\") { status payments { amount } } }" },
+        }))
+        .await;
+        let initial: serde_json::Value = ws.receive_json().await;
+        assert_eq!(initial["type"], "next");
+        assert_eq!(
+            initial["payload"]["data"]["sessionUpdated"],
+            json!({ "status": "ACTIVE", "payments": [] })
+        );
+
+        let payment = crate::models::session::Payment {
+            id: "p1".to_string(),
+            index: 0,
+            recipient: "0x1111111111111111111111111111111111111111"
+                .parse()
+                .unwrap(),
+            recipient_ens: None,
+            amount: "1000000".parse().unwrap(),
+            status: crate::models::session::PaymentStatus::Pending,
+            created_at: chrono::Utc::now(),
+            confirmed_at: None,
+            settled_at: None,
+            cancel_reason: None,
+            memo: None,
+        };
+        state
+            .session_store
+            .add_payment("watched", payment, None)
+            .await
+            .unwrap();
+        let update: serde_json::Value = ws.receive_json().await;
+        assert_eq!(
+            update["payload"]["data"]["sessionUpdated"]["payments"],
+            json!([{ "amount": "1000000" }])
+        );
+
+        // Cancelling ends the subscription
+        state.session_store.cancel("watched", None).await.unwrap();
+        let last: serde_json::Value = ws.receive_json().await;
+        assert_eq!(
+            last["payload"]["data"]["sessionUpdated"]["status"],
+            "CANCELLED"
+        );
+        let complete: serde_json::Value = ws.receive_json().await;
+        assert_eq!(complete, json!({ "id": "1", "type": "complete" }));
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_graphql_resolves_recipient_profile() {
+        let app = TestApp::spawn().await;
+        app.stub_ens_resolution("stub.eth", "0x2222222222222222222222222222222222222222")
+            .await;
+        app.state
+            .session_store
+            .create(
+                "profiled".to_string(),
+                "0x5555555555555555555555555555555555555555"
+                    .parse()
+                    .unwrap(),
+            )
+            .await;
+        let add = "mutation {
+            addPayment(sessionId: \"profiled\", input: {
+                recipient: \"0x2222222222222222222222222222222222222222\",
+                recipientEns: \"stub.eth\",
+                amount: \"1000000\"
+            }) { payments { recipientProfile { name address } } }
+        }";
+        let body = graphql(&app.server, add, json!({})).await;
+        assert_eq!(
+            body["data"]["addPayment"]["payments"][0]["recipientProfile"],
+            json!({ "name": "stub.eth", "address": "0x2222222222222222222222222222222222222222" })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_graphql_playground_behind_flag() {
+        let server = create_test_server();
+        server
+            .get("/graphql/playground")
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+
+        let config = Config {
+            enable_graphql_playground: true,
+            ..Config::default()
+        };
+        let server = TestServer::new(create_app(create_test_state_with_config(config))).unwrap();
+        let response = server.get("/graphql/playground").await;
+        response.assert_status_ok();
+        assert!(response.text().contains("GraphQL Playground"));
+    }
+
+    // ── gRPC ──────────────────────────────────────────
+
+    #[cfg(feature = "grpc")]
+    type GrpcClient = grpc::proto::client::SettleOneGrpcClient<
+        hyper_util::client::legacy::Client<
+            hyper_util::client::legacy::connect::HttpConnector,
+            tonic::body::BoxBody,
+        >,
+    >;
+
+    #[cfg(feature = "grpc")]
+    /// Serve gRPC for `state` on a local port until its shutdown token fires
+    fn spawn_grpc(state: AppState) -> GrpcClient {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let origin = format!("http://{}", listener.local_addr().unwrap());
+        let stopped = state.shutdown.clone().cancelled_owned();
+        tokio::spawn(grpc::serve(listener, state, stopped));
+        let http =
+            hyper_util::client::legacy::Client::builder(hyper_util::rt::TokioExecutor::new())
+                .http2_only(true)
+                .build_http();
+        GrpcClient::with_origin(http, origin.parse().unwrap())
+    }
+
+    #[cfg(feature = "grpc")]
+    fn with_key<T>(message: T, key: &str) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+        request
+            .metadata_mut()
+            .insert("x-api-key", key.parse().unwrap());
+        request
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn test_grpc_session_lifecycle() {
+        use grpc::proto;
+        use tonic::Code;
+
+        let state = create_test_state_with_config(authenticated_config());
+        let mut client = spawn_grpc(state.clone());
+
+        let create = proto::CreateSessionRequest {
+            user_address: "0x5555555555555555555555555555555555555555".to_string(),
+            recipient_name: None,
+            token_decimals: None,
+        };
+        let err = client.create(create.clone()).await.unwrap_err();
+        assert_eq!(err.code(), Code::Unauthenticated);
+        let created = client
+            .create(with_key(create, "client-key"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(created.status, "active");
+        let id = created.session_id;
+
+        for amount in ["1500000", "2500000"] {
+            let payment = proto::AddPaymentRequest {
+                session_id: id.clone(),
+                recipient: "0x1111111111111111111111111111111111111111".to_string(),
+                recipient_ens: None,
+                amount: amount.to_string(),
+            };
+            client
+                .add_payment(with_key(payment, "client-key"))
+                .await
+                .unwrap();
+        }
+        let invalid = proto::AddPaymentRequest {
+            session_id: id.clone(),
+            recipient: "0x1111111111111111111111111111111111111111"
+                .parse()
+                .unwrap(),
+            amount: "lots".to_string(),
+            ..Default::default()
+        };
+        let err = client
+            .add_payment(with_key(invalid, "client-key"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+
+        let get = proto::GetSessionRequest {
+            session_id: id.clone(),
+        };
+        let session = client.get(get.clone()).await.unwrap().into_inner();
+        assert_eq!(session.status(), proto::SessionStatus::Active);
+        assert_eq!(session.total_amount, "4000000");
+        assert_eq!(session.payments.len(), 2);
+        assert_eq!(session.payments[0].status(), proto::PaymentStatus::Pending);
+
+        let stale = proto::FinalizeRequest {
+            session_id: id.clone(),
+            expected_payment_count: Some(3),
+            ..Default::default()
+        };
+        let err = client
+            .finalize(with_key(stale, "client-key"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::FailedPrecondition);
+
+        let finalize = proto::FinalizeRequest {
+            session_id: id.clone(),
+            tx_hash: Some("0xabc123def456".to_string()),
+            expected_total: Some("4000000".to_string()),
+            expected_payment_count: Some(2),
+            ..Default::default()
+        };
+        let finalized = client
+            .finalize(with_key(finalize, "client-key"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(finalized.status, "pending");
+        assert_eq!(finalized.tx_hash.as_deref(), Some("0xabc123def456"));
+        let session = client.get(get).await.unwrap().into_inner();
+        assert_eq!(session.status(), proto::SessionStatus::Pending);
+
+        let missing = proto::GetSessionRequest {
+            session_id: "missing".to_string(),
+        };
+        let err = client.get(missing).await.unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+
+        state.shutdown.cancel();
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn test_grpc_watch_streams_payment_updates() {
+        use grpc::proto;
+
+        let state = create_test_state();
+        let mut client = spawn_grpc(state.clone());
+        let created = client
+            .create(proto::CreateSessionRequest {
+                user_address: "0x7777777777777777777777777777777777777777".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let id = created.session_id;
+
+        let mut updates = client
+            .watch(proto::WatchSessionRequest {
+                session_id: id.clone(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let initial = updates.message().await.unwrap().unwrap();
+        assert!(initial.payments.is_empty());
+
+        client
+            .add_payment(proto::AddPaymentRequest {
+                session_id: id.clone(),
+                recipient: "0x1111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap(),
+                amount: "1000000".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let update = updates.message().await.unwrap().unwrap();
+        assert_eq!(update.payments.len(), 1);
+        assert_eq!(update.total_amount, "1000000");
+        assert!(update.version > initial.version);
+
+        // The stream ends once the session is cancelled
+        state.session_store.cancel(&id, None).await.unwrap();
+        let last = updates.message().await.unwrap().unwrap();
+        assert_eq!(last.status(), proto::SessionStatus::Cancelled);
+        assert!(updates.message().await.unwrap().is_none());
+
+        let err = client
+            .watch(proto::WatchSessionRequest {
+                session_id: "missing".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        state.shutdown.cancel();
+    }
+
+    #[cfg(all(feature = "grpc", feature = "lifi"))]
+    #[tokio::test]
+    async fn test_grpc_gets_quote() {
+        let app = TestApp::spawn().await;
+        app.stub_lifi_quote("999000").await;
+        let mut client = spawn_grpc(app.state.clone());
+
+        let quote = client
+            .get_quote(grpc::proto::QuoteRequest {
+                from_chain: "8453".to_string(),
+                to_chain: "8453".to_string(),
+                from_token: "USDC".to_string(),
+                to_token: "USDC".to_string(),
+                from_amount: "1000000".to_string(),
+                from_address: None,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(quote.to_amount, "999000");
+        assert_eq!(quote.estimated_time, 30);
+
+        app.state.shutdown.cancel();
+    }
+
+    // ── Disabled Integrations ─────────────────────────
+
+    #[cfg(not(feature = "ens"))]
+    #[tokio::test]
+    async fn test_ens_not_compiled_in() {
+        let server = create_test_server();
+        for path in [
+            "/api/v1/ens/resolve?name=vitalik.eth",
+            "/api/ens/lookup?address=0x0000000000000000000000000000000000000000",
+        ] {
+            let response = server.get(path).await;
+            assert_error(&response, StatusCode::NOT_IMPLEMENTED, "not_implemented");
+        }
+
+        // Pinning a recipient needs ENS too
+        let response = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x5555555555555555555555555555555555555555", "recipient_name": "alice.eth" }))
+            .await;
+        assert_error(&response, StatusCode::NOT_IMPLEMENTED, "not_implemented");
+
+        let spec: serde_json::Value = server.get("/api/openapi.json").await.json();
+        assert!(spec["paths"].get("/api/v1/ens/resolve").is_none());
+    }
+
+    #[cfg(not(feature = "lifi"))]
+    #[tokio::test]
+    async fn test_quote_not_compiled_in() {
+        let server = TestServer::new(create_app(create_test_state_with_config(
+            authenticated_config(),
+        )))
+        .unwrap();
+        let response = server
+            .get("/api/v1/quote?from_chain=8453&to_chain=8453&from_token=USDC&to_token=USDC&from_amount=1000000")
+            .await;
+        assert_error(&response, StatusCode::NOT_IMPLEMENTED, "not_implemented");
+
+        let stats: serde_json::Value = server
+            .get("/admin/stats")
+            .add_header("x-api-key", "admin-key")
+            .await
+            .json();
+        assert!(stats.get("quote_cache_entries").is_none());
+    }
+
+    #[cfg(not(feature = "settlement"))]
+    #[tokio::test]
+    async fn test_settlement_not_compiled_in() {
+        let server = create_test_server();
+        let created: serde_json::Value = server
+            .post("/api/v1/session")
+            .json(&json!({ "user_address": "0x4444444444444444444444444444444444444444" }))
+            .await
+            .json();
+        let response = server
+            .get(&format!(
+                "/api/v1/session/{}/settlement-status",
+                created["session_id"].as_str().unwrap()
+            ))
+            .await;
+        assert_error(&response, StatusCode::NOT_IMPLEMENTED, "not_implemented");
     }
 }
@@ -0,0 +1,3 @@
+//! Address and ENS name newtypes (defined in `settleone-types`)
+
+pub use settleone_types::address::*;
@@ -0,0 +1,3 @@
+//! Base-unit token amounts (defined in `settleone-types`)
+
+pub use settleone_types::amount::*;
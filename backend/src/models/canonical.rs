@@ -0,0 +1,139 @@
+//! Canonical JSON encoding for signing and hashing
+//!
+//! Produces a deterministic byte representation: object keys sorted by
+//! byte order at every level, no insignificant whitespace, and strings and
+//! numbers in `serde_json`'s compact form. Two values that are equal as
+//! JSON always encode to the same bytes, independent of struct field order
+//! or map insertion order, so signatures over them stay verifiable.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::models::session::{Payment, Session};
+
+/// Types with a canonical byte encoding
+#[allow(dead_code)]
+pub trait Canonical: Serialize {
+    /// Canonical JSON bytes of this value
+    fn canonical_bytes(&self) -> Vec<u8> {
+        to_canonical_vec(self).expect("model serialization is infallible")
+    }
+}
+
+impl Canonical for Session {}
+impl Canonical for Payment {}
+
+/// Encode any serializable value as canonical JSON bytes
+pub fn to_canonical_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, serde_json::Error> {
+    let value = serde_json::to_value(value)?;
+    let mut out = Vec::new();
+    write_value(&value, &mut out)?;
+    Ok(out)
+}
+
+/// Encode any serializable value as a canonical JSON string
+#[allow(dead_code)]
+pub fn to_canonical_string<T: Serialize + ?Sized>(value: &T) -> Result<String, serde_json::Error> {
+    let bytes = to_canonical_vec(value)?;
+    Ok(String::from_utf8(bytes).expect("serde_json emits valid UTF-8"))
+}
+
+fn write_value(value: &Value, out: &mut Vec<u8>) -> Result<(), serde_json::Error> {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+
+            out.push(b'{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                serde_json::to_writer(&mut *out, key)?;
+                out.push(b':');
+                write_value(value, out)?;
+            }
+            out.push(b'}');
+        }
+        Value::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_value(item, out)?;
+            }
+            out.push(b']');
+        }
+        scalar => serde_json::to_writer(&mut *out, scalar)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::session::PaymentStatus;
+    use chrono::{TimeZone, Utc};
+
+    fn fixed_payment(id: &str, amount: &str) -> Payment {
+        Payment {
+            id: id.to_string(),
+            index: 0,
+            recipient: "0x1111111111111111111111111111111111111111"
+                .parse()
+                .unwrap(),
+            recipient_ens: Some("alice.eth".parse().unwrap()),
+            amount: amount.parse().unwrap(),
+            status: PaymentStatus::Pending,
+            created_at: Utc.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap(),
+            confirmed_at: None,
+            settled_at: None,
+            cancel_reason: None,
+            memo: None,
+        }
+    }
+
+    #[test]
+    fn test_payment_canonical_bytes() {
+        let bytes = fixed_payment("p1", "1000000").canonical_bytes();
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            r#"{"amount":"1000000","confirmed_at":null,"created_at":"2025-01-02T03:04:05Z","id":"p1","index":0,"recipient":"0x1111111111111111111111111111111111111111","recipient_ens":"alice.eth","settled_at":null,"status":"pending"}"#
+        );
+    }
+
+    #[test]
+    fn test_key_order_independent() {
+        let a: Value =
+            serde_json::from_str(r#"{"b":1,"a":{"y":[1,{"d":2,"c":3}],"x":null}}"#).unwrap();
+        let b: Value =
+            serde_json::from_str(r#"{"a":{"x":null,"y":[1,{"c":3,"d":2}]},"b":1}"#).unwrap();
+
+        let a = to_canonical_string(&a).unwrap();
+        let b = to_canonical_string(&b).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a, r#"{"a":{"x":null,"y":[1,{"c":3,"d":2}]},"b":1}"#);
+    }
+
+    #[test]
+    fn test_session_canonical_bytes_stable() {
+        let mut session = Session::new(
+            "s1".to_string(),
+            "0x2222222222222222222222222222222222222222"
+                .parse()
+                .unwrap(),
+        );
+        session.created_at = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        session.add_payment(fixed_payment("p1", "1")).unwrap();
+        session.add_payment(fixed_payment("p2", "2")).unwrap();
+
+        let first = session.canonical_bytes();
+        let second = session.clone().canonical_bytes();
+        assert_eq!(first, second);
+
+        // Round-tripping through serde does not change the encoding
+        let reparsed: Session = serde_json::from_slice(&first).unwrap();
+        assert_eq!(reparsed.canonical_bytes(), first);
+    }
+}
@@ -0,0 +1,23 @@
+//! Session fixtures for tests (defined in `settleone-types` behind its
+//! `test-util` feature), plus storing built sessions
+
+use std::future::Future;
+
+pub use settleone_types::fixtures::*;
+
+use crate::models::session::Session;
+use crate::services::session::SessionStore;
+
+/// Store what a builder builds
+pub trait InsertInto {
+    /// Build the session and load it into `store`, as a snapshot restore would
+    fn insert_into(self, store: &SessionStore) -> impl Future<Output = Session> + Send;
+}
+
+impl InsertInto for SessionBuilder {
+    async fn insert_into(self, store: &SessionStore) -> Session {
+        let session = self.build();
+        store.restore(vec![session.clone()]).await;
+        session
+    }
+}
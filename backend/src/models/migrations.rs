@@ -0,0 +1,204 @@
+//! Upgrades of stored session JSON to the current [`Session`] shape
+//!
+//! Every stored session carries a `schema_version` (absent means 1).
+//! [`migrate_session`] applies one step per version until the JSON has the
+//! shape of [`SESSION_SCHEMA_VERSION`], then deserializes it. Versions newer
+//! than this build are rejected rather than guessed at.
+//!
+//! | Version | Shape |
+//! |---|---|
+//! | 1 | No `schema_version`; newer fields missing (`version`, `token_decimals`, `settlement_mode`, payment timestamps, ...) |
+//! | 2 | `schema_version: 2`; payments in list order without an `index` |
+//! | 3 | Payments carry their `index` |
+
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+use crate::models::session::{Session, DEFAULT_TOKEN_DECIMALS, SESSION_SCHEMA_VERSION};
+
+/// Why stored session JSON could not be loaded
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("stored session is not a JSON object")]
+    NotAnObject,
+
+    #[error("stored session has an invalid schema_version: {0}")]
+    InvalidVersion(Value),
+
+    #[error(
+        "stored session has schema_version {0}, newer than the {SESSION_SCHEMA_VERSION} this build reads; upgrade the server"
+    )]
+    UnsupportedVersion(u64),
+
+    #[error("stored session does not match schema version {SESSION_SCHEMA_VERSION}: {0}")]
+    Invalid(#[from] serde_json::Error),
+}
+
+/// Upgrade stored session JSON of any known version and deserialize it
+pub fn migrate_session(mut value: Value) -> Result<Session, MigrationError> {
+    let object = value.as_object_mut().ok_or(MigrationError::NotAnObject)?;
+    let mut version = match object.get("schema_version") {
+        None => 1,
+        Some(raw) => raw
+            .as_u64()
+            .filter(|v| *v >= 1)
+            .ok_or_else(|| MigrationError::InvalidVersion(raw.clone()))?,
+    };
+    if version > SESSION_SCHEMA_VERSION as u64 {
+        return Err(MigrationError::UnsupportedVersion(version));
+    }
+
+    while version < SESSION_SCHEMA_VERSION as u64 {
+        match version {
+            1 => v1_to_v2(object),
+            2 => v2_to_v3(object),
+            _ => unreachable!("every version below the current one has a step"),
+        }
+        version += 1;
+    }
+    object.insert("schema_version".to_string(), json!(version));
+
+    let mut session: Session = serde_json::from_value(value)?;
+    session.sort_payments();
+    Ok(session)
+}
+
+/// Fill in the fields added after the first release with their defaults
+fn v1_to_v2(session: &mut Map<String, Value>) {
+    // Version 0 would give every legacy session the same ETag as a fresh one
+    fill(session, "version", json!(1));
+    fill(session, "token_decimals", json!(DEFAULT_TOKEN_DECIMALS));
+    fill(session, "settlement_mode", json!("direct"));
+    for field in [
+        "pinned_recipient",
+        "cancel_reason",
+        "treasury_address",
+        "last_finalize_at",
+    ] {
+        fill(session, field, Value::Null);
+    }
+    if let Some(Value::Array(payments)) = session.get_mut("payments") {
+        for payment in payments.iter_mut().filter_map(Value::as_object_mut) {
+            fill(payment, "confirmed_at", Value::Null);
+            fill(payment, "settled_at", Value::Null);
+        }
+    }
+}
+
+/// Number payments by their position in the stored list
+fn v2_to_v3(session: &mut Map<String, Value>) {
+    if let Some(Value::Array(payments)) = session.get_mut("payments") {
+        for (index, payment) in payments.iter_mut().enumerate() {
+            if let Some(payment) = payment.as_object_mut() {
+                payment.insert("index".to_string(), json!(index));
+            }
+        }
+    }
+}
+
+/// Set `key` to `value` unless it is already present
+fn fill(object: &mut Map<String, Value>, key: &str, value: Value) {
+    object.entry(key).or_insert(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::session::{PaymentStatus, SessionStatus, SettlementMode};
+
+    /// Stored sessions of every schema version, oldest first
+    const FIXTURES: &[(u32, &str)] = &[
+        (1, include_str!("../../tests/fixtures/sessions/v1.json")),
+        (2, include_str!("../../tests/fixtures/sessions/v2.json")),
+        (3, include_str!("../../tests/fixtures/sessions/v3.json")),
+    ];
+
+    fn load(fixture: &str) -> Session {
+        migrate_session(serde_json::from_str(fixture).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_every_fixture_loads() {
+        assert_eq!(
+            FIXTURES.last().map(|(version, _)| *version),
+            Some(SESSION_SCHEMA_VERSION),
+            "add a fixture for the current schema version"
+        );
+        for (version, fixture) in FIXTURES {
+            let session = load(fixture);
+            assert_eq!(
+                session.schema_version, SESSION_SCHEMA_VERSION,
+                "v{}",
+                version
+            );
+            assert_eq!(session.status, SessionStatus::Settled, "v{}", version);
+            assert_eq!(session.payments.len(), 2, "v{}", version);
+            assert_eq!(session.total_amount.to_string(), "3500000", "v{}", version);
+
+            // Migrated sessions serialize in the current shape and load as-is
+            let current = serde_json::to_value(&session).unwrap();
+            assert_eq!(current["schema_version"], SESSION_SCHEMA_VERSION);
+            let reloaded = migrate_session(current).unwrap();
+            assert_eq!(reloaded.id, session.id);
+            assert_eq!(reloaded.version, session.version);
+        }
+    }
+
+    #[test]
+    fn test_v1_gets_current_defaults() {
+        let session = load(FIXTURES[0].1);
+        assert_eq!(session.version, 1);
+        assert_eq!(session.token_decimals, DEFAULT_TOKEN_DECIMALS);
+        assert_eq!(session.settlement_mode, SettlementMode::Direct);
+        assert!(session.pinned_recipient.is_none());
+        assert!(session.last_finalize_at.is_none());
+        assert_eq!(session.payments[0].status, PaymentStatus::Settled);
+        assert!(session.payments[0].settled_at.is_none());
+    }
+
+    #[test]
+    fn test_v2_payments_are_indexed_in_list_order() {
+        let session = load(FIXTURES[1].1);
+        let indexes: Vec<u32> = session.payments.iter().map(|p| p.index).collect();
+        assert_eq!(indexes, [0, 1]);
+        assert_eq!(session.payments[0].amount.to_string(), "1500000");
+    }
+
+    #[test]
+    fn test_payments_load_sorted_by_index() {
+        // v3 stores an explicit order, which wins over the list order
+        let session = load(FIXTURES[2].1);
+        let amounts: Vec<String> = session
+            .payments
+            .iter()
+            .map(|p| p.amount.to_string())
+            .collect();
+        assert_eq!(amounts, ["2000000", "1500000"]);
+    }
+
+    #[test]
+    fn test_unknown_versions_are_errors() {
+        let mut future: Value = serde_json::from_str(FIXTURES[2].1).unwrap();
+        future["schema_version"] = json!(SESSION_SCHEMA_VERSION + 1);
+        let err = migrate_session(future).unwrap_err();
+        assert!(matches!(err, MigrationError::UnsupportedVersion(4)));
+        assert!(err.to_string().contains("upgrade the server"));
+
+        for bad in [json!(0), json!("2"), json!(-1)] {
+            let mut session: Value = serde_json::from_str(FIXTURES[2].1).unwrap();
+            session["schema_version"] = bad;
+            assert!(matches!(
+                migrate_session(session),
+                Err(MigrationError::InvalidVersion(_))
+            ));
+        }
+        assert!(matches!(
+            migrate_session(json!([])),
+            Err(MigrationError::NotAnObject)
+        ));
+        assert!(matches!(
+            migrate_session(json!({ "schema_version": 2, "id": "s1" })),
+            Err(MigrationError::Invalid(_))
+        ));
+    }
+}
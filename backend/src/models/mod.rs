@@ -1,3 +1,9 @@
 //! Data models
 
+pub mod address;
+pub mod amount;
+pub mod canonical;
+#[cfg(test)]
+pub mod fixtures;
+pub mod migrations;
 pub mod session;
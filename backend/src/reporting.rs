@@ -0,0 +1,128 @@
+//! Error reporting
+//!
+//! Panics, 5xx responses and failed background job runs are handed to an
+//! [`ErrorReporter`] in addition to being logged. Reports go to Sentry when
+//! the `sentry` feature is compiled in and `SENTRY_DSN` is set; otherwise
+//! they are dropped. Events carry the request id and route template, never
+//! raw paths, query strings, headers or bodies.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::config::Config;
+
+/// Kind of an [`ErrorEvent`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// A handler panicked
+    Panic,
+    /// A handler answered with a 5xx error envelope
+    ServerError,
+    /// A background job run failed or panicked
+    Job,
+}
+
+impl ErrorKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::Panic => "panic",
+            ErrorKind::ServerError => "server_error",
+            ErrorKind::Job => "job",
+        }
+    }
+}
+
+/// An error worth reporting
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorEvent {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub request_id: Option<String>,
+    /// Matched route template, e.g. `/api/v1/session/:id`
+    pub route: Option<String>,
+    /// Extra tags, e.g. `method`, `status` or `job`
+    pub context: BTreeMap<&'static str, String>,
+}
+
+impl ErrorEvent {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            request_id: None,
+            route: None,
+            context: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_context(mut self, key: &'static str, value: impl Into<String>) -> Self {
+        self.context.insert(key, value.into());
+        self
+    }
+}
+
+/// Destination of error reports
+pub trait ErrorReporter: Send + Sync {
+    fn report(&self, event: ErrorEvent);
+}
+
+/// Drops every report
+pub struct NoopReporter;
+
+impl ErrorReporter for NoopReporter {
+    fn report(&self, _event: ErrorEvent) {}
+}
+
+/// Reporter for `config`: Sentry when a DSN is configured, otherwise no-op
+pub fn from_config(config: &Config) -> Arc<dyn ErrorReporter> {
+    #[cfg(feature = "sentry")]
+    if let Some(dsn) = &config.sentry_dsn {
+        return Arc::new(SentryReporter::new(dsn));
+    }
+    #[cfg(not(feature = "sentry"))]
+    let _ = config;
+    Arc::new(NoopReporter)
+}
+
+/// Sends reports to Sentry (`sentry` feature)
+#[cfg(feature = "sentry")]
+pub struct SentryReporter {
+    /// Flushes queued events when dropped
+    _guard: sentry::ClientInitGuard,
+}
+
+#[cfg(feature = "sentry")]
+impl SentryReporter {
+    /// Initialize the Sentry client for `dsn` (validated by [`Config`])
+    pub fn new(dsn: &str) -> Self {
+        let guard = sentry::init((
+            dsn,
+            sentry::ClientOptions {
+                release: sentry::release_name!(),
+                ..Default::default()
+            },
+        ));
+        Self { _guard: guard }
+    }
+}
+
+#[cfg(feature = "sentry")]
+impl ErrorReporter for SentryReporter {
+    fn report(&self, event: ErrorEvent) {
+        sentry::with_scope(
+            |scope| {
+                scope.set_tag("kind", event.kind.as_str());
+                if let Some(request_id) = &event.request_id {
+                    scope.set_tag("request_id", request_id);
+                }
+                if let Some(route) = &event.route {
+                    scope.set_tag("route", route);
+                }
+                for (key, value) in &event.context {
+                    scope.set_tag(key, value);
+                }
+            },
+            || sentry::capture_message(&event.message, sentry::Level::Error),
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Shared-secret API key authentication
+//!
+//! Keys are configured as `key:role` pairs. A `client` key unlocks the
+//! mutating session routes; an `admin` key additionally unlocks `/admin`.
+
+use std::fmt;
+
+use subtle::ConstantTimeEq;
+
+/// Route groups an API key may unlock, ordered by privilege
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiRole {
+    Client,
+    Admin,
+}
+
+impl ApiRole {
+    /// Parse a role name as used in `API_KEYS`
+    pub fn parse(role: &str) -> Option<Self> {
+        match role.trim().to_ascii_lowercase().as_str() {
+            "client" => Some(ApiRole::Client),
+            "admin" => Some(ApiRole::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// A configured API key and the role it grants
+#[derive(Clone, PartialEq)]
+pub struct ApiKey {
+    pub key: String,
+    pub role: ApiRole,
+}
+
+impl fmt::Debug for ApiKey {
+    // Never print the secret itself
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiKey")
+            .field("key", &"<redacted>")
+            .field("role", &self.role)
+            .finish()
+    }
+}
+
+/// Role granted to `provided`, comparing against every key in constant time
+pub fn authenticate(keys: &[ApiKey], provided: &str) -> Option<ApiRole> {
+    // Check every key so timing does not reveal which one matched
+    keys.iter().fold(None, |found, key| {
+        let matches: bool = key.key.as_bytes().ct_eq(provided.as_bytes()).into();
+        if matches {
+            found.max(Some(key.role))
+        } else {
+            found
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> Vec<ApiKey> {
+        vec![
+            ApiKey {
+                key: "client-secret".to_string(),
+                role: ApiRole::Client,
+            },
+            ApiKey {
+                key: "admin-secret".to_string(),
+                role: ApiRole::Admin,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_authenticate_roles() {
+        assert_eq!(
+            authenticate(&keys(), "client-secret"),
+            Some(ApiRole::Client)
+        );
+        assert_eq!(authenticate(&keys(), "admin-secret"), Some(ApiRole::Admin));
+        assert_eq!(authenticate(&keys(), "admin-secre"), None);
+        assert_eq!(authenticate(&keys(), ""), None);
+        assert!(ApiRole::Admin > ApiRole::Client);
+    }
+
+    #[test]
+    fn test_debug_redacts_key() {
+        let debug = format!("{:?}", keys()[0]);
+        assert!(!debug.contains("client-secret"));
+        assert!(debug.contains("Client"));
+    }
+}
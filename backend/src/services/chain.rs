@@ -0,0 +1,311 @@
+//! Transaction nonces of the backend signer
+//!
+//! Every transaction the backend sends from `SETTLEMENT_SIGNER_ADDRESS`
+//! takes its nonce from the [`NonceManager`] instead of leaving it to the
+//! node, so concurrent sends never collide. The counter starts from the
+//! signer's pending transaction count (`eth_getTransactionCount`) and is
+//! re-read from the chain on startup and after a "nonce too low" error, so
+//! a restart or a transaction sent elsewhere does not leave it behind.
+//!
+//! A nonce whose send failed is a gap: later transactions cannot be mined
+//! until it is used. So is the nonce of a sent transaction that the node
+//! dropped from its mempool, found on reconciling when the node no longer
+//! knows its hash. Gaps are handed out again before new nonces, so the next
+//! transaction fills them; gaps at the end of the range are abandoned by
+//! lowering the counter.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::models::address::Address;
+use crate::services::settlement::{SettlementError, SettlementService};
+
+/// Outcome of [`NonceManager::reconcile`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reconciled {
+    /// Next new nonce
+    pub next: u64,
+    /// Hashes of the sent transactions the node dropped; they will not be
+    /// mined, as their nonces go to the next transactions
+    pub dropped: Vec<String>,
+}
+
+/// Hands out the signer's nonces in order, without gaps
+pub struct NonceManager {
+    rpc: Arc<SettlementService>,
+    signer: Address,
+    nonces: Mutex<Nonces>,
+}
+
+/// Nonce bookkeeping, kept apart from the RPC calls that feed it
+#[derive(Debug, Default)]
+struct Nonces {
+    /// Next new nonce; `None` until read from the chain
+    next: Option<u64>,
+    /// Nonces handed out that the chain has not counted yet, with the hash
+    /// of their transaction once sent
+    in_flight: BTreeMap<u64, Option<String>>,
+    /// Nonces below `next` that no transaction took, to hand out first
+    gaps: BTreeSet<u64>,
+}
+
+impl Nonces {
+    /// Take the lowest gap, or else the next new nonce; `next` must be set
+    fn allocate(&mut self, next: u64) -> u64 {
+        let nonce = self.gaps.pop_first().unwrap_or_else(|| {
+            self.next = Some(next + 1);
+            next
+        });
+        self.in_flight.insert(nonce, None);
+        nonce
+    }
+
+    /// Record the hash of the transaction sent with `nonce`
+    fn sent(&mut self, nonce: u64, tx_hash: &str) {
+        if let Some(sent) = self.in_flight.get_mut(&nonce) {
+            *sent = Some(tx_hash.to_string());
+        }
+    }
+
+    /// Give back a nonce no transaction was sent with
+    fn release(&mut self, nonce: u64) {
+        if self.in_flight.remove(&nonce).is_none() {
+            return;
+        }
+        self.gaps.insert(nonce);
+        self.trim();
+    }
+
+    /// Sent transactions the chain, at pending transaction count `chain`,
+    /// has not counted yet
+    fn uncounted(&self, chain: u64) -> Vec<(u64, String)> {
+        self.in_flight
+            .range(chain..)
+            .filter_map(|(&nonce, hash)| Some((nonce, hash.clone()?)))
+            .collect()
+    }
+
+    /// Catch up with `chain`, the signer's pending transaction count: what
+    /// it counts is no longer in flight, nor are the `dropped` nonces whose
+    /// transactions the node lost; nonces from `chain` up to `next` that
+    /// are not in flight are gaps. Returns the abandoned nonces.
+    fn reconcile(&mut self, chain: u64, dropped: &[u64]) -> Vec<u64> {
+        self.in_flight
+            .retain(|nonce, _| *nonce >= chain && !dropped.contains(nonce));
+        let next = self.next.unwrap_or(chain).max(chain);
+        self.gaps = (chain..next)
+            .filter(|nonce| !self.in_flight.contains_key(nonce))
+            .collect();
+        self.next = Some(next);
+        self.trim()
+    }
+
+    /// Lower `next` past the gaps at the end of the range, which no later
+    /// transaction waits on; returns them
+    fn trim(&mut self) -> Vec<u64> {
+        let mut abandoned = Vec::new();
+        while let Some(next) = self.next {
+            match next.checked_sub(1) {
+                Some(last) if self.gaps.remove(&last) => {
+                    abandoned.push(last);
+                    self.next = Some(last);
+                }
+                _ => break,
+            }
+        }
+        abandoned
+    }
+}
+
+impl NonceManager {
+    /// Manage the nonces of `signer`, an account the `rpc` node signs for
+    pub fn new(rpc: Arc<SettlementService>, signer: Address) -> Self {
+        Self {
+            rpc,
+            signer,
+            nonces: Mutex::new(Nonces::default()),
+        }
+    }
+
+    pub fn signer(&self) -> &Address {
+        &self.signer
+    }
+
+    /// Hand out the signer's next nonce, reading the counter from the chain
+    /// the first time. Pass it back with [`release`](Self::release) if the
+    /// transaction is not sent.
+    pub async fn allocate(&self) -> Result<u64, SettlementError> {
+        let mut nonces = self.nonces.lock().await;
+        let next = match nonces.next {
+            Some(next) => next,
+            None => self.rpc.transaction_count(&self.signer).await?,
+        };
+        let nonce = nonces.allocate(next);
+        metrics::gauge!("settlement_nonces_in_flight").set(nonces.in_flight.len() as f64);
+        Ok(nonce)
+    }
+
+    /// Give back a nonce whose transaction was not sent, so the next
+    /// allocation reuses it
+    pub async fn release(&self, nonce: u64) {
+        let mut nonces = self.nonces.lock().await;
+        nonces.release(nonce);
+        metrics::gauge!("settlement_nonces_in_flight").set(nonces.in_flight.len() as f64);
+    }
+
+    /// Nonces handed out that the chain has not counted yet, lowest first
+    pub async fn in_flight(&self) -> Vec<u64> {
+        self.nonces.lock().await.in_flight.keys().copied().collect()
+    }
+
+    /// Re-read the signer's pending transaction count and catch up with it,
+    /// e.g. on startup or after a "nonce too low" error. Sent transactions
+    /// it does not count yet are looked up, and the nonces of those the
+    /// node dropped become gaps.
+    pub async fn reconcile(&self) -> Result<Reconciled, SettlementError> {
+        let mut nonces = self.nonces.lock().await;
+        let chain = self.rpc.transaction_count(&self.signer).await?;
+        let mut dropped = Vec::new();
+        for (nonce, tx_hash) in nonces.uncounted(chain) {
+            if !self.rpc.knows_transaction(&tx_hash).await? {
+                tracing::warn!(
+                    "Transaction {} (nonce {}) of settlement signer {} was dropped",
+                    tx_hash,
+                    nonce,
+                    self.signer
+                );
+                dropped.push((nonce, tx_hash));
+            }
+        }
+        let dropped_nonces: Vec<u64> = dropped.iter().map(|(nonce, _)| *nonce).collect();
+        let abandoned = nonces.reconcile(chain, &dropped_nonces);
+        if !abandoned.is_empty() {
+            tracing::warn!(
+                "Abandoned unused nonces {:?} of settlement signer {}",
+                abandoned,
+                self.signer
+            );
+        }
+        if !nonces.gaps.is_empty() {
+            tracing::warn!(
+                "Settlement signer {} has nonce gaps {:?}; the next transactions fill them",
+                self.signer,
+                nonces.gaps
+            );
+        }
+        metrics::gauge!("settlement_nonces_in_flight").set(nonces.in_flight.len() as f64);
+        Ok(Reconciled {
+            next: nonces.next.unwrap_or(chain),
+            dropped: dropped.into_iter().map(|(_, tx_hash)| tx_hash).collect(),
+        })
+    }
+
+    /// Send a transaction from the signer calling `to` with `data`, with
+    /// the next nonce; returns its hash. After "nonce too low" the counter
+    /// is reconciled and the send tried once more with a fresh nonce.
+    pub async fn send(&self, to: &Address, data: &[u8]) -> Result<String, SettlementError> {
+        let nonce = self.allocate().await?;
+        match self.try_send(to, data, nonce).await {
+            Err(e) if is_nonce_too_low(&e) => {
+                tracing::warn!(
+                    "Nonce {} of settlement signer {} was already used; reconciling",
+                    nonce,
+                    self.signer
+                );
+                self.reconcile().await?;
+                let nonce = self.allocate().await?;
+                self.try_send(to, data, nonce).await
+            }
+            sent => sent,
+        }
+    }
+
+    /// Send with `nonce`, giving it back if the node rejects the transaction
+    /// for anything but the nonce
+    async fn try_send(
+        &self,
+        to: &Address,
+        data: &[u8],
+        nonce: u64,
+    ) -> Result<String, SettlementError> {
+        let sent = self
+            .rpc
+            .send_transaction(&self.signer, to, data, nonce)
+            .await;
+        match &sent {
+            Ok(tx_hash) => self.nonces.lock().await.sent(nonce, tx_hash),
+            Err(e) if !is_nonce_too_low(e) => self.release(nonce).await,
+            Err(_) => {}
+        }
+        sent
+    }
+}
+
+/// Whether the node refused a transaction because its nonce was used
+fn is_nonce_too_low(error: &SettlementError) -> bool {
+    match error {
+        SettlementError::Rpc(message) => message.to_lowercase().contains("nonce too low"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_released_nonces_are_reused_before_new_ones() {
+        let mut nonces = Nonces::default();
+        assert_eq!(
+            [5, 6, 7].map(|_| nonces.allocate(nonces.next.unwrap_or(5))),
+            [5, 6, 7]
+        );
+
+        // A gap in the middle is filled by the next allocation
+        nonces.release(6);
+        assert_eq!(nonces.allocate(nonces.next.unwrap()), 6);
+        assert_eq!(nonces.allocate(nonces.next.unwrap()), 8);
+
+        // One at the end just lowers the counter
+        nonces.release(8);
+        assert!(nonces.gaps.is_empty());
+        assert_eq!(nonces.next, Some(8));
+        // Releasing twice, or a nonce never handed out, changes nothing
+        nonces.release(8);
+        nonces.release(3);
+        assert_eq!(nonces.next, Some(8));
+        assert!(nonces.gaps.is_empty());
+    }
+
+    #[test]
+    fn test_reconcile_catches_up_with_the_chain() {
+        let mut nonces = Nonces::default();
+        for _ in 0..4 {
+            nonces.allocate(nonces.next.unwrap_or(10));
+        }
+        assert_eq!(nonces.next, Some(14));
+
+        nonces.sent(12, "0x12");
+        nonces.sent(13, "0x13");
+        assert_eq!(
+            nonces.uncounted(12),
+            [(12, "0x12".to_string()), (13, "0x13".to_string())]
+        );
+
+        // The chain counted 10 and 11; 12 was dropped, 13 is still pending
+        assert!(nonces.reconcile(12, &[12]).is_empty());
+        assert_eq!(nonces.in_flight.keys().collect::<Vec<_>>(), [&13]);
+        assert_eq!(nonces.gaps, BTreeSet::from([12]));
+        assert_eq!(nonces.next, Some(14));
+
+        // After 13 is dropped too, the trailing gaps go with it
+        assert_eq!(nonces.reconcile(12, &[13]), [13, 12]);
+        assert_eq!(nonces.next, Some(12));
+
+        // Transactions sent elsewhere move the counter forward
+        assert!(nonces.reconcile(20, &[]).is_empty());
+        assert_eq!(nonces.next, Some(20));
+        assert!(nonces.in_flight.is_empty() && nonces.gaps.is_empty());
+    }
+}
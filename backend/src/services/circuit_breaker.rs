@@ -0,0 +1,166 @@
+//! Circuit breaker for upstream providers
+//!
+//! After `threshold` consecutive failures the circuit opens and calls fail
+//! fast for `cooldown`. The first call after the cooldown is let through as
+//! a probe (half-open): success closes the circuit, failure re-opens it.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+/// Default consecutive failures before the circuit opens
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+
+/// Default time the circuit stays open before probing
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Returned instead of calling an upstream whose circuit is open
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("{0} is unavailable (circuit open)")]
+pub struct CircuitOpen(pub &'static str);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// A probe call is in flight
+    HalfOpen {
+        since: Instant,
+    },
+}
+
+/// Per-upstream circuit breaker
+pub struct CircuitBreaker {
+    upstream: &'static str,
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    /// Create a closed breaker for `upstream` (used in errors and metrics)
+    pub fn new(upstream: &'static str, threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            upstream,
+            threshold: threshold.max(1),
+            cooldown,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Ask to call the upstream; fails fast while the circuit is open
+    pub fn try_acquire(&self) -> Result<(), CircuitOpen> {
+        let mut state = self.state.lock().expect("circuit breaker lock poisoned");
+        let now = Instant::now();
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { until } if now >= until => {
+                *state = State::HalfOpen { since: now };
+                Ok(())
+            }
+            // A probe that never reported back must not wedge the circuit
+            State::HalfOpen { since } if now.duration_since(since) >= self.cooldown => {
+                *state = State::HalfOpen { since: now };
+                Ok(())
+            }
+            State::Open { .. } | State::HalfOpen { .. } => {
+                metrics::counter!("circuit_breaker_rejections_total", "upstream" => self.upstream)
+                    .increment(1);
+                Err(CircuitOpen(self.upstream))
+            }
+        }
+    }
+
+    /// Record a successful call, closing the circuit
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().expect("circuit breaker lock poisoned");
+        if !matches!(*state, State::Closed { .. }) {
+            tracing::info!("Circuit for {} closed", self.upstream);
+        }
+        *state = State::Closed { failures: 0 };
+        metrics::gauge!("circuit_breaker_open", "upstream" => self.upstream).set(0.0);
+    }
+
+    /// Record a failed call, opening the circuit at the threshold
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().expect("circuit breaker lock poisoned");
+        let failures = match *state {
+            State::Closed { failures } => failures + 1,
+            // A failed probe re-opens immediately
+            State::HalfOpen { .. } | State::Open { .. } => self.threshold,
+        };
+
+        if failures >= self.threshold {
+            tracing::warn!(
+                "Circuit for {} opened for {:?} after {} failures",
+                self.upstream,
+                self.cooldown,
+                failures
+            );
+            *state = State::Open {
+                until: Instant::now() + self.cooldown,
+            };
+            metrics::gauge!("circuit_breaker_open", "upstream" => self.upstream).set(1.0);
+        } else {
+            *state = State::Closed { failures };
+        }
+    }
+
+    /// Whether calls are currently being rejected
+    #[allow(dead_code)]
+    pub fn is_open(&self) -> bool {
+        matches!(
+            *self.state.lock().expect("circuit breaker lock poisoned"),
+            State::Open { until } if Instant::now() < until
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new("test", 3, Duration::from_secs(60));
+
+        breaker.record_failure();
+        breaker.record_failure();
+        // A success in between resets the count
+        breaker.record_success();
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(breaker.try_acquire().is_ok());
+
+        breaker.record_failure();
+        assert!(breaker.is_open());
+        assert_eq!(breaker.try_acquire(), Err(CircuitOpen("test")));
+    }
+
+    #[test]
+    fn test_half_open_probe_after_cooldown() {
+        let breaker = CircuitBreaker::new("test", 1, Duration::from_millis(20));
+        breaker.record_failure();
+        assert!(breaker.try_acquire().is_err());
+
+        std::thread::sleep(Duration::from_millis(30));
+        // One probe is let through; concurrent callers still fail fast
+        assert!(breaker.try_acquire().is_ok());
+        assert!(breaker.try_acquire().is_err());
+
+        // Failed probe re-opens
+        breaker.record_failure();
+        assert!(breaker.try_acquire().is_err());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(breaker.try_acquire().is_ok());
+        breaker.record_success();
+        assert!(breaker.try_acquire().is_ok());
+        assert!(breaker.try_acquire().is_ok());
+    }
+}
@@ -0,0 +1,271 @@
+//! ENSIP-7 / EIP-1577 `contenthash` decoding
+//!
+//! A content hash is a multicodec-prefixed content identifier. The
+//! protocols ENS front ends link to are decoded into URIs:
+//! `ipfs://` (CIDv0 base58 for dag-pb, base32 CIDv1 otherwise),
+//! `ipns://` (base36 for libp2p keys, the plain domain for DNSLink) and
+//! `bzz://` (Swarm).
+
+use thiserror::Error;
+
+/// Multicodec of IPFS content
+const IPFS_NS: u64 = 0xe3;
+/// Multicodec of Swarm content
+const SWARM_NS: u64 = 0xe4;
+/// Multicodec of IPNS names
+const IPNS_NS: u64 = 0xe5;
+
+/// CID content codecs
+const DAG_PB: u64 = 0x70;
+const LIBP2P_KEY: u64 = 0x72;
+const SWARM_MANIFEST: u64 = 0xfa;
+
+/// Multihash functions
+const IDENTITY: u64 = 0x00;
+const SHA2_256: u64 = 0x12;
+const KECCAK_256: u64 = 0x1b;
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BASE36_ALPHABET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+const BASE32_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// Why a content hash could not be decoded
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ContentHashError {
+    #[error("content hash is truncated")]
+    Truncated,
+
+    #[error("unsupported content hash codec 0x{0:x}")]
+    UnsupportedCodec(u64),
+
+    #[error("malformed {0} content hash")]
+    Malformed(&'static str),
+}
+
+/// Decode a raw `contenthash` record into a URI such as `ipfs://Qm...`
+pub fn decode(bytes: &[u8]) -> Result<String, ContentHashError> {
+    let (codec, cid) = read_varint(bytes)?;
+    match codec {
+        IPFS_NS => decode_ipfs(cid),
+        IPNS_NS => decode_ipns(cid),
+        SWARM_NS => decode_swarm(cid),
+        other => Err(ContentHashError::UnsupportedCodec(other)),
+    }
+}
+
+fn decode_ipfs(cid: &[u8]) -> Result<String, ContentHashError> {
+    // A bare sha2-256 multihash is a CIDv0
+    if is_sha256_multihash(cid) {
+        return Ok(format!("ipfs://{}", base58(cid)));
+    }
+    let (version, rest) = read_varint(cid)?;
+    if version != 1 {
+        return Err(ContentHashError::Malformed("ipfs"));
+    }
+    let (content, multihash) = read_varint(rest)?;
+    // dag-pb over sha2-256 is what CIDv0 can express, and how IPFS tools
+    // print it
+    if content == DAG_PB && is_sha256_multihash(multihash) {
+        return Ok(format!("ipfs://{}", base58(multihash)));
+    }
+    Ok(format!("ipfs://b{}", base32(cid)))
+}
+
+fn decode_ipns(cid: &[u8]) -> Result<String, ContentHashError> {
+    let (version, rest) = read_varint(cid)?;
+    if version != 1 {
+        return Err(ContentHashError::Malformed("ipns"));
+    }
+    let (content, multihash) = read_varint(rest)?;
+    if content == LIBP2P_KEY {
+        return Ok(format!("ipns://k{}", base36(cid)));
+    }
+    // Legacy DNSLink records inline the domain as an identity multihash
+    let (function, digest) = read_varint(multihash)?;
+    if content == DAG_PB && function == IDENTITY {
+        let (len, domain) = read_varint(digest)?;
+        if domain.len() as u64 != len {
+            return Err(ContentHashError::Malformed("ipns"));
+        }
+        let domain =
+            std::str::from_utf8(domain).map_err(|_| ContentHashError::Malformed("ipns"))?;
+        return Ok(format!("ipns://{}", domain));
+    }
+    Ok(format!("ipns://b{}", base32(cid)))
+}
+
+fn decode_swarm(cid: &[u8]) -> Result<String, ContentHashError> {
+    let malformed = ContentHashError::Malformed("swarm");
+    let (version, rest) = read_varint(cid)?;
+    let (content, rest) = read_varint(rest)?;
+    let (function, rest) = read_varint(rest)?;
+    let (len, hash) = read_varint(rest)?;
+    if version != 1
+        || content != SWARM_MANIFEST
+        || function != KECCAK_256
+        || len != 32
+        || hash.len() != 32
+    {
+        return Err(malformed);
+    }
+    let hex: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("bzz://{}", hex))
+}
+
+fn is_sha256_multihash(bytes: &[u8]) -> bool {
+    bytes.len() == 34 && bytes[0] == SHA2_256 as u8 && bytes[1] == 32
+}
+
+/// Read an unsigned LEB128 varint, returning it and the bytes after it
+fn read_varint(bytes: &[u8]) -> Result<(u64, &[u8]), ContentHashError> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate().take(9) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, &bytes[i + 1..]));
+        }
+    }
+    Err(ContentHashError::Truncated)
+}
+
+/// Big-endian base-N encoding where each leading zero byte becomes the
+/// alphabet's first character (base58btc, base36)
+fn encode_radix(bytes: &[u8], alphabet: &[u8]) -> String {
+    let radix = alphabet.len() as u32;
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    // Little-endian digits in `radix`
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in &bytes[zeros..] {
+        let mut carry = u32::from(byte);
+        for digit in digits.iter_mut() {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % radix) as u8;
+            carry /= radix;
+        }
+        while carry > 0 {
+            digits.push((carry % radix) as u8);
+            carry /= radix;
+        }
+    }
+    std::iter::repeat_n(alphabet[0], zeros)
+        .chain(digits.iter().rev().map(|&d| alphabet[d as usize]))
+        .map(char::from)
+        .collect()
+}
+
+fn base58(bytes: &[u8]) -> String {
+    encode_radix(bytes, BASE58_ALPHABET)
+}
+
+fn base36(bytes: &[u8]) -> String {
+    encode_radix(bytes, BASE36_ALPHABET)
+}
+
+/// RFC 4648 base32, lowercase and unpadded, as multibase `b` uses it
+fn base32(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | u16::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(char::from(
+                BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize],
+            ));
+        }
+    }
+    if bits > 0 {
+        out.push(char::from(
+            BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize],
+        ));
+    }
+    out
+}
+
+/// Decode `0x`-prefixed (or bare) hex
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.strip_prefix("0x").unwrap_or(hex);
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_str(hex: &str) -> Result<String, ContentHashError> {
+        decode(&decode_hex(hex).unwrap())
+    }
+
+    #[test]
+    fn test_decode_ipfs_content_hash() {
+        // The EIP-1577 example
+        assert_eq!(
+            decode_str(
+                "e3010170122029f2d17be6139079dc48696d1f582a8530eb9805b561eda517e22a892c7e3f1f"
+            )
+            .unwrap(),
+            "ipfs://QmRAQB6YaCyidP37UdDnjFY5vQuiBrcqdyoW1CuDgwxkD4"
+        );
+        // A raw-leaf CIDv1 has no CIDv0 form
+        let uri = decode_str(
+            "e30101551220b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9",
+        )
+        .unwrap();
+        assert_eq!(
+            uri,
+            "ipfs://bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e"
+        );
+    }
+
+    #[test]
+    fn test_decode_ipns_content_hash() {
+        assert_eq!(
+            decode_str("e5010170000f6170702e756e69737761702e6f7267").unwrap(),
+            "ipns://app.uniswap.org"
+        );
+        // An ed25519 libp2p key
+        let key = format!("e5010172002408011220{}", "ab".repeat(32));
+        assert!(decode_str(&key).unwrap().starts_with("ipns://k51qzi5uqu5"));
+    }
+
+    #[test]
+    fn test_decode_swarm_content_hash() {
+        assert_eq!(
+            decode_str(
+                "e40101fa011b20d1de9994b4d039f6548d191eb26786769f580809256b4685ef316805265ea162"
+            )
+            .unwrap(),
+            "bzz://d1de9994b4d039f6548d191eb26786769f580809256b4685ef316805265ea162"
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_unknown_and_truncated() {
+        // Onion addresses are a valid codec, but not one we link to
+        assert_eq!(
+            decode_str("bc0362636433"),
+            Err(ContentHashError::UnsupportedCodec(0x1bc))
+        );
+        assert_eq!(decode_str("e3"), Err(ContentHashError::Truncated));
+        assert_eq!(
+            decode_str("e30102"),
+            Err(ContentHashError::Malformed("ipfs"))
+        );
+        assert_eq!(decode_hex("0xabc"), None);
+    }
+
+    #[test]
+    fn test_radix_encodings_keep_leading_zeros() {
+        assert_eq!(base58(&[0, 0, 1]), "112");
+        assert_eq!(base36(&[0, 35]), "0z");
+        assert_eq!(base32(b"foobar"), "mzxw6ytboi");
+    }
+}
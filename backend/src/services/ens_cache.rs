@@ -0,0 +1,484 @@
+//! Storage for cached ENS resolutions
+//!
+//! [`EnsService`](crate::services::ens::EnsService) keeps forward
+//! (namehash -> address) and reverse (address -> name) answers in an
+//! [`EnsCache`]. [`MemoryEnsCache`] is per process and the default;
+//! [`RedisEnsCache`] (`redis` feature) shares one cache between instances,
+//! so a new instance starts warm. Entries are never trusted past
+//! `expires_at`, but only the in-memory cache keeps them around after it
+//! for stale-while-revalidate: Redis drops them at their TTL.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::models::address::{Address, EnsName};
+
+/// Longest a Redis connection attempt or command may take before the
+/// cache counts as failed, so an unhealthy Redis cannot stall resolution
+#[cfg(feature = "redis")]
+const REDIS_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Where cached ENS resolutions are kept (`ENS_CACHE_BACKEND`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EnsCacheBackend {
+    /// In this process only
+    #[default]
+    Memory,
+    /// In the Redis server at `ENS_CACHE_REDIS_URL` (`redis` feature)
+    Redis,
+}
+
+impl EnsCacheBackend {
+    /// Parse an `ENS_CACHE_BACKEND` value
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "memory" => Some(EnsCacheBackend::Memory),
+            "redis" => Some(EnsCacheBackend::Redis),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EnsCacheBackend::Memory => "memory",
+            EnsCacheBackend::Redis => "redis",
+        }
+    }
+}
+
+/// A cached address (forward) or name (reverse), with its avatar
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheEntry<T> {
+    pub value: T,
+    pub avatar: Option<String>,
+    pub cached_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl<T> CacheEntry<T> {
+    /// An entry cached now, fresh for `ttl`
+    pub fn new(value: T, avatar: Option<String>, ttl: Duration) -> Self {
+        let now = Utc::now();
+        Self {
+            value,
+            avatar,
+            cached_at: now,
+            expires_at: now + chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX),
+        }
+    }
+
+    pub fn is_fresh(&self) -> bool {
+        self.expires_at > Utc::now()
+    }
+
+    /// Time since the entry was cached
+    pub fn age(&self) -> Duration {
+        (Utc::now() - self.cached_at).to_std().unwrap_or_default()
+    }
+
+    /// Time until the entry expires, zero once it has
+    #[cfg_attr(not(feature = "redis"), allow(dead_code))]
+    fn ttl(&self) -> Duration {
+        (self.expires_at - Utc::now()).to_std().unwrap_or_default()
+    }
+}
+
+/// Storage of cached ENS resolutions.
+///
+/// Errors mean the store could not be reached; callers treat them as
+/// misses, so a failing cache slows resolution down but never breaks it.
+pub trait EnsCache: Send + Sync {
+    /// Stable name, as in `ENS_CACHE_BACKEND`
+    fn name(&self) -> &'static str;
+
+    /// Forward entry of a namehash, expired ones included while kept
+    fn get<'a>(
+        &'a self,
+        node: &'a str,
+    ) -> BoxFuture<'a, Result<Option<CacheEntry<Address>>, String>>;
+
+    fn set<'a>(
+        &'a self,
+        node: &'a str,
+        entry: CacheEntry<Address>,
+    ) -> BoxFuture<'a, Result<(), String>>;
+
+    /// Reverse entry of an address, expired ones included while kept
+    fn get_reverse<'a>(
+        &'a self,
+        address: &'a Address,
+    ) -> BoxFuture<'a, Result<Option<CacheEntry<EnsName>>, String>>;
+
+    fn set_reverse<'a>(
+        &'a self,
+        address: &'a Address,
+        entry: CacheEntry<EnsName>,
+    ) -> BoxFuture<'a, Result<(), String>>;
+
+    /// Number of forward and reverse entries
+    fn sizes(&self) -> BoxFuture<'_, Result<(usize, usize), String>>;
+
+    /// Drop every forward and reverse entry
+    fn clear(&self) -> BoxFuture<'_, Result<(), String>>;
+}
+
+/// Per-process cache; expired entries stay until overwritten
+#[derive(Default)]
+pub struct MemoryEnsCache {
+    forward: RwLock<HashMap<String, CacheEntry<Address>>>,
+    reverse: RwLock<HashMap<Address, CacheEntry<EnsName>>>,
+}
+
+impl MemoryEnsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EnsCache for MemoryEnsCache {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn get<'a>(
+        &'a self,
+        node: &'a str,
+    ) -> BoxFuture<'a, Result<Option<CacheEntry<Address>>, String>> {
+        Box::pin(async move { Ok(self.forward.read().await.get(node).cloned()) })
+    }
+
+    fn set<'a>(
+        &'a self,
+        node: &'a str,
+        entry: CacheEntry<Address>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            self.forward.write().await.insert(node.to_string(), entry);
+            Ok(())
+        })
+    }
+
+    fn get_reverse<'a>(
+        &'a self,
+        address: &'a Address,
+    ) -> BoxFuture<'a, Result<Option<CacheEntry<EnsName>>, String>> {
+        Box::pin(async move { Ok(self.reverse.read().await.get(address).cloned()) })
+    }
+
+    fn set_reverse<'a>(
+        &'a self,
+        address: &'a Address,
+        entry: CacheEntry<EnsName>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            self.reverse.write().await.insert(address.clone(), entry);
+            Ok(())
+        })
+    }
+
+    fn sizes(&self) -> BoxFuture<'_, Result<(usize, usize), String>> {
+        Box::pin(async move {
+            Ok((
+                self.forward.read().await.len(),
+                self.reverse.read().await.len(),
+            ))
+        })
+    }
+
+    fn clear(&self) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(async move {
+            self.forward.write().await.clear();
+            self.reverse.write().await.clear();
+            Ok(())
+        })
+    }
+}
+
+/// Cache shared through Redis: entries are JSON strings under
+/// `settleone:ens:fwd:<namehash>` and `settleone:ens:rev:<address>`,
+/// expiring with their TTL
+#[cfg(feature = "redis")]
+pub struct RedisEnsCache {
+    client: redis::Client,
+    /// Connected on first use, so startup does not wait for Redis
+    connection: tokio::sync::OnceCell<redis::aio::ConnectionManager>,
+    prefix: String,
+}
+
+#[cfg(feature = "redis")]
+impl RedisEnsCache {
+    /// Key prefix of every entry
+    pub const DEFAULT_PREFIX: &'static str = "settleone:ens:";
+
+    /// Cache in the Redis server at `url`; fails only if the URL is invalid
+    pub fn open(url: &str) -> Result<Self, String> {
+        let client = redis::Client::open(url).map_err(|e| e.to_string())?;
+        Ok(Self {
+            client,
+            connection: tokio::sync::OnceCell::new(),
+            prefix: Self::DEFAULT_PREFIX.to_string(),
+        })
+    }
+
+    /// Keep entries under `prefix` instead, e.g. to isolate tests
+    #[allow(dead_code)]
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    async fn connection(&self) -> Result<redis::aio::ConnectionManager, String> {
+        self.connection
+            .get_or_try_init(|| {
+                let config = redis::aio::ConnectionManagerConfig::new()
+                    .set_number_of_retries(1)
+                    .set_connection_timeout(REDIS_TIMEOUT)
+                    .set_response_timeout(REDIS_TIMEOUT);
+                self.client.get_connection_manager_with_config(config)
+            })
+            .await
+            .cloned()
+            .map_err(|e| format!("Redis unavailable: {}", e))
+    }
+
+    fn forward_key(&self, node: &str) -> String {
+        format!("{}fwd:{}", self.prefix, node)
+    }
+
+    fn reverse_key(&self, address: &Address) -> String {
+        format!("{}rev:{}", self.prefix, address.as_str().to_lowercase())
+    }
+
+    async fn load<T: serde::de::DeserializeOwned>(
+        &self,
+        key: String,
+    ) -> Result<Option<CacheEntry<T>>, String> {
+        use redis::AsyncCommands;
+
+        let mut connection = self.connection().await?;
+        let raw: Option<String> = connection.get(&key).await.map_err(|e| e.to_string())?;
+        raw.map(|raw| {
+            serde_json::from_str(&raw).map_err(|e| format!("Malformed entry {}: {}", key, e))
+        })
+        .transpose()
+    }
+
+    /// Store `entry` until it expires; expired entries are not stored
+    async fn store<T: Serialize>(&self, key: String, entry: &CacheEntry<T>) -> Result<(), String> {
+        use redis::AsyncCommands;
+
+        let ttl = entry.ttl().as_millis() as u64;
+        if ttl == 0 {
+            return Ok(());
+        }
+        let raw = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        let mut connection = self.connection().await?;
+        connection
+            .pset_ex::<_, _, ()>(key, raw, ttl)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Keys matching `pattern`, walked with `SCAN`
+    async fn keys(&self, pattern: String) -> Result<Vec<String>, String> {
+        use futures::StreamExt;
+        use redis::AsyncCommands;
+
+        let mut connection = self.connection().await?;
+        let keys = connection
+            .scan_match::<_, String>(pattern)
+            .await
+            .map_err(|e| e.to_string())?
+            .collect()
+            .await;
+        Ok(keys)
+    }
+}
+
+#[cfg(feature = "redis")]
+impl EnsCache for RedisEnsCache {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    fn get<'a>(
+        &'a self,
+        node: &'a str,
+    ) -> BoxFuture<'a, Result<Option<CacheEntry<Address>>, String>> {
+        Box::pin(self.load(self.forward_key(node)))
+    }
+
+    fn set<'a>(
+        &'a self,
+        node: &'a str,
+        entry: CacheEntry<Address>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move { self.store(self.forward_key(node), &entry).await })
+    }
+
+    fn get_reverse<'a>(
+        &'a self,
+        address: &'a Address,
+    ) -> BoxFuture<'a, Result<Option<CacheEntry<EnsName>>, String>> {
+        Box::pin(self.load(self.reverse_key(address)))
+    }
+
+    fn set_reverse<'a>(
+        &'a self,
+        address: &'a Address,
+        entry: CacheEntry<EnsName>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move { self.store(self.reverse_key(address), &entry).await })
+    }
+
+    fn sizes(&self) -> BoxFuture<'_, Result<(usize, usize), String>> {
+        Box::pin(async move {
+            let forward = self.keys(format!("{}fwd:*", self.prefix)).await?;
+            let reverse = self.keys(format!("{}rev:*", self.prefix)).await?;
+            Ok((forward.len(), reverse.len()))
+        })
+    }
+
+    fn clear(&self) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(async move {
+            use redis::AsyncCommands;
+
+            let keys = self.keys(format!("{}*", self.prefix)).await?;
+            if keys.is_empty() {
+                return Ok(());
+            }
+            let mut connection = self.connection().await?;
+            connection
+                .del::<_, ()>(keys)
+                .await
+                .map_err(|e| e.to_string())
+        })
+    }
+}
+
+/// The cache `backend` selects; `redis_url` is required for Redis
+pub fn from_backend(
+    backend: EnsCacheBackend,
+    redis_url: Option<&str>,
+) -> Result<Arc<dyn EnsCache>, String> {
+    match backend {
+        EnsCacheBackend::Memory => Ok(Arc::new(MemoryEnsCache::new())),
+        #[cfg(feature = "redis")]
+        EnsCacheBackend::Redis => {
+            let url = redis_url.ok_or("the redis ENS cache needs ENS_CACHE_REDIS_URL")?;
+            Ok(Arc::new(RedisEnsCache::open(url)?))
+        }
+        #[cfg(not(feature = "redis"))]
+        EnsCacheBackend::Redis => {
+            let _ = redis_url;
+            Err("the redis ENS cache requires the redis feature".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address() -> Address {
+        Address::try_from("0x1234567890abcdef1234567890abcdef12345678").unwrap()
+    }
+
+    fn name() -> EnsName {
+        EnsName::try_from("cache.eth").unwrap()
+    }
+
+    /// Round trip through any backend, through the trait only
+    async fn exercise(cache: &dyn EnsCache) {
+        assert_eq!(cache.get("0xnode").await, Ok(None));
+        assert_eq!(cache.get_reverse(&address()).await, Ok(None));
+
+        let forward = CacheEntry::new(
+            address(),
+            Some("https://avatar".to_string()),
+            Duration::from_secs(60),
+        );
+        cache.set("0xnode", forward.clone()).await.unwrap();
+        let reverse = CacheEntry::new(name(), None, Duration::from_secs(60));
+        cache
+            .set_reverse(&address(), reverse.clone())
+            .await
+            .unwrap();
+
+        let cached = cache.get("0xnode").await.unwrap().unwrap();
+        assert_eq!(cached, forward);
+        assert!(cached.is_fresh());
+        // Reverse entries are found whatever the case of the address
+        let upper = Address::try_from("0x1234567890ABCDEF1234567890ABCDEF12345678").unwrap();
+        assert_eq!(cache.get_reverse(&upper).await, Ok(Some(reverse)));
+        assert_eq!(cache.sizes().await, Ok((1, 1)));
+
+        cache.clear().await.unwrap();
+        assert_eq!(cache.get("0xnode").await, Ok(None));
+        assert_eq!(cache.sizes().await, Ok((0, 0)));
+    }
+
+    #[tokio::test]
+    async fn test_memory_cache_through_the_trait() {
+        let cache = from_backend(EnsCacheBackend::Memory, None).unwrap();
+        assert_eq!(cache.name(), "memory");
+        exercise(cache.as_ref()).await;
+
+        // Expired entries are kept for stale-while-revalidate
+        let expired = CacheEntry::new(address(), None, Duration::ZERO);
+        cache.set("0xold", expired).await.unwrap();
+        let kept = cache.get("0xold").await.unwrap().unwrap();
+        assert!(!kept.is_fresh());
+    }
+
+    #[test]
+    fn test_parse_backend() {
+        assert_eq!(
+            EnsCacheBackend::parse(" Memory "),
+            Some(EnsCacheBackend::Memory)
+        );
+        assert_eq!(
+            EnsCacheBackend::parse("redis"),
+            Some(EnsCacheBackend::Redis)
+        );
+        assert_eq!(EnsCacheBackend::parse("memcached"), None);
+        assert!(from_backend(EnsCacheBackend::Redis, None).is_err());
+    }
+
+    /// Needs a Redis server: `TEST_REDIS_URL=redis://127.0.0.1:6379 cargo
+    /// test --features redis -- --ignored`
+    #[cfg(feature = "redis")]
+    #[tokio::test]
+    #[ignore = "needs a Redis server at TEST_REDIS_URL"]
+    async fn test_redis_cache_through_the_trait() {
+        let url = std::env::var("TEST_REDIS_URL")
+            .unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+        let prefix = format!("settleone-test:{}:", uuid::Uuid::new_v4());
+        let cache = RedisEnsCache::open(&url).unwrap().with_prefix(&prefix);
+        exercise(&cache).await;
+
+        // Redis expires entries itself
+        let short = CacheEntry::new(address(), None, Duration::from_millis(100));
+        cache.set("0xshort", short).await.unwrap();
+        assert!(cache.get("0xshort").await.unwrap().is_some());
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(cache.get("0xshort").await, Ok(None));
+    }
+
+    #[cfg(feature = "redis")]
+    #[tokio::test]
+    async fn test_unreachable_redis_is_an_error_not_a_panic() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("redis://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let cache = RedisEnsCache::open(&url).unwrap();
+        assert!(cache.get("0xnode").await.is_err());
+        assert!(RedisEnsCache::open("not a url").is_err());
+    }
+}
@@ -0,0 +1,246 @@
+//! Fiat exchange rates, for showing session totals in a client's currency
+//!
+//! [`FiatRateSource`] is a [`PriceSource`] whose tokens are lowercased ISO
+//! 4217 codes on [`FIAT_CHAIN_ID`], priced like any token: the USD value of
+//! one unit. A [`PriceService`] of its own caches the rates, so a rate older
+//! than `FX_RATE_MAX_AGE_SECS` is refetched. Settlement tokens are dollar
+//! stablecoins, so a session total converts at one token per dollar.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+
+use crate::models::session::{currency_minor_units, Session};
+use crate::services::prices::{PriceService, PriceSource};
+use crate::telemetry;
+use crate::utils::amounts::format_units;
+use crate::utils::bps::{mul_div, RoundingMode};
+use settleone_types::api::TotalDisplay;
+
+/// Default `FX_RATE_URL`: Coinbase's rates for one US dollar
+pub const DEFAULT_FX_RATE_URL: &str = "https://api.coinbase.com/v2/exchange-rates?currency=USD";
+
+/// Default age until an exchange rate is refetched (`FX_RATE_MAX_AGE_SECS`)
+pub const DEFAULT_FX_RATE_MAX_AGE: Duration = Duration::from_secs(3600);
+
+/// Pseudo chain id fiat currencies are priced on
+pub const FIAT_CHAIN_ID: u64 = 0;
+
+/// Decimals exchange rates are fixed to before converting
+const RATE_DECIMALS: u8 = 8;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+struct RatesResponse {
+    data: RatesData,
+}
+
+#[derive(Deserialize)]
+struct RatesData {
+    /// Units of each currency per US dollar, as decimal strings
+    rates: HashMap<String, String>,
+}
+
+/// USD exchange rates from a Coinbase-style `exchange-rates` endpoint
+pub struct FiatRateSource {
+    http_client: reqwest::Client,
+    url: String,
+}
+
+impl FiatRateSource {
+    pub fn new(url: &str, user_agent: &str) -> Self {
+        Self {
+            http_client: telemetry::http_client(user_agent, Some(REQUEST_TIMEOUT)),
+            url: url.to_string(),
+        }
+    }
+
+    async fn rates(&self) -> Result<HashMap<String, String>, String> {
+        let request = self.http_client.get(&self.url);
+        let response = telemetry::send("fx", &self.http_client, request)
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status().as_u16()));
+        }
+        let body: RatesResponse = response.json().await.map_err(|e| e.to_string())?;
+        Ok(body.data.rates)
+    }
+}
+
+impl PriceSource for FiatRateSource {
+    fn name(&self) -> &'static str {
+        "fx"
+    }
+
+    /// One request for every currency; a currency's USD price is the
+    /// inverse of its rate
+    fn fetch<'a>(
+        &'a self,
+        _chain_id: u64,
+        tokens: &'a [String],
+    ) -> BoxFuture<'a, Result<HashMap<String, f64>, String>> {
+        Box::pin(async move {
+            let rates = self.rates().await?;
+            Ok(tokens
+                .iter()
+                .filter_map(|code| {
+                    let rate: f64 = rates.get(&code.to_uppercase())?.parse().ok()?;
+                    (rate > 0.0).then(|| (code.clone(), 1.0 / rate))
+                })
+                .collect())
+        })
+    }
+}
+
+/// `session`'s total in its `display_currency`, or `None` if it has none
+/// or no rate is available
+pub async fn total_display(rates: &PriceService, session: &Session) -> Option<TotalDisplay> {
+    let currency = session.display_currency.as_deref()?;
+    let quote = match rates.get(FIAT_CHAIN_ID, currency).await {
+        Ok(quote) => quote,
+        Err(e) => {
+            tracing::warn!(currency, error = %e, "No exchange rate for session total");
+            return None;
+        }
+    };
+    let rate_scale = 10u128.pow(RATE_DECIMALS.into());
+    let rate = (rate_scale as f64 / quote.usd).round() as u128;
+    let amount = convert(
+        u128::try_from(session.total_amount).ok()?,
+        session.token_decimals,
+        rate,
+        currency_minor_units(currency),
+    )?;
+    Some(TotalDisplay {
+        currency: currency.to_string(),
+        amount,
+        rate: format_units(rate.into(), RATE_DECIMALS),
+        rate_timestamp: quote.timestamp,
+    })
+}
+
+/// Convert `amount` base units (of `decimals`) at `rate` (with
+/// [`RATE_DECIMALS`]), rounded half up to `places` fractional digits
+fn convert(amount: u128, decimals: u8, rate: u128, places: u8) -> Option<String> {
+    let numerator = rate.checked_mul(10u128.checked_pow(places.into())?)?;
+    let denominator = 10u128.checked_pow(u32::from(decimals) + u32::from(RATE_DECIMALS))?;
+    let minor = mul_div(amount, numerator, denominator, RoundingMode::HalfUp)?;
+    if places == 0 {
+        return Some(minor.to_string());
+    }
+    let scale = 10u128.pow(places.into());
+    Some(format!(
+        "{}.{:0width$}",
+        minor / scale,
+        minor % scale,
+        width = places as usize
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use settleone_types::fixtures::{SessionBuilder, ALICE};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn eur_session(total: &str) -> Session {
+        let mut session = SessionBuilder::new().payment(ALICE, total).build();
+        session.display_currency = Some("EUR".to_string());
+        session
+    }
+
+    async fn stub_rates(server: &MockServer, eur: &str) {
+        Mock::given(method("GET"))
+            .and(path("/v2/exchange-rates"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": { "currency": "USD", "rates": { "EUR": eur, "JPY": "151.2" } }
+            })))
+            .up_to_n_times(1)
+            .mount(server)
+            .await;
+    }
+
+    fn rates_service(server: &MockServer, max_age: Duration) -> PriceService {
+        let source = FiatRateSource::new(
+            &format!("{}/v2/exchange-rates?currency=USD", server.uri()),
+            "test",
+        );
+        PriceService::new(vec![Arc::new(source)]).with_staleness(max_age, max_age)
+    }
+
+    #[test]
+    fn test_convert_rounds_to_minor_units() {
+        // 12.5 USDC at 0.92 EUR/USD
+        assert_eq!(
+            convert(12_500_000, 6, 92_000_000, 2).as_deref(),
+            Some("11.50")
+        );
+        // 0.005 rounds half up; JPY has no minor unit
+        assert_eq!(convert(5_000, 6, 100_000_000, 2).as_deref(), Some("0.01"));
+        assert_eq!(
+            convert(2_500_000, 6, 15_120_000_000, 0).as_deref(),
+            Some("378")
+        );
+        assert_eq!(convert(0, 18, 92_000_000, 2).as_deref(), Some("0.00"));
+    }
+
+    #[tokio::test]
+    async fn test_total_display_from_mocked_rates() {
+        let server = MockServer::start().await;
+        stub_rates(&server, "0.92").await;
+        let rates = rates_service(&server, DEFAULT_FX_RATE_MAX_AGE);
+
+        let display = total_display(&rates, &eur_session("12500000"))
+            .await
+            .unwrap();
+        assert_eq!(
+            (
+                display.currency.as_str(),
+                display.amount.as_str(),
+                display.rate.as_str()
+            ),
+            ("EUR", "11.50", "0.92")
+        );
+
+        // No display currency, or no rate for it: no block
+        let plain = SessionBuilder::new().payment(ALICE, "1000000").build();
+        assert_eq!(total_display(&rates, &plain).await, None);
+        let mut chf = eur_session("1000000");
+        chf.display_currency = Some("CHF".to_string());
+        assert_eq!(total_display(&rates, &chf).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_stale_rate_is_refreshed() {
+        let server = MockServer::start().await;
+        stub_rates(&server, "0.92").await;
+        stub_rates(&server, "0.95").await;
+        let rates = rates_service(&server, Duration::from_millis(200));
+        let session = eur_session("10000000");
+
+        let first = total_display(&rates, &session).await.unwrap();
+        assert_eq!(first.amount, "9.20");
+        // Served from the cache while fresh
+        let cached = total_display(&rates, &session).await.unwrap();
+        assert_eq!(cached, first);
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        let refreshed = total_display(&rates, &session).await.unwrap();
+        assert_eq!(
+            (refreshed.amount.as_str(), refreshed.rate.as_str()),
+            ("9.50", "0.95")
+        );
+        assert!(refreshed.rate_timestamp > first.rate_timestamp);
+
+        // A stale rate that cannot be refreshed is not used
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(total_display(&rates, &session).await, None);
+    }
+}
@@ -0,0 +1,449 @@
+//! Dependency readiness checks
+//!
+//! Probes every upstream the backend needs to serve traffic (ENS API,
+//! LI.FI, Arc RPC and the session store, for the integrations compiled in)
+//! concurrently, each with a short timeout. Results are cached briefly so that aggressive probe intervals
+//! do not turn into a storm of upstream requests. Until startup
+//! initialization has finished (see [`Initialized`]) the backend reports
+//! not ready without probing anything. Every probe is also recorded in a
+//! bounded [`HealthHistory`], which logs and counts up/down transitions.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use futures::future::{join_all, BoxFuture, FutureExt};
+use serde::Serialize;
+#[cfg(feature = "settlement")]
+use serde_json::json;
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::config::Config;
+use crate::services::session::SessionStore;
+use crate::telemetry;
+
+/// Timeout applied to each individual dependency check
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a readiness report is reused before re-probing
+const REPORT_TTL: Duration = Duration::from_secs(5);
+
+/// Default number of checks kept per dependency
+pub const DEFAULT_HEALTH_HISTORY_SIZE: usize = 100;
+
+/// Startup gate, set once the state the server needs (e.g. the restored
+/// session snapshot) is loaded
+#[derive(Debug, Default)]
+pub struct Initialized(AtomicBool);
+
+impl Initialized {
+    /// Mark startup initialization as finished
+    pub fn set(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// Status of a single dependency check
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DependencyStatus {
+    /// "up" or "down"
+    #[schema(value_type = String)]
+    pub status: &'static str,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DependencyStatus {
+    pub fn is_up(&self) -> bool {
+        self.status == "up"
+    }
+}
+
+/// Aggregated readiness report
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReadinessReport {
+    /// "ready" or "not_ready"
+    #[schema(value_type = String)]
+    pub status: &'static str,
+    #[schema(value_type = BTreeMap<String, DependencyStatus>)]
+    pub checks: BTreeMap<&'static str, DependencyStatus>,
+    #[schema(value_type = Vec<String>)]
+    pub failures: Vec<&'static str>,
+}
+
+impl ReadinessReport {
+    pub fn is_ready(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Report while startup initialization is still running
+    fn initializing() -> Self {
+        let startup = DependencyStatus {
+            status: "down",
+            latency_ms: 0,
+            error: Some("Startup initialization in progress".to_string()),
+        };
+        Self {
+            status: "not_ready",
+            checks: BTreeMap::from([("startup", startup)]),
+            failures: vec!["startup"],
+        }
+    }
+}
+
+/// One recorded dependency check
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct HealthSample {
+    pub at: DateTime<Utc>,
+    pub up: bool,
+    pub latency_ms: u64,
+}
+
+/// Recent checks of one dependency
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DependencyHistory {
+    /// "up" or "down" at the latest check
+    #[schema(value_type = String)]
+    pub status: &'static str,
+    /// Share of the recorded checks that were up, in percent
+    pub uptime_percent: f64,
+    /// When the status last changed; null if it has not changed since startup
+    pub last_transition_at: Option<DateTime<Utc>>,
+    /// Recorded checks, oldest first
+    pub checks: Vec<HealthSample>,
+}
+
+/// Recent checks of every probed dependency
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HealthHistoryReport {
+    /// Checks kept per dependency (`HEALTH_HISTORY_SIZE`)
+    pub window: usize,
+    #[schema(value_type = BTreeMap<String, DependencyHistory>)]
+    pub dependencies: BTreeMap<&'static str, DependencyHistory>,
+}
+
+/// The last checks of one dependency, with a running count of the up ones
+#[derive(Default)]
+struct Ring {
+    samples: VecDeque<HealthSample>,
+    up: usize,
+    last_transition_at: Option<DateTime<Utc>>,
+}
+
+/// Per-dependency ring buffers of the last `size` check results
+pub struct HealthHistory {
+    size: usize,
+    rings: std::sync::Mutex<BTreeMap<&'static str, Ring>>,
+}
+
+impl HealthHistory {
+    /// Keep the last `size` checks (at least one) of each dependency
+    pub fn new(size: usize) -> Self {
+        Self {
+            size: size.max(1),
+            rings: std::sync::Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record a check of `name`; returns whether it flipped between up and
+    /// down (the first check of a dependency is not a transition)
+    pub fn record(&self, name: &'static str, sample: HealthSample) -> bool {
+        let mut rings = self.rings.lock().expect("health history lock poisoned");
+        let ring = rings.entry(name).or_default();
+        let changed = ring.samples.back().is_some_and(|last| last.up != sample.up);
+        if changed {
+            ring.last_transition_at = Some(sample.at);
+        }
+        if ring.samples.len() == self.size {
+            if let Some(evicted) = ring.samples.pop_front() {
+                ring.up -= usize::from(evicted.up);
+            }
+        }
+        ring.up += usize::from(sample.up);
+        ring.samples.push_back(sample);
+        changed
+    }
+
+    pub fn report(&self) -> HealthHistoryReport {
+        let rings = self.rings.lock().expect("health history lock poisoned");
+        let dependencies = rings
+            .iter()
+            .filter_map(|(name, ring)| {
+                let last = ring.samples.back()?;
+                let history = DependencyHistory {
+                    status: if last.up { "up" } else { "down" },
+                    uptime_percent: ring.up as f64 * 100.0 / ring.samples.len() as f64,
+                    last_transition_at: ring.last_transition_at,
+                    checks: ring.samples.iter().cloned().collect(),
+                };
+                Some((*name, history))
+            })
+            .collect();
+        HealthHistoryReport {
+            window: self.size,
+            dependencies,
+        }
+    }
+}
+
+/// Readiness checker with a short-lived report cache
+pub struct ReadinessService {
+    #[cfg_attr(
+        not(any(feature = "ens", feature = "lifi", feature = "settlement")),
+        allow(dead_code)
+    )]
+    http_client: reqwest::Client,
+    #[cfg(feature = "ens")]
+    ens_api_url: String,
+    #[cfg(feature = "lifi")]
+    lifi_api_url: String,
+    #[cfg(feature = "settlement")]
+    arc_rpc_url: String,
+    session_store: Arc<SessionStore>,
+    initialized: Arc<Initialized>,
+    cached: Mutex<Option<(Instant, ReadinessReport)>>,
+    report_ttl: Duration,
+    history: HealthHistory,
+}
+
+impl ReadinessService {
+    /// Create a readiness checker for the configured dependencies
+    pub fn new(
+        config: &Config,
+        session_store: Arc<SessionStore>,
+        initialized: Arc<Initialized>,
+    ) -> Self {
+        Self {
+            http_client: telemetry::http_client(&config.http_user_agent, Some(CHECK_TIMEOUT)),
+            #[cfg(feature = "ens")]
+            ens_api_url: config.ens_api_url.clone(),
+            #[cfg(feature = "lifi")]
+            lifi_api_url: config.lifi_api_url.clone(),
+            #[cfg(feature = "settlement")]
+            arc_rpc_url: config.arc_rpc_url.clone(),
+            session_store,
+            initialized,
+            cached: Mutex::new(None),
+            report_ttl: REPORT_TTL,
+            history: HealthHistory::new(config.health_history_size),
+        }
+    }
+
+    /// Reuse reports for `ttl` instead of 5 seconds
+    #[cfg(test)]
+    pub fn with_report_ttl(mut self, ttl: Duration) -> Self {
+        self.report_ttl = ttl;
+        self
+    }
+
+    /// Recent checks of every dependency probed so far
+    pub fn history(&self) -> HealthHistoryReport {
+        self.history.report()
+    }
+
+    /// Return the cached report if fresh, otherwise probe all dependencies
+    pub async fn check(&self) -> ReadinessReport {
+        if !self.initialized.is_set() {
+            return ReadinessReport::initializing();
+        }
+
+        // Holding the lock across the probe coalesces concurrent callers
+        let mut cached = self.cached.lock().await;
+        if let Some((at, report)) = cached.as_ref() {
+            if at.elapsed() < self.report_ttl {
+                return report.clone();
+            }
+        }
+
+        let probes: Vec<(&'static str, BoxFuture<'_, DependencyStatus>)> = vec![
+            #[cfg(feature = "ens")]
+            (
+                "ens",
+                timed(self.check_http("ensdata", &self.ens_api_url)).boxed(),
+            ),
+            #[cfg(feature = "lifi")]
+            (
+                "lifi",
+                timed(self.check_http("lifi", &self.lifi_api_url)).boxed(),
+            ),
+            #[cfg(feature = "settlement")]
+            ("arc_rpc", timed(self.check_rpc()).boxed()),
+            ("session_store", timed(self.check_session_store()).boxed()),
+        ];
+        let (names, probes): (Vec<_>, Vec<_>) = probes.into_iter().unzip();
+        let checks: BTreeMap<_, _> = names.into_iter().zip(join_all(probes).await).collect();
+        let failures: Vec<&'static str> = checks
+            .iter()
+            .filter(|(_, status)| !status.is_up())
+            .map(|(name, _)| *name)
+            .collect();
+
+        for name in &failures {
+            tracing::warn!(
+                "Readiness check failed for {}: {:?}",
+                name,
+                checks[name].error
+            );
+        }
+        let now = Utc::now();
+        for (name, status) in &checks {
+            let sample = HealthSample {
+                at: now,
+                up: status.is_up(),
+                latency_ms: status.latency_ms,
+            };
+            if self.history.record(name, sample) {
+                record_transition(name, status);
+            }
+        }
+
+        let report = ReadinessReport {
+            status: if failures.is_empty() {
+                "ready"
+            } else {
+                "not_ready"
+            },
+            checks,
+            failures,
+        };
+
+        *cached = Some((Instant::now(), report.clone()));
+        report
+    }
+
+    /// Any non-5xx HTTP response means the upstream is reachable
+    #[cfg(any(feature = "ens", feature = "lifi"))]
+    async fn check_http(&self, upstream: &'static str, url: &str) -> Result<(), String> {
+        let request = self.http_client.get(url);
+        let response = telemetry::send(upstream, &self.http_client, request)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if response.status().is_server_error() {
+            return Err(format!("Status: {}", response.status()));
+        }
+        Ok(())
+    }
+
+    /// Arc RPC must answer `eth_chainId` with a result
+    #[cfg(feature = "settlement")]
+    async fn check_rpc(&self) -> Result<(), String> {
+        let request = self.http_client.post(&self.arc_rpc_url).json(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_chainId",
+            "params": [],
+        }));
+        let response = telemetry::send("arc_rpc", &self.http_client, request)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if !response.status().is_success() {
+            return Err(format!("Status: {}", response.status()));
+        }
+
+        let data: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+        match data["result"].as_str() {
+            Some(_) => Ok(()),
+            None => Err(format!("Unexpected eth_chainId response: {}", data)),
+        }
+    }
+
+    /// The in-memory store is healthy as long as its lock can be acquired
+    async fn check_session_store(&self) -> Result<(), String> {
+        self.session_store.ping().await;
+        Ok(())
+    }
+}
+
+/// Log and count a dependency going up or down
+fn record_transition(name: &'static str, status: &DependencyStatus) {
+    metrics::counter!("health_transitions_total", "dependency" => name, "to" => status.status)
+        .increment(1);
+    if status.is_up() {
+        tracing::info!(dependency = name, "Dependency {} is back up", name);
+    } else {
+        tracing::warn!(
+            dependency = name,
+            "Dependency {} went down: {}",
+            name,
+            status.error.as_deref().unwrap_or("unknown error")
+        );
+    }
+}
+
+/// Run a check under the per-check timeout and record its latency
+async fn timed<F>(check: F) -> DependencyStatus
+where
+    F: std::future::Future<Output = Result<(), String>>,
+{
+    let start = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("Timed out after {}ms", CHECK_TIMEOUT.as_millis())),
+    };
+    let latency_ms = start.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(()) => DependencyStatus {
+            status: "up",
+            latency_ms,
+            error: None,
+        },
+        Err(e) => DependencyStatus {
+            status: "down",
+            latency_ms,
+            error: Some(e),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(up: bool, secs: i64) -> HealthSample {
+        HealthSample {
+            at: DateTime::from_timestamp(secs, 0).unwrap(),
+            up,
+            latency_ms: 1,
+        }
+    }
+
+    #[test]
+    fn test_history_is_bounded_and_tracks_uptime() {
+        let history = HealthHistory::new(4);
+        let flaps = [true, false, false, true, true, true];
+        let transitions: Vec<bool> = flaps
+            .iter()
+            .enumerate()
+            .map(|(i, &up)| history.record("rpc", sample(up, i as i64)))
+            .collect();
+        assert_eq!(transitions, [false, true, false, true, false, false]);
+
+        let report = history.report();
+        assert_eq!(report.window, 4);
+        let rpc = &report.dependencies["rpc"];
+        // Only the last four checks remain: down, up, up, up
+        assert_eq!(rpc.checks.len(), 4);
+        assert_eq!(rpc.checks[0], sample(false, 2));
+        assert_eq!(rpc.uptime_percent, 75.0);
+        assert_eq!(rpc.status, "up");
+        assert_eq!(rpc.last_transition_at, Some(sample(true, 3).at));
+
+        // Dependencies are tracked independently
+        assert!(!history.record("ens", sample(false, 9)));
+        let report = history.report();
+        assert_eq!(report.dependencies["ens"].uptime_percent, 0.0);
+        assert_eq!(report.dependencies["ens"].last_transition_at, None);
+        assert_eq!(HealthHistory::new(0).report().window, 1);
+    }
+}
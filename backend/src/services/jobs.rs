@@ -0,0 +1,292 @@
+//! Background jobs
+//!
+//! Periodic workers implement [`Job`]; [`JobRunner`] runs each one on its
+//! own interval, starting after a random delay so jobs do not fire in
+//! lockstep. A run that fails or panics is logged and recorded, and the
+//! loop carries on. Every loop stops when the shutdown token is cancelled.
+//! The outcome of the latest run of each job is served at `GET /admin/jobs`.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use futures::FutureExt;
+use rand::Rng;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use utoipa::ToSchema;
+
+use crate::reporting::{ErrorEvent, ErrorKind, ErrorReporter, NoopReporter};
+
+/// Context handed to every run of a job
+pub struct JobContext {
+    /// Cancelled when the process shuts down; long runs should check it
+    pub shutdown: CancellationToken,
+}
+
+/// A unit of periodic background work
+pub trait Job: Send + Sync + 'static {
+    /// Stable name, used in logs, metrics and `/admin/jobs`
+    fn name(&self) -> &'static str;
+
+    /// Time between the starts of consecutive runs
+    fn interval(&self) -> Duration;
+
+    /// Do one round of work
+    fn run(&self, ctx: &JobContext) -> impl Future<Output = Result<(), String>> + Send;
+}
+
+/// Latest state of a job, as reported by `/admin/jobs`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct JobStatus {
+    pub name: String,
+    pub interval_ms: u64,
+    /// Completed runs, failed ones included
+    pub runs: u64,
+    pub failures: u64,
+    pub running: bool,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<u64>,
+    /// Error (or panic message) of the latest run, cleared by a success
+    pub last_error: Option<String>,
+}
+
+/// Spawns jobs and keeps their status
+pub struct JobRunner {
+    shutdown: CancellationToken,
+    statuses: Arc<Mutex<BTreeMap<&'static str, JobStatus>>>,
+    reporter: Arc<dyn ErrorReporter>,
+}
+
+impl JobRunner {
+    /// Create a runner whose jobs stop when `shutdown` is cancelled
+    pub fn new(shutdown: CancellationToken) -> Self {
+        Self {
+            shutdown,
+            statuses: Arc::new(Mutex::new(BTreeMap::new())),
+            reporter: Arc::new(NoopReporter),
+        }
+    }
+
+    /// Report failed runs to `reporter`
+    pub fn with_reporter(mut self, reporter: Arc<dyn ErrorReporter>) -> Self {
+        self.reporter = reporter;
+        self
+    }
+
+    /// Run `job` every `job.interval()` until shutdown
+    pub fn spawn<J: Job>(&self, job: J) -> tokio::task::JoinHandle<()> {
+        let name = job.name();
+        let interval = job.interval().max(Duration::from_millis(1));
+        self.statuses.lock().unwrap().insert(
+            name,
+            JobStatus {
+                name: name.to_string(),
+                interval_ms: interval.as_millis() as u64,
+                runs: 0,
+                failures: 0,
+                running: false,
+                last_run_at: None,
+                last_duration_ms: None,
+                last_error: None,
+            },
+        );
+
+        let statuses = self.statuses.clone();
+        let reporter = self.reporter.clone();
+        let ctx = JobContext {
+            shutdown: self.shutdown.clone(),
+        };
+        let jitter = rand::thread_rng().gen_range(Duration::ZERO..interval);
+        tracing::info!("Starting job {} every {:?}", name, interval);
+
+        tokio::spawn(async move {
+            let mut ticker =
+                tokio::time::interval_at(tokio::time::Instant::now() + jitter, interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = ctx.shutdown.cancelled() => break,
+                    _ = ticker.tick() => {}
+                }
+                update(&statuses, name, |s| s.running = true);
+
+                let started_at = Utc::now();
+                let start = Instant::now();
+                let result = AssertUnwindSafe(job.run(&ctx))
+                    .catch_unwind()
+                    .await
+                    .unwrap_or_else(|panic| Err(format!("panicked: {}", panic_message(&*panic))));
+                let elapsed = start.elapsed();
+
+                let label = if result.is_ok() { "ok" } else { "error" };
+                metrics::counter!("job_runs_total", "job" => name, "result" => label).increment(1);
+                if let Err(ref e) = result {
+                    tracing::error!("Job {} failed: {}", name, e);
+                    reporter.report(ErrorEvent::new(ErrorKind::Job, e).with_context("job", name));
+                }
+                update(&statuses, name, |s| {
+                    s.running = false;
+                    s.runs += 1;
+                    s.last_run_at = Some(started_at);
+                    s.last_duration_ms = Some(elapsed.as_millis() as u64);
+                    match result {
+                        Ok(()) => s.last_error = None,
+                        Err(e) => {
+                            s.failures += 1;
+                            s.last_error = Some(e);
+                        }
+                    }
+                });
+            }
+            tracing::info!("Job {} stopped", name);
+        })
+    }
+
+    /// Status of every spawned job, sorted by name
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        self.statuses.lock().unwrap().values().cloned().collect()
+    }
+}
+
+fn update(
+    statuses: &Mutex<BTreeMap<&'static str, JobStatus>>,
+    name: &'static str,
+    f: impl FnOnce(&mut JobStatus),
+) {
+    if let Some(status) = statuses.lock().unwrap().get_mut(name) {
+        f(status);
+    }
+}
+
+/// Text of a panic payload (`panic!` with a literal or a formatted message)
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Counts its runs; fails or panics on the runs listed
+    struct CountingJob {
+        runs: Arc<AtomicU64>,
+        fail_on: u64,
+        panic_on: u64,
+    }
+
+    impl Job for CountingJob {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn interval(&self) -> Duration {
+            Duration::from_millis(10)
+        }
+
+        async fn run(&self, _ctx: &JobContext) -> Result<(), String> {
+            let run = self.runs.fetch_add(1, Ordering::SeqCst) + 1;
+            if run == self.panic_on {
+                panic!("boom on run {}", run);
+            }
+            if run == self.fail_on {
+                return Err(format!("failed run {}", run));
+            }
+            Ok(())
+        }
+    }
+
+    fn counting_job(fail_on: u64, panic_on: u64) -> (CountingJob, Arc<AtomicU64>) {
+        let runs = Arc::new(AtomicU64::new(0));
+        let job = CountingJob {
+            runs: runs.clone(),
+            fail_on,
+            panic_on,
+        };
+        (job, runs)
+    }
+
+    // Paused time: sleeps advance the clock instantly and deterministically
+    #[tokio::test(start_paused = true)]
+    async fn test_job_runs_repeatedly_until_shutdown() {
+        let shutdown = CancellationToken::new();
+        let runner = JobRunner::new(shutdown.clone());
+        let (job, runs) = counting_job(0, 0);
+        let handle = runner.spawn(job);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(runs.load(Ordering::SeqCst) >= 3);
+
+        shutdown.cancel();
+        handle.await.unwrap();
+        let stopped_at = runs.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), stopped_at);
+
+        let status = &runner.statuses()[0];
+        assert_eq!(status.name, "counting");
+        assert_eq!(status.interval_ms, 10);
+        assert_eq!(status.runs, stopped_at);
+        assert_eq!(status.failures, 0);
+        assert!(status.last_run_at.is_some());
+        assert!(status.last_error.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failures_and_panics_are_recorded_and_survived() {
+        let shutdown = CancellationToken::new();
+        let runner = JobRunner::new(shutdown.clone());
+        let (job, runs) = counting_job(1, 2);
+        runner.spawn(job);
+
+        // Step the clock until the panicking run has been recorded
+        let mut status = runner.statuses()[0].clone();
+        while status.runs < 2 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            status = runner.statuses()[0].clone();
+        }
+        assert_eq!(status.runs, 2);
+        assert_eq!(status.failures, 2);
+        assert_eq!(
+            status.last_error.as_deref(),
+            Some("panicked: boom on run 2")
+        );
+
+        // The loop keeps going and a success clears the error
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(runs.load(Ordering::SeqCst) >= 4);
+        let status = &runner.statuses()[0];
+        assert_eq!(status.failures, 2);
+        assert!(status.last_error.is_none());
+        shutdown.cancel();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failed_runs_are_reported() {
+        let shutdown = CancellationToken::new();
+        let reporter = crate::testing::CapturingReporter::default();
+        let runner = JobRunner::new(shutdown.clone()).with_reporter(Arc::new(reporter.clone()));
+        let (job, _) = counting_job(1, 2);
+        runner.spawn(job);
+
+        while runner.statuses()[0].runs < 3 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        shutdown.cancel();
+
+        let events = reporter.events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, ErrorKind::Job);
+        assert_eq!(events[0].message, "failed run 1");
+        assert_eq!(events[0].context["job"], "counting");
+        assert_eq!(events[1].message, "panicked: boom on run 2");
+    }
+}
@@ -1,28 +1,183 @@
 //! LI.FI cross-chain quote service
+//!
+//! LI.FI enforces a request budget per API key, shared by every instance,
+//! so outbound calls can be throttled by a process-wide token bucket: calls
+//! beyond the burst queue for a refilled token, and fail with
+//! [`LifiError::RateLimited`] when the queue is longer than a bound.
+//!
+//! With several API keys, calls take the keys in turn. A key answered with
+//! a 429 is parked for the `Retry-After` it was given and the call moves on
+//! to the next key; only with every key parked do calls go out without one.
 
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Deserialize;
 use thiserror::Error;
+use tokio::time::Instant;
 
 use crate::api::quote::QuoteRequest;
+use crate::services::circuit_breaker::{
+    CircuitBreaker, DEFAULT_COOLDOWN, DEFAULT_FAILURE_THRESHOLD,
+};
+use crate::telemetry::{self, TransportError, DEFAULT_USER_AGENT};
+
+/// Default outbound calls a full bucket allows at once
+pub const DEFAULT_RATE_LIMIT_BURST: u32 = 10;
+
+/// Default longest a call queues for a token before it is rejected
+pub const DEFAULT_RATE_LIMIT_MAX_WAIT: Duration = Duration::from_secs(2);
+
+/// Timeout for LI.FI API requests; cross-chain quotes can take seconds
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How long a key answered with a 429 is parked without a `Retry-After`
+const DEFAULT_KEY_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Shortest parking, so a `Retry-After: 0` cannot retry a key in a loop
+const MIN_KEY_COOLDOWN: Duration = Duration::from_secs(1);
 
 /// LI.FI service errors
 #[derive(Error, Debug)]
 pub enum LifiError {
-    #[error("No route available")]
-    #[allow(dead_code)]
+    #[error("No route available for this transfer")]
     NoRoute,
 
     #[error("API request failed: {0}")]
     ApiError(String),
 
+    /// LI.FI could not be reached: DNS, refused connection, TLS
+    #[error("Could not connect to LI.FI: {0}")]
+    ConnectError(String),
+
+    /// LI.FI did not answer within [`REQUEST_TIMEOUT`]
+    #[error("LI.FI timed out: {0}")]
+    TimeoutError(String),
+
+    /// LI.FI answered with an error status
+    #[error("LI.FI answered HTTP {0}")]
+    HttpStatusError(u16),
+
     #[error("Invalid chain: {0}")]
     #[allow(dead_code)]
     InvalidChain(String),
+
+    #[error("{0}")]
+    Unavailable(String),
+
+    #[error("Unexpected LI.FI response: {0}")]
+    ParseError(String),
+
+    /// The outbound budget is exhausted for longer than the queueing bound
+    #[error("LI.FI request budget exhausted; retry in {}s", .0.as_secs_f64().ceil())]
+    RateLimited(Duration),
+}
+
+impl From<TransportError> for LifiError {
+    fn from(e: TransportError) -> Self {
+        match e {
+            TransportError::Connect(cause) => LifiError::ConnectError(cause),
+            TransportError::Timeout(cause) => LifiError::TimeoutError(cause),
+            TransportError::Other(cause) => LifiError::ApiError(cause),
+        }
+    }
+}
+
+/// Error codes LI.FI uses when it finds no route (`1002` is "no available quotes")
+const NO_ROUTE_CODES: &[&str] = &["NO_ROUTE", "NO_POSSIBLE_ROUTE", "1002"];
+
+/// The parts of a LI.FI `/quote` response the backend relies on
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LifiQuote {
+    pub estimate: Estimate,
+    /// Tokens of the transfer (absent from some mocked responses)
+    #[serde(default)]
+    pub action: Option<Action>,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub included_steps: Vec<Step>,
+}
+
+/// Quote estimate: output amount, costs and duration
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Estimate {
+    pub to_amount: String,
+    pub execution_duration: u64,
+    #[serde(default)]
+    pub gas_costs: Vec<GasCost>,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub fee_costs: Vec<FeeCost>,
+}
+
+/// What the quote transfers: the tokens on each side
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Action {
+    pub from_token: Token,
+    pub to_token: Token,
+}
+
+/// A token as LI.FI describes it
+#[derive(Debug, Deserialize)]
+pub struct Token {
+    pub decimals: u8,
+    /// Decimal string, when LI.FI can price the token
+    #[serde(rename = "priceUSD")]
+    pub price_usd: Option<String>,
+}
+
+/// A token from LI.FI's `/token` endpoint, with its USD price
+#[derive(Debug, Deserialize)]
+struct TokenPrice {
+    /// Decimal string; missing for tokens LI.FI cannot price
+    #[serde(rename = "priceUSD")]
+    price_usd: Option<String>,
+}
+
+/// Gas cost of executing the route
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GasCost {
+    pub amount: String,
+    #[serde(rename = "amountUSD")]
+    #[allow(dead_code)]
+    pub amount_usd: Option<String>,
+}
+
+/// Protocol or bridge fee charged along the route
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+pub struct FeeCost {
+    pub name: String,
+    pub amount: String,
+    #[serde(default)]
+    pub included: bool,
+}
+
+/// One step (swap or bridge) of the route
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct Step {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub tool: String,
 }
 
 /// Quote result from LI.FI
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct QuoteResult {
     pub to_amount: String,
+    /// Decimals of the source token, if LI.FI reported them
+    pub from_decimals: Option<u8>,
+    /// Decimals of the destination token, if LI.FI reported them
+    pub to_decimals: Option<u8>,
+    /// USD price of one source token, if LI.FI reported it
+    pub from_price_usd: Option<f64>,
     pub estimated_gas: String,
     pub estimated_time: u64,
     pub route: Option<serde_json::Value>,
@@ -30,79 +185,423 @@ pub struct QuoteResult {
 
 /// LI.FI service
 pub struct LifiService {
+    http_client: reqwest::Client,
     api_url: String,
-    api_key: Option<String>,
+    /// API keys, taken in turn
+    keys: KeyPool,
+    /// Circuit breaker for the LI.FI API
+    breaker: CircuitBreaker,
+    /// Outbound request budget (unlimited if unset)
+    limiter: Option<TokenBucket>,
+}
+
+/// Token bucket shared by all outbound calls of one service.
+///
+/// Tokens are reserved up front and may go negative: a negative balance is
+/// the queue of callers waiting for refills, served in arrival order.
+struct TokenBucket {
+    /// Tokens added per second
+    refill_per_sec: f64,
+    burst: f64,
+    max_wait: Duration,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(per_minute: u32, burst: u32, max_wait: Duration) -> Self {
+        let burst = f64::from(burst.max(1));
+        Self {
+            refill_per_sec: f64::from(per_minute) / 60.0,
+            burst,
+            max_wait,
+            state: Mutex::new(BucketState {
+                tokens: burst,
+                updated_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Reserve a token at `now`, returning how long to wait before using it;
+    /// fails with the wait, reserving nothing, if that exceeds `max_wait`
+    fn reserve(&self, now: Instant) -> Result<Duration, Duration> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = now.saturating_duration_since(state.updated_at);
+        state.tokens = (state.tokens + elapsed.as_secs_f64() * self.refill_per_sec).min(self.burst);
+        state.updated_at = now;
+
+        let wait = if state.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - state.tokens) / self.refill_per_sec)
+        };
+        if wait > self.max_wait {
+            return Err(wait);
+        }
+        state.tokens -= 1.0;
+        Ok(wait)
+    }
+
+    /// Wait for a token, or fail if the queue is too long
+    async fn acquire(&self) -> Result<(), LifiError> {
+        match self.reserve(Instant::now()) {
+            Ok(wait) if wait.is_zero() => Ok(()),
+            Ok(wait) => {
+                metrics::counter!("lifi_rate_limited_total", "result" => "delayed").increment(1);
+                tokio::time::sleep(wait).await;
+                Ok(())
+            }
+            Err(wait) => {
+                metrics::counter!("lifi_rate_limited_total", "result" => "rejected").increment(1);
+                Err(LifiError::RateLimited(wait))
+            }
+        }
+    }
+}
+
+/// API keys used round-robin, each parked for a cooldown after a 429.
+///
+/// Metrics and logs name keys by their index, never their value.
+struct KeyPool {
+    keys: Vec<String>,
+    /// Index the next lookup starts from
+    next: AtomicUsize,
+    /// Per key, when it may be used again
+    parked_until: Mutex<Vec<Option<Instant>>>,
+}
+
+impl KeyPool {
+    fn new(keys: Vec<String>) -> Self {
+        let parked_until = Mutex::new(vec![None; keys.len()]);
+        Self {
+            keys,
+            next: AtomicUsize::new(0),
+            parked_until,
+        }
+    }
+
+    /// The next key not parked at `now`, with its index; `None` without
+    /// keys or with every key parked
+    fn next(&self, now: Instant) -> Option<(usize, &str)> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        let parked_until = self.parked_until.lock().unwrap_or_else(|e| e.into_inner());
+        let start = self.next.load(Ordering::Relaxed);
+        (0..self.keys.len())
+            .map(|offset| (start + offset) % self.keys.len())
+            .find(|index| parked_until[*index].is_none_or(|until| until <= now))
+            .map(|index| {
+                // Continue after the key handed out, skipping parked ones
+                self.next.store(index + 1, Ordering::Relaxed);
+                (index, self.keys[index].as_str())
+            })
+    }
+
+    /// Keep key `index` out of rotation for `cooldown` from `now`
+    fn park(&self, index: usize, now: Instant, cooldown: Duration) {
+        let mut parked_until = self.parked_until.lock().unwrap_or_else(|e| e.into_inner());
+        parked_until[index] = Some(now + cooldown.max(MIN_KEY_COOLDOWN));
+    }
 }
 
 impl LifiService {
     /// Create a new LI.FI service
+    #[allow(dead_code)]
     pub fn new() -> Self {
         let api_url =
             std::env::var("LIFI_API_URL").unwrap_or_else(|_| "https://li.quest/v1".to_string());
         let api_key = std::env::var("LIFI_API_KEY").ok();
 
-        Self { api_url, api_key }
+        Self::with_api(&api_url, api_key)
     }
 
-    /// Get a cross-chain quote
+    /// Create a LI.FI service for a specific API URL and key
+    pub fn with_api(api_url: &str, api_key: Option<String>) -> Self {
+        Self {
+            http_client: telemetry::http_client(DEFAULT_USER_AGENT, Some(REQUEST_TIMEOUT)),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            keys: KeyPool::new(api_key.into_iter().collect()),
+            breaker: CircuitBreaker::new("lifi", DEFAULT_FAILURE_THRESHOLD, DEFAULT_COOLDOWN),
+            limiter: None,
+        }
+    }
+
+    /// Take turns between `keys` (`LIFI_API_KEYS`) instead of one key
+    pub fn with_api_keys(mut self, keys: Vec<String>) -> Self {
+        self.keys = KeyPool::new(keys);
+        self
+    }
+
+    /// Allow `per_minute` outbound calls with bursts of up to `burst`,
+    /// queueing excess calls for at most `max_wait`; 0 disables the limit
+    pub fn with_rate_limit(mut self, per_minute: u32, burst: u32, max_wait: Duration) -> Self {
+        self.limiter = (per_minute > 0).then(|| TokenBucket::new(per_minute, burst, max_wait));
+        self
+    }
+
+    /// Open the API circuit after `threshold` consecutive failures for `cooldown`
+    pub fn with_circuit_breaker(mut self, threshold: u32, cooldown: Duration) -> Self {
+        self.breaker = CircuitBreaker::new("lifi", threshold, cooldown);
+        self
+    }
+
+    /// Send `user_agent` as the `User-Agent` of API requests
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.http_client = telemetry::http_client(user_agent, Some(REQUEST_TIMEOUT));
+        self
+    }
+
+    /// Get a cross-chain quote, failing fast while the LI.FI circuit is open
+    /// or the outbound budget is exhausted
     pub async fn get_quote(&self, params: &QuoteRequest) -> Result<QuoteResult, LifiError> {
-        let client = reqwest::Client::new();
+        // Throttle before taking the breaker so a queued call never holds
+        // the half-open probe
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire().await?;
+        }
+        self.breaker
+            .try_acquire()
+            .map_err(|open| LifiError::Unavailable(open.to_string()))?;
 
-        let mut request = client.get(format!("{}/quote", self.api_url)).query(&[
-            ("fromChain", &params.from_chain),
-            ("toChain", &params.to_chain),
-            ("fromToken", &params.from_token),
-            ("toToken", &params.to_token),
-            ("fromAmount", &params.from_amount),
-        ]);
+        self.request_quote(params).await
+    }
 
-        if let Some(ref from_address) = params.from_address {
-            request = request.query(&[("fromAddress", from_address)]);
+    /// Approximate USD price of `token` on `chain_id`, or `None` if LI.FI
+    /// does not know the token or has no price for it. Throttled and
+    /// circuit broken like quotes.
+    pub async fn token_price(&self, chain_id: u64, token: &str) -> Result<Option<f64>, LifiError> {
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire().await?;
         }
+        self.breaker
+            .try_acquire()
+            .map_err(|open| LifiError::Unavailable(open.to_string()))?;
 
-        if let Some(ref api_key) = self.api_key {
-            request = request.header("x-lifi-api-key", api_key);
+        let chain = chain_id.to_string();
+        let response = self
+            .send_keyed(|| {
+                self.http_client
+                    .get(format!("{}/token", self.api_url))
+                    .query(&[("chain", chain.as_str()), ("token", token)])
+            })
+            .await
+            .map_err(|e| {
+                self.breaker.record_failure();
+                LifiError::from(TransportError::classify(&e))
+            })?;
+        if response.status().is_server_error() {
+            self.breaker.record_failure();
+        } else {
+            self.breaker.record_success();
         }
 
-        let response = request
-            .send()
+        // LI.FI answers unknown tokens with a 404 (or a 400 for malformed ones)
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND || status == reqwest::StatusCode::BAD_REQUEST {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(LifiError::HttpStatusError(status.as_u16()));
+        }
+        let token: TokenPrice = response
+            .json()
             .await
-            .map_err(|e| LifiError::ApiError(e.to_string()))?;
+            .map_err(|e| LifiError::ParseError(e.to_string()))?;
+        parse_price(token.price_usd.as_deref())
+    }
+
+    /// Call the LI.FI quote endpoint.
+    ///
+    /// Transport errors and 5xx responses count as circuit breaker failures;
+    /// any other response shows the upstream is up.
+    async fn request_quote(&self, params: &QuoteRequest) -> Result<QuoteResult, LifiError> {
+        let build = || {
+            let request = self
+                .http_client
+                .get(format!("{}/quote", self.api_url))
+                .query(&[
+                    ("fromChain", &params.from_chain),
+                    ("toChain", &params.to_chain),
+                    ("fromToken", &params.from_token),
+                    ("toToken", &params.to_token),
+                    ("fromAmount", &params.from_amount),
+                ]);
+            let request = match params.from_address {
+                Some(ref from_address) => request.query(&[("fromAddress", from_address)]),
+                None => request,
+            };
+            match params.slippage {
+                Some(slippage) => request.query(&[("slippage", slippage.to_string())]),
+                None => request,
+            }
+        };
+
+        let start = std::time::Instant::now();
+        let response = self.send_keyed(build).await;
+        metrics::histogram!("lifi_upstream_duration_seconds").record(start.elapsed().as_secs_f64());
+
+        let response = response.map_err(|e| {
+            self.breaker.record_failure();
+            metrics::counter!("lifi_quote_requests_total", "result" => "error").increment(1);
+            LifiError::from(TransportError::classify(&e))
+        })?;
+
+        if response.status().is_server_error() {
+            self.breaker.record_failure();
+        } else {
+            self.breaker.record_success();
+        }
 
         if !response.status().is_success() {
-            return Err(LifiError::ApiError(format!(
-                "Status: {}",
-                response.status()
-            )));
+            let status = response.status();
+            let body: Option<serde_json::Value> = response.json().await.ok();
+            if body.as_ref().is_some_and(has_no_route_code) {
+                metrics::counter!("lifi_quote_requests_total", "result" => "no_route").increment(1);
+                return Err(LifiError::NoRoute);
+            }
+            metrics::counter!("lifi_quote_requests_total", "result" => "error").increment(1);
+            return Err(LifiError::HttpStatusError(status.as_u16()));
         }
 
         let data: serde_json::Value = response
             .json()
             .await
-            .map_err(|e| LifiError::ApiError(e.to_string()))?;
+            .map_err(|e| LifiError::ParseError(e.to_string()))?;
+
+        let result = parse_quote(data);
+        let label = match result {
+            Ok(_) => "ok",
+            Err(LifiError::NoRoute) => "no_route",
+            Err(_) => "error",
+        };
+        metrics::counter!("lifi_quote_requests_total", "result" => label).increment(1);
+        result
+    }
+}
 
-        // Extract relevant fields from LI.FI response
-        let to_amount = data["estimate"]["toAmount"]
-            .as_str()
-            .unwrap_or("0")
-            .to_string();
+impl LifiService {
+    /// Send the request `build` makes with the next key in turn.
+    ///
+    /// A 429 parks its key for the `Retry-After` it carried and the request
+    /// is sent again with the next key, or without one once every key is
+    /// parked.
+    async fn send_keyed(
+        &self,
+        build: impl Fn() -> reqwest::RequestBuilder,
+    ) -> reqwest::Result<reqwest::Response> {
+        loop {
+            let key = self.keys.next(Instant::now());
+            let mut request = build();
+            let label = match key {
+                Some((index, value)) => {
+                    request = request.header("x-lifi-api-key", value);
+                    index.to_string()
+                }
+                None => "none".to_string(),
+            };
+            metrics::counter!("lifi_api_key_requests_total", "key" => label.clone()).increment(1);
+
+            let response = telemetry::send("lifi", &self.http_client, request).await?;
+            let Some((index, _)) = key else {
+                return Ok(response);
+            };
+            if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return Ok(response);
+            }
+            let cooldown = retry_after(response.headers()).unwrap_or(DEFAULT_KEY_COOLDOWN);
+            self.keys.park(index, Instant::now(), cooldown);
+            metrics::counter!("lifi_api_key_parked_total", "key" => label).increment(1);
+            tracing::warn!(
+                "LI.FI API key #{} is rate limited; parked for {}s",
+                index,
+                cooldown.max(MIN_KEY_COOLDOWN).as_secs()
+            );
+        }
+    }
+}
+
+/// A `Retry-After` header: delay seconds or an HTTP date
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let raw = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(secs) = raw.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(raw).ok()?;
+    Some(
+        (at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// Whether a LI.FI body carries one of the [`NO_ROUTE_CODES`]
+fn has_no_route_code(data: &serde_json::Value) -> bool {
+    let code = match data.get("code") {
+        Some(serde_json::Value::String(code)) => code.clone(),
+        Some(serde_json::Value::Number(code)) => code.to_string(),
+        _ => return false,
+    };
+    NO_ROUTE_CODES.contains(&code.as_str())
+}
 
-        let estimated_gas = data["estimate"]["gasCosts"]
-            .as_array()
-            .and_then(|arr| arr.first())
-            .and_then(|cost| cost["amount"].as_str())
-            .unwrap_or("0")
-            .to_string();
+/// Deserialize a LI.FI quote, keeping the raw response as the route.
+///
+/// A 200 without an estimate, or with a no-route code, is
+/// [`LifiError::NoRoute`] rather than a zero quote.
+fn parse_quote(data: serde_json::Value) -> Result<QuoteResult, LifiError> {
+    let no_estimate = match data.get("estimate") {
+        None | Some(serde_json::Value::Null) => true,
+        Some(serde_json::Value::Object(estimate)) => estimate.is_empty(),
+        Some(_) => false,
+    };
+    if no_estimate || has_no_route_code(&data) {
+        return Err(LifiError::NoRoute);
+    }
 
-        let estimated_time = data["estimate"]["executionDuration"].as_u64().unwrap_or(0);
+    let quote = LifiQuote::deserialize(&data).map_err(|e| LifiError::ParseError(e.to_string()))?;
 
-        Ok(QuoteResult {
-            to_amount,
-            estimated_gas,
-            estimated_time,
-            route: Some(data),
-        })
+    Ok(QuoteResult {
+        to_amount: quote.estimate.to_amount,
+        from_decimals: quote.action.as_ref().map(|a| a.from_token.decimals),
+        to_decimals: quote.action.as_ref().map(|a| a.to_token.decimals),
+        from_price_usd: quote
+            .action
+            .as_ref()
+            .and_then(|a| a.from_token.price_usd.as_deref())
+            .and_then(|raw| raw.parse().ok()),
+        estimated_gas: quote
+            .estimate
+            .gas_costs
+            .first()
+            .map(|cost| cost.amount.clone())
+            .unwrap_or_else(|| "0".to_string()),
+        estimated_time: quote.estimate.execution_duration,
+        route: Some(data),
+    })
+}
+
+/// A `priceUSD` string as a positive price; `None` if it is missing or zero
+fn parse_price(raw: Option<&str>) -> Result<Option<f64>, LifiError> {
+    let Some(raw) = raw else {
+        return Ok(None);
+    };
+    let price: f64 = raw
+        .trim()
+        .parse()
+        .map_err(|_| LifiError::ParseError(format!("invalid priceUSD {:?}", raw)))?;
+    if !price.is_finite() || price < 0.0 {
+        return Err(LifiError::ParseError(format!("invalid priceUSD {:?}", raw)));
     }
+    Ok((price > 0.0).then_some(price))
 }
 
 impl Default for LifiService {
@@ -110,3 +609,290 @@ impl Default for LifiService {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_well_formed_quote() {
+        let data = json!({
+            "estimate": {
+                "toAmount": "998500",
+                "executionDuration": 45,
+                "gasCosts": [{ "amount": "21000", "amountUSD": "0.05" }],
+                "feeCosts": [{ "name": "LIFI Fixed Fee", "amount": "1500", "included": true }],
+            },
+            "includedSteps": [{ "type": "cross", "tool": "stargate" }],
+            "action": {
+                "fromToken": { "symbol": "USDC", "decimals": 6 },
+                "toToken": { "symbol": "WETH", "decimals": 18 },
+            },
+        });
+
+        let quote = parse_quote(data.clone()).unwrap();
+        assert_eq!(quote.to_amount, "998500");
+        assert_eq!(quote.from_decimals, Some(6));
+        assert_eq!(quote.to_decimals, Some(18));
+        assert_eq!(quote.estimated_gas, "21000");
+        assert_eq!(quote.estimated_time, 45);
+        assert_eq!(quote.route, Some(data));
+    }
+
+    #[test]
+    fn test_parse_malformed_quote_is_an_error() {
+        // toAmount as a number instead of a string
+        let data = json!({ "estimate": { "toAmount": 998500, "executionDuration": 45 } });
+        assert!(matches!(parse_quote(data), Err(LifiError::ParseError(_))));
+
+        let data = json!({ "estimate": "soon", "message": "quote schema changed" });
+        assert!(matches!(parse_quote(data), Err(LifiError::ParseError(_))));
+    }
+
+    #[test]
+    fn test_parse_empty_route_is_no_route() {
+        for data in [
+            json!({}),
+            json!({ "estimate": null }),
+            json!({ "estimate": {} }),
+            json!({ "code": "NO_ROUTE", "estimate": { "toAmount": "0", "executionDuration": 0 } }),
+        ] {
+            assert!(matches!(parse_quote(data), Err(LifiError::NoRoute)));
+        }
+        assert!(has_no_route_code(
+            &json!({ "code": 1002, "message": "No available quotes" })
+        ));
+        assert!(!has_no_route_code(&json!({ "code": 1000 })));
+    }
+
+    #[test]
+    fn test_parse_price() {
+        assert_eq!(parse_price(Some("0.9998")).unwrap(), Some(0.9998));
+        assert_eq!(parse_price(Some(" 2450.5 ")).unwrap(), Some(2450.5));
+        assert_eq!(parse_price(Some("0")).unwrap(), None);
+        assert_eq!(parse_price(None).unwrap(), None);
+        for raw in ["", "cheap", "-1", "NaN", "inf"] {
+            assert!(matches!(
+                parse_price(Some(raw)),
+                Err(LifiError::ParseError(_))
+            ));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_bucket_delays_then_rejects_excess_calls() {
+        // 10 tokens per second, bursts of 2, queueing for up to 250ms
+        let bucket = std::sync::Arc::new(TokenBucket::new(600, 2, Duration::from_millis(250)));
+        let start = Instant::now();
+        let calls: Vec<_> = (0..5)
+            .map(|_| {
+                let bucket = bucket.clone();
+                tokio::spawn(async move {
+                    let result = bucket.acquire().await;
+                    (result, start.elapsed())
+                })
+            })
+            .collect();
+
+        let mut admitted = Vec::new();
+        let mut rejected = Vec::new();
+        for call in calls {
+            match call.await.unwrap() {
+                (Ok(()), elapsed) => admitted.push(elapsed),
+                (Err(LifiError::RateLimited(wait)), _) => rejected.push(wait),
+                (Err(e), _) => panic!("unexpected error: {}", e),
+            }
+        }
+        admitted.sort();
+        // The burst passes at once, the next two wait for refills in turn
+        assert_eq!(
+            admitted,
+            [0, 0, 100, 200].map(Duration::from_millis).to_vec()
+        );
+        // The fifth would have waited 300ms
+        assert_eq!(rejected, [Duration::from_millis(300)]);
+
+        // Refilled tokens are available again
+        tokio::time::advance(Duration::from_millis(200)).await;
+        assert_eq!(bucket.reserve(Instant::now()), Ok(Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_quote_never_reaches_upstream() {
+        let service =
+            LifiService::with_api("http://127.0.0.1:1", None).with_rate_limit(1, 1, Duration::ZERO);
+        let params = QuoteRequest {
+            from_chain: "1".to_string(),
+            to_chain: "8453".to_string(),
+            from_token: "USDC".to_string(),
+            to_token: "USDC".to_string(),
+            from_amount: "1000000".to_string(),
+            from_address: None,
+            slippage: None,
+        };
+        // The first call spends the only token on an unreachable upstream
+        assert!(matches!(
+            service.get_quote(&params).await,
+            Err(LifiError::ConnectError(_))
+        ));
+        let err = service.get_quote(&params).await.unwrap_err();
+        assert!(matches!(err, LifiError::RateLimited(_)));
+        assert_eq!(
+            err.to_string(),
+            "LI.FI request budget exhausted; retry in 60s"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transport_failures_are_distinct() {
+        let params = QuoteRequest {
+            from_chain: "1".to_string(),
+            to_chain: "8453".to_string(),
+            from_token: "USDC".to_string(),
+            to_token: "USDC".to_string(),
+            from_amount: "1000000".to_string(),
+            from_address: None,
+            slippage: None,
+        };
+
+        // Nothing listens on a port just released
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let refused = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let service = LifiService::with_api(&refused, None);
+        match service.get_quote(&params).await {
+            Err(LifiError::ConnectError(cause)) => {
+                assert!(cause.to_lowercase().contains("refused"), "{}", cause)
+            }
+            other => panic!("expected a connect error, got {:?}", other),
+        }
+
+        // Accepts connections but answers too late
+        let slow = axum::Router::new().fallback(|| async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            "{}"
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, slow).await.unwrap() });
+        let mut service = LifiService::with_api(&url, None);
+        service.http_client =
+            telemetry::http_client(DEFAULT_USER_AGENT, Some(Duration::from_millis(100)));
+        assert!(matches!(
+            service.get_quote(&params).await,
+            Err(LifiError::TimeoutError(_))
+        ));
+        assert!(matches!(
+            service.token_price(1, "USDC").await,
+            Err(LifiError::TimeoutError(_))
+        ));
+    }
+
+    #[test]
+    fn test_key_pool_round_robin_skips_parked_keys() {
+        let pool = KeyPool::new(vec!["a".into(), "b".into(), "c".into()]);
+        let now = Instant::now();
+        let order: Vec<usize> = (0..4).map(|_| pool.next(now).unwrap().0).collect();
+        assert_eq!(order, [0, 1, 2, 0]);
+
+        pool.park(2, now, Duration::from_secs(10));
+        let order: Vec<usize> = (0..3).map(|_| pool.next(now).unwrap().0).collect();
+        assert_eq!(order, [1, 0, 1]);
+
+        // Parked keys return after their cooldown
+        let later = now + Duration::from_secs(10);
+        let order: Vec<usize> = (0..3).map(|_| pool.next(later).unwrap().0).collect();
+        assert_eq!(order, [2, 0, 1]);
+
+        pool.park(0, now, Duration::ZERO);
+        pool.park(1, now, Duration::ZERO);
+        pool.park(2, now, Duration::ZERO);
+        assert!(pool.next(now).is_none());
+        // Even a zero Retry-After parks for the minimum
+        assert!(pool.next(now + MIN_KEY_COOLDOWN).is_some());
+        assert!(KeyPool::new(Vec::new()).next(now).is_none());
+    }
+
+    #[test]
+    fn test_retry_after_header() {
+        use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+        assert_eq!(retry_after(&headers("30")), Some(Duration::from_secs(30)));
+        let soon = (chrono::Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+        let delay = retry_after(&headers(&soon)).unwrap();
+        assert!(delay > Duration::from_secs(85) && delay <= Duration::from_secs(90));
+        assert_eq!(
+            retry_after(&headers("Wed, 21 Oct 2015 07:28:00 GMT")),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after(&headers("soon")), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_key_shifts_traffic_to_the_next_key() {
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+        let upstream = MockServer::start().await;
+        let price = ResponseTemplate::new(200).set_body_json(json!({ "priceUSD": "1.00" }));
+        // Key a is rate limited once, for one second
+        Mock::given(method("GET"))
+            .and(path("/token"))
+            .and(header("x-lifi-api-key", "key-a"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "1"))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&upstream)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/token"))
+            .respond_with(price)
+            .mount(&upstream)
+            .await;
+
+        let service = LifiService::with_api(&upstream.uri(), None)
+            .with_api_keys(vec!["key-a".to_string(), "key-b".to_string()]);
+        let keys_used = |requests: Vec<Request>| -> Vec<String> {
+            requests
+                .iter()
+                .map(|r| match r.headers.get("x-lifi-api-key") {
+                    Some(key) => key.to_str().unwrap().to_string(),
+                    None => "none".to_string(),
+                })
+                .collect()
+        };
+
+        // The 429 is retried with key b, which then takes all the traffic
+        for _ in 0..3 {
+            assert_eq!(service.token_price(1, "USDC").await.unwrap(), Some(1.0));
+        }
+        assert_eq!(
+            keys_used(upstream.received_requests().await.unwrap()),
+            ["key-a", "key-b", "key-b", "key-b"]
+        );
+
+        // After the cooldown both keys are back in rotation
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        upstream.reset().await;
+        Mock::given(method("GET"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "60"))
+            .mount(&upstream)
+            .await;
+        service.token_price(1, "USDC").await.unwrap_err();
+        // Every key parked: the request goes out unauthenticated, once
+        assert_eq!(
+            keys_used(upstream.received_requests().await.unwrap()),
+            ["key-a", "key-b", "none"]
+        );
+        service.token_price(1, "USDC").await.unwrap_err();
+        assert_eq!(upstream.received_requests().await.unwrap().len(), 4);
+    }
+}
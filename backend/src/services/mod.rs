@@ -1,5 +1,34 @@
 //! Business logic services
 
+pub mod auth;
+#[cfg(feature = "settlement")]
+pub mod chain;
+// Only the ENS and LI.FI clients are guarded by a breaker
+#[cfg_attr(not(any(feature = "ens", feature = "lifi")), allow(dead_code))]
+pub mod circuit_breaker;
+#[cfg(feature = "ens")]
+pub mod contenthash;
+#[cfg(feature = "ens")]
 pub mod ens;
+#[cfg(feature = "ens")]
+pub mod ens_cache;
+pub mod fx;
+pub mod health;
+pub mod jobs;
+#[cfg(feature = "lifi")]
 pub mod lifi;
+// Quotes price tokens through LI.FI, settlement previews the gas token and
+// display currencies are priced like tokens
+#[cfg_attr(not(all(feature = "lifi", feature = "settlement")), allow(dead_code))]
+pub mod prices;
+#[cfg(feature = "lifi")]
+pub mod quote_cache;
+pub mod rate_limit;
 pub mod session;
+#[cfg(feature = "settlement")]
+pub mod settlement;
+#[cfg(feature = "settlement")]
+pub mod settlement_batch;
+pub mod snapshot;
+pub mod template;
+pub mod webhook;
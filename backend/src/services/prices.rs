@@ -0,0 +1,502 @@
+//! Approximate token prices in USD
+//!
+//! [`PriceService`] is the one place features value tokens. It asks its
+//! [`PriceSource`]s in priority order, and the first source to price a
+//! token wins: a conflicting price from a later source is never used. Each
+//! answer is cached. A price younger than `fresh_for` is served without
+//! asking again; an older one is refetched, but still served if every
+//! source fails, until it is `max_age` old.
+//!
+//! LI.FI prices tokens for the quote features; [`NativePriceSource`] prices
+//! the settlement chain's gas token for settlement fee estimates.
+
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+#[cfg(feature = "lifi")]
+use futures::stream::{self, StreamExt};
+use lru::LruCache;
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+#[cfg(feature = "lifi")]
+use crate::services::lifi::LifiService;
+use crate::telemetry;
+
+/// Default number of cached prices
+pub const DEFAULT_PRICE_CACHE_CAPACITY: usize = 1000;
+
+/// Default age until a cached price is refetched (`PRICE_CACHE_TTL_SECS`)
+pub const DEFAULT_PRICE_FRESH_FOR: Duration = Duration::from_secs(60);
+
+/// Default age until a cached price is no longer served, even when every
+/// source fails (`PRICE_MAX_AGE_SECS`)
+pub const DEFAULT_PRICE_MAX_AGE: Duration = Duration::from_secs(900);
+
+/// Tokens a batch asks LI.FI about at once
+#[cfg(feature = "lifi")]
+const LIFI_PRICE_CONCURRENCY: usize = 4;
+
+/// Price service errors
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum PriceError {
+    #[error("No price source knows token {0}")]
+    NotFound(String),
+
+    /// Every source failed and no cached price is young enough
+    #[error("Token prices are unavailable: {0}")]
+    Unavailable(String),
+}
+
+/// A token's approximate USD price
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceQuote {
+    /// USD per whole token
+    pub usd: f64,
+    /// When the source was asked
+    pub timestamp: DateTime<Utc>,
+    /// [`PriceSource::name`] of the source that priced it
+    pub source: &'static str,
+}
+
+/// Somewhere token prices come from
+pub trait PriceSource: Send + Sync {
+    /// Stable name, reported as a price's `source`
+    fn name(&self) -> &'static str;
+
+    /// USD prices of `tokens` (lowercased) on `chain_id`. Tokens the source
+    /// cannot price are left out; an error means it could not be asked.
+    fn fetch<'a>(
+        &'a self,
+        chain_id: u64,
+        tokens: &'a [String],
+    ) -> BoxFuture<'a, Result<HashMap<String, f64>, String>>;
+}
+
+#[cfg(feature = "lifi")]
+impl PriceSource for LifiService {
+    fn name(&self) -> &'static str {
+        "lifi"
+    }
+
+    /// One `/token` call per token; fails only if every call does
+    fn fetch<'a>(
+        &'a self,
+        chain_id: u64,
+        tokens: &'a [String],
+    ) -> BoxFuture<'a, Result<HashMap<String, f64>, String>> {
+        Box::pin(async move {
+            let results: Vec<_> = stream::iter(tokens.iter().cloned())
+                .map(|token| async move {
+                    let price = self.token_price(chain_id, &token).await;
+                    (token, price)
+                })
+                .buffer_unordered(LIFI_PRICE_CONCURRENCY)
+                .collect()
+                .await;
+
+            let mut prices = HashMap::new();
+            let mut last_error = None;
+            for (token, result) in results {
+                match result {
+                    Ok(Some(usd)) => {
+                        prices.insert(token, usd);
+                    }
+                    Ok(None) => {}
+                    Err(e) => last_error = Some(e.to_string()),
+                }
+            }
+            match last_error {
+                Some(e) if prices.is_empty() => Err(e),
+                _ => Ok(prices),
+            }
+        })
+    }
+}
+
+/// Address LI.FI and most price APIs give a chain's native token
+pub const NATIVE_TOKEN: &str = "0x0000000000000000000000000000000000000000";
+
+/// Timeout of native token price requests
+const NATIVE_PRICE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The price of one chain's native token from a CoinGecko-style
+/// `simple/price` URL, which names the coin itself
+/// (`?ids=usd-coin&vs_currencies=usd` answers `{"usd-coin": {"usd": 1.0}}`).
+/// Prices only [`NATIVE_TOKEN`].
+pub struct NativePriceSource {
+    http_client: reqwest::Client,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct SimplePrice {
+    usd: f64,
+}
+
+impl NativePriceSource {
+    pub fn new(url: &str, user_agent: &str) -> Self {
+        Self {
+            http_client: telemetry::http_client(user_agent, Some(NATIVE_PRICE_TIMEOUT)),
+            url: url.to_string(),
+        }
+    }
+}
+
+impl PriceSource for NativePriceSource {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        _chain_id: u64,
+        tokens: &'a [String],
+    ) -> BoxFuture<'a, Result<HashMap<String, f64>, String>> {
+        Box::pin(async move {
+            if !tokens.iter().any(|t| t == NATIVE_TOKEN) {
+                return Ok(HashMap::new());
+            }
+            let request = self.http_client.get(&self.url);
+            let response = telemetry::send("native_price", &self.http_client, request)
+                .await
+                .map_err(|e| e.to_string())?;
+            if !response.status().is_success() {
+                return Err(format!("HTTP {}", response.status().as_u16()));
+            }
+            let coins: HashMap<String, SimplePrice> =
+                response.json().await.map_err(|e| e.to_string())?;
+            let usd = coins
+                .into_values()
+                .next()
+                .ok_or_else(|| "no coin in the response".to_string())?
+                .usd;
+            Ok(HashMap::from([(NATIVE_TOKEN.to_string(), usd)]))
+        })
+    }
+}
+
+struct CachedPrice {
+    quote: PriceQuote,
+    fetched_at: Instant,
+}
+
+/// Cached, multi-source token prices
+pub struct PriceService {
+    sources: Vec<Arc<dyn PriceSource>>,
+    cache: Mutex<LruCache<(u64, String), CachedPrice>>,
+    fresh_for: Duration,
+    max_age: Duration,
+}
+
+impl PriceService {
+    /// Ask `sources` in the given order, caching with the default thresholds
+    pub fn new(sources: Vec<Arc<dyn PriceSource>>) -> Self {
+        let capacity = NonZeroUsize::new(DEFAULT_PRICE_CACHE_CAPACITY).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            sources,
+            cache: Mutex::new(LruCache::new(capacity)),
+            fresh_for: DEFAULT_PRICE_FRESH_FOR,
+            max_age: DEFAULT_PRICE_MAX_AGE,
+        }
+    }
+
+    /// Refetch prices older than `fresh_for`, serving them while every
+    /// source fails until they are `max_age` old
+    pub fn with_staleness(mut self, fresh_for: Duration, max_age: Duration) -> Self {
+        self.fresh_for = fresh_for;
+        self.max_age = max_age.max(fresh_for);
+        self
+    }
+
+    /// Price of one token
+    pub async fn get(&self, chain_id: u64, token: &str) -> Result<PriceQuote, PriceError> {
+        self.get_many(chain_id, &[token])
+            .await
+            .pop()
+            .expect("one result per token")
+    }
+
+    /// Prices of several tokens of one chain, in the order asked. Tokens
+    /// without a fresh cached price are fetched together, one request per
+    /// source at most.
+    pub async fn get_many(
+        &self,
+        chain_id: u64,
+        tokens: &[&str],
+    ) -> Vec<Result<PriceQuote, PriceError>> {
+        let tokens: Vec<String> = tokens.iter().map(|t| t.trim().to_lowercase()).collect();
+
+        let mut fresh = HashMap::new();
+        let mut missing = Vec::new();
+        {
+            let mut cache = self.cache.lock().await;
+            for token in &tokens {
+                match cache.get(&(chain_id, token.clone())) {
+                    Some(cached) if cached.fetched_at.elapsed() < self.fresh_for => {
+                        fresh.insert(token.clone(), cached.quote.clone());
+                    }
+                    _ if !missing.contains(token) => missing.push(token.clone()),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut failure = None;
+        if !missing.is_empty() {
+            let (fetched, error) = self.fetch(chain_id, &missing).await;
+            failure = error;
+            let mut cache = self.cache.lock().await;
+            let now = Instant::now();
+            for (token, quote) in fetched {
+                cache.put(
+                    (chain_id, token.clone()),
+                    CachedPrice {
+                        quote: quote.clone(),
+                        fetched_at: now,
+                    },
+                );
+                fresh.insert(token, quote);
+            }
+        }
+
+        let mut cache = self.cache.lock().await;
+        tokens
+            .into_iter()
+            .map(|token| {
+                if let Some(quote) = fresh.get(&token) {
+                    return Ok(quote.clone());
+                }
+                // Not priced by any source just now: fall back to a stale price
+                match cache.get(&(chain_id, token.clone())) {
+                    Some(cached) if cached.fetched_at.elapsed() < self.max_age => {
+                        metrics::counter!("price_stale_served_total").increment(1);
+                        Ok(cached.quote.clone())
+                    }
+                    _ => match &failure {
+                        Some(e) => Err(PriceError::Unavailable(e.clone())),
+                        None => Err(PriceError::NotFound(token)),
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Ask each source in turn for the tokens no earlier source priced,
+    /// returning the prices found and the last source error, if any
+    async fn fetch(
+        &self,
+        chain_id: u64,
+        tokens: &[String],
+    ) -> (HashMap<String, PriceQuote>, Option<String>) {
+        let mut remaining: Vec<String> = tokens.to_vec();
+        let mut prices = HashMap::new();
+        let mut error = None;
+        for source in &self.sources {
+            if remaining.is_empty() {
+                break;
+            }
+            let result = source.fetch(chain_id, &remaining).await;
+            let label = if result.is_ok() { "ok" } else { "error" };
+            metrics::counter!("price_source_requests_total", "source" => source.name(), "result" => label)
+                .increment(1);
+
+            match result {
+                Ok(found) => {
+                    let timestamp = Utc::now();
+                    let priced: HashSet<String> = found
+                        .into_iter()
+                        .filter(|(token, usd)| {
+                            remaining.contains(token) && usd.is_finite() && *usd > 0.0
+                        })
+                        .map(|(token, usd)| {
+                            let quote = PriceQuote {
+                                usd,
+                                timestamp,
+                                source: source.name(),
+                            };
+                            prices.insert(token.clone(), quote);
+                            token
+                        })
+                        .collect();
+                    remaining.retain(|token| !priced.contains(token));
+                }
+                Err(e) => {
+                    tracing::warn!(source = source.name(), error = %e, "Price source failed");
+                    error = Some(format!("{}: {}", source.name(), e));
+                }
+            }
+        }
+        (prices, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const USDC: &str = "0x833589fcd6edb6e08f4c7c32d4f71b54bda02913";
+    const WETH: &str = "0x4200000000000000000000000000000000000006";
+
+    /// Fixed prices, or a failure, counting how often it is asked
+    struct MockSource {
+        name: &'static str,
+        prices: HashMap<String, f64>,
+        failing: bool,
+        calls: AtomicUsize,
+    }
+
+    impl MockSource {
+        fn new(name: &'static str, prices: &[(&str, f64)]) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                prices: prices.iter().map(|(t, p)| (t.to_string(), *p)).collect(),
+                failing: false,
+                calls: AtomicUsize::new(0),
+            })
+        }
+
+        fn failing(name: &'static str) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                prices: HashMap::new(),
+                failing: true,
+                calls: AtomicUsize::new(0),
+            })
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    impl PriceSource for MockSource {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn fetch<'a>(
+            &'a self,
+            _chain_id: u64,
+            tokens: &'a [String],
+        ) -> BoxFuture<'a, Result<HashMap<String, f64>, String>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let result = if self.failing {
+                Err("connection refused".to_string())
+            } else {
+                Ok(tokens
+                    .iter()
+                    .filter_map(|t| self.prices.get(t).map(|p| (t.clone(), *p)))
+                    .collect())
+            };
+            Box::pin(async move { result })
+        }
+    }
+
+    /// One price for every token, switchable between calls; `None` is down
+    struct Switchable(std::sync::Mutex<Option<f64>>);
+
+    impl PriceSource for Switchable {
+        fn name(&self) -> &'static str {
+            "switchable"
+        }
+
+        fn fetch<'a>(
+            &'a self,
+            _chain_id: u64,
+            tokens: &'a [String],
+        ) -> BoxFuture<'a, Result<HashMap<String, f64>, String>> {
+            let result = match *self.0.lock().unwrap() {
+                Some(price) => Ok(tokens.iter().map(|t| (t.clone(), price)).collect()),
+                None => Err("down".to_string()),
+            };
+            Box::pin(async move { result })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_first_source_wins_conflicting_prices() {
+        let primary = MockSource::new("primary", &[(USDC, 1.0)]);
+        let secondary = MockSource::new("secondary", &[(USDC, 0.97), (WETH, 2450.0)]);
+        let service = PriceService::new(vec![primary.clone(), secondary.clone()]);
+
+        let prices = service.get_many(8453, &[USDC, WETH]).await;
+        let usdc = prices[0].as_ref().unwrap();
+        assert_eq!((usdc.usd, usdc.source), (1.0, "primary"));
+        // Only the token the primary lacks falls through
+        let weth = prices[1].as_ref().unwrap();
+        assert_eq!((weth.usd, weth.source), (2450.0, "secondary"));
+        assert_eq!((primary.calls(), secondary.calls()), (1, 1));
+
+        // Tokens are matched case-insensitively and served from the cache
+        let cached = service.get(8453, &USDC.to_uppercase()).await.unwrap();
+        assert_eq!(cached.source, "primary");
+        assert_eq!((primary.calls(), secondary.calls()), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_failing_source_falls_back() {
+        let primary = MockSource::failing("primary");
+        let secondary = MockSource::new("secondary", &[(USDC, 0.99)]);
+        let service = PriceService::new(vec![primary, secondary]);
+
+        let quote = service.get(1, USDC).await.unwrap();
+        assert_eq!((quote.usd, quote.source), (0.99, "secondary"));
+
+        // Known to no source, with one source down: unavailable, not unknown
+        assert!(matches!(
+            service.get(1, WETH).await,
+            Err(PriceError::Unavailable(_))
+        ));
+        let healthy = PriceService::new(vec![MockSource::new("only", &[])]);
+        assert_eq!(
+            healthy.get(1, WETH).await,
+            Err(PriceError::NotFound(WETH.to_string()))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stale_prices_serve_until_max_age() {
+        let source = Arc::new(Switchable(std::sync::Mutex::new(Some(1.0))));
+        let service = PriceService::new(vec![source.clone()])
+            .with_staleness(Duration::from_secs(60), Duration::from_secs(300));
+        let first = service.get(1, USDC).await.unwrap();
+
+        // Fresh: the cache answers even though the source changed
+        *source.0.lock().unwrap() = Some(1.02);
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(service.get(1, USDC).await.unwrap(), first);
+
+        // Past fresh_for: refetched
+        tokio::time::advance(Duration::from_secs(31)).await;
+        assert_eq!(service.get(1, USDC).await.unwrap().usd, 1.02);
+
+        // Source down: the stale price is served until max_age
+        *source.0.lock().unwrap() = None;
+        tokio::time::advance(Duration::from_secs(240)).await;
+        assert_eq!(service.get(1, USDC).await.unwrap().usd, 1.02);
+        tokio::time::advance(Duration::from_secs(61)).await;
+        assert!(matches!(
+            service.get(1, USDC).await,
+            Err(PriceError::Unavailable(e)) if e == "switchable: down"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_batch_dedupes_and_keeps_order() {
+        let source = MockSource::new("only", &[(USDC, 1.0), (WETH, 2450.0)]);
+        let service = PriceService::new(vec![source.clone()]);
+
+        let prices = service.get_many(1, &[WETH, USDC, WETH]).await;
+        let usd: Vec<f64> = prices.into_iter().map(|p| p.unwrap().usd).collect();
+        assert_eq!(usd, [2450.0, 1.0, 2450.0]);
+        assert_eq!(source.calls(), 1);
+    }
+}
@@ -0,0 +1,267 @@
+//! Bounded LRU cache for LI.FI quotes
+//!
+//! Quotes are keyed by every request parameter, so many distinct amounts
+//! would grow an unbounded map quickly. The LRU evicts the least recently
+//! used quote once `capacity` is reached, and each entry still expires
+//! after a short TTL because quotes go stale quickly. The TTL is passed on
+//! every insert so a reloaded `QUOTE_CACHE_TTL_SECS` applies right away.
+//!
+//! The decimals and price of each quoted source token outlive the quote, so
+//! the sanity ceiling can still be checked when a later quote fails.
+
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+use tokio::sync::Mutex;
+
+use crate::api::quote::QuoteRequest;
+use crate::services::lifi::QuoteResult;
+
+/// Default number of cached quotes
+pub const DEFAULT_QUOTE_CACHE_CAPACITY: usize = 1000;
+
+/// Default lifetime of a cached quote (`QUOTE_CACHE_TTL_SECS`)
+pub const DEFAULT_QUOTE_CACHE_TTL: Duration = Duration::from_secs(15);
+
+/// Cache key: all parameters that influence a quote
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QuoteKey {
+    from_chain: String,
+    to_chain: String,
+    from_token: String,
+    to_token: String,
+    from_amount: String,
+    from_address: Option<String>,
+    /// Bits of the slippage fraction, as `f64` is not `Eq`
+    slippage: Option<u64>,
+}
+
+impl From<&QuoteRequest> for QuoteKey {
+    fn from(params: &QuoteRequest) -> Self {
+        Self {
+            from_chain: params.from_chain.clone(),
+            to_chain: params.to_chain.clone(),
+            from_token: params.from_token.to_lowercase(),
+            to_token: params.to_token.to_lowercase(),
+            from_amount: params.from_amount.clone(),
+            from_address: params.from_address.as_ref().map(|a| a.to_lowercase()),
+            slippage: params.slippage.map(f64::to_bits),
+        }
+    }
+}
+
+impl QuoteKey {
+    /// Source chain and token of the quote
+    fn source_token(&self) -> (String, String) {
+        (self.from_chain.clone(), self.from_token.clone())
+    }
+}
+
+/// What a quote said about its source token
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenFacts {
+    pub decimals: u8,
+    /// USD price of one token, if LI.FI reported it
+    pub price_usd: Option<f64>,
+}
+
+impl TokenFacts {
+    /// Facts about the source token of `quote`; `None` without its decimals
+    pub fn of(quote: &QuoteResult) -> Option<Self> {
+        Some(Self {
+            decimals: quote.from_decimals?,
+            price_usd: quote.from_price_usd,
+        })
+    }
+}
+
+struct CachedQuote {
+    quote: QuoteResult,
+    expires_at: Instant,
+}
+
+/// LRU-backed quote cache with a per-entry TTL
+pub struct QuoteCache {
+    entries: Mutex<LruCache<QuoteKey, CachedQuote>>,
+    /// Last known facts per source chain and token, without a TTL
+    tokens: Mutex<LruCache<(String, String), TokenFacts>>,
+}
+
+impl QuoteCache {
+    /// Create a cache holding at most `capacity` quotes
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            tokens: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Look up a fresh quote, marking it most recently used
+    pub async fn get(&self, key: &QuoteKey) -> Option<QuoteResult> {
+        let mut entries = self.entries.lock().await;
+
+        let fresh = match entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.quote.clone()),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        };
+
+        match fresh {
+            Some(_) => metrics::counter!("quote_cache_hits_total").increment(1),
+            None => metrics::counter!("quote_cache_misses_total").increment(1),
+        }
+        metrics::gauge!("quote_cache_entries").set(entries.len() as f64);
+        fresh
+    }
+
+    /// Store a quote fresh for `ttl`, evicting the least recently used entry when full
+    pub async fn insert(&self, key: QuoteKey, quote: QuoteResult, ttl: Duration) {
+        if let Some(facts) = TokenFacts::of(&quote) {
+            self.tokens.lock().await.push(key.source_token(), facts);
+        }
+        let mut entries = self.entries.lock().await;
+        let entry = CachedQuote {
+            quote,
+            expires_at: Instant::now() + ttl,
+        };
+
+        if let Some((evicted, _)) = entries.push(key.clone(), entry) {
+            if evicted != key {
+                metrics::counter!("quote_cache_evictions_total").increment(1);
+            }
+        }
+        metrics::gauge!("quote_cache_entries").set(entries.len() as f64);
+    }
+
+    /// Facts about the source token of `key` from the last quote of it,
+    /// even one that has expired or been evicted
+    pub async fn token(&self, key: &QuoteKey) -> Option<TokenFacts> {
+        self.tokens.lock().await.get(&key.source_token()).copied()
+    }
+
+    /// Number of cached quotes (including not yet purged expired ones)
+    pub async fn len(&self) -> usize {
+        self.entries.lock().await.len()
+    }
+}
+
+impl Default for QuoteCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_QUOTE_CACHE_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(amount: &str) -> QuoteKey {
+        QuoteKey::from(&QuoteRequest {
+            from_chain: "8453".to_string(),
+            to_chain: "1".to_string(),
+            from_token: "USDC".to_string(),
+            to_token: "USDC".to_string(),
+            from_amount: amount.to_string(),
+            from_address: None,
+            slippage: None,
+        })
+    }
+
+    fn quote(to_amount: &str) -> QuoteResult {
+        QuoteResult {
+            to_amount: to_amount.to_string(),
+            from_decimals: Some(6),
+            to_decimals: Some(6),
+            from_price_usd: Some(1.0),
+            estimated_gas: "0".to_string(),
+            estimated_time: 30,
+            route: None,
+        }
+    }
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used() {
+        let cache = QuoteCache::new(2);
+
+        cache.insert(key("1"), quote("1"), TTL).await;
+        cache.insert(key("2"), quote("2"), TTL).await;
+        // Touch "1" so that "2" becomes the least recently used
+        assert!(cache.get(&key("1")).await.is_some());
+        cache.insert(key("3"), quote("3"), TTL).await;
+
+        assert_eq!(cache.len().await, 2);
+        assert!(cache.get(&key("2")).await.is_none());
+        assert_eq!(cache.get(&key("1")).await.unwrap().to_amount, "1");
+        assert_eq!(cache.get(&key("3")).await.unwrap().to_amount, "3");
+    }
+
+    #[tokio::test]
+    async fn test_oldest_evicted_when_capacity_exceeded() {
+        let cache = QuoteCache::new(3);
+        for i in 0..10 {
+            cache
+                .insert(key(&i.to_string()), quote(&i.to_string()), TTL)
+                .await;
+        }
+
+        assert_eq!(cache.len().await, 3);
+        for i in 0..7 {
+            assert!(cache.get(&key(&i.to_string())).await.is_none());
+        }
+        for i in 7..10 {
+            assert!(cache.get(&key(&i.to_string())).await.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entries_expire_after_ttl() {
+        let cache = QuoteCache::new(10);
+        cache
+            .insert(key("1"), quote("1"), Duration::from_millis(20))
+            .await;
+        assert!(cache.get(&key("1")).await.is_some());
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(cache.get(&key("1")).await.is_none());
+        assert_eq!(cache.len().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_token_facts_outlive_the_quote() {
+        let cache = QuoteCache::new(1);
+        assert_eq!(cache.token(&key("1")).await, None);
+
+        cache.insert(key("1"), quote("1"), TTL).await;
+        // Evicts the quote of "1"
+        cache.insert(key("2"), quote("2"), TTL).await;
+        assert!(cache.get(&key("1")).await.is_none());
+
+        // Same token, any amount
+        let facts = cache.token(&key("3")).await.unwrap();
+        assert_eq!(facts.decimals, 6);
+        assert_eq!(facts.price_usd, Some(1.0));
+    }
+
+    #[test]
+    fn test_key_ignores_token_case() {
+        let mut upper = QuoteRequest {
+            from_chain: "8453".to_string(),
+            to_chain: "1".to_string(),
+            from_token: "0xABC".to_string(),
+            to_token: "0xDEF".to_string(),
+            from_amount: "1".to_string(),
+            from_address: None,
+            slippage: None,
+        };
+        let a = QuoteKey::from(&upper);
+        upper.from_token = "0xabc".to_string();
+        assert_eq!(a, QuoteKey::from(&upper));
+    }
+}
@@ -0,0 +1,202 @@
+//! Per-client token-bucket rate limiting
+//!
+//! Each (client IP, route class) pair gets its own bucket holding up to
+//! `per_minute` tokens, refilled continuously at `per_minute / 60` tokens
+//! per second. A full bucket allows a burst of `per_minute` requests.
+//! Limits are read from the live config on every check, so a reload applies
+//! to existing buckets immediately.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+use crate::config::{DynamicConfig, LiveConfig};
+
+/// Default requests per minute for read-only endpoints
+pub const DEFAULT_READ_PER_MINUTE: u32 = 600;
+
+/// Default requests per minute for session-mutating endpoints
+pub const DEFAULT_WRITE_PER_MINUTE: u32 = 120;
+
+/// Default requests per minute for the quote endpoint (fans out to LI.FI)
+pub const DEFAULT_QUOTE_PER_MINUTE: u32 = 60;
+
+/// Tracked buckets before idle (full) ones are pruned
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Route classes with independent limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteClass {
+    Read,
+    Write,
+    Quote,
+}
+
+impl RouteClass {
+    /// Metric label for this class
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RouteClass::Read => "read",
+            RouteClass::Write => "write",
+            RouteClass::Quote => "quote",
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Token-bucket limiter keyed by client IP and route class
+pub struct RateLimiter {
+    live: LiveConfig,
+    buckets: Mutex<HashMap<(IpAddr, RouteClass), Bucket>>,
+}
+
+impl RateLimiter {
+    /// Create a limiter enforcing the limits in `live`; a limit of 0
+    /// disables limiting for that class
+    pub fn new(live: LiveConfig) -> Self {
+        Self {
+            live,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take one token for `client`, or return how long until one is available
+    pub async fn check(&self, client: IpAddr, class: RouteClass) -> Result<(), Duration> {
+        self.check_at(client, class, Instant::now()).await
+    }
+
+    async fn check_at(
+        &self,
+        client: IpAddr,
+        class: RouteClass,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let limits = self.live.get();
+        let limit = limit_for(&limits, class);
+        if limit == 0 {
+            return Ok(());
+        }
+        let capacity = f64::from(limit);
+        let refill_per_sec = capacity / 60.0;
+
+        let mut buckets = self.buckets.lock().await;
+        if buckets.len() >= PRUNE_THRESHOLD {
+            // Drop buckets that have refilled completely; they hold no state
+            buckets.retain(|(_, class), bucket| {
+                let limit = f64::from(limit_for(&limits, *class));
+                let elapsed = now.saturating_duration_since(bucket.updated_at);
+                bucket.tokens + elapsed.as_secs_f64() * limit / 60.0 < limit
+            });
+        }
+
+        let bucket = buckets.entry((client, class)).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * refill_per_sec).min(capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / refill_per_sec,
+            ))
+        }
+    }
+}
+
+/// Requests per minute allowed for `class`
+fn limit_for(limits: &DynamicConfig, class: RouteClass) -> u32 {
+    match class {
+        RouteClass::Read => limits.rate_limit_read_per_minute,
+        RouteClass::Write => limits.rate_limit_write_per_minute,
+        RouteClass::Quote => limits.rate_limit_quote_per_minute,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Limiter allowing `write_per_minute` writes and unlimited reads and quotes
+    fn write_limiter(write_per_minute: u32) -> RateLimiter {
+        RateLimiter::new(LiveConfig::new(DynamicConfig {
+            rate_limit_read_per_minute: 0,
+            rate_limit_write_per_minute: write_per_minute,
+            rate_limit_quote_per_minute: 0,
+            ..DynamicConfig::default()
+        }))
+    }
+
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1));
+    const OTHER: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 2));
+
+    #[tokio::test]
+    async fn test_burst_up_to_limit_then_rejected() {
+        let limiter = write_limiter(3);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter
+                .check_at(CLIENT, RouteClass::Write, now)
+                .await
+                .is_ok());
+        }
+        let retry_after = limiter
+            .check_at(CLIENT, RouteClass::Write, now)
+            .await
+            .unwrap_err();
+        // 3/min refills one token every 20s
+        assert_eq!(retry_after.as_secs(), 20);
+
+        // Other clients and classes have their own buckets
+        assert!(limiter
+            .check_at(OTHER, RouteClass::Write, now)
+            .await
+            .is_ok());
+        assert!(limiter
+            .check_at(CLIENT, RouteClass::Read, now)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_tokens_refill_over_time() {
+        let limiter = write_limiter(60);
+        let start = Instant::now();
+
+        for _ in 0..60 {
+            limiter
+                .check_at(CLIENT, RouteClass::Write, start)
+                .await
+                .unwrap();
+        }
+        assert!(limiter
+            .check_at(CLIENT, RouteClass::Write, start)
+            .await
+            .is_err());
+
+        let later = start + Duration::from_secs(2);
+        assert!(limiter
+            .check_at(CLIENT, RouteClass::Write, later)
+            .await
+            .is_ok());
+        assert!(limiter
+            .check_at(CLIENT, RouteClass::Write, later)
+            .await
+            .is_ok());
+        assert!(limiter
+            .check_at(CLIENT, RouteClass::Write, later)
+            .await
+            .is_err());
+    }
+}
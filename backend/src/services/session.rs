@@ -1,14 +1,83 @@
 //! Session management service
 
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use crate::models::session::{Payment, Session, SessionStatus};
+use futures::stream::{self, BoxStream, StreamExt};
+use tokio::sync::{broadcast, RwLock};
+
+use crate::models::address::{Address, EnsName};
+use crate::models::session::{
+    DustPolicy, FinalizeGuard, Payment, PaymentStatus, Session, SessionError, SessionEvent,
+    SessionEventType, SessionStatus, EXPIRED_CANCEL_REASON,
+};
+use crate::services::jobs::{Job, JobContext};
+use settleone_types::api::RecipientPayment;
+
+/// How often the expiry sweep runs
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Session updates buffered per subscriber before it starts missing some
+pub const UPDATE_BUFFER: usize = 256;
+
+/// A payment recipient as recipient history looks it up: by address, or
+/// by the ENS name stored with the payment
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RecipientKey {
+    Address(Address),
+    Ens(EnsName),
+}
+
+impl std::str::FromStr for RecipientKey {
+    type Err = String;
+
+    /// An address if `raw` parses as one, an ENS name otherwise; both
+    /// ignore letter case
+    fn from_str(raw: &str) -> Result<Self, String> {
+        if let Ok(address) = raw.parse() {
+            return Ok(RecipientKey::Address(address));
+        }
+        raw.parse()
+            .map(RecipientKey::Ens)
+            .map_err(|_| format!("'{}' is neither an address nor an ENS name", raw))
+    }
+}
+
+/// `(session id, payment id)` of every payment per user and recipient
+type RecipientIndex = HashMap<(Address, RecipientKey), Vec<(String, String)>>;
+
+/// Settled payments of archived sessions per user and recipient, with what
+/// recipient history needs of their sessions
+type ArchivedPayments = HashMap<(Address, RecipientKey), Vec<RecipientPayment>>;
 
 /// Session store (in-memory for hackathon)
 pub struct SessionStore {
     sessions: Arc<RwLock<HashMap<String, Session>>>,
+    /// Ids of sessions moved out by [`SessionStore::archive_settled`]
+    archived: Arc<RwLock<HashSet<String>>>,
+    /// Lowercase settlement transaction hash of archived sessions, to the
+    /// oldest session archived with it
+    archived_tx_hashes: Arc<RwLock<HashMap<String, String>>>,
+    /// Payments across all stored sessions; only changed under the
+    /// `sessions` write lock
+    payment_count: Arc<AtomicUsize>,
+    /// Every changed session, after the change
+    updates: broadcast::Sender<Session>,
+    /// Per-payment events, in the order of their changes
+    events: broadcast::Sender<SessionEvent>,
+    /// Every event of each stored session, oldest first; kept as long as
+    /// the session and only changed under the `sessions` write lock
+    event_log: Arc<Mutex<HashMap<String, Vec<SessionEvent>>>>,
+    /// Payments of each user to each recipient, under both the recipient's
+    /// address and its ENS name; only changed under the `sessions` write lock
+    recipients: Arc<Mutex<RecipientIndex>>,
+    /// Kept once their sessions are archived, so recipient history still
+    /// counts them; only changed under the `sessions` write lock
+    archived_payments: Arc<Mutex<ArchivedPayments>>,
 }
 
 impl SessionStore {
@@ -16,43 +85,608 @@ impl SessionStore {
     pub fn new() -> Self {
         Self {
             sessions: Arc::new(RwLock::new(HashMap::new())),
+            archived: Arc::new(RwLock::new(HashSet::new())),
+            archived_tx_hashes: Arc::new(RwLock::new(HashMap::new())),
+            payment_count: Arc::new(AtomicUsize::new(0)),
+            updates: broadcast::channel(UPDATE_BUFFER).0,
+            events: broadcast::channel(UPDATE_BUFFER).0,
+            event_log: Arc::new(Mutex::new(HashMap::new())),
+            recipients: Arc::new(Mutex::new(HashMap::new())),
+            archived_payments: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Receive every session changed from now on. A subscriber that falls
+    /// more than [`UPDATE_BUFFER`] updates behind gets `Lagged` and should
+    /// re-read the sessions it follows.
+    pub fn subscribe(&self) -> broadcast::Receiver<Session> {
+        self.updates.subscribe()
+    }
+
+    /// Announce a changed session to subscribers
+    fn publish(&self, session: &Session) {
+        if self.updates.receiver_count() > 0 {
+            let _ = self.updates.send(session.clone());
+        }
+    }
+
+    /// Receive the payment events of every session from now on, only those
+    /// of `types` if given. Lagging works as for [`SessionStore::subscribe`];
+    /// the skipped events show up as a gap in the sequence.
+    pub fn subscribe_events(
+        &self,
+        types: Option<HashSet<SessionEventType>>,
+    ) -> BoxStream<'static, SessionEvent> {
+        let events = self.events.subscribe();
+        stream::unfold(events, |mut events| async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => return Some((event, events)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+        .filter(move |event| {
+            let wanted = types
+                .as_ref()
+                .is_none_or(|types| types.contains(&event.kind));
+            async move { wanted }
+        })
+        .boxed()
+    }
+
+    /// Number, log and announce a payment event; callers hold the
+    /// `sessions` write lock, so sequences follow the order of the changes
+    fn emit(&self, kind: SessionEventType, session: &Session, payment: Payment) {
+        let event = {
+            let mut log = self.event_log.lock().unwrap_or_else(|e| e.into_inner());
+            let events = log.entry(session.id.clone()).or_default();
+            let event = SessionEvent {
+                kind,
+                session_id: session.id.clone(),
+                sequence: events.len() as u64 + 1,
+                payment,
+                total_amount: session.total_amount,
+            };
+            events.push(event.clone());
+            event
+        };
+        if self.events.receiver_count() > 0 {
+            let _ = self.events.send(event);
+        }
+    }
+
+    /// Logged events of a stored session with a sequence above `since`,
+    /// oldest first; `None` if the session is not stored
+    pub async fn events_since(&self, id: &str, since: u64) -> Option<Vec<SessionEvent>> {
+        let sessions = self.sessions.read().await;
+        if !sessions.contains_key(id) {
+            return None;
+        }
+        let log = self.event_log.lock().unwrap_or_else(|e| e.into_inner());
+        Some(
+            log.get(id)
+                .map(|events| {
+                    events
+                        .iter()
+                        .filter(|e| e.sequence > since)
+                        .cloned()
+                        .collect()
+                })
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Event logs of every stored session, by session then sequence
+    pub async fn events_snapshot(&self) -> Vec<SessionEvent> {
+        let sessions = self.sessions.read().await;
+        let log = self.event_log.lock().unwrap_or_else(|e| e.into_inner());
+        let mut ids: Vec<&String> = sessions.keys().filter(|id| log.contains_key(*id)).collect();
+        ids.sort();
+        ids.into_iter().flat_map(|id| log[id].clone()).collect()
+    }
+
+    /// Load event logs (e.g. from a snapshot) of sessions already restored,
+    /// replacing their current logs; events of other sessions are dropped
+    pub async fn restore_events(&self, restored: Vec<SessionEvent>) {
+        let sessions = self.sessions.write().await;
+        let mut by_session: HashMap<String, Vec<SessionEvent>> = HashMap::new();
+        for event in restored {
+            if sessions.contains_key(&event.session_id) {
+                by_session
+                    .entry(event.session_id.clone())
+                    .or_default()
+                    .push(event);
+            }
+        }
+        let mut log = self.event_log.lock().unwrap_or_else(|e| e.into_inner());
+        for (id, mut events) in by_session {
+            events.sort_by_key(|e| e.sequence);
+            log.insert(id, events);
         }
     }
 
     /// Create a new session
-    pub async fn create(&self, id: String, user: String) -> Session {
+    pub async fn create(&self, id: String, user: Address) -> Session {
         let session = Session::new(id.clone(), user);
         let mut sessions = self.sessions.write().await;
         sessions.insert(id, session.clone());
+        metrics::counter!("sessions_created_total").increment(1);
+        metrics::gauge!("sessions_stored").set(sessions.len() as f64);
         session
     }
 
+    /// Store a new session, with any payments it already holds, unless its
+    /// user already holds `max_active` active sessions or its payments would
+    /// take the store past `max_total`. The checks and insert happen under
+    /// one write lock, so a refused session leaves nothing stored.
+    pub async fn try_create(
+        &self,
+        session: Session,
+        max_active: Option<usize>,
+        max_total: Option<usize>,
+    ) -> Result<Session, SessionError> {
+        let mut sessions = self.sessions.write().await;
+        if let Some(max) = max_active {
+            let active = sessions
+                .values()
+                .filter(|s| s.status == SessionStatus::Active && s.user == session.user)
+                .count();
+            if active >= max {
+                return Err(SessionError::ActiveSessionLimitReached {
+                    user: session.user.clone(),
+                    max,
+                });
+            }
+        }
+        let added = session.payments.len();
+        if let Some(max) = max_total {
+            if added > 0 && self.payment_count() + added > max {
+                return Err(SessionError::PaymentLimitReached(max));
+            }
+        }
+
+        sessions.insert(session.id.clone(), session.clone());
+        metrics::counter!("sessions_created_total").increment(1);
+        metrics::gauge!("sessions_stored").set(sessions.len() as f64);
+        if added > 0 {
+            self.count_payments(added, 0);
+            metrics::counter!("session_payments_added_total").increment(added as u64);
+            self.index_payments(&session, &session.payments);
+            // Log the payments as if added one by one, with running totals
+            let mut replay = Session {
+                payments: Vec::new(),
+                ..session.clone()
+            };
+            for payment in &session.payments {
+                if replay.add_payment(payment.clone()).is_ok() {
+                    self.emit(SessionEventType::PaymentAdded, &replay, payment.clone());
+                }
+            }
+        }
+        Ok(session)
+    }
+
+    /// Check the store is responsive
+    pub async fn ping(&self) {
+        let _sessions = self.sessions.read().await;
+    }
+
+    /// Copy of every stored session, oldest first
+    pub async fn snapshot(&self) -> Vec<Session> {
+        let mut sessions: Vec<Session> = self.sessions.read().await.values().cloned().collect();
+        sessions.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+        sessions
+    }
+
+    /// Ids of every stored session, in [`Self::snapshot`] order
+    pub async fn ids(&self) -> Vec<String> {
+        let sessions = self.sessions.read().await;
+        let mut keys: Vec<_> = sessions.values().map(|s| (s.created_at, &s.id)).collect();
+        keys.sort();
+        keys.into_iter().map(|(_, id)| id.clone()).collect()
+    }
+
+    /// Load sessions (e.g. from a snapshot), replacing any with the same id
+    /// and un-archiving them
+    pub async fn restore(&self, restored: Vec<Session>) -> usize {
+        let count = restored.len();
+        {
+            let mut archived = self.archived.write().await;
+            for session in &restored {
+                archived.remove(&session.id);
+            }
+            self.archived_tx_hashes
+                .write()
+                .await
+                .retain(|_, id| !restored.iter().any(|session| &session.id == id));
+        }
+        let mut sessions = self.sessions.write().await;
+        self.archived_payments
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|_, paid| {
+                paid.retain(|p| !restored.iter().any(|session| session.id == p.session_id));
+                !paid.is_empty()
+            });
+        let mut log = self.event_log.lock().unwrap_or_else(|e| e.into_inner());
+        for session in restored {
+            log.remove(&session.id);
+            // Unindexed first: the copy may share payment ids with it
+            if let Some(replaced) = sessions.remove(&session.id) {
+                self.count_payments(0, replaced.payments.len());
+                self.unindex_payments(&replaced, &replaced.payments);
+            }
+            self.count_payments(session.payments.len(), 0);
+            self.index_payments(&session, &session.payments);
+            sessions.insert(session.id.clone(), session);
+        }
+        metrics::gauge!("sessions_stored").set(sessions.len() as f64);
+        count
+    }
+
+    /// Number of stored sessions
+    pub async fn len(&self) -> usize {
+        self.sessions.read().await.len()
+    }
+
     /// Get a session by ID
     pub async fn get(&self, id: &str) -> Option<Session> {
         let sessions = self.sessions.read().await;
         sessions.get(id).cloned()
     }
 
-    /// Add payment to session
-    pub async fn add_payment(&self, session_id: &str, payment: Payment) -> Option<Session> {
+    /// Sessions of `user` and/or in `status`, unordered
+    pub async fn list(
+        &self,
+        user: Option<&Address>,
+        status: Option<SessionStatus>,
+    ) -> Vec<Session> {
+        let sessions = self.sessions.read().await;
+        sessions
+            .values()
+            .filter(|s| user.is_none_or(|user| s.user == *user))
+            .filter(|s| status.as_ref().is_none_or(|status| s.status == *status))
+            .cloned()
+            .collect()
+    }
+
+    /// Session whose settlement transaction is `tx_hash`, compared without
+    /// regard to case. Sessions settled in one batch share a hash; the
+    /// oldest of them is returned.
+    pub async fn find_by_tx_hash(&self, tx_hash: &str) -> Option<Session> {
+        let sessions = self.sessions.read().await;
+        sessions
+            .values()
+            .filter(|s| {
+                s.tx_hash
+                    .as_deref()
+                    .is_some_and(|hash| hash.eq_ignore_ascii_case(tx_hash))
+            })
+            .min_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)))
+            .cloned()
+    }
+
+    /// Follow a session: its current state, then each later version. The
+    /// stream ends after the session is settled or cancelled; `None` if it
+    /// does not exist.
+    pub async fn watch(self: &Arc<Self>, id: &str) -> Option<BoxStream<'static, Session>> {
+        // Subscribe before reading so no change in between is missed
+        let updates = self.subscribe();
+        let current = self.get(id).await?;
+        let watch = Watch {
+            id: id.to_string(),
+            store: self.clone(),
+            updates,
+            next: Some(current),
+            sent_version: 0,
+            finished: false,
+        };
+        Some(stream::unfold(watch, Watch::next).boxed())
+    }
+
+    /// Number of payments across all stored sessions
+    pub fn payment_count(&self) -> usize {
+        self.payment_count.load(Ordering::SeqCst)
+    }
+
+    /// Adjust the global payment count; callers hold the `sessions` write lock
+    fn count_payments(&self, added: usize, removed: usize) {
+        let count = self.payment_count.load(Ordering::SeqCst) + added - removed;
+        self.payment_count.store(count, Ordering::SeqCst);
+        metrics::gauge!("payments_stored").set(count as f64);
+    }
+
+    /// Index `payments` of `session` by recipient; callers hold the
+    /// `sessions` write lock
+    fn index_payments(&self, session: &Session, payments: &[Payment]) {
+        let mut index = self.recipients.lock().unwrap_or_else(|e| e.into_inner());
+        for payment in payments {
+            for key in recipient_keys(payment) {
+                index
+                    .entry((session.user.clone(), key))
+                    .or_default()
+                    .push((session.id.clone(), payment.id.clone()));
+            }
+        }
+    }
+
+    /// Drop `payments` of `session` from the recipient index; callers hold
+    /// the `sessions` write lock
+    fn unindex_payments(&self, session: &Session, payments: &[Payment]) {
+        let mut index = self.recipients.lock().unwrap_or_else(|e| e.into_inner());
+        for payment in payments {
+            for key in recipient_keys(payment) {
+                let key = (session.user.clone(), key);
+                if let Some(refs) = index.get_mut(&key) {
+                    refs.retain(|(s, p)| *s != session.id || *p != payment.id);
+                    if refs.is_empty() {
+                        index.remove(&key);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Payments of `user`'s sessions to `recipient`, optionally only those
+    /// in `status`; unordered. Stored sessions are looked up in the
+    /// recipient index rather than scanned, and the settled payments of
+    /// archived sessions are included.
+    pub async fn payments_to_recipient(
+        &self,
+        user: &Address,
+        recipient: &RecipientKey,
+        status: Option<&PaymentStatus>,
+    ) -> Vec<RecipientPayment> {
+        let key = (user.clone(), recipient.clone());
+        let sessions = self.sessions.read().await;
+        let index = self.recipients.lock().unwrap_or_else(|e| e.into_inner());
+        let mut paid: Vec<RecipientPayment> = index
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .filter_map(|(session_id, payment_id)| {
+                let session = sessions.get(session_id)?;
+                let payment = session.payments.iter().find(|p| &p.id == payment_id)?;
+                if status.is_some_and(|status| &payment.status != status) {
+                    return None;
+                }
+                Some(RecipientPayment {
+                    session_id: session.id.clone(),
+                    token_decimals: session.token_decimals,
+                    payment: payment.clone(),
+                })
+            })
+            .collect();
+        if status.is_none_or(|status| *status == PaymentStatus::Settled) {
+            let archived = self
+                .archived_payments
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            paid.extend(archived.get(&key).into_iter().flatten().cloned());
+        }
+        paid
+    }
+
+    /// Add payment to session unless the store already holds `max_total`
+    /// payments across all sessions. The check and insert happen under one
+    /// write lock.
+    pub async fn add_payment(
+        &self,
+        session_id: &str,
+        payment: Payment,
+        max_total: Option<usize>,
+    ) -> Result<Session, SessionError> {
         let mut sessions = self.sessions.write().await;
-        if let Some(session) = sessions.get_mut(session_id) {
-            if session.add_payment(payment).is_ok() {
-                return Some(session.clone());
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| SessionError::SessionNotFound(session_id.to_string()))?;
+        if let Some(max) = max_total {
+            if self.payment_count() >= max {
+                return Err(SessionError::PaymentLimitReached(max));
             }
         }
-        None
+        let payment_id = payment.id.clone();
+        session
+            .add_payment(payment)
+            .map_err(SessionError::InvalidPayment)?;
+        session.touch();
+        self.count_payments(1, 0);
+        metrics::counter!("session_payments_added_total").increment(1);
+        self.publish(session);
+        if let Some(added) = session.payments.iter().find(|p| p.id == payment_id) {
+            self.index_payments(session, std::slice::from_ref(added));
+            self.emit(SessionEventType::PaymentAdded, session, added.clone());
+        }
+        Ok(session.clone())
     }
 
     /// Remove payment from session
     pub async fn remove_payment(&self, session_id: &str, payment_id: &str) -> Option<Session> {
         let mut sessions = self.sessions.write().await;
-        if let Some(session) = sessions.get_mut(session_id) {
-            if session.remove_payment(payment_id).is_ok() {
-                return Some(session.clone());
+        let session = sessions.get_mut(session_id)?;
+        let removed = session
+            .payments
+            .iter()
+            .find(|p| p.id == payment_id)
+            .cloned()?;
+        session.remove_payment(payment_id).ok()?;
+        session.touch();
+        self.count_payments(0, 1);
+        self.unindex_payments(session, std::slice::from_ref(&removed));
+        self.publish(session);
+        self.emit(SessionEventType::PaymentRemoved, session, removed);
+        Some(session.clone())
+    }
+
+    /// Cancel a pending payment without removing it from the session
+    pub async fn cancel_payment(
+        &self,
+        session_id: &str,
+        payment_id: &str,
+    ) -> Result<Session, SessionError> {
+        let mut sessions = self.sessions.write().await;
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| SessionError::SessionNotFound(session_id.to_string()))?;
+        session.cancel_payment(payment_id)?;
+        session.touch();
+        self.publish(session);
+        Ok(session.clone())
+    }
+
+    /// Put a session's payments in the order of `payment_ids`
+    pub async fn reorder_payments(
+        &self,
+        session_id: &str,
+        payment_ids: &[String],
+    ) -> Result<Session, SessionError> {
+        let mut sessions = self.sessions.write().await;
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| SessionError::SessionNotFound(session_id.to_string()))?;
+        session.reorder_payments(payment_ids)?;
+        session.touch();
+        self.publish(session);
+        Ok(session.clone())
+    }
+
+    /// Cancel a session with an optional reason
+    pub async fn cancel(
+        &self,
+        session_id: &str,
+        reason: Option<String>,
+    ) -> Result<Session, SessionError> {
+        let mut sessions = self.sessions.write().await;
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| SessionError::SessionNotFound(session_id.to_string()))?;
+        session.cancel(reason)?;
+        session.touch();
+        metrics::counter!("sessions_cancelled_total").increment(1);
+        self.publish(session);
+        Ok(session.clone())
+    }
+
+    /// Cancel active sessions created more than `ttl` ago, returning how many
+    pub async fn sweep_expired(&self, ttl: Duration) -> usize {
+        let cutoff =
+            chrono::Utc::now() - chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX);
+        let mut sessions = self.sessions.write().await;
+        let mut expired = 0;
+        for session in sessions.values_mut() {
+            if session.status == SessionStatus::Active
+                && session.created_at < cutoff
+                && session
+                    .cancel(Some(EXPIRED_CANCEL_REASON.to_string()))
+                    .is_ok()
+            {
+                session.touch();
+                self.publish(session);
+                expired += 1;
             }
         }
-        None
+        if expired > 0 {
+            metrics::counter!("sessions_cancelled_total").increment(expired as u64);
+            tracing::info!("Expired {} idle sessions", expired);
+        }
+        expired
+    }
+
+    /// Remove settled sessions created more than `older_than` ago from the
+    /// live map and return them, oldest first, for the caller to persist.
+    ///
+    /// Their ids are remembered so reads can tell "archived" from "never existed".
+    pub async fn archive_settled(&self, older_than: Duration) -> Vec<Session> {
+        let cutoff = chrono::Utc::now()
+            - chrono::Duration::from_std(older_than).unwrap_or(chrono::Duration::MAX);
+        let mut sessions = self.sessions.write().await;
+        let ids: Vec<String> = sessions
+            .values()
+            .filter(|s| s.status == SessionStatus::Settled && s.created_at < cutoff)
+            .map(|s| s.id.clone())
+            .collect();
+        let mut archived: Vec<Session> = ids.iter().filter_map(|id| sessions.remove(id)).collect();
+        {
+            let mut log = self.event_log.lock().unwrap_or_else(|e| e.into_inner());
+            for id in &ids {
+                log.remove(id);
+            }
+        }
+        self.count_payments(0, archived.iter().map(|s| s.payments.len()).sum());
+        {
+            let mut kept = self
+                .archived_payments
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            for session in &archived {
+                self.unindex_payments(session, &session.payments);
+                let settled = session
+                    .payments
+                    .iter()
+                    .filter(|p| p.status == PaymentStatus::Settled);
+                for payment in settled {
+                    for key in recipient_keys(payment) {
+                        kept.entry((session.user.clone(), key)).or_default().push(
+                            RecipientPayment {
+                                session_id: session.id.clone(),
+                                token_decimals: session.token_decimals,
+                                payment: payment.clone(),
+                            },
+                        );
+                    }
+                }
+            }
+        }
+        metrics::gauge!("sessions_stored").set(sessions.len() as f64);
+        drop(sessions);
+
+        archived.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+        if !archived.is_empty() {
+            self.archived.write().await.extend(ids);
+            let mut tx_hashes = self.archived_tx_hashes.write().await;
+            for session in &archived {
+                if let Some(hash) = &session.tx_hash {
+                    tx_hashes
+                        .entry(hash.to_lowercase())
+                        .or_insert_with(|| session.id.clone());
+                }
+            }
+            drop(tx_hashes);
+            metrics::counter!("sessions_archived_total").increment(archived.len() as u64);
+            tracing::info!("Archived {} settled sessions", archived.len());
+        }
+        archived
+    }
+
+    /// Whether `id` was removed by [`SessionStore::archive_settled`]
+    pub async fn is_archived(&self, id: &str) -> bool {
+        self.archived.read().await.contains(id)
+    }
+
+    /// Id of the archived session whose settlement transaction is
+    /// `tx_hash`, compared without regard to case; the oldest of them if
+    /// several were settled in one batch
+    pub async fn archived_by_tx_hash(&self, tx_hash: &str) -> Option<String> {
+        self.archived_tx_hashes
+            .read()
+            .await
+            .get(&tx_hash.to_lowercase())
+            .cloned()
+    }
+
+    /// Number of cancelled sessions per cancellation reason (`unspecified` if none)
+    pub async fn cancel_reason_counts(&self) -> BTreeMap<String, usize> {
+        let sessions = self.sessions.read().await;
+        let mut counts = BTreeMap::new();
+        for session in sessions.values() {
+            if session.status == SessionStatus::Cancelled {
+                let reason = session.cancel_reason.as_deref().unwrap_or("unspecified");
+                *counts.entry(reason.to_string()).or_insert(0) += 1;
+            }
+        }
+        counts
     }
 
     /// Update session status
@@ -60,27 +694,134 @@ impl SessionStore {
         let mut sessions = self.sessions.write().await;
         if let Some(session) = sessions.get_mut(session_id) {
             session.status = status;
+            session.touch();
+            self.publish(session);
             return Some(session.clone());
         }
         None
     }
 
     /// Finalize session with status and optional tx_hash
-    /// Only updates tx_hash if a value is provided (preserves existing tx_hash otherwise)
+    /// Only updates tx_hash if a value is provided (preserves existing tx_hash otherwise).
+    /// Fails with `FinalizeCooldown` within `cooldown` of the previous finalize
+    /// (no cooldown if zero), and with `SessionModified` if the session no
+    /// longer matches `guard`. Transfers below `dust.minimum` fail with
+    /// `DustPayments`, or with `dust.skip` have their payments cancelled.
     pub async fn finalize(
         &self,
         session_id: &str,
         status: SessionStatus,
         tx_hash: Option<String>,
-    ) -> Option<Session> {
+        guard: &FinalizeGuard,
+        dust: &DustPolicy,
+        cooldown: Duration,
+    ) -> Result<Session, SessionError> {
         let mut sessions = self.sessions.write().await;
-        if let Some(session) = sessions.get_mut(session_id) {
-            session.status = status;
-            // Only update tx_hash if a new value is provided
-            if let Some(hash) = tx_hash {
-                session.tx_hash = Some(hash);
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| SessionError::SessionNotFound(session_id.to_string()))?;
+        if matches!(
+            session.status,
+            SessionStatus::Settled | SessionStatus::Cancelled
+        ) {
+            return Err(SessionError::SessionNotFinalizable {
+                id: session.id.clone(),
+                status: session.status.clone(),
+            });
+        }
+        let now = chrono::Utc::now();
+        if let Some(last) = session.last_finalize_at {
+            let elapsed = (now - last).to_std().unwrap_or_default();
+            if elapsed < cooldown {
+                let remaining = cooldown - elapsed;
+                return Err(SessionError::FinalizeCooldown {
+                    id: session.id.clone(),
+                    retry_after_secs: remaining.as_secs_f64().ceil().max(1.0) as u64,
+                });
             }
-            return Some(session.clone());
+        }
+        // Checked under the write lock so no payment can slip in between
+        session.check_guard(guard)?;
+        session.apply_dust_policy(dust)?;
+        session.status = status;
+        session.last_finalize_at = Some(now);
+        metrics::counter!("sessions_finalized_total").increment(1);
+        // Only update tx_hash if a new value is provided
+        if let Some(hash) = tx_hash {
+            session.tx_hash = Some(hash);
+        }
+        session.touch();
+        self.publish(session);
+        Ok(session.clone())
+    }
+
+    /// Mark the payments of a pending session confirmed once its
+    /// transaction is mined
+    pub async fn confirm(&self, session_id: &str) -> Option<Session> {
+        let mut sessions = self.sessions.write().await;
+        let session = sessions.get_mut(session_id)?;
+        if session.mark_confirmed() {
+            session.touch();
+            self.publish(session);
+        }
+        Some(session.clone())
+    }
+
+    /// Mark a pending session settled once its transaction is confirmed
+    pub async fn settle(&self, session_id: &str) -> Option<Session> {
+        let mut sessions = self.sessions.write().await;
+        let session = sessions.get_mut(session_id)?;
+        if session.mark_settled() {
+            session.touch();
+            metrics::counter!("sessions_settled_total").increment(1);
+            self.publish(session);
+        }
+        Some(session.clone())
+    }
+
+    /// Set (or clear) the settlement transaction of a pending session, e.g.
+    /// a batch the backend submitted for it
+    pub async fn set_tx_hash(&self, session_id: &str, tx_hash: Option<&str>) -> Option<Session> {
+        let mut sessions = self.sessions.write().await;
+        let session = sessions
+            .get_mut(session_id)
+            .filter(|s| s.status == SessionStatus::Pending)?;
+        session.tx_hash = tx_hash.map(str::to_string);
+        session.touch();
+        self.publish(session);
+        Some(session.clone())
+    }
+}
+
+/// State of a [`SessionStore::watch`] stream
+struct Watch {
+    id: String,
+    store: Arc<SessionStore>,
+    updates: broadcast::Receiver<Session>,
+    next: Option<Session>,
+    sent_version: u64,
+    finished: bool,
+}
+
+impl Watch {
+    async fn next(mut self) -> Option<(Session, Self)> {
+        while !self.finished {
+            // Updates queued before the initial read are older than it
+            if let Some(session) = self.next.take().filter(|s| s.version > self.sent_version) {
+                self.sent_version = session.version;
+                self.finished = matches!(
+                    session.status,
+                    SessionStatus::Settled | SessionStatus::Cancelled
+                );
+                return Some((session, self));
+            }
+            self.next = match self.updates.recv().await {
+                Ok(session) if session.id == self.id => Some(session),
+                Ok(_) => None,
+                // Skipped updates are covered by the current state
+                Err(broadcast::error::RecvError::Lagged(_)) => self.store.get(&self.id).await,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            };
         }
         None
     }
@@ -92,6 +833,81 @@ impl Default for SessionStore {
     }
 }
 
+/// Keys `payment` is indexed under: its recipient, and its ENS name if any
+fn recipient_keys(payment: &Payment) -> impl Iterator<Item = RecipientKey> {
+    std::iter::once(RecipientKey::Address(payment.recipient.clone()))
+        .chain(payment.recipient_ens.clone().map(RecipientKey::Ens))
+}
+
+/// Job cancelling sessions left active for longer than `ttl`
+pub struct SessionExpiryJob {
+    pub store: Arc<SessionStore>,
+    pub ttl: Duration,
+}
+
+impl Job for SessionExpiryJob {
+    fn name(&self) -> &'static str {
+        "session_expiry"
+    }
+
+    fn interval(&self) -> Duration {
+        SWEEP_INTERVAL.min(self.ttl).max(Duration::from_secs(1))
+    }
+
+    async fn run(&self, _ctx: &JobContext) -> Result<(), String> {
+        self.store.sweep_expired(self.ttl).await;
+        Ok(())
+    }
+}
+
+/// Job archiving settled sessions older than `older_than`, appending them
+/// as JSON lines to `path` when set (otherwise they are dropped)
+pub struct SessionArchiveJob {
+    pub store: Arc<SessionStore>,
+    pub older_than: Duration,
+    pub path: Option<PathBuf>,
+}
+
+impl SessionArchiveJob {
+    fn append(&self, path: &PathBuf, sessions: &[Session]) -> std::io::Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let mut lines = Vec::new();
+        for session in sessions {
+            serde_json::to_writer(&mut lines, session)?;
+            lines.push(b'\n');
+        }
+        file.write_all(&lines)
+    }
+}
+
+impl Job for SessionArchiveJob {
+    fn name(&self) -> &'static str {
+        "session_archive"
+    }
+
+    fn interval(&self) -> Duration {
+        SWEEP_INTERVAL
+            .min(self.older_than)
+            .max(Duration::from_secs(1))
+    }
+
+    async fn run(&self, _ctx: &JobContext) -> Result<(), String> {
+        let sessions = self.store.archive_settled(self.older_than).await;
+        let Some(path) = self.path.as_ref().filter(|_| !sessions.is_empty()) else {
+            return Ok(());
+        };
+        if let Err(e) = self.append(path, &sessions) {
+            // Keep them live rather than lose them; the next run retries
+            self.store.restore(sessions).await;
+            return Err(format!("failed to append to {}: {}", path.display(), e));
+        }
+        Ok(())
+    }
+}
+
 /// Session service
 #[allow(dead_code)]
 pub struct SessionService {
@@ -108,7 +924,7 @@ impl SessionService {
     }
 
     /// Create session
-    pub async fn create_session(&self, user: String) -> Session {
+    pub async fn create_session(&self, user: Address) -> Session {
         let id = uuid::Uuid::new_v4().to_string();
         self.store.create(id, user).await
     }
@@ -124,3 +940,329 @@ impl Default for SessionService {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{self, InsertInto, SessionBuilder};
+    use crate::models::session::PaymentStatus;
+
+    fn payment(id: &str, amount: u64) -> Payment {
+        Payment {
+            id: id.to_string(),
+            index: 0,
+            recipient: fixtures::address(fixtures::ALICE),
+            recipient_ens: None,
+            amount: amount.to_string().parse().unwrap(),
+            status: PaymentStatus::Pending,
+            created_at: chrono::Utc::now(),
+            confirmed_at: None,
+            settled_at: None,
+            cancel_reason: None,
+            memo: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_payment_events_are_ordered_and_numbered() {
+        let store = Arc::new(SessionStore::new());
+        let session = SessionBuilder::new().insert_into(&store).await;
+        let other = SessionBuilder::new().insert_into(&store).await;
+        let all = store.subscribe_events(None);
+        let removals =
+            store.subscribe_events(Some(HashSet::from([SessionEventType::PaymentRemoved])));
+
+        // A burst of concurrent adds, interleaved with another session's
+        let adds: Vec<_> = (1..=20u64)
+            .flat_map(|i| [(session.id.clone(), i), (other.id.clone(), i)])
+            .map(|(id, i)| {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    store
+                        .add_payment(&id, payment(&format!("p{}", i), i), None)
+                        .await
+                        .unwrap();
+                })
+            })
+            .collect();
+        for add in adds {
+            add.await.unwrap();
+        }
+        store.remove_payment(&session.id, "p7").await.unwrap();
+
+        let events: Vec<SessionEvent> = all.take(41).collect().await;
+        let ours: Vec<&SessionEvent> = events
+            .iter()
+            .filter(|e| e.session_id == session.id)
+            .collect();
+        let sequences: Vec<u64> = ours.iter().map(|e| e.sequence).collect();
+        assert_eq!(sequences, (1..=21).collect::<Vec<_>>());
+        // Each add's total includes every earlier add, in sequence order
+        let mut total = 0u64;
+        for event in &ours[..20] {
+            assert_eq!(event.kind, SessionEventType::PaymentAdded);
+            total += event.payment.amount.to_string().parse::<u64>().unwrap();
+            assert_eq!(event.total_amount.to_string(), total.to_string());
+        }
+        assert_eq!(total, 210);
+        let theirs = events.iter().filter(|e| e.session_id == other.id);
+        assert!(theirs.map(|e| e.sequence).eq(1..=20));
+
+        // The filtered subscriber only sees the removal
+        let removal = removals.take(1).collect::<Vec<_>>().await.remove(0);
+        assert_eq!(removal.kind, SessionEventType::PaymentRemoved);
+        assert_eq!((removal.sequence, removal.payment.id.as_str()), (21, "p7"));
+        assert_eq!(removal.total_amount.to_string(), "203");
+
+        let body = serde_json::to_value(&removal).unwrap();
+        assert_eq!(body["type"], "payment.removed");
+        assert_eq!(body["payment"]["amount"], "7");
+        assert_eq!(body["total_amount"], "203");
+    }
+
+    #[tokio::test]
+    async fn test_event_log_lives_as_long_as_the_session() {
+        let store = SessionStore::new();
+        let session = SessionBuilder::new()
+            .created_days_ago(2)
+            .insert_into(&store)
+            .await;
+        for i in 1..=3 {
+            store
+                .add_payment(&session.id, payment(&format!("p{}", i), i), None)
+                .await
+                .unwrap();
+        }
+        store.remove_payment(&session.id, "p2").await.unwrap();
+        let sequences = |events: Vec<SessionEvent>| -> Vec<u64> {
+            events.into_iter().map(|e| e.sequence).collect()
+        };
+        assert_eq!(
+            sequences(store.events_since(&session.id, 0).await.unwrap()),
+            [1, 2, 3, 4]
+        );
+        assert_eq!(store.events_since("unknown", 0).await.map(sequences), None);
+
+        // A restored log keeps numbering where it left off
+        let restored = SessionStore::new();
+        restored.restore(store.snapshot().await).await;
+        restored.restore_events(store.events_snapshot().await).await;
+        restored
+            .add_payment(&session.id, payment("p4", 4), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            sequences(restored.events_since(&session.id, 3).await.unwrap()),
+            [4, 5]
+        );
+
+        // Replacing the session drops its log, and so does archiving it
+        restored.restore(vec![session.clone()]).await;
+        assert_eq!(
+            restored.events_since(&session.id, 0).await.unwrap().len(),
+            0
+        );
+        store
+            .update_status(&session.id, SessionStatus::Settled)
+            .await
+            .unwrap();
+        store.archive_settled(Duration::from_secs(3600)).await;
+        assert!(store.events_snapshot().await.is_empty());
+    }
+
+    fn recipient(raw: &str) -> RecipientKey {
+        raw.parse().unwrap()
+    }
+
+    async fn paid_to(store: &SessionStore, user: &str, to: &str) -> Vec<String> {
+        let mut ids: Vec<String> = store
+            .payments_to_recipient(&fixtures::address(user), &recipient(to), None)
+            .await
+            .into_iter()
+            .map(|paid| format!("{}/{}", paid.session_id, paid.payment.id))
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_try_create_stores_a_session_whole_or_not_at_all() {
+        let store = SessionStore::new();
+        let user = fixtures::address(fixtures::USER);
+        store.create("a".to_string(), user.clone()).await;
+        store
+            .add_payment("a", payment("p1", 10), None)
+            .await
+            .unwrap();
+
+        let mut session = Session::new("b".to_string(), user);
+        session.add_payment(payment("p2", 20)).unwrap();
+        session.add_payment(payment("p3", 30)).unwrap();
+        // Its two payments would take the store past two
+        let refused = store.try_create(session.clone(), None, Some(2)).await;
+        assert!(matches!(refused, Err(SessionError::PaymentLimitReached(2))));
+        let refused = store.try_create(session.clone(), Some(1), None).await;
+        assert!(matches!(
+            refused,
+            Err(SessionError::ActiveSessionLimitReached { max: 1, .. })
+        ));
+        assert!(store.get("b").await.is_none());
+        assert_eq!(store.payment_count(), 1);
+        assert_eq!(
+            paid_to(&store, fixtures::USER, fixtures::ALICE).await,
+            ["a/p1"]
+        );
+
+        store.try_create(session, Some(2), Some(3)).await.unwrap();
+        assert_eq!(store.payment_count(), 3);
+        assert_eq!(
+            paid_to(&store, fixtures::USER, fixtures::ALICE).await,
+            ["a/p1", "b/p2", "b/p3"]
+        );
+        // Logged as if added one by one
+        let totals: Vec<String> = store
+            .events_since("b", 0)
+            .await
+            .unwrap()
+            .iter()
+            .map(|e| format!("{}@{}", e.sequence, e.total_amount))
+            .collect();
+        assert_eq!(totals, ["1@20", "2@50"]);
+    }
+
+    #[tokio::test]
+    async fn test_recipient_index_follows_added_and_removed_payments() {
+        let store = SessionStore::new();
+        SessionBuilder::new()
+            .id("a")
+            .ens_payment(fixtures::ALICE, fixtures::ALICE_ENS, "100")
+            .payment(fixtures::BOB, "50")
+            .insert_into(&store)
+            .await;
+        SessionBuilder::new().id("b").insert_into(&store).await;
+        store
+            .add_payment("b", payment("p9", 7), None)
+            .await
+            .unwrap();
+        // Another user's payments to the same recipient are not listed
+        SessionBuilder::new()
+            .id("c")
+            .user(fixtures::BOB)
+            .payment(fixtures::ALICE, "1")
+            .insert_into(&store)
+            .await;
+
+        // Addresses and names match in any letter case
+        let alice = fixtures::ALICE.to_lowercase();
+        assert_eq!(
+            paid_to(&store, fixtures::USER, &alice).await,
+            ["a/p1", "b/p9"]
+        );
+        assert_eq!(paid_to(&store, fixtures::USER, "ALICE.eth").await, ["a/p1"]);
+        assert_eq!(
+            paid_to(&store, fixtures::BOB, fixtures::ALICE).await,
+            ["c/p1"]
+        );
+        assert!(paid_to(&store, fixtures::USER, fixtures::CAROL)
+            .await
+            .is_empty());
+        assert!(store
+            .payments_to_recipient(
+                &fixtures::address(fixtures::USER),
+                &recipient(fixtures::ALICE),
+                Some(&PaymentStatus::Settled)
+            )
+            .await
+            .is_empty());
+
+        store.remove_payment("b", "p9").await.unwrap();
+        assert_eq!(
+            paid_to(&store, fixtures::USER, fixtures::ALICE).await,
+            ["a/p1"]
+        );
+        // Both keys of a named payment go with it, leaving no empty entries
+        store.remove_payment("a", "p1").await.unwrap();
+        assert!(paid_to(&store, fixtures::USER, fixtures::ALICE_ENS)
+            .await
+            .is_empty());
+        assert_eq!(store.recipients.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_recipient_index_follows_restores_and_archiving() {
+        let store = SessionStore::new();
+        let settled = SessionBuilder::new()
+            .id("s")
+            .payment(fixtures::ALICE, "100")
+            .status(SessionStatus::Settled)
+            .created_days_ago(2)
+            .insert_into(&store)
+            .await;
+        let paid = store
+            .payments_to_recipient(
+                &settled.user,
+                &recipient(fixtures::ALICE),
+                Some(&PaymentStatus::Settled),
+            )
+            .await;
+        assert_eq!(paid.len(), 1);
+        assert_eq!(paid[0].payment.amount.to_string(), "100");
+
+        // A restored copy replaces the session's entries
+        let replacement = SessionBuilder::new()
+            .id("s")
+            .payment(fixtures::BOB, "5")
+            .status(SessionStatus::Settled)
+            .created_days_ago(2)
+            .build();
+        store.restore(vec![replacement.clone()]).await;
+        assert!(paid_to(&store, fixtures::USER, fixtures::ALICE)
+            .await
+            .is_empty());
+        assert_eq!(
+            paid_to(&store, fixtures::USER, fixtures::BOB).await,
+            ["s/p1"]
+        );
+        // Restoring the same copy again keeps its entries
+        store.restore(vec![replacement]).await;
+        assert_eq!(
+            paid_to(&store, fixtures::USER, fixtures::BOB).await,
+            ["s/p1"]
+        );
+
+        // Archived sessions are pruned from the index, their settled
+        // payments kept aside
+        assert_eq!(
+            store.archive_settled(Duration::from_secs(3600)).await.len(),
+            1
+        );
+        assert!(store.recipients.lock().unwrap().is_empty());
+        assert_eq!(
+            paid_to(&store, fixtures::USER, fixtures::BOB).await,
+            ["s/p1"]
+        );
+        let pending = store
+            .payments_to_recipient(
+                &settled.user,
+                &recipient(fixtures::BOB),
+                Some(&PaymentStatus::Pending),
+            )
+            .await;
+        assert!(pending.is_empty());
+        // Restoring it from a snapshot puts it back in the index instead
+        let copy = SessionBuilder::new()
+            .id("s")
+            .payment(fixtures::CAROL, "5")
+            .status(SessionStatus::Settled)
+            .build();
+        store.restore(vec![copy]).await;
+        assert!(paid_to(&store, fixtures::USER, fixtures::BOB)
+            .await
+            .is_empty());
+        assert_eq!(
+            paid_to(&store, fixtures::USER, fixtures::CAROL).await,
+            ["s/p1"]
+        );
+        assert!(store.archived_payments.lock().unwrap().is_empty());
+    }
+}
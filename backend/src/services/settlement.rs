@@ -0,0 +1,267 @@
+//! Settlement transaction tracking on the Arc chain
+//!
+//! Looks up the receipt of a session's settlement transaction over JSON-RPC
+//! (`eth_getTransactionReceipt` + `eth_blockNumber`) and reports how many
+//! blocks have confirmed it, prices settlement gas (`eth_gasPrice`), and
+//! submits transactions signed by the node (`eth_sendTransaction`, with
+//! nonces from [`NonceManager`](crate::services::chain::NonceManager),
+//! which checks on them with `eth_getTransactionByHash`).
+
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::models::address::Address;
+use crate::telemetry::{self, DEFAULT_USER_AGENT};
+
+/// Timeout for settlement chain RPC calls
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Gas of one ERC-20 `transfer`, a safe upper estimate per settlement transfer
+pub const TRANSFER_GAS: u64 = 65_000;
+
+/// Decimals of the settlement chain's native (gas) token
+pub const NATIVE_DECIMALS: u8 = 18;
+
+/// Default `NATIVE_TOKEN_PRICE_URL`: USDC, the gas token of Arc
+pub const DEFAULT_NATIVE_TOKEN_PRICE_URL: &str =
+    "https://api.coingecko.com/api/v3/simple/price?ids=usd-coin&vs_currencies=usd";
+
+/// Chain id the native token price is cached under, in a price service of
+/// its own
+pub const NATIVE_PRICE_CHAIN_ID: u64 = 0;
+
+/// How long a native token price is used before it is refetched
+pub const NATIVE_PRICE_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Settlement lookup errors
+#[derive(Error, Debug)]
+pub enum SettlementError {
+    #[error("RPC request failed: {0}")]
+    Rpc(String),
+}
+
+/// State of a settlement transaction on chain
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxStatus {
+    /// Not mined yet (no receipt)
+    Pending,
+    /// Mined and succeeded
+    Confirmed {
+        block_number: u64,
+        confirmations: u64,
+    },
+    /// Mined but reverted
+    Failed {
+        block_number: u64,
+        confirmations: u64,
+    },
+}
+
+/// A mined transaction's receipt
+#[derive(Debug, Clone, PartialEq)]
+pub struct Receipt {
+    pub block_number: u64,
+    pub succeeded: bool,
+    pub logs: Vec<ReceiptLog>,
+}
+
+/// An event log of a receipt; hex fields are lowercased
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiptLog {
+    /// Contract that emitted the event
+    pub address: String,
+    pub topics: Vec<String>,
+}
+
+/// Settlement chain RPC client
+pub struct SettlementService {
+    http_client: reqwest::Client,
+    rpc_url: String,
+}
+
+impl SettlementService {
+    /// Create a settlement tracker against the given JSON-RPC endpoint
+    pub fn new(rpc_url: &str) -> Self {
+        Self {
+            http_client: telemetry::http_client(DEFAULT_USER_AGENT, Some(REQUEST_TIMEOUT)),
+            rpc_url: rpc_url.to_string(),
+        }
+    }
+
+    /// Send `user_agent` as the `User-Agent` of RPC requests
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.http_client = telemetry::http_client(user_agent, Some(REQUEST_TIMEOUT));
+        self
+    }
+
+    /// Current status of `tx_hash`
+    pub async fn tx_status(&self, tx_hash: &str) -> Result<TxStatus, SettlementError> {
+        let Some(receipt) = self.receipt(tx_hash).await? else {
+            return Ok(TxStatus::Pending);
+        };
+        let block_number = receipt.block_number;
+        let confirmations = self.confirmations(block_number).await?;
+
+        Ok(if receipt.succeeded {
+            TxStatus::Confirmed {
+                block_number,
+                confirmations,
+            }
+        } else {
+            TxStatus::Failed {
+                block_number,
+                confirmations,
+            }
+        })
+    }
+
+    /// Receipt of `tx_hash`, or `None` while it is not mined
+    pub async fn receipt(&self, tx_hash: &str) -> Result<Option<Receipt>, SettlementError> {
+        let receipt = self
+            .call("eth_getTransactionReceipt", json!([tx_hash]))
+            .await?;
+        if receipt.is_null() {
+            return Ok(None);
+        }
+
+        let block_number = receipt["blockNumber"]
+            .as_str()
+            .and_then(parse_quantity)
+            .ok_or_else(|| SettlementError::Rpc("receipt has no blockNumber".to_string()))?;
+        let lowercase = |value: &Value| value.as_str().map(str::to_lowercase);
+        let logs = receipt["logs"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|log| {
+                Some(ReceiptLog {
+                    address: lowercase(&log["address"])?,
+                    topics: log["topics"]
+                        .as_array()?
+                        .iter()
+                        .filter_map(lowercase)
+                        .collect(),
+                })
+            })
+            .collect();
+        Ok(Some(Receipt {
+            block_number,
+            succeeded: receipt["status"].as_str() == Some("0x1"),
+            logs,
+        }))
+    }
+
+    /// Blocks confirming a transaction mined in `block_number`; its own
+    /// block counts as the first confirmation
+    pub async fn confirmations(&self, block_number: u64) -> Result<u64, SettlementError> {
+        let head = self
+            .call("eth_blockNumber", json!([]))
+            .await?
+            .as_str()
+            .and_then(parse_quantity)
+            .ok_or_else(|| SettlementError::Rpc("invalid eth_blockNumber result".to_string()))?;
+        Ok(head.saturating_sub(block_number) + 1)
+    }
+
+    /// Send a transaction with `nonce` from `from`, an account the RPC node
+    /// signs for, calling `to` with `data`; returns the transaction hash
+    pub async fn send_transaction(
+        &self,
+        from: &Address,
+        to: &Address,
+        data: &[u8],
+        nonce: u64,
+    ) -> Result<String, SettlementError> {
+        let data: String = data.iter().map(|b| format!("{:02x}", b)).collect();
+        let params = json!([{
+            "from": from.as_str(),
+            "to": to.as_str(),
+            "data": format!("0x{}", data),
+            "nonce": format!("{:#x}", nonce),
+        }]);
+        self.call("eth_sendTransaction", params)
+            .await?
+            .as_str()
+            .map(str::to_lowercase)
+            .ok_or_else(|| SettlementError::Rpc("invalid eth_sendTransaction result".to_string()))
+    }
+
+    /// Whether the node knows `tx_hash`, pending or mined; a sent
+    /// transaction it no longer knows was dropped from its mempool
+    pub async fn knows_transaction(&self, tx_hash: &str) -> Result<bool, SettlementError> {
+        let transaction = self
+            .call("eth_getTransactionByHash", json!([tx_hash]))
+            .await?;
+        Ok(!transaction.is_null())
+    }
+
+    /// Transactions sent from `address`, counting those still pending: the
+    /// nonce its next transaction takes
+    pub async fn transaction_count(&self, address: &Address) -> Result<u64, SettlementError> {
+        self.call(
+            "eth_getTransactionCount",
+            json!([address.as_str(), "pending"]),
+        )
+        .await?
+        .as_str()
+        .and_then(parse_quantity)
+        .ok_or_else(|| SettlementError::Rpc("invalid eth_getTransactionCount result".to_string()))
+    }
+
+    /// Current gas price, in wei of the native token
+    pub async fn gas_price(&self) -> Result<u128, SettlementError> {
+        self.call("eth_gasPrice", json!([]))
+            .await?
+            .as_str()
+            .and_then(parse_quantity)
+            .map(u128::from)
+            .ok_or_else(|| SettlementError::Rpc("invalid eth_gasPrice result".to_string()))
+    }
+
+    /// Make a JSON-RPC call and return its `result`
+    async fn call(&self, method: &str, params: Value) -> Result<Value, SettlementError> {
+        let request = self.http_client.post(&self.rpc_url).json(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        }));
+        let response = telemetry::send("arc_rpc", &self.http_client, request)
+            .await
+            .map_err(|e| SettlementError::Rpc(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(SettlementError::Rpc(format!(
+                "Status: {}",
+                response.status()
+            )));
+        }
+
+        let mut data: Value = response
+            .json()
+            .await
+            .map_err(|e| SettlementError::Rpc(e.to_string()))?;
+        if let Some(error) = data.get("error") {
+            return Err(SettlementError::Rpc(format!("{} error: {}", method, error)));
+        }
+        Ok(data["result"].take())
+    }
+}
+
+/// Parse a `0x`-prefixed hex quantity
+fn parse_quantity(value: &str) -> Option<u64> {
+    u64::from_str_radix(value.strip_prefix("0x")?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quantity() {
+        assert_eq!(parse_quantity("0x10"), Some(16));
+        assert_eq!(parse_quantity("0x0"), Some(0));
+        assert_eq!(parse_quantity("10"), None);
+        assert_eq!(parse_quantity("0xzz"), None);
+    }
+}
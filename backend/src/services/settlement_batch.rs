@@ -0,0 +1,498 @@
+//! Settlement batching for the backend signer
+//!
+//! With `SETTLEMENT_BATCHING` on, a session finalized without a client
+//! transaction is queued here instead of costing a transaction of its own.
+//! Every `SETTLEMENT_BATCH_INTERVAL_SECS`, or as soon as
+//! `SETTLEMENT_BATCH_MAX_SESSIONS` are queued, the queue goes out as one
+//! `finalizeSessions` call on `SETTLEMENT_CONTRACT_ADDRESS`, sent from
+//! `SETTLEMENT_SIGNER_ADDRESS` (an account the `ARC_RPC_URL` node signs
+//! for, with a nonce from its [`NonceManager`]), and each session records
+//! the transaction hash.
+//!
+//! On chain a session is known by its commitment, the keccak-256 of its id.
+//! The receipt's `BatchSettled` logs are matched against the batch's
+//! commitments: sessions are settled only if the transaction succeeded and
+//! its logs name every one of them, so a partial failure settles none. The
+//! sessions of a failed batch go back in the queue, up to
+//! [`MAX_BATCH_ATTEMPTS`] batches each.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use sha3::{Digest, Keccak256};
+
+use crate::config::LiveConfig;
+use crate::models::address::Address;
+use crate::models::session::{Session, SessionStatus, Transfer};
+use crate::services::chain::NonceManager;
+use crate::services::jobs::{Job, JobContext};
+use crate::services::session::SessionStore;
+use crate::services::settlement::{Receipt, SettlementError, SettlementService};
+
+/// Default `SETTLEMENT_BATCH_INTERVAL_SECS`
+pub const DEFAULT_BATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Default `SETTLEMENT_BATCH_MAX_SESSIONS`
+pub const DEFAULT_BATCH_MAX_SESSIONS: usize = 20;
+
+/// Most sessions the contract settles in one call (its `MAX_BATCH_SIZE`)
+pub const MAX_BATCH_SESSIONS: usize = 100;
+
+/// Failed batches a session is queued again after; past them it stays
+/// pending without a transaction until it is finalized again
+pub const MAX_BATCH_ATTEMPTS: u32 = 3;
+
+/// Contract function a batch calls
+const FINALIZE_SESSIONS: &str = "finalizeSessions(bytes32[],(address,uint256)[][])";
+
+/// Event the contract emits for each settled session
+const BATCH_SETTLED: &str = "BatchSettled(bytes32,uint256,uint256)";
+
+/// On-chain id of a session: the keccak-256 of its id
+pub fn commitment(session_id: &str) -> [u8; 32] {
+    Keccak256::digest(session_id.as_bytes()).into()
+}
+
+/// A submitted batch awaiting its receipt
+#[derive(Debug, Clone)]
+struct SubmittedBatch {
+    tx_hash: String,
+    session_ids: Vec<String>,
+}
+
+/// Queues finalized sessions and settles them in shared transactions
+pub struct SettlementBatcher {
+    store: Arc<SessionStore>,
+    rpc: Arc<SettlementService>,
+    contract: Address,
+    nonces: Arc<NonceManager>,
+    max_sessions: usize,
+    queue: Mutex<VecDeque<String>>,
+    submitted: Mutex<Vec<SubmittedBatch>>,
+    /// Failed batches of the sessions queued again after one
+    attempts: Mutex<HashMap<String, u32>>,
+    /// Held while a batch is built and sent, so flushes never overlap
+    flushing: tokio::sync::Mutex<()>,
+}
+
+impl SettlementBatcher {
+    /// Batch up to `max_sessions` sessions of `store` into `finalizeSessions`
+    /// calls on `contract`, sent by the signer of `nonces` and tracked over
+    /// `rpc`
+    pub fn new(
+        store: Arc<SessionStore>,
+        rpc: Arc<SettlementService>,
+        contract: Address,
+        nonces: Arc<NonceManager>,
+        max_sessions: usize,
+    ) -> Self {
+        Self {
+            store,
+            rpc,
+            contract,
+            nonces,
+            max_sessions: max_sessions.clamp(1, MAX_BATCH_SESSIONS),
+            queue: Mutex::new(VecDeque::new()),
+            submitted: Mutex::new(Vec::new()),
+            attempts: Mutex::new(HashMap::new()),
+            flushing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Queue a session for the next batch, unless it already has a
+    /// transaction or is in a submitted batch, e.g. when finalize is
+    /// retried; returns whether the queue holds a full batch and should be
+    /// flushed now
+    pub fn enqueue(&self, session: &Session) -> bool {
+        let mut queue = self.queue.lock().unwrap();
+        if session.tx_hash.is_none()
+            && !self.is_submitted(&session.id)
+            && !queue.contains(&session.id)
+        {
+            self.attempts.lock().unwrap().remove(&session.id);
+            queue.push_back(session.id.clone());
+        }
+        metrics::gauge!("settlement_batch_queued").set(queue.len() as f64);
+        queue.len() >= self.max_sessions
+    }
+
+    /// Sessions waiting for a batch
+    pub fn queued(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    /// Whether a session is in a submitted batch that is not reconciled yet
+    pub fn is_submitted(&self, session_id: &str) -> bool {
+        self.submitted
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|batch| batch.session_ids.iter().any(|id| id == session_id))
+    }
+
+    /// Send the queued sessions (up to a full batch) in one transaction and
+    /// record its hash on each of them. Sessions that are no longer pending,
+    /// have nothing to transfer, or already have a transaction are dropped;
+    /// if sending fails, the rest
+    /// go back to the front of the queue. Returns the hash, or `None` if
+    /// there was nothing to send.
+    pub async fn flush(&self) -> Result<Option<String>, SettlementError> {
+        let _flushing = self.flushing.lock().await;
+        let ids: Vec<String> = {
+            let mut queue = self.queue.lock().unwrap();
+            let count = queue.len().min(self.max_sessions);
+            queue.drain(..count).collect()
+        };
+
+        let mut sessions = Vec::with_capacity(ids.len());
+        for id in &ids {
+            match self.store.get(id).await {
+                Some(session)
+                    if session.status == SessionStatus::Pending
+                        && session.tx_hash.is_none()
+                        && !self.is_submitted(id)
+                        && !session.transfers().is_empty() =>
+                {
+                    sessions.push(session)
+                }
+                _ => tracing::info!("Dropping session {} from the settlement batch", id),
+            }
+        }
+        if sessions.is_empty() {
+            return Ok(None);
+        }
+
+        let data = finalize_sessions_calldata(&sessions);
+        let tx_hash = match self.nonces.send(&self.contract, &data).await {
+            Ok(tx_hash) => tx_hash,
+            Err(e) => {
+                let mut queue = self.queue.lock().unwrap();
+                for session in sessions.iter().rev() {
+                    queue.push_front(session.id.clone());
+                }
+                metrics::counter!("settlement_batches_total", "result" => "send_failed")
+                    .increment(1);
+                return Err(e);
+            }
+        };
+
+        for session in &sessions {
+            self.store.set_tx_hash(&session.id, Some(&tx_hash)).await;
+        }
+        tracing::info!(
+            "Submitted settlement batch {} for {} sessions",
+            tx_hash,
+            sessions.len()
+        );
+        metrics::counter!("settlement_batches_total", "result" => "submitted").increment(1);
+        self.submitted.lock().unwrap().push(SubmittedBatch {
+            tx_hash: tx_hash.clone(),
+            session_ids: sessions.into_iter().map(|s| s.id).collect(),
+        });
+        metrics::gauge!("settlement_batch_queued").set(self.queued() as f64);
+        Ok(Some(tx_hash))
+    }
+
+    /// Check the receipts of submitted batches. A mined batch that settled
+    /// every one of its sessions confirms their payments, and settles them
+    /// once `min_confirmations` deep. A reverted or incomplete batch settles
+    /// none of them: it is dropped and its sessions, pending without a
+    /// transaction, are queued for the next batch (see
+    /// [`MAX_BATCH_ATTEMPTS`]). Unmined batches are checked again next time.
+    ///
+    /// While any nonce is in flight, the signer's nonces are reconciled
+    /// first. A batch whose transaction the node dropped fails like a
+    /// reverted one, and its nonce goes to the next transaction, so it does
+    /// not stall later batches.
+    pub async fn reconcile(&self, min_confirmations: u64) -> Result<(), SettlementError> {
+        let mut first_error = None;
+        if !self.nonces.in_flight().await.is_empty() {
+            match self.nonces.reconcile().await {
+                Ok(reconciled) => {
+                    for tx_hash in reconciled.dropped {
+                        let batch = {
+                            let mut submitted = self.submitted.lock().unwrap();
+                            let position = submitted.iter().position(|b| b.tx_hash == tx_hash);
+                            position.map(|i| submitted.remove(i))
+                        };
+                        if let Some(batch) = batch {
+                            tracing::warn!(
+                                "Settlement batch {} was dropped by the node; none of its {} sessions is settled",
+                                batch.tx_hash,
+                                batch.session_ids.len()
+                            );
+                            self.fail(&batch).await;
+                        }
+                    }
+                }
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        let batches = self.submitted.lock().unwrap().clone();
+        for batch in batches {
+            match self.reconcile_batch(&batch, min_confirmations).await {
+                Ok(true) => self
+                    .submitted
+                    .lock()
+                    .unwrap()
+                    .retain(|b| b.tx_hash != batch.tx_hash),
+                Ok(false) => {}
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Reconcile one batch; returns whether it is done with
+    async fn reconcile_batch(
+        &self,
+        batch: &SubmittedBatch,
+        min_confirmations: u64,
+    ) -> Result<bool, SettlementError> {
+        let Some(receipt) = self.rpc.receipt(&batch.tx_hash).await? else {
+            return Ok(false);
+        };
+        let settled = settled_commitments(&receipt, &self.contract);
+        let missing: Vec<&String> = batch
+            .session_ids
+            .iter()
+            .filter(|id| !settled.contains(&commitment(id)))
+            .collect();
+        if !receipt.succeeded || !missing.is_empty() {
+            tracing::warn!(
+                "Settlement batch {} failed (succeeded: {}, sessions not settled: {:?}); \
+                 none of its {} sessions is settled",
+                batch.tx_hash,
+                receipt.succeeded,
+                missing,
+                batch.session_ids.len()
+            );
+            self.fail(batch).await;
+            return Ok(true);
+        }
+
+        let confirmations = self.rpc.confirmations(receipt.block_number).await?;
+        if confirmations < min_confirmations {
+            for id in &batch.session_ids {
+                self.store.confirm(id).await;
+            }
+            return Ok(false);
+        }
+        self.attempts
+            .lock()
+            .unwrap()
+            .retain(|id, _| !batch.session_ids.contains(id));
+        for id in &batch.session_ids {
+            self.store.settle(id).await;
+        }
+        tracing::info!(
+            "Settlement batch {} confirmed for {} sessions",
+            batch.tx_hash,
+            batch.session_ids.len()
+        );
+        metrics::counter!("settlement_batches_total", "result" => "settled").increment(1);
+        Ok(true)
+    }
+
+    /// Clear the transaction of a failed batch's sessions and queue them
+    /// again
+    async fn fail(&self, batch: &SubmittedBatch) {
+        for id in &batch.session_ids {
+            self.store.set_tx_hash(id, None).await;
+        }
+        self.requeue(&batch.session_ids);
+        metrics::counter!("settlement_batches_total", "result" => "failed").increment(1);
+    }
+
+    /// Queue the sessions of a failed batch again, except those that have
+    /// failed [`MAX_BATCH_ATTEMPTS`] batches
+    fn requeue(&self, session_ids: &[String]) {
+        let mut queue = self.queue.lock().unwrap();
+        let mut attempts = self.attempts.lock().unwrap();
+        for id in session_ids {
+            let failed = attempts.entry(id.clone()).or_default();
+            *failed += 1;
+            if *failed >= MAX_BATCH_ATTEMPTS {
+                attempts.remove(id);
+                metrics::counter!("settlement_batch_sessions_abandoned_total").increment(1);
+                tracing::error!(
+                    "Session {} failed {} settlement batches; it stays pending until finalized again",
+                    id,
+                    MAX_BATCH_ATTEMPTS
+                );
+            } else if !queue.contains(id) {
+                queue.push_back(id.clone());
+            }
+        }
+        metrics::gauge!("settlement_batch_queued").set(queue.len() as f64);
+    }
+}
+
+/// Commitments of the sessions `contract` reported settled in `receipt`
+fn settled_commitments(receipt: &Receipt, contract: &Address) -> HashSet<[u8; 32]> {
+    let topic = hex(&Keccak256::digest(BATCH_SETTLED.as_bytes()));
+    receipt
+        .logs
+        .iter()
+        .filter(|log| log.address == contract.as_str())
+        .filter(|log| log.topics.first() == Some(&topic))
+        .filter_map(|log| decode_word(log.topics.get(1)?))
+        .collect()
+}
+
+/// ABI-encoded `finalizeSessions(sessionIds, settlements)` call settling
+/// the transfers of `sessions`
+fn finalize_sessions_calldata(sessions: &[Session]) -> Vec<u8> {
+    let transfers: Vec<Vec<Transfer>> = sessions.iter().map(Session::transfers).collect();
+    let count = sessions.len();
+
+    let mut words: Vec<[u8; 32]> = Vec::new();
+    // Heads: offsets of the two dynamic arguments
+    words.push(uint(64));
+    words.push(uint(64 + 32 * (1 + count)));
+    // sessionIds
+    words.push(uint(count));
+    words.extend(sessions.iter().map(|s| commitment(&s.id)));
+    // settlements: offsets of the inner arrays, from the end of the length word
+    words.push(uint(count));
+    let mut offset = 32 * count;
+    for inner in &transfers {
+        words.push(uint(offset));
+        offset += 32 + 64 * inner.len();
+    }
+    for inner in &transfers {
+        words.push(uint(inner.len()));
+        for transfer in inner {
+            words.push(address_word(&transfer.to));
+            words.push(transfer.amount.to_be_bytes());
+        }
+    }
+
+    let selector = Keccak256::digest(FINALIZE_SESSIONS.as_bytes());
+    let mut data = selector[..4].to_vec();
+    for word in words {
+        data.extend_from_slice(&word);
+    }
+    data
+}
+
+fn uint(value: usize) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&(value as u64).to_be_bytes());
+    word
+}
+
+fn address_word(address: &Address) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    let digits = address.as_str().trim_start_matches("0x").as_bytes();
+    for (byte, pair) in word[12..].iter_mut().zip(digits.chunks(2)) {
+        *byte = std::str::from_utf8(pair)
+            .ok()
+            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            .unwrap_or_default();
+    }
+    word
+}
+
+/// A `0x`-prefixed 32-byte hex word
+fn decode_word(value: &str) -> Option<[u8; 32]> {
+    let digits = value.strip_prefix("0x")?;
+    if digits.len() != 64 {
+        return None;
+    }
+    let mut word = [0u8; 32];
+    for (byte, pair) in word.iter_mut().zip(digits.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(word)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let digits: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("0x{}", digits)
+}
+
+/// Job sending the batch queue every `interval` and settling mined batches
+pub struct SettlementBatchJob {
+    pub batcher: Arc<SettlementBatcher>,
+    pub live_config: LiveConfig,
+    pub interval: Duration,
+}
+
+impl Job for SettlementBatchJob {
+    fn name(&self) -> &'static str {
+        "settlement_batch"
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    async fn run(&self, _ctx: &JobContext) -> Result<(), String> {
+        let sent = self.batcher.flush().await;
+        let min_confirmations = self.live_config.get().settlement_min_confirmations;
+        let reconciled = self.batcher.reconcile(min_confirmations).await;
+        sent.map(drop).and(reconciled).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{address, ALICE, BOB};
+    use crate::services::settlement::ReceiptLog;
+    use settleone_types::fixtures::SessionBuilder;
+
+    #[test]
+    fn test_calldata_layout() {
+        let session = SessionBuilder::new()
+            .id("s1")
+            .payment(ALICE, "1000000")
+            .build();
+        let data = finalize_sessions_calldata(std::slice::from_ref(&session));
+        let words: Vec<String> = data[4..].chunks(32).map(hex).collect();
+        let word = |value: &str| format!("0x{:0>64}", value);
+
+        assert_eq!(data.len(), 4 + 9 * 32);
+        assert_eq!(
+            words,
+            [
+                word("40"),
+                word("80"),
+                // sessionIds: one commitment
+                word("1"),
+                hex(&commitment("s1")),
+                // settlements: one list of one transfer
+                word("1"),
+                word("20"),
+                word("1"),
+                word(&ALICE.trim_start_matches("0x").to_lowercase()),
+                word("f4240"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_settled_commitments_come_from_the_contract() {
+        let contract = address(BOB);
+        let topic = hex(&Keccak256::digest(BATCH_SETTLED.as_bytes()));
+        let log = |emitter: &str, session: &str| ReceiptLog {
+            address: emitter.to_lowercase(),
+            topics: vec![topic.clone(), hex(&commitment(session))],
+        };
+        let receipt = Receipt {
+            block_number: 1,
+            succeeded: true,
+            logs: vec![log(BOB, "s1"), log(ALICE, "s2")],
+        };
+
+        let settled = settled_commitments(&receipt, &contract);
+        assert_eq!(settled, HashSet::from([commitment("s1")]));
+    }
+}
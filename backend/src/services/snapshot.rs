@@ -0,0 +1,231 @@
+//! Session store snapshots
+//!
+//! A snapshot is a JSON document
+//! `{ "version": 1, "exported_at": ..., "sessions": [...], "events": [...],
+//! "templates": [...] }`; `events` holds the sessions' event logs and
+//! `templates` the session templates, and either may be absent.
+//! `serve` restores the snapshot at `SESSION_SNAPSHOT_PATH` on startup and
+//! writes it back on shutdown; the `snapshot` CLI commands move it in and out.
+//! Sessions are upgraded from older schema versions as they are read.
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::models::migrations::{migrate_session, MigrationError};
+use crate::models::session::{Session, SessionEvent, SessionTemplate};
+
+/// Snapshot format version written by this build
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Snapshot read/write errors
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("failed to access {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("{path} is not a valid snapshot: {source}")]
+    Parse {
+        path: String,
+        source: serde_json::Error,
+    },
+
+    #[error("unsupported snapshot version {0} (expected {SNAPSHOT_VERSION})")]
+    UnsupportedVersion(u32),
+
+    #[error("{path}: session {index} cannot be loaded: {source}")]
+    Migration {
+        path: String,
+        index: usize,
+        source: MigrationError,
+    },
+}
+
+/// A snapshot as stored, before its sessions are migrated
+#[derive(Deserialize)]
+struct StoredSnapshot {
+    version: u32,
+    exported_at: DateTime<Utc>,
+    sessions: Vec<serde_json::Value>,
+    #[serde(default)]
+    events: Vec<SessionEvent>,
+    #[serde(default)]
+    templates: Vec<SessionTemplate>,
+}
+
+/// Point-in-time copy of every stored session
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub sessions: Vec<Session>,
+    /// Event logs of `sessions`, by session then sequence
+    pub events: Vec<SessionEvent>,
+    pub templates: Vec<SessionTemplate>,
+}
+
+impl Snapshot {
+    /// Snapshot of `sessions` taken now
+    pub fn new(sessions: Vec<Session>) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            exported_at: Utc::now(),
+            sessions,
+            events: Vec::new(),
+            templates: Vec::new(),
+        }
+    }
+
+    /// Carry the event logs of the sessions too
+    pub fn with_events(mut self, events: Vec<SessionEvent>) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Carry the session templates too
+    pub fn with_templates(mut self, templates: Vec<SessionTemplate>) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    /// Read and validate a snapshot file
+    pub fn read(path: &Path) -> Result<Self, SnapshotError> {
+        let display = path.display().to_string();
+        let bytes = std::fs::read(path).map_err(|source| SnapshotError::Io {
+            path: display.clone(),
+            source,
+        })?;
+        let stored: StoredSnapshot =
+            serde_json::from_slice(&bytes).map_err(|source| SnapshotError::Parse {
+                path: display.clone(),
+                source,
+            })?;
+        if stored.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(stored.version));
+        }
+        let sessions = stored
+            .sessions
+            .into_iter()
+            .enumerate()
+            .map(|(index, session)| {
+                migrate_session(session).map_err(|source| SnapshotError::Migration {
+                    path: display.clone(),
+                    index,
+                    source,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Snapshot {
+            version: stored.version,
+            exported_at: stored.exported_at,
+            sessions,
+            events: stored.events,
+            templates: stored.templates,
+        })
+    }
+
+    /// Write the snapshot, replacing `path` atomically
+    pub fn write(&self, path: &Path) -> Result<(), SnapshotError> {
+        let io_error = |source| SnapshotError::Io {
+            path: path.display().to_string(),
+            source,
+        };
+        let json = serde_json::to_vec_pretty(self).expect("sessions serialize to JSON");
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json).map_err(io_error)?;
+        std::fs::rename(&tmp, path).map_err(io_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+        let session = Session::new(
+            "s1".to_string(),
+            "0x2222222222222222222222222222222222222222"
+                .parse()
+                .unwrap(),
+        );
+
+        Snapshot::new(vec![session]).write(&path).unwrap();
+        let restored = Snapshot::read(&path).unwrap();
+        assert_eq!(restored.version, SNAPSHOT_VERSION);
+        assert_eq!(restored.sessions.len(), 1);
+        assert_eq!(restored.sessions[0].id, "s1");
+        assert!(!dir.path().join("sessions.tmp").exists());
+    }
+
+    #[test]
+    fn test_migrates_legacy_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+        let legacy = include_str!("../../tests/fixtures/sessions/v1.json");
+        std::fs::write(
+            &path,
+            format!(
+                r#"{{"version": 1, "exported_at": "2026-01-01T00:00:00Z", "sessions": [{}]}}"#,
+                legacy
+            ),
+        )
+        .unwrap();
+
+        let restored = Snapshot::read(&path).unwrap();
+        assert_eq!(
+            restored.sessions[0].schema_version,
+            crate::models::session::SESSION_SCHEMA_VERSION
+        );
+        assert_eq!(restored.sessions[0].version, 1);
+    }
+
+    #[test]
+    fn test_rejects_unknown_version_and_garbage() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sessions.json");
+
+        std::fs::write(
+            &path,
+            r#"{"version": 99, "exported_at": "2026-01-01T00:00:00Z", "sessions": []}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            Snapshot::read(&path),
+            Err(SnapshotError::UnsupportedVersion(99))
+        ));
+
+        // Sessions from a newer build are refused, not half-loaded
+        std::fs::write(
+            &path,
+            r#"{"version": 1, "exported_at": "2026-01-01T00:00:00Z", "sessions": [{"schema_version": 99}]}"#,
+        )
+        .unwrap();
+        let err = Snapshot::read(&path).unwrap_err();
+        assert!(matches!(
+            err,
+            SnapshotError::Migration {
+                index: 0,
+                source: MigrationError::UnsupportedVersion(99),
+                ..
+            }
+        ));
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(matches!(
+            Snapshot::read(&path),
+            Err(SnapshotError::Parse { .. })
+        ));
+        assert!(matches!(
+            Snapshot::read(&dir.path().join("missing.json")),
+            Err(SnapshotError::Io { .. })
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+//! Session template store
+//!
+//! Templates are kept alongside sessions: in memory, and in the session
+//! snapshot at `SESSION_SNAPSHOT_PATH` when one is configured.
+
+use std::collections::HashMap;
+
+use tokio::sync::RwLock;
+
+use crate::models::address::Address;
+use crate::models::session::SessionTemplate;
+
+/// Template store (in-memory, like [`SessionStore`](crate::services::session::SessionStore))
+#[derive(Default)]
+pub struct TemplateStore {
+    templates: RwLock<HashMap<String, SessionTemplate>>,
+}
+
+impl TemplateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a template, replacing any with the same id
+    pub async fn insert(&self, template: SessionTemplate) {
+        let mut templates = self.templates.write().await;
+        templates.insert(template.id.clone(), template);
+        metrics::gauge!("templates_stored").set(templates.len() as f64);
+    }
+
+    pub async fn get(&self, id: &str) -> Option<SessionTemplate> {
+        self.templates.read().await.get(id).cloned()
+    }
+
+    /// Templates of `owner`, oldest first
+    pub async fn list(&self, owner: &Address) -> Vec<SessionTemplate> {
+        let mut templates: Vec<SessionTemplate> = self
+            .templates
+            .read()
+            .await
+            .values()
+            .filter(|t| &t.owner == owner)
+            .cloned()
+            .collect();
+        sort(&mut templates);
+        templates
+    }
+
+    /// Remove a template, returning it if it existed
+    pub async fn remove(&self, id: &str) -> Option<SessionTemplate> {
+        let mut templates = self.templates.write().await;
+        let removed = templates.remove(id);
+        metrics::gauge!("templates_stored").set(templates.len() as f64);
+        removed
+    }
+
+    /// Copy of every stored template, oldest first
+    pub async fn snapshot(&self) -> Vec<SessionTemplate> {
+        let mut templates: Vec<SessionTemplate> =
+            self.templates.read().await.values().cloned().collect();
+        sort(&mut templates);
+        templates
+    }
+
+    /// Load templates (e.g. from a snapshot), replacing any with the same id
+    pub async fn restore(&self, restored: Vec<SessionTemplate>) -> usize {
+        let count = restored.len();
+        let mut templates = self.templates.write().await;
+        for template in restored {
+            templates.insert(template.id.clone(), template);
+        }
+        metrics::gauge!("templates_stored").set(templates.len() as f64);
+        count
+    }
+}
+
+fn sort(templates: &mut [SessionTemplate]) {
+    templates.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{address, ALICE, BOB, USER};
+    use crate::models::session::TemplateEntry;
+    use chrono::{Duration, Utc};
+
+    fn template(id: &str, owner: &str, age_days: i64) -> SessionTemplate {
+        SessionTemplate {
+            id: id.to_string(),
+            name: format!("template {}", id),
+            owner: address(owner),
+            entries: vec![TemplateEntry {
+                recipient: address(ALICE),
+                recipient_ens: None,
+                default_amount: Some("100".parse().unwrap()),
+                memo: None,
+            }],
+            created_at: Utc::now() - Duration::days(age_days),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_template_crud() {
+        let store = TemplateStore::new();
+        store.insert(template("t1", USER, 1)).await;
+        store.insert(template("t2", USER, 2)).await;
+        store.insert(template("t3", BOB, 0)).await;
+
+        assert_eq!(store.get("t1").await.unwrap().name, "template t1");
+        assert!(store.get("missing").await.is_none());
+        let ids: Vec<String> = store
+            .list(&address(USER))
+            .await
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(ids, ["t2", "t1"]);
+
+        assert_eq!(store.remove("t2").await.unwrap().id, "t2");
+        assert!(store.remove("t2").await.is_none());
+        assert_eq!(store.list(&address(USER)).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_round_trip() {
+        let store = TemplateStore::new();
+        store.insert(template("t1", USER, 0)).await;
+        store.insert(template("t2", BOB, 1)).await;
+        let saved = store.snapshot().await;
+        assert_eq!(saved[0].id, "t2");
+
+        let restored = TemplateStore::new();
+        assert_eq!(restored.restore(saved.clone()).await, 2);
+        assert_eq!(restored.snapshot().await, saved);
+    }
+}
@@ -0,0 +1,274 @@
+//! Webhook delivery of session payment events
+//!
+//! Receivers are registered at `POST /admin/webhooks`, for every event
+//! type or only some. Each registration subscribes to the session store's
+//! events and queues the ones it wants; [`WebhookDeliveryJob`] posts every
+//! receiver's queue one event at a time, oldest first. A failed delivery
+//! stays at the head of the queue and is retried on the next run, so a
+//! receiver gets each session's events in sequence order. Registrations
+//! are kept in memory only.
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::Serialize;
+use tokio::task::AbortHandle;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::session::{SessionEvent, SessionEventType};
+use crate::services::jobs::{Job, JobContext};
+use crate::services::session::SessionStore;
+use crate::telemetry::{self, DEFAULT_USER_AGENT};
+
+/// How often queued events are delivered
+pub const DELIVERY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Events queued per receiver; beyond it the oldest are dropped, which the
+/// receiver sees as a gap in the sequence
+pub const MAX_QUEUED_EVENTS: usize = 1_000;
+
+/// Timeout of one delivery
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A registered webhook receiver
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Webhook {
+    pub id: String,
+    /// Events are POSTed here as JSON
+    pub url: String,
+    /// Event types delivered; every type when absent
+    pub types: Option<Vec<SessionEventType>>,
+    pub created_at: DateTime<Utc>,
+    /// Events waiting to be delivered
+    pub queued: usize,
+    pub delivered: u64,
+    pub failures: u64,
+    /// Error of the latest delivery, cleared by a success
+    pub last_error: Option<String>,
+}
+
+/// A receiver with its queue and the task filling it
+struct Receiver {
+    webhook: Webhook,
+    queue: VecDeque<SessionEvent>,
+    forwarder: AbortHandle,
+}
+
+impl Receiver {
+    fn wants(&self, kind: SessionEventType) -> bool {
+        self.webhook
+            .types
+            .as_ref()
+            .is_none_or(|types| types.contains(&kind))
+    }
+
+    fn push(&mut self, event: SessionEvent) {
+        if self.queue.len() >= MAX_QUEUED_EVENTS {
+            self.queue.pop_front();
+            metrics::counter!("webhook_events_dropped_total").increment(1);
+            tracing::warn!(
+                "Webhook {} has {} undelivered events; dropping the oldest",
+                self.webhook.id,
+                MAX_QUEUED_EVENTS
+            );
+        }
+        self.queue.push_back(event);
+    }
+}
+
+/// Registered receivers and their delivery queues
+pub struct WebhookService {
+    store: Arc<SessionStore>,
+    http_client: reqwest::Client,
+    receivers: Arc<Mutex<BTreeMap<String, Receiver>>>,
+    /// Held while delivering, so deliveries never overlap
+    delivering: tokio::sync::Mutex<()>,
+}
+
+impl WebhookService {
+    /// Deliver the events of `store`
+    pub fn new(store: Arc<SessionStore>) -> Self {
+        Self {
+            store,
+            http_client: telemetry::http_client(DEFAULT_USER_AGENT, Some(REQUEST_TIMEOUT)),
+            receivers: Arc::new(Mutex::new(BTreeMap::new())),
+            delivering: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Send `user_agent` as the `User-Agent` of deliveries
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.http_client = telemetry::http_client(user_agent, Some(REQUEST_TIMEOUT));
+        self
+    }
+
+    /// Register `url` for the events of `types` (all if `None`) from now on
+    pub fn register(&self, url: String, types: Option<Vec<SessionEventType>>) -> Webhook {
+        let webhook = Webhook {
+            id: Uuid::new_v4().to_string(),
+            url,
+            types,
+            created_at: Utc::now(),
+            queued: 0,
+            delivered: 0,
+            failures: 0,
+            last_error: None,
+        };
+        let filter = webhook
+            .types
+            .as_ref()
+            .map(|types| types.iter().copied().collect::<HashSet<_>>());
+        // Subscribed before returning, so no later event is missed
+        let mut events = self.store.subscribe_events(filter);
+        let receivers = self.receivers.clone();
+        let id = webhook.id.clone();
+
+        let mut registered = self.receivers.lock().unwrap();
+        let forwarder = tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                match receivers.lock().unwrap().get_mut(&id) {
+                    Some(receiver) => receiver.push(event),
+                    None => break,
+                }
+            }
+        })
+        .abort_handle();
+        registered.insert(
+            webhook.id.clone(),
+            Receiver {
+                webhook: webhook.clone(),
+                queue: VecDeque::new(),
+                forwarder,
+            },
+        );
+        metrics::gauge!("webhooks_registered").set(registered.len() as f64);
+        tracing::info!("Registered webhook {} for {}", webhook.id, webhook.url);
+        webhook
+    }
+
+    /// Every registered receiver, by id
+    pub fn list(&self) -> Vec<Webhook> {
+        self.receivers
+            .lock()
+            .unwrap()
+            .values()
+            .map(|receiver| Webhook {
+                queued: receiver.queue.len(),
+                ..receiver.webhook.clone()
+            })
+            .collect()
+    }
+
+    /// Unregister a receiver, dropping its queue; returns whether it existed
+    pub fn remove(&self, id: &str) -> bool {
+        let mut receivers = self.receivers.lock().unwrap();
+        let Some(receiver) = receivers.remove(id) else {
+            return false;
+        };
+        receiver.forwarder.abort();
+        metrics::gauge!("webhooks_registered").set(receivers.len() as f64);
+        true
+    }
+
+    /// Queue `event` again for every receiver of its type; returns how many
+    /// receivers it was queued for
+    pub fn enqueue(&self, event: &SessionEvent) -> usize {
+        let mut receivers = self.receivers.lock().unwrap();
+        let mut queued = 0;
+        for receiver in receivers.values_mut().filter(|r| r.wants(event.kind)) {
+            receiver.push(event.clone());
+            queued += 1;
+        }
+        queued
+    }
+
+    /// POST each receiver's queued events, oldest first. A receiver's
+    /// delivery stops at its first failure, leaving that event queued.
+    pub async fn deliver(&self) -> Result<(), String> {
+        let _delivering = self.delivering.lock().await;
+        let ids: Vec<String> = self.receivers.lock().unwrap().keys().cloned().collect();
+        let mut failures = Vec::new();
+        for id in ids {
+            loop {
+                let next = self
+                    .receivers
+                    .lock()
+                    .unwrap()
+                    .get(&id)
+                    .and_then(|receiver| {
+                        let event = receiver.queue.front()?.clone();
+                        Some((receiver.webhook.url.clone(), event))
+                    });
+                let Some((url, event)) = next else {
+                    break;
+                };
+
+                let sent = self.post(&url, &event).await;
+                let label = if sent.is_ok() { "ok" } else { "error" };
+                metrics::counter!("webhook_deliveries_total", "result" => label).increment(1);
+                let mut receivers = self.receivers.lock().unwrap();
+                let Some(receiver) = receivers.get_mut(&id) else {
+                    break;
+                };
+                match sent {
+                    Ok(()) => {
+                        // Unless it was dropped as the oldest meanwhile
+                        if receiver.queue.front().is_some_and(|queued| {
+                            queued.session_id == event.session_id
+                                && queued.sequence == event.sequence
+                        }) {
+                            receiver.queue.pop_front();
+                        }
+                        receiver.webhook.delivered += 1;
+                        receiver.webhook.last_error = None;
+                    }
+                    Err(e) => {
+                        receiver.webhook.failures += 1;
+                        receiver.webhook.last_error = Some(e.clone());
+                        failures.push(format!("webhook {}: {}", id, e));
+                        break;
+                    }
+                }
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join("; "))
+        }
+    }
+
+    async fn post(&self, url: &str, event: &SessionEvent) -> Result<(), String> {
+        let request = self.http_client.post(url).json(event);
+        let response = telemetry::send("webhook", &self.http_client, request)
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("Status: {}", response.status()));
+        }
+        Ok(())
+    }
+}
+
+/// Job delivering queued webhook events every [`DELIVERY_INTERVAL`]
+pub struct WebhookDeliveryJob {
+    pub webhooks: Arc<WebhookService>,
+}
+
+impl Job for WebhookDeliveryJob {
+    fn name(&self) -> &'static str {
+        "webhook_delivery"
+    }
+
+    fn interval(&self) -> Duration {
+        DELIVERY_INTERVAL
+    }
+
+    async fn run(&self, _ctx: &JobContext) -> Result<(), String> {
+        self.webhooks.deliver().await
+    }
+}
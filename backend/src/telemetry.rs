@@ -0,0 +1,233 @@
+//! OpenTelemetry trace export and upstream call spans
+//!
+//! When `OTEL_EXPORTER_OTLP_ENDPOINT` is set, tracing spans are exported over
+//! OTLP/HTTP; otherwise no OpenTelemetry layer is installed at all. Every
+//! outbound HTTP call (ENS, LI.FI, RPC) goes through [`send`], which wraps it
+//! in an `upstream_request` span carrying url, status, latency and the id of
+//! the API request that triggered it, and adds its latency to the request's
+//! [`UpstreamCalls`]. Clients for those calls are built with
+//! [`client_builder`] so every request identifies us with a `User-Agent`.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use opentelemetry::trace::{TraceError, TracerProvider as _};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::TracerProvider;
+use opentelemetry_sdk::{runtime, Resource};
+use tracing::field::Empty;
+use tracing::{Instrument, Subscriber};
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::api::middleware::current_request_id;
+
+/// Service name reported to the collector
+const SERVICE_NAME: &str = "settleone-backend";
+
+tokio::task_local! {
+    /// Upstream calls made while handling the current API request
+    pub static UPSTREAM_CALLS: UpstreamCalls;
+}
+
+/// Call count and total latency per upstream, for one API request
+#[derive(Clone, Default)]
+pub struct UpstreamCalls(Arc<Mutex<BTreeMap<&'static str, (u32, Duration)>>>);
+
+impl UpstreamCalls {
+    fn record(&self, upstream: &'static str, latency: Duration) {
+        let mut calls = self.0.lock().unwrap();
+        let (count, total) = calls.entry(upstream).or_default();
+        *count += 1;
+        *total += latency;
+    }
+
+    /// e.g. `ensdata=1x/12ms lifi=2x/840ms`; empty without upstream calls
+    pub fn summary(&self) -> String {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(upstream, (count, total))| {
+                format!("{}={}x/{}ms", upstream, count, total.as_millis())
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// `User-Agent` sent to upstreams unless `HTTP_USER_AGENT` overrides it
+pub const DEFAULT_USER_AGENT: &str = concat!("settleone-backend/", env!("CARGO_PKG_VERSION"));
+
+/// Build an outbound HTTP client identifying itself as `user_agent`
+pub fn http_client(user_agent: &str, timeout: Option<Duration>) -> reqwest::Client {
+    let mut builder = reqwest::Client::builder().user_agent(user_agent);
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(timeout);
+    }
+    builder.build().expect("Failed to create HTTP client")
+}
+
+/// Build an OTLP/HTTP tracer provider exporting to `endpoint` (the collector
+/// base URL, e.g. `http://localhost:4318`)
+pub fn otlp_provider(endpoint: &str) -> Result<TracerProvider, TraceError> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()?;
+
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new("service.name", SERVICE_NAME)]))
+        .build())
+}
+
+/// Tracing layer forwarding spans to `provider`
+pub fn layer<S>(
+    provider: &TracerProvider,
+) -> OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME))
+}
+
+/// Send an upstream request inside an `upstream_request` span.
+///
+/// `upstream` names the provider (e.g. `lifi`); the span records the HTTP
+/// method, url, response status and latency.
+pub async fn send(
+    upstream: &'static str,
+    client: &reqwest::Client,
+    request: reqwest::RequestBuilder,
+) -> reqwest::Result<reqwest::Response> {
+    let request = request.build()?;
+    let span = tracing::info_span!(
+        "upstream_request",
+        otel.kind = "client",
+        otel.status_code = Empty,
+        upstream,
+        http.method = %request.method(),
+        http.url = %request.url(),
+        http.status_code = Empty,
+        latency_ms = Empty,
+        request_id = current_request_id().unwrap_or_default(),
+    );
+
+    let start = Instant::now();
+    let result = client.execute(request).instrument(span.clone()).await;
+    let latency = start.elapsed();
+    span.record("latency_ms", latency.as_millis() as u64);
+    // Outside an API request (e.g. background jobs) there is nothing to add to
+    let _ = UPSTREAM_CALLS.try_with(|calls| calls.record(upstream, latency));
+    match &result {
+        Ok(response) => {
+            span.record("http.status_code", i64::from(response.status().as_u16()));
+            if response.status().is_server_error() {
+                span.record("otel.status_code", "ERROR");
+            }
+        }
+        Err(_) => {
+            span.record("otel.status_code", "ERROR");
+        }
+    }
+    result
+}
+
+/// Why an outbound call got no response, with the full chain of causes
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(not(any(feature = "ens", feature = "lifi")), allow(dead_code))]
+pub enum TransportError {
+    /// DNS lookup, refused or reset connection, TLS handshake: the
+    /// upstream is down or unreachable
+    Connect(String),
+    /// No response in time: the upstream is slow
+    Timeout(String),
+    /// Anything else, e.g. a request that could not be built
+    Other(String),
+}
+
+#[cfg_attr(not(any(feature = "ens", feature = "lifi")), allow(dead_code))]
+impl TransportError {
+    /// Classify a failed [`send`]. A connection that timed out never
+    /// reached the upstream, so it counts as [`TransportError::Connect`].
+    pub fn classify(e: &reqwest::Error) -> Self {
+        let cause = error_chain(e);
+        if e.is_connect() {
+            TransportError::Connect(cause)
+        } else if e.is_timeout() {
+            TransportError::Timeout(cause)
+        } else {
+            TransportError::Other(cause)
+        }
+    }
+}
+
+/// `e` followed by each of its sources, which is where reqwest keeps the
+/// useful part ("Connection refused", "dns error", ...)
+#[cfg_attr(not(any(feature = "ens", feature = "lifi")), allow(dead_code))]
+fn error_chain(e: &dyn std::error::Error) -> String {
+    let mut chain = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        chain.push_str(": ");
+        chain.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Router};
+    use opentelemetry::Value;
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[tokio::test]
+    async fn test_upstream_call_creates_span() {
+        let app = Router::new().route("/quote", get(|| async { "{}" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/quote", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer(&provider));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let client = reqwest::Client::new();
+        let response = crate::api::middleware::REQUEST_ID
+            .scope(
+                "req-123".to_string(),
+                send("lifi", &client, client.get(&url)),
+            )
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+
+        provider.force_flush();
+        let spans = exporter.get_finished_spans().unwrap();
+        let span = spans
+            .iter()
+            .find(|s| s.name == "upstream_request")
+            .expect("upstream span exported");
+
+        let attribute = |key: &str| {
+            span.attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.clone())
+        };
+        assert_eq!(attribute("upstream"), Some(Value::from("lifi")));
+        assert_eq!(attribute("http.url"), Some(Value::from(url.clone())));
+        assert_eq!(attribute("http.status_code"), Some(Value::I64(200)));
+        assert_eq!(attribute("request_id"), Some(Value::from("req-123")));
+        assert!(attribute("latency_ms").is_some());
+    }
+}
@@ -0,0 +1,425 @@
+//! Test harness: the app wired to mock upstreams
+//!
+//! `TestApp::spawn()` starts wiremock servers for the ENS API, LI.FI and the
+//! chain RPC (for the integrations compiled in) and points every service at
+//! them, so tests run offline and deterministically. Upstream calls nothing
+//! has been stubbed for get a 404. Configuration reloads read the variables
+//! set with [`TestApp::set_var`] instead of the process environment.
+
+// Not every helper is used by every feature combination
+#![cfg_attr(
+    not(all(feature = "ens", feature = "lifi", feature = "settlement")),
+    allow(dead_code)
+)]
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::http::StatusCode;
+use axum_test::{TestResponse, TestServer};
+use serde_json::json;
+#[cfg(any(feature = "ens", feature = "settlement"))]
+use wiremock::matchers::body_partial_json;
+use wiremock::matchers::path;
+#[cfg(feature = "lifi")]
+use wiremock::matchers::query_param;
+use wiremock::MockServer;
+use wiremock::{matchers::method, Mock, ResponseTemplate};
+
+use crate::config::Config;
+use crate::reporting::{ErrorEvent, ErrorReporter};
+use crate::{create_app, AppState};
+
+/// The app under test plus the mock upstreams it talks to
+pub struct TestApp {
+    pub server: TestServer,
+    pub state: AppState,
+    /// Environment seen by configuration reloads
+    vars: Arc<Mutex<HashMap<String, String>>>,
+    #[cfg(feature = "ens")]
+    pub ens: MockServer,
+    #[cfg(feature = "lifi")]
+    pub lifi: MockServer,
+    #[cfg(any(feature = "ens", feature = "settlement"))]
+    pub rpc: MockServer,
+    /// Exchange rates
+    pub fx: MockServer,
+}
+
+impl TestApp {
+    /// Spawn the app with the default config
+    pub async fn spawn() -> Self {
+        Self::spawn_with(Config::default()).await
+    }
+
+    /// Spawn the app with `config`; its upstream URLs are replaced by the mocks
+    pub async fn spawn_with(config: Config) -> Self {
+        let (ens, lifi, rpc, fx) = tokio::join!(
+            MockServer::start(),
+            MockServer::start(),
+            MockServer::start(),
+            MockServer::start()
+        );
+        let config = Config {
+            #[cfg(feature = "ens")]
+            ens_api_url: ens.uri(),
+            #[cfg(feature = "ens")]
+            eth_rpc_url: rpc.uri(),
+            #[cfg(feature = "lifi")]
+            lifi_api_url: lifi.uri(),
+            fx_rate_url: format!("{}/exchange-rates", fx.uri()),
+            #[cfg(feature = "settlement")]
+            arc_rpc_url: rpc.uri(),
+            // The gas token price is served by the RPC mock, under `/price`
+            #[cfg(feature = "settlement")]
+            native_token_price_url: format!("{}/price", rpc.uri()),
+            ..config
+        };
+        let vars: Arc<Mutex<HashMap<String, String>>> = Arc::default();
+        let mut state = AppState::new(config);
+        state.initialized.set();
+        let reload_vars = vars.clone();
+        state.config_loader = Arc::new(move || {
+            let vars = reload_vars.lock().unwrap();
+            Config::from_lookup(|key| vars.get(key).cloned())
+        });
+        let server = TestServer::new(create_app(state.clone())).unwrap();
+        // Mocks of integrations compiled out are never used
+        let _ = (&ens, &lifi, &rpc);
+        Self {
+            server,
+            state,
+            vars,
+            #[cfg(feature = "ens")]
+            ens,
+            #[cfg(feature = "lifi")]
+            lifi,
+            #[cfg(any(feature = "ens", feature = "settlement"))]
+            rpc,
+            fx,
+        }
+    }
+
+    /// Set a variable for the next configuration reload
+    pub fn set_var(&self, key: &str, value: &str) {
+        self.vars
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), value.to_string());
+    }
+
+    /// Stub the ENS API to resolve `name` to `address`
+    #[cfg(feature = "ens")]
+    pub async fn stub_ens_resolution(&self, name: &str, address: &str) {
+        Mock::given(method("GET"))
+            .and(path(format!("/{}", name)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "address": address })))
+            .mount(&self.ens)
+            .await;
+    }
+
+    /// Stub the ENS API to reverse resolve `address` to `name`
+    #[cfg(feature = "ens")]
+    pub async fn stub_ens_reverse(&self, address: &str, name: &str) {
+        Mock::given(method("GET"))
+            .and(path(format!("/{}", address.to_lowercase())))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ens": name })))
+            .mount(&self.ens)
+            .await;
+    }
+
+    /// Stub the chain RPC so every name has `resolver` as its resolver and
+    /// `record` (hex) as its content hash; an empty `record` means unset
+    #[cfg(feature = "ens")]
+    pub async fn stub_ens_content_hash(&self, resolver: &str, record: &str) {
+        let word = |value: usize| format!("{:064x}", value);
+        let resolver = resolver.trim_start_matches("0x").to_lowercase();
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({
+                "method": "eth_call",
+                "params": [{ "to": "0x00000000000c2e074ec69a0dfb2997ba6c7d2e1e" }],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": format!("0x{:0>64}", resolver),
+            })))
+            .mount(&self.rpc)
+            .await;
+
+        // ABI-encoded `bytes`: offset, length, then the right-padded data
+        let record = record.trim_start_matches("0x");
+        let padded = record.len().div_ceil(64) * 64;
+        let encoded = format!(
+            "0x{}{}{:0<padded$}",
+            word(32),
+            word(record.len() / 2),
+            record
+        );
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({
+                "method": "eth_call",
+                "params": [{ "to": format!("0x{}", resolver) }],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": encoded,
+            })))
+            .mount(&self.rpc)
+            .await;
+    }
+
+    /// Stub LI.FI to answer every quote with `to_amount`, USDC to USDC
+    #[cfg(feature = "lifi")]
+    pub async fn stub_lifi_quote(&self, to_amount: &str) {
+        Mock::given(method("GET"))
+            .and(path("/quote"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "estimate": {
+                    "toAmount": to_amount,
+                    "gasCosts": [{ "amount": "21000" }],
+                    "executionDuration": 30,
+                },
+                "action": {
+                    "fromToken": { "symbol": "USDC", "decimals": 6, "priceUSD": "1.00" },
+                    "toToken": { "symbol": "USDC", "decimals": 6, "priceUSD": "1.00" },
+                }
+            })))
+            .mount(&self.lifi)
+            .await;
+    }
+
+    /// Stub LI.FI's token endpoint to price `token` at `price_usd`
+    #[cfg(feature = "lifi")]
+    pub async fn stub_lifi_token_price(&self, token: &str, price_usd: &str) {
+        Mock::given(method("GET"))
+            .and(path("/token"))
+            .and(query_param("token", token))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "address": token,
+                "symbol": "USDC",
+                "decimals": 6,
+                "priceUSD": price_usd,
+            })))
+            .mount(&self.lifi)
+            .await;
+    }
+
+    /// Stub the exchange rate source with `rates` (units per US dollar)
+    /// for the next `times` requests
+    pub async fn stub_fx_rates(&self, rates: &[(&str, &str)], times: u64) {
+        let rates: serde_json::Map<String, serde_json::Value> = rates
+            .iter()
+            .map(|(code, rate)| (code.to_string(), json!(rate)))
+            .collect();
+        Mock::given(method("GET"))
+            .and(path("/exchange-rates"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": { "currency": "USD", "rates": rates }
+            })))
+            .up_to_n_times(times)
+            .mount(&self.fx)
+            .await;
+    }
+
+    /// Stub the settlement chain's `eth_gasPrice` at `wei`
+    #[cfg(feature = "settlement")]
+    pub async fn stub_gas_price(&self, wei: u64) {
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "method": "eth_gasPrice" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": format!("{:#x}", wei),
+            })))
+            .mount(&self.rpc)
+            .await;
+    }
+
+    /// Stub the settlement chain's gas token price at `usd`
+    #[cfg(feature = "settlement")]
+    pub async fn stub_native_price(&self, usd: f64) {
+        Mock::given(method("GET"))
+            .and(path("/price"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "usd-coin": { "usd": usd } })),
+            )
+            .mount(&self.rpc)
+            .await;
+    }
+
+    /// Stub the chain RPC to accept transactions sent by its signer as
+    /// `tx_hash`, which it then knows
+    #[cfg(feature = "settlement")]
+    pub async fn stub_send_transaction(&self, tx_hash: &str) {
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                json!({ "method": "eth_sendTransaction" }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": tx_hash,
+            })))
+            .mount(&self.rpc)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({
+                "method": "eth_getTransactionByHash",
+                "params": [tx_hash],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": { "hash": tx_hash },
+            })))
+            .mount(&self.rpc)
+            .await;
+    }
+
+    /// Stub the chain RPC to have dropped `tx_hash` from its mempool
+    #[cfg(feature = "settlement")]
+    pub async fn stub_dropped_transaction(&self, tx_hash: &str) {
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({
+                "method": "eth_getTransactionByHash",
+                "params": [tx_hash],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": null,
+            })))
+            .with_priority(1)
+            .mount(&self.rpc)
+            .await;
+    }
+
+    /// Stub the chain RPC's pending transaction count of every account
+    #[cfg(feature = "settlement")]
+    pub async fn stub_transaction_count(&self, count: u64) {
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                json!({ "method": "eth_getTransactionCount" }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": format!("{:#x}", count),
+            })))
+            .mount(&self.rpc)
+            .await;
+    }
+
+    /// Stub the chain RPC with a receipt for `tx_hash` mined in `block`,
+    /// with the chain head at `head`; `succeeded` picks the receipt status
+    #[cfg(feature = "settlement")]
+    pub async fn stub_tx_receipt(&self, tx_hash: &str, block: u64, head: u64, succeeded: bool) {
+        self.stub_tx_receipt_with_logs(tx_hash, block, head, succeeded, json!([]))
+            .await;
+    }
+
+    /// [`stub_tx_receipt`](Self::stub_tx_receipt) with the receipt's `logs`
+    #[cfg(feature = "settlement")]
+    pub async fn stub_tx_receipt_with_logs(
+        &self,
+        tx_hash: &str,
+        block: u64,
+        head: u64,
+        succeeded: bool,
+        logs: serde_json::Value,
+    ) {
+        let status = if succeeded { "0x1" } else { "0x0" };
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({
+                "method": "eth_getTransactionReceipt",
+                "params": [tx_hash],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": {
+                    "transactionHash": tx_hash,
+                    "blockNumber": format!("{:#x}", block),
+                    "status": status,
+                    "logs": logs,
+                },
+            })))
+            .mount(&self.rpc)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({ "method": "eth_blockNumber" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": format!("{:#x}", head),
+            })))
+            .mount(&self.rpc)
+            .await;
+    }
+}
+
+/// Writer capturing tracing output in memory
+#[derive(Clone, Default)]
+pub struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Capture {
+    /// Everything written so far
+    pub fn output(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl std::io::Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for Capture {
+    type Writer = Capture;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Error reporter keeping every event in memory
+#[derive(Clone, Default)]
+pub struct CapturingReporter(Arc<Mutex<Vec<ErrorEvent>>>);
+
+impl CapturingReporter {
+    /// Every event reported so far
+    pub fn events(&self) -> Vec<ErrorEvent> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl ErrorReporter for CapturingReporter {
+    fn report(&self, event: ErrorEvent) {
+        self.0.lock().unwrap().push(event);
+    }
+}
+
+/// Assert `response` is an error envelope with `status` and `code`
+pub fn assert_error(response: &TestResponse, status: StatusCode, code: &str) {
+    assert_eq!(response.status_code(), status);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["code"], code, "unexpected error body: {}", body);
+}
+
+/// Write a fresh self-signed `localhost` certificate and its key to
+/// `cert.pem` and `key.pem` in `dir`, returning the certificate
+pub fn write_self_signed_cert(dir: &std::path::Path) -> rustls::pki_types::CertificateDer<'static> {
+    let rcgen::CertifiedKey { cert, key_pair } =
+        rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    std::fs::write(dir.join("cert.pem"), cert.pem()).unwrap();
+    std::fs::write(dir.join("key.pem"), key_pair.serialize_pem()).unwrap();
+    cert.der().clone()
+}
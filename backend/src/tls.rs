@@ -0,0 +1,158 @@
+//! TLS termination
+//!
+//! When `TLS_CERT_PATH` and `TLS_KEY_PATH` are set the listeners speak TLS
+//! (rustls), negotiating HTTP/2 or HTTP/1.1 via ALPN. [`CertReloadJob`]
+//! checks both files periodically and swaps in a changed certificate for
+//! new connections; open connections keep the old one.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+pub use axum_server::tls_rustls::RustlsConfig;
+use thiserror::Error;
+
+use crate::services::jobs::{Job, JobContext};
+
+/// How often the certificate files are checked for changes
+pub const CERT_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Certificate loading errors
+#[derive(Error, Debug)]
+pub enum TlsError {
+    #[error("cannot read {key} '{path}': {source}")]
+    Read {
+        key: &'static str,
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("TLS_CERT_PATH '{0}' contains no PEM certificate")]
+    NoCertificate(String),
+
+    #[error("invalid TLS certificate or key (does the key match the certificate?): {0}")]
+    Invalid(std::io::Error),
+}
+
+/// Load the PEM certificate chain and private key into a server config
+pub async fn load(cert_path: &Path, key_path: &Path) -> Result<RustlsConfig, TlsError> {
+    let (cert, key) = read_pair(cert_path, key_path).await?;
+    RustlsConfig::from_pem(cert, key)
+        .await
+        .map_err(TlsError::Invalid)
+}
+
+async fn read_pair(cert_path: &Path, key_path: &Path) -> Result<(Vec<u8>, Vec<u8>), TlsError> {
+    let read = |key: &'static str, path: &Path| {
+        let path = path.to_path_buf();
+        async move {
+            tokio::fs::read(&path)
+                .await
+                .map_err(|source| TlsError::Read {
+                    key,
+                    path: path.display().to_string(),
+                    source,
+                })
+        }
+    };
+    let cert = read("TLS_CERT_PATH", cert_path).await?;
+    let key = read("TLS_KEY_PATH", key_path).await?;
+    if !String::from_utf8_lossy(&cert).contains("-----BEGIN CERTIFICATE-----") {
+        return Err(TlsError::NoCertificate(cert_path.display().to_string()));
+    }
+    Ok((cert, key))
+}
+
+/// Modification times of the certificate and key files
+fn modified(cert_path: &Path, key_path: &Path) -> Option<(SystemTime, SystemTime)> {
+    let mtime = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    Some((mtime(cert_path)?, mtime(key_path)?))
+}
+
+/// Job reloading the certificate into `config` when either file changes
+pub struct CertReloadJob {
+    config: RustlsConfig,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    /// File times of the certificate currently served
+    loaded: Mutex<Option<(SystemTime, SystemTime)>>,
+}
+
+impl CertReloadJob {
+    /// Watch the files `config` was loaded from
+    pub fn new(config: RustlsConfig, cert_path: PathBuf, key_path: PathBuf) -> Self {
+        let loaded = Mutex::new(modified(&cert_path, &key_path));
+        Self {
+            config,
+            cert_path,
+            key_path,
+            loaded,
+        }
+    }
+}
+
+impl Job for CertReloadJob {
+    fn name(&self) -> &'static str {
+        "tls_cert_reload"
+    }
+
+    fn interval(&self) -> Duration {
+        CERT_CHECK_INTERVAL
+    }
+
+    async fn run(&self, _ctx: &JobContext) -> Result<(), String> {
+        let current = modified(&self.cert_path, &self.key_path);
+        if current.is_some() && current == *self.loaded.lock().unwrap() {
+            return Ok(());
+        }
+
+        // A failed reload (e.g. the key is written after the certificate)
+        // keeps serving the old certificate and is retried next run
+        let (cert, key) = read_pair(&self.cert_path, &self.key_path)
+            .await
+            .map_err(|e| e.to_string())?;
+        self.config
+            .reload_from_pem(cert, key)
+            .await
+            .map_err(|e| TlsError::Invalid(e).to_string())?;
+        *self.loaded.lock().unwrap() = current;
+        tracing::info!("Reloaded TLS certificate from {}", self.cert_path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::write_self_signed_cert;
+
+    #[tokio::test]
+    async fn test_load_rejects_unreadable_and_mismatched_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write_self_signed_cert(dir.path());
+        let cert = dir.path().join("cert.pem");
+        let key = dir.path().join("key.pem");
+        assert!(load(&cert, &key).await.is_ok());
+
+        let err = load(&dir.path().join("missing.pem"), &key)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            TlsError::Read {
+                key: "TLS_CERT_PATH",
+                ..
+            }
+        ));
+
+        // Key and certificate swapped
+        let err = load(&key, &cert).await.unwrap_err();
+        assert!(matches!(err, TlsError::NoCertificate(_)));
+
+        // A key that belongs to a different certificate
+        let other = dir.path().join("other.pem");
+        std::fs::write(&other, rcgen::KeyPair::generate().unwrap().serialize_pem()).unwrap();
+        let err = load(&cert, &other).await.unwrap_err();
+        assert!(matches!(err, TlsError::Invalid(_)));
+    }
+}
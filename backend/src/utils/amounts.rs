@@ -0,0 +1,6 @@
+//! Token amounts in whole-token decimal strings and base units
+//!
+//! Shared with clients through `settleone-types`, so both ends round (that
+//! is, refuse to round) identically.
+
+pub use settleone_types::units::{format_units, parse_units};
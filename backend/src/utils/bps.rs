@@ -0,0 +1,386 @@
+//! Basis-point and proportional math on base-unit amounts
+//!
+//! A basis point is 1/10 000. Every product is taken at 256 bits before
+//! dividing, so no intermediate overflows, and every rounding step is
+//! explicit: amounts are never handled as floats.
+
+use thiserror::Error;
+
+/// Basis points in a whole (100%)
+pub const BPS_DENOMINATOR: u32 = 10_000;
+
+/// How a fractional base unit is resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum RoundingMode {
+    /// Toward zero: the payer never gives more than the exact share
+    Floor,
+    /// To the nearest unit, halves rounding up
+    HalfUp,
+    /// Floor each part, then hand the units left over to the parts with
+    /// the largest remainders. A lone amount has nothing to hand over and
+    /// rounds like [`RoundingMode::Floor`]; [`split_proportionally`]
+    /// always splits this way.
+    LargestRemainder,
+}
+
+/// `amount * bps / 10 000`, rounded per `rounding`.
+///
+/// `bps` may exceed [`BPS_DENOMINATOR`] (15 000 is 150%).
+///
+/// # Panics
+///
+/// If the result does not fit in a `u128`, which takes more than 100% of
+/// an amount near `u128::MAX`.
+#[allow(dead_code)]
+pub fn apply_bps(amount: u128, bps: u32, rounding: RoundingMode) -> u128 {
+    let (quotient, remainder) = mul_div_rem(amount, bps as u128, BPS_DENOMINATOR as u128)
+        .expect("apply_bps result fits in u128");
+    match rounding {
+        RoundingMode::Floor | RoundingMode::LargestRemainder => quotient,
+        RoundingMode::HalfUp if remainder * 2 >= BPS_DENOMINATOR as u128 => quotient
+            .checked_add(1)
+            .expect("apply_bps result fits in u128"),
+        RoundingMode::HalfUp => quotient,
+    }
+}
+
+/// `amount * numerator / denominator`, rounded per `rounding` (as a lone
+/// amount, [`RoundingMode::LargestRemainder`] floors), or `None` if the
+/// result does not fit in a `u128`.
+///
+/// Scales an amount by a fixed-point rate such as an exchange rate.
+///
+/// # Panics
+///
+/// If `denominator` is zero.
+// Only display currencies convert so far, and they need the price service
+#[cfg_attr(not(feature = "lifi"), allow(dead_code))]
+pub fn mul_div(
+    amount: u128,
+    numerator: u128,
+    denominator: u128,
+    rounding: RoundingMode,
+) -> Option<u128> {
+    let (quotient, remainder) = mul_div_rem(amount, numerator, denominator)?;
+    match rounding {
+        RoundingMode::HalfUp if remainder >= denominator - remainder => quotient.checked_add(1),
+        _ => Some(quotient),
+    }
+}
+
+/// Split `total` into one part per weight, in proportion to the weights,
+/// with parts that always sum to exactly `total`.
+///
+/// Rounds by [`RoundingMode::LargestRemainder`]: each part is its exact
+/// share rounded down or up, and ties for the leftover units go to the
+/// earlier weight.
+///
+/// # Panics
+///
+/// If the weights sum to zero (or there are none) or overflow a `u128`.
+#[allow(dead_code)]
+pub fn split_proportionally(total: u128, weights: &[u128]) -> Vec<u128> {
+    let weight_sum = weights
+        .iter()
+        .try_fold(0u128, |sum, weight| sum.checked_add(*weight))
+        .expect("weights sum to at most u128::MAX");
+    assert!(
+        weight_sum > 0,
+        "split_proportionally needs a positive weight"
+    );
+
+    // No share exceeds the total since no weight exceeds their sum
+    let shares: Vec<(u128, u128)> = weights
+        .iter()
+        .map(|weight| mul_div_rem(total, *weight, weight_sum).expect("share fits in the total"))
+        .collect();
+    let mut parts: Vec<u128> = shares.iter().map(|(quotient, _)| *quotient).collect();
+
+    // Each floored part lost less than one unit, so fewer units than parts
+    // are left over
+    let leftover = total - parts.iter().sum::<u128>();
+    let mut by_remainder: Vec<usize> = (0..parts.len()).collect();
+    by_remainder.sort_by(|a, b| shares[*b].1.cmp(&shares[*a].1).then(a.cmp(b)));
+    for index in by_remainder.into_iter().take(leftover as usize) {
+        parts[index] += 1;
+    }
+    parts
+}
+
+/// A split share below the minimum payment
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error(
+    "share {index} of the split would be {share} base units, below the minimum payment of \
+     {minimum}; split at least {needed_total} or leave out the smallest shares"
+)]
+pub struct ShareBelowMinimum {
+    /// Position of the share among the weights
+    pub index: usize,
+    pub share: u128,
+    pub minimum: u128,
+    /// Smallest total that gives every weighted share at least `minimum`
+    pub needed_total: u128,
+}
+
+/// [`split_proportionally`], failing if any share of a positive weight
+/// would come out below `minimum` (`MIN_PAYMENT_AMOUNT`).
+///
+/// Zero weights get zero shares, which are not payments and are not
+/// checked.
+///
+/// # Panics
+///
+/// As [`split_proportionally`].
+#[allow(dead_code)]
+pub fn split_with_minimum(
+    total: u128,
+    weights: &[u128],
+    minimum: u128,
+) -> Result<Vec<u128>, ShareBelowMinimum> {
+    let parts = split_proportionally(total, weights);
+    let smallest = parts
+        .iter()
+        .zip(weights)
+        .enumerate()
+        .filter(|(_, (_, weight))| **weight > 0)
+        .min_by_key(|(_, (part, _))| **part);
+    if let Some((index, (share, _))) = smallest {
+        if *share < minimum {
+            return Err(ShareBelowMinimum {
+                index,
+                share: *share,
+                minimum,
+                needed_total: needed_total(weights, minimum),
+            });
+        }
+    }
+    Ok(parts)
+}
+
+/// `ceil(minimum * weight_sum / smallest_weight)`: the smallest weighted
+/// share is at least `minimum` even rounded down
+fn needed_total(weights: &[u128], minimum: u128) -> u128 {
+    let weight_sum: u128 = weights.iter().sum();
+    let smallest = weights
+        .iter()
+        .copied()
+        .filter(|w| *w > 0)
+        .min()
+        .unwrap_or(1);
+    match mul_div_rem(minimum, weight_sum, smallest) {
+        Some((quotient, 0)) => quotient,
+        Some((quotient, _)) => quotient.saturating_add(1),
+        None => u128::MAX,
+    }
+}
+
+/// `(a * b / divisor, a * b % divisor)` with a 256-bit product, or `None`
+/// if the quotient does not fit in a `u128`
+fn mul_div_rem(a: u128, b: u128, divisor: u128) -> Option<(u128, u128)> {
+    assert!(divisor > 0, "division by zero");
+    let (high, low) = widening_mul(a, b);
+    if high >= divisor {
+        return None;
+    }
+
+    // Schoolbook binary long division of high:low by the divisor. With
+    // high < divisor the quotient fits in 128 bits, so only the low half of
+    // the product feeds it.
+    let mut remainder = high;
+    let mut quotient = 0u128;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        // With the carry the true remainder is 2^128 + remainder, which is
+        // at least the divisor; subtracting wraps back into range
+        if carry == 1 || remainder >= divisor {
+            remainder = remainder.wrapping_sub(divisor);
+            quotient |= 1;
+        }
+    }
+    Some((quotient, remainder))
+}
+
+/// Full 256-bit product of two `u128`s, as `(high, low)` halves
+fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a_high, a_low) = (a >> 64, a & MASK);
+    let (b_high, b_low) = (b >> 64, b & MASK);
+
+    let low_low = a_low * b_low;
+    let high_low = a_high * b_low;
+    let low_high = a_low * b_high;
+    let high_high = a_high * b_high;
+
+    // Middle column: three values below 2^64 each, so no overflow
+    let middle = (low_low >> 64) + (high_low & MASK) + (low_high & MASK);
+    let low = (middle << 64) | (low_low & MASK);
+    let high = high_high + (high_low >> 64) + (low_high >> 64) + (middle >> 64);
+    (high, low)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_apply_bps_rounding() {
+        // 0.3% of 1 USDC
+        assert_eq!(apply_bps(1_000_000, 30, RoundingMode::Floor), 3_000);
+        // 1 bps of 15 units is 0.0015 units
+        assert_eq!(apply_bps(15, 1, RoundingMode::Floor), 0);
+        assert_eq!(apply_bps(5_000, 1, RoundingMode::HalfUp), 1);
+        assert_eq!(apply_bps(4_999, 1, RoundingMode::HalfUp), 0);
+        assert_eq!(apply_bps(5_000, 1, RoundingMode::LargestRemainder), 0);
+        assert_eq!(apply_bps(200, 15_000, RoundingMode::Floor), 300);
+        assert_eq!(apply_bps(123, 0, RoundingMode::HalfUp), 0);
+        // 256-bit intermediates: the product is far past u128
+        assert_eq!(
+            apply_bps(u128::MAX, BPS_DENOMINATOR, RoundingMode::Floor),
+            u128::MAX
+        );
+        assert_eq!(
+            apply_bps(u128::MAX, 5_000, RoundingMode::HalfUp),
+            u128::MAX / 2 + 1
+        );
+    }
+
+    #[test]
+    fn test_mul_div_scales_by_a_rate() {
+        // 12.5 USDC at 0.92345678 EUR/USD, rate with 8 decimals
+        assert_eq!(
+            mul_div(12_500_000, 92_345_678, 100_000_000, RoundingMode::Floor),
+            Some(11_543_209)
+        );
+        assert_eq!(
+            mul_div(12_500_000, 92_345_678, 100_000_000, RoundingMode::HalfUp),
+            Some(11_543_210)
+        );
+        assert_eq!(mul_div(3, 1, 2, RoundingMode::HalfUp), Some(2));
+        assert_eq!(mul_div(u128::MAX, 3, 2, RoundingMode::Floor), None);
+        assert_eq!(
+            mul_div(u128::MAX, u128::MAX, u128::MAX, RoundingMode::HalfUp),
+            Some(u128::MAX)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "fits in u128")]
+    fn test_apply_bps_overflow_panics() {
+        apply_bps(u128::MAX, BPS_DENOMINATOR + 1, RoundingMode::Floor);
+    }
+
+    #[test]
+    fn test_split_proportionally() {
+        assert_eq!(split_proportionally(100, &[1, 1, 1]), [34, 33, 33]);
+        assert_eq!(split_proportionally(100, &[1, 2]), [33, 67]);
+        assert_eq!(split_proportionally(5, &[0, 3, 0]), [0, 5, 0]);
+        assert_eq!(split_proportionally(0, &[4, 6]), [0, 0]);
+        // The largest remainder wins the leftover unit, not the first part
+        assert_eq!(split_proportionally(10, &[3, 3, 1]), [4, 4, 2]);
+        assert_eq!(
+            split_proportionally(u128::MAX, &[u128::MAX / 2, u128::MAX / 2 + 1]),
+            [u128::MAX / 2, u128::MAX / 2 + 1]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "positive weight")]
+    fn test_split_without_weight_panics() {
+        split_proportionally(10, &[0, 0]);
+    }
+
+    #[test]
+    fn test_split_with_minimum_boundaries() {
+        // 0.01 USDC minimum: two shares of exactly the minimum pass
+        assert_eq!(
+            split_with_minimum(20_000, &[1, 1], 10_000),
+            Ok(vec![10_000, 10_000])
+        );
+        // One unit short leaves a share one unit below it
+        assert_eq!(
+            split_with_minimum(19_999, &[1, 1], 10_000),
+            Err(ShareBelowMinimum {
+                index: 1,
+                share: 9_999,
+                minimum: 10_000,
+                needed_total: 20_000,
+            })
+        );
+        // The guidance names a total that passes
+        let err = split_with_minimum(50_000, &[1, 9], 10_000).unwrap_err();
+        assert_eq!((err.index, err.needed_total), (0, 100_000));
+        assert!(split_with_minimum(err.needed_total, &[1, 9], 10_000).is_ok());
+        assert!(err.to_string().contains("split at least 100000"));
+        // Zero weights are not payments
+        assert_eq!(
+            split_with_minimum(10_000, &[0, 1], 10_000),
+            Ok(vec![0, 10_000])
+        );
+    }
+
+    #[test]
+    fn test_widening_mul_matches_narrow_products() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..2_000 {
+            let (a, b): (u64, u64) = rng.gen();
+            assert_eq!(
+                widening_mul(a as u128, b as u128),
+                (0, a as u128 * b as u128)
+            );
+        }
+        assert_eq!(widening_mul(u128::MAX, u128::MAX), (u128::MAX - 1, 1));
+        assert_eq!(widening_mul(1 << 127, 2), (1, 0));
+    }
+
+    #[test]
+    fn test_random_bps_match_reference() {
+        let mut rng = StdRng::seed_from_u64(17);
+        for _ in 0..5_000 {
+            // Small enough for a u128 reference product
+            let amount = rng.gen::<u128>() >> rng.gen_range(32..128);
+            let bps = rng.gen_range(0..=20_000);
+            let exact = amount * bps as u128;
+            let floor = exact / BPS_DENOMINATOR as u128;
+            let half_up = (exact + BPS_DENOMINATOR as u128 / 2) / BPS_DENOMINATOR as u128;
+            assert_eq!(apply_bps(amount, bps, RoundingMode::Floor), floor);
+            assert_eq!(apply_bps(amount, bps, RoundingMode::HalfUp), half_up);
+        }
+    }
+
+    #[test]
+    fn test_random_splits_conserve_the_total() {
+        let mut rng = StdRng::seed_from_u64(29);
+        for _ in 0..5_000 {
+            let total = rng.gen::<u128>() >> rng.gen_range(0..128);
+            let count = rng.gen_range(1..=12);
+            // At least 7 bits short, so a dozen weights cannot overflow
+            let shift = rng.gen_range(7..128);
+            let weights: Vec<u128> = (0..count).map(|_| rng.gen::<u128>() >> shift).collect();
+            let weight_sum: u128 = weights.iter().sum();
+            if weight_sum == 0 {
+                continue;
+            }
+
+            let parts = split_proportionally(total, &weights);
+            assert_eq!(parts.len(), weights.len());
+            assert_eq!(parts.iter().sum::<u128>(), total, "{} {:?}", total, weights);
+            for (part, weight) in parts.iter().zip(&weights) {
+                // Each part is its exact share rounded down or up
+                let (floor, remainder) = mul_div_rem(total, *weight, weight_sum).unwrap();
+                let ceil = floor + (remainder > 0) as u128;
+                assert!(
+                    *part == floor || *part == ceil,
+                    "{} of {} by {:?}",
+                    part,
+                    total,
+                    weights
+                );
+            }
+        }
+    }
+}
@@ -1,5 +1,8 @@
 //! Utility functions
 
+pub mod amounts;
+pub mod bps;
+
 /// Format an Ethereum address for display
 #[allow(dead_code)]
 pub fn format_address(address: &str, chars: usize) -> String {
@@ -25,18 +28,11 @@ pub fn is_valid_address(address: &str) -> bool {
     address[2..].chars().all(|c| c.is_ascii_hexdigit())
 }
 
-/// Validate ENS name format
+/// Validate ENS name format (see [`crate::services::ens::validate_name`])
+#[cfg(feature = "ens")]
 #[allow(dead_code)]
 pub fn is_valid_ens(name: &str) -> bool {
-    if !name.ends_with(".eth") {
-        return false;
-    }
-    if name.len() < 7 {
-        // minimum: a.eth (5) but typical minimum is 3 chars + .eth
-        return false;
-    }
-    let label = &name[..name.len() - 4];
-    label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    crate::services::ens::validate_name(name).is_ok()
 }
 
 #[cfg(test)]
@@ -58,11 +54,11 @@ mod tests {
         assert!(!is_valid_address("not_an_address"));
     }
 
+    #[cfg(feature = "ens")]
     #[test]
     fn test_is_valid_ens() {
-        assert!(is_valid_ens("vitalik.eth"));
-        assert!(is_valid_ens("my-name.eth"));
-        assert!(!is_valid_ens("invalid"));
-        assert!(!is_valid_ens("ab.eth")); // too short
+        for (name, expected) in crate::services::ens::ENS_NAME_CASES {
+            assert_eq!(is_valid_ens(name), expected.is_ok(), "{}", name);
+        }
     }
 }
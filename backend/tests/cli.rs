@@ -0,0 +1,211 @@
+//! End-to-end tests for the command line subcommands
+
+use assert_cmd::Command;
+#[cfg(feature = "ens")]
+use axum::{routing::get, Json, Router};
+use serde_json::{json, Value};
+
+fn backend() -> Command {
+    let mut cmd = Command::cargo_bin("settleone-backend").unwrap();
+    // Keep a developer's local settings out of the tests
+    for key in [
+        "PORT",
+        "API_KEYS",
+        "LIFI_API_KEY",
+        "ENS_API_URL",
+        "SESSION_SNAPSHOT_PATH",
+    ] {
+        cmd.env_remove(key);
+    }
+    cmd
+}
+
+fn stdout(output: &std::process::Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn test_check_config_masks_secrets() {
+    let output = backend()
+        .arg("check-config")
+        .env("LIFI_API_KEY", "lifi-secret")
+        .env("API_KEYS", "admin-secret:admin")
+        .env("PORT", "4000")
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+
+    let stdout = stdout(&output);
+    assert!(stdout.lines().any(|l| l == "PORT=4000"));
+    #[cfg(feature = "lifi")]
+    assert!(stdout.lines().any(|l| l == "LIFI_API_KEY=****"));
+    assert!(stdout.lines().any(|l| l == "API_KEYS=****:admin"));
+    assert!(!stdout.contains("secret"));
+}
+
+#[test]
+fn test_check_config_rejects_invalid_config() {
+    let output = backend()
+        .arg("check-config")
+        .env("PORT", "not-a-port")
+        .assert()
+        .failure()
+        .code(1)
+        .get_output()
+        .clone();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("PORT is invalid"));
+}
+
+#[cfg(feature = "ens")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_resolve_prints_result() {
+    let app = Router::new().route(
+        "/:name",
+        get(|| async { Json(json!({ "address": "0x1111111111111111111111111111111111111111" })) }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let output = tokio::task::spawn_blocking(move || {
+        backend()
+            .args(["resolve", "cli.eth"])
+            .env("ENS_API_URL", url)
+            .assert()
+            .success()
+            .get_output()
+            .clone()
+    })
+    .await
+    .unwrap();
+
+    let result: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(result["name"], "cli.eth");
+    assert_eq!(
+        result["address"],
+        "0x1111111111111111111111111111111111111111"
+    );
+
+    backend().args(["resolve", "no"]).assert().failure();
+}
+
+#[test]
+fn test_snapshot_import_then_export() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = dir.path().join("store.json");
+    let input = dir.path().join("input.json");
+    let output = dir.path().join("output.json");
+
+    let session = json!({
+        "id": "s1",
+        // Stored before addresses were checksummed
+        "user": "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed",
+        "status": "active",
+        "payments": [],
+        "total_amount": "0",
+        "tx_hash": null,
+        "created_at": "2026-01-01T00:00:00Z",
+        "version": 1,
+    });
+    std::fs::write(
+        &input,
+        json!({ "version": 1, "exported_at": "2026-01-01T00:00:00Z", "sessions": [session] })
+            .to_string(),
+    )
+    .unwrap();
+
+    // Needs a store to import into
+    backend()
+        .args(["snapshot", "import"])
+        .arg(&input)
+        .assert()
+        .failure();
+
+    backend()
+        .args(["snapshot", "import"])
+        .arg(&input)
+        .env("SESSION_SNAPSHOT_PATH", &store)
+        .assert()
+        .success();
+    backend()
+        .args(["snapshot", "export"])
+        .arg(&output)
+        .env("SESSION_SNAPSHOT_PATH", &store)
+        .assert()
+        .success();
+
+    let exported: Value = serde_json::from_slice(&std::fs::read(&output).unwrap()).unwrap();
+    assert_eq!(exported["version"], 1);
+    assert_eq!(exported["sessions"][0]["id"], "s1");
+    assert_eq!(
+        exported["sessions"][0]["user"],
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+    );
+
+    // Invalid snapshots are rejected without touching the store
+    std::fs::write(&input, "{}").unwrap();
+    backend()
+        .args(["snapshot", "import"])
+        .arg(&input)
+        .env("SESSION_SNAPSHOT_PATH", &store)
+        .assert()
+        .failure();
+}
+
+#[cfg(not(feature = "ens"))]
+#[test]
+fn test_resolve_without_ens_fails() {
+    let output = backend()
+        .args(["resolve", "cli.eth"])
+        .assert()
+        .failure()
+        .code(1)
+        .get_output()
+        .clone();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not enabled"));
+}
+
+#[test]
+fn test_loadgen_in_process_reports_json() {
+    let output = backend()
+        .args([
+            "loadgen",
+            "--users",
+            "3",
+            "--iterations",
+            "2",
+            "--payments",
+            "2",
+        ])
+        .arg("--json")
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+
+    let report: Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(report["errors"], 0);
+    assert_eq!(report["requests"], 3 * 2 * (2 + 2));
+    assert_eq!(report["endpoints"]["create_session"]["requests"], 6);
+    assert_eq!(report["endpoints"]["add_payment"]["requests"], 12);
+    assert_eq!(report["endpoints"]["finalize"]["requests"], 6);
+    let finalize = &report["endpoints"]["finalize"];
+    assert!(finalize["p50_ms"].as_f64() <= finalize["p99_ms"].as_f64());
+    assert!(report["rps"].as_f64().unwrap() > 0.0);
+}
+
+#[test]
+fn test_loadgen_fails_when_requests_fail() {
+    // Nothing listens on port 9 of loopback
+    let output = backend()
+        .args(["loadgen", "--url", "http://127.0.0.1:9", "--users", "1"])
+        .args(["--iterations", "1"])
+        .assert()
+        .failure()
+        .code(1)
+        .get_output()
+        .clone();
+    assert!(stdout(&output).contains("create_session first error"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 of 1 requests failed"));
+}
@@ -102,29 +102,28 @@ contract SessionSettlement is ISessionSettlement, ReentrancyGuard, Ownable {
         bytes32 sessionId,
         Settlement[] calldata settlements
     ) external nonReentrant {
-        if (_settledSessions[sessionId]) {
-            revert SessionErrors.SessionAlreadySettled(sessionId);
-        }
-        if (settlements.length == 0) {
+        _finalizeBatch(sessionId, settlements);
+    }
+
+    /// @inheritdoc ISessionSettlement
+    function finalizeSessions(
+        bytes32[] calldata sessionIds,
+        Settlement[][] calldata settlements
+    ) external nonReentrant {
+        if (sessionIds.length == 0) {
             revert SessionErrors.EmptyBatch();
         }
-        if (settlements.length > MAX_BATCH_SIZE) {
-            revert SessionErrors.BatchTooLarge(settlements.length, MAX_BATCH_SIZE);
+        if (sessionIds.length != settlements.length) {
+            revert SessionErrors.LengthMismatch(sessionIds.length, settlements.length);
+        }
+        if (sessionIds.length > MAX_BATCH_SIZE) {
+            revert SessionErrors.BatchTooLarge(sessionIds.length, MAX_BATCH_SIZE);
         }
 
-        // Calculate total amount and validate settlements
-        uint256 totalAmount = _calculateAndValidateBatch(settlements);
-
-        // Validate sender has sufficient allowance before any state changes
-        _validateAllowance(msg.sender, totalAmount);
-
-        // Mark session as settled
-        _markSettled(sessionId);
-
-        // Execute all transfers from sender's approved balance
-        _executeBatchTransfersFrom(msg.sender, sessionId, settlements);
-
-        emit BatchSettled(sessionId, totalAmount, settlements.length);
+        // Any failing session reverts the whole transaction
+        for (uint256 i = 0; i < sessionIds.length; i++) {
+            _finalizeBatch(sessionIds[i], settlements[i]);
+        }
     }
 
     // =============================================================
@@ -190,6 +189,35 @@ contract SessionSettlement is ISessionSettlement, ReentrancyGuard, Ownable {
         }
     }
 
+    /**
+     * @dev Settles one session's recipients from the sender's approved balance
+     */
+    function _finalizeBatch(bytes32 sessionId, Settlement[] calldata settlements) internal {
+        if (_settledSessions[sessionId]) {
+            revert SessionErrors.SessionAlreadySettled(sessionId);
+        }
+        if (settlements.length == 0) {
+            revert SessionErrors.EmptyBatch();
+        }
+        if (settlements.length > MAX_BATCH_SIZE) {
+            revert SessionErrors.BatchTooLarge(settlements.length, MAX_BATCH_SIZE);
+        }
+
+        // Calculate total amount and validate settlements
+        uint256 totalAmount = _calculateAndValidateBatch(settlements);
+
+        // Validate sender has sufficient allowance before any state changes
+        _validateAllowance(msg.sender, totalAmount);
+
+        // Mark session as settled
+        _markSettled(sessionId);
+
+        // Execute all transfers from sender's approved balance
+        _executeBatchTransfersFrom(msg.sender, sessionId, settlements);
+
+        emit BatchSettled(sessionId, totalAmount, settlements.length);
+    }
+
     /**
      * @dev Marks a session as settled and deactivates it
      */
@@ -62,6 +62,11 @@ interface ISessionSettlement {
     /// @param settlements Array of recipient-amount pairs
     function finalizeSessionBatch(bytes32 sessionId, Settlement[] calldata settlements) external;
 
+    /// @notice Finalizes several sessions in one transaction; all settle or none do
+    /// @param sessionIds The session identifiers
+    /// @param settlements Recipient-amount pairs of each session, in `sessionIds` order
+    function finalizeSessions(bytes32[] calldata sessionIds, Settlement[][] calldata settlements) external;
+
     /// @notice Checks if a session has been settled
     /// @param sessionId The session ID to check
     /// @return True if the session has been settled
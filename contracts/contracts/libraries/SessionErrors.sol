@@ -48,4 +48,7 @@ library SessionErrors {
 
     /// @notice Thrown when batch total amount overflows
     error BatchAmountOverflow();
+
+    /// @notice Thrown when a multi-session batch has a different number of session ids and settlement lists
+    error LengthMismatch(uint256 sessions, uint256 settlements);
 }
@@ -415,6 +415,74 @@ describe("SessionSettlement", function () {
         ).to.be.revertedWithCustomError(settlement, "BatchAmountOverflow");
       });
     });
+
+    describe("finalizeSessions", function () {
+      it("should settle several sessions in one transaction", async function () {
+        const { settlement, usdc, user1, user2, recipient1, recipient2 } =
+          await loadFixture(deployContractsFixture);
+        const sessionA = generateSessionId(user1.address, 1);
+        const sessionB = generateSessionId(user2.address, 2);
+
+        await expect(
+          settlement.finalizeSessions(
+            [sessionA, sessionB],
+            [
+              [{ recipient: recipient1.address, amount: SETTLEMENT_AMOUNT }],
+              [
+                { recipient: recipient1.address, amount: SETTLEMENT_AMOUNT },
+                { recipient: recipient2.address, amount: SETTLEMENT_AMOUNT },
+              ],
+            ],
+          ),
+        )
+          .to.emit(settlement, "BatchSettled")
+          .withArgs(sessionA, SETTLEMENT_AMOUNT, 1)
+          .and.to.emit(settlement, "BatchSettled")
+          .withArgs(sessionB, SETTLEMENT_AMOUNT * 2n, 2);
+
+        expect(await usdc.balanceOf(recipient1.address)).to.equal(
+          SETTLEMENT_AMOUNT * 2n,
+        );
+        expect(await settlement.isSessionSettled(sessionA)).to.be.true;
+        expect(await settlement.isSessionSettled(sessionB)).to.be.true;
+      });
+
+      it("should settle no session when one fails", async function () {
+        const { settlement, user1, user2, recipient1 } = await loadFixture(
+          deployContractsFixture,
+        );
+        const sessionA = generateSessionId(user1.address, 1);
+        const sessionB = generateSessionId(user2.address, 2);
+        const settlements = [
+          { recipient: recipient1.address, amount: SETTLEMENT_AMOUNT },
+        ];
+        await settlement.finalizeSessionBatch(sessionB, settlements);
+
+        await expect(
+          settlement.finalizeSessions(
+            [sessionA, sessionB],
+            [settlements, settlements],
+          ),
+        ).to.be.revertedWithCustomError(settlement, "SessionAlreadySettled");
+        expect(await settlement.isSessionSettled(sessionA)).to.be.false;
+      });
+
+      it("should revert when the lists differ in length", async function () {
+        const { settlement, user1, recipient1 } = await loadFixture(
+          deployContractsFixture,
+        );
+        const sessionId = generateSessionId(user1.address, 1);
+        const settlements = [
+          { recipient: recipient1.address, amount: SETTLEMENT_AMOUNT },
+        ];
+
+        await expect(
+          settlement.finalizeSessions([sessionId], [settlements, settlements]),
+        )
+          .to.be.revertedWithCustomError(settlement, "LengthMismatch")
+          .withArgs(1, 2);
+      });
+    });
   });
 
   describe("Admin Functions", function () {
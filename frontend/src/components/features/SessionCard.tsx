@@ -20,7 +20,7 @@ export function SessionCard({
 }: SessionCardProps) {
   const formatAmount = (amount: string) => {
     try {
-      const val = parseFloat(formatUnits(BigInt(amount), 6));
+      const val = parseFloat(formatUnits(BigInt(amount), session.token_decimals ?? 6));
       if (val === 0) return '0.00';
       if (val < 0.01) return val.toFixed(6); // Show more precision for small amounts
       return val.toFixed(2);
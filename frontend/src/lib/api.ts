@@ -10,7 +10,17 @@ export interface SessionData {
   status: 'active' | 'pending' | 'settled' | 'cancelled';
   payments: PaymentData[];
   total_amount: string;
+  token_decimals: number;
   created_at: string;
+  pinned_recipient?: PinnedRecipient;
+  version: number;
+  cancel_reason?: string;
+}
+
+export interface PinnedRecipient {
+  name: string;
+  address: string;
+  resolved_at: string;
 }
 
 export interface PaymentData {
@@ -18,8 +28,10 @@ export interface PaymentData {
   recipient: string;
   recipient_ens: string | null;
   amount: string;
-  status: 'pending' | 'confirmed' | 'settled';
+  status: 'pending' | 'confirmed' | 'settled' | 'cancelled';
   created_at: string;
+  confirmed_at: string | null;
+  settled_at: string | null;
 }
 
 export interface ENSResolution {
@@ -32,6 +44,8 @@ export interface ENSResolution {
 export interface QuoteData {
   from_amount: string;
   to_amount: string;
+  from_amount_formatted?: string;
+  to_amount_formatted?: string;
   estimated_gas: string;
   estimated_time: number;
   route: unknown | null;
@@ -89,13 +103,22 @@ class ApiClient {
   }
 
   // Session Management
-  async createSession(userAddress: string): Promise<{
+  async createSession(
+    userAddress: string,
+    recipientName?: string,
+    tokenDecimals?: number
+  ): Promise<{
     session_id: string;
     status: string;
+    pinned_recipient?: PinnedRecipient;
   }> {
     return this.request('/api/session', {
       method: 'POST',
-      body: JSON.stringify({ user_address: userAddress }),
+      body: JSON.stringify({
+        user_address: userAddress,
+        recipient_name: recipientName,
+        token_decimals: tokenDecimals,
+      }),
     });
   }
 
@@ -111,7 +134,10 @@ class ApiClient {
     payment: {
       recipient: string;
       recipient_ens?: string;
-      amount: string;
+      /** Base units; optional when `human_amount` is given */
+      amount?: string;
+      /** Whole tokens, e.g. "12.5" */
+      human_amount?: string;
     }
   ): Promise<{
     session: SessionData | null;
@@ -146,6 +172,24 @@ class ApiClient {
     });
   }
 
+  async cancelSession(sessionId: string, reason?: string): Promise<{ session: SessionData }> {
+    return this.request(`/api/session/${sessionId}/cancel`, {
+      method: 'POST',
+      body: JSON.stringify({ reason }),
+    });
+  }
+
+  async getSettlementStatus(sessionId: string): Promise<{
+    session_id: string;
+    session_status: SessionData['status'];
+    tx_hash: string;
+    tx_status: 'pending' | 'confirmed' | 'failed';
+    confirmations: number;
+    block_number: number | null;
+  }> {
+    return this.request(`/api/session/${sessionId}/settlement-status`);
+  }
+
   // Cross-chain Quotes (LI.FI)
   async getQuote(params: {
     fromChain: string;